@@ -0,0 +1,99 @@
+//! API keys for non-interactive clients.
+//!
+//! An API key looks like `ej_<prefix>_<secret>`. The prefix is public and
+//! identifies the key, the secret proves ownership. Only the SHA-256 hash of
+//! the secret should be stored.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use ej_auth::api_key::{generate_api_key, hash_api_key_secret, parse_api_key};
+//!
+//! let key = generate_api_key();
+//!
+//! // Store `key.prefix` and `hash_api_key_secret(&key.secret)`, hand `key.key` out
+//! let (prefix, secret) = parse_api_key(&key.key).unwrap();
+//! assert_eq!(prefix, key.prefix);
+//! assert_eq!(hash_api_key_secret(secret), hash_api_key_secret(&key.secret));
+//! ```
+
+use crate::refresh_token::random_hex;
+use crate::sha256::generate_hash;
+
+/// HTTP header carrying API keys.
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Marker every API key starts with.
+pub const API_KEY_MARKER: &str = "ej";
+
+/// Number of random bytes in the public prefix of an API key.
+pub const API_KEY_PREFIX_BYTES: usize = 6;
+
+/// Number of random bytes in the secret part of an API key.
+pub const API_KEY_SECRET_BYTES: usize = 32;
+
+/// A freshly generated API key.
+#[derive(Debug, Clone)]
+pub struct GeneratedApiKey {
+    /// The full key handed to the client.
+    pub key: String,
+    /// The public prefix identifying the key.
+    pub prefix: String,
+    /// The secret part of the key.
+    pub secret: String,
+}
+
+/// Generates a new random API key.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_auth::api_key::generate_api_key;
+///
+/// let key = generate_api_key();
+/// assert!(key.key.starts_with("ej_"));
+/// assert!(key.key.ends_with(&key.secret));
+/// ```
+pub fn generate_api_key() -> GeneratedApiKey {
+    let prefix = random_hex(API_KEY_PREFIX_BYTES);
+    let secret = random_hex(API_KEY_SECRET_BYTES);
+    GeneratedApiKey {
+        key: format!("{API_KEY_MARKER}_{prefix}_{secret}"),
+        prefix,
+        secret,
+    }
+}
+
+/// Splits an API key into its prefix and secret.
+///
+/// # Returns
+///
+/// `None` if `key` isn't formatted like an API key.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_auth::api_key::parse_api_key;
+///
+/// assert_eq!(parse_api_key("ej_abc_def"), Some(("abc", "def")));
+/// assert_eq!(parse_api_key("not-a-key"), None);
+/// ```
+pub fn parse_api_key(key: &str) -> Option<(&str, &str)> {
+    let (prefix, secret) = key
+        .strip_prefix(API_KEY_MARKER)?
+        .strip_prefix('_')?
+        .split_once('_')?;
+    if prefix.is_empty() || secret.is_empty() {
+        return None;
+    }
+    Some((prefix, secret))
+}
+
+/// Hashes the secret part of an API key for storage.
+///
+/// # Arguments
+///
+/// * `secret` - The secret part of the key
+pub fn hash_api_key_secret(secret: &str) -> String {
+    generate_hash(secret)
+}
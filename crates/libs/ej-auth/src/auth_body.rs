@@ -8,14 +8,16 @@ use super::CONNECTION_TOKEN_TYPE;
 
 /// Authentication response with access token.
 ///
-/// Contains an access token and token type for HTTP authentication.
+/// Contains an access token and token type for HTTP authentication, along with
+/// the refresh token used to get a new access token once it expires, if any.
 ///
 /// # JSON Format
 ///
 /// ```json
 /// {
 ///   "access_token": "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9...",
-///   "token_type": "Bearer"
+///   "token_type": "Bearer",
+///   "refresh_token": "3f1c9a..."
 /// }
 /// ```
 #[derive(Debug, Deserialize, Serialize)]
@@ -24,6 +26,9 @@ pub struct AuthBody {
     pub access_token: String,
     /// The token type (always "Bearer").
     pub token_type: String,
+    /// The refresh token, if one was issued.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
 }
 impl AuthBody {
     /// Creates a new authentication response.
@@ -44,6 +49,27 @@ impl AuthBody {
         Self {
             access_token,
             token_type: String::from(CONNECTION_TOKEN_TYPE),
+            refresh_token: None,
         }
     }
+
+    /// Attaches a refresh token to the response.
+    ///
+    /// # Arguments
+    ///
+    /// * `refresh_token` - The refresh token handed to the client
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_auth::auth_body::AuthBody;
+    ///
+    /// let response = AuthBody::new("some_token".to_string())
+    ///     .with_refresh_token("some_refresh_token".to_string());
+    /// assert_eq!(response.refresh_token.as_deref(), Some("some_refresh_token"));
+    /// ```
+    pub fn with_refresh_token(mut self, refresh_token: String) -> Self {
+        self.refresh_token = Some(refresh_token);
+        self
+    }
 }
@@ -1,5 +1,7 @@
 //! Authentication error types.
 
+use crate::secret_hash::SecretViolation;
+
 /// Authentication errors.
 #[derive(Debug, thiserror::Error, Clone)]
 pub enum Error {
@@ -15,11 +17,67 @@ pub enum Error {
     #[error("Token Expired")]
     TokenExpired,
 
+    /// JWT token was revoked before it expired.
+    #[error("Token Revoked")]
+    TokenRevoked,
+
+    /// The token lifetime doesn't fit in a timestamp.
+    #[error("Token duration out of range")]
+    TokenDurationOutOfRange,
+
+    /// A signed message doesn't match its signature.
+    #[error("Invalid message signature")]
+    InvalidSignature,
+
     /// JWT token creation or processing failed.
     #[error(transparent)]
     TokenCreation(#[from] jsonwebtoken::errors::Error),
 
+    /// A required JWT environment variable isn't set.
+    #[error("{0} must be set")]
+    JwtConfigMissing(&'static str),
+
+    /// The configured JWT algorithm isn't supported.
+    #[error("Unsupported JWT algorithm {0}, expected HS256, RS256 or ES256")]
+    JwtAlgorithmUnsupported(String),
+
+    /// A JWT key file couldn't be read.
+    #[error("Failed to read JWT key {0} - {1}")]
+    JwtKeyRead(String, String),
+
+    /// A key pair was loaded without its public key.
+    #[error("JWT public key missing")]
+    JwtPublicKeyMissing,
+
+    /// Tokens can't be signed without the private key.
+    #[error("JWT private key missing, tokens can only be verified")]
+    JwtSigningKeyMissing,
+
+    /// The token was signed with a key that isn't in the keyring.
+    #[error("Unknown JWT key {0}")]
+    JwtKeyUnknown(String),
+
+    /// A key with the same ID is already in the keyring.
+    #[error("JWT key {0} already exists")]
+    JwtKeyIdInUse(String),
+
+    /// The key currently used to sign tokens can't be retired.
+    #[error("JWT key {0} is the current key")]
+    JwtKeyCurrent(String),
+
+    /// A new secret doesn't satisfy the password policy.
+    #[error("Secret {}", join_violations(.0))]
+    WeakSecret(Vec<SecretViolation>),
+
     /// Password hashing operation failed.
     #[error("Error hashing password {0}")]
     PasswordHash(argon2::password_hash::Error),
 }
+
+fn join_violations(violations: &[SecretViolation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -7,6 +7,9 @@
 //! - **JWT Tokens**: Create and validate JSON Web Tokens
 //! - **Password Hashing**: Secure Argon2-based password storage
 //! - **SHA-256**: Content hashing for integrity checks
+//! - **Refresh Tokens**: Revocable tokens exchanged for new access tokens
+//! - **API Keys**: Long-lived keys for non-interactive clients
+//! - **Socket Signatures**: HMAC signing of Unix socket messages
 //! - **Auth Responses**: Standard Bearer token responses
 //!
 //! # Components
@@ -23,6 +26,18 @@
 //!
 //! SHA-256 hashing for content integrity.
 //!
+//! ## Refresh Tokens ([`refresh_token`])
+//!
+//! Generate opaque refresh tokens and the hashes they're stored as.
+//!
+//! ## API Keys ([`api_key`])
+//!
+//! Generate and parse API keys.
+//!
+//! ## Socket Signatures ([`socket_signature`])
+//!
+//! Sign and verify the messages sent to the ejd Unix socket with a shared key.
+//!
 //! ## Responses ([`auth_body`])
 //!
 //! Standard authentication response structures.
@@ -83,18 +98,26 @@
 //!
 //! # Configuration
 //!
-//! Set `JWT_SECRET` environment variable for token signing.
+//! Set `JWT_SECRET` environment variable for token signing, or set `JWT_ALGORITHM`
+//! to `RS256`/`ES256` along with `JWT_PUBLIC_KEY_PATH` and `JWT_PRIVATE_KEY_PATH`
+//! to sign tokens with a key pair. See [`jwt`] for details.
 
+pub mod api_key;
 pub mod auth_body;
 pub mod error;
 pub mod jwt;
 pub mod prelude;
+pub mod refresh_token;
 pub mod secret_hash;
 pub mod sha256;
+pub mod socket_signature;
 
 /// JWT issuer identifier.
 pub const ISS: &str = "EJ";
 
+/// JWT audience identifier, the dispatcher the tokens are presented to.
+pub const AUD: &str = "EJD";
+
 /// HTTP Authorization header name.
 pub const AUTH_HEADER: &str = "Authorization";
 
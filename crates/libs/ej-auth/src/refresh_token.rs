@@ -0,0 +1,72 @@
+//! Opaque refresh tokens.
+//!
+//! Refresh tokens are long-lived random strings exchanged for new access
+//! tokens once those expire. Unlike JWTs they carry no claims: they only mean
+//! something to the service that stored them, which makes them revocable.
+//! Only their SHA-256 hash should be stored so a database leak doesn't hand
+//! out valid tokens.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use ej_auth::refresh_token::{generate_refresh_token, hash_refresh_token};
+//!
+//! let token = generate_refresh_token();
+//! let hash = hash_refresh_token(&token);
+//!
+//! // Store `hash`, hand `token` to the client and compare hashes later on
+//! assert_eq!(hash, hash_refresh_token(&token));
+//! ```
+
+use rand::{RngCore, rngs::OsRng};
+
+use crate::sha256::generate_hash;
+
+/// Number of random bytes in a refresh token.
+pub const REFRESH_TOKEN_BYTES: usize = 32;
+
+/// Generates a new random refresh token.
+///
+/// # Returns
+///
+/// A lowercase hexadecimal string encoding [`REFRESH_TOKEN_BYTES`] random bytes.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_auth::refresh_token::{REFRESH_TOKEN_BYTES, generate_refresh_token};
+///
+/// let token = generate_refresh_token();
+/// assert_eq!(token.len(), REFRESH_TOKEN_BYTES * 2);
+/// assert_ne!(token, generate_refresh_token());
+/// ```
+pub fn generate_refresh_token() -> String {
+    random_hex(REFRESH_TOKEN_BYTES)
+}
+
+/// Generates `len` random bytes encoded as lowercase hexadecimal.
+pub(crate) fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Hashes a refresh token for storage.
+///
+/// Refresh tokens are random enough that a plain SHA-256 hash is safe to
+/// store, and it lets them be looked up by hash.
+///
+/// # Arguments
+///
+/// * `token` - The refresh token handed to the client
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_auth::refresh_token::hash_refresh_token;
+///
+/// assert_eq!(hash_refresh_token("token").len(), 64);
+/// ```
+pub fn hash_refresh_token(token: &str) -> String {
+    generate_hash(token)
+}
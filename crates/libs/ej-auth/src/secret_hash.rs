@@ -10,6 +10,8 @@
 //! - [`generate_secret_hash`]: Create secure password hashes
 //! - [`is_secret_valid`]: Verify passwords against stored hashes
 //!
+//! New secrets should be checked against a [`SecretPolicy`] before being hashed.
+//!
 //! # Examples
 //!
 //! ```rust
@@ -33,14 +35,177 @@
 //! assert!(!is_valid);
 //! ```
 
+use std::fmt;
+
 use argon2::{
     Argon2, PasswordHasher, PasswordVerifier,
     password_hash::{self, PasswordHashString, SaltString},
 };
 use rand::rngs::OsRng;
+use serde::Serialize;
 
 use crate::prelude::*;
 
+/// Minimum secret length required by the default policy.
+pub const DEFAULT_SECRET_MIN_LENGTH: usize = 12;
+
+/// Number of character classes required by the default policy.
+pub const DEFAULT_SECRET_MIN_CLASSES: usize = 3;
+
+/// Secrets rejected by policies that reject common secrets, compared case-insensitively.
+const COMMON_SECRETS: &[&str] = &[
+    "000000000000",
+    "111111111111",
+    "123123123123",
+    "123456789012",
+    "1234567890ab",
+    "1q2w3e4r5t6y",
+    "abcdefghijkl",
+    "admin1234567",
+    "administrator",
+    "changeme1234",
+    "iloveyou1234",
+    "letmein12345",
+    "passw0rd1234",
+    "password",
+    "password1",
+    "password123",
+    "password1234",
+    "password12345",
+    "password!123",
+    "p@ssw0rd",
+    "p@ssw0rd123",
+    "p@ssword1234",
+    "qwerty123456",
+    "qwertyuiop12",
+    "qwertyuiop123",
+    "secret123456",
+    "welcome12345",
+    "welcome@1234",
+];
+
+/// Reason a secret doesn't satisfy a [`SecretPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum SecretViolation {
+    /// The secret has fewer than `min_length` characters.
+    TooShort {
+        /// Minimum number of characters.
+        min_length: usize,
+    },
+    /// The secret mixes fewer than `min_classes` character classes.
+    TooFewCharacterClasses {
+        /// Minimum number of character classes among lowercase letters,
+        /// uppercase letters, digits and symbols.
+        min_classes: usize,
+    },
+    /// The secret is a commonly used password.
+    Common,
+}
+
+impl fmt::Display for SecretViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretViolation::TooShort { min_length } => {
+                write!(f, "must be at least {min_length} characters long")
+            }
+            SecretViolation::TooFewCharacterClasses { min_classes } => write!(
+                f,
+                "must mix at least {min_classes} of lowercase letters, uppercase letters, digits and symbols"
+            ),
+            SecretViolation::Common => write!(f, "must not be a commonly used password"),
+        }
+    }
+}
+
+/// Strength requirements for client secrets.
+///
+/// # Example
+///
+/// ```rust
+/// use ej_auth::secret_hash::{SecretPolicy, SecretViolation};
+///
+/// let policy = SecretPolicy::default();
+/// assert!(policy.validate("Correct-Horse-42").is_ok());
+/// assert_eq!(
+///     policy.violations("password"),
+///     vec![
+///         SecretViolation::TooShort { min_length: 12 },
+///         SecretViolation::TooFewCharacterClasses { min_classes: 3 },
+///         SecretViolation::Common,
+///     ]
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretPolicy {
+    /// Minimum number of characters.
+    pub min_length: usize,
+    /// Minimum number of character classes among lowercase letters,
+    /// uppercase letters, digits and symbols.
+    pub min_classes: usize,
+    /// Whether commonly used passwords are rejected.
+    pub reject_common: bool,
+}
+
+impl Default for SecretPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: DEFAULT_SECRET_MIN_LENGTH,
+            min_classes: DEFAULT_SECRET_MIN_CLASSES,
+            reject_common: true,
+        }
+    }
+}
+
+impl SecretPolicy {
+    /// Lists every rule of the policy `secret` breaks.
+    ///
+    /// # Returns
+    ///
+    /// The violations, empty if the secret is acceptable.
+    pub fn violations(&self, secret: &str) -> Vec<SecretViolation> {
+        let mut violations = Vec::new();
+        if secret.chars().count() < self.min_length {
+            violations.push(SecretViolation::TooShort {
+                min_length: self.min_length,
+            });
+        }
+
+        let classes = [
+            secret.chars().any(|c| c.is_lowercase()),
+            secret.chars().any(|c| c.is_uppercase()),
+            secret.chars().any(|c| c.is_ascii_digit()),
+            secret.chars().any(|c| !c.is_alphanumeric()),
+        ];
+        if classes.iter().filter(|present| **present).count() < self.min_classes {
+            violations.push(SecretViolation::TooFewCharacterClasses {
+                min_classes: self.min_classes,
+            });
+        }
+
+        if self.reject_common
+            && COMMON_SECRETS
+                .iter()
+                .any(|common| common.eq_ignore_ascii_case(secret))
+        {
+            violations.push(SecretViolation::Common);
+        }
+        violations
+    }
+
+    /// Checks that `secret` satisfies the policy.
+    ///
+    /// Fails with [`Error::WeakSecret`] listing every broken rule otherwise.
+    pub fn validate(&self, secret: &str) -> Result<()> {
+        let violations = self.violations(secret);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::WeakSecret(violations))
+        }
+    }
+}
+
 /// Generates a secure hash for the provided password.
 ///
 /// This function creates a cryptographically secure hash of the password using
@@ -111,3 +276,110 @@ impl From<password_hash::Error> for Error {
         Self::PasswordHash(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_accepts_strong_secrets() {
+        let policy = SecretPolicy::default();
+        for secret in [
+            "Correct-Horse-42",
+            "correct horse Battery",
+            "CORRECT-horse-b",
+        ] {
+            assert!(policy.violations(secret).is_empty(), "{secret}");
+            assert!(policy.validate(secret).is_ok(), "{secret}");
+        }
+    }
+
+    #[test]
+    fn test_policy_rejects_short_secrets() {
+        let policy = SecretPolicy::default();
+        assert_eq!(
+            policy.violations("Horse-42"),
+            vec![SecretViolation::TooShort { min_length: 12 }]
+        );
+        // Length counts characters, not bytes.
+        assert_eq!(
+            policy.violations("Ça-va-bien-1"),
+            Vec::<SecretViolation>::new()
+        );
+        assert_eq!(
+            policy.violations("Ça-va-bien"),
+            vec![SecretViolation::TooShort { min_length: 12 }]
+        );
+    }
+
+    #[test]
+    fn test_policy_rejects_too_few_character_classes() {
+        let policy = SecretPolicy::default();
+        let violation = SecretViolation::TooFewCharacterClasses { min_classes: 3 };
+        assert_eq!(policy.violations("correcthorsebattery"), vec![violation]);
+        assert_eq!(policy.violations("correcthorse42"), vec![violation]);
+        assert_eq!(policy.violations("CORRECT-HORSE"), vec![violation]);
+        assert!(policy.violations("correct-horse-42").is_empty());
+    }
+
+    #[test]
+    fn test_policy_rejects_common_secrets() {
+        let policy = SecretPolicy::default();
+        assert_eq!(
+            policy.violations("Welcome@1234"),
+            vec![SecretViolation::Common]
+        );
+
+        let policy = SecretPolicy {
+            reject_common: false,
+            ..SecretPolicy::default()
+        };
+        assert!(policy.violations("Welcome@1234").is_empty());
+    }
+
+    #[test]
+    fn test_policy_reports_every_violation() {
+        let policy = SecretPolicy::default();
+        let violations = vec![
+            SecretViolation::TooShort { min_length: 12 },
+            SecretViolation::TooFewCharacterClasses { min_classes: 3 },
+        ];
+        assert_eq!(policy.violations(""), violations);
+        assert!(matches!(
+            policy.validate("abc"),
+            Err(Error::WeakSecret(found)) if found == violations
+        ));
+        assert_eq!(
+            Error::WeakSecret(violations).to_string(),
+            "Secret must be at least 12 characters long, must mix at least 3 of lowercase letters, uppercase letters, digits and symbols"
+        );
+    }
+
+    #[test]
+    fn test_permissive_policy() {
+        let policy = SecretPolicy {
+            min_length: 0,
+            min_classes: 0,
+            reject_common: false,
+        };
+        assert!(policy.validate("").is_ok());
+        assert!(policy.validate("password").is_ok());
+    }
+
+    #[test]
+    fn test_violations_serialize_with_their_rule() {
+        let violations = vec![
+            SecretViolation::TooShort { min_length: 12 },
+            SecretViolation::TooFewCharacterClasses { min_classes: 3 },
+            SecretViolation::Common,
+        ];
+        assert_eq!(
+            serde_json::to_value(&violations).unwrap(),
+            serde_json::json!([
+                { "rule": "too_short", "min_length": 12 },
+                { "rule": "too_few_character_classes", "min_classes": 3 },
+                { "rule": "common" },
+            ])
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! HMAC signatures for messages sent over the ejd Unix socket.
+//!
+//! The socket trusts anyone able to connect to it. When a shared key is
+//! configured, every message must carry an HMAC-SHA256 signature of its
+//! payload computed with that key, so reaching the socket isn't enough to
+//! create users or dispatch jobs.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use ej_auth::socket_signature::SocketKey;
+//!
+//! let key = SocketKey::new("shared secret");
+//! let payload = r#"{"FetchJobs":{"commit_hash":"abc123"}}"#;
+//! let signature = key.sign(payload.as_bytes());
+//!
+//! assert!(key.verify(payload.as_bytes(), &signature).is_ok());
+//! assert!(key.verify(b"tampered", &signature).is_err());
+//! ```
+
+use std::fmt;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::prelude::*;
+
+/// Environment variable holding the key socket messages are signed with.
+pub const SOCKET_KEY_ENV: &str = "EJ_SOCKET_KEY";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Shared key used to sign and verify socket messages.
+#[derive(Clone)]
+pub struct SocketKey(Vec<u8>);
+
+impl SocketKey {
+    /// Creates a key from raw bytes.
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self(key.into())
+    }
+
+    /// Loads the key from `EJ_SOCKET_KEY`.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the variable isn't set or is empty, messages aren't signed then.
+    pub fn from_env() -> Option<Self> {
+        std::env::var(SOCKET_KEY_ENV)
+            .ok()
+            .filter(|key| !key.is_empty())
+            .map(Self::new)
+    }
+
+    /// Signs a message payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The exact bytes sent over the socket
+    ///
+    /// # Returns
+    ///
+    /// The HMAC-SHA256 of the payload encoded as lowercase hexadecimal.
+    pub fn sign(&self, payload: &[u8]) -> String {
+        hex::encode(self.mac(payload).finalize().into_bytes())
+    }
+
+    /// Checks the signature of a message payload in constant time.
+    ///
+    /// Fails with [`Error::InvalidSignature`] if the signature doesn't match.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The exact bytes received over the socket
+    /// * `signature` - The hexadecimal signature sent along with the payload
+    pub fn verify(&self, payload: &[u8], signature: &str) -> Result<()> {
+        let signature = hex::decode(signature).map_err(|_| Error::InvalidSignature)?;
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| Error::InvalidSignature)
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.0).expect("HMAC accepts any key length");
+        mac.update(payload);
+        mac
+    }
+}
+
+impl fmt::Debug for SocketKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SocketKey(..)")
+    }
+}
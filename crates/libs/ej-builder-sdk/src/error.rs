@@ -11,6 +11,18 @@ pub enum Error {
     #[error("Invalid action {0}")]
     InvalidAction(String),
 
+    /// The address of the EJB socket isn't valid.
+    #[error("Invalid EJB socket address {0}")]
+    InvalidEndpoint(String),
+
+    /// The board config of the script isn't in the configuration file.
+    #[error("Board config {1} of board {0} not found in the configuration file")]
+    BoardConfigNotFound(String, String),
+
+    /// Configuration file couldn't be loaded.
+    #[error(transparent)]
+    Config(#[from] ej_config::error::Error),
+
     /// I/O operation failed.
     #[error(transparent)]
     IO(#[from] std::io::Error),
@@ -17,43 +17,124 @@
 //!                 println!("{} {} ({:?})", sdk.board_name(), sdk.board_config_name(), sdk.action());
 //!                 std::process::exit(0);
 //!             }
+//!             BuilderEvent::Cancel { job_id, reason } => {
+//!                 // Stop talking to the board, leaving it in a known state
+//!                 println!("Job {job_id} cancelled: {reason}");
+//!                 std::process::exit(1);
+//!             }
+//!             // EJB restarted, keep going or abort
+//!             BuilderEvent::ConnectionLost | BuilderEvent::Reconnected => Ok(()),
 //!         }
 //!     }).await.unwrap();
 //!     
 //!     // Builder logic here
+//!     sdk.log("Flashing the board").await?;
 //!     Ok(())
 //! }
 //! ```
 
-use std::{env::args, path::PathBuf};
+use std::{
+    env::args,
+    io,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use ej_config::{EjConfig, EjUserConfig, ej_board_config::EjBoardConfig};
 use serde::{Deserialize, Serialize};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::{
-        UnixStream,
-        unix::{OwnedReadHalf, OwnedWriteHalf},
-    },
-    signal::unix::{SignalKind, signal},
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    sync::Mutex,
 };
-use tracing::info;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
 
 use crate::prelude::*;
+use crate::protocol::{FrameReader, write_frame};
+use crate::transport::{Endpoint, TransportReader, TransportWriter};
 pub mod error;
 pub mod prelude;
+pub mod protocol;
+pub mod transport;
 
 /// Events sent from the dispatcher to the builder.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BuilderEvent {
     /// Request to exit the builder.
     Exit,
+    /// The job the script is working for was cancelled.
+    ///
+    /// The script should stop what it's doing, like flashing a board, and
+    /// exit before EJB kills it.
+    Cancel {
+        /// The cancelled job.
+        job_id: Uuid,
+        /// Why the job was cancelled.
+        reason: String,
+    },
+    /// The connection to EJB was lost, likely because it restarted.
+    ///
+    /// Sent by the SDK itself, which reconnects in the background. Events
+    /// sent by EJB until then are missed, scripts that can't afford it should
+    /// abort.
+    ConnectionLost,
+    /// The connection to EJB was established again after being lost.
+    ///
+    /// Sent by the SDK itself.
+    Reconnected,
+}
+
+/// Messages sent by EJB to the scripts, see [`protocol`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BuilderMessage {
+    /// An event for the script.
+    Event {
+        /// The event.
+        event: BuilderEvent,
+        /// How long EJB waits for the script to exit after an
+        /// [`BuilderEvent::Exit`] or a [`BuilderEvent::Cancel`] before killing it.
+        grace_period: Option<Duration>,
+    },
+    /// Sent every [`HEARTBEAT_INTERVAL`] so scripts notice when EJB stops
+    /// responding.
+    Heartbeat,
 }
 
-/// Responses sent from the builder to the dispatcher.
+/// Delay between two heartbeats sent by EJB.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// Time without hearing from EJB after which the connection is considered lost.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Delay before the first attempt to reconnect to EJB.
+const RECONNECT_MIN_DELAY: Duration = Duration::from_millis(100);
+/// Longest delay between two attempts to reconnect to EJB.
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Responses sent from the builder to the dispatcher, see [`protocol`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BuilderResponse {
     /// Acknowledge receipt of an event.
     Ack,
+    /// A log line of the script, added to the logs of its board config.
+    Log {
+        /// The board name of the script.
+        board_name: String,
+        /// The board configuration name of the script.
+        board_config_name: String,
+        /// The log line, without its line ending.
+        line: String,
+    },
+    /// The results of a run script, sent to the dispatcher instead of the
+    /// content of the results file.
+    Results {
+        /// The board name of the script.
+        board_name: String,
+        /// The board configuration name of the script.
+        board_config_name: String,
+        /// The results.
+        results: serde_json::Value,
+    },
 }
 #[derive(Debug, Clone, Copy)]
 pub enum Action {
@@ -93,8 +174,9 @@ impl From<Action> for String {
 
 /// Builder SDK for communicating with the EJ dispatcher.
 ///
-/// Handles Unix socket communication and event processing between
-/// the builder and dispatcher.
+/// Handles the communication with EJB, over a Unix socket, a named pipe
+/// or TCP (see [`transport`]), and event processing between the builder
+/// and dispatcher.
 #[derive(Debug, Clone)]
 pub struct BuilderSdk {
     /// The board name.
@@ -105,13 +187,22 @@ pub struct BuilderSdk {
     config_path: String,
     /// The action the script should take.
     action: Action,
+    /// The address of the EJB socket.
+    endpoint: Endpoint,
+    /// The connection to EJB, shared by the event loop and the loggers.
+    writer: Arc<Mutex<TransportWriter>>,
+    /// When EJB kills the script if it's still running, set while handling
+    /// an event that asks it to exit.
+    deadline: Option<Instant>,
 }
 
 impl BuilderSdk {
     /// Initialize the builder SDK and start event processing.
     ///
-    /// Sets up Unix socket communication with the dispatcher and starts
-    /// an async event loop to handle incoming events.
+    /// Sets up the communication with the dispatcher and starts
+    /// an async event loop to handle incoming events. If the connection is
+    /// lost, the SDK reconnects with an increasing delay, telling the callback
+    /// with [`BuilderEvent::ConnectionLost`] and [`BuilderEvent::Reconnected`].
     ///
     /// # Arguments
     ///
@@ -126,6 +217,8 @@ impl BuilderSdk {
     ///     println!("{:?} {} {} ({:?})", event, sdk.board_name(), sdk.board_config_name(), sdk.action());
     ///     match event {
     ///         BuilderEvent::Exit => std::process::exit(0),
+    ///         BuilderEvent::Cancel { .. } => std::process::exit(1),
+    ///         BuilderEvent::ConnectionLost | BuilderEvent::Reconnected => Ok(()),
     ///     }
     /// }).await.unwrap();
     /// # });
@@ -142,22 +235,30 @@ impl BuilderSdk {
 
         let action: Action = TryFrom::<&str>::try_from(&args[1])?;
 
-        let stream = UnixStream::connect(&args[5]).await?;
+        let endpoint = Endpoint::parse(&args[5])?;
+        let (reader, writer) = tokio::io::split(endpoint.connect().await?);
         let sdk = Self {
             config_path: args[2].clone(),
             board_name: args[3].clone(),
             board_config_name: args[4].clone(),
             action,
+            endpoint,
+            writer: Arc::new(Mutex::new(writer)),
+            deadline: None,
         };
         let sdk_loop = sdk.clone();
-        let mut sigint = signal(SignalKind::interrupt())?;
-        tokio::spawn(async move {
-            while sigint.recv().await.is_some() {
-                info!("SIGINT received");
-            }
-        });
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+            let mut sigint = signal(SignalKind::interrupt())?;
+            tokio::spawn(async move {
+                while sigint.recv().await.is_some() {
+                    info!("SIGINT received");
+                }
+            });
+        }
 
-        tokio::spawn(async move { sdk_loop.start_event_loop(stream, event_callback).await });
+        tokio::spawn(async move { sdk_loop.start_event_loop(reader, event_callback).await });
         Ok(sdk)
     }
     /// Get the action this script should take
@@ -176,45 +277,452 @@ impl BuilderSdk {
     pub fn board_config_name(&self) -> &str {
         &self.board_config_name
     }
-    /// Parse event data from JSON string.
-    fn parse_event(payload: &str) -> Result<BuilderEvent> {
-        Ok(serde_json::from_str(payload)?)
+    /// Get the time by which the script must have exited, past which EJB
+    /// kills it.
+    ///
+    /// Only set on the SDK given to the event callback, when handling a
+    /// [`BuilderEvent::Exit`] or a [`BuilderEvent::Cancel`] sent by EJB.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use ej_builder_sdk::{BuilderSdk, BuilderEvent};
+    /// use std::time::Instant;
+    /// # tokio_test::block_on(async {
+    /// let sdk = BuilderSdk::init(|sdk, event| async move {
+    ///     if let BuilderEvent::Cancel { .. } = event
+    ///         && let Some(deadline) = sdk.deadline()
+    ///     {
+    ///         println!("Killed in {:?}", deadline.saturating_duration_since(Instant::now()));
+    ///     }
+    ///     Ok(())
+    /// }).await.unwrap();
+    /// # });
+    /// ```
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+    /// Load the configuration of the board config this script is for.
+    ///
+    /// Reads the configuration file EJB was started with, see
+    /// [`BuilderSdk::config_path`]. The `id` of the board config is generated
+    /// when loading it and doesn't match the one EJB uses.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use ej_builder_sdk::BuilderSdk;
+    /// # tokio_test::block_on(async {
+    /// let sdk = BuilderSdk::init(|_, _| async { Ok(()) }).await.unwrap();
+    /// let board_config = sdk.board_config().unwrap();
+    /// println!("Building {} from {}", board_config.name, board_config.library_path);
+    /// # });
+    /// ```
+    pub fn board_config(&self) -> Result<EjBoardConfig> {
+        let config = EjConfig::from_user_config(EjUserConfig::from_file(&self.config_path())?);
+        config
+            .boards
+            .into_iter()
+            .filter(|board| board.name == self.board_name)
+            .flat_map(|board| board.configs)
+            .find(|board_config| board_config.name == self.board_config_name)
+            .ok_or_else(|| {
+                Error::BoardConfigNotFound(self.board_name.clone(), self.board_config_name.clone())
+            })
+    }
+    /// Send a log line to EJB, added to the logs of this board config.
+    ///
+    /// Lines are forwarded to the dispatcher as they're sent, like the output
+    /// of the script.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use ej_builder_sdk::{BuilderSdk, BuilderEvent};
+    /// # tokio_test::block_on(async {
+    /// let sdk = BuilderSdk::init(|_, _| async { Ok(()) }).await.unwrap();
+    /// sdk.log("Flashing the board").await.unwrap();
+    /// # });
+    /// ```
+    pub async fn log(&self, line: impl Into<String>) -> Result<()> {
+        self.send_response(&BuilderResponse::Log {
+            board_name: self.board_name.clone(),
+            board_config_name: self.board_config_name.clone(),
+            line: line.into(),
+        })
+        .await
+    }
+    /// Send every line read from `reader` to EJB until it ends, see [`BuilderSdk::log`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use ej_builder_sdk::{BuilderSdk, BuilderEvent};
+    /// use std::process::Stdio;
+    /// use tokio::process::Command;
+    /// # tokio_test::block_on(async {
+    /// let sdk = BuilderSdk::init(|_, _| async { Ok(()) }).await.unwrap();
+    /// let mut flash = Command::new("openocd").stdout(Stdio::piped()).spawn().unwrap();
+    /// sdk.log_stream(flash.stdout.take().unwrap()).await.unwrap();
+    /// # });
+    /// ```
+    pub async fn log_stream<R: AsyncRead + Unpin>(&self, reader: R) -> Result<()> {
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            self.log(line).await?;
+        }
+        Ok(())
+    }
+    /// Submit the results of a run script to EJB.
+    ///
+    /// The results are sent to the dispatcher as JSON once the script exits
+    /// successfully, instead of the content of the `results_path` file of the
+    /// board config. Submitting results again replaces the previous ones.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use ej_builder_sdk::BuilderSdk;
+    /// # tokio_test::block_on(async {
+    /// let sdk = BuilderSdk::init(|_, _| async { Ok(()) }).await.unwrap();
+    /// let results = serde_json::json!({ "passed": 41, "failed": 1 });
+    /// sdk.submit_results(&results).await.unwrap();
+    /// # });
+    /// ```
+    pub async fn submit_results<T: Serialize>(&self, results: &T) -> Result<()> {
+        self.send_response(&BuilderResponse::Results {
+            board_name: self.board_name.clone(),
+            board_config_name: self.board_config_name.clone(),
+            results: serde_json::to_value(results)?,
+        })
+        .await
+    }
+    /// Send a response to EJB.
+    async fn send_response(&self, response: &BuilderResponse) -> Result<()> {
+        write_frame(&mut *self.writer.lock().await, response).await
     }
     /// Start the event loop for processing dispatcher messages.
-    async fn start_event_loop<F, Fut>(self, stream: UnixStream, cb: F) -> Result<()>
+    ///
+    /// Reconnects to EJB whenever the connection is lost, including when it
+    /// doesn't send heartbeats anymore, until Ctrl+C.
+    async fn start_event_loop<F, Fut>(self, reader: TransportReader, cb: F) -> Result<()>
     where
         F: Fn(Self, BuilderEvent) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = Result<()>> + Send + 'static,
     {
-        let mut payload = String::new();
-        let (mut rx, mut tx) = stream.into_split();
+        let mut frames = FrameReader::new(reader);
 
         loop {
-            tokio::select! {
-                read_result = rx.read_to_string(&mut payload)  => {
-                    match read_result {
-                        Ok(0) => break,
-                        Ok(n) => {
-                            let event = BuilderSdk::parse_event(&payload)?;
-                            info!("Received event from builder {:?}", event);
-                            cb(self.clone(), event).await;
-                            info!("Acking event to builder");
-                            let response = serde_json::to_string(&BuilderResponse::Ack)?;
-                            tx.write_all(response.as_bytes()).await;
-                            tx.write_all(b"\n").await;
-                            tx.flush().await;
-                        }
-                        Err(e) => return Err(Error::from(e)),
-                    }
+            let read_result = tokio::select! {
+                read_result = tokio::time::timeout(HEARTBEAT_TIMEOUT, frames.read_frame()) => {
+                    read_result.unwrap_or_else(|_| {
+                        Err(io::Error::new(io::ErrorKind::TimedOut, "No heartbeat from EJB").into())
+                    })
                 }
-
-                _ = tokio::signal::ctrl_c() => {
-                    info!("Received Ctrl+C, shutting down...");
-                    cb(self.clone(), BuilderEvent::Exit).await; // call callback with shutdown event
-                    break;
+                _ = tokio::signal::ctrl_c() => break,
+            };
+            let connected = match read_result {
+                Ok(Some(BuilderMessage::Heartbeat)) => true,
+                Ok(Some(BuilderMessage::Event {
+                    event,
+                    grace_period,
+                })) => {
+                    info!("Received event from builder {:?}", event);
+                    let sdk = Self {
+                        deadline: grace_period.map(|grace_period| Instant::now() + grace_period),
+                        ..self.clone()
+                    };
+                    cb(sdk, event).await;
+                    info!("Acking event to builder");
+                    self.send_response(&BuilderResponse::Ack).await.is_ok()
                 }
+                Ok(None) => false,
+                Err(Error::Json(err)) => {
+                    warn!("Ignoring invalid message from EJB - {err}");
+                    true
+                }
+                Err(err) => {
+                    debug!("Failed to read from EJB - {err}");
+                    false
+                }
+            };
+            if connected {
+                continue;
             }
+
+            warn!("Connection to EJB lost, reconnecting...");
+            cb(self.clone(), BuilderEvent::ConnectionLost).await;
+            let reader = tokio::select! {
+                reader = self.reconnect() => reader,
+                _ = tokio::signal::ctrl_c() => break,
+            };
+            frames = FrameReader::new(reader);
+            info!("Reconnected to EJB");
+            cb(self.clone(), BuilderEvent::Reconnected).await;
         }
+
+        info!("Received Ctrl+C, shutting down...");
+        cb(self.clone(), BuilderEvent::Exit).await; // call callback with shutdown event
         Ok(())
     }
+    /// Connect to EJB again, waiting longer after each failed attempt.
+    async fn reconnect(&self) -> TransportReader {
+        let mut delay = RECONNECT_MIN_DELAY;
+        loop {
+            tokio::time::sleep(delay).await;
+            match self.endpoint.connect().await {
+                Ok(stream) => {
+                    let (reader, writer) = tokio::io::split(stream);
+                    *self.writer.lock().await = writer;
+                    return reader;
+                }
+                Err(err) => {
+                    debug!("Failed to reconnect to EJB - {err}");
+                    delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncWriteExt, DuplexStream};
+
+    use super::*;
+    use crate::transport::Transport;
+
+    #[tokio::test]
+    async fn test_parse_cancel_event() {
+        let job_id = Uuid::new_v4();
+        let payload = serde_json::to_string(&BuilderMessage::Event {
+            event: BuilderEvent::Cancel {
+                job_id,
+                reason: String::from("Timeout"),
+            },
+            grace_period: Some(Duration::from_secs(60)),
+        })
+        .unwrap();
+        assert!(!payload.contains('\n'));
+
+        let payload = format!("{payload}\n\"Heartbeat\"\n");
+        let mut frames = FrameReader::new(payload.as_bytes());
+        match frames.read_frame().await.unwrap() {
+            Some(BuilderMessage::Event {
+                event:
+                    BuilderEvent::Cancel {
+                        job_id: parsed,
+                        reason,
+                    },
+                grace_period,
+            }) => {
+                assert_eq!(parsed, job_id);
+                assert_eq!(reason, "Timeout");
+                assert_eq!(grace_period, Some(Duration::from_secs(60)));
+            }
+            message => panic!("Unexpected message {message:?}"),
+        }
+        assert!(matches!(
+            frames.read_frame().await.unwrap(),
+            Some(BuilderMessage::Heartbeat)
+        ));
+    }
+
+    /// An SDK for the `rpi4` `debug` run script.
+    fn sdk(endpoint: Endpoint, writer: TransportWriter) -> BuilderSdk {
+        BuilderSdk {
+            board_name: String::from("rpi4"),
+            board_config_name: String::from("debug"),
+            config_path: std::env::temp_dir()
+                .join(format!("ej-sdk-{}.toml", Uuid::new_v4()))
+                .display()
+                .to_string(),
+            action: Action::Run,
+            endpoint,
+            writer: Arc::new(Mutex::new(writer)),
+            deadline: None,
+        }
+    }
+
+    /// An SDK for the `rpi4` `debug` run script, with the EJB end of its socket.
+    fn connected_sdk() -> (BuilderSdk, DuplexStream) {
+        let (sdk_stream, ejb_stream) = tokio::io::duplex(4096);
+        let (_, writer) = tokio::io::split(Box::new(sdk_stream) as Box<dyn Transport>);
+        (sdk(Endpoint::Unix(PathBuf::new()), writer), ejb_stream)
+    }
+
+    #[tokio::test]
+    async fn test_log_stream() {
+        let (sdk, ejb_stream) = connected_sdk();
+        sdk.log_stream(&b"Flashing\nDone\n"[..]).await.unwrap();
+        drop(sdk);
+
+        let mut lines = BufReader::new(ejb_stream).lines();
+        let mut logs = Vec::new();
+        while let Some(payload) = lines.next_line().await.unwrap() {
+            match serde_json::from_str(&payload).unwrap() {
+                BuilderResponse::Log {
+                    board_name,
+                    board_config_name,
+                    line,
+                } => {
+                    assert_eq!(
+                        (board_name.as_str(), board_config_name.as_str()),
+                        ("rpi4", "debug")
+                    );
+                    logs.push(line);
+                }
+                response => panic!("Unexpected response {response:?}"),
+            }
+        }
+        assert_eq!(logs, ["Flashing", "Done"]);
+    }
+
+    #[tokio::test]
+    async fn test_submit_results() {
+        let (sdk, ejb_stream) = connected_sdk();
+        let results = serde_json::json!({ "passed": 41, "failed": 1 });
+        sdk.submit_results(&results).await.unwrap();
+        drop(sdk);
+
+        let mut lines = BufReader::new(ejb_stream).lines();
+        let payload = lines.next_line().await.unwrap().unwrap();
+        match serde_json::from_str(&payload).unwrap() {
+            BuilderResponse::Results {
+                board_name,
+                board_config_name,
+                results: submitted,
+            } => {
+                assert_eq!(
+                    (board_name.as_str(), board_config_name.as_str()),
+                    ("rpi4", "debug")
+                );
+                assert_eq!(submitted, results);
+            }
+            response => panic!("Unexpected response {response:?}"),
+        }
+        assert!(lines.next_line().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reconnect() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("tcp://{}", listener.local_addr().unwrap());
+        let endpoint = Endpoint::parse(&address).unwrap();
+        let (reader, writer) = tokio::io::split(endpoint.connect().await.unwrap());
+        let (ejb_stream, _) = listener.accept().await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let sdk = sdk(endpoint, writer);
+        let event_loop = tokio::spawn(sdk.start_event_loop(reader, move |_, event| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(event);
+                Ok(())
+            }
+        }));
+
+        // EJB restarts
+        drop(ejb_stream);
+        assert!(matches!(
+            rx.recv().await,
+            Some(BuilderEvent::ConnectionLost)
+        ));
+        let (mut ejb_stream, _) = listener.accept().await.unwrap();
+        assert!(matches!(rx.recv().await, Some(BuilderEvent::Reconnected)));
+
+        ejb_stream
+            .write_all(b"{\"Event\":{\"event\":\"Exit\",\"grace_period\":null}}\n")
+            .await
+            .unwrap();
+        assert!(matches!(rx.recv().await, Some(BuilderEvent::Exit)));
+        let mut lines = BufReader::new(ejb_stream).lines();
+        let ack = lines.next_line().await.unwrap().unwrap();
+        assert!(matches!(
+            serde_json::from_str(&ack).unwrap(),
+            BuilderResponse::Ack
+        ));
+
+        event_loop.abort();
+    }
+
+    #[tokio::test]
+    async fn test_event_deadline() {
+        let (sdk_stream, ejb_stream) = tokio::io::duplex(4096);
+        let (reader, writer) = tokio::io::split(Box::new(sdk_stream) as Box<dyn Transport>);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let sdk = sdk(Endpoint::Unix(PathBuf::new()), writer);
+        let event_loop = tokio::spawn(sdk.start_event_loop(reader, move |sdk, event| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send((event, sdk.deadline()));
+                Ok(())
+            }
+        }));
+
+        let (ejb_reader, mut ejb_writer) = tokio::io::split(ejb_stream);
+        let grace_period = Duration::from_secs(30);
+        let cancel = BuilderMessage::Event {
+            event: BuilderEvent::Cancel {
+                job_id: Uuid::new_v4(),
+                reason: String::from("Timeout"),
+            },
+            grace_period: Some(grace_period),
+        };
+        let sent_at = Instant::now();
+        for message in [BuilderMessage::Heartbeat, cancel] {
+            let payload = format!("{}\n", serde_json::to_string(&message).unwrap());
+            ejb_writer.write_all(payload.as_bytes()).await.unwrap();
+        }
+
+        // Heartbeats aren't events
+        let (event, deadline) = rx.recv().await.unwrap();
+        assert!(matches!(event, BuilderEvent::Cancel { .. }));
+        let deadline = deadline.unwrap();
+        assert!(deadline >= sent_at + grace_period);
+        assert!(deadline <= Instant::now() + grace_period);
+
+        let mut lines = BufReader::new(ejb_reader).lines();
+        let ack = lines.next_line().await.unwrap().unwrap();
+        assert!(matches!(
+            serde_json::from_str(&ack).unwrap(),
+            BuilderResponse::Ack
+        ));
+        assert!(rx.try_recv().is_err());
+
+        event_loop.abort();
+    }
+
+    #[tokio::test]
+    async fn test_board_config() {
+        let (sdk, _) = connected_sdk();
+        let script = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        let board = |name: &str| {
+            format!(
+                "[[boards]]\nname = \"{name}\"\ndescription = \"\"\n\n\
+                 [[boards.configs]]\nname = \"debug\"\ntags = []\n\
+                 build_script = \"{script}\"\nrun_script = \"{script}\"\n\
+                 results_path = \"{name}.json\"\nlibrary_path = \"lib\"\n\n"
+            )
+        };
+        let content = format!(
+            "[global]\nversion = \"1.0.0\"\n\n{}{}",
+            board("rpi3"),
+            board("rpi4")
+        );
+        std::fs::write(sdk.config_path(), content).unwrap();
+        let board_config = sdk.board_config();
+        let missing = BuilderSdk {
+            board_config_name: String::from("release"),
+            ..sdk.clone()
+        }
+        .board_config();
+        std::fs::remove_file(sdk.config_path()).unwrap();
+
+        let board_config = board_config.unwrap();
+        assert_eq!(board_config.name, "debug");
+        assert_eq!(board_config.results_path, "rpi4.json");
+        assert!(matches!(missing, Err(Error::BoardConfigNotFound(..))));
+        assert!(matches!(sdk.board_config(), Err(Error::Config(_))));
+    }
 }
@@ -0,0 +1,164 @@
+//! Framing of the messages exchanged by EJB and the scripts.
+//!
+//! Every message is a JSON object followed by a newline. JSON escapes the
+//! newlines of strings, so a message never spans several lines, and any
+//! number of messages can be sent one after the other in both directions over
+//! a single connection: EJB sends [`BuilderMessage`](crate::BuilderMessage)s
+//! and the scripts answer with [`BuilderResponse`](crate::BuilderResponse)s.
+//! Empty lines are skipped.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use ej_builder_sdk::{BuilderResponse, protocol::{FrameReader, write_frame}};
+//! # tokio_test::block_on(async {
+//! let (script, ejb) = tokio::io::duplex(1024);
+//! let (_, mut writer) = tokio::io::split(script);
+//! write_frame(&mut writer, &BuilderResponse::Ack).await.unwrap();
+//! write_frame(&mut writer, &BuilderResponse::Ack).await.unwrap();
+//! drop(writer);
+//!
+//! let mut reader = FrameReader::new(ejb);
+//! while let Some(response) = reader.read_frame::<BuilderResponse>().await.unwrap() {
+//!     assert!(matches!(response, BuilderResponse::Ack));
+//! }
+//! # });
+//! ```
+
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines};
+
+use crate::prelude::*;
+
+/// Writes a message and flushes it.
+pub async fn write_frame<W, T>(writer: &mut W, message: &T) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut payload = serde_json::to_vec(message)?;
+    payload.push(b'\n');
+    writer.write_all(&payload).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Reads the messages of a connection, one at a time.
+#[derive(Debug)]
+pub struct FrameReader<R> {
+    lines: Lines<BufReader<R>>,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    /// Reads the messages of `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: BufReader::new(reader).lines(),
+        }
+    }
+
+    /// Reads the next message, `None` once the connection is closed.
+    ///
+    /// A message that can't be parsed is reported as [`Error::Json`] and
+    /// skipped, the next call reading the message after it. Cancelling the
+    /// call doesn't lose any message.
+    pub async fn read_frame<T: DeserializeOwned>(&mut self) -> Result<Option<T>> {
+        loop {
+            let Some(line) = self.lines.next_line().await? else {
+                return Ok(None);
+            };
+            if !line.trim().is_empty() {
+                return Ok(Some(serde_json::from_str(&line)?));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{BuilderEvent, BuilderMessage, BuilderResponse};
+
+    #[tokio::test]
+    async fn test_sequential_messages() {
+        let (ejb, script) = tokio::io::duplex(4096);
+        let (ejb_reader, mut ejb_writer) = tokio::io::split(ejb);
+        let (script_reader, mut script_writer) = tokio::io::split(script);
+        let mut ejb_reader = FrameReader::new(ejb_reader);
+        let mut script_reader = FrameReader::new(script_reader);
+
+        let job_id = Uuid::new_v4();
+        let events = [
+            BuilderEvent::Cancel {
+                job_id,
+                reason: String::from("Timeout\nafter 60s"),
+            },
+            BuilderEvent::Exit,
+        ];
+        for event in events {
+            let message = BuilderMessage::Event {
+                event,
+                grace_period: Some(Duration::from_secs(60)),
+            };
+            write_frame(&mut ejb_writer, &BuilderMessage::Heartbeat)
+                .await
+                .unwrap();
+            write_frame(&mut ejb_writer, &message).await.unwrap();
+
+            let heartbeat = script_reader.read_frame().await.unwrap();
+            assert!(matches!(heartbeat, Some(BuilderMessage::Heartbeat)));
+            match script_reader.read_frame().await.unwrap() {
+                Some(BuilderMessage::Event {
+                    event:
+                        BuilderEvent::Cancel {
+                            job_id: parsed,
+                            reason,
+                        },
+                    ..
+                }) => {
+                    assert_eq!(parsed, job_id);
+                    assert_eq!(reason, "Timeout\nafter 60s");
+                }
+                Some(BuilderMessage::Event {
+                    event: BuilderEvent::Exit,
+                    ..
+                }) => {}
+                message => panic!("Unexpected message {message:?}"),
+            }
+
+            write_frame(&mut script_writer, &BuilderResponse::Ack)
+                .await
+                .unwrap();
+            let ack = ejb_reader.read_frame().await.unwrap();
+            assert!(matches!(ack, Some(BuilderResponse::Ack)));
+        }
+
+        drop((ejb_reader, ejb_writer));
+        let end = script_reader.read_frame::<BuilderMessage>().await.unwrap();
+        assert!(end.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_message_is_skipped() {
+        let payload = "\n\"Ack\"\n{\"Unknown\":{}}\n\n\"Ack\"\n";
+        let mut reader = FrameReader::new(payload.as_bytes());
+
+        let first = reader.read_frame().await.unwrap();
+        assert!(matches!(first, Some(BuilderResponse::Ack)));
+        let invalid = reader.read_frame::<BuilderResponse>().await;
+        assert!(matches!(invalid, Err(Error::Json(_))));
+        let second = reader.read_frame().await.unwrap();
+        assert!(matches!(second, Some(BuilderResponse::Ack)));
+        assert!(
+            reader
+                .read_frame::<BuilderResponse>()
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Connections between builder scripts and EJB.
+//!
+//! EJB hands the address of its socket to the scripts it starts as their
+//! last argument, see [`Endpoint::parse`]. Unix sockets are used on Unix and
+//! named pipes on Windows, localhost TCP connections working everywhere.
+
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadHalf, WriteHalf};
+
+use crate::prelude::*;
+
+/// Prefix of TCP addresses.
+pub const TCP_PREFIX: &str = "tcp://";
+
+/// Prefix of Windows named pipe names.
+pub const NAMED_PIPE_PREFIX: &str = r"\\.\pipe\";
+
+/// A connection to EJB.
+pub trait Transport: AsyncRead + AsyncWrite + fmt::Debug + Send + Unpin + 'static {}
+
+impl<T> Transport for T where T: AsyncRead + AsyncWrite + fmt::Debug + Send + Unpin + 'static {}
+
+/// Reading half of a connection to EJB.
+pub type TransportReader = ReadHalf<Box<dyn Transport>>;
+
+/// Writing half of a connection to EJB.
+pub type TransportWriter = WriteHalf<Box<dyn Transport>>;
+
+/// Address of the EJB socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    /// Unix socket path.
+    Unix(PathBuf),
+    /// Windows named pipe name, like `\\.\pipe\ejb`.
+    NamedPipe(String),
+    /// Localhost TCP address.
+    Tcp(SocketAddr),
+}
+
+impl Endpoint {
+    /// Parse the address of the EJB socket.
+    ///
+    /// Addresses starting with `tcp://` are localhost TCP addresses, names
+    /// starting with `\\.\pipe\` are Windows named pipes and anything else is
+    /// a Unix socket path.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_builder_sdk::transport::Endpoint;
+    ///
+    /// assert!(matches!(Endpoint::parse("/tmp/ejb.sock"), Ok(Endpoint::Unix(_))));
+    /// assert!(matches!(Endpoint::parse(r"\\.\pipe\ejb"), Ok(Endpoint::NamedPipe(_))));
+    /// assert!(matches!(Endpoint::parse("tcp://127.0.0.1:4000"), Ok(Endpoint::Tcp(_))));
+    /// // EJB is always on the same machine as its scripts
+    /// assert!(Endpoint::parse("tcp://192.168.1.10:4000").is_err());
+    /// ```
+    pub fn parse(address: &str) -> Result<Self> {
+        if let Some(address) = address.strip_prefix(TCP_PREFIX) {
+            return match address.parse::<SocketAddr>() {
+                Ok(address) if address.ip().is_loopback() => Ok(Self::Tcp(address)),
+                _ => Err(Error::InvalidEndpoint(format!("{TCP_PREFIX}{address}"))),
+            };
+        }
+        if address.starts_with(NAMED_PIPE_PREFIX) {
+            return Ok(Self::NamedPipe(address.to_string()));
+        }
+        Ok(Self::Unix(PathBuf::from(address)))
+    }
+
+    /// Connect to EJB.
+    ///
+    /// Connecting to a Unix socket on Windows or to a named pipe elsewhere
+    /// fails with [`std::io::ErrorKind::Unsupported`].
+    pub async fn connect(&self) -> std::io::Result<Box<dyn Transport>> {
+        match self {
+            Self::Unix(path) => connect_unix(path).await,
+            Self::NamedPipe(name) => connect_named_pipe(name),
+            Self::Tcp(address) => Ok(Box::new(tokio::net::TcpStream::connect(address).await?)),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unix(path) => write!(f, "{}", path.display()),
+            Self::NamedPipe(name) => f.write_str(name),
+            Self::Tcp(address) => write!(f, "{TCP_PREFIX}{address}"),
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: &Path) -> std::io::Result<Box<dyn Transport>> {
+    Ok(Box::new(tokio::net::UnixStream::connect(path).await?))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(path: &Path) -> std::io::Result<Box<dyn Transport>> {
+    Err(unsupported(&path.display().to_string()))
+}
+
+#[cfg(windows)]
+fn connect_named_pipe(name: &str) -> std::io::Result<Box<dyn Transport>> {
+    Ok(Box::new(
+        tokio::net::windows::named_pipe::ClientOptions::new().open(name)?,
+    ))
+}
+
+#[cfg(not(windows))]
+fn connect_named_pipe(name: &str) -> std::io::Result<Box<dyn Transport>> {
+    Err(unsupported(name))
+}
+
+fn unsupported(address: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("{address} isn't supported on this platform"),
+    )
+}
@@ -0,0 +1,329 @@
+//! Format independent view of a configuration document.
+//!
+//! Validation reports where every problem is, which serde doesn't say for
+//! most of them. Documents are parsed into a tree of [`Node`]s instead, that
+//! keeps the position of every key and value whatever the format. JSON being
+//! valid YAML, both are read with the YAML parser.
+
+use std::ops::Range;
+
+use toml_edit::{ImDocument, Item, TableLike, Value};
+use yaml_rust2::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::{Marker, TScalarStyle},
+};
+
+use crate::format::{EjConfigFormat, without_location};
+
+/// Line and column, both starting at 1.
+pub(crate) type Position = (usize, usize);
+
+/// A value of a document and where it starts.
+pub(crate) struct Node {
+    pub position: Position,
+    pub value: NodeValue,
+}
+
+/// The values a [`Node`] can hold.
+pub(crate) enum NodeValue {
+    Table(Vec<Entry>),
+    Array(Vec<Node>),
+    String(String),
+    Integer(i64),
+    /// Any other value, as written.
+    Other(String),
+}
+
+/// A key of a table, where it is and its value.
+pub(crate) struct Entry {
+    pub key: String,
+    pub position: Position,
+    pub node: Node,
+}
+
+impl Node {
+    /// The entries of a table, none for other values.
+    pub fn entries(&self) -> &[Entry] {
+        match &self.value {
+            NodeValue::Table(entries) => entries,
+            _ => &[],
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        self.entries()
+            .iter()
+            .find(|entry| entry.key == key)
+            .map(|entry| &entry.node)
+    }
+
+    pub fn is_table(&self) -> bool {
+        matches!(self.value, NodeValue::Table(_))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.value {
+            NodeValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self.value {
+            NodeValue::Integer(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Node]> {
+        match &self.value {
+            NodeValue::Array(nodes) => Some(nodes),
+            _ => None,
+        }
+    }
+
+    /// The tables of an array of tables, none if this isn't an array.
+    pub fn tables(&self) -> Vec<&Node> {
+        self.as_array()
+            .unwrap_or_default()
+            .iter()
+            .filter(|node| node.is_table())
+            .collect()
+    }
+
+    /// The value as it would be written, for messages.
+    pub fn written(&self) -> String {
+        match &self.value {
+            NodeValue::Table(_) => String::from("a table"),
+            NodeValue::Array(_) => String::from("an array"),
+            NodeValue::String(value) => format!("{value:?}"),
+            NodeValue::Integer(value) => value.to_string(),
+            NodeValue::Other(value) => value.clone(),
+        }
+    }
+}
+
+/// Line and column of the byte at `offset` in `source`.
+pub(crate) fn position(source: &str, offset: usize) -> Position {
+    let offset = offset.min(source.len());
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Parses a document, returning where the syntax error is and what it is on failure.
+pub(crate) fn parse(source: &str, format: EjConfigFormat) -> Result<Node, (Position, String)> {
+    match format {
+        EjConfigFormat::Toml => parse_toml(source),
+        EjConfigFormat::Yaml => parse_yaml(source),
+        EjConfigFormat::Json => {
+            // Report JSON syntax errors as JSON ones, not YAML ones
+            if let Err(err) = serde_json::from_str::<serde::de::IgnoredAny>(source)
+                && (err.is_syntax() || err.is_eof())
+            {
+                let position = (err.line().max(1), err.column().max(1));
+                return Err((position, without_location(err.to_string())));
+            }
+            parse_yaml(source)
+        }
+    }
+}
+
+fn parse_toml(source: &str) -> Result<Node, (Position, String)> {
+    let document = ImDocument::parse(source).map_err(|err| {
+        let offset = err.span().map_or(0, |span| span.start);
+        (position(source, offset), err.message().trim().to_string())
+    })?;
+    Ok(toml_table(source, document.as_table(), None))
+}
+
+fn toml_position(source: &str, span: Option<Range<usize>>) -> Position {
+    position(source, span.map_or(0, |span| span.start))
+}
+
+fn toml_table(source: &str, table: &dyn TableLike, span: Option<Range<usize>>) -> Node {
+    let entries = table
+        .iter()
+        .filter_map(|(key, item)| {
+            Some(Entry {
+                key: key.to_string(),
+                position: toml_position(source, table.key(key).and_then(|key| key.span())),
+                node: toml_item(source, item)?,
+            })
+        })
+        .collect();
+    Node {
+        position: toml_position(source, span),
+        value: NodeValue::Table(entries),
+    }
+}
+
+fn toml_item(source: &str, item: &Item) -> Option<Node> {
+    match item {
+        Item::None => None,
+        Item::Value(value) => Some(toml_value(source, value)),
+        Item::Table(table) => Some(toml_table(source, table, table.span())),
+        Item::ArrayOfTables(array) => Some(Node {
+            position: toml_position(source, array.span()),
+            value: NodeValue::Array(
+                array
+                    .iter()
+                    .map(|table| toml_table(source, table, table.span()))
+                    .collect(),
+            ),
+        }),
+    }
+}
+
+fn toml_value(source: &str, value: &Value) -> Node {
+    let node_value = match value {
+        Value::String(string) => NodeValue::String(string.value().clone()),
+        Value::Integer(integer) => NodeValue::Integer(*integer.value()),
+        Value::Array(array) => NodeValue::Array(
+            array
+                .iter()
+                .map(|value| toml_value(source, value))
+                .collect(),
+        ),
+        Value::InlineTable(table) => return toml_table(source, table, table.span()),
+        other => NodeValue::Other(other.clone().decorated("", "").to_string()),
+    };
+    Node {
+        position: toml_position(source, value.span()),
+        value: node_value,
+    }
+}
+
+fn parse_yaml(source: &str) -> Result<Node, (Position, String)> {
+    let mut builder = YamlBuilder::default();
+    Parser::new_from_str(source)
+        .load(&mut builder, false)
+        .map_err(|err| {
+            let marker = err.marker();
+            ((marker.line(), marker.col() + 1), err.info().to_string())
+        })?;
+    Ok(builder.root.unwrap_or(Node {
+        position: (1, 1),
+        value: NodeValue::Table(Vec::new()),
+    }))
+}
+
+/// A YAML mapping or sequence being read.
+enum Frame {
+    Table {
+        position: Position,
+        entries: Vec<Entry>,
+        key: Option<(String, Position)>,
+    },
+    Array {
+        position: Position,
+        nodes: Vec<Node>,
+    },
+}
+
+/// Builds the tree of a YAML document from the parser events.
+#[derive(Default)]
+struct YamlBuilder {
+    frames: Vec<Frame>,
+    root: Option<Node>,
+}
+
+impl YamlBuilder {
+    fn add(&mut self, node: Node) {
+        match self.frames.last_mut() {
+            None => {
+                self.root.get_or_insert(node);
+            }
+            Some(Frame::Array { nodes, .. }) => nodes.push(node),
+            Some(Frame::Table { entries, key, .. }) => match key.take() {
+                Some((key, position)) => entries.push(Entry {
+                    key,
+                    position,
+                    node,
+                }),
+                None => {
+                    let name = match node.value {
+                        NodeValue::String(name) | NodeValue::Other(name) => name,
+                        _ => node.written(),
+                    };
+                    *key = Some((name, node.position));
+                }
+            },
+        }
+    }
+}
+
+impl MarkedEventReceiver for YamlBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        let position = (mark.line(), mark.col() + 1);
+        match event {
+            Event::MappingStart(..) => self.frames.push(Frame::Table {
+                position,
+                entries: Vec::new(),
+                key: None,
+            }),
+            Event::SequenceStart(..) => self.frames.push(Frame::Array {
+                position,
+                nodes: Vec::new(),
+            }),
+            Event::MappingEnd | Event::SequenceEnd => {
+                let node = match self.frames.pop() {
+                    // Block mappings start after their first key, point at the key instead
+                    Some(Frame::Table {
+                        position, entries, ..
+                    }) => Node {
+                        position: entries.first().map_or(position, |entry| entry.position),
+                        value: NodeValue::Table(entries),
+                    },
+                    Some(Frame::Array { position, nodes }) => Node {
+                        position,
+                        value: NodeValue::Array(nodes),
+                    },
+                    None => return,
+                };
+                self.add(node);
+            }
+            Event::Scalar(value, style, ..) => self.add(Node {
+                position,
+                value: yaml_scalar(value, style),
+            }),
+            Event::Alias(_) => self.add(Node {
+                position,
+                value: NodeValue::Other(String::from("an alias")),
+            }),
+            _ => {}
+        }
+    }
+}
+
+/// The value of a YAML scalar, plain ones being resolved like YAML 1.2 does.
+fn yaml_scalar(value: String, style: TScalarStyle) -> NodeValue {
+    if style != TScalarStyle::Plain {
+        return NodeValue::String(value);
+    }
+    if let Ok(integer) = value.parse() {
+        return NodeValue::Integer(integer);
+    }
+    let is_other = value.parse::<f64>().is_ok()
+        || matches!(
+            value.as_str(),
+            "" | "~"
+                | "null"
+                | "Null"
+                | "NULL"
+                | "true"
+                | "True"
+                | "TRUE"
+                | "false"
+                | "False"
+                | "FALSE"
+        );
+    if is_other {
+        NodeValue::Other(value)
+    } else {
+        NodeValue::String(value)
+    }
+}
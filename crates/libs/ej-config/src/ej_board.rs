@@ -1,23 +1,40 @@
 //! Board definition types.
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::ej_board_config::{EjBoardConfig, EjUserBoardConfig};
+use crate::{
+    ej_board_config::{EjBoardConfig, EjUserBoardConfig},
+    prelude::*,
+    secret::{EjEnvValue, resolve_env},
+};
 
 /// User-defined board configuration.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjUserBoard {
     /// Board name.
     pub name: String,
     /// Board description.
     pub description: String,
+    /// Environment variables set for the scripts of every board configuration.
+    /// Use them for things shared by the whole board, like its serial port.
+    /// Values may be secret references, see [`secret`](crate::secret).
+    #[serde(
+        default,
+        deserialize_with = "crate::secret::deserialize_env",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub env: BTreeMap<String, EjEnvValue>,
     /// Board configurations.
     pub configs: Vec<EjUserBoardConfig>,
 }
 
 /// Internal board configuration with UUID.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjBoard {
     /// Unique board identifier.
     pub id: Uuid,
@@ -25,6 +42,9 @@ pub struct EjBoard {
     pub name: String,
     /// Board description.
     pub description: String,
+    /// Environment variables set for the scripts of every board configuration.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, EjEnvValue>,
     /// Board configurations.
     pub configs: Vec<EjBoardConfig>,
 }
@@ -41,7 +61,26 @@ impl EjBoard {
             id: Uuid::new_v4(),
             name: board.name,
             description: board.description,
+            env: board.env,
             configs: configs,
         }
     }
+
+    /// Environment variables of a board configuration scripts.
+    ///
+    /// Board configuration variables override the board ones with the same name.
+    pub fn script_env(&self, board_config: &EjBoardConfig) -> BTreeMap<String, EjEnvValue> {
+        let mut env = self.env.clone();
+        env.extend(board_config.env.clone());
+        env
+    }
+
+    /// Environment variables of a board configuration scripts, with their
+    /// secret references read.
+    pub fn resolve_script_env(
+        &self,
+        board_config: &EjBoardConfig,
+    ) -> Result<BTreeMap<String, String>> {
+        resolve_env(&self.script_env(board_config))
+    }
 }
@@ -1,6 +1,9 @@
 //! Board configuration types.
 
+use crate::ej_container::EjContainerConfig;
 use crate::prelude::*;
+use crate::secret::EjEnvValue;
+use std::collections::BTreeMap;
 use std::fmt::{self};
 
 use serde::{Deserialize, Serialize};
@@ -8,6 +11,7 @@ use uuid::Uuid;
 
 /// User-defined board configuration. Usually loaded from TOML files.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjUserBoardConfig {
     /// Configuration name. Used to identify the configuration. Recommended to be unique.
     pub name: String,
@@ -32,12 +36,63 @@ pub struct EjUserBoardConfig {
     pub results_path: String,
     /// Library path. This is the path that will be checked out by the builder before building the configurations.
     /// You can share this path between multiple boards.
-    /// Mandatory to make this a git repository and to have the repository already setup.
+    /// The builder makes it a git repository if it isn't one already.
     pub library_path: String,
+    /// Artifact paths. Files uploaded to the dispatcher once the job is done,
+    /// such as firmware images, test binaries or run logs, so they can be downloaded later.
+    /// Relative paths are resolved from the library path. File names may contain
+    /// `*` and `?` wildcards, see [`glob`](crate::glob).
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    /// Environment variables. Set for the build and run scripts processes on top of
+    /// the builder environment, so scripts don't need to hard-code toolchain paths
+    /// or serial ports. They override the board variables with the same name.
+    /// Values may be secret references, see [`secret`](crate::secret).
+    #[serde(
+        default,
+        deserialize_with = "crate::secret::deserialize_env",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub env: BTreeMap<String, EjEnvValue>,
+    /// Container. Runs the build and run scripts in a container of the given image
+    /// instead of directly on the builder, with the given devices available.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<EjContainerConfig>,
+    /// Probe command. Shell command the builder runs before a job to check the board
+    /// is reachable, e.g. `ping -c 1 -W 2 rpi3.local` or `test -e /dev/ttyUSB0`.
+    /// Jobs skip the board configuration when it fails instead of building it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probe: Option<String>,
+    /// Build timeout in seconds. The build script is killed and the build fails if it
+    /// runs longer, so a wedged board doesn't hold the job until the dispatcher gives up.
+    /// Build timeouts given when dispatching a job take precedence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_timeout: Option<u64>,
+    /// Run timeout in seconds. The run script is killed and produces no results if it
+    /// runs longer. Run timeouts given when dispatching a job take precedence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_timeout: Option<u64>,
+    /// Pre-build hook. Script the builder runs before the build script, with the same
+    /// arguments and environment. The build fails without running the build script if it does.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_build: Option<String>,
+    /// Post-build hook. Script the builder runs after the build script, whether it
+    /// succeeded or not. Its failure is logged but doesn't change the build outcome.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_build: Option<String>,
+    /// Pre-run hook. Script the builder runs before the run script, e.g. to power-cycle
+    /// the board. The run produces no results without running the run script if it fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_run: Option<String>,
+    /// Post-run hook. Script the builder runs after the run script, whether it succeeded
+    /// or not, e.g. to collect coverage. Its failure is logged but doesn't discard the results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_run: Option<String>,
 }
 
 /// Internal board configuration with UUID.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjBoardConfig {
     /// Unique configuration identifier assigned by the system.
     pub id: Uuid,
@@ -53,10 +108,41 @@ pub struct EjBoardConfig {
     pub results_path: String,
     /// Library path from user input.
     pub library_path: String,
+    /// Artifact paths from user input.
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    /// Environment variables from user input.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, EjEnvValue>,
+    /// Container from user input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<EjContainerConfig>,
+    /// Probe command from user input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probe: Option<String>,
+    /// Build timeout in seconds from user input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_timeout: Option<u64>,
+    /// Run timeout in seconds from user input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_timeout: Option<u64>,
+    /// Pre-build hook script path from user input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_build: Option<String>,
+    /// Post-build hook script path from user input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_build: Option<String>,
+    /// Pre-run hook script path from user input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_run: Option<String>,
+    /// Post-run hook script path from user input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_run: Option<String>,
 }
 
 /// API representation of board configuration (subset of full config).
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjBoardConfigApi {
     /// Configuration identifier.
     pub id: Uuid,
@@ -77,8 +163,73 @@ impl EjBoardConfig {
             run_script: value.run_script,
             results_path: value.results_path,
             library_path: value.library_path,
+            artifacts: value.artifacts,
+            env: value.env,
+            container: value.container,
+            probe: value.probe,
+            build_timeout: value.build_timeout,
+            run_timeout: value.run_timeout,
+            pre_build: value.pre_build,
+            post_build: value.post_build,
+            pre_run: value.pre_run,
+            post_run: value.post_run,
         }
     }
+
+    /// Whether the configuration has every tag in `required_tags`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_config::ej_board_config::{EjBoardConfig, EjUserBoardConfig};
+    ///
+    /// let config = EjBoardConfig::from_ej_board_config(EjUserBoardConfig {
+    ///     name: String::from("Rpi3 Wayland"),
+    ///     tags: vec![String::from("arm64"), String::from("wayland")],
+    ///     build_script: String::from("build.sh"),
+    ///     run_script: String::from("run.sh"),
+    ///     results_path: String::from("results.json"),
+    ///     library_path: String::from("lib"),
+    ///     artifacts: Vec::new(),
+    ///     env: Default::default(),
+    ///     container: None,
+    ///     probe: None,
+    ///     build_timeout: None,
+    ///     run_timeout: None,
+    ///     pre_build: None,
+    ///     post_build: None,
+    ///     pre_run: None,
+    ///     post_run: None,
+    /// });
+    /// assert!(config.has_tags(&[]));
+    /// assert!(config.has_tags(&[String::from("arm64")]));
+    /// assert!(!config.has_tags(&[String::from("arm64"), String::from("x11")]));
+    /// ```
+    pub fn has_tags(&self, required_tags: &[String]) -> bool {
+        required_tags.iter().all(|tag| self.tags.contains(tag))
+    }
+
+    /// Whether a job targeting `board_configs` and `required_tags` runs on the configuration.
+    ///
+    /// The configuration must be named in `board_configs`, unless it's empty,
+    /// and have every tag in `required_tags`.
+    pub fn is_targeted(&self, board_configs: &[String], required_tags: &[String]) -> bool {
+        (board_configs.is_empty() || board_configs.contains(&self.name))
+            && self.has_tags(required_tags)
+    }
+}
+
+impl EjBoardConfigApi {
+    /// Whether the configuration has every tag in `required_tags`.
+    pub fn has_tags(&self, required_tags: &[String]) -> bool {
+        required_tags.iter().all(|tag| self.tags.contains(tag))
+    }
+
+    /// Whether a job targeting `board_configs` and `required_tags` runs on the configuration.
+    pub fn is_targeted(&self, board_configs: &[String], required_tags: &[String]) -> bool {
+        (board_configs.is_empty() || board_configs.contains(&self.name))
+            && self.has_tags(required_tags)
+    }
 }
 
 impl fmt::Display for EjBoardConfigApi {
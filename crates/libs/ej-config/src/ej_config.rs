@@ -1,6 +1,13 @@
 //! Core configuration types for the EJ framework.
 
-use crate::{ej_board::EjBoard, prelude::*};
+use crate::{
+    ej_board::EjBoard,
+    ej_board_config::EjBoardConfig,
+    format::EjConfigFormat,
+    migration::{self, CURRENT_CONFIG_VERSION},
+    prelude::*,
+    validation,
+};
 use std::path::Path;
 
 use ej_auth::sha256::generate_hash;
@@ -12,22 +19,35 @@ use super::ej_board::EjUserBoard;
 
 /// Global configuration settings.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjGlobalConfig {
     /// Configuration version.
     pub version: String,
 }
 
-/// User-provided configuration from TOML files.
+/// User-provided configuration from TOML, YAML or JSON files.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjUserConfig {
+    /// Configuration format version, see [`migration`].
+    ///
+    /// Configurations written in older formats are migrated to the current
+    /// one when loaded, so this is always [`CURRENT_CONFIG_VERSION`] once loaded.
+    #[serde(default = "current_config_version")]
+    pub version: u32,
     /// Global settings.
     pub global: EjGlobalConfig,
     /// Board definitions.
     pub boards: Vec<EjUserBoard>,
 }
 
+fn current_config_version() -> u32 {
+    CURRENT_CONFIG_VERSION
+}
+
 /// Internal configuration with generated UUIDs.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjConfig {
     /// Global settings.
     pub global: EjGlobalConfig,
@@ -49,17 +69,109 @@ impl EjConfig {
                 .collect(),
         }
     }
+
+    /// Restricts the configuration to the board configs having every tag in `required_tags`.
+    ///
+    /// # Returns
+    ///
+    /// The restricted configuration, without the boards left with no board
+    /// config, and the IDs of the board configs that were left out.
+    pub fn filter_by_tags(&self, required_tags: &[String]) -> (Self, Vec<Uuid>) {
+        self.filter_for_job(&[], required_tags)
+    }
+
+    /// Restricts the configuration to the board configs a job targets.
+    ///
+    /// Only the board configs named in `board_configs`, or all of them when it's
+    /// empty, having every tag in `required_tags` are kept.
+    ///
+    /// # Returns
+    ///
+    /// The restricted configuration, without the boards left with no board
+    /// config, and the IDs of the board configs that were left out.
+    pub fn filter_for_job(
+        &self,
+        board_configs: &[String],
+        required_tags: &[String],
+    ) -> (Self, Vec<Uuid>) {
+        self.retain(|board_config| board_config.is_targeted(board_configs, required_tags))
+    }
+
+    /// Removes the board configs whose ID is in `board_config_ids` from the
+    /// configuration, along with the boards left with no board config.
+    pub fn without_board_configs(&self, board_config_ids: &[Uuid]) -> Self {
+        self.retain(|board_config| !board_config_ids.contains(&board_config.id))
+            .0
+    }
+
+    /// Keeps the board configs matching `keep`, returning the restricted
+    /// configuration and the IDs of the board configs that were left out.
+    fn retain(&self, keep: impl Fn(&EjBoardConfig) -> bool) -> (Self, Vec<Uuid>) {
+        let mut skipped = Vec::new();
+        let boards = self
+            .boards
+            .iter()
+            .filter_map(|board| {
+                let (configs, left_out): (Vec<_>, Vec<_>) = board
+                    .configs
+                    .iter()
+                    .cloned()
+                    .partition(|board_config| keep(board_config));
+                skipped.extend(left_out.into_iter().map(|board_config| board_config.id));
+                if configs.is_empty() {
+                    return None;
+                }
+                Some(EjBoard {
+                    configs,
+                    ..board.clone()
+                })
+            })
+            .collect();
+        let config = Self {
+            global: self.global.clone(),
+            boards,
+        };
+        (config, skipped)
+    }
 }
 
 impl EjUserConfig {
-    /// Load configuration from a TOML file.
+    /// Load configuration from a file.
+    ///
+    /// The file format is detected from its extension, see
+    /// [`EjConfigFormat::from_path`].
+    ///
+    /// The boards of the fragment files listed in the `include` key are
+    /// appended to the file ones. Entries are paths relative to the file
+    /// directory and may use `*` and `?` wildcards in the file name, like
+    /// `include = ["boards/*.toml"]`. Fragments only contain `[[boards]]` and
+    /// may be written in another format than the file.
+    ///
+    /// The files are validated first: unknown keys, missing required keys,
+    /// duplicate board names, duplicate config names in a board and build or
+    /// run scripts that don't exist are all reported, with their file and
+    /// line, in an [`Error::Invalid`].
+    ///
+    /// Files written in an older format version are migrated, see [`migration`].
     pub fn from_file(file_path: &Path) -> Result<Self> {
         let contents = std::fs::read_to_string(file_path)?;
-        Ok(Self::from_toml(&contents)?)
+        validation::load(file_path, contents).map_err(|issues| Error::Invalid {
+            path: file_path.to_path_buf(),
+            issues,
+        })
     }
     /// Parse configuration from TOML string.
+    ///
+    /// Configurations written in an older format version are migrated, see [`migration`].
     pub fn from_toml(value: &str) -> Result<Self> {
-        Ok(toml::from_str(value)?)
+        Self::parse(value, EjConfigFormat::Toml)
+    }
+
+    /// Parse configuration written in `format`.
+    ///
+    /// Configurations written in an older format version are migrated, see [`migration`].
+    pub fn parse(value: &str, format: EjConfigFormat) -> Result<Self> {
+        migration::parse(value, format)
     }
 }
 
@@ -67,6 +179,8 @@ impl EjUserConfig {
 mod tests {
 
     use super::*;
+    use crate::ej_container::EjContainerConfig;
+    use crate::secret::EjEnvValue;
 
     #[test]
     pub fn deserialize() -> Result<()> {
@@ -127,4 +241,730 @@ mod tests {
         toml::from_str::<EjUserConfig>(content)?;
         Ok(())
     }
+
+    /// Board config using this crate's manifest as build and run script.
+    fn existing_scripts_config(name: &str) -> String {
+        let script = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        format!(
+            r#"
+[[boards.configs]]
+name = "{name}"
+tags = []
+build_script = "{script}"
+run_script = "{script}"
+results_path = "results.json"
+library_path = "lib"
+"#
+        )
+    }
+
+    #[test]
+    pub fn from_file_reports_issues_with_lines() {
+        let content = format!(
+            r#"version = 2
+
+[global]
+version = "1.0.0"
+
+[[boards]]
+name = "Raspberry Pi 3"
+{}
+[[boards.configs]]
+board = "rpi3"
+name = "Rpi3 Wayland"
+tags = []
+build_script = "/does/not/exist.sh"
+run_script = "/does/not/exist.sh"
+results_path = "results.json"
+library_path = "lib"
+pre_run = "/does/not/exist.sh"
+{}"#,
+            existing_scripts_config("Rpi3 Wayland"),
+            existing_scripts_config("Rpi3 SDL")
+        );
+        let path = std::env::temp_dir().join(format!("ej-config-{}.toml", Uuid::new_v4()));
+        std::fs::write(&path, &content).unwrap();
+        let result = EjUserConfig::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let Err(Error::Invalid {
+            path: error_path,
+            issues,
+        }) = result
+        else {
+            panic!("Expected the configuration to be invalid, got {:?}", result);
+        };
+        assert_eq!(error_path, path);
+        assert!(issues.iter().all(|issue| issue.path == path));
+        let found: Vec<(usize, usize, &str)> = issues
+            .iter()
+            .map(|issue| (issue.line, issue.column, issue.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (6, 1, "missing required key `description` in board"),
+                (
+                    18,
+                    1,
+                    "unknown key `board` in board config, expected one of `name`, `tags`, \
+                     `build_script`, `run_script`, `results_path`, `library_path`, `artifacts`, \
+                     `env`, `container`, `probe`, `build_timeout`, `run_timeout`, `pre_build`, \
+                     `post_build`, `pre_run`, `post_run`"
+                ),
+                (
+                    19,
+                    8,
+                    "duplicate board config name `Rpi3 Wayland`, first defined on line 10"
+                ),
+                (
+                    21,
+                    16,
+                    "`build_script` path `/does/not/exist.sh` doesn't exist"
+                ),
+                (
+                    22,
+                    14,
+                    "`run_script` path `/does/not/exist.sh` doesn't exist"
+                ),
+                (25, 11, "`pre_run` path `/does/not/exist.sh` doesn't exist"),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn from_file_accepts_valid_config() {
+        let content = format!(
+            "[global]\nversion = \"1.0.0\"\n\n[[boards]]\nname = \"x86\"\ndescription = \"Desktop\"\n{}",
+            existing_scripts_config("Wayland")
+        );
+        let path = std::env::temp_dir().join(format!("ej-config-{}.toml", Uuid::new_v4()));
+        std::fs::write(&path, &content).unwrap();
+        let result = EjUserConfig::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let config = result.unwrap();
+        assert_eq!(config, EjUserConfig::from_toml(&content).unwrap());
+    }
+
+    #[test]
+    pub fn from_file_checks_container_keys() {
+        let content = |container: &str| {
+            format!(
+                "[global]\nversion = \"1.0.0\"\n\n[[boards]]\nname = \"x86\"\ndescription = \"Desktop\"\n{}\
+                 [boards.configs.container]\n{container}",
+                existing_scripts_config("Wayland")
+            )
+        };
+        let path = std::env::temp_dir().join(format!("ej-config-{}.toml", Uuid::new_v4()));
+        std::fs::write(&path, content("device = \"/dev/ttyUSB0\"\n")).unwrap();
+        let invalid = EjUserConfig::from_file(&path);
+        std::fs::write(
+            &path,
+            content("image = \"toolchain:1.0\"\ndevices = [\"/dev/ttyUSB0\"]\n"),
+        )
+        .unwrap();
+        let valid = EjUserConfig::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let Err(Error::Invalid { issues, .. }) = invalid else {
+            panic!(
+                "Expected the configuration to be invalid, got {:?}",
+                invalid
+            );
+        };
+        let messages: Vec<&str> = issues.iter().map(|issue| issue.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "missing required key `image` in container",
+                "unknown key `device` in container, expected one of `image`, `devices`",
+            ]
+        );
+        assert_eq!(
+            valid.unwrap().boards[0].configs[0].container,
+            Some(EjContainerConfig {
+                image: String::from("toolchain:1.0"),
+                devices: vec![String::from("/dev/ttyUSB0")],
+            })
+        );
+    }
+
+    #[test]
+    pub fn from_file_reports_duplicate_boards_and_type_errors() {
+        let path = Path::new("config.toml");
+        let duplicate = validation::load(
+            path,
+            String::from("[global]\nversion = \"1\"\n\n[[boards]]\nname = \"a\"\ndescription = \"\"\nconfigs = []\n\n[[boards]]\nname = \"a\"\ndescription = \"\"\nconfigs = []\n"),
+        )
+        .unwrap_err();
+        assert_eq!(duplicate.len(), 1);
+        assert_eq!(duplicate[0].line, 10);
+        assert_eq!(
+            duplicate[0].message,
+            "duplicate board name `a`, first defined on line 5"
+        );
+
+        let wrong_type =
+            validation::load(path, String::from("boards = []\n\n[global]\nversion = 1\n"))
+                .unwrap_err();
+        assert_eq!(wrong_type.len(), 1);
+        assert_eq!(wrong_type[0].line, 4);
+    }
+
+    /// Writes `files` to a new temporary directory.
+    fn write_files(files: &[(&str, String)]) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(format!("ej-config-{}", Uuid::new_v4()));
+        for (path, content) in files {
+            let path = directory.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    pub fn from_file_merges_included_fragments() {
+        let board = |name: &str| {
+            format!(
+                "[[boards]]\nname = \"{name}\"\ndescription = \"\"\n{}",
+                existing_scripts_config("Default")
+            )
+        };
+        let directory = write_files(&[
+            (
+                "config.toml",
+                format!(
+                    "include = [\"boards/*.toml\", \"extra.toml\"]\n\n[global]\nversion = \"1.0.0\"\n\n{}",
+                    board("Main")
+                ),
+            ),
+            ("boards/b.toml", board("B")),
+            ("boards/a.toml", format!("{}\n{}", board("A1"), board("A2"))),
+            ("boards/notes.txt", String::from("not a fragment")),
+            ("extra.toml", board("Extra")),
+        ]);
+        let result = EjUserConfig::from_file(&directory.join("config.toml"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let names: Vec<String> = result.unwrap().boards.into_iter().map(|b| b.name).collect();
+        assert_eq!(names, vec!["Main", "A1", "A2", "B", "Extra"]);
+    }
+
+    #[test]
+    pub fn from_file_reports_fragment_issues() {
+        let directory = write_files(&[
+            (
+                "config.toml",
+                format!(
+                    "include = [\"boards/*.toml\", \"missing.toml\"]\n\n[global]\nversion = \"1.0.0\"\n\n[[boards]]\nname = \"Rpi3\"\ndescription = \"\"\n{}",
+                    existing_scripts_config("Default")
+                ),
+            ),
+            (
+                "boards/rpi3.toml",
+                format!(
+                    "[global]\nversion = \"1.0.0\"\n\n[[boards]]\nname = \"Rpi3\"\ndescription = \"\"\n{}",
+                    existing_scripts_config("Default")
+                ),
+            ),
+        ]);
+        let config_path = directory.join("config.toml");
+        let result = EjUserConfig::from_file(&config_path);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let Err(Error::Invalid { issues, .. }) = result else {
+            panic!("Expected the configuration to be invalid, got {:?}", result);
+        };
+        let fragment_path = directory.join("boards/rpi3.toml");
+        let found: Vec<(&std::path::Path, usize, &str)> = issues
+            .iter()
+            .map(|issue| (issue.path.as_path(), issue.line, issue.message.as_str()))
+            .collect();
+        let duplicate = format!(
+            "duplicate board name `Rpi3`, first defined in {} on line 7",
+            config_path.display()
+        );
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].0, config_path);
+        assert_eq!(found[0].1, 1);
+        assert!(found[0].2.starts_with("can't read"), "{}", found[0].2);
+        assert_eq!(
+            found[1],
+            (
+                fragment_path.as_path(),
+                1,
+                "unknown key `global` in the fragment, expected one of `boards`"
+            )
+        );
+        assert_eq!(found[2], (fragment_path.as_path(), 5, duplicate.as_str()));
+    }
+
+    #[test]
+    pub fn board_config_env_overrides_board_env() -> Result<()> {
+        let content = r#"
+            [global]
+            version = "1.0.0"
+
+            [[boards]]
+            name = "Raspberry Pi 3"
+            description = "Raspberry Pi 3 Model B+"
+            env = { SERIAL_PORT = "/dev/ttyUSB0", CC = "gcc" }
+
+            [[boards.configs]]
+            name = "Rpi3 Clang"
+            tags = []
+            build_script = "build.sh"
+            run_script = "run.sh"
+            results_path = "results.json"
+            library_path = "lib"
+
+            [boards.configs.env]
+            CC = "clang"
+        "#;
+        let config = EjConfig::from_user_config(EjUserConfig::from_toml(content)?);
+        let board = &config.boards[0];
+        let env = board.resolve_script_env(&board.configs[0])?;
+        assert_eq!(
+            env.into_iter().collect::<Vec<_>>(),
+            vec![
+                (String::from("CC"), String::from("clang")),
+                (String::from("SERIAL_PORT"), String::from("/dev/ttyUSB0")),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn env_secret_references_are_resolved_for_scripts() -> Result<()> {
+        let directory = write_files(&[("token", String::from("file-secret\n"))]);
+        let content = format!(
+            r#"
+            [global]
+            version = "1.0.0"
+
+            [[boards]]
+            name = "Raspberry Pi 3"
+            description = "Raspberry Pi 3 Model B+"
+            env = {{ API_TOKEN = {{ from_env = "CARGO_PKG_NAME" }} }}
+
+            [[boards.configs]]
+            name = "Rpi3 Wayland"
+            tags = []
+            build_script = "build.sh"
+            run_script = "run.sh"
+            results_path = "results.json"
+            library_path = "lib"
+            env = {{ SERIAL_PORT = "/dev/ttyUSB0", KEY = {{ from_file = "{}" }} }}
+            "#,
+            directory.join("token").display()
+        );
+        let result = EjUserConfig::from_toml(&content).and_then(|config| {
+            let config = EjConfig::from_user_config(config);
+            let board = &config.boards[0];
+            board.resolve_script_env(&board.configs[0])
+        });
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let env = result?;
+        // Cargo sets it when running tests
+        assert_eq!(env["API_TOKEN"], env!("CARGO_PKG_NAME"));
+        assert_eq!(env["KEY"], "file-secret");
+        assert_eq!(env["SERIAL_PORT"], "/dev/ttyUSB0");
+        Ok(())
+    }
+
+    #[test]
+    pub fn serialized_config_keeps_secret_references() -> Result<()> {
+        let directory = write_files(&[("token", String::from("file-secret\n"))]);
+        let content = format!(
+            r#"
+            [global]
+            version = "1.0.0"
+
+            [[boards]]
+            name = "Raspberry Pi 3"
+            description = "Raspberry Pi 3 Model B+"
+            env = {{ API_TOKEN = {{ from_env = "CARGO_PKG_NAME" }} }}
+
+            [[boards.configs]]
+            name = "Rpi3 Wayland"
+            tags = []
+            build_script = "build.sh"
+            run_script = "run.sh"
+            results_path = "results.json"
+            library_path = "lib"
+            env = {{ KEY = {{ from_file = "{}" }} }}
+            "#,
+            directory.join("token").display()
+        );
+        let result = EjUserConfig::from_toml(&content);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let config = EjConfig::from_user_config(result?);
+        let json = serde_json::to_string(&config)?;
+        assert!(!json.contains("file-secret"), "{json}");
+        assert!(
+            !json.contains(&format!("\"{}\"", env!("CARGO_PKG_NAME"))),
+            "{json}"
+        );
+        assert!(json.contains(r#"{"from_env":"CARGO_PKG_NAME"}"#), "{json}");
+        assert!(json.contains(r#"{"from_file":"#), "{json}");
+
+        // The dispatcher reads it back without the secrets being available
+        let uploaded: EjConfig = serde_json::from_str(&json)?;
+        assert_eq!(uploaded, config);
+        Ok(())
+    }
+
+    #[test]
+    pub fn from_file_reports_unresolved_secret_references() {
+        let content = format!(
+            "[global]\nversion = \"1.0.0\"\n\n[[boards]]\nname = \"x86\"\ndescription = \"Desktop\"\nenv = {{ TOKEN = {{ from_env = \"EJ_CONFIG_TEST_UNSET\" }} }}\n{}",
+            existing_scripts_config("Wayland")
+        );
+        let issues = validation::load(Path::new("config.toml"), content).unwrap_err();
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].line, issues[0].column), (7, 17));
+        assert_eq!(
+            issues[0].message,
+            "can't read secret from environment variable `EJ_CONFIG_TEST_UNSET`: \
+             environment variable not found"
+        );
+    }
+
+    #[test]
+    pub fn filter_by_tags() -> Result<()> {
+        let content = r#"
+            [global]
+            version = "1.0.0"
+
+            [[boards]]
+            name = "Raspberry Pi 3"
+            description = ""
+
+            [[boards.configs]]
+            name = "Rpi3 Wayland"
+            tags = ["arm64", "wayland"]
+            build_script = "build.sh"
+            run_script = "run.sh"
+            results_path = "results.json"
+            library_path = "lib"
+
+            [[boards.configs]]
+            name = "Rpi3 SDL"
+            tags = ["arm64", "sdl2"]
+            build_script = "build.sh"
+            run_script = "run.sh"
+            results_path = "results.json"
+            library_path = "lib"
+
+            [[boards]]
+            name = "x86"
+            description = ""
+
+            [[boards.configs]]
+            name = "x86 Wayland"
+            tags = ["x86_64", "wayland"]
+            build_script = "build.sh"
+            run_script = "run.sh"
+            results_path = "results.json"
+            library_path = "lib"
+        "#;
+        let config = EjConfig::from_user_config(EjUserConfig::from_toml(content)?);
+
+        let (all, skipped) = config.filter_by_tags(&[]);
+        assert_eq!(all, config);
+        assert!(skipped.is_empty());
+
+        let (filtered, skipped) =
+            config.filter_by_tags(&[String::from("arm64"), String::from("wayland")]);
+        assert_eq!(filtered.boards.len(), 1);
+        assert_eq!(filtered.boards[0].configs.len(), 1);
+        assert_eq!(filtered.boards[0].configs[0].name, "Rpi3 Wayland");
+        assert_eq!(
+            skipped,
+            vec![
+                config.boards[0].configs[1].id,
+                config.boards[1].configs[0].id
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn filter_for_job() -> Result<()> {
+        let content = r#"
+            [global]
+            version = "1.0.0"
+
+            [[boards]]
+            name = "Raspberry Pi 3"
+            description = ""
+
+            [[boards.configs]]
+            name = "Rpi3 Wayland"
+            tags = ["arm64", "wayland"]
+            build_script = "build.sh"
+            run_script = "run.sh"
+            results_path = "results.json"
+            library_path = "lib"
+
+            [[boards.configs]]
+            name = "Rpi3 SDL"
+            tags = ["arm64", "sdl2"]
+            build_script = "build.sh"
+            run_script = "run.sh"
+            results_path = "results.json"
+            library_path = "lib"
+
+            [[boards]]
+            name = "x86"
+            description = ""
+
+            [[boards.configs]]
+            name = "x86 Wayland"
+            tags = ["x86_64", "wayland"]
+            build_script = "build.sh"
+            run_script = "run.sh"
+            results_path = "results.json"
+            library_path = "lib"
+        "#;
+        let config = EjConfig::from_user_config(EjUserConfig::from_toml(content)?);
+
+        let (filtered, skipped) = config.filter_for_job(&[String::from("Rpi3 SDL")], &[]);
+        assert_eq!(filtered.boards.len(), 1);
+        assert_eq!(filtered.boards[0].configs.len(), 1);
+        assert_eq!(filtered.boards[0].configs[0].name, "Rpi3 SDL");
+        assert_eq!(
+            skipped,
+            vec![
+                config.boards[0].configs[0].id,
+                config.boards[1].configs[0].id
+            ]
+        );
+
+        let (filtered, _) = config.filter_for_job(
+            &[String::from("Rpi3 SDL"), String::from("x86 Wayland")],
+            &[String::from("wayland")],
+        );
+        assert_eq!(filtered.boards.len(), 1);
+        assert_eq!(filtered.boards[0].configs[0].name, "x86 Wayland");
+
+        let filtered = config.without_board_configs(&[config.boards[1].configs[0].id]);
+        assert_eq!(filtered.boards.len(), 1);
+        assert_eq!(filtered.boards[0].configs.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    pub fn from_toml_migrates_older_versions() -> Result<()> {
+        let board = |extra: &str| {
+            format!(
+                r#"
+[global]
+version = "1.0.0"
+
+[[boards]]
+name = "Raspberry Pi 3"
+description = ""
+
+[[boards.configs]]
+{extra}
+name = "Rpi3 Wayland"
+tags = ["wayland", "arm64"]
+build_script = "build.sh"
+run_script = "run.sh"
+results_path = "results.json"
+library_path = "lib"
+"#
+            )
+        };
+        let current = EjUserConfig::from_toml(&format!("version = 2\n{}", board("")))?;
+        assert_eq!(current.version, CURRENT_CONFIG_VERSION);
+
+        let legacy = EjUserConfig::from_toml(&board(r#"board = "rpi3""#))?;
+        assert_eq!(legacy, current);
+        let legacy = EjUserConfig::from_toml(&format!("version = 1\n{}", board("")))?;
+        assert_eq!(legacy, current);
+
+        let result = EjUserConfig::from_toml(&format!("version = 3\n{}", board("")));
+        assert!(matches!(result, Err(Error::UnsupportedVersion(3))));
+        Ok(())
+    }
+
+    #[test]
+    pub fn from_file_reports_unsupported_version() {
+        let directory = write_files(&[(
+            "config.toml",
+            format!(
+                "version = 7\n\n[global]\nversion = \"1.0.0\"\n\n[[boards]]\nname = \"x86\"\ndescription = \"\"\n{}",
+                existing_scripts_config("Wayland")
+            ),
+        )]);
+        let result = EjUserConfig::from_file(&directory.join("config.toml"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let Err(Error::Invalid { issues, .. }) = result else {
+            panic!("Expected the configuration to be invalid, got {:?}", result);
+        };
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].line, issues[0].column), (1, 11));
+        assert_eq!(
+            issues[0].message,
+            "unsupported configuration version `7`, expected 1 to 2"
+        );
+    }
+
+    #[test]
+    pub fn from_file_accepts_yaml_and_json() {
+        let script = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        let toml = format!(
+            "version = 2\n\n[global]\nversion = \"1.0.0\"\n\n[[boards]]\nname = \"x86\"\ndescription = \"Desktop\"\nenv = {{ DISPLAY = \":0\" }}\n{}",
+            existing_scripts_config("Wayland")
+        );
+        let yaml = format!(
+            r#"version: 2
+global:
+  version: "1.0.0"
+boards:
+  - name: x86
+    description: Desktop
+    env:
+      DISPLAY: ":0"
+    configs:
+      - name: Wayland
+        tags: []
+        build_script: {script}
+        run_script: {script}
+        results_path: results.json
+        library_path: lib
+"#
+        );
+        let json = format!(
+            "{{\n\t\"version\": 2,\n\t\"global\": {{\"version\": \"1.0.0\"}},\n\t\"boards\": [{{\n\t\t\"name\": \"x86\",\n\t\t\"description\": \"Desktop\",\n\t\t\"env\": {{\"DISPLAY\": \":0\"}},\n\t\t\"configs\": [{{\"name\": \"Wayland\", \"tags\": [], \"build_script\": \"{script}\", \"run_script\": \"{script}\", \"results_path\": \"results.json\", \"library_path\": \"lib\"}}]\n\t}}]\n}}\n"
+        );
+        let directory = write_files(&[
+            ("config.toml", toml),
+            ("config.yaml", yaml),
+            ("config.json", json),
+        ]);
+        let toml = EjUserConfig::from_file(&directory.join("config.toml"));
+        let yaml = EjUserConfig::from_file(&directory.join("config.yaml"));
+        let json = EjUserConfig::from_file(&directory.join("config.json"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let toml = toml.unwrap();
+        assert_eq!(toml.boards[0].env["DISPLAY"], EjEnvValue::from(":0"));
+        assert_eq!(yaml.unwrap(), toml);
+        assert_eq!(json.unwrap(), toml);
+    }
+
+    #[test]
+    pub fn from_file_reports_yaml_and_json_issues_with_lines() {
+        let yaml = String::from(
+            r#"version: 2
+global:
+  version: "1.0.0"
+include: ["boards.json"]
+boards:
+  - name: rpi3
+    configs:
+      - name: Wayland
+        tags: []
+        build_script: /does/not/exist.sh
+        run_script: /does/not/exist.sh
+        results_path: results.json
+        library_path: lib
+        colour: blue
+"#,
+        );
+        let json = String::from(
+            r#"{
+  "boards": [
+    {
+      "name": "rpi3",
+      "description": "",
+      "configs": []
+    }
+  ],
+  "extra": true
+}"#,
+        );
+        let directory = write_files(&[
+            ("config.yml", yaml),
+            ("boards.json", json),
+            ("broken.json", String::from("{\n  \"boards\": [,]\n}")),
+        ]);
+        let result = EjUserConfig::from_file(&directory.join("config.yml"));
+        let broken = EjUserConfig::from_file(&directory.join("broken.json"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let Err(Error::Invalid { issues, .. }) = result else {
+            panic!("Expected the configuration to be invalid, got {:?}", result);
+        };
+        let duplicate = format!(
+            "duplicate board name `rpi3`, first defined in {} on line 6",
+            directory.join("config.yml").display()
+        );
+        let found: Vec<(String, usize, usize, &str)> = issues
+            .iter()
+            .map(|issue| {
+                let file = issue
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string();
+                (file, issue.line, issue.column, issue.message.as_str())
+            })
+            .collect();
+        let issue = |file: &str, line, column, message| (file.to_string(), line, column, message);
+        assert_eq!(
+            found,
+            vec![
+                issue(
+                    "config.yml",
+                    6,
+                    5,
+                    "missing required key `description` in board"
+                ),
+                issue(
+                    "config.yml",
+                    10,
+                    23,
+                    "`build_script` path `/does/not/exist.sh` doesn't exist"
+                ),
+                issue(
+                    "config.yml",
+                    11,
+                    21,
+                    "`run_script` path `/does/not/exist.sh` doesn't exist"
+                ),
+                issue(
+                    "config.yml",
+                    14,
+                    9,
+                    "unknown key `colour` in board config, expected one of `name`, `tags`, \
+                     `build_script`, `run_script`, `results_path`, `library_path`, \
+                     `artifacts`, `env`, `container`, `probe`, `build_timeout`, \
+                     `run_timeout`, `pre_build`, `post_build`, `pre_run`, `post_run`"
+                ),
+                issue("boards.json", 4, 15, &duplicate),
+                issue(
+                    "boards.json",
+                    9,
+                    3,
+                    "unknown key `extra` in the fragment, expected one of `boards`"
+                ),
+            ]
+        );
+
+        let Err(Error::Invalid { issues, .. }) = broken else {
+            panic!("Expected the configuration to be invalid, got {:?}", broken);
+        };
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].line, issues[0].column), (2, 14));
+        assert_eq!(issues[0].message, "expected value");
+    }
 }
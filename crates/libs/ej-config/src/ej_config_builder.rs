@@ -0,0 +1,468 @@
+//! Building configurations in code.
+//!
+//! [`EjConfigBuilder`] describes boards and board configs with method calls
+//! instead of TOML strings, for tests and tools that generate configurations.
+//! Each [`board`](EjConfigBuilder::board) call starts a new board and each
+//! [`config`](EjConfigBuilder::config) call a new board config of the last
+//! board, the other methods set the last board config.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{
+    ej_board::EjUserBoard,
+    ej_board_config::EjUserBoardConfig,
+    ej_config::{EjGlobalConfig, EjUserConfig},
+    ej_container::EjContainerConfig,
+    migration::CURRENT_CONFIG_VERSION,
+    prelude::*,
+    secret::EjEnvValue,
+};
+
+/// Fluent builder of [`EjUserConfig`]s.
+///
+/// Mistakes, like setting a script before adding a board config or leaving
+/// out a required path, are collected along the way and reported together by
+/// [`build`](Self::build).
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_config::EjConfigBuilder;
+///
+/// let config = EjConfigBuilder::new("1.0.0")
+///     .board("Raspberry Pi 3", "Raspberry Pi 3 Model B+")
+///     .board_env("SERIAL_PORT", "/dev/ttyUSB0")
+///     .config("Rpi3 Wayland")
+///     .tags(["arm64", "wayland"])
+///     .script("ejkmer-builder")
+///     .results_path("results/wayland.txt")
+///     .library_path("kmer")
+///     .config("Rpi3 SDL")
+///     .tags(["arm64", "sdl2"])
+///     .build_script("build.sh")
+///     .run_script("run.sh")
+///     .results_path("results/sdl.txt")
+///     .library_path("kmer")
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(config.boards[0].configs.len(), 2);
+/// assert_eq!(config.boards[0].configs[0].run_script, "ejkmer-builder");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EjConfigBuilder {
+    version: String,
+    boards: Vec<BoardDraft>,
+    problems: Vec<String>,
+}
+
+/// Board being built.
+#[derive(Debug, Clone, Default)]
+struct BoardDraft {
+    name: String,
+    description: String,
+    env: BTreeMap<String, EjEnvValue>,
+    configs: Vec<BoardConfigDraft>,
+}
+
+/// Board config being built, the required paths being unset until given.
+#[derive(Debug, Clone, Default)]
+struct BoardConfigDraft {
+    name: String,
+    tags: Vec<String>,
+    build_script: Option<String>,
+    run_script: Option<String>,
+    results_path: Option<String>,
+    library_path: Option<String>,
+    artifacts: Vec<String>,
+    env: BTreeMap<String, EjEnvValue>,
+    container_image: Option<String>,
+    devices: Vec<String>,
+    probe: Option<String>,
+    build_timeout: Option<u64>,
+    run_timeout: Option<u64>,
+    pre_build: Option<String>,
+    post_build: Option<String>,
+    pre_run: Option<String>,
+    post_run: Option<String>,
+}
+
+impl EjConfigBuilder {
+    /// Starts a configuration with its global `version`.
+    pub fn new(version: impl Into<String>) -> Self {
+        Self {
+            version: version.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds a board, the board configs added next belong to it.
+    pub fn board(mut self, name: impl Into<String>, description: impl Into<String>) -> Self {
+        self.boards.push(BoardDraft {
+            name: name.into(),
+            description: description.into(),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Sets an environment variable for the scripts of every config of the last board.
+    pub fn board_env(mut self, name: impl Into<String>, value: impl Into<EjEnvValue>) -> Self {
+        match self.boards.last_mut() {
+            Some(board) => {
+                board.env.insert(name.into(), value.into());
+            }
+            None => self.misplaced("board_env"),
+        }
+        self
+    }
+
+    /// Adds a board config to the last board.
+    pub fn config(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        match self.boards.last_mut() {
+            Some(board) => board.configs.push(BoardConfigDraft {
+                name,
+                ..Default::default()
+            }),
+            None => self
+                .problems
+                .push(format!("board config `{name}` added before any board")),
+        }
+        self
+    }
+
+    /// Adds tags to the last board config.
+    pub fn tags(self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.with_config("tags", |config| {
+            config.tags.extend(tags.into_iter().map(Into::into))
+        })
+    }
+
+    /// Sets both scripts of the last board config, for builders handling
+    /// the build and the run in a single program.
+    pub fn script(self, path: impl Into<String>) -> Self {
+        let path = path.into();
+        self.with_config("script", |config| {
+            config.build_script = Some(path.clone());
+            config.run_script = Some(path);
+        })
+    }
+
+    /// Sets the build script of the last board config.
+    pub fn build_script(self, path: impl Into<String>) -> Self {
+        self.with_config("build_script", |config| {
+            config.build_script = Some(path.into())
+        })
+    }
+
+    /// Sets the run script of the last board config.
+    pub fn run_script(self, path: impl Into<String>) -> Self {
+        self.with_config("run_script", |config| config.run_script = Some(path.into()))
+    }
+
+    /// Sets the results path of the last board config.
+    pub fn results_path(self, path: impl Into<String>) -> Self {
+        self.with_config("results_path", |config| {
+            config.results_path = Some(path.into())
+        })
+    }
+
+    /// Sets the library path of the last board config.
+    pub fn library_path(self, path: impl Into<String>) -> Self {
+        self.with_config("library_path", |config| {
+            config.library_path = Some(path.into())
+        })
+    }
+
+    /// Adds an artifact path to the last board config.
+    pub fn artifact(self, path: impl Into<String>) -> Self {
+        self.with_config("artifact", |config| config.artifacts.push(path.into()))
+    }
+
+    /// Sets an environment variable for the scripts of the last board config.
+    pub fn env(self, name: impl Into<String>, value: impl Into<EjEnvValue>) -> Self {
+        self.with_config("env", |config| {
+            config.env.insert(name.into(), value.into());
+        })
+    }
+
+    /// Runs the scripts of the last board config in a container of `image`.
+    pub fn container(self, image: impl Into<String>) -> Self {
+        self.with_config("container", |config| {
+            config.container_image = Some(image.into())
+        })
+    }
+
+    /// Makes a device available in the container of the last board config.
+    pub fn device(self, path: impl Into<String>) -> Self {
+        self.with_config("device", |config| config.devices.push(path.into()))
+    }
+
+    /// Sets the command probing the board of the last board config before a job.
+    pub fn probe(self, command: impl Into<String>) -> Self {
+        self.with_config("probe", |config| config.probe = Some(command.into()))
+    }
+
+    /// Limits the build of the last board config to `seconds`.
+    pub fn build_timeout(self, seconds: u64) -> Self {
+        self.with_config("build_timeout", |config| {
+            config.build_timeout = Some(seconds)
+        })
+    }
+
+    /// Limits the run of the last board config to `seconds`.
+    pub fn run_timeout(self, seconds: u64) -> Self {
+        self.with_config("run_timeout", |config| config.run_timeout = Some(seconds))
+    }
+
+    /// Sets the script run before the build script of the last board config.
+    pub fn pre_build(self, path: impl Into<String>) -> Self {
+        self.with_config("pre_build", |config| config.pre_build = Some(path.into()))
+    }
+
+    /// Sets the script run after the build script of the last board config.
+    pub fn post_build(self, path: impl Into<String>) -> Self {
+        self.with_config("post_build", |config| config.post_build = Some(path.into()))
+    }
+
+    /// Sets the script run before the run script of the last board config.
+    pub fn pre_run(self, path: impl Into<String>) -> Self {
+        self.with_config("pre_run", |config| config.pre_run = Some(path.into()))
+    }
+
+    /// Sets the script run after the run script of the last board config.
+    pub fn post_run(self, path: impl Into<String>) -> Self {
+        self.with_config("post_run", |config| config.post_run = Some(path.into()))
+    }
+
+    /// Builds the configuration.
+    ///
+    /// Returns `Error::Incomplete` with every problem found: methods called
+    /// before the board or board config they set, board configs missing a
+    /// script or path, devices of board configs without a container,
+    /// duplicate board names and duplicate config names in a board. Unlike [`EjUserConfig::from_file`], scripts aren't required to
+    /// exist, the configuration may be meant for another machine.
+    pub fn build(self) -> Result<EjUserConfig> {
+        let mut problems = self.problems;
+        let mut board_names = HashSet::new();
+        let mut boards = Vec::new();
+        for board in self.boards {
+            if !board_names.insert(board.name.clone()) {
+                problems.push(format!("duplicate board name `{}`", board.name));
+            }
+            let mut config_names = HashSet::new();
+            let mut configs = Vec::new();
+            for config in board.configs {
+                if !config_names.insert(config.name.clone()) {
+                    problems.push(format!(
+                        "duplicate board config name `{}` in board `{}`",
+                        config.name, board.name
+                    ));
+                }
+                let container = match config.container_image {
+                    Some(image) => Some(EjContainerConfig {
+                        image,
+                        devices: config.devices,
+                    }),
+                    None if config.devices.is_empty() => None,
+                    None => {
+                        problems.push(format!(
+                            "devices without a container in board config `{}` of board `{}`",
+                            config.name, board.name
+                        ));
+                        None
+                    }
+                };
+                let mut require = |value: Option<String>, key: &str| {
+                    value.unwrap_or_else(|| {
+                        problems.push(format!(
+                            "missing `{key}` in board config `{}` of board `{}`",
+                            config.name, board.name
+                        ));
+                        String::new()
+                    })
+                };
+                configs.push(EjUserBoardConfig {
+                    build_script: require(config.build_script, "build_script"),
+                    run_script: require(config.run_script, "run_script"),
+                    results_path: require(config.results_path, "results_path"),
+                    library_path: require(config.library_path, "library_path"),
+                    name: config.name,
+                    tags: config.tags,
+                    artifacts: config.artifacts,
+                    env: config.env,
+                    container,
+                    probe: config.probe,
+                    build_timeout: config.build_timeout,
+                    run_timeout: config.run_timeout,
+                    pre_build: config.pre_build,
+                    post_build: config.post_build,
+                    pre_run: config.pre_run,
+                    post_run: config.post_run,
+                });
+            }
+            boards.push(EjUserBoard {
+                name: board.name,
+                description: board.description,
+                env: board.env,
+                configs,
+            });
+        }
+        if !problems.is_empty() {
+            return Err(Error::Incomplete(problems));
+        }
+        Ok(EjUserConfig {
+            version: CURRENT_CONFIG_VERSION,
+            global: EjGlobalConfig {
+                version: self.version,
+            },
+            boards,
+        })
+    }
+
+    /// Applies `set` to the last board config, `method` being reported if there's none.
+    fn with_config(mut self, method: &str, set: impl FnOnce(&mut BoardConfigDraft)) -> Self {
+        match self
+            .boards
+            .last_mut()
+            .and_then(|board| board.configs.last_mut())
+        {
+            Some(config) => set(config),
+            None => self.misplaced(method),
+        }
+        self
+    }
+
+    fn misplaced(&mut self, method: &str) {
+        self.problems
+            .push(format!("`{method}` called before adding what it sets"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_matches_parsed_config() -> Result<()> {
+        let built = EjConfigBuilder::new("1.0.0")
+            .board("Raspberry Pi 3", "Raspberry Pi 3 Model B+")
+            .board_env("SERIAL_PORT", "/dev/ttyUSB0")
+            .config("Rpi3 Wayland")
+            .tags(["arm64", "wayland"])
+            .build_script("build.sh")
+            .run_script("run.sh")
+            .results_path("results.json")
+            .library_path("lib")
+            .artifact("app.img")
+            .env("CC", "clang")
+            .probe("ping -c 1 rpi3.local")
+            .build_timeout(600)
+            .run_timeout(60)
+            .pre_build("fetch-toolchain.sh")
+            .post_build("strip.sh")
+            .pre_run("power-cycle.sh")
+            .post_run("collect-coverage.sh")
+            .build()?;
+        let parsed = EjUserConfig::from_toml(
+            r#"
+            version = 2
+
+            [global]
+            version = "1.0.0"
+
+            [[boards]]
+            name = "Raspberry Pi 3"
+            description = "Raspberry Pi 3 Model B+"
+            env = { SERIAL_PORT = "/dev/ttyUSB0" }
+
+            [[boards.configs]]
+            name = "Rpi3 Wayland"
+            tags = ["arm64", "wayland"]
+            build_script = "build.sh"
+            run_script = "run.sh"
+            results_path = "results.json"
+            library_path = "lib"
+            artifacts = ["app.img"]
+            env = { CC = "clang" }
+            probe = "ping -c 1 rpi3.local"
+            build_timeout = 600
+            run_timeout = 60
+            pre_build = "fetch-toolchain.sh"
+            post_build = "strip.sh"
+            pre_run = "power-cycle.sh"
+            post_run = "collect-coverage.sh"
+            "#,
+        )?;
+        assert_eq!(built, parsed);
+        Ok(())
+    }
+
+    #[test]
+    fn build_sets_container() -> Result<()> {
+        let config = EjConfigBuilder::new("1.0.0")
+            .board("Rpi3", "")
+            .config("Wayland")
+            .script("ejkmer-builder")
+            .results_path("results.json")
+            .library_path("lib")
+            .container("toolchain:1.0")
+            .device("/dev/ttyUSB0")
+            .build()?;
+        assert_eq!(
+            config.boards[0].configs[0].container,
+            Some(EjContainerConfig {
+                image: String::from("toolchain:1.0"),
+                devices: vec![String::from("/dev/ttyUSB0")],
+            })
+        );
+
+        let result = EjConfigBuilder::new("1.0.0")
+            .board("Rpi3", "")
+            .config("Wayland")
+            .script("ejkmer-builder")
+            .results_path("results.json")
+            .library_path("lib")
+            .device("/dev/ttyUSB0")
+            .build();
+        let Err(Error::Incomplete(problems)) = result else {
+            panic!("Expected the configuration to be incomplete, got {result:?}");
+        };
+        assert_eq!(
+            problems,
+            vec!["devices without a container in board config `Wayland` of board `Rpi3`"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn build_reports_every_problem() {
+        let result = EjConfigBuilder::new("1.0.0")
+            .config("Orphan")
+            .script("ejkmer-builder")
+            .board("Rpi3", "")
+            .config("Wayland")
+            .script("ejkmer-builder")
+            .results_path("results.json")
+            .config("Wayland")
+            .script("ejkmer-builder")
+            .results_path("results.json")
+            .library_path("lib")
+            .board("Rpi3", "")
+            .build();
+        let Err(Error::Incomplete(problems)) = result else {
+            panic!("Expected the configuration to be incomplete, got {result:?}");
+        };
+        assert_eq!(
+            problems,
+            vec![
+                "board config `Orphan` added before any board",
+                "`script` called before adding what it sets",
+                "missing `library_path` in board config `Wayland` of board `Rpi3`",
+                "duplicate board config name `Wayland` in board `Rpi3`",
+                "duplicate board name `Rpi3`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Differences between two configurations.
+//!
+//! Builders give new IDs to their boards and board configs every time they
+//! load their configuration, so boards are matched by name and board configs
+//! by name inside their board.
+
+use std::fmt;
+
+use crate::{ej_board::EjBoard, ej_board_config::EjBoardConfig, ej_config::EjConfig};
+
+/// Differences between two configurations, see [`EjConfig::diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EjConfigDiff {
+    /// Whether the global version changed.
+    pub version_changed: bool,
+    /// Names of the boards only in the new configuration.
+    pub added_boards: Vec<String>,
+    /// Names of the boards only in the old configuration.
+    pub removed_boards: Vec<String>,
+    /// Boards in both configurations that changed.
+    pub changed_boards: Vec<EjBoardDiff>,
+}
+
+/// Changes of a board found in both configurations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EjBoardDiff {
+    /// Board name.
+    pub name: String,
+    /// Keys of the board whose value changed, like `description`.
+    pub changed_keys: Vec<&'static str>,
+    /// Names of the board configs only in the new board.
+    pub added_configs: Vec<String>,
+    /// Names of the board configs only in the old board.
+    pub removed_configs: Vec<String>,
+    /// Board configs in both boards that changed.
+    pub changed_configs: Vec<EjBoardConfigDiff>,
+}
+
+/// Changes of a board config found in both boards.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EjBoardConfigDiff {
+    /// Board config name.
+    pub name: String,
+    /// Keys of the board config whose value changed, like `build_script`.
+    pub changed_keys: Vec<&'static str>,
+}
+
+impl EjConfig {
+    /// Compares this configuration with a `new` one.
+    ///
+    /// Only the names of the changed keys are reported, not their values,
+    /// `env` tables may hold secrets.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_config::{EjConfig, EjConfigBuilder};
+    ///
+    /// let board = |results_path: &str| {
+    ///     let config = EjConfigBuilder::new("1.0.0")
+    ///         .board("Rpi3", "Raspberry Pi 3")
+    ///         .config("Wayland")
+    ///         .script("ejkmer-builder")
+    ///         .results_path(results_path)
+    ///         .library_path("kmer")
+    ///         .build()
+    ///         .unwrap();
+    ///     EjConfig::from_user_config(config)
+    /// };
+    ///
+    /// let old = board("results.txt");
+    /// assert!(old.diff(&board("results.txt")).is_empty());
+    ///
+    /// let diff = old.diff(&board("results.json"));
+    /// assert_eq!(diff.changed_boards[0].changed_configs[0].changed_keys, vec!["results_path"]);
+    /// ```
+    pub fn diff(&self, new: &EjConfig) -> EjConfigDiff {
+        let (added_boards, removed_boards, changed_boards) =
+            diff_by_name(&self.boards, &new.boards, |board| &board.name, diff_board);
+        EjConfigDiff {
+            version_changed: self.global.version != new.global.version,
+            added_boards,
+            removed_boards,
+            changed_boards,
+        }
+    }
+}
+
+/// Names of the items only in `new`, only in `old` and the changes of the
+/// items in both, as computed by `diff`.
+fn diff_by_name<T, D>(
+    old: &[T],
+    new: &[T],
+    name: impl Fn(&T) -> &String,
+    diff: impl Fn(&T, &T) -> Option<D>,
+) -> (Vec<String>, Vec<String>, Vec<D>) {
+    let find = |items: &'_ [T], wanted: &String| -> Option<usize> {
+        items.iter().position(|item| name(item) == wanted)
+    };
+    let added = new
+        .iter()
+        .filter(|item| find(old, name(item)).is_none())
+        .map(|item| name(item).clone())
+        .collect();
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    for item in old {
+        match find(new, name(item)) {
+            Some(index) => changed.extend(diff(item, &new[index])),
+            None => removed.push(name(item).clone()),
+        }
+    }
+    (added, removed, changed)
+}
+
+fn diff_board(old: &EjBoard, new: &EjBoard) -> Option<EjBoardDiff> {
+    let mut changed_keys = Vec::new();
+    if old.description != new.description {
+        changed_keys.push("description");
+    }
+    if old.env != new.env {
+        changed_keys.push("env");
+    }
+    let (added_configs, removed_configs, changed_configs) = diff_by_name(
+        &old.configs,
+        &new.configs,
+        |config| &config.name,
+        diff_board_config,
+    );
+    let diff = EjBoardDiff {
+        name: old.name.clone(),
+        changed_keys,
+        added_configs,
+        removed_configs,
+        changed_configs,
+    };
+    let unchanged = diff.changed_keys.is_empty()
+        && diff.added_configs.is_empty()
+        && diff.removed_configs.is_empty()
+        && diff.changed_configs.is_empty();
+    (!unchanged).then_some(diff)
+}
+
+fn diff_board_config(old: &EjBoardConfig, new: &EjBoardConfig) -> Option<EjBoardConfigDiff> {
+    let keys = [
+        ("tags", old.tags != new.tags),
+        ("build_script", old.build_script != new.build_script),
+        ("run_script", old.run_script != new.run_script),
+        ("results_path", old.results_path != new.results_path),
+        ("library_path", old.library_path != new.library_path),
+        ("artifacts", old.artifacts != new.artifacts),
+        ("env", old.env != new.env),
+        ("container", old.container != new.container),
+        ("probe", old.probe != new.probe),
+        ("build_timeout", old.build_timeout != new.build_timeout),
+        ("run_timeout", old.run_timeout != new.run_timeout),
+        ("pre_build", old.pre_build != new.pre_build),
+        ("post_build", old.post_build != new.post_build),
+        ("pre_run", old.pre_run != new.pre_run),
+        ("post_run", old.post_run != new.post_run),
+    ];
+    let changed_keys: Vec<&'static str> = keys
+        .into_iter()
+        .filter_map(|(key, changed)| changed.then_some(key))
+        .collect();
+    (!changed_keys.is_empty()).then(|| EjBoardConfigDiff {
+        name: old.name.clone(),
+        changed_keys,
+    })
+}
+
+impl EjConfigDiff {
+    /// Whether both configurations are the same.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for EjConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        if self.version_changed {
+            writeln!(f, "~ global: version")?;
+        }
+        for name in &self.added_boards {
+            writeln!(f, "+ board {name}")?;
+        }
+        for name in &self.removed_boards {
+            writeln!(f, "- board {name}")?;
+        }
+        for board in &self.changed_boards {
+            write!(f, "~ board {}", board.name)?;
+            if !board.changed_keys.is_empty() {
+                write!(f, ": {}", board.changed_keys.join(", "))?;
+            }
+            writeln!(f)?;
+            for name in &board.added_configs {
+                writeln!(f, "  + config {name}")?;
+            }
+            for name in &board.removed_configs {
+                writeln!(f, "  - config {name}")?;
+            }
+            for config in &board.changed_configs {
+                writeln!(
+                    f,
+                    "  ~ config {}: {}",
+                    config.name,
+                    config.changed_keys.join(", ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EjConfigBuilder, prelude::*};
+
+    #[test]
+    fn diff_reports_added_removed_and_changed() -> Result<()> {
+        let old = EjConfigBuilder::new("1.0.0")
+            .board("Rpi3", "Raspberry Pi 3")
+            .config("Wayland")
+            .tags(["arm64"])
+            .script("ejkmer-builder")
+            .results_path("results.txt")
+            .library_path("kmer")
+            .config("X11")
+            .script("ejkmer-builder")
+            .results_path("results.txt")
+            .library_path("kmer")
+            .board("Old desktop", "")
+            .config("Default")
+            .script("ejkmer-builder")
+            .results_path("results.txt")
+            .library_path("kmer")
+            .build()?;
+        let new = EjConfigBuilder::new("1.0.0")
+            .board("Rpi3", "Raspberry Pi 3 Model B+")
+            .config("Wayland")
+            .tags(["arm64", "wayland"])
+            .script("ejkmer-builder")
+            .results_path("results.txt")
+            .library_path("kmer")
+            .env("CC", "clang")
+            .config("SDL")
+            .script("ejkmer-builder")
+            .results_path("results.txt")
+            .library_path("kmer")
+            .board("Rpi4", "")
+            .config("Default")
+            .script("ejkmer-builder")
+            .results_path("results.txt")
+            .library_path("kmer")
+            .build()?;
+
+        let diff = EjConfig::from_user_config(old).diff(&EjConfig::from_user_config(new));
+        assert_eq!(
+            diff.to_string(),
+            "+ board Rpi4\n\
+             - board Old desktop\n\
+             ~ board Rpi3: description\n\
+             \x20 + config SDL\n\
+             \x20 - config X11\n\
+             \x20 ~ config Wayland: tags, env\n"
+        );
+        Ok(())
+    }
+}
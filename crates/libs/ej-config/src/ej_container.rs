@@ -0,0 +1,18 @@
+//! Container configuration types.
+
+use serde::{Deserialize, Serialize};
+
+/// Container the build and run scripts of a board configuration run in.
+///
+/// Builders run the scripts inside a container of `image` with Docker or
+/// Podman, so the board configuration builds with the toolchain of the image
+/// rather than the one installed on the builder.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjContainerConfig {
+    /// Image the container is created from, e.g. `ghcr.io/user/toolchain:1.0`.
+    pub image: String,
+    /// Devices of the builder made available in the container, e.g. `/dev/ttyUSB0`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<String>,
+}
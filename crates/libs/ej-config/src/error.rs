@@ -1,5 +1,12 @@
 //! Configuration error types.
 
+use std::path::PathBuf;
+
+use crate::{
+    migration::{CURRENT_CONFIG_VERSION, OLDEST_CONFIG_VERSION},
+    validation::EjConfigIssue,
+};
+
 /// Configuration errors.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -11,7 +18,52 @@ pub enum Error {
     #[error(transparent)]
     Deserialization(#[from] toml::de::Error),
 
+    /// YAML deserialization failed.
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+
+    /// JSON deserialization failed.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// Configuration file is invalid.
+    #[error("invalid configuration file {}:\n{}", .path.display(), format_issues(.issues))]
+    Invalid {
+        /// Path of the configuration file.
+        path: PathBuf,
+        /// Every problem found in the file and the fragments it includes.
+        issues: Vec<EjConfigIssue>,
+    },
+
+    /// Configuration is written in a format version this release can't load.
+    #[error(
+        "unsupported configuration version {0}, expected {OLDEST_CONFIG_VERSION} to {CURRENT_CONFIG_VERSION}"
+    )]
+    UnsupportedVersion(u32),
+
+    /// Configuration built in code is incomplete or inconsistent.
+    #[error("invalid configuration:\n{}", format_problems(.0))]
+    Incomplete(Vec<String>),
+
     /// TOML serialization failed.
     #[error(transparent)]
     Serialization(#[from] toml::ser::Error),
+
+    /// Secret reference can't be read.
+    #[error("{0}")]
+    UnresolvedSecret(String),
+}
+
+/// One `path:line:column: message` line per issue.
+fn format_issues(issues: &[EjConfigIssue]) -> String {
+    format_problems(issues)
+}
+
+/// One indented line per problem.
+fn format_problems(problems: &[impl std::fmt::Display]) -> String {
+    problems
+        .iter()
+        .map(|problem| format!("  {problem}"))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
@@ -0,0 +1,108 @@
+//! Configuration file formats.
+//!
+//! Configurations can be written in TOML, YAML or JSON, which is handy when
+//! they're generated by another program. Every format describes the same
+//! configuration with the same keys and results in the same [`EjUserConfig`].
+//!
+//! [`EjUserConfig`]: crate::EjUserConfig
+
+use std::{fmt, path::Path};
+
+use serde::de::DeserializeOwned;
+
+use crate::{document::Position, prelude::*};
+
+/// Format a configuration is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EjConfigFormat {
+    /// TOML, the default.
+    #[default]
+    Toml,
+    /// YAML.
+    Yaml,
+    /// JSON.
+    Json,
+}
+
+impl EjConfigFormat {
+    /// Detects the format of a file from its extension.
+    ///
+    /// `.yaml` and `.yml` files are YAML, `.json` files are JSON and every
+    /// other file is TOML.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_config::format::EjConfigFormat;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(EjConfigFormat::from_path(Path::new("lab.yml")), EjConfigFormat::Yaml);
+    /// assert_eq!(EjConfigFormat::from_path(Path::new("lab.JSON")), EjConfigFormat::Json);
+    /// assert_eq!(EjConfigFormat::from_path(Path::new("config.toml")), EjConfigFormat::Toml);
+    /// ```
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("yaml" | "yml") => Self::Yaml,
+            Some("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+
+    /// Deserializes `source`.
+    pub(crate) fn deserialize<T: DeserializeOwned>(self, source: &str) -> Result<T> {
+        Ok(match self {
+            Self::Toml => toml::from_str(source)?,
+            Self::Yaml => serde_yaml::from_str(source)?,
+            Self::Json => serde_json::from_str(source)?,
+        })
+    }
+
+    /// Deserializes `source`, returning where the error is, if known, and
+    /// what it is on failure.
+    pub(crate) fn deserialize_located<T: DeserializeOwned>(
+        self,
+        source: &str,
+    ) -> std::result::Result<T, (Option<Position>, String)> {
+        match self {
+            Self::Toml => toml::from_str(source).map_err(|err| {
+                let position = err
+                    .span()
+                    .map(|span| crate::document::position(source, span.start));
+                (position, err.message().trim().to_string())
+            }),
+            Self::Yaml => serde_yaml::from_str(source).map_err(|err| {
+                let position = err
+                    .location()
+                    .map(|location| (location.line(), location.column()));
+                (position, without_location(err.to_string()))
+            }),
+            Self::Json => serde_json::from_str(source).map_err(|err| {
+                let position = (err.line() > 0).then(|| (err.line(), err.column().max(1)));
+                (position, without_location(err.to_string()))
+            }),
+        }
+    }
+}
+
+impl fmt::Display for EjConfigFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Toml => write!(f, "TOML"),
+            Self::Yaml => write!(f, "YAML"),
+            Self::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+/// Removes the ` at line X column Y` YAML and JSON errors end with, issues
+/// carry their position already.
+pub(crate) fn without_location(message: String) -> String {
+    match message.find(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message,
+    }
+}
@@ -0,0 +1,101 @@
+//! File name wildcards.
+//!
+//! Patterns such as `build/*.bin` select files with `*` matching any
+//! characters and `?` a single one. Wildcards are only supported in the file
+//! name, not in the directories leading to it.
+
+use std::path::{Path, PathBuf};
+
+/// The files matching `pattern`, sorted by path.
+///
+/// `*` and `?` wildcards are supported in the file name only, a pattern
+/// without them is returned as is.
+pub fn expand(pattern: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let Some(name) = pattern.file_name().and_then(|name| name.to_str()) else {
+        return Ok(vec![pattern.to_path_buf()]);
+    };
+    if !name.contains(['*', '?']) {
+        return Ok(vec![pattern.to_path_buf()]);
+    }
+    let name: Vec<char> = name.chars().collect();
+    let directory = match pattern.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        let file_name: Vec<char> = entry.file_name().to_string_lossy().chars().collect();
+        if entry.file_type()?.is_file() && wildcard_matches(&name, &file_name) {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// The directory the files matching `pattern` are searched in, its leading
+/// components without wildcards.
+///
+/// Paths of the matches relative to it are unique, even once wildcards are
+/// supported in directories.
+pub fn root(pattern: &Path) -> &Path {
+    let has_wildcards = |path: &Path| {
+        path.components()
+            .any(|component| component.as_os_str().to_string_lossy().contains(['*', '?']))
+    };
+    let mut root = pattern.parent().unwrap_or(Path::new(""));
+    while has_wildcards(root) {
+        root = root.parent().unwrap_or(Path::new(""));
+    }
+    root
+}
+
+/// Whether `name` matches `pattern`, `*` matching any characters and `?` a single one.
+fn wildcard_matches(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            wildcard_matches(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_matches(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => wildcard_matches(&pattern[1..], &name[1..]),
+        (Some(expected), Some(found)) if expected == found => {
+            wildcard_matches(&pattern[1..], &name[1..])
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        wildcard_matches(&pattern, &name)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*.toml", "rpi3.toml"));
+        assert!(matches("*.toml", ".toml"));
+        assert!(matches("rpi?.toml", "rpi4.toml"));
+        assert!(matches("*", "anything"));
+        assert!(!matches("*.toml", "rpi3.toml.bak"));
+        assert!(!matches("rpi?.toml", "rpi.toml"));
+        assert!(!matches("boards.toml", "board.toml"));
+    }
+
+    #[test]
+    fn roots() {
+        assert_eq!(root(Path::new("/lib/build/*.bin")), Path::new("/lib/build"));
+        assert_eq!(
+            root(Path::new("/lib/build/app.bin")),
+            Path::new("/lib/build")
+        );
+        assert_eq!(root(Path::new("/lib/out-?/*.bin")), Path::new("/lib"));
+        assert_eq!(root(Path::new("*.bin")), Path::new(""));
+    }
+}
@@ -8,17 +8,29 @@
 //! use ej_config::{EjUserConfig, EjConfig};
 //! use std::path::Path;
 //!
-//! // Load user configuration from TOML file
-//! let user_config = EjUserConfig::from_file(Path::new("../../../examples/config.toml")).unwrap();
+//! // Parse user configuration from TOML, `EjUserConfig::from_file` also
+//! // checks that the build and run scripts exist
+//! let content = std::fs::read_to_string(Path::new("../../../examples/config.toml")).unwrap();
+//! let user_config = EjUserConfig::from_toml(&content).unwrap();
 //!
 //! // Convert to internal configuration
 //! let config = EjConfig::from_user_config(user_config);
 //! ```
 
+mod document;
 pub mod ej_board;
 pub mod ej_board_config;
 pub mod ej_config;
+pub mod ej_config_builder;
+pub mod ej_config_diff;
+pub mod ej_container;
 pub mod error;
+pub mod format;
+pub mod glob;
+pub mod migration;
 pub mod prelude;
+pub mod secret;
+pub mod validation;
 
 pub use ej_config::{EjConfig, EjUserConfig};
+pub use ej_config_builder::EjConfigBuilder;
@@ -0,0 +1,134 @@
+//! Older configuration formats and their migration to the current one.
+//!
+//! A configuration file sets the format it's written in with its top-level
+//! `version` key, files without one being written in version 1. Each older
+//! format keeps its own parser and is migrated to the current one when
+//! loaded, with a deprecation warning, so existing files keep working after
+//! upgrading the builder.
+//!
+//! # Versions
+//!
+//! * 1 - Board configs may repeat the name of their board in a `board` key.
+//! * 2 - A board config belongs to the board it's listed in, `board` is gone.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{
+    ej_board::EjUserBoard,
+    ej_board_config::EjUserBoardConfig,
+    ej_config::{EjGlobalConfig, EjUserConfig},
+    format::EjConfigFormat,
+    prelude::*,
+    secret::EjEnvValue,
+};
+
+/// Version of the configuration format written by this release.
+pub const CURRENT_CONFIG_VERSION: u32 = 2;
+
+/// Version of the configuration files without a `version` key.
+pub const LEGACY_CONFIG_VERSION: u32 = 1;
+
+/// Oldest configuration format that can still be loaded.
+pub const OLDEST_CONFIG_VERSION: u32 = LEGACY_CONFIG_VERSION;
+
+/// The `version` key of a configuration file.
+#[derive(Deserialize)]
+struct Version {
+    version: Option<u32>,
+}
+
+/// A configuration file with the boards of a given format version.
+#[derive(Deserialize)]
+pub(crate) struct EjVersionedConfig<B> {
+    pub global: EjGlobalConfig,
+    pub boards: Vec<B>,
+}
+
+/// Board of a version 1 configuration.
+#[derive(Deserialize)]
+pub(crate) struct EjUserBoardV1 {
+    name: String,
+    description: String,
+    #[serde(default, deserialize_with = "crate::secret::deserialize_env")]
+    env: BTreeMap<String, EjEnvValue>,
+    configs: Vec<EjUserBoardConfigV1>,
+}
+
+/// Board config of a version 1 configuration.
+#[derive(Deserialize)]
+pub(crate) struct EjUserBoardConfigV1 {
+    /// Name of the board the config belongs to, deprecated.
+    #[serde(default)]
+    board: Option<String>,
+    #[serde(flatten)]
+    config: EjUserBoardConfig,
+}
+
+/// Whether configurations written in `version` can be loaded.
+pub fn is_supported(version: u32) -> bool {
+    (OLDEST_CONFIG_VERSION..=CURRENT_CONFIG_VERSION).contains(&version)
+}
+
+/// Parses a configuration written in any supported format version.
+///
+/// Returns `Error::UnsupportedVersion` if the configuration is written in a
+/// format version this release doesn't know about.
+pub(crate) fn parse(source: &str, format: EjConfigFormat) -> Result<EjUserConfig> {
+    let version = format
+        .deserialize::<Version>(source)?
+        .version
+        .unwrap_or(LEGACY_CONFIG_VERSION);
+    match version {
+        LEGACY_CONFIG_VERSION => Ok(migrate_v1(format.deserialize(source)?)),
+        CURRENT_CONFIG_VERSION => Ok(current(format.deserialize(source)?)),
+        version => Err(Error::UnsupportedVersion(version)),
+    }
+}
+
+/// Converts a configuration written in the current format.
+pub(crate) fn current(config: EjVersionedConfig<EjUserBoard>) -> EjUserConfig {
+    EjUserConfig {
+        version: CURRENT_CONFIG_VERSION,
+        global: config.global,
+        boards: config.boards,
+    }
+}
+
+/// Migrates a version 1 configuration to the current format.
+pub(crate) fn migrate_v1(config: EjVersionedConfig<EjUserBoardV1>) -> EjUserConfig {
+    warn!(
+        "Configuration version {LEGACY_CONFIG_VERSION} is deprecated, \
+        add `version = {CURRENT_CONFIG_VERSION}` at the top of the file to use the current format"
+    );
+    let boards = config
+        .boards
+        .into_iter()
+        .map(|board| EjUserBoard {
+            configs: board
+                .configs
+                .into_iter()
+                .map(|board_config| {
+                    if let Some(name) = board_config.board {
+                        warn!(
+                            "`board = \"{name}\"` in board config `{}` is deprecated and ignored, \
+                            board configs belong to the board they're listed in",
+                            board_config.config.name
+                        );
+                    }
+                    board_config.config
+                })
+                .collect(),
+            name: board.name,
+            description: board.description,
+            env: board.env,
+        })
+        .collect();
+    EjUserConfig {
+        version: CURRENT_CONFIG_VERSION,
+        global: config.global,
+        boards,
+    }
+}
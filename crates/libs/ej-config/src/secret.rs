@@ -0,0 +1,168 @@
+//! Secret references.
+//!
+//! Tokens, passwords and other secrets shouldn't be written in configuration
+//! files. Instead of a string, the values of the board and board config `env`
+//! tables can reference where the secret is read from:
+//!
+//! ```toml
+//! env = { TOKEN = { from_env = "RPI_TOKEN" }, KEY = { from_file = "/run/secrets/key" } }
+//! ```
+//!
+//! References are checked when the configuration is loaded, a variable that
+//! isn't set or a file that can't be read being reported like any other
+//! invalid value. They're only read again when the environment of the scripts
+//! is built, the configuration itself keeps the references so the secrets
+//! never leave the builder when it uploads its configuration. Relative
+//! `from_file` paths are read from the current directory and the trailing
+//! newline of the file is dropped.
+
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{self, MapAccess, Visitor},
+};
+
+use crate::prelude::*;
+
+/// Keys of a secret reference.
+const REFERENCE_KEYS: &[&str] = &["from_env", "from_file"];
+
+/// Where a secret is read from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum EjSecretRef {
+    /// Environment variable of the builder.
+    FromEnv(String),
+    /// File of the builder.
+    #[cfg_attr(feature = "utoipa", schema(value_type = String))]
+    FromFile(PathBuf),
+}
+
+impl EjSecretRef {
+    /// Reads the secret, returning why it can't be read on failure.
+    fn resolve(&self) -> std::result::Result<String, String> {
+        match self {
+            Self::FromEnv(name) => std::env::var(name).map_err(|err| {
+                format!("can't read secret from environment variable `{name}`: {err}")
+            }),
+            Self::FromFile(path) => std::fs::read_to_string(path)
+                .map(|secret| secret.trim_end_matches(['\n', '\r']).to_string())
+                .map_err(|err| format!("can't read secret from `{}`: {err}", path.display())),
+        }
+    }
+}
+
+/// A value of an `env` table, written as is or referencing a secret.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(untagged)]
+pub enum EjEnvValue {
+    /// Value written in the configuration.
+    Value(String),
+    /// Secret read by the builder when it runs the scripts.
+    Secret(EjSecretRef),
+}
+
+impl EjEnvValue {
+    /// The value, reading it if it's a secret reference.
+    pub fn resolve(&self) -> Result<String> {
+        match self {
+            Self::Value(value) => Ok(value.clone()),
+            Self::Secret(reference) => reference.resolve().map_err(Error::UnresolvedSecret),
+        }
+    }
+}
+
+impl From<String> for EjEnvValue {
+    fn from(value: String) -> Self {
+        Self::Value(value)
+    }
+}
+
+impl From<&str> for EjEnvValue {
+    fn from(value: &str) -> Self {
+        Self::Value(value.to_string())
+    }
+}
+
+/// Reads every value of an `env` table, reading its secret references.
+pub fn resolve_env(env: &BTreeMap<String, EjEnvValue>) -> Result<BTreeMap<String, String>> {
+    env.iter()
+        .map(|(name, value)| Ok((name.clone(), value.resolve()?)))
+        .collect()
+}
+
+struct EnvValueVisitor {
+    /// Whether secret references must be readable.
+    check: bool,
+}
+
+impl<'de> Visitor<'de> for EnvValueVisitor {
+    type Value = EjEnvValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string or a secret reference like `{ from_env = \"NAME\" }`")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<Self::Value, E> {
+        Ok(EjEnvValue::Value(value.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> std::result::Result<Self::Value, E> {
+        Ok(EjEnvValue::Value(value))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(
+        self,
+        mut map: A,
+    ) -> std::result::Result<Self::Value, A::Error> {
+        let Some(key) = map.next_key::<String>()? else {
+            return Err(de::Error::invalid_length(0, &self));
+        };
+        let reference = match key.as_str() {
+            "from_env" => EjSecretRef::FromEnv(map.next_value()?),
+            "from_file" => EjSecretRef::FromFile(map.next_value()?),
+            _ => return Err(de::Error::unknown_field(&key, REFERENCE_KEYS)),
+        };
+        if map.next_key::<String>()?.is_some() {
+            return Err(de::Error::custom(
+                "a secret reference has a single key, `from_env` or `from_file`",
+            ));
+        }
+        if self.check {
+            reference.resolve().map_err(de::Error::custom)?;
+        }
+        Ok(EjEnvValue::Secret(reference))
+    }
+}
+
+impl<'de> Deserialize<'de> for EjEnvValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(EnvValueVisitor { check: false })
+    }
+}
+
+/// A value of an `env` table whose secret reference must be readable.
+struct CheckedEnvValue(EjEnvValue);
+
+impl<'de> Deserialize<'de> for CheckedEnvValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer
+            .deserialize_any(EnvValueVisitor { check: true })
+            .map(CheckedEnvValue)
+    }
+}
+
+/// Deserializes an `env` table of a user configuration, checking its secret
+/// references can be read.
+pub(crate) fn deserialize_env<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<BTreeMap<String, EjEnvValue>, D::Error> {
+    let env = BTreeMap::<String, CheckedEnvValue>::deserialize(deserializer)?;
+    Ok(env
+        .into_iter()
+        .map(|(name, CheckedEnvValue(value))| (name, value))
+        .collect())
+}
@@ -0,0 +1,431 @@
+//! Configuration file validation.
+//!
+//! Deserializing a configuration only reports the first problem serde runs
+//! into, and some of them, like missing fields, without saying where. The
+//! validation walks the parsed document instead so every problem is reported
+//! along with the file and line it's on, whatever the [format] of the file.
+//!
+//! A configuration can include fragment files with more boards, listed in its
+//! `include` key. Fragments are validated the same way and their boards are
+//! appended to the configuration ones. Fragments are written in the format
+//! version of the configuration including them, each in the format matching
+//! its extension.
+//!
+//! [format]: crate::format::EjConfigFormat
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, de::DeserializeOwned};
+
+use crate::{
+    document::{self, Node, Position},
+    ej_config::EjUserConfig,
+    format::EjConfigFormat,
+    glob,
+    migration::{
+        self, CURRENT_CONFIG_VERSION, EjVersionedConfig, LEGACY_CONFIG_VERSION,
+        OLDEST_CONFIG_VERSION,
+    },
+};
+
+/// Keys of a configuration table and whether each of them is required.
+type Fields = &'static [(&'static str, bool)];
+
+const ROOT_FIELDS: Fields = &[
+    ("version", false),
+    ("global", true),
+    ("boards", true),
+    ("include", false),
+];
+const FRAGMENT_FIELDS: Fields = &[("boards", true)];
+const GLOBAL_FIELDS: Fields = &[("version", true)];
+const BOARD_FIELDS: Fields = &[
+    ("name", true),
+    ("description", true),
+    ("env", false),
+    ("configs", true),
+];
+const BOARD_CONFIG_FIELDS: Fields = &[
+    ("name", true),
+    ("tags", true),
+    ("build_script", true),
+    ("run_script", true),
+    ("results_path", true),
+    ("library_path", true),
+    ("artifacts", false),
+    ("env", false),
+    ("container", false),
+    ("probe", false),
+    ("build_timeout", false),
+    ("run_timeout", false),
+    ("pre_build", false),
+    ("post_build", false),
+    ("pre_run", false),
+    ("post_run", false),
+];
+const CONTAINER_FIELDS: Fields = &[("image", true), ("devices", false)];
+/// Version 1 board configs may also name their board.
+const BOARD_CONFIG_FIELDS_V1: Fields = &[
+    ("board", false),
+    ("name", true),
+    ("tags", true),
+    ("build_script", true),
+    ("run_script", true),
+    ("results_path", true),
+    ("library_path", true),
+    ("artifacts", false),
+    ("env", false),
+    ("container", false),
+    ("probe", false),
+    ("build_timeout", false),
+    ("run_timeout", false),
+    ("pre_build", false),
+    ("post_build", false),
+    ("pre_run", false),
+    ("post_run", false),
+];
+
+/// A problem found in a configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EjConfigIssue {
+    /// Path of the file the problem is in, the configuration or one of its fragments.
+    pub path: PathBuf,
+    /// Line of the problem, starting at 1.
+    pub line: usize,
+    /// Column of the problem, starting at 1.
+    pub column: usize,
+    /// What's wrong.
+    pub message: String,
+}
+
+impl fmt::Display for EjConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.path.display(),
+            self.line,
+            self.column,
+            self.message
+        )
+    }
+}
+
+/// Boards defined in a fragment file.
+#[derive(Deserialize)]
+struct EjUserConfigFragment<B> {
+    boards: Vec<B>,
+}
+
+/// A configuration file, the main one or a fragment.
+struct File {
+    path: PathBuf,
+    source: String,
+    format: EjConfigFormat,
+}
+
+impl File {
+    fn new(path: PathBuf, source: String) -> Self {
+        let format = EjConfigFormat::from_path(&path);
+        Self {
+            path,
+            source,
+            format,
+        }
+    }
+}
+
+/// Where each name was first defined.
+type Definitions = HashMap<String, (PathBuf, usize)>;
+
+/// Collects the problems found in the configuration files.
+#[derive(Default)]
+struct Validator {
+    issues: Vec<EjConfigIssue>,
+}
+
+impl Validator {
+    fn report(&mut self, file: &File, (line, column): Position, message: String) {
+        self.issues.push(EjConfigIssue {
+            path: file.path.clone(),
+            line,
+            column,
+            message,
+        });
+    }
+
+    /// Parses `file`, reporting syntax errors.
+    fn parse(&mut self, file: &File) -> Option<Node> {
+        document::parse(&file.source, file.format)
+            .map_err(|(position, message)| self.report(file, position, message))
+            .ok()
+    }
+
+    /// Deserializes `file`, reporting type errors.
+    fn deserialize<T: DeserializeOwned>(&mut self, file: &File) -> Option<T> {
+        file.format
+            .deserialize_located(&file.source)
+            .map_err(|(position, message)| self.report(file, position.unwrap_or((1, 1)), message))
+            .ok()
+    }
+
+    /// Reads the format version of `document`, reporting unsupported ones.
+    fn check_version(&mut self, file: &File, document: &Node) -> u32 {
+        let Some(node) = document.get("version") else {
+            return LEGACY_CONFIG_VERSION;
+        };
+        match node
+            .as_integer()
+            .and_then(|version| u32::try_from(version).ok())
+        {
+            Some(version) if migration::is_supported(version) => version,
+            _ => {
+                self.report(
+                    file,
+                    node.position,
+                    format!(
+                        "unsupported configuration version `{}`, expected \
+                        {OLDEST_CONFIG_VERSION} to {CURRENT_CONFIG_VERSION}",
+                        node.written()
+                    ),
+                );
+                CURRENT_CONFIG_VERSION
+            }
+        }
+    }
+
+    /// Deserializes the configuration and its fragments, with the boards of a
+    /// format version, reporting type errors.
+    fn deserialize_config<B: DeserializeOwned>(
+        &mut self,
+        main: &File,
+        fragments: &[File],
+    ) -> Option<EjVersionedConfig<B>> {
+        let mut config: EjVersionedConfig<B> = self.deserialize(main)?;
+        for fragment in fragments {
+            if let Some(fragment) = self.deserialize::<EjUserConfigFragment<B>>(fragment) {
+                config.boards.extend(fragment.boards);
+            }
+        }
+        self.issues.is_empty().then_some(config)
+    }
+
+    /// Reports the unknown and missing keys of `table`, named `section` in messages.
+    fn check_fields(&mut self, file: &File, table: &Node, fields: Fields, section: &str) {
+        for entry in table.entries() {
+            if !fields.iter().any(|(name, _)| *name == entry.key) {
+                let expected: Vec<String> =
+                    fields.iter().map(|(name, _)| format!("`{name}`")).collect();
+                self.report(
+                    file,
+                    entry.position,
+                    format!(
+                        "unknown key `{}` in {section}, expected one of {}",
+                        entry.key,
+                        expected.join(", ")
+                    ),
+                );
+            }
+        }
+        for (name, required) in fields {
+            if *required && table.get(name).is_none() {
+                self.report(
+                    file,
+                    table.position,
+                    format!("missing required key `{name}` in {section}"),
+                );
+            }
+        }
+    }
+
+    /// Reports the names of `tables` already in `seen` and adds the new ones.
+    fn check_unique_names(
+        &mut self,
+        file: &File,
+        tables: &[&Node],
+        kind: &str,
+        seen: &mut Definitions,
+    ) {
+        for table in tables {
+            let Some(node) = table.get("name") else {
+                continue;
+            };
+            let Some(name) = node.as_str() else {
+                continue;
+            };
+            let message = match seen.get(name) {
+                Some((path, line)) if *path == file.path => {
+                    format!("duplicate {kind} name `{name}`, first defined on line {line}")
+                }
+                Some((path, line)) => format!(
+                    "duplicate {kind} name `{name}`, first defined in {} on line {line}",
+                    path.display()
+                ),
+                None => {
+                    seen.insert(name.to_string(), (file.path.clone(), node.position.0));
+                    continue;
+                }
+            };
+            self.report(file, node.position, message);
+        }
+    }
+
+    /// Reports the scripts of a board config that don't exist.
+    fn check_scripts(&mut self, file: &File, table: &Node) {
+        let keys = [
+            "build_script",
+            "run_script",
+            "pre_build",
+            "post_build",
+            "pre_run",
+            "post_run",
+        ];
+        for key in keys {
+            let Some(node) = table.get(key) else {
+                continue;
+            };
+            let Some(script) = node.as_str() else {
+                continue;
+            };
+            if !Path::new(script).exists() {
+                self.report(
+                    file,
+                    node.position,
+                    format!("`{key}` path `{script}` doesn't exist"),
+                );
+            }
+        }
+    }
+
+    /// Checks the boards of `document`, written in format `version`, `boards`
+    /// holding the names of the boards of the files checked before.
+    fn check_boards(
+        &mut self,
+        file: &File,
+        document: &Node,
+        version: u32,
+        boards: &mut Definitions,
+    ) {
+        let board_config_fields = match version {
+            LEGACY_CONFIG_VERSION => BOARD_CONFIG_FIELDS_V1,
+            _ => BOARD_CONFIG_FIELDS,
+        };
+        let board_tables = document.get("boards").map(Node::tables).unwrap_or_default();
+        self.check_unique_names(file, &board_tables, "board", boards);
+        for board in &board_tables {
+            self.check_fields(file, board, BOARD_FIELDS, "board");
+            let configs = board.get("configs").map(Node::tables).unwrap_or_default();
+            self.check_unique_names(file, &configs, "board config", &mut Definitions::new());
+            for config in &configs {
+                self.check_fields(file, config, board_config_fields, "board config");
+                self.check_scripts(file, config);
+                if let Some(container) = config.get("container")
+                    && container.is_table()
+                {
+                    self.check_fields(file, container, CONTAINER_FIELDS, "container");
+                }
+            }
+        }
+    }
+
+    /// Reads the fragment files included by `file`.
+    ///
+    /// Relative paths are resolved from the directory of `file`.
+    fn read_includes(&mut self, file: &File, document: &Node) -> Vec<File> {
+        let Some(include) = document.get("include") else {
+            return Vec::new();
+        };
+        let Some(patterns) = include.as_array() else {
+            let message = String::from("`include` must be an array of file paths");
+            self.report(file, include.position, message);
+            return Vec::new();
+        };
+        let directory = file.path.parent().unwrap_or(Path::new(""));
+        let mut fragments: Vec<File> = Vec::new();
+        for pattern in patterns {
+            let Some(pattern_str) = pattern.as_str() else {
+                let message = String::from("`include` entries must be file paths");
+                self.report(file, pattern.position, message);
+                continue;
+            };
+            let paths = match glob::expand(&directory.join(pattern_str)) {
+                Ok(paths) => paths,
+                Err(err) => {
+                    let message = format!("can't expand `{pattern_str}`: {err}");
+                    self.report(file, pattern.position, message);
+                    continue;
+                }
+            };
+            for path in paths {
+                if fragments.iter().any(|fragment| fragment.path == path) {
+                    continue;
+                }
+                match std::fs::read_to_string(&path) {
+                    Ok(source) => fragments.push(File::new(path, source)),
+                    Err(err) => {
+                        let message = format!("can't read `{}`: {err}", path.display());
+                        self.report(file, pattern.position, message);
+                    }
+                }
+            }
+        }
+        fragments
+    }
+}
+
+/// Parses and validates a configuration file and the fragments it includes.
+///
+/// On top of the checks done when deserializing, reports unknown keys,
+/// duplicate board names across files, duplicate config names inside a board
+/// and build or run scripts that don't exist. Relative script paths are
+/// checked from the current directory, the one the builder runs them from.
+///
+/// Configurations written in an older format version are migrated to the
+/// current one.
+pub(crate) fn load(path: &Path, source: String) -> Result<EjUserConfig, Vec<EjConfigIssue>> {
+    let mut validator = Validator::default();
+    let main = File::new(path.to_path_buf(), source);
+    let Some(document) = validator.parse(&main) else {
+        return Err(validator.issues);
+    };
+
+    let mut boards = Definitions::new();
+    validator.check_fields(&main, &document, ROOT_FIELDS, "the configuration");
+    let version = validator.check_version(&main, &document);
+    if let Some(global) = document.get("global").filter(|global| global.is_table()) {
+        validator.check_fields(&main, global, GLOBAL_FIELDS, "`global`");
+    }
+    validator.check_boards(&main, &document, version, &mut boards);
+
+    let fragments = validator.read_includes(&main, &document);
+    for fragment in &fragments {
+        let Some(document) = validator.parse(fragment) else {
+            continue;
+        };
+        validator.check_fields(fragment, &document, FRAGMENT_FIELDS, "the fragment");
+        validator.check_boards(fragment, &document, version, &mut boards);
+    }
+
+    if validator.issues.is_empty() {
+        let config = match version {
+            LEGACY_CONFIG_VERSION => validator
+                .deserialize_config(&main, &fragments)
+                .map(migration::migrate_v1),
+            _ => validator
+                .deserialize_config(&main, &fragments)
+                .map(migration::current),
+        };
+        if let Some(config) = config {
+            return Ok(config);
+        }
+    }
+    // Each file issues are reported together, keep the files order
+    for issues in validator.issues.chunk_by_mut(|a, b| a.path == b.path) {
+        issues.sort_by_key(|issue| (issue.line, issue.column));
+    }
+    Err(validator.issues)
+}
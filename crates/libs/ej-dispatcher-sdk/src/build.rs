@@ -1,17 +1,11 @@
 //! Build job dispatch and management.
 
-use std::{fmt, path::Path, time::Duration};
-use tracing::{error, info};
-
-use tokio::{
-    io::{AsyncBufReadExt, BufReader},
-    net::UnixStream,
-};
+use futures_util::{Stream, StreamExt};
+use std::{fmt, pin::pin, time::Duration};
 
 use crate::{
-    dispatch,
-    ejjob::{EjBuildResult, EjJobUpdate},
-    ejsocket_message::EjSocketServerMessage,
+    connection::{DispatchConnection, EjDispatcherAddress},
+    ejjob::{EjBuildResult, EjJobTarget, EjJobUpdate, timeouts::EjDispatchTimeouts},
 };
 use crate::{
     ejjob::{EjJob, EjJobType},
@@ -20,15 +14,17 @@ use crate::{
 
 /// Dispatch a build job to the dispatcher.
 ///
-/// Creates a build-only job and sends it to the dispatcher via Unix socket.
+/// Creates a build-only job and sends it to the dispatcher via its Unix socket
+/// or its API.
 ///
 /// # Arguments
 ///
-/// * `socket_path` - Path to the dispatcher Unix socket
+/// * `dispatcher` - Path to the dispatcher Unix socket or [`EjRemoteDispatcher`](crate::connection::EjRemoteDispatcher)
 /// * `commit_hash` - Git commit hash to build
 /// * `remote_url` - Git repository URL
 /// * `remote_token` - Optional authentication token for private repos
-/// * `max_duration` - Maximum time to wait for build completion
+/// * `timeouts` - Maximum time to wait for build completion, or [`EjDispatchTimeouts`]
+///   to also limit the build of each board config
 ///
 /// # Examples
 ///
@@ -51,50 +47,150 @@ use crate::{
 /// # });
 /// ```
 pub async fn dispatch_build(
-    socket_path: &Path,
+    dispatcher: impl Into<EjDispatcherAddress>,
     commit_hash: String,
     remote_url: String,
     remote_token: Option<String>,
-    max_duration: Duration,
+    timeouts: impl Into<EjDispatchTimeouts>,
 ) -> Result<EjBuildResult> {
-    let mut stream = UnixStream::connect(socket_path).await?;
-
-    let job = EjJob {
-        job_type: EjJobType::Build,
-        commit_hash: commit_hash,
-        remote_url: remote_url,
-        remote_token: remote_token,
-    };
-
-    let lines = dispatch(&mut stream, job, max_duration).await?;
-
-    let mut reader = BufReader::new(stream);
-    let mut lines = reader.lines();
-
-    while let Some(line) = lines.next_line().await? {
-        match serde_json::from_str::<EjSocketServerMessage>(&line) {
-            Ok(message) => {
-                info!("{}", message);
-                match message {
-                    EjSocketServerMessage::JobUpdate(update) => match update {
-                        EjJobUpdate::BuildFinished(build_result) => return Ok(build_result),
-                        _ => continue,
-                    },
-                    _ => continue,
-                }
-            }
-            Err(e) => {
-                error!("Failed to parse message {} - {}", line, e);
-            }
+    dispatch_build_with_updates(
+        dispatcher,
+        commit_hash,
+        remote_url,
+        remote_token,
+        EjJobTarget::default(),
+        timeouts,
+        |_| {},
+    )
+    .await
+}
+
+/// Same as [`dispatch_build`] but calls `on_update` for every job update received while
+/// waiting for the job to finish.
+///
+/// This can be used to follow the job's progress, for instance to print the
+/// `EjJobUpdate::LogChunk` updates as builders produce output.
+///
+/// Only the board configs `target` selects run the job, the others are
+/// reported as skipped in the result.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_dispatcher_sdk::{EjJobTarget, EjJobUpdate, dispatch_build_with_updates};
+/// use std::{path::Path, time::Duration};
+///
+/// # tokio_test::block_on(async {
+/// let job_result = dispatch_build_with_updates(
+///     Path::new("/tmp/dispatcher.sock"),
+///     "abc123".to_string(),
+///     "https://github.com/user/repo.git".to_string(),
+///     None,
+///     EjJobTarget {
+///         required_tags: vec![String::from("arm64")],
+///         ..Default::default()
+///     },
+///     Duration::from_secs(600),
+///     |update| {
+///         if let EjJobUpdate::LogChunk { board_config, lines } = update {
+///             for line in lines.iter().flat_map(|chunk| chunk.lines()) {
+///                 println!("[{}] {}", board_config.name, line);
+///             }
+///         }
+///     },
+/// ).await.unwrap();
+/// # });
+/// ```
+pub async fn dispatch_build_with_updates(
+    dispatcher: impl Into<EjDispatcherAddress>,
+    commit_hash: String,
+    remote_url: String,
+    remote_token: Option<String>,
+    target: EjJobTarget,
+    timeouts: impl Into<EjDispatchTimeouts>,
+    mut on_update: impl FnMut(&EjJobUpdate),
+) -> Result<EjBuildResult> {
+    let updates = dispatch_build_updates(
+        dispatcher,
+        commit_hash,
+        remote_url,
+        remote_token,
+        target,
+        timeouts,
+    )
+    .await?;
+    let mut updates = pin!(updates);
+
+    while let Some(update) = updates.next().await {
+        on_update(&update);
+        if let EjJobUpdate::BuildFinished(result) = update {
+            return Ok(result);
         }
     }
     Err(Error::BuildError)
 }
 
+/// Dispatch a build job and get the stream of its updates.
+///
+/// Unlike [`dispatch_build_with_updates`], the caller decides how to follow
+/// the job's progress. The stream ends once the dispatcher closes the
+/// connection after the job finishes, or if the connection is lost: the job's
+/// result is the `EjJobUpdate::BuildFinished` update.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_dispatcher_sdk::{EjJobTarget, EjJobUpdate, dispatch_build_updates};
+/// use futures_util::StreamExt;
+/// use std::{path::Path, time::Duration};
+///
+/// # tokio_test::block_on(async {
+/// let updates = dispatch_build_updates(
+///     Path::new("/tmp/dispatcher.sock"),
+///     "abc123".to_string(),
+///     "https://github.com/user/repo.git".to_string(),
+///     None,
+///     EjJobTarget {
+///         board_configs: vec![String::from("Rpi3 Wayland")],
+///         ..Default::default()
+///     },
+///     Duration::from_secs(600),
+/// ).await.unwrap();
+/// let mut updates = std::pin::pin!(updates);
+/// while let Some(update) = updates.next().await {
+///     match update {
+///         EjJobUpdate::LogChunk { board_config, lines } => {
+///             for line in lines.iter().flat_map(|chunk| chunk.lines()) {
+///                 println!("[{}] {}", board_config.name, line);
+///             }
+///         }
+///         EjJobUpdate::BuildFinished(result) => println!("{}", result),
+///         _ => (),
+///     }
+/// }
+/// # });
+/// ```
+pub async fn dispatch_build_updates(
+    dispatcher: impl Into<EjDispatcherAddress>,
+    commit_hash: String,
+    remote_url: String,
+    remote_token: Option<String>,
+    target: EjJobTarget,
+    timeouts: impl Into<EjDispatchTimeouts>,
+) -> Result<impl Stream<Item = EjJobUpdate>> {
+    let timeouts = timeouts.into();
+    let job = EjJob::new(EjJobType::Build, commit_hash, remote_url, remote_token)
+        .with_target(target)
+        .with_timeouts(timeouts.phases);
+
+    let connection = DispatchConnection::open(dispatcher.into(), job, timeouts.job).await?;
+    Ok(connection.into_updates())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::ejjob::{EjDeployableJob, EjJobCancelReason};
-    use crate::ejsocket_message::EjSocketClientMessage;
+    use crate::ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage};
 
     use super::*;
     use ej_config::ej_board_config::EjBoardConfigApi;
@@ -151,6 +247,10 @@ mod tests {
                 commit_hash: "test_commit_hash".to_string(),
                 remote_url: "test_remote_url".to_string(),
                 remote_token: Some("test_token".to_string()),
+                required_tags: Vec::new(),
+                board_configs: Vec::new(),
+                timeouts: Default::default(),
+                checkout: Default::default(),
             });
             let response = serde_json::to_string(&dispatch_ok).unwrap();
             stream.write_all(response.as_bytes()).await.unwrap();
@@ -174,6 +274,7 @@ mod tests {
                     },
                     "Test build log output".to_string(),
                 )],
+                skipped: Vec::new(),
             };
             let build_finished =
                 EjSocketServerMessage::JobUpdate(EjJobUpdate::BuildFinished(build_result));
@@ -203,6 +304,154 @@ mod tests {
         assert_eq!(build_result.logs[0].1, "Test build log output");
     }
 
+    #[tokio::test]
+    async fn test_dispatch_build_with_updates_forwards_log_chunks() {
+        let (temp_file, listener) = create_test_socket().await;
+        let socket_path = temp_file.path();
+        let board_config = EjBoardConfigApi {
+            id: Uuid::new_v4(),
+            name: "test_board".to_string(),
+            tags: vec!["test".to_string()],
+        };
+        let server_board_config = board_config.clone();
+
+        let server_task = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(&mut stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+
+            let messages = vec![
+                EjSocketServerMessage::DispatchOk(EjDeployableJob {
+                    id: Uuid::new_v4(),
+                    job_type: EjJobType::Build,
+                    commit_hash: "test_commit_hash".to_string(),
+                    remote_url: "test_remote_url".to_string(),
+                    remote_token: None,
+                    required_tags: Vec::new(),
+                    board_configs: Vec::new(),
+                    timeouts: Default::default(),
+                    checkout: Default::default(),
+                }),
+                EjSocketServerMessage::JobUpdate(EjJobUpdate::LogChunk {
+                    board_config: server_board_config.clone(),
+                    lines: vec!["Compiling\n".to_string(), "Done\n".to_string()],
+                }),
+                EjSocketServerMessage::JobUpdate(EjJobUpdate::BuildFinished(EjBuildResult {
+                    success: true,
+                    logs: vec![(server_board_config, "Compiling\nDone\n".to_string())],
+                    skipped: Vec::new(),
+                })),
+            ];
+            for message in messages {
+                let response = serde_json::to_string(&message).unwrap();
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.write_all(b"\n").await.unwrap();
+            }
+        });
+
+        let mut streamed = Vec::new();
+        let result = dispatch_build_with_updates(
+            socket_path,
+            "test_commit_hash".to_string(),
+            "test_remote_url".to_string(),
+            None,
+            EjJobTarget::default(),
+            Duration::from_secs(60),
+            |update| {
+                if let EjJobUpdate::LogChunk {
+                    board_config,
+                    lines,
+                } = update
+                {
+                    streamed.push((board_config.clone(), lines.clone()));
+                }
+            },
+        )
+        .await;
+
+        server_task.await.unwrap();
+
+        assert!(result.unwrap().success);
+        assert_eq!(
+            streamed,
+            vec![(
+                board_config,
+                vec!["Compiling\n".to_string(), "Done\n".to_string()]
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_build_updates_yields_updates_until_closed() {
+        let (temp_file, listener) = create_test_socket().await;
+        let socket_path = temp_file.path();
+        let board_config = EjBoardConfigApi {
+            id: Uuid::new_v4(),
+            name: "test_board".to_string(),
+            tags: Vec::new(),
+        };
+        let updates = move || {
+            vec![
+                EjJobUpdate::LogChunk {
+                    board_config: board_config.clone(),
+                    lines: vec!["Done\n".to_string()],
+                },
+                EjJobUpdate::BuildFinished(EjBuildResult {
+                    success: true,
+                    logs: vec![(board_config.clone(), "Done\n".to_string())],
+                    skipped: Vec::new(),
+                }),
+            ]
+        };
+        let server_updates = updates();
+
+        let server_task = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(&mut stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+
+            let messages = std::iter::once(EjSocketServerMessage::DispatchOk(EjDeployableJob {
+                id: Uuid::new_v4(),
+                job_type: EjJobType::Build,
+                commit_hash: "test_commit_hash".to_string(),
+                remote_url: "test_remote_url".to_string(),
+                remote_token: None,
+                required_tags: Vec::new(),
+                board_configs: Vec::new(),
+                timeouts: Default::default(),
+                checkout: Default::default(),
+            }))
+            .chain(
+                server_updates
+                    .into_iter()
+                    .map(EjSocketServerMessage::JobUpdate),
+            );
+            for message in messages {
+                let response = serde_json::to_string(&message).unwrap();
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.write_all(b"\n").await.unwrap();
+            }
+        });
+
+        let stream = dispatch_build_updates(
+            socket_path,
+            "test_commit_hash".to_string(),
+            "test_remote_url".to_string(),
+            None,
+            EjJobTarget::default(),
+            Duration::from_secs(60),
+        )
+        .await
+        .unwrap();
+        let streamed: Vec<EjJobUpdate> = stream.collect().await;
+
+        server_task.await.unwrap();
+
+        assert_eq!(streamed, updates());
+    }
+
     #[tokio::test]
     async fn test_dispatch_build_connection_closed_early() {
         // Create a temporary Unix socket
@@ -237,6 +486,10 @@ mod tests {
                 commit_hash: "test_commit_hash".to_string(),
                 remote_url: "test_remote_url".to_string(),
                 remote_token: None,
+                required_tags: Vec::new(),
+                board_configs: Vec::new(),
+                timeouts: Default::default(),
+                checkout: Default::default(),
             });
             let response = serde_json::to_string(&dispatch_ok).unwrap();
             stream.write_all(response.as_bytes()).await.unwrap();
@@ -298,6 +551,10 @@ mod tests {
                 commit_hash: "test_commit_hash".to_string(),
                 remote_url: "test_remote_url".to_string(),
                 remote_token: None,
+                required_tags: Vec::new(),
+                board_configs: Vec::new(),
+                timeouts: Default::default(),
+                checkout: Default::default(),
             });
             let response = serde_json::to_string(&dispatch_ok).unwrap();
             stream.write_all(response.as_bytes()).await.unwrap();
@@ -370,6 +627,10 @@ mod tests {
                 commit_hash: "test_commit_hash".to_string(),
                 remote_url: "test_remote_url".to_string(),
                 remote_token: None,
+                required_tags: Vec::new(),
+                board_configs: Vec::new(),
+                timeouts: Default::default(),
+                checkout: Default::default(),
             });
             let response = serde_json::to_string(&dispatch_ok).unwrap();
             stream.write_all(response.as_bytes()).await.unwrap();
@@ -393,6 +654,7 @@ mod tests {
                     },
                     "Test build log with error output".to_string(),
                 )],
+                skipped: Vec::new(),
             };
             let build_finished =
                 EjSocketServerMessage::JobUpdate(EjJobUpdate::BuildFinished(build_result));
@@ -0,0 +1,314 @@
+//! Connections to the dispatcher.
+//!
+//! Jobs are dispatched either through the dispatcher's Unix socket, when
+//! running on the same machine, or through a WebSocket on its API
+//! authenticated with an API key, so CI runners on other machines can
+//! dispatch jobs without forwarding the socket. Both carry the same
+//! [`EjSocketClientMessage`]s and [`EjSocketServerMessage`]s.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use ej_dispatcher_sdk::{connection::EjRemoteDispatcher, dispatch_build};
+//! use std::time::Duration;
+//!
+//! # tokio_test::block_on(async {
+//! let dispatcher = EjRemoteDispatcher::new("https://ejd.example.com:3000", "ej_1a2b3c_secret");
+//! let result = dispatch_build(
+//!     dispatcher,
+//!     "abc123".to_string(),
+//!     "https://github.com/user/repo.git".to_string(),
+//!     None,
+//!     Duration::from_secs(600),
+//! ).await.unwrap();
+//! # });
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use ej_auth::api_key::API_KEY_HEADER;
+use futures_util::{SinkExt, Stream, StreamExt, stream};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader, Lines},
+    net::{TcpStream, UnixStream},
+};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{Message, client::IntoClientRequest, http::HeaderValue},
+};
+use tracing::{error, info};
+
+use crate::{
+    ejjob::{EjDeployableJob, EjJob, EjJobUpdate},
+    ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage},
+    prelude::*,
+    socket,
+};
+
+/// Path of the dispatcher's WebSocket dispatch endpoint, relative to its API URL.
+pub const REMOTE_DISPATCH_PATH: &str = "/v1/client/dispatch/ws";
+
+/// A dispatcher reached through its API.
+#[derive(Debug, Clone)]
+pub struct EjRemoteDispatcher {
+    /// URL of the dispatcher's API, such as `https://ejd.example.com:3000`.
+    pub url: String,
+    /// API key of a client with the `client.dispatch` permission.
+    pub api_key: String,
+}
+
+/// Dispatcher a job is dispatched to.
+///
+/// Paths convert to [`EjDispatcherAddress::Socket`] and
+/// [`EjRemoteDispatcher`]s to [`EjDispatcherAddress::Remote`].
+#[derive(Debug, Clone)]
+pub enum EjDispatcherAddress {
+    /// The dispatcher's Unix socket.
+    Socket(PathBuf),
+    /// The dispatcher's API.
+    Remote(EjRemoteDispatcher),
+}
+
+impl EjRemoteDispatcher {
+    pub fn new(url: impl Into<String>, api_key: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            api_key: api_key.into(),
+        }
+    }
+
+    /// URL of the WebSocket dispatch endpoint.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_dispatcher_sdk::connection::EjRemoteDispatcher;
+    ///
+    /// let dispatcher = EjRemoteDispatcher::new("https://ejd.example.com/", "key");
+    /// assert_eq!(dispatcher.dispatch_url(), "wss://ejd.example.com/v1/client/dispatch/ws");
+    /// ```
+    pub fn dispatch_url(&self) -> String {
+        let url = self.url.trim_end_matches('/');
+        let url = if let Some(host) = url.strip_prefix("https://") {
+            format!("wss://{host}")
+        } else if let Some(host) = url.strip_prefix("http://") {
+            format!("ws://{host}")
+        } else {
+            url.to_string()
+        };
+        format!("{url}{REMOTE_DISPATCH_PATH}")
+    }
+}
+
+impl From<&Path> for EjDispatcherAddress {
+    fn from(path: &Path) -> Self {
+        Self::Socket(path.to_path_buf())
+    }
+}
+
+impl From<&PathBuf> for EjDispatcherAddress {
+    fn from(path: &PathBuf) -> Self {
+        Self::Socket(path.clone())
+    }
+}
+
+impl From<PathBuf> for EjDispatcherAddress {
+    fn from(path: PathBuf) -> Self {
+        Self::Socket(path)
+    }
+}
+
+impl From<EjRemoteDispatcher> for EjDispatcherAddress {
+    fn from(dispatcher: EjRemoteDispatcher) -> Self {
+        Self::Remote(dispatcher)
+    }
+}
+
+/// Connection a job was dispatched on, the dispatcher sends the job's updates on it.
+pub(crate) enum DispatchConnection {
+    Socket(Lines<BufReader<UnixStream>>),
+    Remote(Box<WebSocketStream<MaybeTlsStream<TcpStream>>>),
+}
+
+impl DispatchConnection {
+    /// Connects to the dispatcher and dispatches `job`.
+    pub(crate) async fn open(
+        address: EjDispatcherAddress,
+        job: EjJob,
+        max_duration: Duration,
+    ) -> Result<Self> {
+        let message = EjSocketClientMessage::Dispatch {
+            job,
+            timeout: max_duration,
+        };
+        match address {
+            EjDispatcherAddress::Socket(path) => {
+                let mut stream = UnixStream::connect(path).await?;
+                socket::send(&mut stream, message).await?;
+                Ok(Self::Socket(BufReader::new(stream).lines()))
+            }
+            EjDispatcherAddress::Remote(dispatcher) => {
+                let mut request = dispatcher.dispatch_url().into_client_request()?;
+                let api_key =
+                    HeaderValue::from_str(&dispatcher.api_key).map_err(|_| Error::InvalidApiKey)?;
+                request.headers_mut().insert(API_KEY_HEADER, api_key);
+                let (mut stream, _) = connect_async(request).await?;
+                stream
+                    .send(Message::text(serde_json::to_string(&message)?))
+                    .await?;
+                Ok(Self::Remote(Box::new(stream)))
+            }
+        }
+    }
+
+    /// Reads the next message, `None` once the dispatcher closed the connection.
+    ///
+    /// Messages that can't be parsed are logged and skipped.
+    pub(crate) async fn next_message(&mut self) -> Result<Option<EjSocketServerMessage>> {
+        loop {
+            let text = match self {
+                Self::Socket(lines) => match lines.next_line().await? {
+                    Some(line) => line,
+                    None => return Ok(None),
+                },
+                Self::Remote(stream) => match stream.next().await.transpose()? {
+                    Some(Message::Text(text)) => text.to_string(),
+                    Some(Message::Close(_)) | None => return Ok(None),
+                    Some(_) => continue,
+                },
+            };
+            match serde_json::from_str(&text) {
+                Ok(message) => return Ok(Some(message)),
+                Err(err) => error!("Failed to parse message {} - {}", text, err),
+            }
+        }
+    }
+
+    /// Waits for the dispatcher to accept the job.
+    ///
+    /// Fails with [`Error::UnexpectedSocketMessage`] if the dispatcher answers
+    /// anything but [`EjSocketServerMessage::DispatchOk`], such as an error.
+    pub(crate) async fn accepted_job(&mut self) -> Result<EjDeployableJob> {
+        match self.next_message().await? {
+            Some(EjSocketServerMessage::DispatchOk(job)) => Ok(job),
+            Some(message) => Err(Error::UnexpectedSocketMessage(Box::new(message))),
+            None => Err(Error::IO(std::io::ErrorKind::UnexpectedEof.into())),
+        }
+    }
+
+    /// Turns the connection into the stream of the job's updates.
+    ///
+    /// The stream ends once the dispatcher closes the connection or it is lost.
+    pub(crate) fn into_updates(self) -> impl Stream<Item = EjJobUpdate> {
+        stream::unfold(self, |mut connection| async move {
+            loop {
+                let message = match connection.next_message().await {
+                    Ok(Some(message)) => message,
+                    Ok(None) => return None,
+                    Err(err) => {
+                        error!("Failed to read job updates - {err}");
+                        return None;
+                    }
+                };
+                info!("{}", message);
+                if let EjSocketServerMessage::JobUpdate(update) = message {
+                    return Some((update, connection));
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ej_config::ej_board_config::EjBoardConfigApi;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{
+        accept_hdr_async,
+        tungstenite::handshake::server::{Request, Response},
+    };
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{EjBuildResult, EjJobType, EjJobUpdate, dispatch_build};
+
+    #[tokio::test]
+    async fn test_remote_dispatch() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server_task = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            // The handshake's error type is large, it doesn't matter in a test
+            #[allow(clippy::result_large_err)]
+            let check_request = |request: &Request, response: Response| {
+                assert_eq!(request.uri().path(), REMOTE_DISPATCH_PATH);
+                assert_eq!(request.headers()[API_KEY_HEADER], "ej_test_key");
+                Ok(response)
+            };
+            let mut stream = accept_hdr_async(stream, check_request).await.unwrap();
+
+            let text = match stream.next().await.unwrap().unwrap() {
+                Message::Text(text) => text,
+                message => panic!("Expected a text message, got {message:?}"),
+            };
+            let job = match serde_json::from_str(&text).unwrap() {
+                EjSocketClientMessage::Dispatch { job, timeout } => {
+                    assert_eq!(timeout, Duration::from_secs(60));
+                    job
+                }
+                message => panic!("Expected Dispatch message, got {message:?}"),
+            };
+            assert_eq!(job.job_type, EjJobType::Build);
+
+            let build_result = EjBuildResult {
+                success: true,
+                logs: vec![(
+                    EjBoardConfigApi {
+                        id: Uuid::new_v4(),
+                        name: "test_board".to_string(),
+                        tags: Vec::new(),
+                    },
+                    "Test build log output".to_string(),
+                )],
+                skipped: Vec::new(),
+            };
+            let messages = [
+                EjSocketServerMessage::DispatchOk(EjDeployableJob {
+                    id: Uuid::new_v4(),
+                    job_type: job.job_type,
+                    commit_hash: job.commit_hash,
+                    remote_url: job.remote_url,
+                    remote_token: job.remote_token,
+                    required_tags: job.required_tags,
+                    board_configs: job.board_configs,
+                    timeouts: job.timeouts,
+                    checkout: job.checkout,
+                }),
+                EjSocketServerMessage::JobUpdate(EjJobUpdate::BuildFinished(build_result)),
+            ];
+            for message in messages {
+                let text = serde_json::to_string(&message).unwrap();
+                stream.send(Message::text(text)).await.unwrap();
+            }
+            stream.close(None).await.unwrap();
+        });
+
+        let result = dispatch_build(
+            EjRemoteDispatcher::new(url, "ej_test_key"),
+            "test_commit_hash".to_string(),
+            "test_remote_url".to_string(),
+            None,
+            Duration::from_secs(60),
+        )
+        .await
+        .unwrap();
+        server_task.await.unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.logs[0].1, "Test build log output");
+    }
+}
@@ -0,0 +1,103 @@
+//! Dispatch of arbitrary jobs.
+
+use futures_util::Stream;
+
+use crate::{
+    connection::{DispatchConnection, EjDispatcherAddress},
+    ejjob::{EjDeployableJob, EjJob, EjJobUpdate, timeouts::EjDispatchTimeouts},
+    prelude::*,
+};
+
+/// Dispatches a job and returns it once the dispatcher accepted it.
+///
+/// Unlike [`dispatch_build_updates`](crate::build::dispatch_build_updates)
+/// and [`dispatch_run_updates`](crate::run::dispatch_run_updates), the job is
+/// given as is, and the job the dispatcher created, with its ID, is returned
+/// along with the stream of its updates. Dropping the stream doesn't cancel
+/// the job.
+///
+/// # Arguments
+///
+/// * `dispatcher` - Dispatcher to send the job to
+/// * `job` - The job to dispatch, its timeouts are replaced by the ones of `timeouts`
+/// * `timeouts` - Time limits of the job
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_dispatcher_sdk::{EjJob, EjJobType, dispatch_job};
+/// use std::{path::Path, time::Duration};
+///
+/// # tokio_test::block_on(async {
+/// let job = EjJob::new(EjJobType::BuildAndRun, "abc123", "https://github.com/user/repo.git", None)
+///     .with_required_tags(vec!["arm64".to_string()]);
+/// let (job, _updates) = dispatch_job(Path::new("/tmp/ejd.sock"), job, Duration::from_secs(600))
+///     .await
+///     .unwrap();
+/// println!("Dispatched job {}", job.id);
+/// # });
+/// ```
+pub async fn dispatch_job(
+    dispatcher: impl Into<EjDispatcherAddress>,
+    job: EjJob,
+    timeouts: impl Into<EjDispatchTimeouts>,
+) -> Result<(EjDeployableJob, impl Stream<Item = EjJobUpdate>)> {
+    let timeouts = timeouts.into();
+    let job = job.with_timeouts(timeouts.phases);
+    let mut connection = DispatchConnection::open(dispatcher.into(), job, timeouts.job).await?;
+    let job = connection.accepted_job().await?;
+    Ok((job, connection.into_updates()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+
+    use super::*;
+    use crate::{
+        EjJobType,
+        ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage},
+        mock::{MockDispatcher, dispatch_ok},
+    };
+
+    #[tokio::test]
+    async fn test_dispatch_job() {
+        let dispatcher = MockDispatcher::start(vec![
+            vec![
+                dispatch_ok(EjJobType::BuildAndRun),
+                EjSocketServerMessage::JobUpdate(EjJobUpdate::JobStarted { nb_builders: 2 }),
+            ],
+            vec![EjSocketServerMessage::Error(String::from("No builders"))],
+        ])
+        .unwrap();
+        let job = EjJob::new(EjJobType::BuildAndRun, "abc123", "remote", None)
+            .with_board_configs(vec![String::from("rpi4")]);
+
+        let (deployed, updates) =
+            dispatch_job(dispatcher.socket_path(), job, Duration::from_secs(60))
+                .await
+                .unwrap();
+        assert_eq!(deployed.job_type, EjJobType::BuildAndRun);
+        let updates: Vec<EjJobUpdate> = updates.collect().await;
+        assert!(matches!(
+            updates.as_slice(),
+            [EjJobUpdate::JobStarted { nb_builders: 2 }]
+        ));
+        match dispatcher.take_received().as_slice() {
+            [EjSocketClientMessage::Dispatch { job, .. }] => {
+                assert_eq!(job.board_configs, vec![String::from("rpi4")]);
+            }
+            received => panic!("Unexpected messages {received:?}"),
+        }
+
+        let job = EjJob::new(EjJobType::Build, "abc123", "remote", None);
+        let result = dispatch_job(dispatcher.socket_path(), job, Duration::from_secs(60)).await;
+        assert!(matches!(
+            result,
+            Err(Error::UnexpectedSocketMessage(message))
+                if matches!(*message, EjSocketServerMessage::Error(_))
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+//! API key types.
+//!
+//! API keys let non-interactive clients such as CI systems authenticate with
+//! a single header instead of logging in. Every key is limited to the scopes
+//! it was created with, on top of the permissions of the client owning it.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// What an API key is allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub enum EjApiKeyScope {
+    /// Dispatch jobs and cancel them.
+    #[serde(rename = "dispatch")]
+    Dispatch,
+    /// Read job results and download their artifacts.
+    #[serde(rename = "read:results")]
+    ReadResults,
+}
+
+/// API key creation request.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjApiKeyPost {
+    /// Name used to recognize the key.
+    pub name: String,
+    /// Scopes granted to the key.
+    pub scopes: Vec<EjApiKeyScope>,
+    /// Number of days the key is valid for, never expires if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_in_days: Option<u32>,
+}
+
+/// API key as listed by its owner. The key itself is never shown again.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjApiKeyApi {
+    /// Unique API key identifier.
+    pub id: Uuid,
+    /// Name used to recognize the key.
+    pub name: String,
+    /// Public prefix of the key.
+    pub prefix: String,
+    /// Scopes granted to the key.
+    pub scopes: Vec<EjApiKeyScope>,
+    /// When the key stops being accepted, if ever.
+    pub expires_at: Option<DateTime<Utc>>,
+    /// When the key was revoked, if it was.
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// When the key was created.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Newly created API key.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjApiKeyCreated {
+    /// The key to send in the `X-Api-Key` header. It can't be retrieved later.
+    pub key: String,
+    /// The created key.
+    pub api_key: EjApiKeyApi,
+}
+
+impl EjApiKeyScope {
+    /// Every scope an API key can be granted.
+    pub const ALL: [EjApiKeyScope; 2] = [EjApiKeyScope::Dispatch, EjApiKeyScope::ReadResults];
+
+    /// Name of the scope as it's serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_dispatcher_sdk::ejapi_key::EjApiKeyScope;
+    ///
+    /// assert_eq!(EjApiKeyScope::ReadResults.as_str(), "read:results");
+    /// assert_eq!(EjApiKeyScope::from_name("dispatch"), Some(EjApiKeyScope::Dispatch));
+    /// ```
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EjApiKeyScope::Dispatch => "dispatch",
+            EjApiKeyScope::ReadResults => "read:results",
+        }
+    }
+
+    /// Parses a scope from its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == name)
+    }
+}
+
+impl fmt::Display for EjApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Display for EjApiKeyApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scopes: Vec<&str> = self.scopes.iter().map(|scope| scope.as_str()).collect();
+        write!(
+            f,
+            "API key {} '{}' ({}) [{}]",
+            self.id,
+            self.name,
+            self.prefix,
+            scopes.join(", ")
+        )?;
+        if self.revoked_at.is_some() {
+            write!(f, " - revoked")?;
+        } else if let Some(expires_at) = self.expires_at {
+            write!(f, " - expires {expires_at}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes_round_trip() {
+        for scope in EjApiKeyScope::ALL {
+            let json = serde_json::to_string(&scope).unwrap();
+            assert_eq!(json, format!("\"{}\"", scope.as_str()));
+            assert_eq!(serde_json::from_str::<EjApiKeyScope>(&json).unwrap(), scope);
+            assert_eq!(EjApiKeyScope::from_name(scope.as_str()), Some(scope));
+        }
+        assert_eq!(EjApiKeyScope::from_name("admin"), None);
+    }
+}
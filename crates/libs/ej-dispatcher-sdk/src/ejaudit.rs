@@ -0,0 +1,160 @@
+//! Audit log types.
+//!
+//! EJD records security-relevant actions such as logins, token issuance,
+//! builder creation, dispatches and cancellations. Clients with the
+//! `audit.read` permission can query them.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A security-relevant action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum EjAuditAction {
+    /// A client logged in.
+    Login,
+    /// A client or builder failed to log in.
+    LoginFailed,
+    /// Tokens were issued in exchange for a builder token or a refresh token.
+    TokenIssued,
+    /// A builder was created.
+    BuilderCreated,
+    /// A job was dispatched.
+    Dispatch,
+    /// A board config of a job was cancelled.
+    Cancel,
+    /// A permission was granted to a client.
+    PermissionGranted,
+    /// A permission was revoked from a client.
+    PermissionRevoked,
+    /// A role was assigned to a client.
+    RoleGranted,
+    /// A role was removed from a client.
+    RoleRevoked,
+}
+
+/// Audit log entry.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjAuditEntry {
+    /// Unique entry identifier.
+    pub id: Uuid,
+    /// What was done.
+    pub action: EjAuditAction,
+    /// The client or builder that did it, if known.
+    pub actor_id: Option<Uuid>,
+    /// What it was done to, e.g. the job ID for dispatches.
+    pub target: Option<String>,
+    /// When it was done.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Audit log query, every criterion is optional.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "utoipa", into_params(parameter_in = Query))]
+pub struct EjAuditQuery {
+    /// Only entries for this action.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<EjAuditAction>,
+    /// Only entries recorded for this client or builder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor_id: Option<Uuid>,
+    /// Only entries recorded at or after this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries recorded before this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+    /// Maximum number of entries returned, newest first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+impl EjAuditAction {
+    /// Every recorded action.
+    pub const ALL: [EjAuditAction; 10] = [
+        EjAuditAction::Login,
+        EjAuditAction::LoginFailed,
+        EjAuditAction::TokenIssued,
+        EjAuditAction::BuilderCreated,
+        EjAuditAction::Dispatch,
+        EjAuditAction::Cancel,
+        EjAuditAction::PermissionGranted,
+        EjAuditAction::PermissionRevoked,
+        EjAuditAction::RoleGranted,
+        EjAuditAction::RoleRevoked,
+    ];
+
+    /// Name of the action as it's serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_dispatcher_sdk::ejaudit::EjAuditAction;
+    ///
+    /// assert_eq!(EjAuditAction::LoginFailed.as_str(), "login_failed");
+    /// assert_eq!(EjAuditAction::from_name("dispatch"), Some(EjAuditAction::Dispatch));
+    /// ```
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EjAuditAction::Login => "login",
+            EjAuditAction::LoginFailed => "login_failed",
+            EjAuditAction::TokenIssued => "token_issued",
+            EjAuditAction::BuilderCreated => "builder_created",
+            EjAuditAction::Dispatch => "dispatch",
+            EjAuditAction::Cancel => "cancel",
+            EjAuditAction::PermissionGranted => "permission_granted",
+            EjAuditAction::PermissionRevoked => "permission_revoked",
+            EjAuditAction::RoleGranted => "role_granted",
+            EjAuditAction::RoleRevoked => "role_revoked",
+        }
+    }
+
+    /// Parses an action from its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.as_str() == name)
+    }
+}
+
+impl fmt::Display for EjAuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Display for EjAuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.created_at, self.action)?;
+        if let Some(actor_id) = self.actor_id {
+            write!(f, " by {actor_id}")?;
+        }
+        if let Some(target) = &self.target {
+            write!(f, " on {target}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_actions_round_trip() {
+        for action in EjAuditAction::ALL {
+            let json = serde_json::to_string(&action).unwrap();
+            assert_eq!(json, format!("\"{}\"", action.as_str()));
+            assert_eq!(
+                serde_json::from_str::<EjAuditAction>(&json).unwrap(),
+                action
+            );
+            assert_eq!(EjAuditAction::from_name(action.as_str()), Some(action));
+        }
+        assert_eq!(EjAuditAction::from_name("logout"), None);
+    }
+}
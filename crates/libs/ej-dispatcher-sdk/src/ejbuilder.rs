@@ -1,15 +1,374 @@
 //! Builder registration and management types.
 
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+    net::SocketAddr,
+    time::Duration,
+};
 
+use chrono::{DateTime, Utc};
+use ej_config::{ej_board_config::EjBoardConfigApi, ej_config::EjConfig};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 /// Builder API representation.
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjBuilderApi {
     /// Unique builder identifier.
     pub id: Uuid,
     /// Builder authentication token.
     pub token: String,
 }
+
+/// Board as registered by a builder in its latest uploaded config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjBuilderBoardApi {
+    /// Unique board identifier.
+    pub id: Uuid,
+    /// Board name.
+    pub name: String,
+    /// Board description.
+    pub description: String,
+    /// Board configurations.
+    pub configs: Vec<EjBoardConfigApi>,
+}
+
+/// What a builder can do, advertised when it connects to the dispatcher.
+///
+/// Derived from the builder's config so the dispatcher can route jobs without
+/// looking the config up in the database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjBuilderCapabilities {
+    /// Boards connected to the builder.
+    pub boards: Vec<EjBuilderBoardApi>,
+    /// Every tag of the builder's board configs, sorted and deduplicated.
+    pub tags: Vec<String>,
+    /// Number of jobs the builder can run at the same time.
+    pub parallel_capacity: usize,
+}
+
+impl EjBuilderCapabilities {
+    /// Derives the capabilities of a builder from its config.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Config the builder uploaded to the dispatcher
+    pub fn from_config(config: &EjConfig) -> Self {
+        let boards: Vec<EjBuilderBoardApi> = config
+            .boards
+            .iter()
+            .map(|board| EjBuilderBoardApi {
+                id: board.id,
+                name: board.name.clone(),
+                description: board.description.clone(),
+                configs: board
+                    .configs
+                    .iter()
+                    .map(|board_config| EjBoardConfigApi {
+                        id: board_config.id,
+                        name: board_config.name.clone(),
+                        tags: board_config.tags.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let tags: BTreeSet<&String> = boards
+            .iter()
+            .flat_map(|board| &board.configs)
+            .flat_map(|board_config| &board_config.tags)
+            .collect();
+
+        Self {
+            tags: tags.into_iter().cloned().collect(),
+            // EJB runs a single job at a time on all of its boards, a second
+            // job would cancel the first one
+            parallel_capacity: 1,
+            boards,
+        }
+    }
+
+    /// Whether any of the builder's board configs has the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Whether any of the builder's board configs has every tag in `required_tags`.
+    pub fn matches_tags(&self, required_tags: &[String]) -> bool {
+        self.matches_job(&[], required_tags)
+    }
+
+    /// Whether any of the builder's board configs is targeted by a job for
+    /// `board_configs` and `required_tags`.
+    pub fn matches_job(&self, board_configs: &[String], required_tags: &[String]) -> bool {
+        self.boards
+            .iter()
+            .flat_map(|board| &board.configs)
+            .any(|board_config| board_config.is_targeted(board_configs, required_tags))
+    }
+
+    /// Whether the board config with the given ID is connected to the builder.
+    pub fn has_board_config(&self, board_config_id: &Uuid) -> bool {
+        self.boards
+            .iter()
+            .flat_map(|board| &board.configs)
+            .any(|board_config| board_config.id == *board_config_id)
+    }
+}
+
+/// Config version uploaded by a builder.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjBuilderConfigApi {
+    /// Unique config identifier.
+    pub id: Uuid,
+    /// The builder that uploaded the config.
+    pub builder_id: Uuid,
+    /// Config version.
+    pub version: String,
+    /// When the config was last uploaded.
+    pub uploaded_at: DateTime<Utc>,
+    /// Boards described by this config.
+    pub boards: Vec<EjBuilderBoardApi>,
+}
+
+/// A WebSocket connection a builder opened with the dispatcher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjBuilderConnectionApi {
+    /// Address the builder connected from.
+    pub address: String,
+    /// When the connection was opened.
+    pub connected_at: DateTime<Utc>,
+    /// When the builder was last heard from on this connection.
+    pub last_seen_at: DateTime<Utc>,
+    /// When the connection was closed, `None` while it's open.
+    pub disconnected_at: Option<DateTime<Utc>>,
+}
+
+/// Detailed builder information, including its connection status.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjBuilderInfo {
+    /// Unique builder identifier.
+    pub id: Uuid,
+    /// The client that owns this builder, `None` once the client was deleted.
+    pub client_id: Option<Uuid>,
+    /// When the builder was created.
+    pub created_at: DateTime<Utc>,
+    /// When the builder was revoked, if it was.
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Addresses of the currently open WebSocket connections for this builder.
+    #[cfg_attr(feature = "utoipa", schema(value_type = Vec<String>))]
+    pub connections: Vec<SocketAddr>,
+    /// Messages that found the queue of one of the open connections full.
+    #[serde(default)]
+    pub slow_sends: u64,
+    /// Messages dropped because the queue of one of the open connections stayed full.
+    #[serde(default)]
+    pub dropped_messages: u64,
+    /// When the builder was last heard from, `None` if it never connected.
+    #[serde(default)]
+    pub last_seen_at: Option<DateTime<Utc>>,
+    /// Latest connections opened by the builder, most recent first.
+    #[serde(default)]
+    pub connection_history: Vec<EjBuilderConnectionApi>,
+    /// Latest config uploaded by the builder.
+    pub config: Option<EjBuilderConfigApi>,
+    /// Boards, tags and parallel capacity advertised on the open connections,
+    /// `None` while disconnected or until the builder sends them.
+    #[serde(default)]
+    pub capabilities: Option<EjBuilderCapabilities>,
+    /// Job the builder is working on, if any.
+    #[serde(default)]
+    pub current_job: Option<Uuid>,
+    /// Boards a job of the builder has exclusive access to, as reported by the builder.
+    #[serde(default)]
+    pub locked_boards: Vec<Uuid>,
+}
+
+impl EjBuilderInfo {
+    /// Whether the builder currently has at least one open connection.
+    pub fn is_connected(&self) -> bool {
+        !self.connections.is_empty()
+    }
+
+    /// Whether the builder hasn't been heard from within `stale_after` of `now`.
+    ///
+    /// Builders that never connected are stale, revoked builders never are
+    /// since they aren't expected to connect anymore.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use chrono::Utc;
+    /// # use ej_dispatcher_sdk::ejbuilder::EjBuilderInfo;
+    ///
+    /// # fn example(builder: &EjBuilderInfo) {
+    /// if builder.is_stale(Utc::now(), Duration::from_secs(300)) {
+    ///     println!("{} went quiet", builder.id);
+    /// }
+    /// # }
+    /// ```
+    pub fn is_stale(&self, now: DateTime<Utc>, stale_after: Duration) -> bool {
+        if self.revoked_at.is_some() {
+            return false;
+        }
+        match self.last_seen_at {
+            Some(last_seen_at) => now
+                .signed_duration_since(last_seen_at)
+                .to_std()
+                .is_ok_and(|silence| silence > stale_after),
+            None => true,
+        }
+    }
+}
+
+impl fmt::Display for EjBuilderInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.revoked_at.is_some() {
+            "revoked"
+        } else if self.is_connected() {
+            "connected"
+        } else {
+            "disconnected"
+        };
+        write!(f, "Builder {} ({status})", self.id)?;
+        for address in self.connections.iter() {
+            write!(f, " from {address}")?;
+        }
+        if let Some(job_id) = self.current_job {
+            write!(f, " - working on job {job_id}")?;
+        }
+        if let Some(capabilities) = &self.capabilities {
+            write!(f, " - capacity {}", capabilities.parallel_capacity)?;
+        }
+        if !self.locked_boards.is_empty() {
+            write!(f, " - {} locked board(s)", self.locked_boards.len())?;
+        }
+        match self.last_seen_at {
+            Some(last_seen_at) => write!(f, " - last seen {last_seen_at}")?,
+            None => write!(f, " - never seen")?,
+        }
+        if self.dropped_messages > 0 {
+            write!(f, " - {} dropped message(s)", self.dropped_messages)?;
+        }
+        if let Some(config) = &self.config {
+            for board in config.boards.iter() {
+                write!(f, "\n  {} - {}", board.name, board.description)?;
+                for board_config in board.configs.iter() {
+                    write!(f, "\n    {board_config}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for EjBuilderConnectionApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} connected at {}", self.address, self.connected_at)?;
+        match self.disconnected_at {
+            Some(disconnected_at) => write!(f, ", disconnected at {disconnected_at}"),
+            None => write!(f, ", last seen {}", self.last_seen_at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ej_config::ej_config::EjUserConfig;
+
+    #[test]
+    fn test_capabilities_from_config() {
+        let config = EjConfig::from_user_config(
+            EjUserConfig::from_toml(
+                r#"
+                [global]
+                version = "1.0.0"
+
+                [[boards]]
+                name = "rpi4"
+                description = "Raspberry Pi 4"
+
+                [[boards.configs]]
+                name = "wayland"
+                tags = ["wayland", "arm64"]
+                build_script = "build.sh"
+                run_script = "run.sh"
+                results_path = "results.json"
+                library_path = "lib"
+
+                [[boards]]
+                name = "x86"
+                description = "Desktop"
+
+                [[boards.configs]]
+                name = "sdl"
+                tags = ["sdl2", "x86_64", "wayland"]
+                build_script = "build.sh"
+                run_script = "run.sh"
+                results_path = "results.json"
+                library_path = "lib"
+                "#,
+            )
+            .unwrap(),
+        );
+
+        let capabilities = EjBuilderCapabilities::from_config(&config);
+        assert_eq!(capabilities.parallel_capacity, 1);
+        assert_eq!(capabilities.boards[0].name, "rpi4");
+        assert_eq!(
+            capabilities.boards[1].configs[0].id,
+            config.boards[1].configs[0].id
+        );
+        assert_eq!(capabilities.tags, ["arm64", "sdl2", "wayland", "x86_64"]);
+        assert!(capabilities.has_tag("sdl2"));
+        assert!(!capabilities.has_tag("riscv"));
+        assert!(capabilities.matches_tags(&[]));
+        assert!(capabilities.matches_tags(&[String::from("arm64"), String::from("wayland")]));
+        assert!(!capabilities.matches_tags(&[String::from("arm64"), String::from("x86_64")]));
+        let board_config = config.boards[0].configs[0].name.clone();
+        assert!(capabilities.matches_job(&[board_config], &[]));
+        assert!(!capabilities.matches_job(&[String::from("missing")], &[]));
+        assert!(capabilities.has_board_config(&config.boards[0].configs[0].id));
+        assert!(!capabilities.has_board_config(&Uuid::new_v4()));
+    }
+
+    #[test]
+    fn test_builder_is_stale() {
+        let now = Utc::now();
+        let mut builder = EjBuilderInfo {
+            id: Uuid::new_v4(),
+            client_id: Some(Uuid::new_v4()),
+            created_at: now,
+            revoked_at: None,
+            connections: Vec::new(),
+            slow_sends: 0,
+            dropped_messages: 0,
+            last_seen_at: None,
+            connection_history: Vec::new(),
+            config: None,
+            capabilities: None,
+            current_job: None,
+            locked_boards: Vec::new(),
+        };
+        let stale_after = Duration::from_secs(300);
+        assert!(builder.is_stale(now, stale_after));
+
+        builder.last_seen_at = Some(now - chrono::Duration::seconds(60));
+        assert!(!builder.is_stale(now, stale_after));
+
+        builder.last_seen_at = Some(now - chrono::Duration::seconds(600));
+        assert!(builder.is_stale(now, stale_after));
+
+        builder.revoked_at = Some(now);
+        assert!(!builder.is_stale(now, stale_after));
+    }
+}
@@ -8,6 +8,7 @@ use uuid::Uuid;
 
 /// Client API representation.
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjClientApi {
     /// Unique client identifier.
     pub id: Uuid,
@@ -17,6 +18,7 @@ pub struct EjClientApi {
 
 /// Client registration data.
 #[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjClientPost {
     /// Client name for registration.
     pub name: String,
@@ -26,6 +28,7 @@ pub struct EjClientPost {
 
 /// Client login request.
 #[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjClientLoginRequest {
     /// Client name.
     pub name: String,
@@ -35,11 +38,39 @@ pub struct EjClientLoginRequest {
 
 /// Client login response.
 #[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjClientLogin {
     /// JWT access token.
     pub access_token: String,
     /// Token type (usually "Bearer").
     pub token_type: String,
+    /// Refresh token used to get a new access token once it expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+}
+
+/// Client update request.
+///
+/// Only the fields that are set are updated.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjClientUpdate {
+    /// New client name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// New client secret.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+/// Role API representation.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjRoleApi {
+    /// Unique role identifier.
+    pub id: String,
+    /// Permissions granted by the role.
+    pub permissions: Vec<String>,
 }
 
 impl EjClientLoginRequest {
@@ -66,3 +97,9 @@ impl fmt::Display for EjClientApi {
         write!(f, "Client '{}' (ID: {})", self.name, self.id)
     }
 }
+
+impl fmt::Display for EjRoleApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Role '{}' [{}]", self.id, self.permissions.join(", "))
+    }
+}
@@ -0,0 +1,23 @@
+//! Build artifact types.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Artifact uploaded by a builder and stored by the dispatcher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjJobArtifact {
+    /// Unique artifact ID.
+    pub id: Uuid,
+    /// The job that produced the artifact.
+    pub job_id: Uuid,
+    /// The board config that produced the artifact.
+    pub board_config_id: Uuid,
+    /// File name of the artifact.
+    pub name: String,
+    /// Size of the artifact in bytes.
+    pub size: u64,
+    /// When the artifact was stored.
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,50 @@
+//! How builders check out the source code of a job.
+
+use serde::{Deserialize, Serialize};
+
+/// Options of the checkout of a job's commit.
+///
+/// Builders fetch the whole history of the commit and check out every file
+/// by default.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_dispatcher_sdk::ejjob::checkout::EjCheckoutOptions;
+///
+/// let checkout = EjCheckoutOptions::default()
+///     .with_depth(1)
+///     .with_sparse_paths(vec!["/src/".to_string(), "/CMakeLists.txt".to_string()]);
+/// assert!(!checkout.is_empty());
+/// assert!(EjCheckoutOptions::default().is_empty());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjCheckoutOptions {
+    /// Number of commits of history to fetch, the whole history if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,
+    /// Patterns of the paths to check out, in the `.gitignore` format, every
+    /// path if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sparse_paths: Vec<String>,
+}
+
+impl EjCheckoutOptions {
+    /// Only fetches the last `depth` commits of history.
+    pub fn with_depth(mut self, depth: u32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Only checks out the paths matching `sparse_paths`.
+    pub fn with_sparse_paths(mut self, sparse_paths: Vec<String>) -> Self {
+        self.sparse_paths = sparse_paths;
+        self
+    }
+
+    /// Whether the options are the default ones, a full checkout.
+    pub fn is_empty(&self) -> bool {
+        self.depth.is_none() && self.sparse_paths.is_empty()
+    }
+}
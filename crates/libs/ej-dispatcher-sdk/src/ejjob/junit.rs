@@ -0,0 +1,191 @@
+//! JUnit XML reports of run results.
+//!
+//! CI systems such as GitLab and Jenkins display JUnit XML reports natively,
+//! so each board config of a run is reported as a test case.
+
+use std::fmt::Write;
+
+use ej_config::ej_board_config::EjBoardConfigApi;
+
+use crate::ejjob::EjRunResult;
+
+impl EjRunResult {
+    /// Converts the run result into a JUnit XML report.
+    ///
+    /// Every board config is a test case of the `suite` test suite, with its
+    /// results as standard output and its logs as standard error. Board
+    /// configs that produced no results fail, as do all of them if the run
+    /// failed although every board config produced results. Skipped board
+    /// configs are reported as skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_config::ej_board_config::EjBoardConfigApi;
+    /// use ej_dispatcher_sdk::EjRunResult;
+    /// use uuid::Uuid;
+    ///
+    /// let board_config = EjBoardConfigApi {
+    ///     id: Uuid::new_v4(),
+    ///     name: "k-mer".to_string(),
+    ///     tags: Vec::new(),
+    /// };
+    /// let result = EjRunResult {
+    ///     logs: vec![(board_config.clone(), "Running\n".to_string())],
+    ///     results: vec![(board_config, "ABC: 2\n".to_string())],
+    ///     skipped: Vec::new(),
+    ///     success: true,
+    /// };
+    ///
+    /// let report = result.to_junit("kmer");
+    /// assert!(report.contains(r#"<testsuite name="kmer" tests="1" failures="0" skipped="0">"#));
+    /// ```
+    pub fn to_junit(&self, suite: &str) -> String {
+        let mut board_configs: Vec<&EjBoardConfigApi> = Vec::new();
+        for (board_config, _) in self.logs.iter().chain(self.results.iter()) {
+            if !board_configs
+                .iter()
+                .any(|config| config.id == board_config.id)
+            {
+                board_configs.push(board_config);
+            }
+        }
+        let has_results = |board_config: &EjBoardConfigApi| {
+            self.results.iter().any(|(c, _)| c.id == board_config.id)
+        };
+        let all_have_results = board_configs.iter().all(|config| has_results(config));
+        let failure = |board_config: &EjBoardConfigApi| {
+            if !has_results(board_config) {
+                Some("The board config produced no results")
+            } else if !self.success && all_have_results {
+                Some("The run failed")
+            } else {
+                None
+            }
+        };
+        let failures = board_configs
+            .iter()
+            .filter(|config| failure(config).is_some())
+            .count();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuites>\n  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">",
+            escape(suite),
+            board_configs.len() + self.skipped.len(),
+            failures,
+            self.skipped.len()
+        );
+        for board_config in board_configs {
+            let _ = writeln!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\">",
+                escape(&board_config.name),
+                escape(suite)
+            );
+            if let Some(message) = failure(board_config) {
+                let _ = writeln!(xml, "      <failure message=\"{message}\"/>");
+            }
+            let results = output_of(&self.results, board_config);
+            let logs = output_of(&self.logs, board_config);
+            let _ = writeln!(xml, "      <system-out>{}</system-out>", escape(&results));
+            let _ = writeln!(xml, "      <system-err>{}</system-err>", escape(&logs));
+            xml.push_str("    </testcase>\n");
+        }
+        for board_config in self.skipped.iter() {
+            let _ = writeln!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\">\n      <skipped message=\"Not targeted by the job\"/>\n    </testcase>",
+                escape(&board_config.name),
+                escape(suite)
+            );
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+/// Concatenates the entries of `board_config` in `entries`.
+fn output_of(entries: &[(EjBoardConfigApi, String)], board_config: &EjBoardConfigApi) -> String {
+    entries
+        .iter()
+        .filter(|(config, _)| config.id == board_config.id)
+        .map(|(_, output)| output.as_str())
+        .collect()
+}
+
+/// Escapes text for XML content and attributes, dropping the control
+/// characters XML doesn't allow.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn board_config(name: &str) -> EjBoardConfigApi {
+        EjBoardConfigApi {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_to_junit() {
+        let passed = board_config("rpi4 <gcc>");
+        let failed = board_config("rpi3");
+        let skipped = board_config("x86");
+        let result = EjRunResult {
+            logs: vec![
+                (passed.clone(), "Running & done\u{1b}[0m\n".to_string()),
+                (failed.clone(), "Segmentation fault\n".to_string()),
+            ],
+            results: vec![(passed, "ABC: 2\n".to_string())],
+            skipped: vec![skipped],
+            success: false,
+        };
+
+        let report = result.to_junit("kmer");
+        assert!(report.contains(r#"<testsuite name="kmer" tests="3" failures="1" skipped="1">"#));
+        assert!(report.contains(
+            "<testcase name=\"rpi4 &lt;gcc&gt;\" classname=\"kmer\">\n      <system-out>ABC: 2\n</system-out>\n      <system-err>Running &amp; done[0m\n</system-err>"
+        ));
+        assert!(report.contains(
+            "<testcase name=\"rpi3\" classname=\"kmer\">\n      <failure message=\"The board config produced no results\"/>"
+        ));
+        assert!(report.contains("<skipped message=\"Not targeted by the job\"/>"));
+    }
+
+    #[test]
+    fn test_to_junit_failed_run_with_results() {
+        let config = board_config("rpi4");
+        let result = EjRunResult {
+            logs: vec![(config.clone(), String::new())],
+            results: vec![(config, "ABC: 3\n".to_string())],
+            skipped: Vec::new(),
+            success: false,
+        };
+
+        let report = result.to_junit("kmer");
+        assert!(report.contains(r#"failures="1""#));
+        assert!(report.contains("<failure message=\"The run failed\"/>"));
+    }
+}
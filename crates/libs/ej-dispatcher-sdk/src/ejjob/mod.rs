@@ -1,6 +1,11 @@
 //! Job management types and utilities.
 
+pub mod artifact;
+pub mod checkout;
+pub mod junit;
 pub mod results;
+pub mod search;
+pub mod timeouts;
 
 use std::{cmp::Ordering, fmt};
 
@@ -9,8 +14,14 @@ use ej_config::ej_board_config::EjBoardConfigApi;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::{
+    ejjob::{checkout::EjCheckoutOptions, timeouts::EjJobTimeouts},
+    ejpage::EjPageQuery,
+};
+
 /// Type of job to execute.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub enum EjJobType {
     /// Build only (compile/prepare without running).
     Build = 0,
@@ -30,6 +41,7 @@ impl From<i32> for EjJobType {
 
 /// Type of job to execute.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub enum EjJobStatus {
     /// Job not started yet
     NotStarted = 0,
@@ -58,6 +70,7 @@ impl From<i32> for EjJobStatus {
 
 /// Job configuration for the dispatcher.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjJob {
     /// Type of job to execute.
     pub job_type: EjJobType,
@@ -67,6 +80,24 @@ pub struct EjJob {
     pub remote_url: String,
     /// Optional authentication token for private repositories.
     pub remote_token: Option<String>,
+    /// Tags a board config must all have to run the job.
+    ///
+    /// Board configs missing any of them are skipped, no tags runs the job on
+    /// every board config.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_tags: Vec<String>,
+    /// Names of the board configs to run the job on.
+    ///
+    /// The other board configs are skipped, no names runs the job on every
+    /// board config having the required tags.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub board_configs: Vec<String>,
+    /// Build and run timeouts of the board configs.
+    #[serde(default, skip_serializing_if = "EjJobTimeouts::is_empty")]
+    pub timeouts: EjJobTimeouts,
+    /// How builders check out the commit.
+    #[serde(default, skip_serializing_if = "EjCheckoutOptions::is_empty")]
+    pub checkout: EjCheckoutOptions,
 }
 impl EjJob {
     pub fn new(
@@ -80,12 +111,68 @@ impl EjJob {
             commit_hash: commit_hash.into(),
             remote_url: remote_url.into(),
             remote_token,
+            required_tags: Vec::new(),
+            board_configs: Vec::new(),
+            timeouts: EjJobTimeouts::default(),
+            checkout: EjCheckoutOptions::default(),
         }
     }
+
+    /// Only runs the job on the board configs having every one of `tags`.
+    pub fn with_required_tags(mut self, tags: Vec<String>) -> Self {
+        self.required_tags = tags;
+        self
+    }
+
+    /// Only runs the job on the board configs named in `board_configs`.
+    pub fn with_board_configs(mut self, board_configs: Vec<String>) -> Self {
+        self.board_configs = board_configs;
+        self
+    }
+
+    /// Limits the duration of the board configs' build and run.
+    pub fn with_timeouts(mut self, timeouts: EjJobTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Changes how builders check out the job's commit.
+    pub fn with_checkout(mut self, checkout: EjCheckoutOptions) -> Self {
+        self.checkout = checkout;
+        self
+    }
+
+    /// Only runs the job on the board configs `target` selects.
+    pub fn with_target(self, target: EjJobTarget) -> Self {
+        self.with_board_configs(target.board_configs)
+            .with_required_tags(target.required_tags)
+    }
+}
+
+/// Board configs a job runs on, the default targets every board config.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_dispatcher_sdk::EjJobTarget;
+///
+/// // Only the "Rpi3 Wayland" board config, if it has the arm64 tag
+/// let target = EjJobTarget {
+///     board_configs: vec![String::from("Rpi3 Wayland")],
+///     required_tags: vec![String::from("arm64")],
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EjJobTarget {
+    /// Names of the board configs to run the job on, empty for every board config.
+    pub board_configs: Vec<String>,
+    /// Tags a board config must all have to run the job.
+    pub required_tags: Vec<String>,
 }
 
 /// Job presentation model.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjJobApi {
     /// Unique job ID.
     pub id: Uuid,
@@ -101,7 +188,43 @@ pub struct EjJobApi {
     pub dispatched_at: Option<DateTime<Utc>>,
     /// When the job finished execution.
     pub finished_at: Option<DateTime<Utc>>,
+    /// Tags a board config must all have to run the job.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_tags: Vec<String>,
+    /// Names of the board configs the job runs on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub board_configs: Vec<String>,
 }
+
+/// Job history query, every criterion is optional.
+///
+/// Jobs are listed oldest first, set `page.sort` to `created_at`,
+/// `dispatched_at` or `finished_at` and `page.order` to change that.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct EjJobQuery {
+    /// Only jobs for this commit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_hash: Option<String>,
+    /// Only jobs for this remote URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_url: Option<String>,
+    /// Only jobs with this status.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<EjJobStatus>,
+    /// Only jobs of this type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_type: Option<EjJobType>,
+    /// Only jobs created at or after this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    /// Only jobs created before this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+    /// Page of the matching jobs to return.
+    #[serde(default)]
+    pub page: EjPageQuery,
+}
+
 impl EjJobApi {
     /// Sort jobs by finished timestamp, with most recently finished first.
     /// Jobs without a finished timestamp are placed at the end.
@@ -128,6 +251,7 @@ impl EjJobApi {
 
 /// Deployable job with assigned ID.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjDeployableJob {
     /// Unique job identifier.
     pub id: Uuid,
@@ -139,6 +263,18 @@ pub struct EjDeployableJob {
     pub remote_url: String,
     /// Optional authentication token for private repositories.
     pub remote_token: Option<String>,
+    /// Tags a board config must all have to run the job.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_tags: Vec<String>,
+    /// Names of the board configs the job runs on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub board_configs: Vec<String>,
+    /// Build and run timeouts of the board configs.
+    #[serde(default, skip_serializing_if = "EjJobTimeouts::is_empty")]
+    pub timeouts: EjJobTimeouts,
+    /// How builders check out the commit.
+    #[serde(default, skip_serializing_if = "EjCheckoutOptions::is_empty")]
+    pub checkout: EjCheckoutOptions,
 }
 
 /// Reason for job cancellation.
@@ -148,6 +284,10 @@ pub enum EjJobCancelReason {
     NoBuilders,
     /// Job exceeded maximum execution time.
     Timeout,
+    /// Connection between the builder and the dispatcher was lost.
+    ConnectionLost,
+    /// A client asked for the cancellation.
+    Requested,
 }
 
 /// Job status updates from the dispatcher.
@@ -169,6 +309,37 @@ pub enum EjJobUpdate {
     BuildFinished(EjBuildResult),
     /// Run phase completed.
     RunFinished(EjRunResult),
+    /// New output produced by a builder while executing the job.
+    LogChunk {
+        /// Board config the output belongs to.
+        board_config: EjBoardConfigApi,
+        /// Output lines, in the order they were produced.
+        lines: Vec<String>,
+    },
+    /// A builder working on the job stopped responding and was disconnected.
+    BuilderLost {
+        /// ID of the builder that was lost.
+        builder_id: Uuid,
+    },
+    /// A single board config of the job was cancelled, the others keep going.
+    BoardCancelled {
+        /// ID of the cancelled board config.
+        board_config_id: Uuid,
+        /// Reason for the cancellation.
+        reason: EjJobCancelReason,
+    },
+}
+
+/// Output produced by a builder for a board config of a running job.
+///
+/// Sent to the clients following the logs of a job, see
+/// [`subscribe_logs`](crate::subscribe_logs::subscribe_logs).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EjLogChunk {
+    /// Board config the output belongs to.
+    pub board_config: EjBoardConfigApi,
+    /// Output lines, in the order they were produced.
+    pub lines: Vec<String>,
 }
 
 /// Build operation result.
@@ -176,6 +347,10 @@ pub enum EjJobUpdate {
 pub struct EjBuildResult {
     /// Build logs per board configuration.
     pub logs: Vec<(EjBoardConfigApi, String)>,
+    /// Board configurations skipped because the job doesn't target them or
+    /// their board is unavailable.
+    #[serde(default)]
+    pub skipped: Vec<EjBoardConfigApi>,
     /// Whether the build was successful.
     pub success: bool,
 }
@@ -187,6 +362,10 @@ pub struct EjRunResult {
     pub logs: Vec<(EjBoardConfigApi, String)>,
     /// Run results per board configuration.
     pub results: Vec<(EjBoardConfigApi, String)>,
+    /// Board configurations skipped because the job doesn't target them or
+    /// their board is unavailable.
+    #[serde(default)]
+    pub skipped: Vec<EjBoardConfigApi>,
     /// Whether the run was successful.
     pub success: bool,
 }
@@ -223,7 +402,14 @@ impl fmt::Display for EjDeployableJob {
             f,
             "Job {} ({}) - Commit: {} from {} {}",
             self.id, self.job_type, self.commit_hash, self.remote_url, token_status
-        )
+        )?;
+        if !self.required_tags.is_empty() {
+            write!(f, " requiring tags [{}]", self.required_tags.join(","))?;
+        }
+        if !self.board_configs.is_empty() {
+            write!(f, " on board configs [{}]", self.board_configs.join(","))?;
+        }
+        Ok(())
     }
 }
 
@@ -245,6 +431,26 @@ impl fmt::Display for EjJobUpdate {
             EjJobUpdate::RunFinished(result) => {
                 write!(f, "{}", result)
             }
+            EjJobUpdate::LogChunk {
+                board_config,
+                lines,
+            } => {
+                write!(
+                    f,
+                    "{} new log line(s) for {}",
+                    lines.len(),
+                    board_config.name
+                )
+            }
+            EjJobUpdate::BuilderLost { builder_id } => {
+                write!(f, "Builder {} stopped responding", builder_id)
+            }
+            EjJobUpdate::BoardCancelled {
+                board_config_id,
+                reason,
+            } => {
+                write!(f, "Board config {} cancelled: {}", board_config_id, reason)
+            }
         }
     }
 }
@@ -254,6 +460,8 @@ impl fmt::Display for EjJobCancelReason {
         match self {
             EjJobCancelReason::NoBuilders => write!(f, "no builders"),
             EjJobCancelReason::Timeout => write!(f, "job timed out"),
+            EjJobCancelReason::ConnectionLost => write!(f, "connection lost"),
+            EjJobCancelReason::Requested => write!(f, "cancellation requested"),
         }
     }
 }
@@ -277,8 +485,25 @@ impl fmt::Display for EjBuildResult {
             writeln!(f, "=======================================")?;
             writeln!(f, "{}", log)?;
         }
-        writeln!(f, "=======================================")
+        writeln!(f, "=======================================")?;
+        write_skipped(f, &self.skipped)
+    }
+}
+
+/// Lists the board configs skipped by a job, if any.
+fn write_skipped(f: &mut fmt::Formatter<'_>, skipped: &[EjBoardConfigApi]) -> fmt::Result {
+    if skipped.is_empty() {
+        return Ok(());
+    }
+    writeln!(
+        f,
+        "Skipped {} board config(s) not targeted by the job or unavailable:",
+        skipped.len()
+    )?;
+    for board in skipped {
+        writeln!(f, "  {}", board)?;
     }
+    Ok(())
 }
 
 impl fmt::Display for EjRunResult {
@@ -315,7 +540,8 @@ impl fmt::Display for EjRunResult {
             writeln!(f, "=======================================")?;
             writeln!(f, "{}", result)?;
         }
-        writeln!(f, "=======================================")
+        writeln!(f, "=======================================")?;
+        write_skipped(f, &self.skipped)
     }
 }
 
@@ -343,6 +569,13 @@ impl fmt::Display for EjJobApi {
             self.finished_at
                 .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
                 .unwrap_or_else(|| "Not finished".to_string())
-        )
+        )?;
+        if !self.required_tags.is_empty() {
+            write!(f, "\n  Required tags: {}", self.required_tags.join(","))?;
+        }
+        if !self.board_configs.is_empty() {
+            write!(f, "\n  Board configs: {}", self.board_configs.join(","))?;
+        }
+        Ok(())
     }
 }
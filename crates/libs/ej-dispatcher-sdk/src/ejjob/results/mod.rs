@@ -11,28 +11,43 @@ pub type EjBoardConfigId = Uuid;
 
 /// Build result from a specific builder.
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjBuilderBuildResult {
     /// Job identifier.
     pub job_id: Uuid,
     /// Builder identifier.
     pub builder_id: Uuid,
     /// Build logs per board configuration.
+    #[cfg_attr(feature = "utoipa", schema(value_type = HashMap<Uuid, Vec<String>>))]
     pub logs: HashMap<EjBoardConfigId, Vec<String>>,
+    /// Board configurations skipped because the job doesn't target them or
+    /// their board is unavailable.
+    #[serde(default)]
+    #[cfg_attr(feature = "utoipa", schema(value_type = Vec<Uuid>))]
+    pub skipped: Vec<EjBoardConfigId>,
     /// Whether the build was successful.
     pub successful: bool,
 }
 
 /// Run result from a specific builder.
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct EjBuilderRunResult {
     /// Job identifier.
     pub job_id: Uuid,
     /// Builder identifier.
     pub builder_id: Uuid,
     /// Run logs per board configuration.
+    #[cfg_attr(feature = "utoipa", schema(value_type = HashMap<Uuid, Vec<String>>))]
     pub logs: HashMap<EjBoardConfigId, Vec<String>>,
     /// Run results per board configuration.
+    #[cfg_attr(feature = "utoipa", schema(value_type = HashMap<Uuid, String>))]
     pub results: HashMap<EjBoardConfigId, String>,
+    /// Board configurations skipped because the job doesn't target them or
+    /// their board is unavailable.
+    #[serde(default)]
+    #[cfg_attr(feature = "utoipa", schema(value_type = Vec<Uuid>))]
+    pub skipped: Vec<EjBoardConfigId>,
     /// Whether the run was successful.
     pub successful: bool,
 }
@@ -0,0 +1,92 @@
+//! Job log and result search types.
+//!
+//! Searching lets clients find which jobs produced a given error message
+//! without downloading every log.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Where a search match was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum EjJobSearchSource {
+    /// The output of a job.
+    Log,
+    /// The result of a run job.
+    Result,
+}
+
+/// Job search query.
+///
+/// `q` uses the web search syntax: words must all appear, `"quoted phrases"`
+/// must appear as written, `or` matches either side and `-word` excludes
+/// matches containing `word`. Every other criterion is optional.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "utoipa", into_params(parameter_in = Query))]
+pub struct EjJobSearchQuery {
+    /// Text to search for.
+    pub q: String,
+    /// Only search logs or only search results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EjJobSearchSource>,
+    /// Only matches from this job.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<Uuid>,
+    /// Only matches from jobs for this commit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_hash: Option<String>,
+    /// Only matches from this board config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub board_config_id: Option<Uuid>,
+    /// Only matches stored at or after this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    /// Only matches stored before this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+    /// Maximum number of matches returned, newest first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+/// A job log or result matching a search.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjJobSearchHit {
+    /// The job the match belongs to.
+    pub job_id: Uuid,
+    /// The commit the job ran for.
+    pub commit_hash: String,
+    /// The board config the match is associated with.
+    pub board_config_id: Uuid,
+    /// Whether the match was found in a log or a result.
+    pub source: EjJobSearchSource,
+    /// Excerpts of the matching text, matched words surrounded by `**`.
+    pub snippet: String,
+    /// When the matching text was stored.
+    pub created_at: DateTime<Utc>,
+}
+
+impl fmt::Display for EjJobSearchSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EjJobSearchSource::Log => write!(f, "log"),
+            EjJobSearchSource::Result => write!(f, "result"),
+        }
+    }
+}
+
+impl fmt::Display for EjJobSearchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Job {} ({}) {} of board config {}: {}",
+            self.job_id, self.commit_hash, self.source, self.board_config_id, self.snippet
+        )
+    }
+}
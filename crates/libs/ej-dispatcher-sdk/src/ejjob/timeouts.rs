@@ -0,0 +1,151 @@
+//! Time limits of a job and of its build and run phases.
+
+use std::{collections::HashMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum durations of a board config's build and run scripts.
+///
+/// Scripts running past them are killed and the job fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjPhaseTimeouts {
+    /// Maximum duration of the build script, unlimited if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "utoipa", schema(value_type = Option<Object>))]
+    pub build: Option<Duration>,
+    /// Maximum duration of the run script, unlimited if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "utoipa", schema(value_type = Option<Object>))]
+    pub run: Option<Duration>,
+}
+
+/// Build and run timeouts of the board configs of a job.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_dispatcher_sdk::ejjob::timeouts::{EjJobTimeouts, EjPhaseTimeouts};
+/// use std::time::Duration;
+///
+/// let timeouts = EjJobTimeouts::default()
+///     .with_build(Duration::from_secs(600))
+///     .with_run(Duration::from_secs(60))
+///     .with_board(
+///         "Rpi3 Wayland",
+///         EjPhaseTimeouts {
+///             run: Some(Duration::from_secs(300)),
+///             ..Default::default()
+///         },
+///     );
+///
+/// let rpi3 = timeouts.for_board("Rpi3 Wayland");
+/// assert_eq!(rpi3.build, Some(Duration::from_secs(600)));
+/// assert_eq!(rpi3.run, Some(Duration::from_secs(300)));
+/// assert_eq!(timeouts.for_board("x86").run, Some(Duration::from_secs(60)));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjJobTimeouts {
+    /// Timeouts of every board config.
+    #[serde(flatten)]
+    pub phases: EjPhaseTimeouts,
+    /// Timeouts overriding `phases` for some board configs, by board config name.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub boards: HashMap<String, EjPhaseTimeouts>,
+}
+
+impl EjJobTimeouts {
+    /// Limits the build of every board config to `timeout`.
+    pub fn with_build(mut self, timeout: Duration) -> Self {
+        self.phases.build = Some(timeout);
+        self
+    }
+
+    /// Limits the run of every board config to `timeout`.
+    pub fn with_run(mut self, timeout: Duration) -> Self {
+        self.phases.run = Some(timeout);
+        self
+    }
+
+    /// Overrides the timeouts of the board config named `board_config`.
+    ///
+    /// The timeouts unset in `timeouts` keep the ones of every board config.
+    pub fn with_board(
+        mut self,
+        board_config: impl Into<String>,
+        timeouts: EjPhaseTimeouts,
+    ) -> Self {
+        self.boards.insert(board_config.into(), timeouts);
+        self
+    }
+
+    /// Timeouts of the board config named `board_config`.
+    pub fn for_board(&self, board_config: &str) -> EjPhaseTimeouts {
+        match self.boards.get(board_config) {
+            Some(timeouts) => EjPhaseTimeouts {
+                build: timeouts.build.or(self.phases.build),
+                run: timeouts.run.or(self.phases.run),
+            },
+            None => self.phases,
+        }
+    }
+
+    /// Whether no board config has a timeout.
+    pub fn is_empty(&self) -> bool {
+        self.phases == EjPhaseTimeouts::default() && self.boards.is_empty()
+    }
+}
+
+/// Time limits of a dispatched job.
+///
+/// A [`Duration`] converts to a job limit without build and run timeouts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EjDispatchTimeouts {
+    /// Maximum duration of the whole job, the dispatcher cancels it past it.
+    pub job: Duration,
+    /// Build and run timeouts of the board configs.
+    pub phases: EjJobTimeouts,
+}
+
+impl EjDispatchTimeouts {
+    pub fn new(job: Duration, phases: EjJobTimeouts) -> Self {
+        Self { job, phases }
+    }
+}
+
+impl From<Duration> for EjDispatchTimeouts {
+    fn from(job: Duration) -> Self {
+        Self::new(job, EjJobTimeouts::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeouts_serialization() {
+        let timeouts = EjJobTimeouts::default()
+            .with_build(Duration::from_secs(600))
+            .with_board(
+                "Rpi3 Wayland",
+                EjPhaseTimeouts {
+                    run: Some(Duration::from_secs(30)),
+                    ..Default::default()
+                },
+            );
+        let json = serde_json::to_value(&timeouts).unwrap();
+        assert_eq!(json["build"]["secs"], 600);
+        assert!(json.get("run").is_none());
+        assert_eq!(json["boards"]["Rpi3 Wayland"]["run"]["secs"], 30);
+
+        let deserialized: EjJobTimeouts = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized, timeouts);
+        assert!(
+            serde_json::from_str::<EjJobTimeouts>("{}")
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
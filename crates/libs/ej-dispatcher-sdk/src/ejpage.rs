@@ -0,0 +1,89 @@
+//! Pagination and sorting of list endpoints.
+//!
+//! List endpoints accept `page`, `per_page`, `sort` and `order` query
+//! parameters and report the number of matching items in the
+//! `X-Total-Count` response header.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Direction items are sorted in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum EjSortOrder {
+    /// Smallest first.
+    #[default]
+    Asc,
+    /// Largest first.
+    Desc,
+}
+
+/// Page requested from a list endpoint, every parameter is optional.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "utoipa", into_params(parameter_in = Query))]
+pub struct EjPageQuery {
+    /// Page number, starting at 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    /// Number of items per page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_page: Option<u32>,
+    /// Field the items are sorted by, each endpoint documents the fields it supports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    /// Direction the items are sorted in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<EjSortOrder>,
+}
+
+impl EjSortOrder {
+    /// Every sort order.
+    pub const ALL: [EjSortOrder; 2] = [EjSortOrder::Asc, EjSortOrder::Desc];
+
+    /// Name of the sort order as it's serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_dispatcher_sdk::ejpage::EjSortOrder;
+    ///
+    /// assert_eq!(EjSortOrder::Desc.as_str(), "desc");
+    /// assert_eq!(EjSortOrder::from_name("asc"), Some(EjSortOrder::Asc));
+    /// ```
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EjSortOrder::Asc => "asc",
+            EjSortOrder::Desc => "desc",
+        }
+    }
+
+    /// Parses a sort order from its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|order| order.as_str() == name)
+    }
+}
+
+impl fmt::Display for EjSortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_orders_round_trip() {
+        for order in EjSortOrder::ALL {
+            let json = serde_json::to_string(&order).unwrap();
+            assert_eq!(json, format!("\"{}\"", order.as_str()));
+            assert_eq!(serde_json::from_str::<EjSortOrder>(&json).unwrap(), order);
+            assert_eq!(EjSortOrder::from_name(order.as_str()), Some(order));
+        }
+        assert_eq!(EjSortOrder::from_name("random"), None);
+    }
+}
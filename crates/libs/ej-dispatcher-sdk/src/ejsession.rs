@@ -0,0 +1,41 @@
+//! Client session types.
+//!
+//! A session starts when a client logs in and lasts as long as its refresh
+//! tokens keep being exchanged. Revoking a session logs it out everywhere
+//! its tokens are used.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Active client session.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjSessionApi {
+    /// Unique session identifier.
+    pub id: Uuid,
+    /// When the session started.
+    pub created_at: DateTime<Utc>,
+    /// When the session was last refreshed.
+    pub last_refreshed_at: DateTime<Utc>,
+    /// When the session ends unless it's refreshed.
+    pub expires_at: DateTime<Utc>,
+    /// Whether this is the session the request was made with.
+    pub current: bool,
+}
+
+impl fmt::Display for EjSessionApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Session {} started {} - last refreshed {}",
+            self.id, self.created_at, self.last_refreshed_at
+        )?;
+        if self.current {
+            write!(f, " (current)")?;
+        }
+        Ok(())
+    }
+}
@@ -1,14 +1,22 @@
 //! Unix socket message types for dispatcher communication.
 
-use std::{fmt, time::Duration};
+use std::{fmt, path::PathBuf, time::Duration};
 
+use ej_auth::socket_signature::SocketKey;
+use ej_config::ej_config::EjConfig;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    EjRunResult,
+    EjBuildResult, EjRunResult,
+    ejbuilder::EjBuilderInfo,
     ejclient::{EjClientApi, EjClientPost},
-    ejjob::{EjDeployableJob, EjJob, EjJobApi, EjJobUpdate},
+    ejjob::{
+        EjDeployableJob, EjJob, EjJobApi, EjJobQuery, EjJobUpdate, EjLogChunk,
+        search::{EjJobSearchHit, EjJobSearchQuery},
+    },
+    ejstats::{EjStats, EjStatsQuery},
+    ejtoken::EjJwtKeys,
 };
 
 /// Messages sent from client to dispatcher via Unix socket.
@@ -26,8 +34,87 @@ pub enum EjSocketClientMessage {
     /// Fetch jobs associated to a commit hash
     FetchJobs { commit_hash: String },
 
+    /// Fetch a page of the jobs matching a query
+    QueryJobs(EjJobQuery),
+
+    /// Fetch the job associated to this id
+    FetchJob { job_id: Uuid },
+
     /// Fetch job results associated to this id
     FetchJobResults { job_id: Uuid },
+
+    /// Fetch the build logs of the job associated to this id
+    FetchBuildResult { job_id: Uuid },
+
+    /// Search the logs and results of every job
+    SearchJobs(EjJobSearchQuery),
+
+    /// Fetch statistics about the finished jobs
+    FetchStats(EjStatsQuery),
+
+    /// List every registered builder
+    ListBuilders,
+
+    /// List the builders currently connected, with the job they're working on
+    ListConnectedBuilders,
+
+    /// Fetch the latest config uploaded by a builder
+    FetchBuilderConfig { builder_id: Uuid },
+
+    /// Follow the output of a running or queued job until it finishes
+    SubscribeLogs { job_id: Uuid },
+
+    /// Sign new tokens with a key read from the dispatcher filesystem, the
+    /// previous keys keep verifying the tokens they signed
+    RotateJwtKey {
+        kid: String,
+        key_path: PathBuf,
+        public_key_path: Option<PathBuf>,
+    },
+
+    /// Stop accepting the tokens signed with a previous key
+    RetireJwtKey { kid: String },
+}
+
+/// Client message signed with a shared key.
+///
+/// Sent instead of the bare [`EjSocketClientMessage`] when a socket key is
+/// configured, see [`ej_auth::socket_signature`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EjSignedSocketMessage {
+    /// The serialized [`EjSocketClientMessage`].
+    pub payload: String,
+    /// HMAC-SHA256 of `payload`.
+    pub signature: String,
+}
+
+impl EjSocketClientMessage {
+    /// Serializes the message as sent over the socket, without the trailing newline.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Key to sign the message with, sent unsigned if `None`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_auth::socket_signature::SocketKey;
+    /// use ej_dispatcher_sdk::ejsocket_message::EjSocketClientMessage;
+    ///
+    /// let message = EjSocketClientMessage::FetchJobs { commit_hash: "abc123".to_string() };
+    /// let line = message.encode(Some(&SocketKey::new("shared secret"))).unwrap();
+    /// assert!(line.contains("signature"));
+    /// ```
+    pub fn encode(&self, key: Option<&SocketKey>) -> serde_json::Result<String> {
+        let payload = serde_json::to_string(self)?;
+        match key {
+            Some(key) => serde_json::to_string(&EjSignedSocketMessage {
+                signature: key.sign(payload.as_bytes()),
+                payload,
+            }),
+            None => Ok(payload),
+        }
+    }
 }
 
 /// Messages sent from dispatcher to client via Unix socket.
@@ -40,9 +127,31 @@ pub enum EjSocketServerMessage {
     /// Job status update.
     JobUpdate(EjJobUpdate),
     /// A list of jobs. Response of `EjSocketClientMessage::FetchJobs`
+    /// and `EjSocketClientMessage::QueryJobs`
     Jobs(Vec<EjJobApi>),
+    /// A job. Response of `EjSocketClientMessage::FetchJob`
+    Job(EjJobApi),
     /// A run result. Response of `EjSocketClientMessage::FetchJobResults`
     RunResult(EjRunResult),
+    /// A build result. Response of `EjSocketClientMessage::FetchBuildResult`
+    BuildResult(EjBuildResult),
+    /// Job search matches, newest first. Response of `EjSocketClientMessage::SearchJobs`
+    SearchResults(Vec<EjJobSearchHit>),
+    /// Job statistics. Response of `EjSocketClientMessage::FetchStats`
+    Stats(EjStats),
+    /// Registered builders. Response of `EjSocketClientMessage::ListBuilders`
+    /// and `EjSocketClientMessage::ListConnectedBuilders`
+    Builders(Vec<EjBuilderInfo>),
+    /// Latest config uploaded by a builder, `None` if it never uploaded one.
+    /// Response of `EjSocketClientMessage::FetchBuilderConfig`
+    BuilderConfig(Option<EjConfig>),
+    /// Log subscription successful, followed by the `LogChunk`s of the job.
+    /// Response of `EjSocketClientMessage::SubscribeLogs`
+    SubscribeLogsOk,
+    /// New output of the job the client subscribed to.
+    LogChunk(EjLogChunk),
+    /// JWT keys after a rotation or retirement.
+    JwtKeys(EjJwtKeys),
     /// General error message.
     Error(String),
 }
@@ -70,7 +179,65 @@ impl fmt::Display for EjSocketServerMessage {
                 writeln!(f, "== Jobs ==")?;
                 Ok(())
             }
+            EjSocketServerMessage::Job(job) => write!(f, "{}", job),
             EjSocketServerMessage::RunResult(run_result) => write!(f, "{}", run_result),
+            EjSocketServerMessage::BuildResult(build_result) => write!(f, "{}", build_result),
+            EjSocketServerMessage::SearchResults(hits) => {
+                for hit in hits {
+                    writeln!(f, "{}", hit)?;
+                }
+                Ok(())
+            }
+            EjSocketServerMessage::Stats(stats) => write!(f, "{}", stats),
+            EjSocketServerMessage::Builders(builders) => {
+                for builder in builders {
+                    writeln!(f, "{}", builder)?;
+                }
+                Ok(())
+            }
+            EjSocketServerMessage::BuilderConfig(Some(config)) => write!(
+                f,
+                "Config {} with {} boards",
+                config.global.version,
+                config.boards.len()
+            ),
+            EjSocketServerMessage::BuilderConfig(None) => write!(f, "No config uploaded"),
+            EjSocketServerMessage::SubscribeLogsOk => write!(f, "Subscribed to the job logs"),
+            EjSocketServerMessage::LogChunk(chunk) => write!(
+                f,
+                "{} new log line(s) for {}",
+                chunk.lines.len(),
+                chunk.board_config.name
+            ),
+            EjSocketServerMessage::JwtKeys(keys) => write!(f, "{}", keys),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_signed_message() {
+        let key = SocketKey::new("shared secret");
+        let message = EjSocketClientMessage::FetchJobs {
+            commit_hash: String::from("abc123"),
+        };
+
+        let line = message.encode(Some(&key)).unwrap();
+        let signed: EjSignedSocketMessage = serde_json::from_str(&line).unwrap();
+        assert!(
+            key.verify(signed.payload.as_bytes(), &signed.signature)
+                .is_ok()
+        );
+        assert_eq!(signed.payload, message.encode(None).unwrap());
+
+        let other_key = SocketKey::new("another secret");
+        assert!(
+            other_key
+                .verify(signed.payload.as_bytes(), &signed.signature)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Job statistics types.
+//!
+//! Statistics are computed over the jobs that ran to completion, successfully
+//! or not. Cancelled and unfinished jobs aren't counted.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Job statistics query, every criterion is optional.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "utoipa", into_params(parameter_in = Query))]
+pub struct EjStatsQuery {
+    /// Only jobs dispatched at or after this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    /// Only jobs dispatched before this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+    /// Only jobs for these commits, comma separated.
+    ///
+    /// Pass the output of `git rev-list --abbrev-commit a..b` joined with
+    /// commas to restrict the statistics to a commit range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commits: Option<String>,
+    /// Maximum number of entries returned in each list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+/// Duration statistics of the jobs run on a board config.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjBoardConfigStats {
+    /// The board config.
+    pub board_config_id: Uuid,
+    /// Name of the board config.
+    pub name: String,
+    /// Number of jobs run on the board config.
+    pub jobs: u64,
+    /// Average job duration in seconds.
+    pub average_seconds: f64,
+    /// Median job duration in seconds.
+    pub p50_seconds: f64,
+    /// 95th percentile of the job durations in seconds.
+    pub p95_seconds: f64,
+}
+
+/// Outcome of the jobs run for a commit.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjCommitStats {
+    /// The commit.
+    pub commit_hash: String,
+    /// Number of jobs run for the commit.
+    pub jobs: u64,
+    /// Number of those jobs that succeeded.
+    pub successful: u64,
+    /// Share of the jobs that succeeded, between 0 and 1.
+    pub success_rate: f64,
+    /// When the last of those jobs finished.
+    pub last_finished_at: Option<DateTime<Utc>>,
+}
+
+/// Number of jobs run by a builder.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjBuilderStats {
+    /// The builder.
+    pub builder_id: Uuid,
+    /// Number of jobs the builder ran.
+    pub jobs: u64,
+    /// When the builder was last dispatched a job.
+    pub last_dispatched_at: Option<DateTime<Utc>>,
+}
+
+/// Job statistics.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjStats {
+    /// Job durations per board config, busiest first.
+    pub board_configs: Vec<EjBoardConfigStats>,
+    /// Success rate per commit, most recently finished first.
+    pub commits: Vec<EjCommitStats>,
+    /// Jobs run per builder, busiest first.
+    pub builders: Vec<EjBuilderStats>,
+}
+
+impl fmt::Display for EjStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "== Board configs ==")?;
+        for stats in &self.board_configs {
+            writeln!(
+                f,
+                "{} ({}) - {} job(s), avg {:.1}s, p50 {:.1}s, p95 {:.1}s",
+                stats.name,
+                stats.board_config_id,
+                stats.jobs,
+                stats.average_seconds,
+                stats.p50_seconds,
+                stats.p95_seconds
+            )?;
+        }
+        writeln!(f, "== Commits ==")?;
+        for stats in &self.commits {
+            writeln!(
+                f,
+                "{} - {}/{} job(s) succeeded ({:.0}%)",
+                stats.commit_hash,
+                stats.successful,
+                stats.jobs,
+                stats.success_rate * 100.0
+            )?;
+        }
+        writeln!(f, "== Builders ==")?;
+        for stats in &self.builders {
+            write!(f, "{} - {} job(s)", stats.builder_id, stats.jobs)?;
+            if let Some(last_dispatched_at) = stats.last_dispatched_at {
+                write!(f, ", last dispatched {last_dispatched_at}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
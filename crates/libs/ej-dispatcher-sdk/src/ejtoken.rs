@@ -0,0 +1,78 @@
+//! Access and refresh token types.
+//!
+//! Access tokens are short-lived JWTs sent with every request. Refresh tokens
+//! are long-lived opaque tokens exchanged for a new pair of tokens once the
+//! access token expires. Every refresh token can only be used once. Access
+//! tokens can be revoked as well if they leak.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Request carrying a refresh token, used to refresh or revoke it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjRefreshTokenRequest {
+    /// The refresh token.
+    pub refresh_token: String,
+}
+
+/// Request revoking an access token before it expires.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjAccessTokenRevokeRequest {
+    /// The JWT access token to revoke.
+    pub access_token: String,
+}
+
+/// Access token along with the refresh token used to renew it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EjTokens {
+    /// JWT access token.
+    pub access_token: String,
+    /// Token type (usually "Bearer").
+    pub token_type: String,
+    /// Number of seconds the access token is valid for.
+    pub expires_in: i64,
+    /// Refresh token, valid for a single refresh.
+    pub refresh_token: String,
+}
+
+/// Keys the dispatcher signs and verifies JWT tokens with.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EjJwtKeys {
+    /// ID of the key new tokens are signed with.
+    pub current: String,
+    /// IDs of every key tokens are verified with, sorted.
+    pub key_ids: Vec<String>,
+}
+
+impl fmt::Display for EjJwtKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Signing with {}, verifying with {}",
+            self.current,
+            self.key_ids.join(", ")
+        )
+    }
+}
+
+impl EjRefreshTokenRequest {
+    /// Create a new refresh token request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_dispatcher_sdk::ejtoken::EjRefreshTokenRequest;
+    ///
+    /// let request = EjRefreshTokenRequest::new("refresh_token");
+    /// assert_eq!(request.refresh_token, "refresh_token");
+    /// ```
+    pub fn new(refresh_token: impl Into<String>) -> Self {
+        Self {
+            refresh_token: refresh_token.into(),
+        }
+    }
+}
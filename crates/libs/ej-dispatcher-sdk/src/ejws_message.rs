@@ -1,14 +1,181 @@
 //! WebSocket message types for builder communication.
 
-use std::{fmt, time::Duration};
+use std::{
+    fmt,
+    io::{Read, Write},
+    time::Duration,
+};
 
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::ejbuilder::EjBuilderCapabilities;
 use crate::ejjob::{EjDeployableJob, EjJobCancelReason};
+use crate::prelude::*;
+
+/// Version of the WebSocket protocol spoken between builders and the dispatcher.
+///
+/// Bump it whenever [`EjWsServerMessage`] or [`EjWsClientMessage`] change in a way
+/// that peers built from an older release can't parse.
+pub const EJ_WS_PROTOCOL_VERSION: u32 = 6;
+
+/// HTTP header carrying the protocol version in the WebSocket upgrade request and response.
+pub const EJ_WS_PROTOCOL_VERSION_HEADER: &str = "x-ej-protocol-version";
+
+/// HTTP header used to negotiate the compression of WebSocket payloads.
+///
+/// Builders offer the compressions they support in the upgrade request and the
+/// dispatcher answers with the one it picked. Without an answer nothing is compressed.
+pub const EJ_WS_COMPRESSION_HEADER: &str = "x-ej-compression";
+
+/// Payloads smaller than this are always sent uncompressed.
+pub const EJ_WS_COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Maximum size of a gzipped message once decompressed, the maximum size of
+/// WebSocket messages, so a small gzip bomb can't exhaust the receiver's memory.
+pub const EJ_WS_MAX_DECOMPRESSED_SIZE: u64 = 64 << 20;
+
+/// Maximum size of the artifact data carried by a single binary frame.
+pub const EJ_WS_ARTIFACT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// First byte of binary frames carrying a gzipped message.
+const BINARY_MESSAGE: u8 = 0;
+
+/// First byte of binary frames carrying a piece of an artifact.
+const BINARY_ARTIFACT_CHUNK: u8 = 1;
+
+/// Compression applied to large WebSocket payloads.
+///
+/// Compressed payloads are sent as binary frames while uncompressed ones are
+/// sent as text frames, so the receiver doesn't need to know what was negotiated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EjWsCompression {
+    /// Payloads are sent as is.
+    #[default]
+    None,
+    /// Large payloads are gzipped.
+    Gzip,
+}
+
+/// Serialized WebSocket payload, ready to be sent as a text or binary frame.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EjWsPayload {
+    /// Uncompressed JSON.
+    Text(String),
+    /// Binary frame, see [`EjWsBinary`] for its content.
+    Binary(Vec<u8>),
+}
+
+impl EjWsPayload {
+    /// Creates the binary frame carrying a piece of an artifact.
+    ///
+    /// # Arguments
+    ///
+    /// * `artifact_id` - ID announced in [`EjWsClientMessage::ArtifactStart`]
+    /// * `data` - Next bytes of the artifact, at most [`EJ_WS_ARTIFACT_CHUNK_SIZE`]
+    pub fn artifact_chunk(artifact_id: Uuid, data: &[u8]) -> Self {
+        let mut frame = Vec::with_capacity(1 + 16 + data.len());
+        frame.push(BINARY_ARTIFACT_CHUNK);
+        frame.extend_from_slice(artifact_id.as_bytes());
+        frame.extend_from_slice(data);
+        Self::Binary(frame)
+    }
+}
+
+/// Content of a binary WebSocket frame.
+///
+/// The first byte of every binary frame tells what the rest of the frame carries.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EjWsBinary {
+    /// JSON message that was gzipped because of its size.
+    Message(String),
+    /// Piece of an artifact announced with [`EjWsClientMessage::ArtifactStart`].
+    ArtifactChunk {
+        /// ID of the artifact this piece belongs to.
+        artifact_id: Uuid,
+        /// Next bytes of the artifact.
+        data: Vec<u8>,
+    },
+}
+
+impl EjWsBinary {
+    /// Decodes the content of a binary frame.
+    ///
+    /// Gzipped messages larger than [`EJ_WS_MAX_DECOMPRESSED_SIZE`] once
+    /// decompressed are rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Content of the binary frame
+    pub fn decode(frame: &[u8]) -> Result<Self> {
+        match frame.split_first() {
+            Some((&BINARY_MESSAGE, data)) => {
+                let mut payload = String::new();
+                GzDecoder::new(data)
+                    .take(EJ_WS_MAX_DECOMPRESSED_SIZE + 1)
+                    .read_to_string(&mut payload)?;
+                if payload.len() as u64 > EJ_WS_MAX_DECOMPRESSED_SIZE {
+                    return Err(Error::WsMessageTooLarge);
+                }
+                Ok(Self::Message(payload))
+            }
+            Some((&BINARY_ARTIFACT_CHUNK, data)) if data.len() >= 16 => {
+                let (artifact_id, data) = data.split_at(16);
+                Ok(Self::ArtifactChunk {
+                    artifact_id: Uuid::from_slice(artifact_id)
+                        .map_err(|_| Error::InvalidWsBinaryFrame)?,
+                    data: data.to_vec(),
+                })
+            }
+            _ => Err(Error::InvalidWsBinaryFrame),
+        }
+    }
+}
+
+impl EjWsCompression {
+    /// Picks the compression to use from the ones offered by a peer.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - Comma separated list of compressions, as found in [`EJ_WS_COMPRESSION_HEADER`]
+    pub fn negotiate(offer: Option<&str>) -> Self {
+        offer
+            .into_iter()
+            .flat_map(|offer| offer.split(','))
+            .map(str::trim)
+            .find_map(|name| match name {
+                "gzip" => Some(Self::Gzip),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Value to send in the [`EJ_WS_COMPRESSION_HEADER`] header.
+    pub fn header_value(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Gzip => "gzip",
+        }
+    }
+
+    /// Prepares a serialized message to be sent, compressing it if it's large enough.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - JSON serialized message
+    pub fn compress(self, payload: String) -> Result<EjWsPayload> {
+        if self == Self::None || payload.len() < EJ_WS_COMPRESSION_THRESHOLD {
+            return Ok(EjWsPayload::Text(payload));
+        }
+        let mut encoder = GzEncoder::new(vec![BINARY_MESSAGE], Compression::default());
+        encoder.write_all(payload.as_bytes())?;
+        Ok(EjWsPayload::Binary(encoder.finish()?))
+    }
+}
 
 /// Messages sent from dispatcher to builder via WebSocket.
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EjWsServerMessage {
     /// Build job assignment.
     Build(EjDeployableJob),
@@ -16,10 +183,247 @@ pub enum EjWsServerMessage {
     BuildAndRun(EjDeployableJob),
     /// Cancel job with reason and ID.
     Cancel(EjJobCancelReason, Uuid),
+    /// Message addressed to a single board config of the builder.
+    ///
+    /// Lets the dispatcher act on one board config while the builder's other
+    /// board configs keep going, e.g. cancelling the run of a single board.
+    /// Builders currently only act on board-scoped [`EjWsServerMessage::Cancel`].
+    Board {
+        /// ID of the board config the message is addressed to.
+        board_config_id: Uuid,
+        /// The board-scoped message.
+        message: Box<EjWsServerMessage>,
+    },
     /// Close WebSocket connection.
     Close,
 }
 
+impl EjWsServerMessage {
+    /// Scopes a message to a single board config.
+    ///
+    /// # Arguments
+    ///
+    /// * `board_config_id` - ID of the board config the message is addressed to
+    /// * `message` - The message to scope
+    pub fn for_board(board_config_id: Uuid, message: EjWsServerMessage) -> Self {
+        Self::Board {
+            board_config_id,
+            message: Box::new(message),
+        }
+    }
+
+    /// Whether the builder must acknowledge this message.
+    ///
+    /// Job assignments and cancellations are retransmitted until acknowledged.
+    pub fn requires_ack(&self) -> bool {
+        match self {
+            Self::Board { message, .. } => message.requires_ack(),
+            Self::Close => false,
+            _ => true,
+        }
+    }
+}
+
+/// Frame carrying an [`EjWsServerMessage`] on the wire.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EjWsServerEnvelope {
+    /// ID the builder must acknowledge with [`EjWsClientMessage::Ack`], if any.
+    ///
+    /// A retransmitted message keeps its ID so builders can ignore duplicates.
+    pub id: Option<Uuid>,
+    /// The message itself.
+    pub message: EjWsServerMessage,
+}
+
+impl EjWsServerEnvelope {
+    /// Wraps a message, assigning it an ID if it must be acknowledged.
+    pub fn new(message: EjWsServerMessage) -> Self {
+        Self {
+            id: message.requires_ack().then(Uuid::new_v4),
+            message,
+        }
+    }
+}
+
 /// Messages sent from builder to dispatcher via WebSocket.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
-pub enum EjWsClientMessage {}
+pub enum EjWsClientMessage {
+    /// Job the builder is currently executing, if any.
+    ///
+    /// Sent right after the WebSocket connection is established so the dispatcher
+    /// can re-adopt work started before a reconnection.
+    JobState {
+        /// ID of the job in progress.
+        job_id: Option<Uuid>,
+    },
+    /// Acknowledges the reception of an [`EjWsServerEnvelope`].
+    Ack {
+        /// ID of the received envelope.
+        message_id: Uuid,
+    },
+    /// Boards, tags and parallel capacity of the builder.
+    ///
+    /// Sent right after [`EjWsClientMessage::JobState`] when the connection is established.
+    Capabilities(EjBuilderCapabilities),
+    /// New output produced while executing a job.
+    LogChunk {
+        /// ID of the job producing the output.
+        job_id: Uuid,
+        /// Board config the output belongs to.
+        board_config_id: Uuid,
+        /// Output lines, in the order they were produced.
+        lines: Vec<String>,
+    },
+    /// Start of an artifact upload.
+    ///
+    /// The artifact content follows in binary frames, see [`EjWsPayload::artifact_chunk`].
+    ArtifactStart {
+        /// ID chosen by the builder to identify the upload.
+        artifact_id: Uuid,
+        /// ID of the job that produced the artifact.
+        job_id: Uuid,
+        /// Board config that produced the artifact.
+        board_config_id: Uuid,
+        /// File name of the artifact.
+        name: String,
+        /// Size of the artifact in bytes.
+        size: u64,
+    },
+    /// End of an artifact upload, every chunk has been sent.
+    ArtifactEnd {
+        /// ID of the uploaded artifact.
+        artifact_id: Uuid,
+    },
+    /// Boards the jobs of the builder currently have exclusive access to.
+    ///
+    /// Sent when the connection is established and whenever they change. The
+    /// builder enforces the locks itself, the dispatcher only reports them.
+    BoardLocks {
+        /// IDs of the locked boards.
+        boards: Vec<Uuid>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_compression() {
+        assert_eq!(EjWsCompression::negotiate(None), EjWsCompression::None);
+        assert_eq!(
+            EjWsCompression::negotiate(Some("br")),
+            EjWsCompression::None
+        );
+        assert_eq!(
+            EjWsCompression::negotiate(Some("br, gzip")),
+            EjWsCompression::Gzip
+        );
+    }
+
+    #[test]
+    fn test_small_payloads_are_not_compressed() {
+        let payload = String::from("{}");
+        assert_eq!(
+            EjWsCompression::Gzip.compress(payload.clone()).unwrap(),
+            EjWsPayload::Text(payload)
+        );
+    }
+
+    #[test]
+    fn test_gzip_bomb_is_rejected() {
+        let mut encoder = GzEncoder::new(vec![BINARY_MESSAGE], Compression::best());
+        let zeros = vec![b'0'; 1 << 20];
+        for _ in 0..=(EJ_WS_MAX_DECOMPRESSED_SIZE >> 20) {
+            encoder.write_all(&zeros).unwrap();
+        }
+        let frame = encoder.finish().unwrap();
+        assert!(frame.len() < 1 << 20);
+        assert!(matches!(
+            EjWsBinary::decode(&frame),
+            Err(Error::WsMessageTooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_large_payloads_round_trip() {
+        let message = EjWsClientMessage::LogChunk {
+            job_id: Uuid::new_v4(),
+            board_config_id: Uuid::new_v4(),
+            lines: vec![String::from("[ 42%] Building C object main.c.o\n"); 100],
+        };
+        let payload = serde_json::to_string(&message).unwrap();
+
+        assert_eq!(
+            EjWsCompression::None.compress(payload.clone()).unwrap(),
+            EjWsPayload::Text(payload.clone())
+        );
+
+        let EjWsPayload::Binary(data) = EjWsCompression::Gzip.compress(payload.clone()).unwrap()
+        else {
+            panic!("Expected a compressed payload");
+        };
+        assert!(data.len() < payload.len());
+
+        let EjWsBinary::Message(decompressed) = EjWsBinary::decode(&data).unwrap() else {
+            panic!("Expected a compressed message");
+        };
+        let decoded: EjWsClientMessage = serde_json::from_str(&decompressed).unwrap();
+        assert_eq!(decoded, message);
+    }
+
+    #[test]
+    fn test_artifact_chunk_round_trip() {
+        let artifact_id = Uuid::new_v4();
+        let EjWsPayload::Binary(frame) = EjWsPayload::artifact_chunk(artifact_id, b"\x7fELF")
+        else {
+            panic!("Expected a binary frame");
+        };
+        assert_eq!(
+            EjWsBinary::decode(&frame).unwrap(),
+            EjWsBinary::ArtifactChunk {
+                artifact_id,
+                data: b"\x7fELF".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_binary_frames_are_rejected() {
+        assert!(EjWsBinary::decode(&[]).is_err());
+        assert!(EjWsBinary::decode(&[BINARY_ARTIFACT_CHUNK, 1, 2, 3]).is_err());
+        assert!(EjWsBinary::decode(&[42]).is_err());
+    }
+
+    #[test]
+    fn test_only_job_messages_require_ack() {
+        let job_id = Uuid::new_v4();
+        let cancel = EjWsServerEnvelope::new(EjWsServerMessage::Cancel(
+            EjJobCancelReason::Timeout,
+            job_id,
+        ));
+        assert!(cancel.id.is_some());
+        assert_eq!(EjWsServerEnvelope::new(EjWsServerMessage::Close).id, None);
+
+        let serialized = serde_json::to_string(&cancel).unwrap();
+        let deserialized: EjWsServerEnvelope = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, cancel);
+    }
+
+    #[test]
+    fn test_board_scoped_messages_round_trip() {
+        let board_config_id = Uuid::new_v4();
+        let cancel = EjWsServerEnvelope::new(EjWsServerMessage::for_board(
+            board_config_id,
+            EjWsServerMessage::Cancel(EjJobCancelReason::Requested, Uuid::new_v4()),
+        ));
+        assert!(cancel.id.is_some());
+        assert!(
+            !EjWsServerMessage::for_board(board_config_id, EjWsServerMessage::Close).requires_ack()
+        );
+
+        let serialized = serde_json::to_string(&cancel).unwrap();
+        let deserialized: EjWsServerEnvelope = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, cancel);
+    }
+}
@@ -14,13 +14,39 @@ pub enum Error {
 
     /// Unexpected Socket Message
     #[error("Unexpected message from socket")]
-    UnexpectedSocketMessage(EjSocketServerMessage),
+    UnexpectedSocketMessage(Box<EjSocketServerMessage>),
+
+    /// Binary WebSocket frame with an unknown or malformed content.
+    #[error("Invalid binary WebSocket frame")]
+    InvalidWsBinaryFrame,
+
+    /// Gzipped WebSocket message too large once decompressed.
+    #[error("WebSocket message too large once decompressed")]
+    WsMessageTooLarge,
+
+    /// API key that can't be sent in an HTTP header.
+    #[error("Invalid API key")]
+    InvalidApiKey,
+
+    /// WebSocket connection to the dispatcher failed.
+    #[error(transparent)]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
 
     /// I/O operation failed.
     #[error(transparent)]
     IO(#[from] std::io::Error),
 
+    /// Configuration couldn't be loaded.
+    #[error(transparent)]
+    Config(#[from] ej_config::error::Error),
+
     /// JSON serialization/deserialization failed.
     #[error(transparent)]
     Json(#[from] serde_json::Error),
 }
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(err))
+    }
+}
@@ -0,0 +1,27 @@
+use tokio::net::UnixStream;
+use uuid::Uuid;
+
+use crate::{
+    EjBuildResult,
+    ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage},
+    prelude::*,
+    socket,
+};
+use std::path::Path;
+
+/// Fetches the build logs of a job, per board config.
+///
+/// Lets build-only pipelines retrieve the logs of a job after it finished,
+/// the same way [`fetch_run_result`](crate::fetch_run_result::fetch_run_result)
+/// does for run jobs.
+pub async fn fetch_build_result(socket_path: &Path, job_id: Uuid) -> Result<EjBuildResult> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    let message = EjSocketClientMessage::FetchBuildResult { job_id };
+    socket::send(&mut stream, message).await?;
+    let message = socket::receive(&mut stream).await?;
+
+    match message {
+        EjSocketServerMessage::BuildResult(result) => Ok(result),
+        _ => Err(Error::UnexpectedSocketMessage(Box::new(message))),
+    }
+}
@@ -0,0 +1,29 @@
+use ej_config::ej_config::EjConfig;
+use tokio::net::UnixStream;
+use uuid::Uuid;
+
+use crate::{
+    ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage},
+    prelude::*,
+    socket,
+};
+use std::path::Path;
+
+/// Fetches the latest config uploaded by a builder, `None` if it never uploaded one.
+pub async fn fetch_builder_config(
+    socket_path: &Path,
+    builder_id: Uuid,
+) -> Result<Option<EjConfig>> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    socket::send(
+        &mut stream,
+        EjSocketClientMessage::FetchBuilderConfig { builder_id },
+    )
+    .await?;
+    let message = socket::receive(&mut stream).await?;
+
+    match message {
+        EjSocketServerMessage::BuilderConfig(config) => Ok(config),
+        _ => Err(Error::UnexpectedSocketMessage(Box::new(message))),
+    }
+}
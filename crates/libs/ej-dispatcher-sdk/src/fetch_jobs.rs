@@ -4,12 +4,13 @@ use tokio::{
 };
 
 use crate::{
-    ejjob::EjJobApi,
+    ejjob::{EjJobApi, EjJobQuery},
     ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage},
     prelude::*,
     socket,
 };
 use std::path::Path;
+use uuid::Uuid;
 pub async fn fetch_jobs(socket_path: &Path, commit_hash: String) -> Result<Vec<EjJobApi>> {
     let mut stream = UnixStream::connect(socket_path).await?;
     let message = EjSocketClientMessage::FetchJobs { commit_hash };
@@ -18,6 +19,73 @@ pub async fn fetch_jobs(socket_path: &Path, commit_hash: String) -> Result<Vec<E
 
     match message {
         EjSocketServerMessage::Jobs(jobs) => Ok(jobs),
-        _ => Err(Error::UnexpectedSocketMessage(message)),
+        _ => Err(Error::UnexpectedSocketMessage(Box::new(message))),
+    }
+}
+
+/// Fetches the job with the given ID.
+///
+/// Fails with [`Error::UnexpectedSocketMessage`] holding the dispatcher's
+/// error if there is no such job.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_dispatcher_sdk::fetch_job;
+/// use std::path::Path;
+/// use uuid::Uuid;
+///
+/// # tokio_test::block_on(async {
+/// let job_id = Uuid::parse_str("a1a2a3a4-b1b2-c1c2-d1d2-d3d4d5d6d7d8").unwrap();
+/// let job = fetch_job(Path::new("/tmp/ejd.sock"), job_id).await.unwrap();
+/// println!("{}", job);
+/// # });
+/// ```
+pub async fn fetch_job(socket_path: &Path, job_id: Uuid) -> Result<EjJobApi> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    socket::send(&mut stream, EjSocketClientMessage::FetchJob { job_id }).await?;
+    let message: EjSocketServerMessage = socket::receive(&mut stream).await?;
+
+    match message {
+        EjSocketServerMessage::Job(job) => Ok(job),
+        _ => Err(Error::UnexpectedSocketMessage(Box::new(message))),
+    }
+}
+
+/// Fetches a page of the jobs matching `query`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_dispatcher_sdk::{
+///     ejjob::{EjJobQuery, EjJobStatus},
+///     ejpage::{EjPageQuery, EjSortOrder},
+///     query_jobs,
+/// };
+/// use std::path::Path;
+///
+/// # tokio_test::block_on(async {
+/// // The last 20 jobs that failed
+/// let query = EjJobQuery {
+///     status: Some(EjJobStatus::Failed),
+///     page: EjPageQuery {
+///         per_page: Some(20),
+///         sort: Some("created_at".to_string()),
+///         order: Some(EjSortOrder::Desc),
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+/// let jobs = query_jobs(Path::new("/tmp/ejd.sock"), query).await.unwrap();
+/// # });
+/// ```
+pub async fn query_jobs(socket_path: &Path, query: EjJobQuery) -> Result<Vec<EjJobApi>> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    socket::send(&mut stream, EjSocketClientMessage::QueryJobs(query)).await?;
+    let message: EjSocketServerMessage = socket::receive(&mut stream).await?;
+
+    match message {
+        EjSocketServerMessage::Jobs(jobs) => Ok(jobs),
+        _ => Err(Error::UnexpectedSocketMessage(Box::new(message))),
     }
 }
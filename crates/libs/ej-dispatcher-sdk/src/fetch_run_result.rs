@@ -20,6 +20,6 @@ pub async fn fetch_run_result(socket_path: &Path, job_id: Uuid) -> Result<EjRunR
 
     match message {
         EjSocketServerMessage::RunResult(result) => Ok(result),
-        _ => Err(Error::UnexpectedSocketMessage(message)),
+        _ => Err(Error::UnexpectedSocketMessage(Box::new(message))),
     }
 }
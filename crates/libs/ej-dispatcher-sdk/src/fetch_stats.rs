@@ -0,0 +1,22 @@
+use tokio::net::UnixStream;
+
+use crate::{
+    ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage},
+    ejstats::{EjStats, EjStatsQuery},
+    prelude::*,
+    socket,
+};
+use std::path::Path;
+
+/// Fetches statistics about the jobs that ran to completion.
+pub async fn fetch_stats(socket_path: &Path, query: EjStatsQuery) -> Result<EjStats> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    let message = EjSocketClientMessage::FetchStats(query);
+    socket::send(&mut stream, message).await?;
+    let message = socket::receive(&mut stream).await?;
+
+    match message {
+        EjSocketServerMessage::Stats(stats) => Ok(stats),
+        _ => Err(Error::UnexpectedSocketMessage(Box::new(message))),
+    }
+}
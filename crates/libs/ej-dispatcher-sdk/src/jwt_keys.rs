@@ -0,0 +1,72 @@
+use tokio::net::UnixStream;
+
+use crate::{
+    ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage},
+    ejtoken::EjJwtKeys,
+    prelude::*,
+    socket,
+};
+use std::path::{Path, PathBuf};
+
+/// Makes the dispatcher sign new tokens with a new key.
+///
+/// The key is read by the dispatcher, the paths must be valid on its host.
+/// Tokens signed with the previous keys stay valid until they're retired with
+/// [`retire_jwt_key`]. The new key is lost when the dispatcher restarts, point
+/// its `JWT_*` environment variables to it before then.
+///
+/// # Arguments
+///
+/// * `socket_path` - Path of the dispatcher socket
+/// * `kid` - ID of the new key, written to the `kid` header of the tokens
+/// * `key_path` - Shared secret with `HS256`, PEM private key otherwise
+/// * `public_key_path` - PEM public key, unused with `HS256`
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_dispatcher_sdk::{retire_jwt_key, rotate_jwt_key};
+/// use std::path::{Path, PathBuf};
+///
+/// # tokio_test::block_on(async {
+/// let socket = Path::new("/tmp/ejd.sock");
+/// let keys = rotate_jwt_key(socket, "2025-06".to_string(), PathBuf::from("/run/secrets/jwt"), None)
+///     .await
+///     .unwrap();
+/// println!("{keys}");
+///
+/// // Once the tokens signed with the previous key expired
+/// retire_jwt_key(socket, "2025-01".to_string()).await.unwrap();
+/// # });
+/// ```
+pub async fn rotate_jwt_key(
+    socket_path: &Path,
+    kid: String,
+    key_path: PathBuf,
+    public_key_path: Option<PathBuf>,
+) -> Result<EjJwtKeys> {
+    let message = EjSocketClientMessage::RotateJwtKey {
+        kid,
+        key_path,
+        public_key_path,
+    };
+    send(socket_path, message).await
+}
+
+/// Makes the dispatcher reject the tokens signed with a previous key.
+///
+/// The current key can't be retired, rotate to a new one first.
+pub async fn retire_jwt_key(socket_path: &Path, kid: String) -> Result<EjJwtKeys> {
+    send(socket_path, EjSocketClientMessage::RetireJwtKey { kid }).await
+}
+
+async fn send(socket_path: &Path, message: EjSocketClientMessage) -> Result<EjJwtKeys> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    socket::send(&mut stream, message).await?;
+    let message = socket::receive(&mut stream).await?;
+
+    match message {
+        EjSocketServerMessage::JwtKeys(keys) => Ok(keys),
+        _ => Err(Error::UnexpectedSocketMessage(Box::new(message))),
+    }
+}
@@ -22,58 +22,54 @@
 //!# });
 //! ```
 
-use crate::{ejsocket_message::EjSocketClientMessage, prelude::*};
-use std::{collections::HashMap, fmt, path::Path, time::Duration};
-use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
-    net::UnixStream,
-};
-use tracing::{error, info};
-use uuid::Uuid;
-
 pub use crate::{
-    build::dispatch_build,
+    build::{dispatch_build, dispatch_build_updates, dispatch_build_with_updates},
+    connection::{EjDispatcherAddress, EjRemoteDispatcher},
+    dispatch_job::dispatch_job,
     ejjob::{
-        EjBuildResult, EjDeployableJob, EjJob, EjJobCancelReason, EjJobType, EjJobUpdate,
-        EjRunResult,
+        EjBuildResult, EjDeployableJob, EjJob, EjJobCancelReason, EjJobTarget, EjJobType,
+        EjJobUpdate, EjRunResult,
+        checkout::EjCheckoutOptions,
+        timeouts::{EjDispatchTimeouts, EjJobTimeouts, EjPhaseTimeouts},
     },
-    fetch_jobs::fetch_jobs,
+    fetch_build_result::fetch_build_result,
+    fetch_builder_config::fetch_builder_config,
+    fetch_jobs::{fetch_job, fetch_jobs, query_jobs},
     fetch_run_result::fetch_run_result,
-    run::dispatch_run,
+    fetch_stats::fetch_stats,
+    jwt_keys::{retire_jwt_key, rotate_jwt_key},
+    list_builders::{list_builders, list_connected_builders},
+    run::{dispatch_run, dispatch_run_updates, dispatch_run_with_updates},
+    search_jobs::search_jobs,
+    subscribe_logs::subscribe_logs,
 };
 
 pub mod build;
+pub mod connection;
+pub mod dispatch_job;
+pub mod ejapi_key;
+pub mod ejaudit;
 pub mod ejbuilder;
 pub mod ejclient;
 pub mod ejjob;
+pub mod ejpage;
+pub mod ejsession;
 pub mod ejsocket_message;
+pub mod ejstats;
+pub mod ejtoken;
 pub mod ejws_message;
 pub mod error;
+pub mod fetch_build_result;
+pub mod fetch_builder_config;
 pub mod fetch_jobs;
 pub mod fetch_run_result;
+pub mod fetch_stats;
+pub mod jwt_keys;
+pub mod list_builders;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod prelude;
 pub mod run;
+pub mod search_jobs;
 mod socket;
-
-/// Dispatch a job to the EJ dispatcher.
-///
-/// Sends a job request to the dispatcher via Unix socket with a maximum duration timeout.
-///
-/// # Arguments
-///
-/// * `stream` - Unix socket connection to the dispatcher
-/// * `job` - Job configuration to dispatch
-/// * `max_duration` - Maximum time to wait for job completion
-/// ```
-async fn dispatch(stream: &mut UnixStream, job: EjJob, max_duration: Duration) -> Result<()> {
-    let message = EjSocketClientMessage::Dispatch {
-        job,
-        timeout: max_duration,
-    };
-
-    let payload = serde_json::to_string(&message)?;
-    stream.write_all(payload.as_bytes()).await;
-    stream.write_all(b"\n").await;
-    stream.flush().await;
-    Ok(())
-}
+pub mod subscribe_logs;
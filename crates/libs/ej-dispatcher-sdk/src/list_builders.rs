@@ -0,0 +1,47 @@
+use tokio::net::UnixStream;
+
+use crate::{
+    ejbuilder::EjBuilderInfo,
+    ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage},
+    prelude::*,
+    socket,
+};
+use std::path::Path;
+
+/// Lists every registered builder along with when it was last online.
+pub async fn list_builders(socket_path: &Path) -> Result<Vec<EjBuilderInfo>> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    socket::send(&mut stream, EjSocketClientMessage::ListBuilders).await?;
+    let message = socket::receive(&mut stream).await?;
+
+    match message {
+        EjSocketServerMessage::Builders(builders) => Ok(builders),
+        _ => Err(Error::UnexpectedSocketMessage(Box::new(message))),
+    }
+}
+
+/// Lists the builders currently connected along with their boards, addresses,
+/// capacity and the job they're working on.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_dispatcher_sdk::list_connected_builders;
+/// use std::path::Path;
+///
+/// # tokio_test::block_on(async {
+/// let builders = list_connected_builders(Path::new("/tmp/ejd.sock")).await.unwrap();
+/// let idle = builders.iter().filter(|builder| builder.current_job.is_none()).count();
+/// println!("{idle} of {} connected builders are idle", builders.len());
+/// # });
+/// ```
+pub async fn list_connected_builders(socket_path: &Path) -> Result<Vec<EjBuilderInfo>> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    socket::send(&mut stream, EjSocketClientMessage::ListConnectedBuilders).await?;
+    let message = socket::receive(&mut stream).await?;
+
+    match message {
+        EjSocketServerMessage::Builders(builders) => Ok(builders),
+        _ => Err(Error::UnexpectedSocketMessage(Box::new(message))),
+    }
+}
@@ -0,0 +1,216 @@
+//! Mock dispatcher for testing applications built on the SDK.
+//!
+//! [`MockDispatcher`] listens on a temporary Unix socket and answers each
+//! connection with the next scripted sequence of [`EjSocketServerMessage`]s,
+//! so dispatch logic can be integration tested without Postgres or a
+//! running `ejd`. Enabled with the `mock` feature, see the Dispatcher SDK guide
+//! for an example.
+
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    task::JoinHandle,
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    ejjob::{EjDeployableJob, EjJobType},
+    ejsocket_message::{EjSignedSocketMessage, EjSocketClientMessage, EjSocketServerMessage},
+    prelude::*,
+};
+
+/// Name of the socket inside the mock dispatcher's temporary directory.
+const SOCKET_NAME: &str = "ejd.sock";
+
+/// Dispatcher replaying scripted messages on a temporary Unix socket.
+///
+/// Every connection reads one client message, records it, sends the next
+/// script and closes. Connections past the last script are closed without
+/// an answer. Signed messages are accepted without checking their signature.
+///
+/// The socket is removed and the dispatcher stops once it is dropped.
+pub struct MockDispatcher {
+    socket_path: PathBuf,
+    received: Arc<Mutex<Vec<EjSocketClientMessage>>>,
+    task: JoinHandle<()>,
+    _dir: TempDir,
+}
+
+impl MockDispatcher {
+    /// Starts listening, must be called within a Tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `scripts` - Messages sent on each connection, in connection order
+    pub fn start(scripts: impl IntoIterator<Item = Vec<EjSocketServerMessage>>) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let socket_path = dir.path().join(SOCKET_NAME);
+        let listener = UnixListener::bind(&socket_path)?;
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let task = tokio::spawn(serve(
+            listener,
+            scripts.into_iter().collect(),
+            received.clone(),
+        ));
+        Ok(Self {
+            socket_path,
+            received,
+            task,
+            _dir: dir,
+        })
+    }
+
+    /// Path of the socket to dispatch to.
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Takes the client messages received so far, in the order they arrived.
+    pub fn take_received(&self) -> Vec<EjSocketClientMessage> {
+        std::mem::take(&mut self.received.lock().unwrap())
+    }
+}
+
+impl Drop for MockDispatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// `DispatchOk` answer for a job of type `job_type`, with a random ID.
+pub fn dispatch_ok(job_type: EjJobType) -> EjSocketServerMessage {
+    EjSocketServerMessage::DispatchOk(EjDeployableJob {
+        id: Uuid::new_v4(),
+        job_type,
+        commit_hash: String::new(),
+        remote_url: String::new(),
+        remote_token: None,
+        required_tags: Vec::new(),
+        board_configs: Vec::new(),
+        timeouts: Default::default(),
+        checkout: Default::default(),
+    })
+}
+
+async fn serve(
+    listener: UnixListener,
+    mut scripts: VecDeque<Vec<EjSocketServerMessage>>,
+    received: Arc<Mutex<Vec<EjSocketClientMessage>>>,
+) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                error!("Mock dispatcher failed to accept a connection - {err}");
+                return;
+            }
+        };
+        let script = scripts.pop_front().unwrap_or_default();
+        if let Err(err) = handle_connection(stream, script, &received).await {
+            error!("Mock dispatcher connection failed - {err}");
+        }
+    }
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    script: Vec<EjSocketServerMessage>,
+    received: &Mutex<Vec<EjSocketClientMessage>>,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    received.lock().unwrap().push(parse_message(&line)?);
+
+    let mut stream = reader.into_inner();
+    for message in script {
+        let payload = serde_json::to_string(&message)?;
+        stream.write_all(payload.as_bytes()).await?;
+        stream.write_all(b"\n").await?;
+    }
+    stream.shutdown().await?;
+    Ok(())
+}
+
+fn parse_message(line: &str) -> Result<EjSocketClientMessage> {
+    match serde_json::from_str::<EjSignedSocketMessage>(line) {
+        Ok(signed) => Ok(serde_json::from_str(&signed.payload)?),
+        Err(_) => Ok(serde_json::from_str(line)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ej_auth::socket_signature::SocketKey;
+
+    use super::*;
+    use crate::{EjJobUpdate, EjRunResult, dispatch_run, fetch_jobs};
+
+    #[tokio::test]
+    async fn test_mock_dispatcher_replays_scripts() {
+        let dispatcher = MockDispatcher::start(vec![
+            vec![
+                dispatch_ok(EjJobType::BuildAndRun),
+                EjSocketServerMessage::JobUpdate(EjJobUpdate::RunFinished(EjRunResult {
+                    success: false,
+                    logs: Vec::new(),
+                    results: Vec::new(),
+                    skipped: Vec::new(),
+                })),
+            ],
+            vec![EjSocketServerMessage::Jobs(Vec::new())],
+        ])
+        .unwrap();
+
+        let result = dispatch_run(
+            dispatcher.socket_path(),
+            "test_commit_hash".to_string(),
+            "test_remote_url".to_string(),
+            None,
+            Duration::from_secs(60),
+        )
+        .await
+        .unwrap();
+        assert!(!result.success);
+
+        let jobs = fetch_jobs(dispatcher.socket_path(), "test_commit_hash".to_string())
+            .await
+            .unwrap();
+        assert!(jobs.is_empty());
+
+        match dispatcher.take_received().as_slice() {
+            [
+                EjSocketClientMessage::Dispatch { job, timeout },
+                EjSocketClientMessage::FetchJobs { commit_hash },
+            ] => {
+                assert_eq!(job.job_type, EjJobType::BuildAndRun);
+                assert_eq!(*timeout, Duration::from_secs(60));
+                assert_eq!(commit_hash, "test_commit_hash");
+            }
+            received => panic!("Unexpected messages {received:?}"),
+        }
+        assert!(dispatcher.take_received().is_empty());
+    }
+
+    #[test]
+    fn test_parse_signed_message() {
+        let message = EjSocketClientMessage::FetchJobs {
+            commit_hash: "abc123".to_string(),
+        };
+        let line = message.encode(Some(&SocketKey::new("key"))).unwrap();
+        assert!(matches!(
+            parse_message(&line).unwrap(),
+            EjSocketClientMessage::FetchJobs { commit_hash } if commit_hash == "abc123"
+        ));
+    }
+}
@@ -1,31 +1,29 @@
 //! Run job dispatch and management.
 
-use std::{collections::HashMap, fmt, path::Path, time::Duration};
-use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
-    net::UnixStream,
-};
-use tracing::{error, info};
+use futures_util::{Stream, StreamExt};
+use std::{collections::HashMap, fmt, pin::pin, time::Duration};
 use uuid::Uuid;
 
 use crate::{
-    ejjob::{EjJob, EjJobType, EjJobUpdate, EjRunResult},
-    ejsocket_message::EjSocketServerMessage,
+    connection::{DispatchConnection, EjDispatcherAddress},
+    ejjob::{
+        EjJob, EjJobTarget, EjJobType, EjJobUpdate, EjRunResult, timeouts::EjDispatchTimeouts,
+    },
     prelude::*,
 };
-
-use crate::dispatch;
 /// Dispatch a build-and-run job to the dispatcher.
 ///
-/// Creates a build-and-run job and sends it to the dispatcher via Unix socket.
+/// Creates a build-and-run job and sends it to the dispatcher via its Unix socket
+/// or its API.
 ///
 /// # Arguments
 ///
-/// * `socket_path` - Path to the dispatcher Unix socket
+/// * `dispatcher` - Path to the dispatcher Unix socket or [`EjRemoteDispatcher`](crate::connection::EjRemoteDispatcher)
 /// * `commit_hash` - Git commit hash to build and run
 /// * `remote_url` - Git repository URL
 /// * `remote_token` - Optional authentication token for private repos
-/// * `max_duration` - Maximum time to wait for job completion
+/// * `timeouts` - Maximum time to wait for job completion, or [`EjDispatchTimeouts`]
+///   to also limit the build and run of each board config
 ///
 /// # Examples
 ///
@@ -48,50 +46,155 @@ use crate::dispatch;
 /// # });
 /// ```
 pub async fn dispatch_run(
-    socket_path: &Path,
+    dispatcher: impl Into<EjDispatcherAddress>,
     commit_hash: String,
     remote_url: String,
     remote_token: Option<String>,
-    max_duration: Duration,
+    timeouts: impl Into<EjDispatchTimeouts>,
 ) -> Result<EjRunResult> {
-    let mut stream = UnixStream::connect(socket_path).await?;
-
-    let job = EjJob {
-        job_type: EjJobType::BuildAndRun,
-        commit_hash: commit_hash,
-        remote_url: remote_url,
-        remote_token: remote_token,
-    };
-
-    let lines = dispatch(&mut stream, job, max_duration).await?;
-
-    let mut reader = BufReader::new(stream);
-    let mut lines = reader.lines();
-
-    while let Some(line) = lines.next_line().await? {
-        match serde_json::from_str::<EjSocketServerMessage>(&line) {
-            Ok(message) => {
-                info!("{}", message);
-                match message {
-                    EjSocketServerMessage::JobUpdate(update) => match update {
-                        EjJobUpdate::RunFinished(result) => return Ok(result),
-                        _ => continue,
-                    },
-                    _ => continue,
-                }
-            }
-            Err(e) => {
-                error!("Failed to parse message {} - {}", line, e);
-            }
+    dispatch_run_with_updates(
+        dispatcher,
+        commit_hash,
+        remote_url,
+        remote_token,
+        EjJobTarget::default(),
+        timeouts,
+        |_| {},
+    )
+    .await
+}
+
+/// Same as [`dispatch_run`] but calls `on_update` for every job update received while
+/// waiting for the job to finish.
+///
+/// This can be used to follow the job's progress, for instance to print the
+/// `EjJobUpdate::LogChunk` updates as builders produce output.
+///
+/// Only the board configs `target` selects run the job, the others are
+/// reported as skipped in the result.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_dispatcher_sdk::{EjJobTarget, EjJobUpdate, dispatch_run_with_updates};
+/// use std::{path::Path, time::Duration};
+///
+/// # tokio_test::block_on(async {
+/// let job_result = dispatch_run_with_updates(
+///     Path::new("/tmp/dispatcher.sock"),
+///     "abc123".to_string(),
+///     "https://github.com/user/repo.git".to_string(),
+///     None,
+///     EjJobTarget {
+///         required_tags: vec![String::from("arm64")],
+///         ..Default::default()
+///     },
+///     Duration::from_secs(600),
+///     |update| {
+///         if let EjJobUpdate::LogChunk { board_config, lines } = update {
+///             for line in lines.iter().flat_map(|chunk| chunk.lines()) {
+///                 println!("[{}] {}", board_config.name, line);
+///             }
+///         }
+///     },
+/// ).await.unwrap();
+/// # });
+/// ```
+pub async fn dispatch_run_with_updates(
+    dispatcher: impl Into<EjDispatcherAddress>,
+    commit_hash: String,
+    remote_url: String,
+    remote_token: Option<String>,
+    target: EjJobTarget,
+    timeouts: impl Into<EjDispatchTimeouts>,
+    mut on_update: impl FnMut(&EjJobUpdate),
+) -> Result<EjRunResult> {
+    let updates = dispatch_run_updates(
+        dispatcher,
+        commit_hash,
+        remote_url,
+        remote_token,
+        target,
+        timeouts,
+    )
+    .await?;
+    let mut updates = pin!(updates);
+
+    while let Some(update) = updates.next().await {
+        on_update(&update);
+        if let EjJobUpdate::RunFinished(result) = update {
+            return Ok(result);
         }
     }
     Err(Error::RunError)
 }
 
+/// Dispatch a build-and-run job and get the stream of its updates.
+///
+/// Unlike [`dispatch_run_with_updates`], the caller decides how to follow
+/// the job's progress. The stream ends once the dispatcher closes the
+/// connection after the job finishes, or if the connection is lost: the job's
+/// result is the `EjJobUpdate::RunFinished` update.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_dispatcher_sdk::{EjJobTarget, EjJobUpdate, dispatch_run_updates};
+/// use futures_util::StreamExt;
+/// use std::{path::Path, time::Duration};
+///
+/// # tokio_test::block_on(async {
+/// let updates = dispatch_run_updates(
+///     Path::new("/tmp/dispatcher.sock"),
+///     "abc123".to_string(),
+///     "https://github.com/user/repo.git".to_string(),
+///     None,
+///     EjJobTarget {
+///         board_configs: vec![String::from("Rpi3 Wayland")],
+///         ..Default::default()
+///     },
+///     Duration::from_secs(600),
+/// ).await.unwrap();
+/// let mut updates = std::pin::pin!(updates);
+/// while let Some(update) = updates.next().await {
+///     match update {
+///         EjJobUpdate::LogChunk { board_config, lines } => {
+///             for line in lines.iter().flat_map(|chunk| chunk.lines()) {
+///                 println!("[{}] {}", board_config.name, line);
+///             }
+///         }
+///         EjJobUpdate::RunFinished(result) => println!("{}", result),
+///         _ => (),
+///     }
+/// }
+/// # });
+/// ```
+pub async fn dispatch_run_updates(
+    dispatcher: impl Into<EjDispatcherAddress>,
+    commit_hash: String,
+    remote_url: String,
+    remote_token: Option<String>,
+    target: EjJobTarget,
+    timeouts: impl Into<EjDispatchTimeouts>,
+) -> Result<impl Stream<Item = EjJobUpdate>> {
+    let timeouts = timeouts.into();
+    let job = EjJob::new(
+        EjJobType::BuildAndRun,
+        commit_hash,
+        remote_url,
+        remote_token,
+    )
+    .with_target(target)
+    .with_timeouts(timeouts.phases);
+
+    let connection = DispatchConnection::open(dispatcher.into(), job, timeouts.job).await?;
+    Ok(connection.into_updates())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::ejjob::{EjDeployableJob, EjJobCancelReason};
-    use crate::ejsocket_message::EjSocketClientMessage;
+    use crate::ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage};
 
     use super::*;
     use ej_config::ej_board_config::EjBoardConfigApi;
@@ -148,6 +251,10 @@ mod tests {
                 commit_hash: "test_commit_hash".to_string(),
                 remote_url: "test_remote_url".to_string(),
                 remote_token: Some("test_token".to_string()),
+                required_tags: Vec::new(),
+                board_configs: Vec::new(),
+                timeouts: Default::default(),
+                checkout: Default::default(),
             });
             let response = serde_json::to_string(&dispatch_ok).unwrap();
             stream.write_all(response.as_bytes()).await.unwrap();
@@ -179,6 +286,7 @@ mod tests {
                     },
                     "Test result output".to_string(),
                 )],
+                skipped: Vec::new(),
             };
             let run_finished =
                 EjSocketServerMessage::JobUpdate(EjJobUpdate::RunFinished(run_result));
@@ -244,6 +352,10 @@ mod tests {
                 commit_hash: "test_commit_hash".to_string(),
                 remote_url: "test_remote_url".to_string(),
                 remote_token: None,
+                required_tags: Vec::new(),
+                board_configs: Vec::new(),
+                timeouts: Default::default(),
+                checkout: Default::default(),
             });
             let response = serde_json::to_string(&dispatch_ok).unwrap();
             stream.write_all(response.as_bytes()).await.unwrap();
@@ -305,6 +417,10 @@ mod tests {
                 commit_hash: "test_commit_hash".to_string(),
                 remote_url: "test_remote_url".to_string(),
                 remote_token: None,
+                required_tags: Vec::new(),
+                board_configs: Vec::new(),
+                timeouts: Default::default(),
+                checkout: Default::default(),
             });
             let response = serde_json::to_string(&dispatch_ok).unwrap();
             stream.write_all(response.as_bytes()).await.unwrap();
@@ -377,6 +493,10 @@ mod tests {
                 commit_hash: "test_commit_hash".to_string(),
                 remote_url: "test_remote_url".to_string(),
                 remote_token: None,
+                required_tags: Vec::new(),
+                board_configs: Vec::new(),
+                timeouts: Default::default(),
+                checkout: Default::default(),
             });
             let response = serde_json::to_string(&dispatch_ok).unwrap();
             stream.write_all(response.as_bytes()).await.unwrap();
@@ -401,6 +521,7 @@ mod tests {
                     "Test log with error output".to_string(),
                 )],
                 results: vec![],
+                skipped: Vec::new(),
             };
             let run_finished =
                 EjSocketServerMessage::JobUpdate(EjJobUpdate::RunFinished(run_result));
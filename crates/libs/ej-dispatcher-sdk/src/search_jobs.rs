@@ -0,0 +1,42 @@
+use tokio::net::UnixStream;
+
+use crate::{
+    ejjob::search::{EjJobSearchHit, EjJobSearchQuery},
+    ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage},
+    prelude::*,
+    socket,
+};
+use std::path::Path;
+
+/// Searches the logs and results of every job.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_dispatcher_sdk::{ejjob::search::EjJobSearchQuery, search_jobs};
+/// use std::path::Path;
+///
+/// # tokio_test::block_on(async {
+/// let query = EjJobSearchQuery {
+///     q: String::from("\"undefined reference\""),
+///     ..Default::default()
+/// };
+/// for hit in search_jobs(Path::new("/tmp/ejd.sock"), query).await.unwrap() {
+///     println!("{hit}");
+/// }
+/// # });
+/// ```
+pub async fn search_jobs(
+    socket_path: &Path,
+    query: EjJobSearchQuery,
+) -> Result<Vec<EjJobSearchHit>> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    let message = EjSocketClientMessage::SearchJobs(query);
+    socket::send(&mut stream, message).await?;
+    let message = socket::receive(&mut stream).await?;
+
+    match message {
+        EjSocketServerMessage::SearchResults(hits) => Ok(hits),
+        _ => Err(Error::UnexpectedSocketMessage(Box::new(message))),
+    }
+}
@@ -1,3 +1,4 @@
+use ej_auth::socket_signature::SocketKey;
 use serde::de::DeserializeOwned;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UnixStream;
@@ -6,7 +7,7 @@ use crate::ejsocket_message::EjSocketClientMessage;
 use crate::prelude::*;
 
 pub async fn send(stream: &mut UnixStream, message: EjSocketClientMessage) -> Result<()> {
-    let payload = serde_json::to_string(&message)?;
+    let payload = message.encode(SocketKey::from_env().as_ref())?;
     stream.write_all(payload.as_bytes()).await;
     stream.write_all(b"\n").await;
     stream.flush().await;
@@ -0,0 +1,154 @@
+use futures_util::{Stream, stream};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::UnixStream,
+};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    ejjob::EjLogChunk,
+    ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage},
+    prelude::*,
+    socket,
+};
+use std::path::Path;
+
+/// Follows the output of a running or queued job.
+///
+/// The stream yields the output builders produce for the job from now on,
+/// and ends once the job finishes or the connection to the dispatcher is lost.
+/// The output produced before subscribing can be fetched with
+/// [`fetch_run_result`](crate::fetch_run_result::fetch_run_result) once the
+/// job is done.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_dispatcher_sdk::subscribe_logs;
+/// use futures_util::StreamExt;
+/// use std::path::Path;
+/// use uuid::Uuid;
+///
+/// # tokio_test::block_on(async {
+/// let job_id = Uuid::parse_str("a1a2a3a4-b1b2-c1c2-d1d2-d3d4d5d6d7d8").unwrap();
+/// let logs = subscribe_logs(Path::new("/tmp/ejd.sock"), job_id).await.unwrap();
+/// let mut logs = std::pin::pin!(logs);
+/// while let Some(chunk) = logs.next().await {
+///     for line in chunk.lines {
+///         print!("[{}] {}", chunk.board_config.name, line);
+///     }
+/// }
+/// # });
+/// ```
+pub async fn subscribe_logs(
+    socket_path: &Path,
+    job_id: Uuid,
+) -> Result<impl Stream<Item = EjLogChunk>> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    socket::send(&mut stream, EjSocketClientMessage::SubscribeLogs { job_id }).await?;
+
+    let mut lines = BufReader::new(stream).lines();
+    let Some(line) = lines.next_line().await? else {
+        return Err(Error::IO(std::io::ErrorKind::UnexpectedEof.into()));
+    };
+    let message: EjSocketServerMessage = serde_json::from_str(&line)?;
+    if !matches!(message, EjSocketServerMessage::SubscribeLogsOk) {
+        return Err(Error::UnexpectedSocketMessage(Box::new(message)));
+    }
+    info!("Subscribed to the logs of job {job_id}");
+
+    Ok(stream::unfold(lines, |mut lines| async move {
+        loop {
+            let line = match lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => return None,
+                Err(err) => {
+                    error!("Failed to read job logs - {err}");
+                    return None;
+                }
+            };
+            match serde_json::from_str(&line) {
+                Ok(EjSocketServerMessage::LogChunk(chunk)) => return Some((chunk, lines)),
+                Ok(message) => info!("{}", message),
+                Err(err) => error!("Failed to parse message {} - {}", line, err),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ej_config::ej_board_config::EjBoardConfigApi;
+    use futures_util::StreamExt;
+    use tempfile::TempDir;
+    use tokio::{io::AsyncWriteExt, net::UnixListener};
+
+    fn chunk(line: &str) -> EjLogChunk {
+        EjLogChunk {
+            board_config: EjBoardConfigApi {
+                id: Uuid::new_v4(),
+                name: String::from("debug"),
+                tags: Vec::new(),
+            },
+            lines: vec![String::from(line)],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_logs() {
+        let dir = TempDir::new().unwrap();
+        let socket_path = dir.path().join("ejd.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let job_id = Uuid::new_v4();
+        let chunks = vec![chunk("Compiling\n"), chunk("Done\n")];
+
+        let sent = chunks.clone();
+        let server_task = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let line = BufReader::new(reader).lines().next_line().await.unwrap();
+            let message: EjSocketClientMessage = serde_json::from_str(&line.unwrap()).unwrap();
+            assert!(matches!(
+                message,
+                EjSocketClientMessage::SubscribeLogs { job_id: id } if id == job_id
+            ));
+
+            let mut messages = vec![EjSocketServerMessage::SubscribeLogsOk];
+            messages.extend(sent.into_iter().map(EjSocketServerMessage::LogChunk));
+            for message in messages {
+                let payload = serde_json::to_string(&message).unwrap();
+                writer.write_all(payload.as_bytes()).await.unwrap();
+                writer.write_all(b"\n").await.unwrap();
+            }
+        });
+
+        let logs = subscribe_logs(&socket_path, job_id).await.unwrap();
+        let received: Vec<EjLogChunk> = logs.collect().await;
+        assert_eq!(received, chunks);
+        server_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_logs_of_finished_job() {
+        let dir = TempDir::new().unwrap();
+        let socket_path = dir.path().join("ejd.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let message = EjSocketServerMessage::Error(String::from("Job isn't running"));
+            let payload = serde_json::to_string(&message).unwrap();
+            stream.write_all(payload.as_bytes()).await.unwrap();
+            stream.write_all(b"\n").await.unwrap();
+        });
+
+        let result = subscribe_logs(&socket_path, Uuid::new_v4()).await;
+        assert!(matches!(
+            result,
+            Err(Error::UnexpectedSocketMessage(message))
+                if matches!(*message, EjSocketServerMessage::Error(_))
+        ));
+    }
+}
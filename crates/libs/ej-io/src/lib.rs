@@ -24,12 +24,18 @@
 //!     while let Some(event) = rx.recv().await {
 //!         match event {
 //!             RunEvent::ProcessNewOutputLine(line) => println!("Output: {}", line),
-//!             RunEvent::ProcessEnd(success) => println!("Process ended: {}", success),
+//!             RunEvent::ProcessEnd(success, usage) => {
+//!                 println!("Process ended: {} in {:?}", success, usage.wall_time)
+//!             }
 //!             _ => {}
 //!         }
 //!     }
 //! }
 //! ```
 
+pub mod limits;
+pub mod output;
 pub mod process;
 pub mod runner;
+pub mod shell;
+pub mod usage;
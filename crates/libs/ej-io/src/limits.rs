@@ -0,0 +1,234 @@
+//! Resource limits of spawned processes.
+//!
+//! Builders may run several board builds at once, a build using all the
+//! memory or file descriptors of the host shouldn't starve the others.
+//! Limits are applied by a `/bin/sh` wrapper that sets the resource limits
+//! with `ulimit`, joins the cgroup of the process if any, then `exec`s the
+//! command, so they're in place before the command starts.
+//!
+//! Resource limits apply to every process on its own, cgroup v2 limits to
+//! the process and all its descendants together. Cgroups are created inside
+//! a parent cgroup the builder can write to, with the controllers of the
+//! limits used enabled in its `cgroup.subtree_control`.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Default period of the cgroup CPU bandwidth limit, in microseconds.
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Limits applied to a process, none by default.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_io::limits::{CgroupLimits, ResourceLimits};
+/// use std::time::Duration;
+///
+/// let limits = ResourceLimits {
+///     max_open_files: Some(1024),
+///     max_cpu_time: Some(Duration::from_secs(3600)),
+///     cgroup: Some(CgroupLimits {
+///         memory_max_bytes: Some(4 * 1024 * 1024 * 1024),
+///         cpus: Some(2.0),
+///         ..CgroupLimits::new("/sys/fs/cgroup/ej")
+///     }),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// Maximum virtual memory of each process, `RLIMIT_AS`.
+    pub max_virtual_memory_bytes: Option<u64>,
+    /// Maximum CPU time of each process, `RLIMIT_CPU`, rounded up to the second.
+    pub max_cpu_time: Option<Duration>,
+    /// Maximum number of file descriptors each process can open, `RLIMIT_NOFILE`.
+    pub max_open_files: Option<u64>,
+    /// Cgroup the process and its descendants run in.
+    pub cgroup: Option<CgroupLimits>,
+}
+
+/// Limits of the cgroup v2 a process runs in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CgroupLimits {
+    /// Cgroup the cgroup of the process is created in.
+    pub parent: PathBuf,
+    /// Maximum memory used by the processes together, `memory.max`.
+    pub memory_max_bytes: Option<u64>,
+    /// Number of CPUs the processes may use together, `cpu.max`.
+    pub cpus: Option<f64>,
+    /// Maximum number of processes, `pids.max`.
+    pub pids_max: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Whether no limit is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl CgroupLimits {
+    /// Cgroup created in `parent`, without limits.
+    pub fn new(parent: impl Into<PathBuf>) -> Self {
+        Self {
+            parent: parent.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A cgroup created for a process.
+#[derive(Debug)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// Creates a cgroup in the parent of `limits` and sets its limits.
+    ///
+    /// Fails if the parent can't be written to or if the controller of a
+    /// limit isn't enabled in it, the cgroup being removed then.
+    pub fn create(limits: &CgroupLimits) -> io::Result<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = limits
+            .parent
+            .join(format!("ej-{}-{id}", std::process::id()));
+        std::fs::create_dir(&path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("can't create cgroup `{}`: {err}", path.display()),
+            )
+        })?;
+        let cgroup = Self { path };
+
+        let cpu_max = limits.cpus.map(|cpus| {
+            let quota = (cpus * CPU_PERIOD_US as f64).round().max(1000.0) as u64;
+            format!("{quota} {CPU_PERIOD_US}")
+        });
+        let settings = [
+            (
+                "memory.max",
+                limits.memory_max_bytes.map(|max| max.to_string()),
+            ),
+            ("cpu.max", cpu_max),
+            ("pids.max", limits.pids_max.map(|max| max.to_string())),
+        ];
+        for (file, value) in settings {
+            let Some(value) = value else {
+                continue;
+            };
+            if let Err(err) = std::fs::write(cgroup.path.join(file), value) {
+                let _ = std::fs::remove_dir(&cgroup.path);
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!(
+                        "can't set `{file}` of cgroup `{}`, is its controller enabled? {err}",
+                        cgroup.path.display()
+                    ),
+                ));
+            }
+        }
+        Ok(cgroup)
+    }
+
+    /// Path of the cgroup.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Kills what's left in the cgroup and removes it.
+    ///
+    /// Processes take a moment to leave the cgroup once killed, removing it
+    /// is retried for up to a second.
+    pub async fn remove(self) -> io::Result<()> {
+        // Only there since Linux 5.14, the process group is killed anyway
+        let _ = std::fs::write(self.path.join("cgroup.kill"), "1");
+        let mut attempts = 0;
+        loop {
+            match std::fs::remove_dir(&self.path) {
+                Err(err) if err.kind() == io::ErrorKind::ResourceBusy && attempts < 20 => {
+                    attempts += 1;
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// The command and arguments running `cmd` with `limits`, in `cgroup` if any.
+pub(crate) fn limited_command(
+    cmd: &str,
+    args: Vec<String>,
+    limits: &ResourceLimits,
+    cgroup: Option<&Cgroup>,
+) -> (String, Vec<String>) {
+    let mut script = Vec::new();
+    if let Some(cgroup) = cgroup {
+        let procs = cgroup.path.join("cgroup.procs");
+        script.push(format!(
+            "echo $$ > {}",
+            shell_quote(&procs.to_string_lossy())
+        ));
+    }
+    if let Some(bytes) = limits.max_virtual_memory_bytes {
+        script.push(format!("ulimit -v {}", bytes / 1024));
+    }
+    if let Some(cpu_time) = limits.max_cpu_time {
+        script.push(format!(
+            "ulimit -t {}",
+            cpu_time.as_secs_f64().ceil() as u64
+        ));
+    }
+    if let Some(files) = limits.max_open_files {
+        script.push(format!("ulimit -n {files}"));
+    }
+    script.push(String::from("exec \"$@\""));
+
+    // `$0` is the first argument after the script, the command comes after it
+    let mut wrapper_args = vec![String::from("-c"), script.join(" && "), String::from("sh")];
+    wrapper_args.push(cmd.to_string());
+    wrapper_args.extend(args);
+    (String::from("/bin/sh"), wrapper_args)
+}
+
+/// Quotes `value` for `sh`.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cgroup_limits_are_written() -> io::Result<()> {
+        // A plain directory stands for the parent cgroup
+        let parent = std::env::temp_dir().join(format!("ej-io-cgroup-{}", std::process::id()));
+        std::fs::create_dir_all(&parent)?;
+        let cgroup = Cgroup::create(&CgroupLimits {
+            memory_max_bytes: Some(64 * 1024 * 1024),
+            cpus: Some(1.5),
+            ..CgroupLimits::new(&parent)
+        })?;
+
+        let read = |file: &str| std::fs::read_to_string(cgroup.path().join(file));
+        assert_eq!(read("memory.max")?, "67108864");
+        assert_eq!(read("cpu.max")?, "150000 100000");
+        assert!(read("pids.max").is_err());
+
+        let path = cgroup.path().to_path_buf();
+        // Plain directories aren't emptied like cgroups are
+        assert!(cgroup.remove().await.is_err());
+        std::fs::remove_dir_all(&parent)?;
+        assert!(!path.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! Process output as a byte stream.
+//!
+//! [`Runner::run`](crate::runner::Runner::run) reports output through
+//! `RunEvent`s. [`RunOutput`] reads those events as the bytes the process
+//! wrote instead, through [`AsyncRead`] or as a [`Stream`] of chunks, for
+//! consumers forwarding output somewhere else, like a socket, that don't care
+//! about the other events.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use bytes::Bytes;
+use futures_core::Stream;
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    sync::mpsc::Receiver,
+};
+
+use crate::runner::RunEvent;
+
+/// Output of a run, read from its `RunEvent`s.
+///
+/// Output ends when the run does. A process that couldn't be created is
+/// reported as an error, other events are skipped.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_io::runner::Runner;
+/// use std::sync::{Arc, atomic::AtomicBool};
+/// use tokio::io::AsyncReadExt;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let runner = Runner::new("echo", vec!["Hello"]);
+///     let (mut output, handle) = runner.spawn_with_output(Arc::new(AtomicBool::new(false)));
+///
+///     let mut text = String::new();
+///     output.read_to_string(&mut text).await.unwrap();
+///     assert_eq!(text, "Hello\n");
+///     assert!(handle.await.unwrap().unwrap().success());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RunOutput {
+    rx: Receiver<RunEvent>,
+    /// Part of the last chunk that wasn't read yet.
+    pending: Bytes,
+}
+
+impl RunOutput {
+    /// Reads the output from the events of a run.
+    pub fn new(rx: Receiver<RunEvent>) -> Self {
+        Self {
+            rx,
+            pending: Bytes::new(),
+        }
+    }
+
+    /// Polls the next chunk of output that isn't empty.
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<Bytes>>> {
+        if !self.pending.is_empty() {
+            return Poll::Ready(Some(Ok(std::mem::take(&mut self.pending))));
+        }
+        loop {
+            match ready!(self.rx.poll_recv(cx)) {
+                Some(RunEvent::ProcessNewOutputLine(chunk)) if !chunk.is_empty() => {
+                    return Poll::Ready(Some(Ok(Bytes::from(chunk))));
+                }
+                Some(RunEvent::ProcessCreationFailed(err)) => {
+                    return Poll::Ready(Some(Err(io::Error::other(err))));
+                }
+                Some(_) => continue,
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl AsyncRead for RunOutput {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        match ready!(this.poll_chunk(cx)) {
+            Some(Ok(mut chunk)) => {
+                let read = chunk.split_to(chunk.len().min(buf.remaining()));
+                buf.put_slice(&read);
+                this.pending = chunk;
+                Poll::Ready(Ok(()))
+            }
+            Some(Err(err)) => Poll::Ready(Err(err)),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl Stream for RunOutput {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_chunk(cx)
+    }
+}
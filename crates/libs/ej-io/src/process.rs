@@ -3,6 +3,7 @@
 use std::{
     ffi::OsStr,
     io,
+    path::PathBuf,
     process::{ExitStatus, Stdio},
     sync::{
         Arc,
@@ -11,8 +12,16 @@ use std::{
     time::Duration,
 };
 
+use nix::{
+    errno::Errno,
+    sys::signal::{Signal, killpg},
+    unistd::Pid,
+};
 use tokio::process::{Child, Command};
 
+/// Time processes get to exit after being asked to before they are killed.
+pub const STOP_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
 /// Errors that can occur during process operations.
 #[derive(Debug)]
 pub enum ProcessError {
@@ -24,6 +33,17 @@ pub enum ProcessError {
     Quit,
 }
 
+/// How to spawn a process, on top of its command and arguments.
+#[derive(Debug, Clone, Default)]
+pub struct SpawnOptions {
+    /// Environment variables set on top of the inherited ones.
+    pub env: Vec<(String, String)>,
+    /// Directory the process runs in, the current one if `None`.
+    pub current_dir: Option<PathBuf>,
+    /// Whether stdin is piped, it's inherited otherwise.
+    pub piped_stdin: bool,
+}
+
 /// Current status of a running process.
 pub enum ProcessStatus {
     /// Process has completed with exit status.
@@ -36,6 +56,9 @@ pub enum ProcessStatus {
 /// Launches a subprocess with the given command and arguments using tokio.
 /// Both stdout and stderr are piped and can be accessed via the returned Child.
 ///
+/// The process is the leader of a new process group, which the processes it
+/// starts join, so [`stop_child`] can stop all of them.
+///
 /// # Arguments
 ///
 /// * `cmd` - Command to execute
@@ -57,11 +80,139 @@ pub enum ProcessStatus {
 /// }
 /// ```
 pub fn spawn_process(cmd: &str, args: Vec<String>) -> Result<Child, io::Error> {
-    Command::new(OsStr::new(&cmd))
+    spawn_process_with_env(cmd, args, &[])
+}
+
+/// Spawn a new async process with extra environment variables.
+///
+/// Same as [`spawn_process`], the process inherits the current environment
+/// with `env` set on top of it.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_io::process::spawn_process_with_env;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let env = [(String::from("GREETING"), String::from("Hello"))];
+///     let child = spawn_process_with_env(
+///         "sh",
+///         vec![String::from("-c"), String::from("echo $GREETING")],
+///         &env,
+///     )
+///     .unwrap();
+/// }
+/// ```
+pub fn spawn_process_with_env(
+    cmd: &str,
+    args: Vec<String>,
+    env: &[(String, String)],
+) -> Result<Child, io::Error> {
+    let options = SpawnOptions {
+        env: env.to_vec(),
+        ..Default::default()
+    };
+    spawn_process_with_options(cmd, args, &options)
+}
+
+/// Spawn a new async process with the given [`SpawnOptions`].
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_io::process::{SpawnOptions, spawn_process_with_options};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let options = SpawnOptions {
+///         current_dir: Some(std::env::temp_dir()),
+///         piped_stdin: true,
+///         ..Default::default()
+///     };
+///     let mut child = spawn_process_with_options("cat", Vec::new(), &options).unwrap();
+///     let stdin = child.stdin.take().unwrap();
+/// }
+/// ```
+pub fn spawn_process_with_options(
+    cmd: &str,
+    args: Vec<String>,
+    options: &SpawnOptions,
+) -> Result<Child, io::Error> {
+    let mut command = command_with_options(cmd, args, options);
+    if options.piped_stdin {
+        command.stdin(Stdio::piped());
+    }
+    command.spawn()
+}
+
+/// Spawn the commands of a pipeline, each reading the stdout of the previous one.
+///
+/// The first command gets the stdin described by `options`, the stdout of
+/// the last one and the stderr of all of them are piped. The first command
+/// leads a new process group the others join, so [`stop_child`] on the
+/// first child stops the whole pipeline.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_io::process::{SpawnOptions, spawn_pipeline};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let commands = vec![
+///         (String::from("echo"), vec![String::from("hello")]),
+///         (String::from("tr"), vec![String::from("a-z"), String::from("A-Z")]),
+///     ];
+///     let mut children = spawn_pipeline(commands, &SpawnOptions::default()).unwrap();
+///     let stdout = children.last_mut().unwrap().stdout.take().unwrap();
+/// }
+/// ```
+pub fn spawn_pipeline(
+    commands: Vec<(String, Vec<String>)>,
+    options: &SpawnOptions,
+) -> Result<Vec<Child>, io::Error> {
+    let mut children: Vec<Child> = Vec::new();
+    for (cmd, args) in commands {
+        let Some(previous) = children.last_mut() else {
+            children.push(spawn_process_with_options(&cmd, args, options)?);
+            continue;
+        };
+        let stdout = previous.stdout.take();
+        // The leader isn't reaped yet, its group is still there
+        let group = children[0].id().unwrap_or(0) as i32;
+        let spawned = stdout
+            .ok_or_else(|| io::Error::other("the previous command has no stdout"))
+            .and_then(|stdout| {
+                let mut command = command_with_options(&cmd, args, options);
+                let stdin: Stdio = stdout.try_into()?;
+                command.stdin(stdin).process_group(group);
+                command.spawn()
+            });
+        match spawned {
+            Ok(child) => children.push(child),
+            Err(err) => {
+                let _ = signal_group(Pid::from_raw(group), Signal::SIGKILL);
+                return Err(err);
+            }
+        }
+    }
+    Ok(children)
+}
+
+/// The command running `cmd` with the environment and directory of `options`.
+fn command_with_options(cmd: &str, args: Vec<String>, options: &SpawnOptions) -> Command {
+    let mut command = Command::new(OsStr::new(&cmd));
+    command
         .args(args)
+        .envs(options.env.iter().map(|(key, value)| (key, value)))
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
-        .spawn()
+        .process_group(0);
+    if let Some(current_dir) = &options.current_dir {
+        command.current_dir(current_dir);
+    }
+    command
 }
 /// Asynchronously check process status without blocking.
 ///
@@ -115,9 +266,12 @@ pub async fn get_process_status(child: &mut Child) -> Result<ProcessStatus, Proc
     }
 }
 
-/// Asynchronously terminate a child process.
+/// Asynchronously terminate a child process and the processes it started.
 ///
-/// Sends a kill signal to the child process using tokio.
+/// Sends `SIGTERM` to the process group of the child, see
+/// [`spawn_process`], and `SIGKILL` to whatever is left of it once the child
+/// exited or after [`STOP_GRACE_PERIOD`], so build tools like `make` or `ssh`
+/// started by a script don't outlive it.
 ///
 /// # Arguments
 ///
@@ -139,7 +293,32 @@ pub async fn get_process_status(child: &mut Child) -> Result<ProcessStatus, Proc
 /// }
 /// ```
 pub async fn stop_child(child: &mut Child) -> Result<(), io::Error> {
-    child.kill().await
+    stop_process_group(child, STOP_GRACE_PERIOD).await
+}
+
+/// Same as [`stop_child`], the child having `grace_period` to exit before
+/// its process group is killed.
+pub async fn stop_process_group(
+    child: &mut Child,
+    grace_period: Duration,
+) -> Result<(), io::Error> {
+    // Already reaped, its group is gone with it
+    let Some(id) = child.id() else {
+        return Ok(());
+    };
+    let group = Pid::from_raw(id as i32);
+    signal_group(group, Signal::SIGTERM)?;
+    let _ = tokio::time::timeout(grace_period, child.wait()).await;
+    signal_group(group, Signal::SIGKILL)?;
+    child.wait().await.map(|_| ())
+}
+
+/// Sends `signal` to the process `group`, the group being gone already is fine.
+fn signal_group(group: Pid, signal: Signal) -> Result<(), io::Error> {
+    match killpg(group, signal) {
+        Ok(()) | Err(Errno::ESRCH) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
 }
 /// Asynchronously capture the exit status of a child process.
 ///
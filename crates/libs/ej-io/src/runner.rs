@@ -2,24 +2,33 @@
 
 use std::{
     io::{self, BufRead, Read},
+    path::PathBuf,
     process::ExitStatus,
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicBool, Ordering},
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use tokio::{
-    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader},
-    sync::mpsc::Sender,
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::ChildStdin,
+    sync::mpsc::{Receiver, Sender, channel},
     task::{self, JoinHandle},
     time::sleep,
 };
 use tracing::{error, info};
 
-use crate::process::{
-    ProcessStatus, capture_exit_status, get_process_status, spawn_process, stop_child,
+use crate::{
+    limits::{Cgroup, ResourceLimits, limited_command},
+    output::RunOutput,
+    process::{
+        ProcessStatus, SpawnOptions, capture_exit_status, get_process_status, spawn_pipeline,
+        stop_child,
+    },
+    shell::{ShellCommand, ShellParseError, parse_pipeline},
+    usage::{ProcessUsage, UsageTracker},
 };
 
 /// Events emitted during process execution.
@@ -29,10 +38,12 @@ pub enum RunEvent {
     ProcessCreationFailed(String),
     /// Process was successfully created.
     ProcessCreated,
-    /// Process ended (true = success, false = failure).
-    ProcessEnd(bool),
+    /// Process ended (true = success, false = failure) and the resources it used.
+    ProcessEnd(bool, ProcessUsage),
     /// New output line from the process.
     ProcessNewOutputLine(String),
+    /// Process ran longer than its timeout and was killed.
+    Timeout,
 }
 
 /// High-level async process runner with event-driven output handling.
@@ -41,6 +52,25 @@ pub struct Runner {
     command: String,
     /// Command line arguments.
     args: Vec<String>,
+    /// Commands the output of the command is piped to, in order.
+    piped_to: Vec<ShellCommand>,
+    /// Environment, working directory and stdin of the process.
+    options: SpawnOptions,
+    /// What's written to the process stdin, taken by the run using it.
+    stdin: Mutex<Option<RunnerStdin>>,
+    /// Maximum time the process may run for.
+    timeout: Option<Duration>,
+    /// Resources the process may use.
+    limits: ResourceLimits,
+}
+
+/// Source of the stdin of a process.
+#[derive(Debug)]
+pub enum RunnerStdin {
+    /// Bytes written to stdin, closed once they are.
+    Bytes(Vec<u8>),
+    /// Chunks written to stdin as they're received, closed with the channel.
+    Channel(Receiver<Vec<u8>>),
 }
 
 impl Runner {
@@ -57,6 +87,11 @@ impl Runner {
         Self {
             command: command.into(),
             args: args.into_iter().map(|a| a.into()).collect(),
+            piped_to: Vec::new(),
+            options: SpawnOptions::default(),
+            stdin: Mutex::new(None),
+            timeout: None,
+            limits: ResourceLimits::default(),
         }
     }
 
@@ -73,8 +108,160 @@ impl Runner {
         Self {
             command: command.into(),
             args: Vec::new(),
+            piped_to: Vec::new(),
+            options: SpawnOptions::default(),
+            stdin: Mutex::new(None),
+            timeout: None,
+            limits: ResourceLimits::default(),
         }
     }
+
+    /// Create a runner from a shell-style command line, see [`crate::shell`].
+    ///
+    /// Commands separated by `|` are run as a pipeline, like [`Runner::pipe`]
+    /// does, without a shell in between.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_io::runner::Runner;
+    ///
+    /// let runner = Runner::from_shell("./build.sh --board 'Raspberry Pi 3' | tee build.log").unwrap();
+    /// assert_eq!(
+    ///     runner.get_full_command(),
+    ///     "./build.sh --board Raspberry Pi 3 | tee build.log"
+    /// );
+    /// ```
+    pub fn from_shell(line: &str) -> Result<Self, ShellParseError> {
+        let mut commands = parse_pipeline(line)?.into_iter();
+        // Parsing fails on empty lines, there's always a first command
+        let (command, args) = commands.next().ok_or(ShellParseError::EmptyCommand)?;
+        let mut runner = Self::new(command, args);
+        runner.piped_to = commands.collect();
+        Ok(runner)
+    }
+
+    /// Pipe the output of the process to the command of `next`.
+    ///
+    /// The commands run side by side, each one reading the output of the
+    /// previous one, and are stopped together. The stderr of every command
+    /// and the stdout of the last one are reported. The environment,
+    /// directory, stdin, timeout and limits of this runner apply to the whole
+    /// pipeline, the ones of `next` are ignored. Like with `set -o pipefail`,
+    /// the exit status is the one of the last command that failed, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_io::runner::Runner;
+    ///
+    /// let runner = Runner::new("make", vec!["-j8"]).pipe(Runner::new("tee", vec!["build.log"]));
+    /// assert_eq!(runner.get_full_command(), "make -j8 | tee build.log");
+    /// ```
+    pub fn pipe(mut self, next: Runner) -> Self {
+        self.piped_to.push((next.command, next.args));
+        self.piped_to.extend(next.piped_to);
+        self
+    }
+
+    /// Set environment variables for the process, on top of the ones it inherits.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_io::runner::Runner;
+    ///
+    /// let runner = Runner::new("sh", vec!["-c", "echo $SERIAL_PORT"])
+    ///     .with_env([("SERIAL_PORT", "/dev/ttyUSB0")]);
+    /// ```
+    pub fn with_env<K: Into<String>, V: Into<String>>(
+        mut self,
+        env: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        self.options.env.extend(
+            env.into_iter()
+                .map(|(key, value)| (key.into(), value.into())),
+        );
+        self
+    }
+
+    /// Run the process in `directory` instead of the current one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_io::runner::Runner;
+    ///
+    /// let runner = Runner::new_without_args("./build.sh").with_current_dir("/srv/boards/rpi3");
+    /// ```
+    pub fn with_current_dir(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.options.current_dir = Some(directory.into());
+        self
+    }
+
+    /// Write `stdin` to the process stdin, which is inherited otherwise.
+    ///
+    /// Bytes are written on every run, a channel is read by the first run only.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_io::runner::{Runner, RunnerStdin};
+    /// use tokio::sync::mpsc;
+    ///
+    /// let runner = Runner::new_without_args("./flash.sh")
+    ///     .with_stdin(RunnerStdin::Bytes(b"y\n".to_vec()));
+    ///
+    /// let (stdin_tx, stdin_rx) = mpsc::channel(10);
+    /// let interactive = Runner::new_without_args("./console.sh")
+    ///     .with_stdin(RunnerStdin::Channel(stdin_rx));
+    /// ```
+    pub fn with_stdin(mut self, stdin: RunnerStdin) -> Self {
+        self.options.piped_stdin = true;
+        self.stdin = Mutex::new(Some(stdin));
+        self
+    }
+
+    /// Kill the process if it's still running after `timeout`.
+    ///
+    /// A `RunEvent::Timeout` is sent when the process is killed, before its
+    /// `RunEvent::ProcessEnd`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_io::runner::Runner;
+    /// use std::time::Duration;
+    ///
+    /// let runner = Runner::new("./flash.sh", vec!["firmware.bin"])
+    ///     .with_timeout(Duration::from_secs(120));
+    /// ```
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Run the process with resource `limits`.
+    ///
+    /// When they include a cgroup, one is created for every run and removed
+    /// once the process ended, a run failing with a
+    /// `RunEvent::ProcessCreationFailed` if it can't be created.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_io::{limits::ResourceLimits, runner::Runner};
+    ///
+    /// let runner = Runner::new_without_args("./build.sh").with_limits(ResourceLimits {
+    ///     max_open_files: Some(1024),
+    ///     ..Default::default()
+    /// });
+    /// ```
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// Get the full command string with arguments.
     ///
     /// # Examples
@@ -86,7 +273,11 @@ impl Runner {
     /// assert_eq!(runner.get_full_command(), "ls -la");
     /// ```
     pub fn get_full_command(&self) -> String {
-        format!("{} {}", &self.command, &self.args.join(" "))
+        let mut full_command = format!("{} {}", &self.command, &self.args.join(" "));
+        for (command, args) in &self.piped_to {
+            full_command = format!("{} | {command} {}", full_command.trim_end(), args.join(" "));
+        }
+        full_command
     }
     async fn read_stream<T: AsyncRead + Unpin>(tx: Sender<RunEvent>, mut stream: T) {
         let mut buffer = [0; 1024];
@@ -104,6 +295,67 @@ impl Runner {
             }
         }
     }
+    /// The stdin source of the next run, bytes being kept for the runs after it.
+    fn take_stdin(&self) -> Option<RunnerStdin> {
+        let mut stdin = self.stdin.lock().unwrap_or_else(|err| err.into_inner());
+        match stdin.take() {
+            Some(RunnerStdin::Bytes(bytes)) => {
+                *stdin = Some(RunnerStdin::Bytes(bytes.clone()));
+                Some(RunnerStdin::Bytes(bytes))
+            }
+            source => source,
+        }
+    }
+
+    /// Writes `source` to the process `stdin`, closing it once done.
+    fn launch_stdin_writer(source: Option<RunnerStdin>, mut stdin: ChildStdin) -> JoinHandle<()> {
+        task::spawn(async move {
+            match source {
+                Some(RunnerStdin::Bytes(bytes)) => {
+                    let _ = stdin.write_all(&bytes).await;
+                }
+                Some(RunnerStdin::Channel(mut rx)) => {
+                    while let Some(chunk) = rx.recv().await {
+                        if stdin.write_all(&chunk).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                None => {}
+            }
+        })
+    }
+
+    /// Exit status of a pipeline, the one of the last command that failed if any.
+    ///
+    /// `None` if a command has no exit status.
+    fn pipeline_status(statuses: &[Option<ExitStatus>]) -> Option<ExitStatus> {
+        let statuses: Vec<ExitStatus> = statuses.iter().copied().collect::<Option<_>>()?;
+        statuses
+            .iter()
+            .rev()
+            .find(|status| !status.success())
+            .or(statuses.last())
+            .copied()
+    }
+
+    fn combined_usage(usages: &[UsageTracker]) -> ProcessUsage {
+        usages
+            .iter()
+            .map(UsageTracker::usage)
+            .reduce(ProcessUsage::combined)
+            .unwrap_or_default()
+    }
+
+    async fn remove_cgroup(cgroup: Option<Cgroup>) {
+        if let Some(cgroup) = cgroup {
+            let path = cgroup.path().to_path_buf();
+            if let Err(err) = cgroup.remove().await {
+                error!("Failed to remove cgroup {} - {err}", path.display());
+            }
+        }
+    }
+
     async fn launch_stream_reader<T>(tx: Sender<RunEvent>, stream: T) -> JoinHandle<()>
     where
         T: AsyncRead + Unpin + Send + 'static,
@@ -114,7 +366,8 @@ impl Runner {
     /// Asynchronously run the process with event monitoring.
     ///
     /// Starts the process and monitors its execution asynchronously, sending events via the provided tokio channel.
-    /// Reads stdout and stderr concurrently until the process finishes or is stopped.
+    /// Reads stdout and stderr concurrently until the process finishes, is stopped
+    /// or runs past its timeout.
     ///
     /// # Arguments
     ///
@@ -147,17 +400,47 @@ impl Runner {
         tx: Sender<RunEvent>,
         should_stop: Arc<AtomicBool>,
     ) -> Option<ExitStatus> {
-        let mut process = spawn_process(&self.command, self.args.clone())
-            .map_err(async |err| {
+        let cgroup = match &self.limits.cgroup {
+            Some(limits) => match Cgroup::create(limits) {
+                Ok(cgroup) => Some(cgroup),
+                Err(err) => {
+                    let _ = tx
+                        .send(RunEvent::ProcessCreationFailed(err.to_string()))
+                        .await;
+                    return None;
+                }
+            },
+            None => None,
+        };
+        let commands = std::iter::once((self.command.clone(), self.args.clone()))
+            .chain(self.piped_to.iter().cloned())
+            .map(|(command, args): ShellCommand| {
+                if self.limits.is_empty() {
+                    (command, args)
+                } else {
+                    limited_command(&command, args, &self.limits, cgroup.as_ref())
+                }
+            })
+            .collect();
+
+        let mut processes = match spawn_pipeline(commands, &self.options) {
+            Ok(processes) => processes,
+            Err(err) => {
                 let _ = tx
                     .send(RunEvent::ProcessCreationFailed(format!("{:?}", err)))
                     .await;
-            })
-            .ok()?;
+                Runner::remove_cgroup(cgroup).await;
+                return None;
+            }
+        };
 
         let _ = tx.send(RunEvent::ProcessCreated).await;
 
-        let stdout_task = if let Some(stdout) = process.stdout.take() {
+        if let Some(stdin) = processes[0].stdin.take() {
+            Runner::launch_stdin_writer(self.take_stdin(), stdin);
+        }
+
+        let stdout_task = if let Some(stdout) = processes.last_mut().and_then(|p| p.stdout.take()) {
             info!("Launching stdout reader function");
             Some(Runner::launch_stream_reader(tx.clone(), stdout))
         } else {
@@ -165,32 +448,64 @@ impl Runner {
             None
         };
 
-        let stderr_task = if let Some(stderr) = process.stderr.take() {
-            info!("Launching stderr reader function");
-            Some(Runner::launch_stream_reader(tx.clone(), stderr))
-        } else {
-            error!("Failed to launch stderr reader function");
-            None
-        };
+        let mut stderr_tasks = Vec::new();
+        for process in processes.iter_mut() {
+            if let Some(stderr) = process.stderr.take() {
+                info!("Launching stderr reader function");
+                stderr_tasks.push(Runner::launch_stream_reader(tx.clone(), stderr));
+            } else {
+                error!("Failed to launch stderr reader function");
+            }
+        }
 
+        let timeout = self.timeout;
+        let timeout_tx = tx.clone();
+        let mut usages: Vec<UsageTracker> = processes.iter().map(UsageTracker::new).collect();
         let process_task = task::spawn(async move {
+            let started = Instant::now();
+            let mut statuses = vec![None; processes.len()];
             loop {
-                if should_stop.load(Ordering::Relaxed) {
-                    if stop_child(&mut process).await.is_ok() {
-                        return capture_exit_status(&mut process).await.ok();
+                let timed_out = timeout.is_some_and(|timeout| started.elapsed() >= timeout);
+                if timed_out {
+                    let _ = timeout_tx.send(RunEvent::Timeout).await;
+                }
+                if timed_out || should_stop.load(Ordering::Relaxed) {
+                    // Every command is in the process group of the first one
+                    if stop_child(&mut processes[0]).await.is_err() {
+                        return (None, Runner::combined_usage(&usages));
                     }
-                    return None;
+                    for (process, status) in processes.iter_mut().zip(statuses.iter_mut()) {
+                        if status.is_none() {
+                            *status = capture_exit_status(process).await.ok();
+                        }
+                    }
+                    break;
                 }
 
-                // Check process status
-                match get_process_status(&mut process).await {
-                    Err(_) => return None,
-                    Ok(ProcessStatus::Done(status)) => return Some(status),
-                    Ok(ProcessStatus::Running) => {
-                        sleep(Duration::from_millis(100)).await;
+                for ((process, usage), status) in processes
+                    .iter_mut()
+                    .zip(usages.iter_mut())
+                    .zip(statuses.iter_mut())
+                {
+                    // Don't reap the process before sampling it once it exited
+                    if status.is_some() || usage.sample() {
+                        continue;
+                    }
+                    match get_process_status(process).await {
+                        Err(_) => return (None, Runner::combined_usage(&usages)),
+                        Ok(ProcessStatus::Done(exit_status)) => *status = Some(exit_status),
+                        Ok(ProcessStatus::Running) => {}
                     }
                 }
+                if statuses.iter().all(Option::is_some) {
+                    break;
+                }
+                sleep(Duration::from_millis(100)).await;
             }
+            (
+                Runner::pipeline_status(&statuses),
+                Runner::combined_usage(&usages),
+            )
         });
 
         let (process_result, stdout_result, stderr_result) = tokio::join!(
@@ -201,16 +516,31 @@ impl Runner {
                 }
             },
             async {
-                if let Some(task) = stderr_task {
+                for task in stderr_tasks {
                     task.await;
                 }
             }
         );
-        let exit_status = process_result.ok().flatten();
+        Runner::remove_cgroup(cgroup).await;
+        let (exit_status, usage) = process_result.unwrap_or_default();
         let success = exit_status.map_or(false, |status| status.success());
-        let _ = tx.send(RunEvent::ProcessEnd(success)).await;
+        let _ = tx.send(RunEvent::ProcessEnd(success, usage)).await;
         exit_status
     }
+
+    /// Run the process in a new task, its output being read as bytes.
+    ///
+    /// See [`RunOutput`], the returned handle gives the exit status like
+    /// [`Runner::run`] does. The process is slowed down if its output isn't
+    /// read.
+    pub fn spawn_with_output(
+        self,
+        should_stop: Arc<AtomicBool>,
+    ) -> (RunOutput, JoinHandle<Option<ExitStatus>>) {
+        let (tx, rx) = channel(100);
+        let handle = task::spawn(async move { self.run(tx, should_stop).await });
+        (RunOutput::new(rx), handle)
+    }
 }
 
 #[cfg(test)]
@@ -290,6 +620,196 @@ mod test {
         let target = "./infinit_loop_map_signals";
         compile_and_run_blocking_program(c_file, target).await;
     }
+    #[tokio::test]
+    async fn test_env() {
+        let runner = Runner::new("sh", vec!["-c", "echo $EJ_RUNNER_TEST"])
+            .with_env([("EJ_RUNNER_TEST", "from the runner")]);
+        assert_eq!(run_output(runner).await, "from the runner\n");
+    }
+
+    /// Whether `pid` is gone, zombies waiting to be reaped by init included.
+    fn is_dead(pid: &str) -> bool {
+        match std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+            Ok(stat) => stat
+                .rsplit(')')
+                .next()
+                .is_some_and(|rest| rest.trim_start().starts_with('Z')),
+            Err(_) => true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_kills_process_group() {
+        // The shell starts a grandchild ignoring SIGTERM and waits for it
+        let runner = Runner::new("sh", vec!["-c", "trap '' TERM; sleep 30 & echo $!; wait"]);
+        let (tx, mut rx) = channel(10);
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = task::spawn({
+            let stop = stop.clone();
+            async move { runner.run(tx, stop).await }
+        });
+
+        let grandchild = loop {
+            match rx.recv().await.expect("The grandchild PID") {
+                RunEvent::ProcessNewOutputLine(line) => break line.trim().to_string(),
+                _ => continue,
+            }
+        };
+        assert!(!is_dead(&grandchild));
+
+        stop.store(true, Ordering::Relaxed);
+        tokio::time::timeout(Duration::from_secs(15), handle)
+            .await
+            .expect("The runner to stop the process")
+            .expect("Couldn't join thread");
+        let mut waited = Duration::ZERO;
+        while !is_dead(&grandchild) && waited < Duration::from_secs(2) {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            waited += Duration::from_millis(50);
+        }
+        assert!(is_dead(&grandchild), "grandchild {grandchild} survived");
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let runner =
+            Runner::new("sh", vec!["-c", "sleep 30"]).with_timeout(Duration::from_millis(300));
+        let (tx, mut rx) = channel(10);
+        let exit = tokio::time::timeout(
+            Duration::from_secs(10),
+            runner.run(tx, Arc::new(AtomicBool::new(false))),
+        )
+        .await
+        .expect("The runner to kill the process on timeout");
+        assert!(exit.is_some_and(|status| !status.success()));
+
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+        let Some(RunEvent::ProcessEnd(false, usage)) = events.pop() else {
+            panic!("Expected the process to end unsuccessfully, got {events:?}");
+        };
+        assert!(usage.wall_time >= Duration::from_millis(300));
+        assert_eq!(events, vec![RunEvent::ProcessCreated, RunEvent::Timeout]);
+    }
+
+    #[tokio::test]
+    async fn test_usage() {
+        // The busy loop runs in a child the shell waits for
+        let runner = Runner::new(
+            "sh",
+            vec![
+                "-c",
+                "sh -c 'i=0; while [ $i -lt 300000 ]; do i=$((i+1)); done'; sleep 0.2",
+            ],
+        );
+        let (tx, mut rx) = channel(10);
+        let exit = runner.run(tx, Arc::new(AtomicBool::new(false))).await;
+        assert!(exit.is_some_and(|status| status.success()));
+
+        let mut usage = None;
+        while let Some(event) = rx.recv().await {
+            if let RunEvent::ProcessEnd(true, end_usage) = event {
+                usage = Some(end_usage);
+            }
+        }
+        let usage = usage.expect("The process to end successfully");
+        assert!(usage.cpu_time >= Duration::from_millis(50), "{usage:?}");
+        assert!(usage.wall_time >= usage.cpu_time, "{usage:?}");
+        assert!(
+            usage.peak_rss_bytes.is_some_and(|bytes| bytes > 0),
+            "{usage:?}"
+        );
+    }
+
+    /// Runs `runner` to completion and returns its output.
+    async fn run_output(runner: Runner) -> String {
+        let (tx, mut rx) = channel(10);
+        let exit = runner.run(tx, Arc::new(AtomicBool::new(false))).await;
+        assert!(exit.is_some_and(|status| status.success()));
+
+        let mut output = String::new();
+        while let Some(event) = rx.recv().await {
+            if let RunEvent::ProcessNewOutputLine(line) = event {
+                output.push_str(&line);
+            }
+        }
+        output
+    }
+
+    #[tokio::test]
+    async fn test_current_dir() {
+        let directory = env::temp_dir().canonicalize().unwrap();
+        let runner = Runner::new_without_args("pwd").with_current_dir(&directory);
+        assert_eq!(
+            run_output(runner).await,
+            format!("{}\n", directory.display())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let runner = Runner::new("sh", vec!["-c", "ulimit -v; ulimit -t; ulimit -n"]).with_limits(
+            ResourceLimits {
+                max_virtual_memory_bytes: Some(4 * 1024 * 1024 * 1024),
+                max_cpu_time: Some(Duration::from_millis(59_500)),
+                max_open_files: Some(64),
+                cgroup: None,
+            },
+        );
+        assert_eq!(run_output(runner).await, "4194304\n60\n64\n");
+    }
+
+    #[tokio::test]
+    async fn test_pipeline() {
+        let runner = Runner::from_shell("printf 'b\\na\\n' | sort | tr a-z A-Z").unwrap();
+        assert_eq!(run_output(runner).await, "A\nB\n");
+
+        // The status of a failing command isn't hidden by the next ones
+        let runner = Runner::new("sh", vec!["-c", "exit 3"]).pipe(Runner::new_without_args("cat"));
+        let (tx, _rx) = channel(10);
+        let exit = runner.run(tx, Arc::new(AtomicBool::new(false))).await;
+        assert_eq!(exit.and_then(|status| status.code()), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_output_stream() {
+        use futures_core::Stream;
+        use std::pin::Pin;
+
+        let runner = Runner::new("sh", vec!["-c", "echo flashing; sleep 0.2; echo done"]);
+        let (mut output, handle) = runner.spawn_with_output(Arc::new(AtomicBool::new(false)));
+        let mut chunks = Vec::new();
+        while let Some(chunk) = std::future::poll_fn(|cx| Pin::new(&mut output).poll_next(cx)).await
+        {
+            chunks.push(chunk.expect("A chunk of output"));
+        }
+        assert_eq!(chunks, vec!["flashing\n", "done\n"]);
+        assert!(handle.await.unwrap().is_some_and(|status| status.success()));
+
+        let runner = Runner::new_without_args("./does-not-exist");
+        let (mut output, handle) = runner.spawn_with_output(Arc::new(AtomicBool::new(false)));
+        let mut bytes = Vec::new();
+        assert!(output.read_to_end(&mut bytes).await.is_err());
+        assert!(handle.await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stdin() {
+        let runner = Runner::new_without_args("cat")
+            .with_stdin(RunnerStdin::Bytes(b"flash\nconfirm\n".to_vec()));
+        assert_eq!(run_output(runner).await, "flash\nconfirm\n");
+
+        let (stdin_tx, stdin_rx) = channel(10);
+        let runner = Runner::new("sh", vec!["-c", "read answer; echo \"got $answer\""])
+            .with_stdin(RunnerStdin::Channel(stdin_rx));
+        let output = task::spawn(run_output(runner));
+        stdin_tx.send(b"yes\n".to_vec()).await.unwrap();
+        drop(stdin_tx);
+        assert_eq!(output.await.unwrap(), "got yes\n");
+    }
+
     #[tokio::test]
     async fn test_infinite_loop_with_timeouts() {
         // This code loops forever and prints Hello * every second
@@ -0,0 +1,197 @@
+//! Shell-style command lines.
+//!
+//! Board scripts are often written as shell command lines, like
+//! `make -j8 | tee build.log`. Running them with `sh -c` puts a shell between
+//! the runner and the commands, which may not forward the signals it gets.
+//! [`parse_pipeline`] splits such lines into commands and arguments instead,
+//! following the quoting rules of `sh`:
+//!
+//! - Words are separated by unquoted blanks.
+//! - Single quotes keep everything up to the next single quote as is.
+//! - Double quotes keep everything but `\"`, `\\`, `` \` `` and `\$` as is.
+//! - A backslash outside of quotes keeps the next character as is.
+//! - An unquoted `#` starting a word starts a comment.
+//! - An unquoted `|` separates the commands of a pipeline.
+//!
+//! Nothing is expanded, variables, command substitutions, redirections and
+//! command lists are rejected instead of being passed as arguments.
+
+use std::fmt;
+
+/// Errors of [`parse_pipeline`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShellParseError {
+    /// A quote isn't closed.
+    UnterminatedQuote(char),
+    /// The line or a command of the pipeline is empty.
+    EmptyCommand,
+    /// A character only a shell could handle, like `$`, `>` or `;`.
+    Unsupported(char),
+}
+
+impl fmt::Display for ShellParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnterminatedQuote(quote) => write!(f, "unterminated {quote} quote"),
+            Self::EmptyCommand => write!(f, "empty command"),
+            Self::Unsupported(character) => write!(
+                f,
+                "`{character}` isn't supported, quote it or run the line with `sh -c`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShellParseError {}
+
+/// A command and its arguments.
+pub type ShellCommand = (String, Vec<String>);
+
+/// Splits a command line into the commands of its pipeline.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_io::shell::parse_pipeline;
+///
+/// let commands = parse_pipeline(r#"make -j8 CFLAGS="-O2 -g" | tee 'build log.txt'"#).unwrap();
+/// assert_eq!(commands[0].0, "make");
+/// assert_eq!(commands[0].1, vec!["-j8", "CFLAGS=-O2 -g"]);
+/// assert_eq!(commands[1].1, vec!["build log.txt"]);
+/// ```
+pub fn parse_pipeline(line: &str) -> Result<Vec<ShellCommand>, ShellParseError> {
+    let mut commands = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    // `None` between words, empty quotes starting an empty word
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+
+    let mut end_command = |words: &mut Vec<String>| -> Result<(), ShellParseError> {
+        let mut words = std::mem::take(words).into_iter();
+        let command = words.next().ok_or(ShellParseError::EmptyCommand)?;
+        commands.push((command, words.collect()));
+        Ok(())
+    };
+
+    while let Some(character) = chars.next() {
+        match character {
+            ' ' | '\t' | '\n' => words.extend(word.take()),
+            '#' if word.is_none() => break,
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(character) => word.push(character),
+                        None => return Err(ShellParseError::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '\\' | '`' | '$')) => word.push(escaped),
+                            Some('\n') => {}
+                            Some(other) => {
+                                word.push('\\');
+                                word.push(other);
+                            }
+                            None => return Err(ShellParseError::UnterminatedQuote('"')),
+                        },
+                        Some(unsupported @ ('$' | '`')) => {
+                            return Err(ShellParseError::Unsupported(unsupported));
+                        }
+                        Some(character) => word.push(character),
+                        None => return Err(ShellParseError::UnterminatedQuote('"')),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                // Line continuation
+                Some('\n') => {}
+                Some(escaped) => word.get_or_insert_with(String::new).push(escaped),
+                None => word.get_or_insert_with(String::new).push('\\'),
+            },
+            '|' => {
+                words.extend(word.take());
+                end_command(&mut words)?;
+            }
+            '$' | '`' | ';' | '&' | '<' | '>' | '(' | ')' => {
+                return Err(ShellParseError::Unsupported(character));
+            }
+            character => word.get_or_insert_with(String::new).push(character),
+        }
+    }
+    words.extend(word);
+    end_command(&mut words)?;
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(command: &str, args: &[&str]) -> ShellCommand {
+        (
+            command.to_string(),
+            args.iter().map(|arg| arg.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn parse_pipeline_follows_sh_quoting() {
+        assert_eq!(
+            parse_pipeline(
+                r#"  ./flash.sh --port "/dev/tty USB0" 'it'\''s' a\ b "" \"x\" # comment"#
+            ),
+            Ok(vec![command(
+                "./flash.sh",
+                &["--port", "/dev/tty USB0", "it's", "a b", "", "\"x\""]
+            )])
+        );
+        assert_eq!(
+            parse_pipeline("make 2|tee build.log | grep -v '|'"),
+            Ok(vec![
+                command("make", &["2"]),
+                command("tee", &["build.log"]),
+                command("grep", &["-v", "|"]),
+            ])
+        );
+        assert_eq!(
+            parse_pipeline(
+                r#"echo "price: \$5 \n" done\
+            "#
+            ),
+            Ok(vec![command("echo", &["price: $5 \\n", "done"])])
+        );
+    }
+
+    #[test]
+    fn parse_pipeline_rejects_what_needs_a_shell() {
+        assert_eq!(parse_pipeline("   "), Err(ShellParseError::EmptyCommand));
+        assert_eq!(parse_pipeline("make |"), Err(ShellParseError::EmptyCommand));
+        assert_eq!(
+            parse_pipeline("make || true"),
+            Err(ShellParseError::EmptyCommand)
+        );
+        assert_eq!(
+            parse_pipeline("echo 'oops"),
+            Err(ShellParseError::UnterminatedQuote('\''))
+        );
+        assert_eq!(
+            parse_pipeline("make > log"),
+            Err(ShellParseError::Unsupported('>'))
+        );
+        assert_eq!(
+            parse_pipeline("echo \"$HOME\""),
+            Err(ShellParseError::Unsupported('$'))
+        );
+        assert_eq!(
+            parse_pipeline("make; make install"),
+            Err(ShellParseError::Unsupported(';'))
+        );
+    }
+}
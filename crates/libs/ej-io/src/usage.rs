@@ -0,0 +1,172 @@
+//! Resources used by processes.
+//!
+//! Usage is read from `/proc` while the process runs. Exited processes are
+//! sampled once more before being reaped, which `waitid` with `WNOWAIT` lets
+//! us do, so their CPU time includes the children they waited for, like the
+//! compilers started by a build script. Outside of Linux only the wall time
+//! is measured.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use nix::unistd::{Pid, SysconfVar, sysconf};
+use tokio::process::Child;
+
+/// Resources used by a process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcessUsage {
+    /// Time between the process start and its exit.
+    pub wall_time: Duration,
+    /// User and system CPU time of the process and the children it waited for.
+    pub cpu_time: Duration,
+    /// Peak resident set size of the process, without its children.
+    ///
+    /// Sampled while the process runs, `None` if it exited before the first sample.
+    pub peak_rss_bytes: Option<u64>,
+}
+
+impl fmt::Display for ProcessUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "wall time {:.2}s, CPU time {:.2}s",
+            self.wall_time.as_secs_f64(),
+            self.cpu_time.as_secs_f64()
+        )?;
+        if let Some(bytes) = self.peak_rss_bytes {
+            write!(f, ", peak RSS {:.1} MiB", bytes as f64 / (1024.0 * 1024.0))?;
+        }
+        Ok(())
+    }
+}
+
+impl ProcessUsage {
+    /// Usage of `self` and `other` running side by side, like the commands
+    /// of a pipeline: the longest wall time, the total CPU time and the
+    /// largest peak RSS.
+    pub fn combined(self, other: Self) -> Self {
+        Self {
+            wall_time: self.wall_time.max(other.wall_time),
+            cpu_time: self.cpu_time + other.cpu_time,
+            peak_rss_bytes: self.peak_rss_bytes.max(other.peak_rss_bytes),
+        }
+    }
+}
+
+/// Samples the resources used by a child process until it's reaped.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_io::{process::spawn_process, usage::UsageTracker};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut child = spawn_process("sleep", vec!["1".to_string()]).unwrap();
+///     let mut tracker = UsageTracker::new(&child);
+///     while tracker.sample() {
+///         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+///     }
+///     child.wait().await.unwrap();
+///     assert!(tracker.usage().wall_time.as_secs_f64() >= 1.0);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct UsageTracker {
+    pid: Option<Pid>,
+    started: Instant,
+    ended: Option<Instant>,
+    usage: ProcessUsage,
+}
+
+impl UsageTracker {
+    /// Starts tracking `child`, which should have just been spawned.
+    pub fn new(child: &Child) -> Self {
+        Self {
+            pid: child.id().map(|id| Pid::from_raw(id as i32)),
+            started: Instant::now(),
+            ended: None,
+            usage: ProcessUsage::default(),
+        }
+    }
+
+    /// Samples the resources used so far.
+    ///
+    /// Returns whether the process is known to be running. Once it isn't,
+    /// the process has to be reaped, with `try_wait` or `wait`, but not
+    /// before: its last CPU time would be lost.
+    pub fn sample(&mut self) -> bool {
+        let Some(pid) = self.pid else {
+            return false;
+        };
+        let running = is_running(pid);
+        if let Some(cpu_time) = read_cpu_time(pid) {
+            self.usage.cpu_time = cpu_time;
+        }
+        // The high-water mark only grows while the process runs the same program
+        if let Some(peak_rss_bytes) = read_peak_rss_bytes(pid) {
+            self.usage.peak_rss_bytes = Some(peak_rss_bytes);
+        }
+        if !running {
+            self.ended.get_or_insert_with(Instant::now);
+        }
+        running
+    }
+
+    /// The resources used, as of the last sample for the CPU time and peak RSS.
+    pub fn usage(&self) -> ProcessUsage {
+        let ended = self.ended.unwrap_or_else(Instant::now);
+        ProcessUsage {
+            wall_time: ended.duration_since(self.started),
+            ..self.usage
+        }
+    }
+}
+
+/// Whether `pid` is a child still running, without reaping it if it exited.
+#[cfg(target_os = "linux")]
+fn is_running(pid: Pid) -> bool {
+    use nix::sys::wait::{Id, WaitPidFlag, WaitStatus, waitid};
+
+    let flags = WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG | WaitPidFlag::WNOWAIT;
+    matches!(waitid(Id::Pid(pid), flags), Ok(WaitStatus::StillAlive))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_running(_pid: Pid) -> bool {
+    false
+}
+
+/// CPU time of `pid` and of the children it waited for, from `/proc/<pid>/stat`.
+fn read_cpu_time(pid: Pid) -> Option<Duration> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The command name may hold spaces, the fields after it start with the state
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    // utime, stime, cutime and cstime, fields 14 to 17 of proc(5)
+    let ticks = fields
+        .get(11..15)?
+        .iter()
+        .map(|field| field.parse::<u64>().ok())
+        .sum::<Option<u64>>()?;
+    let ticks_per_second = sysconf(SysconfVar::CLK_TCK).ok()??;
+    let ticks_per_second = u64::try_from(ticks_per_second).ok()?.max(1);
+    Some(Duration::from_millis(ticks * 1000 / ticks_per_second))
+}
+
+/// Peak resident set size of `pid`, from `VmHWM` in `/proc/<pid>/status`.
+///
+/// Exited processes don't have one anymore.
+fn read_peak_rss_bytes(pid: Pid) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let kilobytes = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
@@ -0,0 +1,94 @@
+//! Audit log of security-relevant actions.
+//!
+//! Entries are only ever appended, they aren't updated nor deleted.
+
+use crate::prelude::*;
+use crate::{db::connection::DbConnection, schema::ejauditlog::dsl::*};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// A recorded action.
+#[derive(Debug, Clone, Queryable, Selectable, PartialEq)]
+#[diesel(table_name = crate::schema::ejauditlog)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EjAuditLog {
+    /// The unique entry ID.
+    pub id: Uuid,
+    /// What was done.
+    pub action: String,
+    /// The client or builder that did it, if known.
+    pub actor_id: Option<Uuid>,
+    /// What it was done to, if anything.
+    pub target: Option<String>,
+    /// When it was done.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Data for recording an action.
+#[derive(Insertable, PartialEq, Debug, Clone)]
+#[diesel(table_name = crate::schema::ejauditlog)]
+pub struct EjAuditLogCreate {
+    /// What was done.
+    pub action: String,
+    /// The client or builder that did it, if known.
+    pub actor_id: Option<Uuid>,
+    /// What it was done to, if anything.
+    pub target: Option<String>,
+}
+
+/// Criteria used to select audit log entries.
+///
+/// Unset criteria match every entry.
+#[derive(Debug, Clone, Default)]
+pub struct EjAuditLogFilter {
+    /// Only entries for this action.
+    pub action: Option<String>,
+    /// Only entries recorded for this actor.
+    pub actor_id: Option<Uuid>,
+    /// Only entries recorded at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries recorded before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Maximum number of entries returned.
+    pub limit: i64,
+}
+
+impl EjAuditLogCreate {
+    /// Saves the entry to the database.
+    pub fn save(self, connection: &DbConnection) -> Result<EjAuditLog> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::insert_into(ejauditlog)
+            .values(&self)
+            .returning(EjAuditLog::as_returning())
+            .get_result(conn)?)
+    }
+}
+
+impl EjAuditLog {
+    /// Fetches the entries matching `filter`, newest first.
+    pub fn fetch_filtered(
+        filter: &EjAuditLogFilter,
+        connection: &DbConnection,
+    ) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+        let mut query = ejauditlog.into_boxed();
+        if let Some(target_action) = &filter.action {
+            query = query.filter(action.eq(target_action));
+        }
+        if let Some(target_actor) = filter.actor_id {
+            query = query.filter(actor_id.eq(target_actor));
+        }
+        if let Some(since) = filter.since {
+            query = query.filter(created_at.ge(since));
+        }
+        if let Some(until) = filter.until {
+            query = query.filter(created_at.lt(until));
+        }
+        Ok(query
+            .order(created_at.desc())
+            .limit(filter.limit)
+            .select(EjAuditLog::as_select())
+            .load(conn)?)
+    }
+}
@@ -0,0 +1,6 @@
+//! Audit models.
+//!
+//! This module contains data models for recording security-relevant
+//! actions performed in the ej system.
+
+pub mod ejaudit_log;
@@ -67,6 +67,17 @@ impl ClientPermission {
     }
 
     /// Fetches all permissions for a given client.
+    pub fn delete(conn: &DbConnection, key: &ClientPermissionKey) -> Result<usize> {
+        use crate::schema::client_permission::dsl::*;
+        let conn = &mut conn.pool.get()?;
+        Ok(diesel::delete(
+            client_permission
+                .filter(ejclient_id.eq(key.ej_client_id))
+                .filter(permission_id.eq(&key.permission_id)),
+        )
+        .execute(conn)?)
+    }
+
     pub fn fetch_by_client<'a>(
         conn: &DbConnection,
         client: &'a EjClient,
@@ -0,0 +1,110 @@
+//! Client role associations for authorization.
+
+use crate::{
+    auth::{permission::Permission, role::Role},
+    client::ejclient::EjClient,
+    db::connection::DbConnection,
+    prelude::*,
+};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Assigns a role to a client.
+#[derive(Identifiable, Selectable, Queryable, Associations, Debug)]
+#[diesel(belongs_to(EjClient, foreign_key = ejclient_id))]
+#[diesel(belongs_to(Role))]
+#[diesel(table_name = crate::schema::client_role)]
+#[diesel(primary_key(ejclient_id, role_id))]
+pub struct ClientRole {
+    /// The client ID.
+    pub ejclient_id: Uuid,
+    /// The role ID.
+    pub role_id: String,
+    /// When this association was created.
+    pub created_at: DateTime<Utc>,
+    /// When this association was last updated.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Data for assigning a role to a client.
+#[derive(Insertable, PartialEq, Debug, Clone)]
+#[diesel(table_name = crate::schema::client_role)]
+pub struct NewClientRole {
+    /// The client ID.
+    pub ejclient_id: Uuid,
+    /// The role ID.
+    pub role_id: String,
+}
+
+/// Composite key for identifying a client role.
+pub struct ClientRoleKey {
+    /// The client ID.
+    pub ej_client_id: Uuid,
+    /// The role ID.
+    pub role_id: String,
+}
+
+impl ClientRole {
+    /// Assigns a role to a client.
+    pub fn new(conn: &DbConnection, item: NewClientRole) -> Result<Self> {
+        let connection = &mut conn.pool.get()?;
+        Ok(diesel::insert_into(crate::schema::client_role::table)
+            .values(item)
+            .returning(ClientRole::as_returning())
+            .get_result(connection)?)
+    }
+
+    /// Fetches a client role by its composite key.
+    pub fn fetch_by_id(conn: &DbConnection, key: &ClientRoleKey) -> Result<Self> {
+        use crate::schema::client_role::dsl::*;
+        let conn = &mut conn.pool.get()?;
+        Ok(client_role
+            .filter(ejclient_id.eq(key.ej_client_id))
+            .filter(role_id.eq(&key.role_id))
+            .select(ClientRole::as_select())
+            .get_result(conn)?)
+    }
+
+    /// Removes a role from a client.
+    ///
+    /// # Returns
+    ///
+    /// The number of removed assignments, 0 if the client didn't have the role.
+    pub fn delete(conn: &DbConnection, key: &ClientRoleKey) -> Result<usize> {
+        use crate::schema::client_role::dsl::*;
+        let conn = &mut conn.pool.get()?;
+        Ok(diesel::delete(
+            client_role
+                .filter(ejclient_id.eq(key.ej_client_id))
+                .filter(role_id.eq(&key.role_id)),
+        )
+        .execute(conn)?)
+    }
+
+    /// Fetches the roles assigned to a client.
+    pub fn fetch_roles(conn: &DbConnection, client: &EjClient) -> Result<Vec<Role>> {
+        let conn = &mut conn.pool.get()?;
+        Ok(ClientRole::belonging_to(client)
+            .inner_join(crate::schema::role::table)
+            .select(Role::as_select())
+            .order(crate::schema::role::id.asc())
+            .load(conn)?)
+    }
+
+    /// Fetches every permission granted to a client through its roles.
+    pub fn fetch_role_permissions(
+        conn: &DbConnection,
+        client: &EjClient,
+    ) -> Result<Vec<Permission>> {
+        use crate::schema::role_permission;
+        let conn = &mut conn.pool.get()?;
+        let roles = ClientRole::belonging_to(client).select(crate::schema::client_role::role_id);
+        Ok(role_permission::table
+            .filter(role_permission::role_id.eq_any(roles))
+            .inner_join(crate::schema::permission::table)
+            .select(Permission::as_select())
+            .distinct()
+            .load(conn)?)
+    }
+}
@@ -0,0 +1,121 @@
+//! API keys that let clients authenticate without logging in.
+//!
+//! Keys are looked up by their public prefix, only the hash of their secret
+//! part is stored.
+
+use crate::prelude::*;
+use crate::{db::connection::DbConnection, schema::ejapikey::dsl::*};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// An API key owned by a client.
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, PartialEq)]
+#[diesel(table_name = crate::schema::ejapikey)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EjApiKey {
+    /// Unique API key ID.
+    pub id: Uuid,
+    /// The client that owns this key.
+    pub ejclient_id: Uuid,
+    /// Name given to the key by its owner.
+    pub name: String,
+    /// Public prefix identifying the key.
+    pub prefix: String,
+    /// SHA-256 hash of the key's secret.
+    pub hash: String,
+    /// Scopes granted to the key.
+    pub scopes: Vec<String>,
+    /// When this key stops being accepted, if ever.
+    pub expires_at: Option<DateTime<Utc>>,
+    /// When this key was revoked, if it was.
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// When this key was created.
+    pub created_at: DateTime<Utc>,
+    /// When this key was last updated.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Data for creating a new API key.
+#[derive(Insertable, PartialEq, Debug, Clone)]
+#[diesel(table_name = crate::schema::ejapikey)]
+pub struct EjApiKeyCreate {
+    /// The client that owns this key.
+    pub ejclient_id: Uuid,
+    /// Name given to the key by its owner.
+    pub name: String,
+    /// Public prefix identifying the key.
+    pub prefix: String,
+    /// SHA-256 hash of the key's secret.
+    pub hash: String,
+    /// Scopes granted to the key.
+    pub scopes: Vec<String>,
+    /// When this key stops being accepted, if ever.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl EjApiKeyCreate {
+    /// Saves the API key to the database.
+    pub fn save(self, connection: &DbConnection) -> Result<EjApiKey> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::insert_into(ejapikey)
+            .values(&self)
+            .returning(EjApiKey::as_returning())
+            .get_result(conn)?)
+    }
+}
+
+impl EjApiKey {
+    /// Fetches an API key by its ID.
+    pub fn fetch_by_id(target: &Uuid, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(EjApiKey::by_id(target)
+            .select(EjApiKey::as_select())
+            .get_result(conn)?)
+    }
+
+    /// Fetches an API key by its public prefix.
+    pub fn fetch_by_prefix(target: &str, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(ejapikey
+            .filter(prefix.eq(target))
+            .select(EjApiKey::as_select())
+            .get_result(conn)?)
+    }
+
+    /// Fetches every API key owned by a client.
+    pub fn fetch_by_client_id(client_id: &Uuid, connection: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+        Ok(ejapikey
+            .filter(ejclient_id.eq(client_id))
+            .order(created_at.asc())
+            .select(EjApiKey::as_select())
+            .load(conn)?)
+    }
+
+    /// Marks this key as revoked.
+    pub fn revoke(&self, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::update(EjApiKey::by_id(&self.id))
+            .set(revoked_at.eq(Utc::now()))
+            .returning(EjApiKey::as_returning())
+            .get_result(conn)?)
+    }
+
+    /// Whether this key has been revoked.
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
+    /// Whether this key has expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expiration| expiration <= Utc::now())
+    }
+
+    /// Returns a query filtered by API key ID.
+    #[diesel::dsl::auto_type(no_type_alias)]
+    pub fn by_id(target: &Uuid) -> _ {
+        crate::schema::ejapikey::dsl::ejapikey.filter(id.eq(target))
+    }
+}
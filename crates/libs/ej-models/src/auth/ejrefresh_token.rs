@@ -0,0 +1,194 @@
+//! Refresh tokens issued to clients and builders.
+//!
+//! Only the hash of each token is stored. A token belongs to either a client
+//! or a builder, never both.
+
+use crate::prelude::*;
+use crate::{db::connection::DbConnection, schema::ejrefreshtoken::dsl::*};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// A refresh token issued to a client or a builder.
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, PartialEq)]
+#[diesel(table_name = crate::schema::ejrefreshtoken)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EjRefreshToken {
+    /// Unique refresh token ID.
+    pub id: Uuid,
+    /// SHA-256 hash of the token.
+    pub token_hash: String,
+    /// The client this token was issued to, if any.
+    pub ejclient_id: Option<Uuid>,
+    /// The builder this token was issued to, if any.
+    pub ejbuilder_id: Option<Uuid>,
+    /// When this token stops being accepted.
+    pub expires_at: DateTime<Utc>,
+    /// When this token was revoked, if it was.
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// When this token was created.
+    pub created_at: DateTime<Utc>,
+    /// When this token was last updated.
+    pub updated_at: DateTime<Utc>,
+    /// The client session this token belongs to, if any.
+    pub ejsession_id: Option<Uuid>,
+    /// When this token was exchanged for a new one, if it was.
+    pub rotated_at: Option<DateTime<Utc>>,
+}
+
+/// Data for creating a new refresh token.
+#[derive(Insertable, PartialEq, Debug, Clone)]
+#[diesel(table_name = crate::schema::ejrefreshtoken)]
+pub struct EjRefreshTokenCreate {
+    /// SHA-256 hash of the token.
+    pub token_hash: String,
+    /// The client this token is issued to, if any.
+    pub ejclient_id: Option<Uuid>,
+    /// The builder this token is issued to, if any.
+    pub ejbuilder_id: Option<Uuid>,
+    /// When this token stops being accepted.
+    pub expires_at: DateTime<Utc>,
+    /// The client session this token belongs to, if any.
+    pub ejsession_id: Option<Uuid>,
+}
+
+impl EjRefreshTokenCreate {
+    /// Creates a refresh token issued to a client for one of its sessions.
+    pub fn for_client(
+        hash: String,
+        client_id: Uuid,
+        session_id: Uuid,
+        expiration: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            token_hash: hash,
+            ejclient_id: Some(client_id),
+            ejbuilder_id: None,
+            expires_at: expiration,
+            ejsession_id: Some(session_id),
+        }
+    }
+
+    /// Creates a refresh token issued to a builder.
+    pub fn for_builder(hash: String, builder_id: Uuid, expiration: DateTime<Utc>) -> Self {
+        Self {
+            token_hash: hash,
+            ejclient_id: None,
+            ejbuilder_id: Some(builder_id),
+            expires_at: expiration,
+            ejsession_id: None,
+        }
+    }
+
+    /// Saves the refresh token to the database.
+    pub fn save(self, connection: &DbConnection) -> Result<EjRefreshToken> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::insert_into(ejrefreshtoken)
+            .values(&self)
+            .returning(EjRefreshToken::as_returning())
+            .get_result(conn)?)
+    }
+}
+
+impl EjRefreshToken {
+    /// Fetches a refresh token by its hash.
+    pub fn fetch_by_hash(target: &str, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(EjRefreshToken::by_hash(target)
+            .select(EjRefreshToken::as_select())
+            .get_result(conn)?)
+    }
+
+    /// Revokes the refresh token with the given hash, if it's still active.
+    ///
+    /// The check and the update happen atomically so a token can only be
+    /// consumed once, even by concurrent requests.
+    ///
+    /// # Returns
+    ///
+    /// The revoked token, or `None` if no active token has this hash.
+    pub fn consume(target: &str, connection: &DbConnection) -> Result<Option<Self>> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::update(
+            EjRefreshToken::by_hash(target)
+                .filter(revoked_at.is_null())
+                .filter(expires_at.gt(Utc::now())),
+        )
+        .set(revoked_at.eq(Utc::now()))
+        .returning(EjRefreshToken::as_returning())
+        .get_result(conn)
+        .optional()?)
+    }
+
+    /// Revokes the refresh token with the given hash as it's exchanged for a
+    /// new one, if it's still active.
+    ///
+    /// Same as [`EjRefreshToken::consume`], but the token is marked as
+    /// rotated so using it again can be told apart from using a token
+    /// revoked on purpose.
+    pub fn rotate(target: &str, connection: &DbConnection) -> Result<Option<Self>> {
+        let conn = &mut connection.pool.get()?;
+        let now = Utc::now();
+        Ok(diesel::update(
+            EjRefreshToken::by_hash(target)
+                .filter(revoked_at.is_null())
+                .filter(expires_at.gt(now)),
+        )
+        .set((revoked_at.eq(now), rotated_at.eq(now)))
+        .returning(EjRefreshToken::as_returning())
+        .get_result(conn)
+        .optional()?)
+    }
+
+    /// Revokes every active refresh token issued to a client.
+    pub fn revoke_all_for_client(client_id: &Uuid, connection: &DbConnection) -> Result<usize> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::update(
+            ejrefreshtoken
+                .filter(ejclient_id.eq(client_id))
+                .filter(revoked_at.is_null()),
+        )
+        .set(revoked_at.eq(Utc::now()))
+        .execute(conn)?)
+    }
+
+    /// Revokes every active refresh token of a client session.
+    pub fn revoke_all_for_session(session_id: &Uuid, connection: &DbConnection) -> Result<usize> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::update(
+            ejrefreshtoken
+                .filter(ejsession_id.eq(session_id))
+                .filter(revoked_at.is_null()),
+        )
+        .set(revoked_at.eq(Utc::now()))
+        .execute(conn)?)
+    }
+
+    /// Revokes every active refresh token issued to a builder.
+    pub fn revoke_all_for_builder(builder_id: &Uuid, connection: &DbConnection) -> Result<usize> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::update(
+            ejrefreshtoken
+                .filter(ejbuilder_id.eq(builder_id))
+                .filter(revoked_at.is_null()),
+        )
+        .set(revoked_at.eq(Utc::now()))
+        .execute(conn)?)
+    }
+
+    /// Whether this token has been revoked.
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
+    /// Whether this token has been exchanged for a new one.
+    pub fn is_rotated(&self) -> bool {
+        self.rotated_at.is_some()
+    }
+
+    /// Returns a query filtered by token hash.
+    #[diesel::dsl::auto_type(no_type_alias)]
+    pub fn by_hash(target: &str) -> _ {
+        crate::schema::ejrefreshtoken::dsl::ejrefreshtoken.filter(token_hash.eq(target))
+    }
+}
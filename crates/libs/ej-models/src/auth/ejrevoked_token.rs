@@ -0,0 +1,66 @@
+//! Access tokens revoked before they expired.
+//!
+//! Tokens are identified by their `jti` claim. Entries are only useful until
+//! the token expires, after which it's rejected anyway and can be purged.
+
+use crate::prelude::*;
+use crate::{db::connection::DbConnection, schema::ejrevokedtoken::dsl::*};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// An access token that is no longer accepted.
+#[derive(Debug, Clone, Queryable, Selectable, PartialEq)]
+#[diesel(table_name = crate::schema::ejrevokedtoken)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EjRevokedToken {
+    /// The `jti` claim of the revoked token.
+    pub jti: Uuid,
+    /// When the revoked token expires.
+    pub expires_at: DateTime<Utc>,
+    /// When this token was revoked.
+    pub created_at: DateTime<Utc>,
+    /// When this entry was last updated.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Data for revoking a token.
+#[derive(Insertable, PartialEq, Debug, Clone)]
+#[diesel(table_name = crate::schema::ejrevokedtoken)]
+pub struct EjRevokedTokenCreate {
+    /// The `jti` claim of the token to revoke.
+    pub jti: Uuid,
+    /// When the token expires.
+    pub expires_at: DateTime<Utc>,
+}
+
+impl EjRevokedTokenCreate {
+    /// Saves the revoked token to the database.
+    ///
+    /// Revoking an already revoked token is a no-op.
+    pub fn save(self, connection: &DbConnection) -> Result<()> {
+        let conn = &mut connection.pool.get()?;
+        diesel::insert_into(ejrevokedtoken)
+            .values(&self)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+impl EjRevokedToken {
+    /// Fetches every revoked token that hasn't expired yet.
+    pub fn fetch_active(connection: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+        Ok(ejrevokedtoken
+            .filter(expires_at.gt(Utc::now()))
+            .select(EjRevokedToken::as_select())
+            .load(conn)?)
+    }
+
+    /// Deletes the revoked tokens that have expired.
+    pub fn delete_expired(connection: &DbConnection) -> Result<usize> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::delete(ejrevokedtoken.filter(expires_at.le(Utc::now()))).execute(conn)?)
+    }
+}
@@ -0,0 +1,122 @@
+//! Client sessions.
+//!
+//! A session starts when a client logs in and lasts as long as its refresh
+//! tokens keep being exchanged. Every access token issued during a session
+//! carries its ID so the whole session can be revoked at once.
+
+use crate::prelude::*;
+use crate::{db::connection::DbConnection, schema::ejsession::dsl::*};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// A client session.
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, PartialEq)]
+#[diesel(table_name = crate::schema::ejsession)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EjSession {
+    /// Unique session ID.
+    pub id: Uuid,
+    /// The client this session belongs to.
+    pub ejclient_id: Uuid,
+    /// When the session ends unless its tokens are refreshed.
+    pub expires_at: DateTime<Utc>,
+    /// When the session was revoked, if it was.
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// When the session started.
+    pub created_at: DateTime<Utc>,
+    /// When the session was last refreshed.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Data for starting a new session.
+#[derive(Insertable, PartialEq, Debug, Clone)]
+#[diesel(table_name = crate::schema::ejsession)]
+pub struct EjSessionCreate {
+    /// The client the session belongs to.
+    pub ejclient_id: Uuid,
+    /// When the session ends unless its tokens are refreshed.
+    pub expires_at: DateTime<Utc>,
+}
+
+impl EjSessionCreate {
+    /// Saves the session to the database.
+    pub fn save(self, connection: &DbConnection) -> Result<EjSession> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::insert_into(ejsession)
+            .values(&self)
+            .returning(EjSession::as_returning())
+            .get_result(conn)?)
+    }
+}
+
+impl EjSession {
+    /// Fetches a session by its ID.
+    pub fn fetch_by_id(target: &Uuid, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(ejsession
+            .find(target)
+            .select(EjSession::as_select())
+            .get_result(conn)?)
+    }
+
+    /// Fetches the sessions of a client that weren't revoked and haven't expired, newest first.
+    pub fn fetch_active_by_client(
+        client_id: &Uuid,
+        connection: &DbConnection,
+    ) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+        Ok(ejsession
+            .filter(ejclient_id.eq(client_id))
+            .filter(revoked_at.is_null())
+            .filter(expires_at.gt(Utc::now()))
+            .order(created_at.desc())
+            .select(EjSession::as_select())
+            .load(conn)?)
+    }
+
+    /// Extends the session until `expiration`.
+    pub fn extend(&self, expiration: DateTime<Utc>, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::update(self)
+            .set(expires_at.eq(expiration))
+            .returning(EjSession::as_returning())
+            .get_result(conn)?)
+    }
+
+    /// Revokes the session.
+    pub fn revoke(&self, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::update(self)
+            .set(revoked_at.eq(Utc::now()))
+            .returning(EjSession::as_returning())
+            .get_result(conn)?)
+    }
+
+    /// Revokes every active session of a client.
+    ///
+    /// # Returns
+    ///
+    /// The revoked sessions.
+    pub fn revoke_all_for_client(client_id: &Uuid, connection: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::update(
+            ejsession
+                .filter(ejclient_id.eq(client_id))
+                .filter(revoked_at.is_null()),
+        )
+        .set(revoked_at.eq(Utc::now()))
+        .returning(EjSession::as_returning())
+        .get_results(conn)?)
+    }
+
+    /// Whether this session has been revoked.
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
+    /// Whether this session has ended.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
@@ -1,7 +1,13 @@
 //! Authentication and authorization models.
 //!
-//! This module contains data models for managing client permissions
-//! and authorization in the ej system.
+//! This module contains data models for managing client permissions, roles,
+//! API keys, refresh tokens, revoked tokens, sessions and authorization in the ej system.
 
 pub mod client_permission;
+pub mod client_role;
+pub mod ejapi_key;
+pub mod ejrefresh_token;
+pub mod ejrevoked_token;
+pub mod ejsession;
 pub mod permission;
+pub mod role;
@@ -0,0 +1,56 @@
+//! Role model grouping permissions.
+
+use crate::{auth::permission::Permission, db::connection::DbConnection, prelude::*};
+use diesel::prelude::*;
+
+/// Role granting every permission, assigned to the root user.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// A named group of permissions that can be assigned to clients.
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Hash, PartialEq, Eq)]
+#[diesel(table_name = crate::schema::role)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Role {
+    /// The unique role identifier.
+    pub id: String,
+}
+
+impl From<&str> for Role {
+    fn from(value: &str) -> Self {
+        Self {
+            id: String::from(value),
+        }
+    }
+}
+
+impl Role {
+    /// Fetches all roles from the database.
+    pub fn fetch_all(conn: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut conn.pool.get()?;
+        Ok(crate::schema::role::table
+            .order(crate::schema::role::id.asc())
+            .load(conn)?)
+    }
+
+    /// Fetches a role by its ID.
+    pub fn fetch_by_id(conn: &DbConnection, target_id: &str) -> Result<Self> {
+        use crate::schema::role::dsl::*;
+        let conn = &mut conn.pool.get()?;
+        Ok(role
+            .filter(id.eq(target_id))
+            .select(Role::as_select())
+            .get_result(conn)?)
+    }
+
+    /// Fetches the permissions granted by this role.
+    pub fn fetch_permissions(&self, conn: &DbConnection) -> Result<Vec<Permission>> {
+        use crate::schema::role_permission::dsl::*;
+        let conn = &mut conn.pool.get()?;
+        Ok(role_permission
+            .filter(role_id.eq(&self.id))
+            .inner_join(crate::schema::permission::table)
+            .select(Permission::as_select())
+            .order(permission_id.asc())
+            .load(conn)?)
+    }
+}
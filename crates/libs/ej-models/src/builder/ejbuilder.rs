@@ -1,5 +1,7 @@
 //! Builder service model for managing build instances.
 
+use crate::config::ejconfig::EjConfigDb;
+use crate::db::pagination::{DbPage, Paginate, SortBy};
 use crate::prelude::*;
 use crate::{db::connection::DbConnection, schema::ejbuilder::dsl::*};
 use chrono::{DateTime, Utc};
@@ -15,12 +17,16 @@ use uuid::Uuid;
 pub struct EjBuilder {
     /// Unique builder ID.
     pub id: Uuid,
-    /// The client that owns this builder.
-    pub ejclient_id: Uuid,
+    /// The client that owns this builder, `None` once the client was deleted.
+    pub ejclient_id: Option<Uuid>,
     /// When this builder was created.
     pub created_at: DateTime<Utc>,
     /// When this builder was last updated.
     pub updated_at: DateTime<Utc>,
+    /// When this builder was revoked, if it was.
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// When this builder was last heard from, if it ever connected.
+    pub last_seen_at: Option<DateTime<Utc>>,
 }
 
 /// Data for creating a new builder.
@@ -62,6 +68,82 @@ impl EjBuilder {
         Ok(client.into())
     }
 
+    /// Fetches every registered builder.
+    pub fn fetch_all(connection: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+
+        Ok(ejbuilder
+            .order(created_at.asc())
+            .select(EjBuilder::as_select())
+            .load(conn)?)
+    }
+
+    /// Fields builders can be sorted by.
+    pub const SORT_FIELDS: [&str; 2] = ["created_at", "updated_at"];
+
+    /// Fetches a page of builders, oldest first unless `page` says otherwise.
+    pub fn fetch_page(page: &DbPage, connection: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+        let query = ejbuilder.into_boxed();
+        let query = match page.sort.as_deref() {
+            Some("updated_at") => query.sort_by(updated_at, page.descending),
+            _ => query.sort_by(created_at, page.descending),
+        };
+        Ok(query
+            .then_order_by(id.asc())
+            .paginate(page)
+            .select(EjBuilder::as_select())
+            .load(conn)?)
+    }
+
+    /// Counts every registered builder.
+    pub fn count(connection: &DbConnection) -> Result<i64> {
+        let conn = &mut connection.pool.get()?;
+
+        Ok(ejbuilder.count().get_result(conn)?)
+    }
+
+    /// Fetches every builder owned by a client.
+    pub fn fetch_by_client_id(client_id: &Uuid, connection: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+
+        Ok(ejbuilder
+            .filter(ejclient_id.eq(client_id))
+            .select(EjBuilder::as_select())
+            .load(conn)?)
+    }
+
+    /// Fetches the config this builder uploaded last, if any.
+    pub fn fetch_latest_config(&self, connection: &DbConnection) -> Result<Option<EjConfigDb>> {
+        use crate::schema::ejconfig;
+        let conn = &mut connection.pool.get()?;
+
+        Ok(ejconfig::table
+            .filter(ejconfig::ejbuilder_id.eq(self.id))
+            .order(ejconfig::uploaded_at.desc())
+            .select(EjConfigDb::as_select())
+            .first(conn)
+            .optional()?)
+    }
+
+    /// Marks this builder as revoked.
+    ///
+    /// Revoked builders can no longer authenticate against the dispatcher,
+    /// even if their token hasn't expired yet.
+    pub fn revoke(&self, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+
+        Ok(diesel::update(EjBuilder::by_id(&self.id))
+            .set(revoked_at.eq(Utc::now()))
+            .returning(EjBuilder::as_returning())
+            .get_result(conn)?)
+    }
+
+    /// Whether this builder has been revoked.
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
     /// Returns a query filtered by builder ID.
     #[diesel::dsl::auto_type(no_type_alias)]
     pub fn by_id(target: &Uuid) -> _ {
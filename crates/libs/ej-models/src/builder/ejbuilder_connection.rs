@@ -0,0 +1,153 @@
+//! Builder connection history.
+//!
+//! Every WebSocket connection a builder opens with the dispatcher is recorded
+//! along with the last time the builder was heard from on it, so builders that
+//! went quiet can be told apart from the ones that are still online.
+
+use crate::prelude::*;
+use crate::schema::ejbuilder;
+use crate::{db::connection::DbConnection, schema::ejbuilderconnection::dsl::*};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A WebSocket connection opened by a builder.
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, PartialEq, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::ejbuilderconnection)]
+#[diesel(belongs_to(EjBuilder))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EjBuilderConnection {
+    /// Connection ID, the same one the dispatcher tracks the open connection with.
+    pub id: Uuid,
+    /// The builder that opened the connection.
+    pub ejbuilder_id: Uuid,
+    /// Address the builder connected from.
+    pub address: String,
+    /// When the connection was opened.
+    pub connected_at: DateTime<Utc>,
+    /// When the builder was last heard from on this connection.
+    pub last_seen_at: DateTime<Utc>,
+    /// When the connection was closed, `None` while it's open.
+    pub disconnected_at: Option<DateTime<Utc>>,
+}
+
+/// Data for recording a new builder connection.
+#[derive(Insertable, PartialEq, Debug, Clone, Deserialize)]
+#[diesel(table_name = crate::schema::ejbuilderconnection)]
+pub struct EjBuilderConnectionCreate {
+    /// Connection ID.
+    pub id: Uuid,
+    /// The builder that opened the connection.
+    pub ejbuilder_id: Uuid,
+    /// Address the builder connected from.
+    pub address: String,
+}
+
+impl EjBuilderConnectionCreate {
+    /// Records the connection and marks its builder as seen.
+    pub fn create(self, connection: &DbConnection) -> Result<EjBuilderConnection> {
+        let conn = &mut connection.pool.get()?;
+        Ok(conn.transaction(|conn| {
+            let created = diesel::insert_into(ejbuilderconnection)
+                .values(self)
+                .returning(EjBuilderConnection::as_returning())
+                .get_result(conn)?;
+            EjBuilderConnection::mark_builder_seen(&created, conn)?;
+            Ok::<_, diesel::result::Error>(created)
+        })?)
+    }
+}
+
+impl EjBuilderConnection {
+    /// Records that the builder was heard from on a connection.
+    ///
+    /// The builder's own `last_seen_at` is updated along with the connection's,
+    /// which bumps its `updated_at` as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - ID of the connection
+    /// * `seen_at` - When the builder was last heard from
+    /// * `connection` - Database connection
+    pub fn touch(target: &Uuid, seen_at: DateTime<Utc>, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(conn.transaction(|conn| {
+            let updated = diesel::update(EjBuilderConnection::by_id(target))
+                .set(last_seen_at.eq(seen_at))
+                .returning(EjBuilderConnection::as_returning())
+                .get_result(conn)?;
+            EjBuilderConnection::mark_builder_seen(&updated, conn)?;
+            Ok::<_, diesel::result::Error>(updated)
+        })?)
+    }
+
+    /// Marks a connection as closed.
+    pub fn close(target: &Uuid, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::update(EjBuilderConnection::by_id(target))
+            .set(disconnected_at.eq(Utc::now()))
+            .returning(EjBuilderConnection::as_returning())
+            .get_result(conn)?)
+    }
+
+    /// Closes the connections left open by a previous dispatcher run.
+    ///
+    /// They're considered closed the last time their builder was heard from on them.
+    ///
+    /// # Returns
+    ///
+    /// The number of connections closed.
+    pub fn close_dangling(connection: &DbConnection) -> Result<usize> {
+        let conn = &mut connection.pool.get()?;
+        Ok(
+            diesel::update(ejbuilderconnection.filter(disconnected_at.is_null()))
+                .set(disconnected_at.eq(last_seen_at.nullable()))
+                .execute(conn)?,
+        )
+    }
+
+    /// Fetches the latest connections of a builder, most recent first.
+    pub fn fetch_recent_by_builder_id(
+        builder_id: &Uuid,
+        limit: i64,
+        connection: &DbConnection,
+    ) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+        Ok(ejbuilderconnection
+            .filter(ejbuilder_id.eq(builder_id))
+            .order(connected_at.desc())
+            .limit(limit)
+            .select(EjBuilderConnection::as_select())
+            .load(conn)?)
+    }
+
+    /// Whether the connection is still open.
+    pub fn is_open(&self) -> bool {
+        self.disconnected_at.is_none()
+    }
+
+    /// Moves the `last_seen_at` of the connection's builder forward.
+    fn mark_builder_seen(
+        builder_connection: &Self,
+        conn: &mut PgConnection,
+    ) -> std::result::Result<usize, diesel::result::Error> {
+        diesel::update(
+            ejbuilder::table
+                .filter(ejbuilder::id.eq(builder_connection.ejbuilder_id))
+                .filter(
+                    ejbuilder::last_seen_at
+                        .is_null()
+                        .or(ejbuilder::last_seen_at.lt(builder_connection.last_seen_at)),
+                ),
+        )
+        .set(ejbuilder::last_seen_at.eq(builder_connection.last_seen_at))
+        .execute(conn)
+    }
+
+    /// Returns a query filtered by connection ID.
+    #[diesel::dsl::auto_type(no_type_alias)]
+    pub fn by_id(target: &Uuid) -> _ {
+        crate::schema::ejbuilderconnection::dsl::ejbuilderconnection.filter(id.eq(target))
+    }
+}
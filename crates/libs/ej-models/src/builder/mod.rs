@@ -4,3 +4,4 @@
 //! that process and execute jobs in the ej system.
 
 pub mod ejbuilder;
+pub mod ejbuilder_connection;
@@ -1,5 +1,8 @@
 use crate::auth::client_permission::ClientPermission;
+use crate::auth::client_role::ClientRole;
 use crate::auth::permission::Permission;
+use crate::auth::role::Role;
+use crate::db::pagination::{DbPage, Paginate, SortBy};
 use crate::prelude::*;
 use crate::{db::connection::DbConnection, schema::ejclient::dsl::*};
 use chrono::{DateTime, Utc};
@@ -52,6 +55,14 @@ impl EjClient {
 
         Ok(client.into())
     }
+    /// Fetches a client by its ID, locking its row until the transaction of `conn` ends.
+    pub fn fetch_for_update(target: &Uuid, conn: &mut PgConnection) -> Result<Self> {
+        Ok(EjClient::by_id(target)
+            .select(EjClient::as_select())
+            .for_update()
+            .get_result(conn)?)
+    }
+
     pub fn fetch_by_name(target: &str, connection: &DbConnection) -> Result<Self> {
         let conn = &mut connection.pool.get()?;
 
@@ -64,11 +75,81 @@ impl EjClient {
     pub fn fetch_permissions(&self, connection: &DbConnection) -> Result<Vec<Permission>> {
         Ok(ClientPermission::fetch_by_client(connection, self)?.1)
     }
+
+    /// Fetches the roles assigned to the client.
+    pub fn fetch_roles(&self, connection: &DbConnection) -> Result<Vec<Role>> {
+        ClientRole::fetch_roles(connection, self)
+    }
+
+    /// Fetches the permissions granted to the client through its roles.
+    pub fn fetch_role_permissions(&self, connection: &DbConnection) -> Result<Vec<Permission>> {
+        ClientRole::fetch_role_permissions(connection, self)
+    }
     pub fn fetch_all(connection: &DbConnection) -> Result<Vec<Self>> {
         let conn = &mut connection.pool.get()?;
 
         Ok(EjClient::table().select(EjClient::as_select()).load(conn)?)
     }
+
+    /// Fields clients can be sorted by.
+    pub const SORT_FIELDS: [&str; 2] = ["name", "created_at"];
+
+    /// Fetches a page of clients, sorted by name unless `page` says otherwise.
+    pub fn fetch_page(page: &DbPage, connection: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+        let query = ejclient.into_boxed();
+        let query = match page.sort.as_deref() {
+            Some("created_at") => query.sort_by(created_at, page.descending),
+            _ => query.sort_by(name, page.descending),
+        };
+        Ok(query
+            .then_order_by(id.asc())
+            .paginate(page)
+            .select(EjClient::as_select())
+            .load(conn)?)
+    }
+
+    /// Counts every registered client.
+    pub fn count(connection: &DbConnection) -> Result<i64> {
+        let conn = &mut connection.pool.get()?;
+
+        Ok(ejclient.count().get_result(conn)?)
+    }
+
+    /// Updates the client name.
+    pub fn update_name(&self, new_name: &str, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+
+        Ok(diesel::update(EjClient::by_id(&self.id))
+            .set(name.eq(new_name))
+            .returning(EjClient::as_returning())
+            .get_result(conn)?)
+    }
+
+    /// Updates the client secret hash.
+    pub fn update_hash(
+        &self,
+        new_hash: &str,
+        new_hash_version: i32,
+        connection: &DbConnection,
+    ) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+
+        Ok(diesel::update(EjClient::by_id(&self.id))
+            .set((hash.eq(new_hash), hash_version.eq(new_hash_version)))
+            .returning(EjClient::as_returning())
+            .get_result(conn)?)
+    }
+
+    /// Deletes the client.
+    ///
+    /// Permissions granted to the client are removed along with it.
+    pub fn delete(&self, connection: &DbConnection) -> Result<()> {
+        let conn = &mut connection.pool.get()?;
+
+        diesel::delete(EjClient::by_id(&self.id)).execute(conn)?;
+        Ok(())
+    }
 }
 
 impl EjClient {
@@ -25,6 +25,12 @@ pub struct EjConfigDb {
     pub created_at: DateTime<Utc>,
     /// When this config was last updated.
     pub updated_at: DateTime<Utc>,
+    /// The config as uploaded, serialized as JSON.
+    ///
+    /// `None` for configs uploaded before their content was stored.
+    pub content: Option<String>,
+    /// When the builder last uploaded this config.
+    pub uploaded_at: DateTime<Utc>,
 }
 
 /// Data for creating a new config entry.
@@ -37,6 +43,8 @@ pub struct NewEjConfigDb {
     pub version: String,
     /// Configuration hash.
     pub hash: String,
+    /// The config as uploaded, serialized as JSON.
+    pub content: String,
 }
 
 impl EjConfigDb {
@@ -51,14 +59,37 @@ impl EjConfigDb {
             .select(EjConfigDb::as_select())
             .first(conn)?)
     }
+
+    /// Fetches a config by its ID.
+    pub fn fetch_by_id(target: &Uuid, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(EjConfigDb::by_id(target)
+            .select(EjConfigDb::as_select())
+            .get_result(conn)?)
+    }
+
+    /// Records that the builder uploaded this config again, making it its latest one.
+    pub fn mark_uploaded(&self, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::update(EjConfigDb::by_id(&self.id))
+            .set(uploaded_at.eq(Utc::now()))
+            .returning(EjConfigDb::as_returning())
+            .get_result(conn)?)
+    }
 }
 
 impl NewEjConfigDb {
-    pub fn new(builder_id: Uuid, config_version: String, config_hash: String) -> Self {
+    pub fn new(
+        builder_id: Uuid,
+        config_version: String,
+        config_hash: String,
+        config_content: String,
+    ) -> Self {
         Self {
             ejbuilder_id: builder_id,
             version: config_version,
             hash: config_hash,
+            content: config_content,
         }
     }
     pub fn save(self, connection: &mut DbConnection) -> Result<EjConfigDb> {
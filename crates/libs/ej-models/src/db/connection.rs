@@ -1,6 +1,9 @@
 //! Database connection management and migrations.
 
 use diesel::PgConnection;
+use diesel::connection::Connection;
+use diesel::migration::MigrationSource;
+use diesel::pg::Pg;
 use diesel::r2d2::ConnectionManager;
 use diesel::r2d2::Pool;
 use diesel_migrations::embed_migrations;
@@ -10,16 +13,36 @@ use tracing::info;
 const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
 
 use super::config::DbConfig;
+use crate::prelude::*;
 /// Database connection pool wrapper.
 #[derive(Debug, Clone)]
 pub struct DbConnection {
     /// PostgreSQL connection pool.
     pub pool: Pool<ConnectionManager<PgConnection>>,
+    /// Pool for read-only queries that tolerate replication lag.
+    ///
+    /// Connected to the read replica if one is configured, the same pool as
+    /// `pool` otherwise. Anything read back right after being written must
+    /// go through `pool` instead.
+    pub read_pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+/// Builds a connection pool with the settings of `config`.
+fn build_pool(url: &str, config: &DbConfig) -> Pool<ConnectionManager<PgConnection>> {
+    Pool::builder()
+        .max_size(config.pool_size)
+        .min_idle(config.min_idle)
+        .connection_timeout(config.connection_timeout)
+        .idle_timeout(config.idle_timeout)
+        .build(ConnectionManager::<PgConnection>::new(url))
+        .expect("Couldn't establish connection with database")
 }
 
 impl DbConnection {
     /// Create a new database connection pool.
     ///
+    /// A second pool is created for the read replica if `config` has one.
+    ///
     /// # Examples
     ///
     /// ```rust,no_run
@@ -29,17 +52,33 @@ impl DbConnection {
     /// let db = DbConnection::new(&config);
     /// ```
     pub fn new(config: &DbConfig) -> Self {
-        let manager = ConnectionManager::<PgConnection>::new(&config.database_url);
-        let pool = Pool::builder()
-            .build(manager)
-            .expect("Couldn't establish connection with database");
-        Self { pool }
+        let pool = build_pool(&config.database_url, config);
+        let read_pool = match &config.read_database_url {
+            Some(url) => build_pool(url, config),
+            None => pool.clone(),
+        };
+        Self { pool, read_pool }
     }
+    /// Connection sending every query to the read pool.
+    ///
+    /// For read-only work that tolerates replication lag and goes through model
+    /// functions that use `pool`, like answering a client's request for results.
+    pub fn reader(&self) -> Self {
+        Self {
+            pool: self.read_pool.clone(),
+            read_pool: self.read_pool.clone(),
+        }
+    }
+
     /// Run database migrations and return configured connection.
     ///
     /// This method runs all pending database migrations before returning
     /// the connection, ensuring the database schema is up to date.
     ///
+    /// # Panics
+    ///
+    /// If the migrations fail, see [`DbConnection::migrate`].
+    ///
     /// # Examples
     ///
     /// ```rust,no_run
@@ -49,12 +88,258 @@ impl DbConnection {
     /// let db = DbConnection::new(&config).setup();
     /// ```
     pub fn setup(self) -> Self {
-        info!("Running Database Migrations");
-        self.pool
-            .get()
-            .expect("Couldn't get a connection from the pool to run migrations")
-            .run_pending_migrations(MIGRATIONS)
-            .expect("Failed to run database migrations");
+        self.migrate().expect("Failed to run database migrations");
         self
     }
+
+    /// Applies the migrations bundled with this build that the database is missing.
+    ///
+    /// Fails with [`Error::SchemaTooNew`] without touching the database if it
+    /// was migrated by a newer build, since this one doesn't know its schema.
+    ///
+    /// # Returns
+    ///
+    /// The versions of the migrations applied, oldest first.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use ej_models::db::{connection::DbConnection, config::DbConfig};
+    ///
+    /// let db = DbConnection::new(&DbConfig::from_env());
+    /// let applied = db.migrate().unwrap();
+    /// println!("Applied {} migrations, schema at {:?}", applied.len(), db.schema_version().unwrap());
+    /// ```
+    pub fn migrate(&self) -> Result<Vec<String>> {
+        let conn = &mut self.pool.get()?;
+        let known: Vec<String> = MigrationSource::<Pg>::migrations(&MIGRATIONS)
+            .map_err(|err| Error::Migration(err.to_string()))?
+            .iter()
+            .map(|migration| migration.name().version().to_string())
+            .collect();
+        let applied = conn
+            .applied_migrations()
+            .map_err(|err| Error::Migration(err.to_string()))?;
+        if let Some(unknown) = applied
+            .iter()
+            .map(|version| version.to_string())
+            .find(|version| !known.contains(version))
+        {
+            return Err(Error::SchemaTooNew(unknown));
+        }
+
+        info!(
+            "Running Database Migrations, schema at version {}",
+            applied
+                .first()
+                .map(|version| version.to_string())
+                .unwrap_or_else(|| String::from("none"))
+        );
+        let versions: Vec<String> = conn
+            .run_pending_migrations(MIGRATIONS)
+            .map_err(|err| Error::Migration(err.to_string()))?
+            .iter()
+            .map(|version| version.to_string())
+            .collect();
+        for version in &versions {
+            info!("Applied migration {version}");
+        }
+        Ok(versions)
+    }
+
+    /// Runs `f` inside a database transaction.
+    ///
+    /// The transaction is committed if `f` returns `Ok` and rolled back
+    /// otherwise, so the statements it runs on the connection it's given are
+    /// either all applied or none are. Model functions ending in `_in` take
+    /// that connection.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use ej_models::db::{connection::DbConnection, config::DbConfig};
+    /// use ej_models::job::{ejjob::EjJobDb, ejjob_status::EjJobStatus};
+    /// # use uuid::Uuid;
+    ///
+    /// # fn example(job_id: Uuid) -> ej_models::prelude::Result<()> {
+    /// let db = DbConnection::new(&DbConfig::from_env());
+    /// db.transaction(|conn| {
+    ///     let job = EjJobDb::fetch_for_update(&job_id, conn)?;
+    ///     job.update_status_in(EjJobStatus::success(), conn)
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn transaction<T, E, F>(&self, f: F) -> std::result::Result<T, E>
+    where
+        F: FnOnce(&mut PgConnection) -> std::result::Result<T, E>,
+        E: From<Error>,
+    {
+        let conn = &mut self.pool.get().map_err(Error::from)?;
+        conn.transaction(|conn| f(conn).map_err(TransactionError::Inner))
+            .map_err(|err| match err {
+                TransactionError::Inner(err) => err,
+                TransactionError::Diesel(err) => Error::from(err).into(),
+            })
+    }
+
+    /// Version of the latest migration applied to the database, if any.
+    pub fn schema_version(&self) -> Result<Option<String>> {
+        let conn = &mut self.pool.get()?;
+        Ok(conn
+            .applied_migrations()
+            .map_err(|err| Error::Migration(err.to_string()))?
+            .first()
+            .map(|version| version.to_string()))
+    }
+}
+
+/// Error of a transaction, either returned by its body or raised by diesel
+/// while beginning, committing or rolling it back.
+enum TransactionError<E> {
+    Inner(E),
+    Diesel(diesel::result::Error),
+}
+
+impl<E> From<diesel::result::Error> for TransactionError<E> {
+    fn from(err: diesel::result::Error) -> Self {
+        TransactionError::Diesel(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use diesel::sql_types::Text;
+    use diesel::{QueryableByName, RunQueryDsl};
+
+    /// First migration granting permissions that didn't exist when the root
+    /// clients of older deployments were created.
+    const FIRST_UPGRADE_MIGRATION: &str = "20250701090000";
+
+    /// Empty database dropped once the test is done.
+    struct TestDb {
+        base_url: String,
+        name: String,
+    }
+
+    impl TestDb {
+        fn create() -> Self {
+            let base_url =
+                std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL env variable missing");
+            let name = format!("ej_test_{}", uuid::Uuid::new_v4().simple());
+            let conn = &mut PgConnection::establish(&format!("{base_url}/postgres"))
+                .expect("Failed to connect to base database");
+            diesel::sql_query(format!("CREATE DATABASE {name}"))
+                .execute(conn)
+                .expect("Failed to create test database");
+            Self { base_url, name }
+        }
+
+        fn connect(&self) -> PgConnection {
+            PgConnection::establish(&format!("{}/{}", self.base_url, self.name))
+                .expect("Failed to connect to test database")
+        }
+    }
+
+    impl Drop for TestDb {
+        fn drop(&mut self) {
+            if let Ok(conn) = &mut PgConnection::establish(&format!("{}/postgres", self.base_url)) {
+                diesel::sql_query(format!(
+                    "DROP DATABASE IF EXISTS {} WITH (FORCE)",
+                    self.name
+                ))
+                .execute(conn)
+                .ok();
+            }
+        }
+    }
+
+    #[derive(QueryableByName)]
+    struct Id {
+        #[diesel(sql_type = Text)]
+        id: String,
+    }
+
+    /// Applies the migrations older than `version`, oldest first.
+    fn migrate_before(conn: &mut PgConnection, version: &str) {
+        // Creates the table of the applied migrations
+        conn.applied_migrations().unwrap();
+        let mut migrations = MigrationSource::<Pg>::migrations(&MIGRATIONS).unwrap();
+        migrations.sort_by_key(|migration| migration.name().version().to_string());
+        for migration in migrations
+            .iter()
+            .filter(|migration| migration.name().version().to_string().as_str() < version)
+        {
+            conn.run_migration(migration.as_ref()).unwrap();
+        }
+    }
+
+    fn create_client(conn: &mut PgConnection, name: &str) -> String {
+        diesel::sql_query(format!(
+            "INSERT INTO ejclient (name, hash) VALUES ('{name}', 'hash') RETURNING id::text AS id"
+        ))
+        .get_result::<Id>(conn)
+        .unwrap()
+        .id
+    }
+
+    fn client_ids(conn: &mut PgConnection, query: &str, client_id: &str) -> Vec<String> {
+        let mut ids: Vec<String> =
+            diesel::sql_query(format!("{query} WHERE ejclient_id = '{client_id}'"))
+                .load::<Id>(conn)
+                .unwrap()
+                .into_iter()
+                .map(|row| row.id)
+                .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_upgrade_keeps_root_clients_admins() {
+        let db = TestDb::create();
+        let conn = &mut db.connect();
+        migrate_before(conn, FIRST_UPGRADE_MIGRATION);
+
+        // Root clients were created with every permission, others with some of them
+        let root = create_client(conn, "root");
+        diesel::sql_query(format!(
+            "INSERT INTO client_permission (ejclient_id, permission_id) SELECT '{root}', id FROM permission"
+        ))
+        .execute(conn)
+        .unwrap();
+        let user = create_client(conn, "user");
+        diesel::sql_query(format!(
+            "INSERT INTO client_permission (ejclient_id, permission_id) VALUES ('{user}', 'client.dispatch')"
+        ))
+        .execute(conn)
+        .unwrap();
+
+        conn.run_pending_migrations(MIGRATIONS).unwrap();
+
+        let permissions = "SELECT permission_id AS id FROM client_permission";
+        let all: Vec<String> = {
+            let mut all: Vec<String> = diesel::sql_query("SELECT id FROM permission")
+                .load::<Id>(conn)
+                .unwrap()
+                .into_iter()
+                .map(|row| row.id)
+                .collect();
+            all.sort();
+            all
+        };
+        assert_eq!(client_ids(conn, permissions, &root), all);
+        assert_eq!(
+            client_ids(conn, permissions, &user),
+            vec![
+                String::from("client.dispatch"),
+                String::from("client.results")
+            ]
+        );
+
+        let roles = "SELECT role_id AS id FROM client_role";
+        assert_eq!(client_ids(conn, roles, &root), vec![String::from("admin")]);
+        assert!(client_ids(conn, roles, &user).is_empty());
+    }
 }
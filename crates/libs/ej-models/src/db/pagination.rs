@@ -0,0 +1,61 @@
+//! Pagination and sorting of listings.
+//!
+//! Models that can be listed page by page take a [`DbPage`] and apply it to
+//! their boxed queries with [`Paginate`] and [`SortBy`], mapping the sort
+//! field names they support to their columns.
+
+use diesel::{
+    ExpressionMethods,
+    dsl::{Asc, Desc},
+    query_dsl::methods::{LimitDsl, OffsetDsl, OrderDsl},
+};
+
+/// A page of a listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbPage {
+    /// Number of rows skipped.
+    pub offset: i64,
+    /// Maximum number of rows returned.
+    pub limit: i64,
+    /// Field the rows are sorted by, the model's default order if unset.
+    pub sort: Option<String>,
+    /// Whether the rows are sorted largest first.
+    pub descending: bool,
+}
+
+/// Restricts a query to a page of its rows.
+pub trait Paginate: Sized {
+    /// Skips the rows before the page and drops the ones after it.
+    fn paginate(self, page: &DbPage) -> Self;
+}
+
+impl<Q> Paginate for Q
+where
+    Q: LimitDsl<Output = Q> + OffsetDsl<Output = Q>,
+{
+    fn paginate(self, page: &DbPage) -> Self {
+        self.limit(page.limit).offset(page.offset)
+    }
+}
+
+/// Sorts a query by a column in either direction.
+pub trait SortBy<C>: Sized {
+    /// Orders the rows by `column`, largest first if `descending` is set.
+    ///
+    /// Replaces any order set before.
+    fn sort_by(self, column: C, descending: bool) -> Self;
+}
+
+impl<Q, C> SortBy<C> for Q
+where
+    C: ExpressionMethods,
+    Q: OrderDsl<Asc<C>, Output = Q> + OrderDsl<Desc<C>, Output = Q>,
+{
+    fn sort_by(self, column: C, descending: bool) -> Self {
+        if descending {
+            self.order(column.desc())
+        } else {
+            self.order(column.asc())
+        }
+    }
+}
@@ -10,4 +10,19 @@ pub enum Error {
     /// Diesel ORM operation error.
     #[error(transparent)]
     Diesel(#[from] diesel::result::Error),
+
+    /// Applying or listing the database migrations failed.
+    #[error("Database migration failed: {0}")]
+    Migration(String),
+
+    /// The database was migrated by a newer build than this one.
+    #[error("Database schema version {0} is newer than this build supports")]
+    SchemaTooNew(String),
+}
+
+impl Error {
+    /// Whether the error was caused by a missing database row.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Error::Diesel(diesel::result::Error::NotFound))
+    }
 }
@@ -1,6 +1,7 @@
 //! Job model for managing job execution in the ej system.
 
 use crate::db::connection::DbConnection;
+use crate::db::pagination::{DbPage, Paginate, SortBy};
 use crate::job::ejjob_type::EjJobTypeDb;
 use crate::prelude::*;
 use crate::schema::ejjob::dsl::*;
@@ -8,6 +9,7 @@ use crate::schema::ejjob::status;
 use crate::{config::ejboard_config::EjBoardConfigDb, job::ejjob_status::EjJobStatus};
 use chrono::{DateTime, Utc};
 use diesel::associations::HasTable;
+use diesel::pg::Pg;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -35,6 +37,12 @@ pub struct EjJobDb {
     pub created_at: DateTime<Utc>,
     /// When this job was last updated.
     pub updated_at: DateTime<Utc>,
+    /// The client that dispatched the job, unset for jobs dispatched through the socket.
+    pub ejclient_id: Option<Uuid>,
+    /// Tags a board config must all have to run the job, empty for every board config.
+    pub required_tags: Vec<String>,
+    /// Names of the board configs the job runs on, empty for every board config.
+    pub board_configs: Vec<String>,
 }
 
 /// Data for creating a new job.
@@ -47,12 +55,42 @@ pub struct EjJobCreate {
     pub remote_url: String,
     /// The type of job to create.
     pub job_type: i32,
+    /// The client dispatching the job, if any.
+    pub ejclient_id: Option<Uuid>,
+    /// Tags a board config must all have to run the job.
+    pub required_tags: Vec<String>,
+    /// Names of the board configs the job runs on.
+    pub board_configs: Vec<String>,
+}
+
+/// Criteria used to select jobs.
+///
+/// Unset criteria match every job.
+#[derive(Debug, Clone, Default)]
+pub struct EjJobFilter {
+    /// Only jobs for this commit.
+    pub commit_hash: Option<String>,
+    /// Only jobs for this remote URL.
+    pub remote_url: Option<String>,
+    /// Only jobs with this status.
+    pub status: Option<i32>,
+    /// Only jobs of this type.
+    pub job_type: Option<i32>,
+    /// Only jobs created at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only jobs created before this time.
+    pub until: Option<DateTime<Utc>>,
 }
 
 impl EjJobCreate {
     /// Saves the job to the database.
     pub fn save(self, connection: &DbConnection) -> Result<EjJobDb> {
         let conn = &mut connection.pool.get()?;
+        self.save_in(conn)
+    }
+
+    /// Same as [`EjJobCreate::save`] on the connection of a transaction.
+    pub fn save_in(self, conn: &mut PgConnection) -> Result<EjJobDb> {
         Ok(diesel::insert_into(ejjob)
             .values(&self)
             .returning(EjJobDb::as_returning())
@@ -70,8 +108,18 @@ impl EjJobDb {
         Ok(job.into())
     }
 
+    /// Fetches a job and locks it until the end of the transaction `conn` is in.
+    ///
+    /// Concurrent writers of the job wait for the transaction to finish.
+    pub fn fetch_for_update(target: &Uuid, conn: &mut PgConnection) -> Result<Self> {
+        Ok(EjJobDb::by_id(target)
+            .select(EjJobDb::as_select())
+            .for_update()
+            .get_result(conn)?)
+    }
+
     pub fn fetch_by_commit_hash(target: &str, connection: &DbConnection) -> Result<Vec<Self>> {
-        let conn = &mut connection.pool.get()?;
+        let conn = &mut connection.read_pool.get()?;
         Ok(EjJobDb::by_commit_hash(target)
             .select(EjJobDb::as_select())
             .load(conn)?)
@@ -86,21 +134,117 @@ impl EjJobDb {
     }
 
     pub fn fetch_all(connection: &DbConnection) -> Result<Vec<Self>> {
-        let conn = &mut connection.pool.get()?;
+        let conn = &mut connection.read_pool.get()?;
         Ok(EjJobDb::table().select(EjJobDb::as_select()).load(conn)?)
     }
 
     pub fn update_status(&self, new_status: i32, connection: &DbConnection) -> Result<Self> {
         let conn = &mut connection.pool.get()?;
+        self.update_status_in(new_status, conn)
+    }
+
+    /// Same as [`EjJobDb::update_status`], run on `conn`.
+    pub fn update_status_in(&self, new_status: i32, conn: &mut PgConnection) -> Result<Self> {
         Ok(diesel::update(EjJobDb::by_id(&self.id))
             .set(status.eq(new_status))
             .returning(EjJobDb::as_returning())
             .get_result(conn)?
             .into())
     }
+
+    /// Fails the jobs left unfinished by a previous dispatcher run.
+    ///
+    /// Jobs only make progress while the dispatcher that queued them runs, so
+    /// the ones that didn't finish would otherwise count against their client's
+    /// queued jobs forever.
+    ///
+    /// # Returns
+    ///
+    /// The number of jobs failed.
+    pub fn fail_unfinished(connection: &DbConnection) -> Result<usize> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::update(
+            ejjob.filter(status.eq_any([EjJobStatus::not_started(), EjJobStatus::running()])),
+        )
+        .set(status.eq(EjJobStatus::failed()))
+        .execute(conn)?)
+    }
+
+    /// Counts the jobs of a client that haven't finished yet.
+    pub fn count_queued_by_client_in(client_id: &Uuid, conn: &mut PgConnection) -> Result<i64> {
+        Ok(EjJobDb::by_client_id(client_id)
+            .filter(status.eq_any([EjJobStatus::not_started(), EjJobStatus::running()]))
+            .count()
+            .get_result(conn)?)
+    }
+
+    /// Counts the jobs a client dispatched at or after `since`.
+    pub fn count_by_client_since_in(
+        client_id: &Uuid,
+        since: DateTime<Utc>,
+        conn: &mut PgConnection,
+    ) -> Result<i64> {
+        Ok(EjJobDb::by_client_id(client_id)
+            .filter(created_at.ge(since))
+            .count()
+            .get_result(conn)?)
+    }
+
     pub fn success(&self) -> bool {
         self.status == EjJobStatus::success()
     }
+
+    /// Fields jobs can be sorted by.
+    pub const SORT_FIELDS: [&str; 3] = ["created_at", "dispatched_at", "finished_at"];
+
+    /// Fetches a page of the jobs matching `filter`, oldest first unless `page` says otherwise.
+    pub fn fetch_filtered(
+        filter: &EjJobFilter,
+        page: &DbPage,
+        connection: &DbConnection,
+    ) -> Result<Vec<Self>> {
+        let conn = &mut connection.read_pool.get()?;
+        let query = EjJobDb::filtered(filter);
+        let query = match page.sort.as_deref() {
+            Some("dispatched_at") => query.sort_by(dispatched_at, page.descending),
+            Some("finished_at") => query.sort_by(finished_at, page.descending),
+            _ => query.sort_by(created_at, page.descending),
+        };
+        Ok(query
+            .then_order_by(id.asc())
+            .paginate(page)
+            .select(EjJobDb::as_select())
+            .load(conn)?)
+    }
+
+    /// Counts the jobs matching `filter`.
+    pub fn count_filtered(filter: &EjJobFilter, connection: &DbConnection) -> Result<i64> {
+        let conn = &mut connection.read_pool.get()?;
+        Ok(EjJobDb::filtered(filter).count().get_result(conn)?)
+    }
+
+    fn filtered(filter: &EjJobFilter) -> crate::schema::ejjob::BoxedQuery<'_, Pg> {
+        let mut query = crate::schema::ejjob::dsl::ejjob.into_boxed();
+        if let Some(target) = &filter.commit_hash {
+            query = query.filter(commit_hash.eq(target));
+        }
+        if let Some(target) = &filter.remote_url {
+            query = query.filter(remote_url.eq(target));
+        }
+        if let Some(target) = filter.status {
+            query = query.filter(status.eq(target));
+        }
+        if let Some(target) = filter.job_type {
+            query = query.filter(job_type.eq(target));
+        }
+        if let Some(since) = filter.since {
+            query = query.filter(created_at.ge(since));
+        }
+        if let Some(until) = filter.until {
+            query = query.filter(created_at.lt(until));
+        }
+        query
+    }
 }
 
 impl EjJobDb {
@@ -114,6 +258,11 @@ impl EjJobDb {
         crate::schema::ejjob::dsl::ejjob.filter(commit_hash.eq(target))
     }
 
+    #[diesel::dsl::auto_type(no_type_alias)]
+    pub fn by_client_id(target: &Uuid) -> _ {
+        crate::schema::ejjob::dsl::ejjob.filter(ejclient_id.eq(target))
+    }
+
     #[diesel::dsl::auto_type(no_type_alias)]
     pub fn by_remote_url(target: &str) -> _ {
         crate::schema::ejjob::dsl::ejjob.filter(remote_url.eq(target))
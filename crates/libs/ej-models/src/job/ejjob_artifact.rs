@@ -0,0 +1,93 @@
+//! Job artifact management for tracking files produced by builds.
+//!
+//! Only the artifact metadata is stored in the database, the content is kept
+//! on disk by the dispatcher.
+
+use crate::config::ejboard_config::EjBoardConfigDb;
+use crate::prelude::*;
+use crate::{db::connection::DbConnection, schema::ejjobartifact::dsl::*};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// An artifact produced by a job.
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, PartialEq, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::ejjobartifact)]
+#[diesel(belongs_to(EjJob))]
+#[diesel(belongs_to(EjBoardConfigDb))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EjJobArtifactDb {
+    /// Unique artifact ID.
+    pub id: Uuid,
+    /// The job that produced this artifact.
+    pub ejjob_id: Uuid,
+    /// The board config that produced this artifact.
+    pub ejboard_config_id: Uuid,
+    /// File name of the artifact.
+    pub name: String,
+    /// Size of the artifact in bytes.
+    pub size: i64,
+    /// When this artifact was created.
+    pub created_at: DateTime<Utc>,
+    /// When this artifact was last updated.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Data for creating a new job artifact entry.
+#[derive(Insertable, PartialEq, Debug, Clone, Deserialize)]
+#[diesel(table_name = crate::schema::ejjobartifact)]
+pub struct EjJobArtifactCreate {
+    /// The artifact ID, also used to locate its content.
+    pub id: Uuid,
+    /// The job ID this artifact belongs to.
+    pub ejjob_id: Uuid,
+    /// The board config ID this artifact is associated with.
+    pub ejboard_config_id: Uuid,
+    /// File name of the artifact.
+    pub name: String,
+    /// Size of the artifact in bytes.
+    pub size: i64,
+}
+
+impl EjJobArtifactCreate {
+    /// Saves the job artifact to the database.
+    pub fn save(self, connection: &DbConnection) -> Result<EjJobArtifactDb> {
+        let conn = &mut connection.pool.get()?;
+        Ok(diesel::insert_into(ejjobartifact)
+            .values(&self)
+            .returning(EjJobArtifactDb::as_returning())
+            .get_result(conn)?)
+    }
+}
+
+impl EjJobArtifactDb {
+    /// Fetches a job artifact by its ID.
+    pub fn fetch_by_id(target: &Uuid, connection: &DbConnection) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(EjJobArtifactDb::by_id(target)
+            .select(EjJobArtifactDb::as_select())
+            .get_result(conn)?)
+    }
+
+    /// Fetches all artifacts for a specific job.
+    pub fn fetch_by_job_id(target: &Uuid, connection: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+        Ok(EjJobArtifactDb::by_job_id(target)
+            .select(EjJobArtifactDb::as_select())
+            .order(created_at.asc())
+            .load(conn)?)
+    }
+
+    /// Returns a query filtered by artifact ID.
+    #[diesel::dsl::auto_type(no_type_alias)]
+    pub fn by_id(target: &Uuid) -> _ {
+        crate::schema::ejjobartifact::dsl::ejjobartifact.filter(id.eq(target))
+    }
+
+    /// Returns a query filtered by job ID.
+    #[diesel::dsl::auto_type(no_type_alias)]
+    pub fn by_job_id(target: &Uuid) -> _ {
+        crate::schema::ejjobartifact::dsl::ejjobartifact.filter(ejjob_id.eq(target))
+    }
+}
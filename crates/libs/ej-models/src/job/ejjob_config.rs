@@ -0,0 +1,94 @@
+//! The config versions jobs ran against.
+//!
+//! Builders upload a new config version whenever their config changes, so
+//! recording the version each job ran against keeps its results interpretable
+//! after the builder moved on.
+
+use crate::config::ejconfig::EjConfigDb;
+use crate::prelude::*;
+use crate::schema::{ejboard, ejboard_config, ejconfig};
+use crate::{db::connection::DbConnection, schema::ejjobconfig::dsl::*};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A config version a job ran against.
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, PartialEq, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::ejjobconfig)]
+#[diesel(primary_key(ejjob_id, ejconfig_id))]
+#[diesel(belongs_to(EjJob))]
+#[diesel(belongs_to(EjConfigDb))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EjJobConfig {
+    /// The job.
+    pub ejjob_id: Uuid,
+    /// The config version the job ran against.
+    pub ejconfig_id: Uuid,
+    /// When the association was recorded.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Data for recording the config version a job ran against.
+#[derive(Insertable, PartialEq, Debug, Clone, Deserialize)]
+#[diesel(table_name = crate::schema::ejjobconfig)]
+pub struct EjJobConfigCreate {
+    /// The job.
+    pub ejjob_id: Uuid,
+    /// The config version the job ran against.
+    pub ejconfig_id: Uuid,
+}
+
+impl EjJobConfig {
+    /// Records the config versions the given board configs belong to as the
+    /// ones a job ran against, on a connection that may be in a transaction.
+    ///
+    /// Versions already recorded for the job are left as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `job_id` - The job
+    /// * `board_config_ids` - Board configs the job produced output for
+    /// * `conn` - Database connection
+    ///
+    /// # Returns
+    ///
+    /// The number of versions newly recorded.
+    pub fn record_in(
+        job_id: &Uuid,
+        board_config_ids: &[Uuid],
+        conn: &mut PgConnection,
+    ) -> Result<usize> {
+        let config_ids: Vec<Uuid> = ejboard_config::table
+            .inner_join(ejboard::table)
+            .filter(ejboard_config::id.eq_any(board_config_ids))
+            .select(ejboard::ejconfig_id)
+            .distinct()
+            .load(conn)?;
+        let rows: Vec<EjJobConfigCreate> = config_ids
+            .into_iter()
+            .map(|config_id| EjJobConfigCreate {
+                ejjob_id: *job_id,
+                ejconfig_id: config_id,
+            })
+            .collect();
+        Ok(diesel::insert_into(ejjobconfig)
+            .values(&rows)
+            .on_conflict_do_nothing()
+            .execute(conn)?)
+    }
+
+    /// Fetches the config versions a job ran against, oldest first.
+    pub fn fetch_configs_by_job_id(
+        target: &Uuid,
+        connection: &DbConnection,
+    ) -> Result<Vec<EjConfigDb>> {
+        let conn = &mut connection.pool.get()?;
+        Ok(ejjobconfig
+            .inner_join(ejconfig::table)
+            .filter(ejjob_id.eq(target))
+            .order((ejconfig::created_at.asc(), ejconfig::id.asc()))
+            .select(EjConfigDb::as_select())
+            .load(conn)?)
+    }
+}
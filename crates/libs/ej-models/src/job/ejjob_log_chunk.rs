@@ -0,0 +1,244 @@
+//! Job log chunks, the pieces job logs are stored in.
+//!
+//! The output of a job for a board config is stored as chunks numbered from 0
+//! so it can be appended to while the job runs and read back a range at a time
+//! instead of as a single string.
+
+use crate::config::ejboard_config::EjBoardConfigDb;
+use crate::job::ejjob_search::{
+    EjJobSearchFilter, EjJobSearchMatch, search_matches, search_snippet,
+};
+use crate::prelude::*;
+use crate::{db::connection::DbConnection, schema::ejjoblogchunk::dsl::*};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A chunk of the output of a job for a board config.
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, PartialEq, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::ejjoblogchunk)]
+#[diesel(belongs_to(EjJob))]
+#[diesel(belongs_to(EjBoardConfigDb))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EjJobLogChunk {
+    /// Unique chunk ID.
+    pub id: Uuid,
+    /// The job this chunk belongs to.
+    pub ejjob_id: Uuid,
+    /// The board config this chunk is associated with.
+    pub ejboard_config_id: Uuid,
+    /// Position of the chunk in the log, starting at 0.
+    pub sequence: i32,
+    /// The output in this chunk.
+    pub content: String,
+    /// When this chunk was stored.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Data for storing a chunk of a job log.
+#[derive(Insertable, PartialEq, Debug, Clone, Deserialize)]
+#[diesel(table_name = crate::schema::ejjoblogchunk)]
+pub struct EjJobLogChunkCreate {
+    /// The job ID this chunk belongs to.
+    pub ejjob_id: Uuid,
+    /// The board config ID this chunk is associated with.
+    pub ejboard_config_id: Uuid,
+    /// Position of the chunk in the log.
+    pub sequence: i32,
+    /// The output in this chunk.
+    pub content: String,
+}
+
+impl EjJobLogChunk {
+    /// Appends a chunk after the last one stored for a job and board config.
+    pub fn append(
+        job_id: &Uuid,
+        board_config_id: &Uuid,
+        chunk: &str,
+        connection: &DbConnection,
+    ) -> Result<Self> {
+        let conn = &mut connection.pool.get()?;
+        Ok(conn.transaction(|conn| {
+            let last: Option<i32> = EjJobLogChunk::by_job_and_board(job_id, board_config_id)
+                .select(diesel::dsl::max(sequence))
+                .get_result(conn)?;
+            diesel::insert_into(ejjoblogchunk)
+                .values(EjJobLogChunkCreate {
+                    ejjob_id: *job_id,
+                    ejboard_config_id: *board_config_id,
+                    sequence: last.map_or(0, |last| last + 1),
+                    content: chunk.to_string(),
+                })
+                .returning(EjJobLogChunk::as_returning())
+                .get_result(conn)
+        })?)
+    }
+
+    /// Replaces every chunk stored for a job and board config.
+    ///
+    /// # Arguments
+    /// * `chunks` - The new chunks, in order
+    pub fn replace(
+        job_id: &Uuid,
+        board_config_id: &Uuid,
+        chunks: Vec<String>,
+        connection: &DbConnection,
+    ) -> Result<Vec<Self>> {
+        let conn = &mut connection.pool.get()?;
+        Self::replace_in(job_id, board_config_id, chunks, conn)
+    }
+
+    /// Same as [`EjJobLogChunk::replace`], run on `conn`.
+    pub fn replace_in(
+        job_id: &Uuid,
+        board_config_id: &Uuid,
+        chunks: Vec<String>,
+        conn: &mut PgConnection,
+    ) -> Result<Vec<Self>> {
+        let chunks: Vec<EjJobLogChunkCreate> = chunks
+            .into_iter()
+            .zip(0..)
+            .map(|(chunk, position)| EjJobLogChunkCreate {
+                ejjob_id: *job_id,
+                ejboard_config_id: *board_config_id,
+                sequence: position,
+                content: chunk,
+            })
+            .collect();
+        Ok(conn.transaction(|conn| {
+            diesel::delete(EjJobLogChunk::by_job_and_board(job_id, board_config_id))
+                .execute(conn)?;
+            diesel::insert_into(ejjoblogchunk)
+                .values(&chunks)
+                .returning(EjJobLogChunk::as_returning())
+                .get_results(conn)
+        })?)
+    }
+
+    /// Fetches the chunks of a job and board config starting at `from_sequence`.
+    ///
+    /// Served by the read pool, so the latest chunks may be missing while a
+    /// read replica catches up.
+    ///
+    /// # Arguments
+    /// * `from_sequence` - Position of the first chunk returned
+    /// * `limit` - Maximum number of chunks returned
+    pub fn fetch_range(
+        job_id: &Uuid,
+        board_config_id: &Uuid,
+        from_sequence: i32,
+        limit: i64,
+        connection: &DbConnection,
+    ) -> Result<Vec<Self>> {
+        let conn = &mut connection.read_pool.get()?;
+        Ok(EjJobLogChunk::by_job_and_board(job_id, board_config_id)
+            .filter(sequence.ge(from_sequence))
+            .order(sequence.asc())
+            .limit(limit)
+            .select(EjJobLogChunk::as_select())
+            .load(conn)?)
+    }
+
+    /// Fetches the last `count` chunks of a job and board config, in order.
+    ///
+    /// Served by the read pool, see [`EjJobLogChunk::fetch_range`].
+    pub fn fetch_tail(
+        job_id: &Uuid,
+        board_config_id: &Uuid,
+        count: i64,
+        connection: &DbConnection,
+    ) -> Result<Vec<Self>> {
+        let conn = &mut connection.read_pool.get()?;
+        let mut chunks = EjJobLogChunk::by_job_and_board(job_id, board_config_id)
+            .order(sequence.desc())
+            .limit(count)
+            .select(EjJobLogChunk::as_select())
+            .load(conn)?;
+        chunks.reverse();
+        Ok(chunks)
+    }
+
+    /// Fetches every chunk of a job along with its board config, ordered by
+    /// board config and position.
+    pub fn fetch_with_board_config_by_job_id(
+        target: &Uuid,
+        connection: &DbConnection,
+    ) -> Result<Vec<(EjJobLogChunk, EjBoardConfigDb)>> {
+        let conn = &mut connection.pool.get()?;
+        Ok(EjJobLogChunk::by_job_id(target)
+            .inner_join(crate::schema::ejboard_config::table)
+            .order((ejboard_config_id.asc(), sequence.asc()))
+            .select((EjJobLogChunk::as_select(), EjBoardConfigDb::as_select()))
+            .load(conn)?)
+    }
+
+    /// Searches the chunks of every job for `query`, newest first.
+    ///
+    /// See [`crate::job::ejjob_search`] for the query syntax.
+    pub fn search(
+        query: &str,
+        filter: &EjJobSearchFilter,
+        connection: &DbConnection,
+    ) -> Result<Vec<EjJobSearchMatch>> {
+        use crate::schema::ejjob::dsl::commit_hash;
+
+        let conn = &mut connection.read_pool.get()?;
+        let mut matches = ejjoblogchunk
+            .inner_join(crate::schema::ejjob::table)
+            .filter(search_matches("ejjoblogchunk.content", query))
+            .into_boxed();
+        if let Some(target_job) = filter.job_id {
+            matches = matches.filter(ejjob_id.eq(target_job));
+        }
+        if let Some(target_commit) = &filter.commit_hash {
+            matches = matches.filter(commit_hash.eq(target_commit));
+        }
+        if let Some(target_board_config) = filter.board_config_id {
+            matches = matches.filter(ejboard_config_id.eq(target_board_config));
+        }
+        if let Some(since) = filter.since {
+            matches = matches.filter(created_at.ge(since));
+        }
+        if let Some(until) = filter.until {
+            matches = matches.filter(created_at.lt(until));
+        }
+        let matches: Vec<(Uuid, String, Uuid, String, DateTime<Utc>)> = matches
+            .order((created_at.desc(), sequence.asc()))
+            .limit(filter.limit)
+            .select((
+                ejjob_id,
+                commit_hash,
+                ejboard_config_id,
+                search_snippet("ejjoblogchunk.content", query),
+                created_at,
+            ))
+            .load(conn)?;
+        Ok(matches
+            .into_iter()
+            .map(
+                |(job_id, job_commit_hash, board_config_id, snippet, stored_at)| EjJobSearchMatch {
+                    ejjob_id: job_id,
+                    commit_hash: job_commit_hash,
+                    ejboard_config_id: board_config_id,
+                    snippet,
+                    created_at: stored_at,
+                },
+            )
+            .collect())
+    }
+
+    /// Returns a query filtered by job ID.
+    #[diesel::dsl::auto_type(no_type_alias)]
+    pub fn by_job_id(target: &Uuid) -> _ {
+        crate::schema::ejjoblogchunk::dsl::ejjoblogchunk.filter(ejjob_id.eq(target))
+    }
+
+    /// Returns a query filtered by both job and board config ID.
+    #[diesel::dsl::auto_type(no_type_alias)]
+    pub fn by_job_and_board<'a>(job_id: &'a Uuid, board_config_id: &'a Uuid) -> _ {
+        crate::schema::ejjoblogchunk::dsl::ejjoblogchunk
+            .filter(ejjob_id.eq(job_id))
+            .filter(ejboard_config_id.eq(board_config_id))
+    }
+}
@@ -1,76 +1,79 @@
 //! Job log management for tracking execution output.
+//!
+//! Logs are stored as [`EjJobLogChunk`]s. An [`EjJobLog`] is the full output
+//! of a job for a board config, assembled from its chunks.
 
 use crate::config::ejboard_config::EjBoardConfigDb;
+use crate::db::connection::DbConnection;
 use crate::job::ejjob::EjJobDb;
+use crate::job::ejjob_log_chunk::EjJobLogChunk;
+use crate::job::ejjob_search::{EjJobSearchFilter, EjJobSearchMatch};
 use crate::prelude::*;
-use crate::{db::connection::DbConnection, schema::ejjoblog::dsl::*};
-use chrono::{DateTime, Utc};
-use diesel::associations::HasTable;
-use diesel::prelude::*;
+use diesel::PgConnection;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-/// A log entry for a job execution.
-#[derive(Debug, Clone, Queryable, Selectable, Identifiable, PartialEq, Serialize, Deserialize)]
-#[diesel(table_name = crate::schema::ejjoblog)]
-#[diesel(belongs_to(EjJob))]
-#[diesel(belongs_to(EjBoardConfigDb))]
-#[diesel(check_for_backend(diesel::pg::Pg))]
+/// Size in bytes above which a log stored in one go is split into several chunks.
+pub const LOG_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The output of a job for a board config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EjJobLog {
-    /// Unique log entry ID.
-    pub id: Uuid,
     /// The job this log belongs to.
     pub ejjob_id: Uuid,
     /// The board config this log is associated with.
     pub ejboard_config_id: Uuid,
     /// The log content.
     pub log: String,
-    /// When this log entry was created.
-    pub created_at: DateTime<Utc>,
-    /// When this log entry was last updated.
-    pub updated_at: DateTime<Utc>,
 }
 
-/// Data for creating a new job log entry.
-#[derive(Insertable, PartialEq, Debug, Clone, Deserialize)]
-#[diesel(table_name = crate::schema::ejjoblog)]
-pub struct EjJobLogCreate {
-    /// The job ID this log belongs to.
-    pub ejjob_id: Uuid,
-    /// The board config ID this log is associated with.
-    pub ejboard_config_id: Uuid,
-    /// The log content.
-    pub log: String,
-}
+impl EjJobLog {
+    /// Appends a chunk of output to the log of a job for a board config.
+    pub fn append(
+        job_id: &Uuid,
+        board_config_id: &Uuid,
+        chunk: &str,
+        connection: &DbConnection,
+    ) -> Result<EjJobLogChunk> {
+        EjJobLogChunk::append(job_id, board_config_id, chunk, connection)
+    }
 
-impl EjJobLogCreate {
-    /// Saves the job log to the database.
-    pub fn save(self, connection: &DbConnection) -> Result<EjJobLog> {
+    /// Stores the whole log of a job for a board config, replacing anything
+    /// appended to it so far.
+    ///
+    /// The log is split into chunks of about [`LOG_CHUNK_SIZE`] bytes, on line
+    /// boundaries.
+    pub fn replace(
+        job_id: &Uuid,
+        board_config_id: &Uuid,
+        log: &str,
+        connection: &DbConnection,
+    ) -> Result<Self> {
         let conn = &mut connection.pool.get()?;
-        Ok(diesel::insert_into(ejjoblog)
-            .values(&self)
-            .returning(EjJobLog::as_returning())
-            .get_result(conn)?
-            .into())
+        Self::replace_in(job_id, board_config_id, log, conn)
     }
-}
 
-impl EjJobLog {
-    /// Fetches a job log by its ID.
-    pub fn fetch_by_id(target: &Uuid, connection: &DbConnection) -> Result<Self> {
-        let conn = &mut connection.pool.get()?;
-        let job_log: EjJobLog = EjJobLog::by_id(target)
-            .select(EjJobLog::as_select())
-            .get_result(conn)?;
-        Ok(job_log.into())
+    /// Same as [`EjJobLog::replace`], run on `conn`.
+    pub fn replace_in(
+        job_id: &Uuid,
+        board_config_id: &Uuid,
+        log: &str,
+        conn: &mut PgConnection,
+    ) -> Result<Self> {
+        EjJobLogChunk::replace_in(job_id, board_config_id, split_log(log), conn)?;
+        Ok(Self {
+            ejjob_id: *job_id,
+            ejboard_config_id: *board_config_id,
+            log: log.to_string(),
+        })
     }
 
     /// Fetches all logs for a specific job.
     pub fn fetch_by_job_id(target: &Uuid, connection: &DbConnection) -> Result<Vec<Self>> {
-        let conn = &mut connection.pool.get()?;
-        Ok(EjJobLog::by_job_id(target)
-            .select(EjJobLog::as_select())
-            .load(conn)?)
+        Ok(Self::fetch_with_board_config_by_job_id(target, connection)?
+            .into_iter()
+            .map(|(log, _)| log)
+            .collect())
     }
 
     /// Fetches job logs with their associated board config.
@@ -78,70 +81,59 @@ impl EjJobLog {
         target: &Uuid,
         connection: &DbConnection,
     ) -> Result<Vec<(EjJobLog, EjBoardConfigDb)>> {
-        let conn = &mut connection.pool.get()?;
-
-        let results = EjJobLog::by_job_id(target)
-            .inner_join(crate::schema::ejboard_config::table)
-            .select((EjJobLog::as_select(), EjBoardConfigDb::as_select()))
-            .load::<(EjJobLog, EjBoardConfigDb)>(conn)?;
-
-        Ok(results)
-    }
-
-    /// Fetches all logs for a specific board config.
-    pub fn fetch_by_board_config_id(target: &Uuid, connection: &DbConnection) -> Result<Vec<Self>> {
-        let conn = &mut connection.pool.get()?;
-        Ok(EjJobLog::by_board_config_id(target)
-            .select(EjJobLog::as_select())
-            .load(conn)?)
+        let mut logs: Vec<(EjJobLog, EjBoardConfigDb)> = Vec::new();
+        for (chunk, board_config) in
+            EjJobLogChunk::fetch_with_board_config_by_job_id(target, connection)?
+        {
+            match logs.last_mut() {
+                Some((log, _)) if log.ejboard_config_id == chunk.ejboard_config_id => {
+                    log.log.push_str(&chunk.content);
+                }
+                _ => logs.push((
+                    EjJobLog {
+                        ejjob_id: chunk.ejjob_id,
+                        ejboard_config_id: chunk.ejboard_config_id,
+                        log: chunk.content,
+                    },
+                    board_config,
+                )),
+            }
+        }
+        Ok(logs)
     }
 
-    /// Fetches logs for a specific job and board config combination.
-    pub fn fetch_by_job_and_board(
-        job_id: &Uuid,
-        board_config_id: &Uuid,
+    /// Searches the logs of every job for `query`, newest first.
+    ///
+    /// Each match is a chunk of a log, so a log matching in several chunks
+    /// shows up several times. See [`crate::job::ejjob_search`] for the query
+    /// syntax.
+    pub fn search(
+        query: &str,
+        filter: &EjJobSearchFilter,
         connection: &DbConnection,
-    ) -> Result<Vec<Self>> {
-        let conn = &mut connection.pool.get()?;
-        Ok(EjJobLog::by_job_and_board(job_id, board_config_id)
-            .select(EjJobLog::as_select())
-            .load(conn)?)
+    ) -> Result<Vec<EjJobSearchMatch>> {
+        EjJobLogChunk::search(query, filter, connection)
     }
 
     /// Fetches the job associated with this log.
     pub fn fetch_job(&self, connection: &DbConnection) -> Result<EjJobDb> {
         EjJobDb::fetch_by_id(&self.ejjob_id, connection)
     }
+}
 
-    /// Fetches all job logs from the database.
-    pub fn fetch_all(connection: &DbConnection) -> Result<Vec<Self>> {
-        let conn = &mut connection.pool.get()?;
-        Ok(EjJobLog::table().select(EjJobLog::as_select()).load(conn)?)
-    }
-
-    /// Returns a query filtered by log ID.
-    #[diesel::dsl::auto_type(no_type_alias)]
-    pub fn by_id(target: &Uuid) -> _ {
-        crate::schema::ejjoblog::dsl::ejjoblog.filter(id.eq(target))
-    }
-
-    /// Returns a query filtered by job ID.
-    #[diesel::dsl::auto_type(no_type_alias)]
-    pub fn by_job_id(target: &Uuid) -> _ {
-        crate::schema::ejjoblog::dsl::ejjoblog.filter(ejjob_id.eq(target))
-    }
-
-    /// Returns a query filtered by board config ID.
-    #[diesel::dsl::auto_type(no_type_alias)]
-    pub fn by_board_config_id(target: &Uuid) -> _ {
-        crate::schema::ejjoblog::dsl::ejjoblog.filter(ejboard_config_id.eq(target))
-    }
-
-    /// Returns a query filtered by both job and board config ID.
-    #[diesel::dsl::auto_type(no_type_alias)]
-    pub fn by_job_and_board<'a>(job_id: &'a Uuid, board_config_id: &'a Uuid) -> _ {
-        crate::schema::ejjoblog::dsl::ejjoblog
-            .filter(ejjob_id.eq(job_id))
-            .filter(ejboard_config_id.eq(board_config_id))
+/// Splits a log into chunks of about [`LOG_CHUNK_SIZE`] bytes.
+///
+/// Lines are never split, so a single line longer than the chunk size gets a
+/// chunk of its own. An empty log still gets an empty chunk so it's listed.
+fn split_log(log: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    for line in log.split_inclusive('\n') {
+        if !chunk.is_empty() && chunk.len() + line.len() > LOG_CHUNK_SIZE {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+        chunk.push_str(line);
     }
+    chunks.push(chunk);
+    chunks
 }
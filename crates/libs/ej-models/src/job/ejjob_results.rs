@@ -2,6 +2,9 @@
 
 use crate::config::ejboard_config::EjBoardConfigDb;
 use crate::job::ejjob::EjJobDb;
+use crate::job::ejjob_search::{
+    EjJobSearchFilter, EjJobSearchMatch, search_matches, search_snippet,
+};
 use crate::prelude::*;
 use crate::{db::connection::DbConnection, schema::ejjobresult::dsl::*};
 use chrono::{DateTime, Utc};
@@ -44,6 +47,11 @@ impl EjJobResultCreate {
     /// Saves the job result to the database.
     pub fn save(self, connection: &DbConnection) -> Result<EjJobResultDb> {
         let conn = &mut connection.pool.get()?;
+        self.save_in(conn)
+    }
+
+    /// Same as [`EjJobResultCreate::save`], run on `conn`.
+    pub fn save_in(self, conn: &mut PgConnection) -> Result<EjJobResultDb> {
         Ok(diesel::insert_into(ejjobresult)
             .values(&self)
             .returning(EjJobResultDb::as_returning())
@@ -96,6 +104,61 @@ impl EjJobResultDb {
         Ok(results)
     }
 
+    /// Searches the results of every job for `query`, newest first.
+    ///
+    /// See [`crate::job::ejjob_search`] for the query syntax.
+    pub fn search(
+        query: &str,
+        filter: &EjJobSearchFilter,
+        connection: &DbConnection,
+    ) -> Result<Vec<EjJobSearchMatch>> {
+        use crate::schema::ejjob::dsl::commit_hash;
+
+        let conn = &mut connection.read_pool.get()?;
+        let mut matches = ejjobresult
+            .inner_join(crate::schema::ejjob::table)
+            .filter(search_matches("ejjobresult.result", query))
+            .into_boxed();
+        if let Some(target_job) = filter.job_id {
+            matches = matches.filter(ejjob_id.eq(target_job));
+        }
+        if let Some(target_commit) = &filter.commit_hash {
+            matches = matches.filter(commit_hash.eq(target_commit));
+        }
+        if let Some(target_board_config) = filter.board_config_id {
+            matches = matches.filter(ejboard_config_id.eq(target_board_config));
+        }
+        if let Some(since) = filter.since {
+            matches = matches.filter(updated_at.ge(since));
+        }
+        if let Some(until) = filter.until {
+            matches = matches.filter(updated_at.lt(until));
+        }
+        let matches: Vec<(Uuid, String, Uuid, String, DateTime<Utc>)> = matches
+            .order(updated_at.desc())
+            .limit(filter.limit)
+            .select((
+                ejjob_id,
+                commit_hash,
+                ejboard_config_id,
+                search_snippet("ejjobresult.result", query),
+                updated_at,
+            ))
+            .load(conn)?;
+        Ok(matches
+            .into_iter()
+            .map(
+                |(job_id, job_commit_hash, board_config_id, snippet, stored_at)| EjJobSearchMatch {
+                    ejjob_id: job_id,
+                    commit_hash: job_commit_hash,
+                    ejboard_config_id: board_config_id,
+                    snippet,
+                    created_at: stored_at,
+                },
+            )
+            .collect())
+    }
+
     pub fn fetch_job(&self, connection: &DbConnection) -> Result<EjJobDb> {
         EjJobDb::fetch_by_id(&self.ejjob_id, connection)
     }
@@ -0,0 +1,73 @@
+//! Full-text search over job logs and results.
+//!
+//! Log chunks and results are indexed with the `simple` text search
+//! configuration, which doesn't stem words nor drop stop words, so searching
+//! for an error message matches it as written. Queries use the
+//! `websearch_to_tsquery` syntax: quoted phrases, `or` and `-word` exclusions.
+
+use chrono::{DateTime, Utc};
+use diesel::{
+    dsl::sql,
+    expression::{AsExpression, SqlLiteral, UncheckedBind},
+    sql_types::{Bool, Text},
+};
+use uuid::Uuid;
+
+/// Criteria used to narrow a search down.
+///
+/// Unset criteria match every job.
+#[derive(Debug, Clone, Default)]
+pub struct EjJobSearchFilter {
+    /// Only matches from this job.
+    pub job_id: Option<Uuid>,
+    /// Only matches from jobs for this commit.
+    pub commit_hash: Option<String>,
+    /// Only matches from this board config.
+    pub board_config_id: Option<Uuid>,
+    /// Only matches stored at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only matches stored before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Maximum number of matches returned.
+    pub limit: i64,
+}
+
+/// A job log chunk or result matching a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EjJobSearchMatch {
+    /// The job the match belongs to.
+    pub ejjob_id: Uuid,
+    /// The commit the job ran for.
+    pub commit_hash: String,
+    /// The board config the match is associated with.
+    pub ejboard_config_id: Uuid,
+    /// Excerpts of the matching text, matched words surrounded by `**`.
+    pub snippet: String,
+    /// When the matching text was stored.
+    pub created_at: DateTime<Utc>,
+}
+
+/// SQL expression around a bound search query.
+pub(crate) type SearchSql<ST> =
+    SqlLiteral<ST, UncheckedBind<SqlLiteral<ST>, <String as AsExpression<Text>>::Expression>>;
+
+/// Condition matching rows whose `column` matches the search `query`.
+///
+/// The expression must be the one the search indexes are built on for them
+/// to be used.
+pub(crate) fn search_matches(column: &str, query: &str) -> SearchSql<Bool> {
+    sql::<Bool>(&format!(
+        "to_tsvector('simple', {column}) @@ websearch_to_tsquery('simple', "
+    ))
+    .bind::<Text, _>(query.to_string())
+    .sql(")")
+}
+
+/// Excerpts of `column` around the words matching the search `query`.
+pub(crate) fn search_snippet(column: &str, query: &str) -> SearchSql<Text> {
+    sql::<Text>(&format!(
+        "ts_headline('simple', {column}, websearch_to_tsquery('simple', "
+    ))
+    .bind::<Text, _>(query.to_string())
+    .sql("), 'MaxFragments=3, StartSel=**, StopSel=**')")
+}
@@ -0,0 +1,197 @@
+//! Aggregate statistics over finished jobs.
+//!
+//! Jobs are attributed to the board configs they produced logs for, and
+//! through them to the builders that uploaded those board configs. Only jobs
+//! that succeeded or failed are counted, cancelled jobs didn't run to
+//! completion so their durations would skew the numbers.
+
+use crate::db::connection::DbConnection;
+use crate::job::ejjob_status::EjJobStatus;
+use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt, Double, Int4, Nullable, Text, Timestamptz};
+use uuid::Uuid;
+
+/// Criteria used to select the jobs statistics are computed over.
+///
+/// Unset criteria match every finished job.
+#[derive(Debug, Clone, Default)]
+pub struct EjJobStatsFilter {
+    /// Only jobs dispatched at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only jobs dispatched before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Only jobs for these commits.
+    pub commit_hashes: Option<Vec<String>>,
+    /// Maximum number of rows returned by each query.
+    pub limit: i64,
+}
+
+/// Duration statistics of the jobs run on a board config.
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct EjBoardConfigDurationStats {
+    /// The board config.
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub ejboard_config_id: Uuid,
+    /// Name of the board config.
+    #[diesel(sql_type = Text)]
+    pub name: String,
+    /// Number of jobs run on the board config.
+    #[diesel(sql_type = BigInt)]
+    pub jobs: i64,
+    /// Average job duration in seconds.
+    #[diesel(sql_type = Double)]
+    pub average_seconds: f64,
+    /// Median job duration in seconds.
+    #[diesel(sql_type = Double)]
+    pub p50_seconds: f64,
+    /// 95th percentile of the job durations in seconds.
+    #[diesel(sql_type = Double)]
+    pub p95_seconds: f64,
+}
+
+/// Outcome of the jobs run for a commit.
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct EjCommitSuccessStats {
+    /// The commit.
+    #[diesel(sql_type = Text)]
+    pub commit_hash: String,
+    /// Number of jobs run for the commit.
+    #[diesel(sql_type = BigInt)]
+    pub jobs: i64,
+    /// Number of those jobs that succeeded.
+    #[diesel(sql_type = BigInt)]
+    pub successful: i64,
+    /// When the last of those jobs finished.
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub last_finished_at: Option<DateTime<Utc>>,
+}
+
+/// Number of jobs run by a builder.
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct EjBuilderJobStats {
+    /// The builder.
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub ejbuilder_id: Uuid,
+    /// Number of jobs the builder ran.
+    #[diesel(sql_type = BigInt)]
+    pub jobs: i64,
+    /// When the builder was last dispatched a job.
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub last_dispatched_at: Option<DateTime<Utc>>,
+}
+
+/// Jobs matching the filter bound as `$1` to `$5`.
+const FILTERED_JOBS: &str = "
+    SELECT id, commit_hash, status, dispatched_at, finished_at
+    FROM ejjob
+    WHERE status IN ($1, $2)
+        AND ($3::timestamptz IS NULL OR dispatched_at >= $3)
+        AND ($4::timestamptz IS NULL OR dispatched_at < $4)
+        AND ($5::text[] IS NULL OR commit_hash = ANY($5))";
+
+/// The board configs each job produced logs for.
+const JOB_BOARD_CONFIGS: &str = "SELECT DISTINCT ejjob_id, ejboard_config_id FROM ejjoblogchunk";
+
+/// Binds the filter parameters expected by [`FILTERED_JOBS`] followed by the limit.
+macro_rules! bind_filter {
+    ($query:expr, $filter:expr) => {
+        $query
+            .bind::<Int4, _>(EjJobStatus::success())
+            .bind::<Int4, _>(EjJobStatus::failed())
+            .bind::<Nullable<Timestamptz>, _>($filter.since)
+            .bind::<Nullable<Timestamptz>, _>($filter.until)
+            .bind::<Nullable<Array<Text>>, _>($filter.commit_hashes.clone())
+            .bind::<BigInt, _>($filter.limit)
+    };
+}
+
+impl EjBoardConfigDurationStats {
+    /// Computes the duration statistics of each board config, busiest first.
+    pub fn fetch(filter: &EjJobStatsFilter, connection: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut connection.read_pool.get()?;
+        let query = diesel::sql_query(format!(
+            "WITH jobs AS ({FILTERED_JOBS}),
+            durations AS (
+                SELECT l.ejboard_config_id,
+                    EXTRACT(EPOCH FROM jobs.finished_at - jobs.dispatched_at)::float8 AS seconds
+                FROM jobs
+                JOIN ({JOB_BOARD_CONFIGS}) l ON l.ejjob_id = jobs.id
+                WHERE jobs.dispatched_at IS NOT NULL AND jobs.finished_at IS NOT NULL
+            )
+            SELECT d.ejboard_config_id, bc.name, COUNT(*) AS jobs,
+                AVG(d.seconds) AS average_seconds,
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY d.seconds) AS p50_seconds,
+                percentile_cont(0.95) WITHIN GROUP (ORDER BY d.seconds) AS p95_seconds
+            FROM durations d
+            JOIN ejboard_config bc ON bc.id = d.ejboard_config_id
+            GROUP BY d.ejboard_config_id, bc.name
+            ORDER BY jobs DESC, bc.name
+            LIMIT $6"
+        ));
+        Ok(bind_filter!(query, filter).load(conn)?)
+    }
+}
+
+impl EjCommitSuccessStats {
+    /// Computes how many of the jobs run for each commit succeeded, most
+    /// recently finished first.
+    pub fn fetch(filter: &EjJobStatsFilter, connection: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut connection.read_pool.get()?;
+        let query = diesel::sql_query(format!(
+            "WITH jobs AS ({FILTERED_JOBS})
+            SELECT commit_hash, COUNT(*) AS jobs,
+                COUNT(*) FILTER (WHERE status = $1) AS successful,
+                MAX(finished_at) AS last_finished_at
+            FROM jobs
+            GROUP BY commit_hash
+            ORDER BY last_finished_at DESC NULLS LAST, commit_hash
+            LIMIT $6"
+        ));
+        Ok(bind_filter!(query, filter).load(conn)?)
+    }
+
+    /// Share of the jobs that succeeded, between 0 and 1.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_models::job::ejjob_stats::EjCommitSuccessStats;
+    ///
+    /// let stats = EjCommitSuccessStats {
+    ///     commit_hash: String::from("abc123"),
+    ///     jobs: 4,
+    ///     successful: 3,
+    ///     last_finished_at: None,
+    /// };
+    /// assert_eq!(stats.success_rate(), 0.75);
+    /// ```
+    pub fn success_rate(&self) -> f64 {
+        if self.jobs == 0 {
+            return 0.0;
+        }
+        self.successful as f64 / self.jobs as f64
+    }
+}
+
+impl EjBuilderJobStats {
+    /// Counts the jobs run by each builder, busiest first.
+    pub fn fetch(filter: &EjJobStatsFilter, connection: &DbConnection) -> Result<Vec<Self>> {
+        let conn = &mut connection.read_pool.get()?;
+        let query = diesel::sql_query(format!(
+            "WITH jobs AS ({FILTERED_JOBS})
+            SELECT c.ejbuilder_id, COUNT(DISTINCT jobs.id) AS jobs,
+                MAX(jobs.dispatched_at) AS last_dispatched_at
+            FROM jobs
+            JOIN ({JOB_BOARD_CONFIGS}) l ON l.ejjob_id = jobs.id
+            JOIN ejboard_config bc ON bc.id = l.ejboard_config_id
+            JOIN ejboard b ON b.id = bc.ejboard_id
+            JOIN ejconfig c ON c.id = b.ejconfig_id
+            GROUP BY c.ejbuilder_id
+            ORDER BY jobs DESC, last_dispatched_at DESC NULLS LAST
+            LIMIT $6"
+        ));
+        Ok(bind_filter!(query, filter).load(conn)?)
+    }
+}
@@ -1,10 +1,15 @@
 //! Job execution models.
 //!
 //! This module contains data models for managing jobs, their execution status,
-//! logs, results, and related metadata in the ej system.
+//! logs, results, artifacts and related metadata in the ej system.
 
 pub mod ejjob;
+pub mod ejjob_artifact;
+pub mod ejjob_config;
+pub mod ejjob_log_chunk;
 pub mod ejjob_logs;
 pub mod ejjob_results;
+pub mod ejjob_search;
+pub mod ejjob_stats;
 pub mod ejjob_status;
 pub mod ejjob_type;
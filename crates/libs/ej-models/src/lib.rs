@@ -1,7 +1,7 @@
 //! Database models and ORM layer for the EJ framework.
 //!
 //! Provides Diesel-based database models, queries, and connection management
-//! for all EJ entities including clients, builders, jobs, and permissions.
+//! for all EJ entities including clients, builders, jobs, permissions and the audit log.
 //!
 //! # Usage
 //!
@@ -17,6 +17,7 @@
 //! println!("Found {} clients", clients.len());
 //! ```
 
+pub mod audit;
 pub mod auth;
 pub mod builder;
 pub mod client;
@@ -9,6 +9,42 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    client_role (ejclient_id, role_id) {
+        ejclient_id -> Uuid,
+        role_id -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    ejapikey (id) {
+        id -> Uuid,
+        ejclient_id -> Uuid,
+        name -> Varchar,
+        #[max_length = 16]
+        prefix -> Varchar,
+        #[max_length = 64]
+        hash -> Varchar,
+        scopes -> Array<Text>,
+        expires_at -> Nullable<Timestamptz>,
+        revoked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    ejauditlog (id) {
+        id -> Uuid,
+        action -> Varchar,
+        actor_id -> Nullable<Uuid>,
+        target -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     ejboard (id) {
         id -> Uuid,
@@ -41,9 +77,22 @@ diesel::table! {
 diesel::table! {
     ejbuilder (id) {
         id -> Uuid,
-        ejclient_id -> Uuid,
+        ejclient_id -> Nullable<Uuid>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+        last_seen_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    ejbuilderconnection (id) {
+        id -> Uuid,
+        ejbuilder_id -> Uuid,
+        address -> Varchar,
+        connected_at -> Timestamptz,
+        last_seen_at -> Timestamptz,
+        disconnected_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -69,6 +118,8 @@ diesel::table! {
         hash -> Varchar,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        content -> Nullable<Text>,
+        uploaded_at -> Timestamptz,
     }
 }
 
@@ -83,20 +134,43 @@ diesel::table! {
         finished_at -> Nullable<Timestamptz>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        ejclient_id -> Nullable<Uuid>,
+        required_tags -> Array<Text>,
+        board_configs -> Array<Text>,
     }
 }
 
 diesel::table! {
-    ejjoblog (id) {
+    ejjobartifact (id) {
         id -> Uuid,
         ejjob_id -> Uuid,
         ejboard_config_id -> Uuid,
-        log -> Varchar,
+        name -> Varchar,
+        size -> Int8,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
     }
 }
 
+diesel::table! {
+    ejjobconfig (ejjob_id, ejconfig_id) {
+        ejjob_id -> Uuid,
+        ejconfig_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    ejjoblogchunk (id) {
+        id -> Uuid,
+        ejjob_id -> Uuid,
+        ejboard_config_id -> Uuid,
+        sequence -> Int4,
+        content -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     ejjobresult (ejjob_id, ejboard_config_id) {
         ejjob_id -> Uuid,
@@ -121,6 +195,42 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    ejrefreshtoken (id) {
+        id -> Uuid,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        ejclient_id -> Nullable<Uuid>,
+        ejbuilder_id -> Nullable<Uuid>,
+        expires_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        ejsession_id -> Nullable<Uuid>,
+        rotated_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    ejrevokedtoken (jti) {
+        jti -> Uuid,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    ejsession (id) {
+        id -> Uuid,
+        ejclient_id -> Uuid,
+        expires_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     ejtag (id) {
         id -> Uuid,
@@ -137,34 +247,75 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    role (id) {
+        id -> Varchar,
+    }
+}
+
+diesel::table! {
+    role_permission (role_id, permission_id) {
+        role_id -> Varchar,
+        permission_id -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::joinable!(client_permission -> ejclient (ejclient_id));
 diesel::joinable!(client_permission -> permission (permission_id));
+diesel::joinable!(client_role -> ejclient (ejclient_id));
+diesel::joinable!(client_role -> role (role_id));
+diesel::joinable!(ejapikey -> ejclient (ejclient_id));
 diesel::joinable!(ejboard -> ejconfig (ejconfig_id));
 diesel::joinable!(ejboard_config -> ejboard (ejboard_id));
 diesel::joinable!(ejboard_config_tag -> ejboard_config (ejboard_config_id));
 diesel::joinable!(ejboard_config_tag -> ejtag (ejtag_id));
 diesel::joinable!(ejbuilder -> ejclient (ejclient_id));
+diesel::joinable!(ejbuilderconnection -> ejbuilder (ejbuilder_id));
 diesel::joinable!(ejconfig -> ejbuilder (ejbuilder_id));
+diesel::joinable!(ejjob -> ejclient (ejclient_id));
 diesel::joinable!(ejjob -> ejjobstatus (status));
 diesel::joinable!(ejjob -> ejjobtype (job_type));
-diesel::joinable!(ejjoblog -> ejboard_config (ejboard_config_id));
-diesel::joinable!(ejjoblog -> ejjob (ejjob_id));
+diesel::joinable!(ejjobartifact -> ejboard_config (ejboard_config_id));
+diesel::joinable!(ejjobartifact -> ejjob (ejjob_id));
+diesel::joinable!(ejjobconfig -> ejconfig (ejconfig_id));
+diesel::joinable!(ejjobconfig -> ejjob (ejjob_id));
+diesel::joinable!(ejjoblogchunk -> ejboard_config (ejboard_config_id));
+diesel::joinable!(ejjoblogchunk -> ejjob (ejjob_id));
 diesel::joinable!(ejjobresult -> ejboard_config (ejboard_config_id));
 diesel::joinable!(ejjobresult -> ejjob (ejjob_id));
+diesel::joinable!(ejrefreshtoken -> ejbuilder (ejbuilder_id));
+diesel::joinable!(ejrefreshtoken -> ejclient (ejclient_id));
+diesel::joinable!(ejrefreshtoken -> ejsession (ejsession_id));
+diesel::joinable!(ejsession -> ejclient (ejclient_id));
+diesel::joinable!(role_permission -> permission (permission_id));
+diesel::joinable!(role_permission -> role (role_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     client_permission,
+    client_role,
+    ejapikey,
+    ejauditlog,
     ejboard,
     ejboard_config,
     ejboard_config_tag,
     ejbuilder,
+    ejbuilderconnection,
     ejclient,
     ejconfig,
     ejjob,
-    ejjoblog,
+    ejjobartifact,
+    ejjobconfig,
+    ejjoblogchunk,
     ejjobresult,
     ejjobstatus,
     ejjobtype,
+    ejrefreshtoken,
+    ejrevokedtoken,
+    ejsession,
     ejtag,
     permission,
+    role,
+    role_permission,
 );
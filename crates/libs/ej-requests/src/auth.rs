@@ -0,0 +1,119 @@
+//! Bearer-token authentication.
+//!
+//! An [`ApiClient`](crate::ApiClient) given a token sends it in the
+//! `Authorization: Bearer` header of every request that doesn't set one
+//! itself. Clients built from one another, like with
+//! [`ApiClient::with_timeout`](crate::ApiClient::with_timeout), share their
+//! token. When the server answers `401 Unauthorized`, the refresh hook of the
+//! client, if any, is asked for a new token and the request is sent again
+//! once with it.
+//!
+//! Refreshes are single-flight: requests rejected while a refresh is running
+//! wait for it and use its token instead of refreshing again, as servers
+//! rotating refresh tokens take a second use of one as a stolen token.
+
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, RwLock},
+};
+
+use reqwest::{
+    Request,
+    header::{self, HeaderValue},
+};
+use tokio::sync::Mutex;
+
+/// Prefix of the `Authorization` header value.
+pub const BEARER_PREFIX: &str = "Bearer ";
+
+/// Future of a token refresh, resolving to the new token if there's one.
+type TokenRefreshFuture = Pin<Box<dyn Future<Output = Option<String>> + Send>>;
+
+/// Token shared by the clients built from one another.
+#[derive(Clone, Default)]
+pub(crate) struct SharedToken(Arc<RwLock<Option<String>>>);
+
+impl SharedToken {
+    pub fn get(&self) -> Option<String> {
+        self.0.read().unwrap_or_else(|err| err.into_inner()).clone()
+    }
+
+    pub fn set(&self, token: Option<String>) {
+        *self.0.write().unwrap_or_else(|err| err.into_inner()) = token;
+    }
+
+    /// The `Authorization` header value of the token, if it's set and valid.
+    pub fn header_value(&self) -> Option<HeaderValue> {
+        let mut value = HeaderValue::from_str(&format!("{BEARER_PREFIX}{}", self.get()?)).ok()?;
+        value.set_sensitive(true);
+        Some(value)
+    }
+
+    /// Adds the token to `request` unless it already has an `Authorization` header.
+    pub fn authorize(&self, mut request: Request) -> Request {
+        if !request.headers().contains_key(header::AUTHORIZATION)
+            && let Some(value) = self.header_value()
+        {
+            request.headers_mut().insert(header::AUTHORIZATION, value);
+        }
+        request
+    }
+}
+
+impl fmt::Debug for SharedToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get() {
+            Some(_) => f.write_str("Some(<redacted>)"),
+            None => f.write_str("None"),
+        }
+    }
+}
+
+/// Hook asked for a new token when the server rejects the current one.
+#[derive(Clone)]
+pub(crate) struct TokenRefresh {
+    refresh: Arc<dyn Fn() -> TokenRefreshFuture + Send + Sync>,
+    /// Held while the hook runs, shared by the clients built from one another.
+    in_flight: Arc<Mutex<()>>,
+}
+
+impl TokenRefresh {
+    pub fn new<F, Fut>(refresh: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<String>> + Send + 'static,
+    {
+        Self {
+            refresh: Arc::new(move || Box::pin(refresh())),
+            in_flight: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Replaces `token` with a new one from the hook, unless it changed since
+    /// `rejected` was sent.
+    ///
+    /// # Returns
+    ///
+    /// Whether `token` is newer than `rejected`, `false` if the hook gave none.
+    pub async fn renew(&self, token: &SharedToken, rejected: Option<&str>) -> bool {
+        let _in_flight = self.in_flight.lock().await;
+        if token.get().as_deref() != rejected {
+            return true;
+        }
+        match (self.refresh)().await {
+            Some(renewed) => {
+                token.set(Some(renewed));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl fmt::Debug for TokenRefresh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TokenRefresh")
+    }
+}
@@ -0,0 +1,208 @@
+//! Configuring [`ApiClient`]s.
+
+use std::time::Duration;
+
+use reqwest::{Certificate, Identity, Proxy, header};
+
+use crate::{ApiClient, RetryPolicy, auth::SharedToken};
+
+/// Default time to wait for a connection to be established.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default time to wait for the server to send more of a response.
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Builder of [`ApiClient`]s, see [`ApiClient::builder`].
+///
+/// Connections time out after [`DEFAULT_CONNECT_TIMEOUT`] and reads after
+/// [`DEFAULT_READ_TIMEOUT`] unless told otherwise, so requests don't hang
+/// while the server is down. Requests have no overall timeout by default,
+/// large uploads may take a while.
+///
+/// Proxies are taken from the `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`
+/// environment variables unless some are set with [`ApiClientBuilder::proxy`]
+/// or [`ApiClientBuilder::no_proxy`] is called. Certificates signed by a
+/// private CA are trusted once it's added with
+/// [`ApiClientBuilder::add_root_certificate`].
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_requests::{ApiClient, RetryPolicy};
+/// use std::time::Duration;
+///
+/// let client = ApiClient::builder("https://api.example.com")
+///     .connect_timeout(Duration::from_secs(5))
+///     .read_timeout(Duration::from_secs(30))
+///     .timeout(Duration::from_secs(120))
+///     .retry(RetryPolicy::never())
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ApiClientBuilder {
+    url: String,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    timeout: Option<Duration>,
+    retry: RetryPolicy,
+    proxies: Vec<Proxy>,
+    no_proxy: bool,
+    root_certificates: Vec<Certificate>,
+    identity: Option<Identity>,
+    log_bodies: bool,
+}
+
+impl ApiClientBuilder {
+    pub(crate) fn new(url: String) -> Self {
+        Self {
+            url,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            timeout: None,
+            retry: RetryPolicy::default(),
+            proxies: Vec::new(),
+            no_proxy: false,
+            root_certificates: Vec::new(),
+            identity: None,
+            log_bodies: false,
+        }
+    }
+
+    /// Sets how long to wait for a connection to be established.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets how long to wait for the server to send more of a response.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a whole request may take, from connecting to reading the
+    /// end of the response.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets when and how often failed requests are retried.
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Sends requests through `proxy`, replacing the proxies of the environment.
+    ///
+    /// Can be called several times, the first proxy intercepting a request
+    /// being used.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_requests::{ApiClient, Proxy};
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = ApiClient::builder("https://api.example.com")
+    ///     .proxy(Proxy::all("http://proxy.lab:3128")?)
+    ///     .try_build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Connects directly to the server, ignoring the proxies of the environment.
+    pub fn no_proxy(mut self) -> Self {
+        self.no_proxy = true;
+        self
+    }
+
+    /// Trusts the certificates signed by `certificate`, on top of the system ones.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use ej_requests::{ApiClient, Certificate};
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let ca = Certificate::from_pem(&std::fs::read("/etc/ej/lab-ca.pem")?)?;
+    /// let client = ApiClient::builder("https://ejd.lab")
+    ///     .add_root_certificate(ca)
+    ///     .try_build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /// Presents `identity` to servers asking for a client certificate.
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Sets whether request and response bodies are logged at debug level.
+    ///
+    /// Off by default, bodies may hold credentials. The `secret`, `token`,
+    /// `refresh_token` and `access_token` fields of JSON bodies are redacted,
+    /// anything else, like other credentials or non-JSON bodies, is logged as
+    /// it is.
+    pub fn log_bodies(mut self, log_bodies: bool) -> Self {
+        self.log_bodies = log_bodies;
+        self
+    }
+
+    /// Builds the client.
+    ///
+    /// # Panics
+    ///
+    /// If the client can't be built, see [`ApiClientBuilder::try_build`].
+    pub fn build(self) -> ApiClient {
+        self.try_build().expect("Failed to build reqwest Client")
+    }
+
+    /// Builds the client, failing if the TLS backend can't be initialized or
+    /// doesn't accept the certificates or the identity.
+    pub fn try_build(self) -> reqwest::Result<ApiClient> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            "content-type",
+            header::HeaderValue::from_static("application/json"),
+        );
+        let mut client = reqwest::ClientBuilder::new()
+            .default_headers(headers)
+            .cookie_store(true)
+            .connect_timeout(self.connect_timeout)
+            .read_timeout(self.read_timeout);
+        if let Some(timeout) = self.timeout {
+            client = client.timeout(timeout);
+        }
+        if self.no_proxy {
+            client = client.no_proxy();
+        }
+        for proxy in self.proxies {
+            client = client.proxy(proxy);
+        }
+        for certificate in self.root_certificates {
+            client = client.add_root_certificate(certificate);
+        }
+        if let Some(identity) = self.identity {
+            client = client.identity(identity);
+        }
+        Ok(ApiClient {
+            url: self.url,
+            client: client.build()?,
+            retry: self.retry,
+            request_timeout: None,
+            token: SharedToken::default(),
+            token_refresh: None,
+            log_bodies: self.log_bodies,
+        })
+    }
+}
@@ -1,7 +1,16 @@
 //! HTTP client library for making API requests.
 //!
 //! This library provides a simplified wrapper around reqwest for making
-//! HTTP requests with JSON serialization/deserialization support.
+//! HTTP requests with JSON serialization/deserialization support. Requests
+//! failing with transient errors are retried, see [`RetryPolicy`], and
+//! authenticated with a bearer token if the client has one, see [`auth`].
+//! Large files are streamed to and from disk, see [`transfer`].
+//!
+//! Every request is traced in a `request` span at debug level, with its
+//! method, endpoint, status and latency, along with its retries. Bodies are
+//! logged too if the client is told to, see [`ApiClientBuilder::log_bodies`],
+//! with the secrets and tokens of JSON bodies redacted.
+//! Run with `RUST_LOG=ej_requests=debug` to see them.
 //!
 //! # Examples
 //!
@@ -15,20 +24,83 @@
 //! # }
 //! ```
 
-use std::{borrow::Borrow, error::Error, str::FromStr};
+pub mod auth;
+pub mod builder;
+pub mod retry;
+pub mod transfer;
+
+use std::{
+    borrow::Borrow,
+    error::Error,
+    future::Future,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use reqwest::{Response, StatusCode, Url, header};
+use auth::{SharedToken, TokenRefresh};
+use reqwest::{Request, RequestBuilder, Response, StatusCode, Url};
 use serde::de::DeserializeOwned;
+use tokio::{fs::File, io::AsyncWriteExt};
+use tracing::{Instrument, Span, debug, debug_span, field};
+use transfer::{MultipartUpload, Progress};
+
+pub use builder::ApiClientBuilder;
+pub use reqwest::{Certificate, Identity, Proxy};
+pub use retry::RetryPolicy;
+
+/// JSON fields whose values aren't logged along with the bodies holding them.
+const REDACTED_FIELDS: [&str; 4] = ["secret", "token", "refresh_token", "access_token"];
+
+/// `body` as logged, with the values of [`REDACTED_FIELDS`] replaced at any
+/// depth if it's JSON, as it is otherwise.
+fn redacted_body(body: &str) -> String {
+    let Ok(mut value) = serde_json::from_str::<serde_json::Value>(body) else {
+        return body.to_owned();
+    };
+    redact(&mut value);
+    value.to_string()
+}
+
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (name, field) in fields {
+                if REDACTED_FIELDS.contains(&name.as_str()) {
+                    *field = serde_json::Value::from("<redacted>");
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
 
 /// HTTP client for making API requests with JSON support.
+#[derive(Debug, Clone)]
 pub struct ApiClient {
     url: String,
     pub client: reqwest::Client,
+    retry: RetryPolicy,
+    /// Timeout of every request, overriding the one of the client.
+    request_timeout: Option<Duration>,
+    /// Bearer token sent with every request.
+    token: SharedToken,
+    /// Asked for a new token when the server rejects the current one.
+    token_refresh: Option<TokenRefresh>,
+    /// Whether request and response bodies are logged.
+    log_bodies: bool,
 }
 
 impl ApiClient {
     /// Creates a new API client with the given base URL.
     ///
+    /// Uses the default timeouts and retry policy, see [`ApiClient::builder`]
+    /// to change them.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -37,19 +109,220 @@ impl ApiClient {
     /// let client = ApiClient::new("https://api.example.com");
     /// ```
     pub fn new(url: impl Into<String>) -> Self {
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            "content-type",
-            header::HeaderValue::from_static("application/json"),
-        );
-        let client = reqwest::ClientBuilder::new()
-            .default_headers(headers)
-            .cookie_store(true)
-            .build()
-            .expect("Failed to build reqwest Client");
+        Self::builder(url).build()
+    }
+
+    /// Starts configuring an API client with the given base URL.
+    pub fn builder(url: impl Into<String>) -> ApiClientBuilder {
+        ApiClientBuilder::new(url.into())
+    }
+
+    /// A copy of this client whose requests time out after `timeout`.
+    ///
+    /// Overrides the timeout of the whole request for calls known to be
+    /// faster or slower than the others, the copy sharing the connections
+    /// and cookies of this client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_requests::ApiClient;
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = ApiClient::new("https://api.example.com");
+    /// let response = client
+    ///     .with_timeout(Duration::from_secs(300))
+    ///     .post("v1/builder/run_result", "{}")
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
         Self {
-            url: url.into(),
-            client,
+            request_timeout: Some(timeout),
+            ..self.clone()
+        }
+    }
+
+    /// Sets when and how often failed requests are retried.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_requests::{ApiClient, RetryPolicy};
+    ///
+    /// let client = ApiClient::new("https://api.example.com").with_retry(RetryPolicy::never());
+    /// ```
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Authenticates every request with a bearer `token`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_requests::ApiClient;
+    ///
+    /// let client = ApiClient::new("https://api.example.com").with_token("my-access-token");
+    /// assert_eq!(client.authorization().as_deref(), Some("Bearer my-access-token"));
+    /// ```
+    pub fn with_token(self, token: impl Into<String>) -> Self {
+        self.set_token(token);
+        self
+    }
+
+    /// Replaces the bearer token, for this client and the ones sharing it.
+    pub fn set_token(&self, token: impl Into<String>) {
+        self.token.set(Some(token.into()));
+    }
+
+    /// Stops authenticating requests.
+    pub fn clear_token(&self) {
+        self.token.set(None);
+    }
+
+    /// The `Authorization` header value of the token, `Bearer <token>`, for
+    /// connections this client doesn't make, like WebSockets.
+    pub fn authorization(&self) -> Option<String> {
+        self.token
+            .get()
+            .map(|token| format!("{}{token}", auth::BEARER_PREFIX))
+    }
+
+    /// Asks `refresh` for a new token when a request is answered with
+    /// `401 Unauthorized`, sending it again once if it gives one.
+    ///
+    /// `refresh` is never run twice at once, see [`auth`]. Requests it makes
+    /// must go through a client without the hook, or they'd wait for it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_requests::ApiClient;
+    ///
+    /// let login = ApiClient::new("https://api.example.com");
+    /// let client = ApiClient::new("https://api.example.com").with_token_refresh(move || {
+    ///     let login = login.clone();
+    ///     async move {
+    ///         let tokens: serde_json::Value = login.post_and_deserialize("v1/login", "{}").await.ok()?;
+    ///         Some(tokens["access_token"].as_str()?.to_string())
+    ///     }
+    /// });
+    /// ```
+    pub fn with_token_refresh<F, Fut>(mut self, refresh: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<String>> + Send + 'static,
+    {
+        self.token_refresh = Some(TokenRefresh::new(refresh));
+        self
+    }
+
+    /// Asks the refresh hook for a new token now, like after a
+    /// `401 Unauthorized`.
+    ///
+    /// # Returns
+    ///
+    /// Whether the client has a new token, `false` if it has no refresh hook
+    /// or the hook gave none.
+    pub async fn renew_token(&self) -> bool {
+        let Some(refresh) = &self.token_refresh else {
+            return false;
+        };
+        let current = self.token.get();
+        refresh.renew(&self.token, current.as_deref()).await
+    }
+
+    /// Sends a request, retrying it as the retry policy says.
+    ///
+    /// Requests with a streamed body can't be sent twice and are sent once.
+    pub async fn send(&self, mut request: RequestBuilder) -> reqwest::Result<Response> {
+        if let Some(timeout) = self.request_timeout {
+            request = request.timeout(timeout);
+        }
+        let request = request.build()?;
+        let span = debug_span!(
+            "request",
+            method = %request.method(),
+            endpoint = request.url().path(),
+            status = field::Empty,
+            latency = field::Empty,
+        );
+        async {
+            if self.log_bodies
+                && let Some(body) = request.body().and_then(reqwest::Body::as_bytes)
+            {
+                let body = redacted_body(&String::from_utf8_lossy(body));
+                debug!(body, "Sending request body");
+            }
+            let started = Instant::now();
+            let result = self.send_authenticated(request).await;
+            let span = Span::current();
+            span.record("latency", field::debug(started.elapsed()));
+            match &result {
+                Ok(response) => {
+                    span.record("status", response.status().as_u16());
+                    debug!("Request finished");
+                }
+                Err(err) => debug!(%err, "Request failed"),
+            }
+            result
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Sends a request, asking for a new token and sending it again if the
+    /// server rejects the current one.
+    async fn send_authenticated(&self, request: Request) -> reqwest::Result<Response> {
+        let after_refresh = self
+            .token_refresh
+            .as_ref()
+            .and_then(|_| request.try_clone());
+        let rejected = self.token.get();
+        let response = self.execute(request).await?;
+        if response.status() == StatusCode::UNAUTHORIZED
+            && let Some(request) = after_refresh
+            && let Some(refresh) = &self.token_refresh
+            && refresh.renew(&self.token, rejected.as_deref()).await
+        {
+            debug!("Token refreshed, sending the request again");
+            return self.execute(request).await;
+        }
+        Ok(response)
+    }
+
+    /// Sends an authenticated request, retrying it as the retry policy says.
+    async fn execute(&self, request: Request) -> reqwest::Result<Response> {
+        let method = request.method().clone();
+        let mut attempt = 0;
+        loop {
+            let Some(retry_request) = request.try_clone() else {
+                return self.client.execute(self.token.authorize(request)).await;
+            };
+            let retry_request = self.token.authorize(retry_request);
+            attempt += 1;
+            let can_retry = attempt <= self.retry.max_retries;
+            let response = match self.client.execute(retry_request).await {
+                Ok(response)
+                    if can_retry && self.retry.should_retry_response(&method, &response) =>
+                {
+                    Some(response)
+                }
+                Err(err) if can_retry && self.retry.should_retry_error(&method, &err) => None,
+                result => return result,
+            };
+            let delay = self.retry.delay(attempt, response.as_ref());
+            debug!(
+                attempt,
+                status = response.as_ref().map(|response| response.status().as_u16()),
+                ?delay,
+                "Retrying request"
+            );
+            tokio::time::sleep(delay).await;
         }
     }
 
@@ -58,13 +331,38 @@ impl ApiClient {
         format!("{}/{endpoint}", self.url)
     }
 
+    /// Sends a request and deserializes the response.
+    async fn send_and_deserialize<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<T, Box<dyn Error>> {
+        let response = self.read_text(self.send(request).await?).await?;
+        Ok(serde_json::from_str(&response)?)
+    }
+
+    /// Reads the body of `response`, logging it if bodies are logged.
+    async fn read_text(&self, response: Response) -> reqwest::Result<String> {
+        let endpoint = response.url().path().to_owned();
+        let text = response.text().await?;
+        if self.log_bodies {
+            debug!(
+                endpoint,
+                body = redacted_body(&text),
+                "Received response body"
+            );
+        }
+        Ok(text)
+    }
+
     /// Makes a GET request to the specified URL and deserializes the response.
-    async fn get_url<T: DeserializeOwned>(url: Url) -> T {
+    async fn get_url<T: DeserializeOwned>(&self, url: Url) -> T {
+        let response = self
+            .send(self.client.get(url))
+            .await
+            .expect("Failed to send http request");
         serde_json::from_str(
-            &reqwest::get(url)
-                .await
-                .expect("Failed to send http request")
-                .text()
+            &self
+                .read_text(response)
                 .await
                 .expect("Failed to get response text"),
         )
@@ -74,7 +372,7 @@ impl ApiClient {
     /// Makes a GET request to the specified endpoint.
     pub async fn get<T: DeserializeOwned>(&self, endpoint: &str) -> T {
         let url = reqwest::Url::from_str(&self.path(&endpoint)).unwrap();
-        Self::get_url(url).await
+        self.get_url(url).await
     }
 
     /// Makes a GET request with query parameters.
@@ -88,7 +386,7 @@ impl ApiClient {
     {
         let url = reqwest::Url::parse_with_params(&self.path(&endpoint), params)
             .expect("Couldn't create get request");
-        Self::get_url(url).await
+        self.get_url(url).await
     }
 
     /// Makes a POST request with the given body.
@@ -98,13 +396,12 @@ impl ApiClient {
         body: T,
     ) -> Result<Response, Box<dyn Error>> {
         let url = reqwest::Url::from_str(&self.path(endpoint)).unwrap();
-        Ok(self
+        let request = self
             .client
             .post(url)
             .header("content-type", "application/json")
-            .body(body)
-            .send()
-            .await?)
+            .body(body);
+        Ok(self.send(request).await?)
     }
 
     /// Makes a POST request and deserializes the response.
@@ -115,17 +412,12 @@ impl ApiClient {
     ) -> Result<U, Box<dyn Error>> {
         let url = reqwest::Url::from_str(&self.path(endpoint)).unwrap();
 
-        let response = self
+        let request = self
             .client
             .post(url)
             .header("content-type", "application/json")
-            .body(body)
-            .send()
-            .await?
-            .text()
-            .await?;
-
-        Ok(serde_json::from_str(&response)?)
+            .body(body);
+        self.send_and_deserialize(request).await
     }
 
     /// Makes a POST request without a body and deserializes the response.
@@ -135,10 +427,126 @@ impl ApiClient {
     ) -> Result<T, Box<dyn Error>> {
         let url = reqwest::Url::from_str(&self.path(endpoint)).unwrap();
 
-        let response = self.client.post(url).send().await?.text().await?;
+        self.send_and_deserialize(self.client.post(url)).await
+    }
 
-        println!("Response {response}");
-        Ok(serde_json::from_str(&response)?)
+    /// Makes a PUT request with the given body and deserializes the response.
+    pub async fn put<T: Into<reqwest::Body>, U: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        body: T,
+    ) -> Result<U, Box<dyn Error>> {
+        let url = reqwest::Url::from_str(&self.path(endpoint)).unwrap();
+        let request = self
+            .client
+            .put(url)
+            .header("content-type", "application/json")
+            .body(body);
+        self.send_and_deserialize(request).await
+    }
+
+    /// Makes a PATCH request with the given body and deserializes the response.
+    ///
+    /// PATCH requests aren't idempotent, so they're retried on connection
+    /// errors only unless the retry policy says otherwise.
+    pub async fn patch<T: Into<reqwest::Body>, U: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        body: T,
+    ) -> Result<U, Box<dyn Error>> {
+        let url = reqwest::Url::from_str(&self.path(endpoint)).unwrap();
+        let request = self
+            .client
+            .patch(url)
+            .header("content-type", "application/json")
+            .body(body);
+        self.send_and_deserialize(request).await
+    }
+
+    /// Makes a `multipart/form-data` POST request, streaming the files of
+    /// `upload` from disk.
+    ///
+    /// `progress` is told how many bytes of the files were sent so far.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_requests::{ApiClient, transfer::MultipartUpload};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = ApiClient::new("https://api.example.com");
+    /// let upload = MultipartUpload::new().file("artifact", "target/firmware.bin");
+    /// let response = client
+    ///     .post_multipart("v1/artifacts", upload, |progress| {
+    ///         println!("Sent {} of {:?} bytes", progress.transferred, progress.total);
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn post_multipart(
+        &self,
+        endpoint: &str,
+        upload: MultipartUpload,
+        progress: impl Fn(Progress) + Send + Sync + 'static,
+    ) -> Result<Response, Box<dyn Error>> {
+        let url = reqwest::Url::from_str(&self.path(endpoint)).unwrap();
+        let form = upload.into_form(Arc::new(progress)).await?;
+        Ok(self.send(self.client.post(url).multipart(form)).await?)
+    }
+
+    /// Makes a GET request and streams the response body to the file at `path`.
+    ///
+    /// The body is written next to `path` first and moved there once complete,
+    /// so an interrupted download never leaves a truncated file behind.
+    /// `progress` is told how many bytes were received so far. Returns the size
+    /// of the file.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_requests::ApiClient;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = ApiClient::new("https://api.example.com");
+    /// let size = client
+    ///     .download_to_file("v1/toolchains/arm-none-eabi.tar.xz", "toolchain.tar.xz", |_| {})
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn download_to_file(
+        &self,
+        endpoint: &str,
+        path: impl AsRef<Path>,
+        progress: impl Fn(Progress),
+    ) -> Result<u64, Box<dyn Error>> {
+        let path = path.as_ref();
+        let url = reqwest::Url::from_str(&self.path(endpoint)).unwrap();
+        let mut response = self.send(self.client.get(url)).await?.error_for_status()?;
+        let total = response.content_length();
+
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        let mut file = File::create(&partial).await?;
+        let mut transferred = 0;
+        let result: Result<(), Box<dyn Error>> = async {
+            while let Some(chunk) = response.chunk().await? {
+                file.write_all(&chunk).await?;
+                transferred += chunk.len() as u64;
+                progress(Progress { transferred, total });
+            }
+            file.sync_all().await?;
+            Ok(())
+        }
+        .await;
+        drop(file);
+        if let Err(err) = result {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(err);
+        }
+        tokio::fs::rename(&partial, path).await?;
+        Ok(transferred)
     }
 
     /// Makes a DELETE request with query parameters.
@@ -156,12 +564,347 @@ impl ApiClient {
     {
         let url = reqwest::Url::parse_with_params(&self.path(&endpoint), params)
             .expect("Couldn't create get request");
-        client
+        self.send(
+            client
+                .delete(url)
+                .header("content-type", "application/json"),
+        )
+        .await
+        .expect("Failed to send patch request")
+        .status()
+    }
+
+    /// Makes a DELETE request with query parameters and deserializes the response.
+    pub async fn delete_and_deserialize<T, I, K, V>(
+        &self,
+        endpoint: &str,
+        params: I,
+    ) -> Result<T, Box<dyn Error>>
+    where
+        T: DeserializeOwned,
+        I: IntoIterator,
+        I::Item: Borrow<(K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let url = reqwest::Url::parse_with_params(&self.path(endpoint), params)?;
+        let request = self
+            .client
             .delete(url)
-            .header("content-type", "application/json")
-            .send()
+            .header("content-type", "application/json");
+        self.send_and_deserialize(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Reads a request up to the end of its body, lowercased.
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        loop {
+            let read = stream.read(&mut buffer).await.unwrap_or(0);
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).to_lowercase();
+            let complete = text.find("\r\n\r\n").is_some_and(|end| {
+                let length = text
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .and_then(|length| length.trim().parse().ok())
+                    .unwrap_or(0);
+                request.len() >= end + 4 + length
+            });
+            if read == 0 || complete {
+                return text;
+            }
+        }
+    }
+
+    /// Serves `responses` in order, one per connection, keeping the requests.
+    async fn serve(responses: Vec<&'static str>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let request = read_request(&mut stream).await;
+                received.lock().unwrap().push(request);
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (url, requests)
+    }
+
+    const UNAVAILABLE: &str = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\n\
+                               Connection: close\r\nContent-Length: 0\r\n\r\n";
+    const UNAUTHORIZED: &str = "HTTP/1.1 401 Unauthorized\r\nConnection: close\r\n\
+                                Content-Length: 0\r\n\r\n";
+    const OK: &str = "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 11\r\n\r\n\
+                      {\"ok\":true}";
+
+    #[tokio::test]
+    async fn idempotent_requests_are_retried() {
+        let (url, requests) = serve(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let client = ApiClient::new(url);
+        let value: serde_json::Value = client.get("status").await;
+        assert_eq!(value, serde_json::json!({ "ok": true }));
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn non_idempotent_requests_are_retried_on_connection_errors_only() {
+        let (url, requests) = serve(vec![UNAVAILABLE, OK]).await;
+        let client = ApiClient::new(url);
+        let response = client.post("results", "{}").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        // Nothing listens on the port anymore once the listener is dropped
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let client = ApiClient::new(url).with_retry(RetryPolicy {
+            max_retries: 2,
+            initial_delay: Duration::from_millis(10),
+            ..Default::default()
+        });
+        let started = std::time::Instant::now();
+        assert!(client.post("results", "{}").await.is_err());
+        assert!(started.elapsed() >= Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn requests_time_out() {
+        // Accepts connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let client = ApiClient::builder(url)
+            .read_timeout(Duration::from_millis(200))
+            .retry(RetryPolicy::never())
+            .build();
+        let err = client.post("results", "{}").await.unwrap_err();
+        let is_timeout = |err: &(dyn Error + 'static)| {
+            err.downcast_ref::<reqwest::Error>()
+                .is_some_and(reqwest::Error::is_timeout)
+        };
+        assert!(is_timeout(err.as_ref()), "{err}");
+
+        let started = std::time::Instant::now();
+        let err = client
+            .with_timeout(Duration::from_millis(50))
+            .post("results", "{}")
             .await
-            .expect("Failed to send patch request")
-            .status()
+            .unwrap_err();
+        assert!(is_timeout(err.as_ref()), "{err}");
+        assert!(started.elapsed() < Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn tokens_are_sent_and_refreshed() {
+        let (url, requests) = serve(vec![OK, UNAUTHORIZED, OK]).await;
+        let client = ApiClient::new(url)
+            .with_token("expired")
+            .with_token_refresh(|| async { Some(String::from("renewed")) });
+        client.post("results", "{}").await.unwrap();
+        let response = client
+            .with_timeout(Duration::from_secs(5))
+            .post("results", "{}")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("authorization: bearer expired"));
+        assert!(requests[1].contains("authorization: bearer expired"));
+        assert!(requests[2].contains("authorization: bearer renewed"));
+        assert_eq!(client.authorization().as_deref(), Some("Bearer renewed"));
+    }
+
+    #[tokio::test]
+    async fn concurrent_rejections_refresh_once() {
+        let (url, requests) = serve(vec![UNAUTHORIZED, UNAUTHORIZED, OK, OK]).await;
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let counted = refreshes.clone();
+        let client = ApiClient::new(url)
+            .with_token("expired")
+            .with_token_refresh(move || {
+                let counted = counted.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    let refresh = counted.fetch_add(1, Ordering::SeqCst) + 1;
+                    Some(format!("renewed-{refresh}"))
+                }
+            });
+        let other = client.with_timeout(Duration::from_secs(5));
+        let (first, second) = tokio::join!(client.post("results", "{}"), other.post("logs", "{}"));
+        assert_eq!(first.unwrap().status(), StatusCode::OK);
+        assert_eq!(second.unwrap().status(), StatusCode::OK);
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+
+        {
+            let requests = requests.lock().unwrap();
+            assert!(requests[..2].iter().all(|r| r.contains("bearer expired")));
+            assert!(requests[2..].iter().all(|r| r.contains("bearer renewed-1")));
+        }
+
+        assert!(client.renew_token().await);
+        assert_eq!(refreshes.load(Ordering::SeqCst), 2);
+        assert_eq!(client.authorization().as_deref(), Some("Bearer renewed-2"));
+        assert!(!ApiClient::new("http://localhost").renew_token().await);
+    }
+
+    #[test]
+    fn logged_bodies_are_redacted() {
+        let body = serde_json::json!({
+            "id": "builder",
+            "secret": "hunter2",
+            "tokens": [{ "access_token": "a", "refresh_token": "r", "expires_in": 900 }],
+            "nested": { "token": "t" },
+        });
+        let redacted: serde_json::Value =
+            serde_json::from_str(&redacted_body(&body.to_string())).unwrap();
+        assert_eq!(
+            redacted,
+            serde_json::json!({
+                "id": "builder",
+                "secret": "<redacted>",
+                "tokens": [{
+                    "access_token": "<redacted>",
+                    "refresh_token": "<redacted>",
+                    "expires_in": 900,
+                }],
+                "nested": { "token": "<redacted>" },
+            })
+        );
+        assert_eq!(redacted_body("plain text"), "plain text");
+    }
+
+    #[tokio::test]
+    async fn files_are_uploaded() {
+        let (url, requests) = serve(vec![OK]).await;
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, &[b'x'; 10000]).unwrap();
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let progress = reported.clone();
+        let upload = MultipartUpload::new()
+            .text("board", "rpi4")
+            .file("artifact", file.path());
+        let response = ApiClient::new(url)
+            .post_multipart("artifacts", upload, move |update| {
+                progress.lock().unwrap().push(update)
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = &requests.lock().unwrap()[0];
+        assert!(request.contains("content-type: multipart/form-data; boundary="));
+        assert!(request.contains("name=\"board\"\r\n\r\nrpi4"));
+        assert!(request.contains(&"x".repeat(10000)));
+        let reported = reported.lock().unwrap();
+        assert!(reported.len() > 1);
+        assert_eq!(
+            reported.last(),
+            Some(&Progress {
+                transferred: 10000,
+                total: Some(10000)
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn files_are_downloaded() {
+        const FILE: &str = "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 7\r\n\r\n\
+                            toolkit";
+        const NOT_FOUND: &str = "HTTP/1.1 404 Not Found\r\nConnection: close\r\n\
+                                 Content-Length: 0\r\n\r\n";
+        let (url, _) = serve(vec![FILE, NOT_FOUND]).await;
+        let client = ApiClient::new(url);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("toolchain");
+
+        let reported = Mutex::new(None);
+        let size = client
+            .download_to_file("toolchain", &path, |update| {
+                *reported.lock().unwrap() = Some(update)
+            })
+            .await
+            .unwrap();
+        assert_eq!(size, 7);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "toolkit");
+        assert_eq!(
+            reported.into_inner().unwrap(),
+            Some(Progress {
+                transferred: 7,
+                total: Some(7)
+            })
+        );
+
+        let missing = dir.path().join("missing");
+        assert!(
+            client
+                .download_to_file("missing", &missing, |_| {})
+                .await
+                .is_err()
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn put_patch_and_delete_are_deserialized() {
+        let (url, requests) = serve(vec![OK, OK, OK]).await;
+        let client = ApiClient::new(url);
+        let expected = serde_json::json!({ "ok": true });
+
+        let value: serde_json::Value = client.put("builders/1", "{}").await.unwrap();
+        assert_eq!(value, expected);
+        let value: serde_json::Value = client.patch("builders/1", "{}").await.unwrap();
+        assert_eq!(value, expected);
+        let value: serde_json::Value = client
+            .delete_and_deserialize("builders", [("id", "1")])
+            .await
+            .unwrap();
+        assert_eq!(value, expected);
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].starts_with("put /builders/1 "));
+        assert!(requests[1].starts_with("patch /builders/1 "));
+        assert!(requests[2].starts_with("delete /builders?id=1 "));
+    }
+
+    #[tokio::test]
+    async fn requests_go_through_the_proxy() {
+        let (proxy, requests) = serve(vec![OK]).await;
+        let client = ApiClient::builder("http://ejd.invalid")
+            .proxy(Proxy::http(proxy).unwrap())
+            .build();
+        let value: serde_json::Value = client.get("status").await;
+        assert_eq!(value, serde_json::json!({ "ok": true }));
+        assert!(requests.lock().unwrap()[0].starts_with("get http://ejd.invalid/status "));
     }
 }
@@ -0,0 +1,122 @@
+//! Retrying failed requests.
+//!
+//! EJD restarting or being briefly unreachable shouldn't fail a builder
+//! registration or a result upload. Requests failing with a transient error
+//! are sent again after an exponentially growing, randomized delay:
+//!
+//! - Requests that couldn't connect to the server are retried whatever their
+//!   method, the server never saw them.
+//! - Requests that timed out or got a `429`, `502`, `503` or `504` response
+//!   are only retried if their method is idempotent, unless the policy says
+//!   otherwise. The delay asked by a `Retry-After` header is honored, up to
+//!   the maximum delay of the policy.
+
+use std::time::{Duration, SystemTime};
+
+use rand::Rng;
+use reqwest::{Method, Response, StatusCode, header};
+
+/// When and how often failed requests are retried.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_requests::{ApiClient, RetryPolicy};
+/// use std::time::Duration;
+///
+/// let client = ApiClient::new("https://api.example.com").with_retry(RetryPolicy {
+///     max_retries: 10,
+///     max_delay: Duration::from_secs(60),
+///     ..Default::default()
+/// });
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a request is sent again at most.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each of the next ones.
+    pub initial_delay: Duration,
+    /// Longest delay between two attempts.
+    pub max_delay: Duration,
+    /// Whether requests that may have reached the server are retried even if
+    /// their method isn't idempotent, like `POST`.
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Five retries, waiting up to 0.5s, 1s, 2s, 4s then 8s.
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn never() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a request failing with `err` is retried.
+    pub(crate) fn should_retry_error(&self, method: &Method, err: &reqwest::Error) -> bool {
+        err.is_connect() || (err.is_timeout() && self.may_retry(method))
+    }
+
+    /// Whether a request answered with `response` is retried.
+    pub(crate) fn should_retry_response(&self, method: &Method, response: &Response) -> bool {
+        let transient = matches!(
+            response.status(),
+            StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT
+        );
+        transient && self.may_retry(method)
+    }
+
+    fn may_retry(&self, method: &Method) -> bool {
+        self.retry_non_idempotent || method.is_idempotent()
+    }
+
+    /// Delay before the retry following `attempt` failed ones, `response`
+    /// being the last response if any.
+    ///
+    /// Half of the backoff delay is random, so clients failing together
+    /// don't retry together.
+    pub(crate) fn delay(&self, attempt: u32, response: Option<&Response>) -> Duration {
+        if let Some(delay) = response.and_then(retry_after) {
+            return delay.min(self.max_delay);
+        }
+        let backoff = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let half = backoff / 2;
+        half + half.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+/// The delay asked by the `Retry-After` header of `response`, in seconds or as a date.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
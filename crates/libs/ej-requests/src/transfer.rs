@@ -0,0 +1,130 @@
+//! Uploading and downloading files.
+//!
+//! Artifacts and toolchains can be much larger than the JSON bodies of the
+//! other requests. [`ApiClient::post_multipart`](crate::ApiClient::post_multipart)
+//! streams files from disk into a `multipart/form-data` body and
+//! [`ApiClient::download_to_file`](crate::ApiClient::download_to_file) streams
+//! a response body to disk, neither of them holding a whole file in memory.
+//!
+//! Streamed uploads can't be sent twice, so they aren't retried.
+
+use std::{
+    io,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use futures_util::StreamExt;
+use reqwest::{
+    Body,
+    multipart::{Form, Part},
+};
+use tokio::fs::File;
+use tokio_util::io::ReaderStream;
+
+/// How much of a transfer is done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Bytes transferred so far.
+    pub transferred: u64,
+    /// Bytes to transfer in total, if known.
+    pub total: Option<u64>,
+}
+
+/// Callback told about the progress of a transfer.
+pub(crate) type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
+
+/// Fields of a `multipart/form-data` upload.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_requests::transfer::MultipartUpload;
+///
+/// let upload = MultipartUpload::new()
+///     .text("board", "rpi4")
+///     .file("artifact", "target/firmware.bin");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MultipartUpload {
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone)]
+enum Field {
+    Text { name: String, value: String },
+    File { name: String, path: PathBuf },
+}
+
+impl MultipartUpload {
+    /// Creates an upload without any field.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a text field.
+    pub fn text(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fields.push(Field::Text {
+            name: name.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Adds a file field, read from `path` while the request is sent.
+    pub fn file(mut self, name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.fields.push(Field::File {
+            name: name.into(),
+            path: path.into(),
+        });
+        self
+    }
+
+    /// Opens the files of the upload and builds the form streaming them.
+    ///
+    /// `progress` is told about the bytes of the files read so far, the text
+    /// fields not counting.
+    pub(crate) async fn into_form(self, progress: ProgressCallback) -> io::Result<Form> {
+        let mut files = Vec::new();
+        for field in &self.fields {
+            if let Field::File { path, .. } = field {
+                let file = File::open(path).await?;
+                let length = file.metadata().await?.len();
+                files.push((file, length));
+            }
+        }
+        let total = files.iter().map(|(_, length)| length).sum();
+
+        let transferred = Arc::new(AtomicU64::new(0));
+        let mut files = files.into_iter();
+        let mut form = Form::new();
+        for field in self.fields {
+            form = match field {
+                Field::Text { name, value } => form.text(name, value),
+                Field::File { name, path } => {
+                    let (file, length) = files.next().expect("Every file is opened");
+                    let transferred = transferred.clone();
+                    let progress = progress.clone();
+                    let stream = ReaderStream::new(file).inspect(move |chunk| {
+                        if let Ok(chunk) = chunk {
+                            let read = chunk.len() as u64;
+                            progress(Progress {
+                                transferred: transferred.fetch_add(read, Ordering::Relaxed) + read,
+                                total: Some(total),
+                            });
+                        }
+                    });
+                    let mut part = Part::stream_with_length(Body::wrap_stream(stream), length);
+                    if let Some(file_name) = path.file_name() {
+                        part = part.file_name(file_name.to_string_lossy().into_owned());
+                    }
+                    form.part(name, part)
+                }
+            };
+        }
+        Ok(form)
+    }
+}
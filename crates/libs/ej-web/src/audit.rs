@@ -0,0 +1,107 @@
+//! Audit log of security-relevant actions.
+//!
+//! Handlers record what they did with [`record_audit`]. Failing to record an
+//! entry is logged but never fails the request that triggered it.
+
+use ej_dispatcher_sdk::ejaudit::{EjAuditAction, EjAuditEntry, EjAuditQuery};
+use ej_models::{
+    audit::ejaudit_log::{EjAuditLog, EjAuditLogCreate, EjAuditLogFilter},
+    db::connection::DbConnection,
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+/// Number of entries returned by a query that doesn't set a limit.
+pub const DEFAULT_AUDIT_LIMIT: u32 = 100;
+/// Maximum number of entries a single query can return.
+pub const MAX_AUDIT_LIMIT: u32 = 1000;
+
+impl TryFrom<EjAuditLog> for W<EjAuditEntry> {
+    type Error = EjAuditLog;
+
+    /// Fails if the entry was recorded for an action this version doesn't know.
+    fn try_from(value: EjAuditLog) -> std::result::Result<Self, Self::Error> {
+        let Some(action) = EjAuditAction::from_name(&value.action) else {
+            return Err(value);
+        };
+        Ok(Self(EjAuditEntry {
+            id: value.id,
+            action,
+            actor_id: value.actor_id,
+            target: value.target,
+            created_at: value.created_at,
+        }))
+    }
+}
+
+/// Records an action in the audit log.
+///
+/// # Arguments
+/// * `action` - What was done
+/// * `actor_id` - The client or builder that did it, if known
+/// * `target` - What it was done to, if anything
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_web::audit::record_audit;
+/// use ej_dispatcher_sdk::ejaudit::EjAuditAction;
+/// # use ej_models::db::connection::DbConnection;
+/// # use uuid::Uuid;
+///
+/// # fn example(client_id: Uuid, job_id: Uuid, connection: &DbConnection) {
+/// record_audit(
+///     EjAuditAction::Dispatch,
+///     Some(client_id),
+///     Some(job_id.to_string()),
+///     connection,
+/// );
+/// # }
+/// ```
+pub fn record_audit(
+    action: EjAuditAction,
+    actor_id: Option<Uuid>,
+    target: Option<String>,
+    connection: &DbConnection,
+) {
+    let entry = EjAuditLogCreate {
+        action: String::from(action.as_str()),
+        actor_id,
+        target,
+    };
+    if let Err(err) = entry.save(connection) {
+        error!("Failed to record {action} in the audit log - {err}");
+    }
+}
+
+/// Queries the audit log.
+///
+/// # Returns
+///
+/// The matching entries, newest first. At most [`DEFAULT_AUDIT_LIMIT`]
+/// entries are returned unless the query sets a limit, which is capped to
+/// [`MAX_AUDIT_LIMIT`]. Entries recorded for actions this version doesn't
+/// know about are skipped.
+pub fn query_audit_log(
+    query: EjAuditQuery,
+    connection: &DbConnection,
+) -> Result<Vec<EjAuditEntry>> {
+    let filter = EjAuditLogFilter {
+        action: query.action.map(|action| String::from(action.as_str())),
+        actor_id: query.actor_id,
+        since: query.since,
+        until: query.until,
+        limit: query
+            .limit
+            .unwrap_or(DEFAULT_AUDIT_LIMIT)
+            .min(MAX_AUDIT_LIMIT)
+            .into(),
+    };
+    Ok(EjAuditLog::fetch_filtered(&filter, connection)?
+        .into_iter()
+        .filter_map(|entry| W::try_from(entry).ok())
+        .map(|entry| entry.0)
+        .collect())
+}
@@ -3,11 +3,10 @@
 use std::collections::HashSet;
 
 use crate::prelude::*;
-use chrono::{TimeDelta, Utc};
+use chrono::TimeDelta;
 use ej_auth::{
-    ISS,
     auth_body::AuthBody,
-    jwt::{jwt_decode, jwt_encode},
+    jwt::{Claims, JwtValidation, jwt_decode_with_validation, jwt_encode},
     secret_hash::is_secret_valid,
 };
 use ej_dispatcher_sdk::ejclient::{EjClientApi, EjClientLoginRequest};
@@ -18,29 +17,25 @@ use serde::{Deserialize, Serialize};
 use tracing::error;
 use uuid::Uuid;
 
-use crate::ctx::{CtxWho, ctx_client::CtxClient};
+use crate::ctx::{CtxWho, ctx_client::CtxClient, resolve_permissions};
 
 /// JWT authentication token containing user/builder identity and permissions.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthToken {
+    /// Registered claims (issuer, audience, validity period and ID).
+    #[serde(flatten)]
+    pub claims: Claims,
     /// Subject (user/builder ID).
     pub sub: Uuid,
-    /// Issuer.
-    pub iss: String,
-    /// Expiration time.
-    pub exp: i64,
-    /// Issued at time.
-    pub iat: i64,
-    /// Not before time.
-    pub nbf: i64,
-    /// JWT ID.
-    pub jti: Uuid,
     /// Granted permissions.
     pub permissions: HashSet<String>,
     /// Client data (for client tokens).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_data: Option<CtxClient>,
     pub who: CtxWho,
+    /// Session the token was issued for (for client tokens).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
 }
 
 impl AuthToken {
@@ -73,20 +68,17 @@ impl AuthToken {
         permissions: HashSet<String>,
         token_duration: TimeDelta,
     ) -> Result<Self> {
-        let expiration = chrono::Utc::now()
-            .checked_add_signed(token_duration)
-            .ok_or_else(|| Error::AuthTokenCreation)?;
+        let claims = Claims::builder(token_duration)
+            .build()
+            .map_err(|_| Error::AuthTokenCreation)?;
 
         Ok(Self {
+            claims,
             sub: *id,
-            exp: expiration.timestamp(),
-            iat: Utc::now().timestamp(),
-            nbf: Utc::now().timestamp(),
-            iss: String::from(ISS),
-            jti: Uuid::new_v4(),
             permissions,
             client_data: None,
             who: CtxWho::Client,
+            sid: None,
         })
     }
 
@@ -118,20 +110,17 @@ impl AuthToken {
         permissions: HashSet<String>,
         token_duration: TimeDelta,
     ) -> Result<Self> {
-        let expiration = chrono::Utc::now()
-            .checked_add_signed(token_duration)
-            .ok_or_else(|| Error::AuthTokenCreation)?;
+        let claims = Claims::builder(token_duration)
+            .build()
+            .map_err(|_| Error::AuthTokenCreation)?;
 
         Ok(Self {
+            claims,
             sub: *id,
-            exp: expiration.timestamp(),
-            iat: Utc::now().timestamp(),
-            nbf: Utc::now().timestamp(),
-            iss: String::from(ISS),
-            jti: Uuid::new_v4(),
             permissions,
             client_data: None,
             who: CtxWho::Builder,
+            sid: None,
         })
     }
 }
@@ -171,7 +160,7 @@ pub fn authenticate(
     if !is_valid {
         return Err(Error::WrongCredentials);
     }
-    let permissions = client.fetch_permissions(connection)?;
+    let permissions = resolve_permissions(&client, connection)?;
     Ok((
         EjClientApi {
             id: client.id,
@@ -213,6 +202,8 @@ pub fn encode_token(token: &AuthToken) -> Result<AuthBody> {
 
 /// Decodes a JWT string back into an authentication token.
 ///
+/// The issuer, audience and validity period of the token are enforced.
+///
 /// # Examples
 ///
 /// ```rust
@@ -236,10 +227,12 @@ pub fn encode_token(token: &AuthToken) -> Result<AuthBody> {
 /// # }
 /// ```
 pub fn decode_token(token: &str) -> Result<AuthToken> {
-    Ok(jwt_decode::<AuthToken>(token)
-        .map_err(|err| {
-            log::error!("Failed to decode jwt token {err}");
-            err
-        })?
-        .claims)
+    Ok(
+        jwt_decode_with_validation::<AuthToken>(token, &JwtValidation::default())
+            .map_err(|err| {
+                log::error!("Failed to decode jwt token {err}");
+                err
+            })?
+            .claims,
+    )
 }
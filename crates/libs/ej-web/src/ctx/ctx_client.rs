@@ -5,6 +5,7 @@ use std::net::SocketAddr;
 
 use chrono::TimeDelta;
 use ej_auth::auth_body::AuthBody;
+use ej_dispatcher_sdk::ejaudit::EjAuditAction;
 use ej_dispatcher_sdk::ejbuilder::EjBuilderApi;
 use ej_dispatcher_sdk::ejclient::EjClientApi;
 use ej_dispatcher_sdk::ejws_message::EjWsServerMessage;
@@ -14,6 +15,7 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Sender;
 use uuid::Uuid;
 
+use crate::audit::record_audit;
 use crate::auth_token::{AuthToken, encode_token};
 use crate::ejconnected_builder::EjConnectedBuilder;
 use crate::prelude::*;
@@ -26,7 +28,8 @@ pub struct CtxClient {
 }
 
 const BUILDER_TOKEN_EXPIRATION_TIME: TimeDelta = TimeDelta::days(365);
-const CLIENT_TOKEN_EXPIRATION_TIME: TimeDelta = TimeDelta::hours(12);
+/// How long the access tokens handed out on login and refresh are valid for.
+pub const ACCESS_TOKEN_EXPIRATION_TIME: TimeDelta = TimeDelta::minutes(15);
 const BUILDER_PERMISSIONS: [&'static str; 1] = ["builder"];
 
 impl CtxClient {
@@ -54,14 +57,13 @@ impl CtxClient {
     pub fn create_builder(&self, conn: &mut DbConnection) -> Result<EjBuilderApi> {
         let builder = EjBuilderCreate::new(self.id).create(conn)?;
 
-        let permissions: HashSet<String> = BUILDER_PERMISSIONS
-            .into_iter()
-            .map(|p| String::from(p))
-            .collect();
-
-        let claims =
-            AuthToken::new_builder(&builder.id, permissions, BUILDER_TOKEN_EXPIRATION_TIME)?;
-        let token = encode_token(&claims)?;
+        let token = encode_builder_token(&builder.id, BUILDER_TOKEN_EXPIRATION_TIME)?;
+        record_audit(
+            EjAuditAction::BuilderCreated,
+            Some(self.id),
+            Some(builder.id.to_string()),
+            conn,
+        );
         Ok(EjBuilderApi {
             id: builder.id,
             token: token.access_token,
@@ -100,13 +102,17 @@ impl CtxClient {
             tx,
             addr,
             connection_id: Uuid::new_v4(),
+            capabilities: None,
+            locked_boards: Vec::new(),
+            stats: Default::default(),
         }
     }
 }
 
 /// Generates an authentication token for a client with specified permissions.
 ///
-/// Creates a JWT token that can be used for authenticating API requests.
+/// Creates a JWT token that can be used for authenticating API requests
+/// during the session `session_id`.
 ///
 /// # Examples
 ///
@@ -127,13 +133,47 @@ impl CtxClient {
 ///     Permission::new("write".to_string()),
 /// ];
 ///
-/// let auth_body = generate_token(&client, permissions)?;
+/// let auth_body = generate_token(&client, permissions, &Uuid::new_v4())?;
 /// println!("Generated token: {}", auth_body.access_token);
 /// # Ok(())
 /// # }
 /// ```
-pub fn generate_token(client: &EjClientApi, permissions: Vec<Permission>) -> Result<AuthBody> {
+pub fn generate_token(
+    client: &EjClientApi,
+    permissions: Vec<Permission>,
+    session_id: &Uuid,
+) -> Result<AuthBody> {
     let permissions: HashSet<String> = permissions.into_iter().map(|p| p.id).collect();
-    let claims = AuthToken::new_client(&client.id, permissions, CLIENT_TOKEN_EXPIRATION_TIME)?;
+    let mut claims = AuthToken::new_client(&client.id, permissions, ACCESS_TOKEN_EXPIRATION_TIME)?;
+    claims.sid = Some(*session_id);
+    encode_token(&claims)
+}
+
+/// Generates a short-lived access token for a builder.
+///
+/// Builders authenticate with the long-lived token they got when they were
+/// created and use this one for the rest of their session.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_web::ctx::ctx_client::generate_builder_token;
+/// use uuid::Uuid;
+///
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let auth_body = generate_builder_token(&Uuid::new_v4())?;
+/// println!("Generated token: {}", auth_body.access_token);
+/// # Ok(())
+/// # }
+/// ```
+pub fn generate_builder_token(id: &Uuid) -> Result<AuthBody> {
+    encode_builder_token(id, ACCESS_TOKEN_EXPIRATION_TIME)
+}
+
+/// Encodes a builder token valid for `duration`.
+fn encode_builder_token(id: &Uuid, duration: TimeDelta) -> Result<AuthBody> {
+    let permissions: HashSet<String> = BUILDER_PERMISSIONS.into_iter().map(String::from).collect();
+
+    let claims = AuthToken::new_builder(id, permissions, duration)?;
     encode_token(&claims)
 }
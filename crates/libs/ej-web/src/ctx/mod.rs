@@ -5,10 +5,13 @@
 
 use std::collections::HashSet;
 
+use ej_models::{
+    auth::permission::Permission, client::ejclient::EjClient, db::connection::DbConnection,
+};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::ctx::ctx_client::CtxClient;
+use crate::{ctx::ctx_client::CtxClient, prelude::*};
 
 pub mod ctx_client;
 pub mod resolver;
@@ -31,6 +34,10 @@ pub struct Ctx {
     pub permissions: HashSet<String>,
     /// Type of authenticated entity (client or builder).
     pub who: CtxWho,
+    /// The API key used to authenticate, if any.
+    pub api_key: Option<Uuid>,
+    /// The session the access token was issued for, if any.
+    pub session: Option<Uuid>,
 }
 
 impl Ctx {
@@ -57,6 +64,62 @@ impl Ctx {
             client: CtxClient { id },
             who,
             permissions,
+            api_key: None,
+            session: None,
         }
     }
+
+    /// Creates a request context for a client authenticated with an API key.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_web::ctx::{Ctx, CtxWho};
+    /// use std::collections::HashSet;
+    /// use uuid::Uuid;
+    ///
+    /// let key_id = Uuid::new_v4();
+    /// let ctx = Ctx::from_api_key(Uuid::new_v4(), key_id, HashSet::new());
+    /// assert_eq!(ctx.who, CtxWho::Client);
+    /// assert_eq!(ctx.api_key, Some(key_id));
+    /// ```
+    pub fn from_api_key(client_id: Uuid, key_id: Uuid, permissions: HashSet<String>) -> Self {
+        Self {
+            api_key: Some(key_id),
+            ..Self::new(client_id, CtxWho::Client, permissions)
+        }
+    }
+}
+
+/// Resolves every permission a client has.
+///
+/// Clients get the permissions granted to them directly plus the permissions
+/// of each of their roles, so `require_permission!` checks work the same way
+/// whichever way a permission was granted.
+///
+/// # Returns
+///
+/// The permissions of the client, sorted and without duplicates.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_web::ctx::resolve_permissions;
+/// # use ej_models::{client::ejclient::EjClient, db::connection::DbConnection};
+///
+/// # fn example(client: &EjClient, connection: &DbConnection) -> Result<(), Box<dyn std::error::Error>> {
+/// let permissions = resolve_permissions(client, connection)?;
+/// println!("{} has {} permissions", client.name, permissions.len());
+/// # Ok(())
+/// # }
+/// ```
+pub fn resolve_permissions(
+    client: &EjClient,
+    connection: &DbConnection,
+) -> Result<Vec<Permission>> {
+    let mut permissions = client.fetch_permissions(connection)?;
+    permissions.extend(client.fetch_role_permissions(connection)?);
+    permissions.sort_by(|a, b| a.id.cmp(&b.id));
+    permissions.dedup();
+    Ok(permissions)
 }
@@ -2,49 +2,101 @@
 
 use axum::{
     body::Body,
-    extract::{FromRequestParts, Request},
+    extract::{FromRequestParts, Request, State},
     http::{HeaderMap, request::Parts},
     middleware::Next,
     response::Response,
 };
-use ej_auth::{AUTH_HEADER, AUTH_HEADER_PREFIX, jwt::jwt_decode};
+use ej_auth::{
+    AUTH_HEADER, AUTH_HEADER_PREFIX,
+    api_key::API_KEY_HEADER,
+    jwt::{JwtValidation, jwt_decode_with_validation},
+};
 use ej_dispatcher_sdk::{
+    ejaudit::EjAuditAction,
     ejbuilder::EjBuilderApi,
     ejclient::{EjClientLogin, EjClientLoginRequest},
+    ejtoken::{EjRefreshTokenRequest, EjTokens},
 };
 use ej_models::db::connection::DbConnection;
 use tower_cookies::{Cookie, Cookies};
+use tracing::error;
 
 use crate::{
-    auth_token::AuthToken,
-    ctx::{Ctx, ctx_client::generate_token},
+    audit::record_audit,
+    auth_token::{AuthToken, decode_token},
+    ctx::{Ctx, CtxWho, ctx_client::generate_token, resolve_permissions},
+    ejapi_key::authenticate_api_key,
+    ejbuilder::fetch_active_builder,
+    ejclient::fetch_client,
+    ejsession::start_session,
+    ejtoken::{issue_builder_tokens, issue_client_refresh_token, refresh_tokens},
+    revocation::EjRevocationStore,
 };
 use crate::{auth_token::authenticate, prelude::*};
 
 /// The name of the cookie used to store authentication tokens.
 pub const AUTH_TOKEN_COOKIE: &str = "auth-token";
 
+/// State needed to resolve the request context.
+#[derive(Debug, Clone)]
+pub struct CtxResolverState {
+    /// Access tokens that are no longer accepted.
+    pub revoked_tokens: EjRevocationStore,
+    /// Database connection used to look API keys up.
+    pub connection: DbConnection,
+}
+
 /// Middleware for resolving request context from authentication tokens.
 ///
-/// Extracts authentication tokens from cookies or headers, validates them,
-/// and adds the resulting context to the request extensions.
+/// Requests carrying an API key in the `X-Api-Key` header are authenticated
+/// with it. Otherwise the authentication token is extracted from cookies or
+/// headers and validated, rejecting the ones that were revoked or whose
+/// session was. The resulting context is added to the request extensions.
 ///
 /// # Examples
 ///
 /// ```rust
 /// use axum::Router;
-/// use ej_web::ctx::resolver::mw_ctx_resolver;
+/// use ej_web::{
+///     ctx::resolver::{CtxResolverState, mw_ctx_resolver},
+///     revocation::EjRevocationStore,
+/// };
+/// # use ej_models::db::connection::DbConnection;
 ///
+/// # fn example(connection: DbConnection) {
+/// let state = CtxResolverState {
+///     revoked_tokens: EjRevocationStore::default(),
+///     connection,
+/// };
 /// let app: Router<()> = Router::new()
-///     .layer(axum::middleware::from_fn(mw_ctx_resolver));
+///     .layer(axum::middleware::from_fn_with_state(state, mw_ctx_resolver));
+/// # }
 /// ```
 #[axum::debug_middleware]
 pub async fn mw_ctx_resolver(
+    State(state): State<CtxResolverState>,
     cookies: Cookies,
     headers: HeaderMap,
     mut req: Request<Body>,
     next: Next,
 ) -> Response {
+    if let Some(key) = headers.get(API_KEY_HEADER) {
+        let ctx = key
+            .to_str()
+            .map_err(|_| Error::Auth(ej_auth::error::Error::InvalidToken))
+            .and_then(|key| authenticate_api_key(key, &state.connection))
+            .map_err(|err| match err {
+                Error::Auth(err) => err,
+                err => {
+                    error!("Failed to authenticate API key - {err}");
+                    ej_auth::error::Error::InvalidToken
+                }
+            });
+        req.extensions_mut().insert(ctx);
+        return next.run(req).await;
+    }
+
     let token = cookies
         .get(AUTH_TOKEN_COOKIE)
         .map(|c| c.value().to_string())
@@ -56,16 +108,32 @@ pub async fn mw_ctx_resolver(
                 .map(|s| s.to_string())
         })
         .ok_or(ej_auth::error::Error::TokenMissing)
-        .and_then(|token| Ok(jwt_decode::<AuthToken>(&token)?.claims))
         .and_then(|token| {
-            if token.exp < chrono::Utc::now().timestamp() {
+            Ok(jwt_decode_with_validation::<AuthToken>(&token, &JwtValidation::default())?.claims)
+        })
+        .and_then(|token| {
+            if token.claims.exp < chrono::Utc::now().timestamp() {
                 Err(ej_auth::error::Error::TokenExpired)
+            } else if state.revoked_tokens.is_revoked(&token.claims.jti)
+                || token
+                    .sid
+                    .is_some_and(|sid| state.revoked_tokens.is_revoked(&sid))
+            {
+                Err(ej_auth::error::Error::TokenRevoked)
             } else {
                 Ok(token)
             }
         });
 
-    let ctx = token.map(|token: AuthToken| Ctx::new(token.sub, token.who, token.permissions));
+    let ctx = token.and_then(|token| {
+        token_ctx(token, &state.connection).map_err(|err| match err {
+            Error::Auth(err) => err,
+            err => {
+                error!("Failed to resolve the permissions of a token - {err}");
+                ej_auth::error::Error::InvalidToken
+            }
+        })
+    });
 
     if ctx.is_err() {
         cookies.remove(Cookie::from(AUTH_TOKEN_COOKIE));
@@ -75,36 +143,92 @@ pub async fn mw_ctx_resolver(
     next.run(req).await
 }
 
+/// Builds the context of a request authenticated with an access token.
+///
+/// The permissions of clients are resolved again instead of trusting the ones
+/// in the token, so granting or revoking a permission or a role takes effect
+/// right away rather than when the token is refreshed.
+///
+/// # Errors
+/// Returns `ej_auth::error::Error::InvalidToken` if the client no longer exists.
+pub fn token_ctx(token: AuthToken, connection: &DbConnection) -> Result<Ctx> {
+    let permissions = match token.who {
+        CtxWho::Client => {
+            let client = fetch_client(&token.sub, connection).map_err(|err| match err {
+                Error::ClientNotFound => Error::Auth(ej_auth::error::Error::InvalidToken),
+                err => err,
+            })?;
+            resolve_permissions(&client, connection)?
+                .into_iter()
+                .map(|permission| permission.id)
+                .collect()
+        }
+        CtxWho::Builder => token.permissions,
+    };
+    Ok(Ctx {
+        session: token.sid,
+        ..Ctx::new(token.sub, token.who, permissions)
+    })
+}
+
 /// Logs in a builder and sets authentication cookie.
 ///
+/// Builders authenticate with the token they got when they were created and
+/// receive a short-lived access token along with a refresh token.
+///
 /// # Examples
 ///
 ///
 /// ```rust,no_run
 /// use ej_web::ctx::resolver::login_builder;
 /// use ej_dispatcher_sdk::ejbuilder::EjBuilderApi;
+/// use ej_models::db::connection::DbConnection;
 /// use tower_cookies::Cookies;
 /// use uuid::Uuid;
 ///
-/// # fn example(cookies: &Cookies) -> Result<(), Box<dyn std::error::Error>> {
+/// # fn example(connection: &DbConnection, cookies: &Cookies) -> Result<(), Box<dyn std::error::Error>> {
 /// let builder = EjBuilderApi {
 ///     id: Uuid::new_v4(),
 ///     token: "jwt_tokezn_here".to_string(),
 /// };
 ///
 /// // In a real handler, cookies would be extracted from the request
-/// let logged_in_builder = login_builder(builder, cookies)?;
+/// let tokens = login_builder(&builder, connection, cookies)?;
+/// println!("Builder logged in, access token valid for {}s", tokens.expires_in);
 /// # Ok(())
 /// # }
 /// ```
-pub fn login_builder(auth: EjBuilderApi, cookies: &Cookies) -> Result<EjBuilderApi> {
-    cookies.add(Cookie::new(AUTH_TOKEN_COOKIE, auth.token.clone()));
-    Ok(auth)
+pub fn login_builder(
+    auth: &EjBuilderApi,
+    connection: &DbConnection,
+    cookies: &Cookies,
+) -> Result<EjTokens> {
+    let tokens = authenticate_builder(auth, connection).inspect_err(|_| {
+        record_audit(
+            EjAuditAction::LoginFailed,
+            None,
+            Some(auth.id.to_string()),
+            connection,
+        )
+    })?;
+    record_audit(EjAuditAction::TokenIssued, Some(auth.id), None, connection);
+    cookies.add(Cookie::new(AUTH_TOKEN_COOKIE, tokens.access_token.clone()));
+    Ok(tokens)
+}
+
+fn authenticate_builder(auth: &EjBuilderApi, connection: &DbConnection) -> Result<EjTokens> {
+    let token = decode_token(&auth.token)?;
+    if token.who != CtxWho::Builder || token.sub != auth.id {
+        return Err(Error::WrongCredentials);
+    }
+    fetch_active_builder(&auth.id, connection)?;
+    issue_builder_tokens(&auth.id, connection)
 }
 
 /// Logs in a client and sets authentication cookie.
 ///
-/// Authenticates the client credentials and generatezs a JWT token for subsequent requests.
+/// Authenticates the client credentials, starts a new session and generatezs a JWT token
+/// for subsequent requests, along with a refresh token to renew it once it expires.
 ///
 /// # Examples
 ///
@@ -130,14 +254,57 @@ pub fn login_client(
     connection: &DbConnection,
     cookies: &Cookies,
 ) -> Result<EjClientLogin> {
-    let (client, permissions) = authenticate(auth, connection)?;
-    let token = generate_token(&client, permissions)?;
+    let (client, permissions) = authenticate(auth, connection).inspect_err(|_| {
+        record_audit(
+            EjAuditAction::LoginFailed,
+            None,
+            Some(auth.name.clone()),
+            connection,
+        )
+    })?;
+    record_audit(EjAuditAction::Login, Some(client.id), None, connection);
+    let session = start_session(&client.id, connection)?;
+    let refresh_token = issue_client_refresh_token(&session, connection)?;
+    let token =
+        generate_token(&client, permissions, &session.id)?.with_refresh_token(refresh_token);
     cookies.add(Cookie::new(AUTH_TOKEN_COOKIE, token.access_token.clone()));
 
-    Ok(EjClientLogin {
-        access_token: token.access_token,
-        token_type: token.token_type,
-    })
+    Ok(W::from(token).0)
+}
+
+/// Exchanges a refresh token for new tokens and sets authentication cookie.
+///
+/// Works for both clients and builders. The refresh token can't be used again,
+/// reusing it revokes every session of its owner in `store`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ej_web::{ctx::resolver::refresh_login, revocation::EjRevocationStore};
+/// use ej_dispatcher_sdk::ejtoken::EjRefreshTokenRequest;
+/// use ej_models::db::connection::DbConnection;
+/// use tower_cookies::Cookies;
+///
+/// # fn example(store: &EjRevocationStore, connection: &DbConnection, cookies: &Cookies) -> Result<(), Box<dyn std::error::Error>> {
+/// let request = EjRefreshTokenRequest::new("refresh-token");
+/// let tokens = refresh_login(&request, store, connection, cookies)?;
+/// println!("Next refresh token: {}", tokens.refresh_token);
+/// # Ok(())
+/// # }
+/// ```
+pub fn refresh_login(
+    auth: &EjRefreshTokenRequest,
+    store: &EjRevocationStore,
+    connection: &DbConnection,
+    cookies: &Cookies,
+) -> Result<EjTokens> {
+    let tokens = refresh_tokens(&auth.refresh_token, store, connection)?;
+    let owner = decode_token(&tokens.access_token)
+        .ok()
+        .map(|token| token.sub);
+    record_audit(EjAuditAction::TokenIssued, owner, None, connection);
+    cookies.add(Cookie::new(AUTH_TOKEN_COOKIE, tokens.access_token.clone()));
+    Ok(tokens)
 }
 
 impl<S: Send + Sync> FromRequestParts<S> for Ctx {
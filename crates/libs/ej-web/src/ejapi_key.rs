@@ -0,0 +1,188 @@
+//! API key management utilities for web handlers.
+//!
+//! Every API key scope grants a single permission. A request authenticated
+//! with an API key only gets the permissions of its scopes that the owning
+//! client still has.
+
+use std::collections::HashSet;
+
+use chrono::{TimeDelta, Utc};
+use ej_auth::api_key::{generate_api_key, hash_api_key_secret, parse_api_key};
+use ej_dispatcher_sdk::ejapi_key::{EjApiKeyApi, EjApiKeyCreated, EjApiKeyPost, EjApiKeyScope};
+use ej_models::{
+    auth::ejapi_key::{EjApiKey, EjApiKeyCreate},
+    db::connection::DbConnection,
+};
+use uuid::Uuid;
+
+use crate::{
+    ctx::{Ctx, resolve_permissions},
+    ejclient::fetch_client,
+    prelude::*,
+};
+
+impl From<EjApiKey> for W<EjApiKeyApi> {
+    fn from(value: EjApiKey) -> Self {
+        Self(EjApiKeyApi {
+            id: value.id,
+            name: value.name,
+            prefix: value.prefix,
+            scopes: value
+                .scopes
+                .iter()
+                .filter_map(|scope| EjApiKeyScope::from_name(scope))
+                .collect(),
+            expires_at: value.expires_at,
+            revoked_at: value.revoked_at,
+            created_at: value.created_at,
+        })
+    }
+}
+
+/// Permission granted by an API key scope.
+pub fn scope_permission(scope: &EjApiKeyScope) -> &'static str {
+    match scope {
+        EjApiKeyScope::Dispatch => "client.dispatch",
+        EjApiKeyScope::ReadResults => "client.results",
+    }
+}
+
+/// Creates an API key for the client in `ctx`.
+///
+/// Clients can only grant scopes whose permission they have, and API keys
+/// can't be used to create other keys.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_web::{ctx::Ctx, ejapi_key::create_api_key};
+/// use ej_dispatcher_sdk::ejapi_key::{EjApiKeyPost, EjApiKeyScope};
+/// # use ej_models::db::connection::DbConnection;
+///
+/// # fn example(ctx: &Ctx, connection: &DbConnection) -> Result<(), Box<dyn std::error::Error>> {
+/// let payload = EjApiKeyPost {
+///     name: "ci".to_string(),
+///     scopes: vec![EjApiKeyScope::Dispatch],
+///     expires_in_days: Some(90),
+/// };
+/// let created = create_api_key(ctx, payload, connection)?;
+/// println!("Store this key somewhere safe: {}", created.key);
+/// # Ok(())
+/// # }
+/// ```
+pub fn create_api_key(
+    ctx: &Ctx,
+    payload: EjApiKeyPost,
+    connection: &DbConnection,
+) -> Result<EjApiKeyCreated> {
+    if ctx.api_key.is_some() {
+        return Err(Error::ApiForbidden);
+    }
+    if payload
+        .scopes
+        .iter()
+        .any(|scope| !ctx.permissions.contains(scope_permission(scope)))
+    {
+        return Err(Error::ApiForbidden);
+    }
+
+    let expires_at = match payload.expires_in_days {
+        Some(days) => Some(
+            Utc::now()
+                .checked_add_signed(TimeDelta::days(days.into()))
+                .ok_or(Error::AuthTokenCreation)?,
+        ),
+        None => None,
+    };
+    let mut scopes: Vec<String> = payload
+        .scopes
+        .iter()
+        .map(|scope| String::from(scope.as_str()))
+        .collect();
+    scopes.sort();
+    scopes.dedup();
+
+    let key = generate_api_key();
+    let api_key = EjApiKeyCreate {
+        ejclient_id: ctx.client.id,
+        name: payload.name,
+        prefix: key.prefix,
+        hash: hash_api_key_secret(&key.secret),
+        scopes,
+        expires_at,
+    }
+    .save(connection)?;
+
+    Ok(EjApiKeyCreated {
+        key: key.key,
+        api_key: W::from(api_key).0,
+    })
+}
+
+/// Lists the API keys of the client in `ctx`.
+pub fn list_api_keys(ctx: &Ctx, connection: &DbConnection) -> Result<Vec<EjApiKeyApi>> {
+    Ok(EjApiKey::fetch_by_client_id(&ctx.client.id, connection)?
+        .into_iter()
+        .map(|api_key| W::from(api_key).0)
+        .collect())
+}
+
+/// Revokes one of the API keys of the client in `ctx`.
+///
+/// Revoking an already revoked key is a no-op.
+pub fn revoke_api_key(ctx: &Ctx, id: &Uuid, connection: &DbConnection) -> Result<EjApiKeyApi> {
+    let api_key = EjApiKey::fetch_by_id(id, connection).map_err(|err| {
+        if err.is_not_found() {
+            Error::ApiKeyNotFound
+        } else {
+            Error::Models(err)
+        }
+    })?;
+    if api_key.ejclient_id != ctx.client.id {
+        return Err(Error::ApiKeyNotFound);
+    }
+    if api_key.is_revoked() {
+        return Ok(W::from(api_key).0);
+    }
+    Ok(W::from(api_key.revoke(connection)?).0)
+}
+
+/// Authenticates a request carrying an API key.
+///
+/// # Returns
+///
+/// The request context of the client owning the key, limited to the key's scopes.
+pub fn authenticate_api_key(key: &str, connection: &DbConnection) -> Result<Ctx> {
+    let (prefix, secret) = parse_api_key(key).ok_or(ej_auth::error::Error::InvalidToken)?;
+    let api_key = EjApiKey::fetch_by_prefix(prefix, connection).map_err(|err| {
+        if err.is_not_found() {
+            Error::Auth(ej_auth::error::Error::InvalidToken)
+        } else {
+            Error::Models(err)
+        }
+    })?;
+    if api_key.hash != hash_api_key_secret(secret) {
+        return Err(ej_auth::error::Error::InvalidToken.into());
+    }
+    if api_key.is_revoked() {
+        return Err(ej_auth::error::Error::TokenRevoked.into());
+    }
+    if api_key.is_expired() {
+        return Err(ej_auth::error::Error::TokenExpired.into());
+    }
+
+    let client = fetch_client(&api_key.ejclient_id, connection)?;
+    let client_permissions: HashSet<String> = resolve_permissions(&client, connection)?
+        .into_iter()
+        .map(|permission| permission.id)
+        .collect();
+    let permissions = api_key
+        .scopes
+        .iter()
+        .filter_map(|scope| EjApiKeyScope::from_name(scope))
+        .map(|scope| String::from(scope_permission(&scope)))
+        .filter(|permission| client_permissions.contains(permission))
+        .collect();
+
+    Ok(Ctx::from_api_key(client.id, api_key.id, permissions))
+}
@@ -0,0 +1,72 @@
+//! Job artifact utilities for web handlers.
+
+use ej_dispatcher_sdk::ejjob::artifact::EjJobArtifact;
+use ej_models::{
+    config::{ejboard_config::EjBoardConfigDb, ejconfig::EjConfigDb},
+    db::connection::DbConnection,
+    job::ejjob_artifact::{EjJobArtifactCreate, EjJobArtifactDb},
+};
+use uuid::Uuid;
+
+use crate::{error::Error, prelude::*};
+
+/// Stores the metadata of an artifact whose content has been received.
+pub fn save_artifact(
+    artifact: EjJobArtifactCreate,
+    connection: &DbConnection,
+) -> Result<EjJobArtifact> {
+    Ok(artifact_db_to_api(artifact.save(connection)?))
+}
+
+/// Fetches the metadata of an artifact.
+///
+/// Returns `Error::ArtifactNotFound` if no artifact has this ID.
+pub fn fetch_artifact(id: &Uuid, connection: &DbConnection) -> Result<EjJobArtifact> {
+    EjJobArtifactDb::fetch_by_id(id, connection)
+        .map(artifact_db_to_api)
+        .map_err(|err| {
+            if err.is_not_found() {
+                Error::ArtifactNotFound
+            } else {
+                Error::Models(err)
+            }
+        })
+}
+
+/// Lists the artifacts produced by a job, oldest first.
+pub fn list_job_artifacts(job_id: &Uuid, connection: &DbConnection) -> Result<Vec<EjJobArtifact>> {
+    Ok(EjJobArtifactDb::fetch_by_job_id(job_id, connection)?
+        .into_iter()
+        .map(artifact_db_to_api)
+        .collect())
+}
+
+/// Whether a board config belongs to the configuration uploaded by a builder.
+///
+/// Unknown board configs belong to no builder.
+pub fn is_builder_board_config(
+    builder_id: &Uuid,
+    board_config_id: &Uuid,
+    connection: &DbConnection,
+) -> Result<bool> {
+    let board_config = match EjBoardConfigDb::fetch_by_id(board_config_id, connection) {
+        Ok(board_config) => board_config,
+        Err(err) if err.is_not_found() => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+    let board = board_config.fetch_board(connection)?;
+    let config = EjConfigDb::fetch_by_id(&board.ejconfig_id, connection)?;
+    Ok(config.ejbuilder_id == *builder_id)
+}
+
+/// Converts an artifact stored in the database into its API representation.
+pub fn artifact_db_to_api(artifact: EjJobArtifactDb) -> EjJobArtifact {
+    EjJobArtifact {
+        id: artifact.id,
+        job_id: artifact.ejjob_id,
+        board_config_id: artifact.ejboard_config_id,
+        name: artifact.name,
+        size: artifact.size as u64,
+        created_at: artifact.created_at,
+    }
+}
@@ -0,0 +1,179 @@
+//! Builder management utilities for web handlers.
+
+use chrono::{TimeDelta, Utc};
+use ej_dispatcher_sdk::ejbuilder::{EjBuilderConnectionApi, EjBuilderInfo};
+use ej_models::{
+    auth::ejrefresh_token::EjRefreshToken,
+    builder::{ejbuilder::EjBuilder, ejbuilder_connection::EjBuilderConnection},
+    db::connection::DbConnection,
+};
+use std::{collections::BTreeSet, time::Duration};
+use uuid::Uuid;
+
+use crate::{
+    ejconfig::config_db_to_config_api,
+    ejconnected_builder::EjConnectedBuilder,
+    pagination::{Page, Pagination},
+    prelude::*,
+};
+
+/// Number of past connections included in the detailed view of a builder.
+pub const BUILDER_CONNECTION_HISTORY: i64 = 5;
+
+/// Fetches a builder by its ID, mapping a missing row to `Error::BuilderNotFound`.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_web::ejbuilder::fetch_builder;
+/// use uuid::Uuid;
+/// # use ej_models::db::connection::DbConnection;
+///
+/// # fn example(connection: &DbConnection) -> Result<(), Box<dyn std::error::Error>> {
+/// let builder = fetch_builder(&Uuid::new_v4(), connection)?;
+/// println!("Builder {} revoked: {}", builder.id, builder.is_revoked());
+/// # Ok(())
+/// # }
+/// ```
+pub fn fetch_builder(id: &Uuid, connection: &DbConnection) -> Result<EjBuilder> {
+    EjBuilder::fetch_by_id(id, connection).map_err(|err| {
+        if err.is_not_found() {
+            Error::BuilderNotFound
+        } else {
+            Error::Models(err)
+        }
+    })
+}
+
+/// Fetches a builder that is still allowed to authenticate.
+///
+/// Missing and revoked builders are both reported as `Error::BuilderRevoked`.
+pub fn fetch_active_builder(id: &Uuid, connection: &DbConnection) -> Result<EjBuilder> {
+    let builder = fetch_builder(id, connection).map_err(|err| match err {
+        Error::BuilderNotFound => Error::BuilderRevoked,
+        err => err,
+    })?;
+    if builder.is_revoked() {
+        return Err(Error::BuilderRevoked);
+    }
+    Ok(builder)
+}
+
+/// Builds the detailed view of a builder.
+///
+/// Combines the builder stored in the database with its latest uploaded config,
+/// the currently open connections, how well they keep up with their messages
+/// and its latest connections.
+pub fn builder_info(
+    builder: EjBuilder,
+    connected: &[EjConnectedBuilder],
+    connection: &DbConnection,
+) -> Result<EjBuilderInfo> {
+    let connected: Vec<&EjConnectedBuilder> = connected
+        .iter()
+        .filter(|c| c.builder.id == builder.id)
+        .collect();
+    let connections = connected.iter().map(|c| c.addr).collect();
+    let slow_sends = connected.iter().map(|c| c.stats.slow()).sum();
+    let dropped_messages = connected.iter().map(|c| c.stats.dropped()).sum();
+    let capabilities = connected.iter().find_map(|c| c.capabilities.clone());
+    let locked_boards: BTreeSet<Uuid> = connected
+        .iter()
+        .flat_map(|c| c.locked_boards.iter().copied())
+        .collect();
+
+    let config = builder
+        .fetch_latest_config(connection)?
+        .map(|config| config_db_to_config_api(config, connection))
+        .transpose()?;
+
+    let connection_history = EjBuilderConnection::fetch_recent_by_builder_id(
+        &builder.id,
+        BUILDER_CONNECTION_HISTORY,
+        connection,
+    )?
+    .into_iter()
+    .map(|builder_connection| EjBuilderConnectionApi {
+        address: builder_connection.address,
+        connected_at: builder_connection.connected_at,
+        last_seen_at: builder_connection.last_seen_at,
+        disconnected_at: builder_connection.disconnected_at,
+    })
+    .collect();
+
+    Ok(EjBuilderInfo {
+        id: builder.id,
+        client_id: builder.ejclient_id,
+        created_at: builder.created_at,
+        revoked_at: builder.revoked_at,
+        connections,
+        slow_sends,
+        dropped_messages,
+        last_seen_at: builder.last_seen_at,
+        connection_history,
+        config,
+        capabilities,
+        current_job: None,
+        locked_boards: locked_boards.into_iter().collect(),
+    })
+}
+
+/// Lists a page of the registered builders along with their connection status and config.
+///
+/// Builders are sorted by creation time unless `pagination` sorts them by `updated_at`.
+pub fn list_builders(
+    connected: &[EjConnectedBuilder],
+    pagination: &Pagination,
+    connection: &DbConnection,
+) -> Result<Page<EjBuilderInfo>> {
+    let page = pagination.db_page(&EjBuilder::SORT_FIELDS)?;
+    Ok(Page {
+        items: EjBuilder::fetch_page(&page, connection)?
+            .into_iter()
+            .map(|builder| builder_info(builder, connected, connection))
+            .collect::<Result<_>>()?,
+        total: EjBuilder::count(connection)?,
+    })
+}
+
+/// Lists every registered builder along with its connection status and config, oldest first.
+pub fn list_all_builders(
+    connected: &[EjConnectedBuilder],
+    connection: &DbConnection,
+) -> Result<Vec<EjBuilderInfo>> {
+    EjBuilder::fetch_all(connection)?
+        .into_iter()
+        .map(|builder| builder_info(builder, connected, connection))
+        .collect()
+}
+
+/// Records that a builder was heard from on one of its connections.
+///
+/// # Arguments
+///
+/// * `connection_id` - ID of the connection the builder was heard from on
+/// * `silence` - How long ago the builder was heard from
+/// * `connection` - Database connection
+pub fn mark_builder_seen(
+    connection_id: &Uuid,
+    silence: Duration,
+    connection: &DbConnection,
+) -> Result<()> {
+    let seen_at = Utc::now() - TimeDelta::from_std(silence).unwrap_or_default();
+    EjBuilderConnection::touch(connection_id, seen_at, connection)?;
+    Ok(())
+}
+
+/// Revokes a builder so its tokens are no longer accepted.
+///
+/// Its refresh tokens are revoked along with it.
+/// Revoking an already revoked builder is a no-op.
+/// Closing any open connection is left to the caller.
+pub fn revoke_builder(id: &Uuid, connection: &DbConnection) -> Result<EjBuilder> {
+    let builder = fetch_builder(id, connection)?;
+    if builder.is_revoked() {
+        return Ok(builder);
+    }
+    EjRefreshToken::revoke_all_for_builder(&builder.id, connection)?;
+    Ok(builder.revoke(connection)?)
+}
@@ -1,44 +1,86 @@
 //! Client management utilities for web handlers.
 
-use ej_auth::{auth_body::AuthBody, secret_hash::generate_secret_hash};
-use ej_dispatcher_sdk::ejclient::{EjClientApi, EjClientLogin, EjClientPost};
-use ej_models::{client::ejclient::EjClientCreate, db::connection::DbConnection};
+use ej_auth::{
+    auth_body::AuthBody,
+    secret_hash::{SecretPolicy, generate_secret_hash},
+};
+use ej_dispatcher_sdk::ejclient::{
+    EjClientApi, EjClientLogin, EjClientPost, EjClientUpdate, EjRoleApi,
+};
+use ej_models::{
+    auth::{
+        client_permission::{ClientPermission, ClientPermissionKey, NewClientPermission},
+        client_role::{ClientRole, ClientRoleKey, NewClientRole},
+        ejrefresh_token::EjRefreshToken,
+        ejsession::EjSession,
+        permission::Permission,
+        role::Role,
+    },
+    builder::ejbuilder::EjBuilder,
+    client::ejclient::{EjClient, EjClientCreate},
+    db::connection::DbConnection,
+};
+use uuid::Uuid;
 
-use crate::prelude::*;
+use crate::{
+    pagination::{Page, Pagination},
+    prelude::*,
+};
+
+/// Version of the hashing scheme used for client secrets.
+const SECRET_HASH_VERSION: i32 = 1;
+
+impl From<EjClient> for W<EjClientApi> {
+    fn from(value: EjClient) -> Self {
+        Self(EjClientApi {
+            id: value.id,
+            name: value.name,
+        })
+    }
+}
 
 impl From<AuthBody> for W<EjClientLogin> {
     fn from(value: AuthBody) -> Self {
         Self(EjClientLogin {
             access_token: value.access_token,
             token_type: value.token_type,
+            refresh_token: value.refresh_token,
         })
     }
 }
 
 /// Creates a new client from the provided payload.
 ///
+/// The secret must satisfy `policy`.
+///
 /// # Examples
 ///
 /// ```rust
 /// use ej_web::ejclient::create_client;
+/// use ej_auth::secret_hash::SecretPolicy;
 /// use ej_dispatcher_sdk::ejclient::EjClientPost;
 /// # use ej_models::db::connection::DbConnection;
 ///
 /// # async fn example(connection: &DbConnection) -> Result<(), Box<dyn std::error::Error>> {
 /// let payload = EjClientPost {
 ///     name: "example-client".to_string(),
-///     secret: "secret123".to_string(),
+///     secret: "Correct-Horse-42".to_string(),
 /// };
-/// let client = create_client(payload, connection)?;
+/// let client = create_client(payload, &SecretPolicy::default(), connection)?;
 /// # Ok(())
 /// # }
 /// ```
-pub fn create_client(payload: EjClientPost, connection: &DbConnection) -> Result<EjClientApi> {
+pub fn create_client(
+    payload: EjClientPost,
+    policy: &SecretPolicy,
+    connection: &DbConnection,
+) -> Result<EjClientApi> {
+    policy.validate(&payload.secret)?;
     let hash = generate_secret_hash(&payload.secret)?;
     let model = EjClientCreate {
         name: payload.name,
         hash,
-        hash_version: 1,
+        hash_version: SECRET_HASH_VERSION,
     };
     let model = model.save(connection)?;
 
@@ -48,3 +90,243 @@ pub fn create_client(payload: EjClientPost, connection: &DbConnection) -> Result
     };
     Ok(result)
 }
+
+/// Fetches a client by its ID, mapping a missing row to `Error::ClientNotFound`.
+pub fn fetch_client(id: &Uuid, connection: &DbConnection) -> Result<EjClient> {
+    EjClient::fetch_by_id(id, connection).map_err(|err| {
+        if err.is_not_found() {
+            Error::ClientNotFound
+        } else {
+            Error::Models(err)
+        }
+    })
+}
+
+/// Lists a page of the registered clients.
+///
+/// Clients are sorted by name unless `pagination` sorts them by `created_at`.
+pub fn list_clients(
+    pagination: &Pagination,
+    connection: &DbConnection,
+) -> Result<Page<EjClientApi>> {
+    let page = pagination.db_page(&EjClient::SORT_FIELDS)?;
+    Ok(Page {
+        items: EjClient::fetch_page(&page, connection)?
+            .into_iter()
+            .map(|client| W::from(client).0)
+            .collect(),
+        total: EjClient::count(connection)?,
+    })
+}
+
+/// Updates a client's name and/or secret.
+///
+/// A new secret must satisfy `policy`. Changing the secret revokes the
+/// client's refresh tokens.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_web::ejclient::update_client;
+/// use ej_auth::secret_hash::SecretPolicy;
+/// use ej_dispatcher_sdk::ejclient::EjClientUpdate;
+/// use uuid::Uuid;
+/// # use ej_models::db::connection::DbConnection;
+///
+/// # fn example(connection: &DbConnection) -> Result<(), Box<dyn std::error::Error>> {
+/// let payload = EjClientUpdate {
+///     name: Some("renamed-client".to_string()),
+///     secret: None,
+/// };
+/// let client = update_client(&Uuid::new_v4(), payload, &SecretPolicy::default(), connection)?;
+/// assert_eq!(client.name, "renamed-client");
+/// # Ok(())
+/// # }
+/// ```
+pub fn update_client(
+    id: &Uuid,
+    payload: EjClientUpdate,
+    policy: &SecretPolicy,
+    connection: &DbConnection,
+) -> Result<EjClientApi> {
+    if let Some(secret) = &payload.secret {
+        policy.validate(secret)?;
+    }
+    let mut client = fetch_client(id, connection)?;
+    if let Some(name) = payload.name {
+        client = client.update_name(&name, connection)?;
+    }
+    if let Some(secret) = payload.secret {
+        let hash = generate_secret_hash(&secret)?;
+        client = client.update_hash(&hash, SECRET_HASH_VERSION, connection)?;
+        EjRefreshToken::revoke_all_for_client(&client.id, connection)?;
+        EjSession::revoke_all_for_client(&client.id, connection)?;
+    }
+    Ok(W::from(client).0)
+}
+
+/// Deletes a client.
+///
+/// Clients that still own builders that aren't revoked can't be deleted,
+/// the builders must be revoked first. Revoked builders are kept along with
+/// their job history, without an owner.
+pub fn delete_client(id: &Uuid, connection: &DbConnection) -> Result<()> {
+    let client = fetch_client(id, connection)?;
+    let builders = EjBuilder::fetch_by_client_id(&client.id, connection)?;
+    if builders.iter().any(|builder| !builder.is_revoked()) {
+        return Err(Error::ClientHasBuilders);
+    }
+    Ok(client.delete(connection)?)
+}
+
+/// Lists the permissions granted to a client.
+pub fn fetch_client_permissions(id: &Uuid, connection: &DbConnection) -> Result<Vec<String>> {
+    let client = fetch_client(id, connection)?;
+    Ok(client
+        .fetch_permissions(connection)?
+        .into_iter()
+        .map(|permission| permission.id)
+        .collect())
+}
+
+/// Grants a permission to a client.
+///
+/// Granting a permission the client already has is a no-op.
+/// Returns the resulting list of client permissions.
+pub fn grant_permission(
+    id: &Uuid,
+    permission_id: &str,
+    connection: &DbConnection,
+) -> Result<Vec<String>> {
+    let client = fetch_client(id, connection)?;
+    let permission =
+        Permission::fetch_by_id(connection, &permission_id.to_string()).map_err(|err| {
+            if err.is_not_found() {
+                Error::PermissionNotFound
+            } else {
+                Error::Models(err)
+            }
+        })?;
+
+    let key = ClientPermissionKey {
+        ej_client_id: client.id,
+        permission_id: permission.id.clone(),
+    };
+    match ClientPermission::fetch_by_id(connection, &key) {
+        Ok(_) => {}
+        Err(err) if err.is_not_found() => {
+            ClientPermission::new(
+                connection,
+                NewClientPermission {
+                    ejclient_id: client.id,
+                    permission_id: permission.id,
+                },
+            )?;
+        }
+        Err(err) => return Err(err.into()),
+    }
+    fetch_client_permissions(id, connection)
+}
+
+/// Revokes a permission from a client.
+///
+/// Revoking a permission the client doesn't have is a no-op.
+/// Returns the resulting list of client permissions.
+pub fn revoke_permission(
+    id: &Uuid,
+    permission_id: &str,
+    connection: &DbConnection,
+) -> Result<Vec<String>> {
+    let client = fetch_client(id, connection)?;
+    let key = ClientPermissionKey {
+        ej_client_id: client.id,
+        permission_id: permission_id.to_string(),
+    };
+    ClientPermission::delete(connection, &key)?;
+    fetch_client_permissions(id, connection)
+}
+
+/// Lists every permission that can be granted.
+pub fn list_permissions(connection: &DbConnection) -> Result<Vec<String>> {
+    Ok(Permission::fetch_all(connection)?
+        .into_iter()
+        .map(|permission| permission.id)
+        .collect())
+}
+
+/// Lists every role along with the permissions it grants.
+pub fn list_roles(connection: &DbConnection) -> Result<Vec<EjRoleApi>> {
+    Role::fetch_all(connection)?
+        .into_iter()
+        .map(|role| {
+            let permissions = role
+                .fetch_permissions(connection)?
+                .into_iter()
+                .map(|permission| permission.id)
+                .collect();
+            Ok(EjRoleApi {
+                id: role.id,
+                permissions,
+            })
+        })
+        .collect()
+}
+
+/// Lists the roles assigned to a client.
+pub fn fetch_client_roles(id: &Uuid, connection: &DbConnection) -> Result<Vec<String>> {
+    let client = fetch_client(id, connection)?;
+    Ok(client
+        .fetch_roles(connection)?
+        .into_iter()
+        .map(|role| role.id)
+        .collect())
+}
+
+/// Assigns a role to a client.
+///
+/// Assigning a role the client already has is a no-op. The client gets the
+/// role's permissions on its next request.
+/// Returns the resulting list of client roles.
+pub fn grant_role(id: &Uuid, role_id: &str, connection: &DbConnection) -> Result<Vec<String>> {
+    let client = fetch_client(id, connection)?;
+    let role = Role::fetch_by_id(connection, role_id).map_err(|err| {
+        if err.is_not_found() {
+            Error::RoleNotFound
+        } else {
+            Error::Models(err)
+        }
+    })?;
+
+    let key = ClientRoleKey {
+        ej_client_id: client.id,
+        role_id: role.id.clone(),
+    };
+    match ClientRole::fetch_by_id(connection, &key) {
+        Ok(_) => {}
+        Err(err) if err.is_not_found() => {
+            ClientRole::new(
+                connection,
+                NewClientRole {
+                    ejclient_id: client.id,
+                    role_id: role.id,
+                },
+            )?;
+        }
+        Err(err) => return Err(err.into()),
+    }
+    fetch_client_roles(id, connection)
+}
+
+/// Removes a role from a client.
+///
+/// Removing a role the client doesn't have is a no-op.
+/// Returns the resulting list of client roles.
+pub fn revoke_role(id: &Uuid, role_id: &str, connection: &DbConnection) -> Result<Vec<String>> {
+    let client = fetch_client(id, connection)?;
+    let key = ClientRoleKey {
+        ej_client_id: client.id,
+        role_id: role_id.to_string(),
+    };
+    ClientRole::delete(connection, &key)?;
+    fetch_client_roles(id, connection)
+}
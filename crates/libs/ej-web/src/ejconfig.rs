@@ -1,11 +1,12 @@
 //! Configuration management utilities for web handlers.
 
-use crate::prelude::*;
+use crate::{ejbuilder::fetch_builder, prelude::*};
 use ej_auth::sha256::generate_hash;
 use ej_config::{ej_board_config::EjBoardConfigApi, ej_config::EjConfig};
+use ej_dispatcher_sdk::ejbuilder::{EjBuilderBoardApi, EjBuilderConfigApi};
 use ej_models::{
     config::{
-        ejboard::NewEjBoardDb,
+        ejboard::{EjBoardDb, NewEjBoardDb},
         ejboard_config::{EjBoardConfigDb, NewEjBoardConfigDb},
         ejboard_config_tag::{EjBoardConfigTag, NewEjBoardConfigTag},
         ejconfig::{EjConfigDb, NewEjConfigDb},
@@ -16,24 +17,45 @@ use ej_models::{
 use tracing::info;
 use uuid::Uuid;
 
+/// Hash identifying the content of a config, regardless of its IDs.
+///
+/// Builders get new IDs for their boards every time they load their config,
+/// so the IDs are left out for an unchanged config to hash the same.
+fn config_hash(config: &EjConfig) -> Result<String> {
+    let mut content = config.clone();
+    for board in content.boards.iter_mut() {
+        board.id = Uuid::nil();
+        for board_config in board.configs.iter_mut() {
+            board_config.id = Uuid::nil();
+        }
+    }
+    Ok(generate_hash(&serde_json::to_string(&content)?))
+}
+
 /// Saves a configuration to the database.
 ///
-/// If a config with the same hash already exists for the builder, returns the existing config.
-/// Otherwise, creates a new config entry with associated boards and tags.
+/// Each distinct config a builder uploads is stored as a new version. If the builder
+/// already uploaded the same config, that version becomes its latest one again and
+/// is returned as it was stored, so its boards keep the IDs jobs already refer to.
 pub fn save_config(
     config: EjConfig,
     builder_id: &Uuid,
     conn: &mut DbConnection,
 ) -> Result<EjConfig> {
-    let payload = serde_json::to_string(&config)?;
-    let hash = generate_hash(&payload);
-    if let Ok(_) = EjConfigDb::fetch_client_config(conn, builder_id, &hash) {
-        info!("Config already exists");
-        return Ok(config);
+    let hash = config_hash(&config)?;
+    if let Ok(existing) = EjConfigDb::fetch_client_config(conn, builder_id, &hash) {
+        info!("Config {} already exists", existing.id);
+        let existing = existing.mark_uploaded(conn)?;
+        return match existing.content {
+            Some(content) => Ok(serde_json::from_str(&content)?),
+            None => Ok(config),
+        };
     }
     info!("Config with hash {hash} not found for builder {builder_id}. Creating one...");
     let result = config.clone();
-    let configdb = NewEjConfigDb::new(*builder_id, config.global.version, hash).save(conn)?;
+    let content = serde_json::to_string(&config)?;
+    let configdb =
+        NewEjConfigDb::new(*builder_id, config.global.version, hash, content).save(conn)?;
     for board in config.boards {
         NewEjBoardDb::new(board.id, configdb.id.clone(), board.name, board.description)
             .save(conn)?;
@@ -61,6 +83,51 @@ pub fn save_config(
     Ok(result)
 }
 
+/// Fetches the latest config uploaded by a builder, as the builder loaded it.
+///
+/// Returns `None` if the builder never uploaded a config, or only ones
+/// uploaded before their content was stored.
+pub fn fetch_latest_config(
+    builder_id: &Uuid,
+    connection: &DbConnection,
+) -> Result<Option<EjConfig>> {
+    let builder = fetch_builder(builder_id, connection)?;
+    let Some(content) = builder
+        .fetch_latest_config(connection)?
+        .and_then(|config| config.content)
+    else {
+        return Ok(None);
+    };
+    Ok(Some(serde_json::from_str(&content)?))
+}
+
+/// Converts a config version stored in the database into its API representation.
+pub fn config_db_to_config_api(
+    config: EjConfigDb,
+    connection: &DbConnection,
+) -> Result<EjBuilderConfigApi> {
+    let mut boards = Vec::new();
+    for board in EjBoardDb::fetch_by_ejconfig_id(&config.id, connection)? {
+        let configs = EjBoardConfigDb::fetch_by_board_id(&board.id, connection)?
+            .into_iter()
+            .map(|board_config| board_config_db_to_board_config_api(board_config, connection))
+            .collect::<Result<Vec<_>>>()?;
+        boards.push(EjBuilderBoardApi {
+            id: board.id,
+            name: board.name,
+            description: board.description,
+            configs,
+        });
+    }
+    Ok(EjBuilderConfigApi {
+        id: config.id,
+        builder_id: config.ejbuilder_id,
+        version: config.version,
+        uploaded_at: config.uploaded_at,
+        boards,
+    })
+}
+
 pub fn board_config_db_to_board_config_api(
     config_db: EjBoardConfigDb,
     connection: &DbConnection,
@@ -1,12 +1,68 @@
 //! Connected builder management for WebSocket communication.
+//!
+//! Messages for a builder go through a bounded queue drained by its WebSocket
+//! connection. A builder that doesn't keep up can't stall the dispatcher: sends
+//! either give up after `BUILDER_SEND_TIMEOUT` or drop the message right away,
+//! depending on the `EjOverflowPolicy`, and are counted in `EjBuilderSendStats`.
 
-use std::net::SocketAddr;
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 
-use ej_dispatcher_sdk::ejws_message::EjWsServerMessage;
-use tokio::sync::mpsc::Sender;
+use ej_dispatcher_sdk::{ejbuilder::EjBuilderCapabilities, ejws_message::EjWsServerMessage};
+use tokio::sync::mpsc::{
+    Sender,
+    error::{SendTimeoutError, TrySendError},
+};
+use tracing::{error, warn};
 use uuid::Uuid;
 
-use crate::ctx::ctx_client::CtxClient;
+use crate::{ctx::ctx_client::CtxClient, prelude::*};
+
+/// Number of messages that can wait to be sent to a builder.
+pub const BUILDER_QUEUE_CAPACITY: usize = 32;
+
+/// How long a send waits for room in a full builder queue before giving up.
+pub const BUILDER_SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What to do with a message when the builder's queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EjOverflowPolicy {
+    /// Wait up to `BUILDER_SEND_TIMEOUT` for room, then drop the message.
+    Wait,
+    /// Drop the message right away.
+    Drop,
+}
+
+/// Counters describing how well a builder keeps up with its messages.
+#[derive(Debug, Default)]
+pub struct EjBuilderSendStats {
+    sent: AtomicU64,
+    slow: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl EjBuilderSendStats {
+    /// Number of messages queued for the builder.
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages that found the builder's queue full.
+    pub fn slow(&self) -> u64 {
+        self.slow.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages dropped because the builder's queue stayed full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
 
 /// Represents a builder that is currently connected via WebSocket.
 #[derive(Debug, Clone)]
@@ -14,9 +70,66 @@ pub struct EjConnectedBuilder {
     /// The builder's client context.
     pub builder: CtxClient,
     /// Message sender for WebSocket communication.
+    ///
+    /// Prefer `EjConnectedBuilder::send` which enforces the overflow policy.
     pub tx: Sender<EjWsServerMessage>,
     /// The builder's network address.
     pub addr: SocketAddr,
     /// Connection ID
     pub connection_id: Uuid,
+    /// Capabilities advertised by the builder, `None` until it sends them.
+    pub capabilities: Option<EjBuilderCapabilities>,
+    /// Boards the builder reported its jobs have exclusive access to.
+    pub locked_boards: Vec<Uuid>,
+    /// Statistics about the messages sent to this connection.
+    pub stats: Arc<EjBuilderSendStats>,
+}
+
+impl EjConnectedBuilder {
+    /// Queues a message for the builder.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Message to send
+    /// * `policy` - What to do if the builder's queue is full
+    ///
+    /// # Returns
+    ///
+    /// `Error::BuilderQueueFull` if the message was dropped and
+    /// `Error::BuilderDisconnected` if the connection is closed.
+    pub async fn send(&self, message: EjWsServerMessage, policy: EjOverflowPolicy) -> Result<()> {
+        let message = match self.tx.try_send(message) {
+            Ok(()) => {
+                self.stats.sent.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+            Err(TrySendError::Closed(_)) => return Err(Error::BuilderDisconnected),
+            Err(TrySendError::Full(message)) => message,
+        };
+
+        self.stats.slow.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "Queue of builder {} at {} is full",
+            self.builder.id, self.addr
+        );
+        let result = match policy {
+            EjOverflowPolicy::Wait => self.tx.send_timeout(message, BUILDER_SEND_TIMEOUT).await,
+            EjOverflowPolicy::Drop => Err(SendTimeoutError::Timeout(message)),
+        };
+        match result {
+            Ok(()) => {
+                self.stats.sent.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(SendTimeoutError::Closed(_)) => Err(Error::BuilderDisconnected),
+            Err(SendTimeoutError::Timeout(_)) => {
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                error!(
+                    "Dropping message for builder {} at {}, it isn't keeping up",
+                    self.builder.id, self.addr
+                );
+                Err(Error::BuilderQueueFull)
+            }
+        }
+    }
 }
@@ -1,31 +1,52 @@
 //! Job management utilities for web handlers.
 
+use ej_dispatcher_sdk::ejbuilder::EjBuilderConfigApi;
 use ej_dispatcher_sdk::ejjob::{
-    EjDeployableJob, EjJob, EjJobApi, EjJobType,
+    EjDeployableJob, EjJob, EjJobApi, EjJobQuery, EjJobType,
     results::{EjBuilderBuildResult, EjBuilderRunResult},
+    search::{EjJobSearchHit, EjJobSearchQuery, EjJobSearchSource},
 };
 use ej_models::{
+    config::ejboard_config::EjBoardConfigDb,
     db::connection::DbConnection,
     job::{
-        ejjob::{EjJobCreate, EjJobDb},
-        ejjob_logs::EjJobLogCreate,
-        ejjob_results::EjJobResultCreate,
+        ejjob::{EjJobCreate, EjJobDb, EjJobFilter},
+        ejjob_config::EjJobConfig,
+        ejjob_logs::EjJobLog,
+        ejjob_results::{EjJobResultCreate, EjJobResultDb},
+        ejjob_search::{EjJobSearchFilter, EjJobSearchMatch},
         ejjob_status::EjJobStatus,
     },
 };
 use uuid::Uuid;
 
-use crate::{error::Error, prelude::*, traits::job_result::EjJobResult};
+use crate::{
+    ctx::Ctx,
+    ejconfig::config_db_to_config_api,
+    error::Error,
+    pagination::{Page, Pagination},
+    prelude::*,
+    quota::DispatchQuota,
+    traits::job_result::EjJobResult,
+};
+
+/// Number of matches returned by a search that doesn't set a limit.
+pub const DEFAULT_SEARCH_LIMIT: u32 = 50;
+/// Maximum number of matches a single search can return.
+pub const MAX_SEARCH_LIMIT: u32 = 500;
 
 /// Creates a new job from the provided job data.
 ///
 /// Converts an `EjJob` into a database record and returns a `EjDeployableJob`
-/// that can be dispatched to builders.
+/// that can be dispatched to builders. `owner` is the client dispatching the
+/// job, which is refused with [`Error::QuotaExceeded`] if it would go over
+/// `quota`. Jobs without an owner aren't limited.
 ///
 /// # Examples
 ///
 /// ```rust
 /// use ej_web::ejjob::create_job;
+/// use ej_web::quota::DispatchQuota;
 /// use ej_dispatcher_sdk::ejjob::{EjJob, EjJobType};
 /// # use ej_models::db::connection::DbConnection;
 ///
@@ -35,20 +56,37 @@ use crate::{error::Error, prelude::*, traits::job_result::EjJobResult};
 ///     remote_url: "https://github.com/user/repo.git".to_string(),
 ///     remote_token: Some("github_token".to_string()),
 ///     job_type: EjJobType::Build,
+///     required_tags: vec!["arm64".to_string()],
+///     board_configs: Vec::new(),
+///     timeouts: Default::default(),
+///     checkout: Default::default(),
 /// };
 ///
-/// let deployable_job = create_job(job, &mut connection)?;
+/// let deployable_job = create_job(job, None, &DispatchQuota::default(), &mut connection)?;
 /// println!("Created job with ID: {}", deployable_job.id);
 /// # Ok(())
 /// # }
 /// ```
-pub fn create_job(ejjob: EjJob, connection: &mut DbConnection) -> Result<EjDeployableJob> {
+pub fn create_job(
+    ejjob: EjJob,
+    owner: Option<Uuid>,
+    quota: &DispatchQuota,
+    connection: &mut DbConnection,
+) -> Result<EjDeployableJob> {
     let job = EjJobCreate {
         commit_hash: ejjob.commit_hash,
         remote_url: ejjob.remote_url,
         job_type: ejjob.job_type as i32,
+        ejclient_id: owner,
+        required_tags: ejjob.required_tags,
+        board_configs: ejjob.board_configs,
     };
-    let job = job.save(connection)?;
+    let job = connection.transaction(|conn| {
+        if let Some(owner) = owner {
+            quota.check_in(&owner, conn)?;
+        }
+        Ok::<_, Error>(job.save_in(conn)?)
+    })?;
 
     Ok(EjDeployableJob {
         id: job.id,
@@ -56,9 +94,73 @@ pub fn create_job(ejjob: EjJob, connection: &mut DbConnection) -> Result<EjDeplo
         commit_hash: job.commit_hash,
         remote_url: job.remote_url,
         remote_token: ejjob.remote_token,
+        required_tags: job.required_tags,
+        board_configs: job.board_configs,
+        timeouts: ejjob.timeouts,
+        checkout: ejjob.checkout,
     })
 }
 
+/// Searches the logs and results of every job.
+///
+/// Returns `Error::EmptySearchQuery` if there's no text to search for.
+///
+/// # Returns
+///
+/// The matches, newest first. At most [`DEFAULT_SEARCH_LIMIT`] matches are
+/// returned unless the query sets a limit, which is capped to
+/// [`MAX_SEARCH_LIMIT`].
+pub fn search_jobs(
+    query: EjJobSearchQuery,
+    connection: &DbConnection,
+) -> Result<Vec<EjJobSearchHit>> {
+    if query.q.trim().is_empty() {
+        return Err(Error::EmptySearchQuery);
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .min(MAX_SEARCH_LIMIT);
+    let filter = EjJobSearchFilter {
+        job_id: query.job_id,
+        commit_hash: query.commit_hash,
+        board_config_id: query.board_config_id,
+        since: query.since,
+        until: query.until,
+        limit: limit.into(),
+    };
+
+    let mut hits = Vec::new();
+    if query.source != Some(EjJobSearchSource::Result) {
+        hits.extend(
+            EjJobLog::search(&query.q, &filter, connection)?
+                .into_iter()
+                .map(|hit| search_hit(hit, EjJobSearchSource::Log)),
+        );
+    }
+    if query.source != Some(EjJobSearchSource::Log) {
+        hits.extend(
+            EjJobResultDb::search(&query.q, &filter, connection)?
+                .into_iter()
+                .map(|hit| search_hit(hit, EjJobSearchSource::Result)),
+        );
+    }
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.created_at));
+    hits.truncate(limit as usize);
+    Ok(hits)
+}
+
+fn search_hit(hit: EjJobSearchMatch, source: EjJobSearchSource) -> EjJobSearchHit {
+    EjJobSearchHit {
+        job_id: hit.ejjob_id,
+        commit_hash: hit.commit_hash,
+        board_config_id: hit.ejboard_config_id,
+        source,
+        snippet: hit.snippet,
+        created_at: hit.created_at,
+    }
+}
+
 impl From<EjJobDb> for W<EjJobApi> {
     fn from(value: EjJobDb) -> Self {
         Self(EjJobApi {
@@ -69,13 +171,98 @@ impl From<EjJobDb> for W<EjJobApi> {
             status: value.status.into(),
             dispatched_at: value.dispatched_at,
             finished_at: value.finished_at,
+            required_tags: value.required_tags,
+            board_configs: value.board_configs,
         })
     }
 }
 
+/// Lists a page of the jobs matching `query`.
+///
+/// Fails with [`Error::InvalidSortField`] if the query sorts jobs by a field
+/// other than [`EjJobDb::SORT_FIELDS`].
+pub fn query_jobs(query: EjJobQuery, connection: &DbConnection) -> Result<Page<EjJobApi>> {
+    let page = Pagination::from(query.page).db_page(&EjJobDb::SORT_FIELDS)?;
+    let filter = EjJobFilter {
+        commit_hash: query.commit_hash,
+        remote_url: query.remote_url,
+        status: query.status.map(|status| status as i32),
+        job_type: query.job_type.map(|job_type| job_type as i32),
+        since: query.since,
+        until: query.until,
+    };
+    Ok(Page {
+        items: EjJobDb::fetch_filtered(&filter, &page, connection)?
+            .into_iter()
+            .map(|job| W::<EjJobApi>::from(job).0)
+            .collect(),
+        total: EjJobDb::count_filtered(&filter, connection)?,
+    })
+}
+
+/// Lists the config versions a job ran against, oldest first.
+///
+/// Versions are recorded when builders submit their results, so jobs that
+/// didn't finish may not have any.
+pub fn list_job_configs(
+    job_id: &Uuid,
+    connection: &DbConnection,
+) -> Result<Vec<EjBuilderConfigApi>> {
+    EjJobConfig::fetch_configs_by_job_id(job_id, connection)?
+        .into_iter()
+        .map(|config| config_db_to_config_api(config, connection))
+        .collect()
+}
+
+/// Cancels a job no builder received.
+///
+/// Nothing would ever finish it, leaving it queued against its client's quota.
+pub fn cancel_undelivered_job(job_id: &Uuid, connection: &DbConnection) -> Result<()> {
+    EjJobDb::fetch_by_id(job_id, connection)?
+        .update_status(EjJobStatus::cancelled(), connection)?;
+    Ok(())
+}
+
+/// Checks a board config of a job can be cancelled by the authenticated client.
+///
+/// Clients can cancel their own jobs, cancelling other jobs requires
+/// `client.manage`.
+///
+/// # Errors
+/// Returns `Error::JobNotFound` or `Error::BoardConfigNotFound` if either ID
+/// is unknown and `Error::ApiForbidden` if the job belongs to another client.
+pub fn check_job_board_cancel(
+    ctx: &Ctx,
+    job_id: &Uuid,
+    board_config_id: &Uuid,
+    connection: &DbConnection,
+) -> Result<()> {
+    let job = EjJobDb::fetch_by_id(job_id, connection).map_err(|err| {
+        if err.is_not_found() {
+            Error::JobNotFound
+        } else {
+            Error::Models(err)
+        }
+    })?;
+    EjBoardConfigDb::fetch_by_id(board_config_id, connection).map_err(|err| {
+        if err.is_not_found() {
+            Error::BoardConfigNotFound
+        } else {
+            Error::Models(err)
+        }
+    })?;
+    if job.ejclient_id != Some(ctx.client.id) && !ctx.permissions.contains("client.manage") {
+        return Err(Error::ApiForbidden);
+    }
+    Ok(())
+}
+
 /// Implementation of EjJobResult for build job results.
 ///
-/// Saves build job results including logs and status updates to the database.
+/// Saves build job results including logs and status updates to the database, along
+/// with the config versions the builder ran the job against.
+///
+/// Everything is saved in a single transaction, nothing is saved if any of it fails.
 ///
 /// # Examples
 ///
@@ -92,6 +279,7 @@ impl From<EjJobDb> for W<EjJobApi> {
 ///     builder_id: Uuid::new_v4(),
 ///     successful: true,
 ///     logs: HashMap::new(),
+///     skipped: Vec::new(),
 /// };
 ///
 /// build_result.save(connection)?;
@@ -101,28 +289,28 @@ impl From<EjJobDb> for W<EjJobApi> {
 impl EjJobResult for EjBuilderBuildResult {
     fn save(self, connection: &DbConnection) -> Result<()> {
         let result = self;
-        let job = EjJobDb::fetch_by_id(&result.job_id, connection)?;
-        let job_type: EjJobType = job.fetch_type(connection)?.id.into();
-        if job_type != EjJobType::Build {
-            return Err(Error::InvalidJobType);
-        }
+        connection.transaction(|conn| {
+            let job = EjJobDb::fetch_for_update(&result.job_id, conn)?;
+            let job_type: EjJobType = job.job_type.into();
+            if job_type != EjJobType::Build {
+                return Err(Error::InvalidJobType);
+            }
 
-        let job_status = if result.successful {
-            EjJobStatus::success()
-        } else {
-            EjJobStatus::failed()
-        };
-        job.update_status(job_status, connection)?;
-
-        for (board_config_id, logs) in result.logs.iter() {
-            let log = EjJobLogCreate {
-                ejjob_id: result.job_id.clone(),
-                ejboard_config_id: *board_config_id,
-                log: logs.join(""),
+            let job_status = if result.successful {
+                EjJobStatus::success()
+            } else {
+                EjJobStatus::failed()
             };
-            log.save(connection)?;
-        }
-        Ok(())
+            job.update_status_in(job_status, conn)?;
+
+            for (board_config_id, logs) in result.logs.iter() {
+                EjJobLog::replace_in(&result.job_id, board_config_id, &logs.join(""), conn)?;
+            }
+
+            let board_config_ids: Vec<Uuid> = result.logs.keys().copied().collect();
+            EjJobConfig::record_in(&result.job_id, &board_config_ids, conn)?;
+            Ok(())
+        })
     }
 
     fn job_id(&self) -> Uuid {
@@ -132,11 +320,18 @@ impl EjJobResult for EjBuilderBuildResult {
     fn builder_id(&self) -> Uuid {
         self.builder_id
     }
+
+    fn skipped(&self) -> Vec<Uuid> {
+        self.skipped.clone()
+    }
 }
 
 /// Implementation of EjJobResult for run job results.
 ///
-/// Saves run job results including logs, execution results, and status updates to the database.
+/// Saves run job results including logs, execution results, and status updates to the database,
+/// along with the config versions the builder ran the job against.
+///
+/// Everything is saved in a single transaction, nothing is saved if any of it fails.
 ///
 /// # Examples
 ///
@@ -154,6 +349,7 @@ impl EjJobResult for EjBuilderBuildResult {
 ///     successful: true,
 ///     logs: HashMap::new(),
 ///     results: HashMap::new(),
+///     skipped: Vec::new(),
 /// };
 ///
 /// run_result.save(connection)?;
@@ -163,37 +359,42 @@ impl EjJobResult for EjBuilderBuildResult {
 impl EjJobResult for EjBuilderRunResult {
     fn save(self, connection: &DbConnection) -> Result<()> {
         let run_result = self;
-        let job = EjJobDb::fetch_by_id(&run_result.job_id, connection)?;
-        let job_type: EjJobType = job.fetch_type(connection)?.id.into();
-        if job_type != EjJobType::BuildAndRun {
-            return Err(Error::InvalidJobType);
-        }
+        connection.transaction(|conn| {
+            let job = EjJobDb::fetch_for_update(&run_result.job_id, conn)?;
+            let job_type: EjJobType = job.job_type.into();
+            if job_type != EjJobType::BuildAndRun {
+                return Err(Error::InvalidJobType);
+            }
 
-        let job_status = if run_result.successful {
-            EjJobStatus::success()
-        } else {
-            EjJobStatus::failed()
-        };
-        job.update_status(job_status, connection)?;
-
-        for (board_config_id, logs) in run_result.logs.iter() {
-            let logs = EjJobLogCreate {
-                ejjob_id: run_result.job_id.clone(),
-                ejboard_config_id: *board_config_id,
-                log: logs.join(""),
+            let job_status = if run_result.successful {
+                EjJobStatus::success()
+            } else {
+                EjJobStatus::failed()
             };
-            logs.save(connection)?;
-        }
+            job.update_status_in(job_status, conn)?;
 
-        for (board_config_id, result) in run_result.results.iter() {
-            let result = EjJobResultCreate {
-                ejjob_id: run_result.job_id.clone(),
-                ejboard_config_id: *board_config_id,
-                result: result.to_string(),
-            };
-            result.save(connection)?;
-        }
-        Ok(())
+            for (board_config_id, logs) in run_result.logs.iter() {
+                EjJobLog::replace_in(&run_result.job_id, board_config_id, &logs.join(""), conn)?;
+            }
+
+            for (board_config_id, result) in run_result.results.iter() {
+                let result = EjJobResultCreate {
+                    ejjob_id: run_result.job_id,
+                    ejboard_config_id: *board_config_id,
+                    result: result.to_string(),
+                };
+                result.save_in(conn)?;
+            }
+
+            let board_config_ids: Vec<Uuid> = run_result
+                .logs
+                .keys()
+                .chain(run_result.results.keys())
+                .copied()
+                .collect();
+            EjJobConfig::record_in(&run_result.job_id, &board_config_ids, conn)?;
+            Ok(())
+        })
     }
 
     fn job_id(&self) -> Uuid {
@@ -203,4 +404,8 @@ impl EjJobResult for EjBuilderRunResult {
     fn builder_id(&self) -> Uuid {
         self.builder_id
     }
+
+    fn skipped(&self) -> Vec<Uuid> {
+        self.skipped.clone()
+    }
 }
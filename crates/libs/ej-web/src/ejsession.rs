@@ -0,0 +1,127 @@
+//! Client session management utilities for web handlers.
+//!
+//! Clients start a session every time they log in. Clients can list their
+//! active sessions and revoke them, which rejects the access tokens issued
+//! for the session right away and prevents refreshing them.
+
+use chrono::Utc;
+use ej_dispatcher_sdk::ejsession::EjSessionApi;
+use ej_models::{
+    auth::{
+        ejrefresh_token::EjRefreshToken,
+        ejsession::{EjSession, EjSessionCreate},
+    },
+    db::connection::DbConnection,
+};
+use tower_cookies::{Cookie, Cookies};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    ctx::{Ctx, resolver::AUTH_TOKEN_COOKIE},
+    ejtoken::REFRESH_TOKEN_EXPIRATION_TIME,
+    prelude::*,
+    revocation::EjRevocationStore,
+};
+
+impl W<EjSessionApi> {
+    /// Builds the API representation of a session as seen from `ctx`.
+    fn new(session: EjSession, ctx: &Ctx) -> Self {
+        Self(EjSessionApi {
+            current: ctx.session == Some(session.id),
+            id: session.id,
+            created_at: session.created_at,
+            last_refreshed_at: session.updated_at,
+            expires_at: session.expires_at,
+        })
+    }
+}
+
+/// Starts a new session for a client.
+pub fn start_session(client_id: &Uuid, connection: &DbConnection) -> Result<EjSession> {
+    Ok(EjSessionCreate {
+        ejclient_id: *client_id,
+        expires_at: Utc::now() + REFRESH_TOKEN_EXPIRATION_TIME,
+    }
+    .save(connection)?)
+}
+
+/// Lists the active sessions of the client in `ctx`, newest first.
+///
+/// API keys can't be used to manage sessions.
+pub fn list_sessions(ctx: &Ctx, connection: &DbConnection) -> Result<Vec<EjSessionApi>> {
+    if ctx.api_key.is_some() {
+        return Err(Error::ApiForbidden);
+    }
+    Ok(
+        EjSession::fetch_active_by_client(&ctx.client.id, connection)?
+            .into_iter()
+            .map(|session| W::new(session, ctx).0)
+            .collect(),
+    )
+}
+
+/// Revokes one of the sessions of the client in `ctx`.
+///
+/// The access tokens issued for the session are rejected right away and its
+/// refresh tokens can't be used anymore. Sessions can be revoked again, e.g.
+/// to reject the access tokens of a session revoked along with its client's
+/// other sessions.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_web::{ctx::Ctx, ejsession::revoke_session, revocation::EjRevocationStore};
+/// # use ej_models::db::connection::DbConnection;
+/// # use uuid::Uuid;
+///
+/// # fn example(ctx: &Ctx, session_id: Uuid, connection: &DbConnection) -> Result<(), Box<dyn std::error::Error>> {
+/// let store = EjRevocationStore::load(connection)?;
+/// revoke_session(ctx, &session_id, &store, connection)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn revoke_session(
+    ctx: &Ctx,
+    id: &Uuid,
+    store: &EjRevocationStore,
+    connection: &DbConnection,
+) -> Result<()> {
+    if ctx.api_key.is_some() {
+        return Err(Error::ApiForbidden);
+    }
+    let session = EjSession::fetch_by_id(id, connection).map_err(|err| {
+        if err.is_not_found() {
+            Error::SessionNotFound
+        } else {
+            Error::Models(err)
+        }
+    })?;
+    if session.ejclient_id != ctx.client.id {
+        return Err(Error::SessionNotFound);
+    }
+    if !session.is_revoked() {
+        session.revoke(connection)?;
+        EjRefreshToken::revoke_all_for_session(&session.id, connection)?;
+    }
+    store.revoke_session(session.id, connection)?;
+    info!("Revoked session {} of client {}", session.id, ctx.client.id);
+    Ok(())
+}
+
+/// Logs the client in `ctx` out.
+///
+/// Revokes the session the request was made with, if any, and clears the
+/// authentication cookie.
+pub fn logout(
+    ctx: &Ctx,
+    store: &EjRevocationStore,
+    connection: &DbConnection,
+    cookies: &Cookies,
+) -> Result<()> {
+    cookies.remove(Cookie::from(AUTH_TOKEN_COOKIE));
+    match ctx.session {
+        Some(session) => revoke_session(ctx, &session, store, connection),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,79 @@
+//! Job statistics utilities for web handlers.
+
+use ej_dispatcher_sdk::ejstats::{
+    EjBoardConfigStats, EjBuilderStats, EjCommitStats, EjStats, EjStatsQuery,
+};
+use ej_models::{
+    db::connection::DbConnection,
+    job::ejjob_stats::{
+        EjBoardConfigDurationStats, EjBuilderJobStats, EjCommitSuccessStats, EjJobStatsFilter,
+    },
+};
+
+use crate::prelude::*;
+
+/// Number of entries returned in each list unless the query sets a limit.
+pub const DEFAULT_STATS_LIMIT: u32 = 20;
+/// Maximum number of entries returned in each list.
+pub const MAX_STATS_LIMIT: u32 = 200;
+
+/// Computes statistics about the jobs that ran to completion.
+///
+/// Each list holds at most [`DEFAULT_STATS_LIMIT`] entries unless the query
+/// sets a limit, which is capped to [`MAX_STATS_LIMIT`].
+pub fn fetch_stats(query: EjStatsQuery, connection: &DbConnection) -> Result<EjStats> {
+    let commit_hashes: Option<Vec<String>> = query.commits.map(|commits| {
+        commits
+            .split(',')
+            .map(str::trim)
+            .filter(|commit| !commit.is_empty())
+            .map(String::from)
+            .collect()
+    });
+    let filter = EjJobStatsFilter {
+        since: query.since,
+        until: query.until,
+        commit_hashes,
+        limit: query
+            .limit
+            .unwrap_or(DEFAULT_STATS_LIMIT)
+            .min(MAX_STATS_LIMIT)
+            .into(),
+    };
+
+    let board_configs = EjBoardConfigDurationStats::fetch(&filter, connection)?
+        .into_iter()
+        .map(|stats| EjBoardConfigStats {
+            board_config_id: stats.ejboard_config_id,
+            name: stats.name,
+            jobs: stats.jobs as u64,
+            average_seconds: stats.average_seconds,
+            p50_seconds: stats.p50_seconds,
+            p95_seconds: stats.p95_seconds,
+        })
+        .collect();
+    let commits = EjCommitSuccessStats::fetch(&filter, connection)?
+        .into_iter()
+        .map(|stats| EjCommitStats {
+            success_rate: stats.success_rate(),
+            commit_hash: stats.commit_hash,
+            jobs: stats.jobs as u64,
+            successful: stats.successful as u64,
+            last_finished_at: stats.last_finished_at,
+        })
+        .collect();
+    let builders = EjBuilderJobStats::fetch(&filter, connection)?
+        .into_iter()
+        .map(|stats| EjBuilderStats {
+            builder_id: stats.ejbuilder_id,
+            jobs: stats.jobs as u64,
+            last_dispatched_at: stats.last_dispatched_at,
+        })
+        .collect();
+
+    Ok(EjStats {
+        board_configs,
+        commits,
+        builders,
+    })
+}
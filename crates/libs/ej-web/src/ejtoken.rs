@@ -0,0 +1,183 @@
+//! Refresh token utilities for web handlers.
+//!
+//! Refresh tokens are handed out alongside short-lived access tokens when
+//! clients and builders log in. Each refresh token can be exchanged once for a
+//! new access token and a new refresh token. Presenting a refresh token that
+//! was already exchanged revokes every refresh token and session of its owner
+//! since it likely leaked. Tokens revoked on purpose, such as on logout, are
+//! only rejected.
+
+use chrono::{TimeDelta, Utc};
+use ej_auth::{
+    auth_body::AuthBody,
+    refresh_token::{generate_refresh_token, hash_refresh_token},
+};
+use ej_dispatcher_sdk::ejtoken::EjTokens;
+use ej_models::{
+    auth::{
+        ejrefresh_token::{EjRefreshToken, EjRefreshTokenCreate},
+        ejsession::EjSession,
+    },
+    db::connection::DbConnection,
+};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    ctx::{
+        ctx_client::{ACCESS_TOKEN_EXPIRATION_TIME, generate_builder_token, generate_token},
+        resolve_permissions,
+    },
+    ejbuilder::fetch_active_builder,
+    ejclient::fetch_client,
+    ejsession::start_session,
+    prelude::*,
+    revocation::EjRevocationStore,
+};
+
+/// How long refresh tokens are valid for.
+pub const REFRESH_TOKEN_EXPIRATION_TIME: TimeDelta = TimeDelta::days(30);
+
+impl W<EjTokens> {
+    /// Builds the token pair from an access token and its refresh token.
+    fn new(body: AuthBody, refresh_token: String) -> Self {
+        Self(EjTokens {
+            access_token: body.access_token,
+            token_type: body.token_type,
+            expires_in: ACCESS_TOKEN_EXPIRATION_TIME.num_seconds(),
+            refresh_token,
+        })
+    }
+}
+
+/// Issues a new refresh token for a client session and stores its hash.
+///
+/// The session is extended until the new token expires.
+///
+/// # Returns
+///
+/// The refresh token to hand out. It can't be recovered later.
+pub fn issue_client_refresh_token(
+    session: &EjSession,
+    connection: &DbConnection,
+) -> Result<String> {
+    let token = generate_refresh_token();
+    let expiration = Utc::now() + REFRESH_TOKEN_EXPIRATION_TIME;
+    EjRefreshTokenCreate::for_client(
+        hash_refresh_token(&token),
+        session.ejclient_id,
+        session.id,
+        expiration,
+    )
+    .save(connection)?;
+    session.extend(expiration, connection)?;
+    Ok(token)
+}
+
+/// Issues a new refresh token to a builder and stores its hash.
+///
+/// # Returns
+///
+/// The refresh token to hand out. It can't be recovered later.
+pub fn issue_builder_refresh_token(id: &Uuid, connection: &DbConnection) -> Result<String> {
+    let token = generate_refresh_token();
+    let expiration = Utc::now() + REFRESH_TOKEN_EXPIRATION_TIME;
+    EjRefreshTokenCreate::for_builder(hash_refresh_token(&token), *id, expiration)
+        .save(connection)?;
+    Ok(token)
+}
+
+/// Issues an access token and a refresh token to a builder.
+pub fn issue_builder_tokens(id: &Uuid, connection: &DbConnection) -> Result<EjTokens> {
+    let body = generate_builder_token(id)?;
+    let refresh_token = issue_builder_refresh_token(id, connection)?;
+    Ok(W::<EjTokens>::new(body, refresh_token).0)
+}
+
+/// Exchanges a refresh token for a new access token and refresh token.
+///
+/// The refresh token is revoked in the process. Client permissions are read
+/// again so changes apply on the next refresh, and revoked builders can't
+/// refresh their tokens.
+/// Exchanging a refresh token again revokes every token of its owner, the
+/// sessions being revoked in `store` as well.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_web::{ejtoken::refresh_tokens, revocation::EjRevocationStore};
+/// # use ej_models::db::connection::DbConnection;
+///
+/// # async fn example(connection: &DbConnection) -> Result<(), Box<dyn std::error::Error>> {
+/// let store = EjRevocationStore::load(connection)?;
+/// let tokens = refresh_tokens("refresh-token", &store, connection)?;
+/// println!("New access token: {}", tokens.access_token);
+/// # Ok(())
+/// # }
+/// ```
+pub fn refresh_tokens(
+    refresh_token: &str,
+    store: &EjRevocationStore,
+    connection: &DbConnection,
+) -> Result<EjTokens> {
+    let hash = hash_refresh_token(refresh_token);
+    let Some(stored) = EjRefreshToken::rotate(&hash, connection)? else {
+        if let Ok(stored) = EjRefreshToken::fetch_by_hash(&hash, connection)
+            && stored.is_rotated()
+        {
+            warn!("Rotated refresh token {} was used again", stored.id);
+            revoke_owner_tokens(&stored, store, connection)?;
+        }
+        return Err(Error::RefreshTokenInvalid);
+    };
+
+    match (stored.ejclient_id, stored.ejbuilder_id) {
+        (Some(client_id), _) => {
+            let client = fetch_client(&client_id, connection)?;
+            let session = match stored.ejsession_id {
+                Some(session_id) => EjSession::fetch_by_id(&session_id, connection)?,
+                None => start_session(&client_id, connection)?,
+            };
+            if session.is_revoked() || session.is_expired() {
+                return Err(Error::RefreshTokenInvalid);
+            }
+            let permissions = resolve_permissions(&client, connection)?;
+            let body = generate_token(&W::from(client).0, permissions, &session.id)?;
+            let refresh_token = issue_client_refresh_token(&session, connection)?;
+            Ok(W::<EjTokens>::new(body, refresh_token).0)
+        }
+        (None, Some(builder_id)) => {
+            fetch_active_builder(&builder_id, connection)?;
+            issue_builder_tokens(&builder_id, connection)
+        }
+        (None, None) => Err(Error::RefreshTokenInvalid),
+    }
+}
+
+/// Revokes a refresh token.
+///
+/// Revoking an unknown or already revoked token is a no-op.
+pub fn revoke_refresh_token(refresh_token: &str, connection: &DbConnection) -> Result<()> {
+    EjRefreshToken::consume(&hash_refresh_token(refresh_token), connection)?;
+    Ok(())
+}
+
+/// Revokes every refresh token and session of the owner of `token`.
+///
+/// The access tokens of the revoked sessions are rejected right away.
+fn revoke_owner_tokens(
+    token: &EjRefreshToken,
+    store: &EjRevocationStore,
+    connection: &DbConnection,
+) -> Result<()> {
+    if let Some(client_id) = &token.ejclient_id {
+        EjRefreshToken::revoke_all_for_client(client_id, connection)?;
+        for session in EjSession::revoke_all_for_client(client_id, connection)? {
+            store.revoke_session(session.id, connection)?;
+        }
+    }
+    if let Some(builder_id) = &token.ejbuilder_id {
+        EjRefreshToken::revoke_all_for_builder(builder_id, connection)?;
+    }
+    Ok(())
+}
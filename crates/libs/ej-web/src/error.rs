@@ -1,12 +1,20 @@
 //! Main crate error types for ej-web.
 //!
 //! This module defines the error types used throughout the ej-web library,
-//! including HTTP response mapping for API endpoints.
+//! including HTTP response mapping for API endpoints. Errors are returned as
+//! RFC 7807 `application/problem+json` bodies carrying a stable error code.
 
-use axum::{Json, http::StatusCode, response::IntoResponse};
-use serde_json::json;
+use axum::{
+    Json,
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use ej_auth::secret_hash::SecretViolation;
+use serde::Serialize;
 use tracing::error;
 
+use crate::{quota::QuotaExceeded, request_id::current_request_id};
+
 /// Main error type for the ej-web library.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -38,6 +46,58 @@ pub enum Error {
     #[error("No builders available")]
     NoBuildersAvailable,
 
+    /// The requested builder doesn't exist.
+    #[error("Builder not found")]
+    BuilderNotFound,
+
+    /// The requested client doesn't exist.
+    #[error("Client not found")]
+    ClientNotFound,
+
+    /// The client still owns builders that aren't revoked and can't be deleted.
+    #[error("Client has builders")]
+    ClientHasBuilders,
+
+    /// The requested permission doesn't exist.
+    #[error("Permission not found")]
+    PermissionNotFound,
+
+    /// The requested role doesn't exist.
+    #[error("Role not found")]
+    RoleNotFound,
+
+    /// The requested session doesn't exist or belongs to another client.
+    #[error("Session not found")]
+    SessionNotFound,
+
+    /// The builder has been revoked and can no longer authenticate.
+    #[error("Builder revoked")]
+    BuilderRevoked,
+
+    /// The requested artifact doesn't exist.
+    #[error("Artifact not found")]
+    ArtifactNotFound,
+
+    /// The requested job doesn't exist.
+    #[error("Job not found")]
+    JobNotFound,
+
+    /// The requested board config doesn't exist.
+    #[error("Board config not found")]
+    BoardConfigNotFound,
+
+    /// A message for a builder was dropped because its queue stayed full.
+    #[error("Builder queue full")]
+    BuilderQueueFull,
+
+    /// The builder's WebSocket connection is closed.
+    #[error("Builder disconnected")]
+    BuilderDisconnected,
+
+    /// The builder speaks a different WebSocket protocol version than the dispatcher.
+    #[error("WebSocket protocol version mismatch")]
+    WsProtocolVersionMismatch,
+
     /* Api Errors */
     /// API access is forbidden for the current user.
     #[error("API Forbidden")]
@@ -58,47 +118,272 @@ pub enum Error {
     /// Request context is missing.
     #[error("Context Missing")]
     CtxMissing,
+
+    /// The API key doesn't exist or belongs to another client.
+    #[error("API Key Not Found")]
+    ApiKeyNotFound,
+
+    /// The refresh token is unknown, expired or was already used.
+    #[error("Invalid Refresh Token")]
+    RefreshTokenInvalid,
+
+    /// The pagination query parameters couldn't be parsed.
+    #[error("Invalid Pagination")]
+    InvalidPagination,
+
+    /// The list can't be sorted by the requested field.
+    #[error("Invalid Sort Field")]
+    InvalidSortField,
+
+    /// A search was requested without any text to search for.
+    #[error("Empty Search Query")]
+    EmptySearchQuery,
+
+    /// The client went over one of its dispatch quotas.
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(QuotaExceeded),
 }
 
-impl IntoResponse for Error {
-    fn into_response(self) -> axum::response::Response {
-        error!("Creating API error response for error: {:?}", self);
-        let (status, message) = match self {
-            Error::WrongCredentials => (StatusCode::UNAUTHORIZED, "Invalid credentials"),
-            Error::MissingCredentials | Error::CtxMissing => {
-                (StatusCode::UNAUTHORIZED, "Missing credentials")
-            }
-            Error::ApiForbidden => (StatusCode::FORBIDDEN, "Access forbidden"),
-            Error::InvalidJobType => (StatusCode::BAD_REQUEST, "Invalid job type"),
-            Error::NoBuildersAvailable => (StatusCode::NOT_FOUND, "No builders available"),
+/// Media type of the error responses.
+pub const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Prefix of the `type` URI of every error response, followed by the error code.
+pub const PROBLEM_TYPE_PREFIX: &str = "urn:ej:error:";
+
+/// Error response body following RFC 7807 (`application/problem+json`).
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiProblem {
+    /// URI identifying the kind of error, `urn:ej:error:<code>`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Human readable summary of the error.
+    pub title: &'static str,
+    /// HTTP status code.
+    pub status: u16,
+    /// Stable machine-readable error code.
+    pub code: &'static str,
+    /// Explanation specific to this occurrence of the error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// ID of the request that failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Password policy rules broken by a rejected secret.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violations: Option<Vec<SecretViolation>>,
+}
+
+impl Error {
+    /// HTTP status, stable error code and summary returned to API clients.
+    ///
+    /// Internal errors all share the `internal_error` code so their details
+    /// don't leak, they're logged instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use axum::http::StatusCode;
+    /// use ej_web::error::Error;
+    ///
+    /// let (status, code, _) = Error::ClientNotFound.problem();
+    /// assert_eq!(status, StatusCode::NOT_FOUND);
+    /// assert_eq!(code, "client_not_found");
+    /// ```
+    pub fn problem(&self) -> (StatusCode, &'static str, &'static str) {
+        match self {
+            Error::WrongCredentials => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_credentials",
+                "Invalid credentials",
+            ),
+            Error::MissingCredentials | Error::CtxMissing => (
+                StatusCode::UNAUTHORIZED,
+                "missing_credentials",
+                "Missing credentials",
+            ),
+            Error::ApiForbidden => (StatusCode::FORBIDDEN, "forbidden", "Access forbidden"),
+            Error::InvalidJobType => (
+                StatusCode::BAD_REQUEST,
+                "invalid_job_type",
+                "Invalid job type",
+            ),
+            Error::InvalidPagination => (
+                StatusCode::BAD_REQUEST,
+                "invalid_pagination",
+                "Invalid pagination parameters",
+            ),
+            Error::InvalidSortField => (
+                StatusCode::BAD_REQUEST,
+                "invalid_sort_field",
+                "Unsupported sort field",
+            ),
+            Error::EmptySearchQuery => (
+                StatusCode::BAD_REQUEST,
+                "empty_search_query",
+                "Empty search query",
+            ),
+            Error::QuotaExceeded(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "quota_exceeded",
+                "Dispatch quota exceeded",
+            ),
+            Error::NoBuildersAvailable => (
+                StatusCode::NOT_FOUND,
+                "no_builders_available",
+                "No builders available",
+            ),
+            Error::BuilderNotFound => (
+                StatusCode::NOT_FOUND,
+                "builder_not_found",
+                "Builder not found",
+            ),
+            Error::BuilderRevoked => (
+                StatusCode::UNAUTHORIZED,
+                "builder_revoked",
+                "Builder revoked",
+            ),
+            Error::RefreshTokenInvalid => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_refresh_token",
+                "Invalid refresh token",
+            ),
+            Error::ClientNotFound => (
+                StatusCode::NOT_FOUND,
+                "client_not_found",
+                "Client not found",
+            ),
+            Error::ClientHasBuilders => (
+                StatusCode::CONFLICT,
+                "client_has_builders",
+                "Client still owns builders that aren't revoked",
+            ),
+            Error::PermissionNotFound => (
+                StatusCode::NOT_FOUND,
+                "permission_not_found",
+                "Permission not found",
+            ),
+            Error::RoleNotFound => (StatusCode::NOT_FOUND, "role_not_found", "Role not found"),
+            Error::SessionNotFound => (
+                StatusCode::NOT_FOUND,
+                "session_not_found",
+                "Session not found",
+            ),
+            Error::ArtifactNotFound => (
+                StatusCode::NOT_FOUND,
+                "artifact_not_found",
+                "Artifact not found",
+            ),
+            Error::JobNotFound => (StatusCode::NOT_FOUND, "job_not_found", "Job not found"),
+            Error::BoardConfigNotFound => (
+                StatusCode::NOT_FOUND,
+                "board_config_not_found",
+                "Board config not found",
+            ),
+            Error::ApiKeyNotFound => (
+                StatusCode::NOT_FOUND,
+                "api_key_not_found",
+                "API key not found",
+            ),
+            Error::BuilderQueueFull => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "builder_queue_full",
+                "Builder is overloaded",
+            ),
+            Error::BuilderDisconnected => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "builder_disconnected",
+                "Builder disconnected",
+            ),
+            Error::WsProtocolVersionMismatch => (
+                StatusCode::UPGRADE_REQUIRED,
+                "ws_protocol_version_mismatch",
+                "WebSocket protocol version mismatch",
+            ),
             Error::Auth(err) => match err {
-                ej_auth::error::Error::InvalidToken => {
-                    (StatusCode::UNAUTHORIZED, "Invalid authentication token")
-                }
-                ej_auth::error::Error::TokenMissing => {
-                    (StatusCode::UNAUTHORIZED, "Authentication required")
-                }
-                ej_auth::error::Error::TokenExpired => {
-                    (StatusCode::UNAUTHORIZED, "Authentication token expired")
-                }
+                ej_auth::error::Error::InvalidToken | ej_auth::error::Error::JwtKeyUnknown(_) => (
+                    StatusCode::UNAUTHORIZED,
+                    "invalid_token",
+                    "Invalid authentication token",
+                ),
+                ej_auth::error::Error::TokenMissing => (
+                    StatusCode::UNAUTHORIZED,
+                    "token_missing",
+                    "Authentication required",
+                ),
+                ej_auth::error::Error::TokenExpired => (
+                    StatusCode::UNAUTHORIZED,
+                    "token_expired",
+                    "Authentication token expired",
+                ),
+                ej_auth::error::Error::TokenRevoked => (
+                    StatusCode::UNAUTHORIZED,
+                    "token_revoked",
+                    "Authentication token revoked",
+                ),
+                ej_auth::error::Error::InvalidSignature => (
+                    StatusCode::UNAUTHORIZED,
+                    "invalid_signature",
+                    "Invalid message signature",
+                ),
+                ej_auth::error::Error::WeakSecret(_) => (
+                    StatusCode::BAD_REQUEST,
+                    "weak_secret",
+                    "Secret doesn't satisfy the password policy",
+                ),
                 ej_auth::error::Error::TokenCreation(_)
-                | ej_auth::error::Error::PasswordHash(_) => {
-                    (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
-                }
+                | ej_auth::error::Error::TokenDurationOutOfRange
+                | ej_auth::error::Error::JwtConfigMissing(_)
+                | ej_auth::error::Error::JwtAlgorithmUnsupported(_)
+                | ej_auth::error::Error::JwtKeyRead(..)
+                | ej_auth::error::Error::JwtSigningKeyMissing
+                | ej_auth::error::Error::JwtPublicKeyMissing
+                | ej_auth::error::Error::JwtKeyIdInUse(_)
+                | ej_auth::error::Error::JwtKeyCurrent(_)
+                | ej_auth::error::Error::PasswordHash(_) => (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "internal_error",
+                    "Internal server error",
+                ),
             },
             Error::AuthTokenCreation
             | Error::IO(_)
             | Error::InternalErrorDispatchingJob
             | Error::Json(_)
-            | Error::Models(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
-        };
+            | Error::Models(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Internal server error",
+            ),
+        }
+    }
+}
 
-        let body = Json(json!({
-            "error": {
-                "message": message,
-                "status": status.as_u16()
-            }
-        }));
-        (status, body).into_response()
+impl IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        error!("Creating API error response for error: {:?}", self);
+        let (status, code, title) = self.problem();
+        let detail = match &self {
+            Error::QuotaExceeded(quota) => Some(quota.to_string()),
+            _ => None,
+        };
+        let violations = match self {
+            Error::Auth(ej_auth::error::Error::WeakSecret(violations)) => Some(violations),
+            _ => None,
+        };
+        let problem = ApiProblem {
+            kind: format!("{PROBLEM_TYPE_PREFIX}{code}"),
+            title,
+            status: status.as_u16(),
+            code,
+            detail,
+            request_id: current_request_id(),
+            violations,
+        };
+        (
+            status,
+            [(header::CONTENT_TYPE, PROBLEM_JSON_CONTENT_TYPE)],
+            Json(problem),
+        )
+            .into_response()
     }
 }
@@ -3,13 +3,24 @@
 //! This library provides authentication, request context, and web-specific
 //! models and utilities for building HTTP APIs and web services.
 
+pub mod audit;
 pub mod auth_token;
 pub mod ctx;
+pub mod ejapi_key;
+pub mod ejartifact;
+pub mod ejbuilder;
 pub mod ejclient;
 pub mod ejconfig;
 pub mod ejconnected_builder;
 pub mod ejjob;
+pub mod ejsession;
+pub mod ejstats;
+pub mod ejtoken;
 pub mod error;
 pub mod mw_auth;
+pub mod pagination;
 pub mod prelude;
+pub mod quota;
+pub mod request_id;
+pub mod revocation;
 pub mod traits;
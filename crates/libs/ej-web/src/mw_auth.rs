@@ -10,7 +10,10 @@ use axum::{
     response::Response,
 };
 
-use super::ctx::Ctx;
+use ej_models::db::connection::DbConnection;
+
+use super::ctx::{Ctx, CtxWho};
+use crate::ejbuilder::fetch_active_builder;
 
 /// Middleware that requires authentication for a route.
 ///
@@ -99,3 +102,38 @@ macro_rules! require_permission {
         axum::middleware::from_fn_with_state($permission, mw_require_permission)
     }};
 }
+
+/// Middleware that rejects requests made by revoked builders.
+///
+/// Builder tokens are long lived, so the token alone isn't enough to know whether
+/// the builder is still allowed to talk to the dispatcher. Requests coming from
+/// clients are let through untouched.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use axum::{Router, routing::get};
+/// use ej_web::mw_auth::mw_require_active_builder;
+/// # use ej_models::db::connection::DbConnection;
+///
+/// # fn example(connection: DbConnection) {
+/// let app: Router<()> = Router::new()
+///     .route("/builder", get(builder_handler))
+///     .layer(axum::middleware::from_fn_with_state(connection, mw_require_active_builder));
+/// # }
+///
+/// async fn builder_handler() -> &'static str {
+///     "Only active builders get here"
+/// }
+/// ```
+pub async fn mw_require_active_builder(
+    State(connection): State<DbConnection>,
+    ctx: Ctx,
+    req: Request,
+    next: Next,
+) -> Result<Response> {
+    if ctx.who == CtxWho::Builder {
+        fetch_active_builder(&ctx.client.id, &connection)?;
+    }
+    Ok(next.run(req).await)
+}
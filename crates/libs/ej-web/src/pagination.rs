@@ -0,0 +1,143 @@
+//! Pagination and sorting of list endpoints.
+//!
+//! Handlers take a [`Pagination`] extractor, turn it into a page of the
+//! listed model with [`Pagination::db_page`] and answer with a [`Page`],
+//! which reports the total number of items in the `X-Total-Count` header.
+
+use axum::{
+    Json,
+    extract::{FromRequestParts, Query},
+    http::{HeaderName, HeaderValue, request::Parts},
+    response::{IntoResponse, Response},
+};
+use ej_dispatcher_sdk::ejpage::{EjPageQuery, EjSortOrder};
+use ej_models::db::pagination::DbPage;
+use serde::Serialize;
+
+use crate::prelude::*;
+
+/// Number of items per page when the request doesn't say.
+pub const DEFAULT_PER_PAGE: u32 = 50;
+/// Maximum number of items a single page can hold.
+pub const MAX_PER_PAGE: u32 = 200;
+/// Response header holding the number of items across every page.
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Page requested by a list endpoint's caller.
+///
+/// Pages start at 1. Page 0 is treated as the first page and `per_page` is
+/// kept between 1 and [`MAX_PER_PAGE`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pagination {
+    /// Page number, starting at 1.
+    pub page: u32,
+    /// Number of items per page.
+    pub per_page: u32,
+    /// Field the items are sorted by, the endpoint's default order if unset.
+    pub sort: Option<String>,
+    /// Direction the items are sorted in.
+    pub order: EjSortOrder,
+}
+
+/// A page of items, sent as a JSON array along with the `X-Total-Count` header.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    /// Items on the page.
+    pub items: Vec<T>,
+    /// Number of items across every page.
+    pub total: i64,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self::from(EjPageQuery::default())
+    }
+}
+
+impl From<EjPageQuery> for Pagination {
+    fn from(query: EjPageQuery) -> Self {
+        Self {
+            page: query.page.unwrap_or(1).max(1),
+            per_page: query
+                .per_page
+                .unwrap_or(DEFAULT_PER_PAGE)
+                .clamp(1, MAX_PER_PAGE),
+            sort: query.sort,
+            order: query.order.unwrap_or_default(),
+        }
+    }
+}
+
+impl Pagination {
+    /// Number of items skipped before the page.
+    pub fn offset(&self) -> i64 {
+        i64::from(self.page - 1) * i64::from(self.per_page)
+    }
+
+    /// Maximum number of items on the page.
+    pub fn limit(&self) -> i64 {
+        self.per_page.into()
+    }
+
+    /// The page to fetch from the database.
+    ///
+    /// Fails with [`Error::InvalidSortField`] if the request sorts by a field
+    /// the listed model doesn't support.
+    ///
+    /// # Arguments
+    /// * `sort_fields` - Fields the listed model can be sorted by
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_dispatcher_sdk::ejpage::{EjPageQuery, EjSortOrder};
+    /// use ej_web::pagination::Pagination;
+    ///
+    /// let pagination = Pagination::from(EjPageQuery {
+    ///     page: Some(3),
+    ///     per_page: Some(20),
+    ///     sort: Some("name".to_string()),
+    ///     order: Some(EjSortOrder::Desc),
+    /// });
+    /// let page = pagination.db_page(&["name", "created_at"]).unwrap();
+    /// assert_eq!((page.offset, page.limit), (40, 20));
+    /// assert!(page.descending);
+    ///
+    /// assert!(pagination.db_page(&["created_at"]).is_err());
+    /// ```
+    pub fn db_page(&self, sort_fields: &[&str]) -> Result<DbPage> {
+        if let Some(sort) = &self.sort
+            && !sort_fields.contains(&sort.as_str())
+        {
+            return Err(Error::InvalidSortField);
+        }
+        Ok(DbPage {
+            offset: self.offset(),
+            limit: self.limit(),
+            sort: self.sort.clone(),
+            descending: self.order == EjSortOrder::Desc,
+        })
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Pagination {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let Query(query) = Query::<EjPageQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| Error::InvalidPagination)?;
+        Ok(Self::from(query))
+    }
+}
+
+impl<T: Serialize> IntoResponse for Page<T> {
+    fn into_response(self) -> Response {
+        let mut response = Json(self.items).into_response();
+        response.headers_mut().insert(
+            HeaderName::from_static(TOTAL_COUNT_HEADER),
+            HeaderValue::from(self.total),
+        );
+        response
+    }
+}
@@ -0,0 +1,113 @@
+//! Dispatch quotas limiting how many jobs a single client can submit.
+//!
+//! Quotas keep one client from monopolizing a shared dispatcher. They're
+//! checked against the jobs recorded for the client, in the transaction
+//! saving the new job. Jobs dispatched through the Unix socket don't belong
+//! to any client and aren't limited.
+
+use std::fmt;
+
+use chrono::{TimeDelta, Utc};
+use diesel::PgConnection;
+use ej_models::{client::ejclient::EjClient, job::ejjob::EjJobDb};
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+/// Limits on the jobs a single client can dispatch, unlimited when unset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DispatchQuota {
+    /// Maximum number of jobs a client can have waiting or running at once.
+    pub max_queued_jobs: Option<u32>,
+    /// Maximum number of jobs a client can dispatch over the last hour.
+    pub max_jobs_per_hour: Option<u32>,
+}
+
+/// The quota a dispatch was rejected by, along with its limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaExceeded {
+    /// The client already has this many jobs waiting or running.
+    QueuedJobs(u32),
+    /// The client already dispatched this many jobs over the last hour.
+    JobsPerHour(u32),
+}
+
+impl DispatchQuota {
+    /// Whether the quota doesn't limit anything.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_queued_jobs.is_none() && self.max_jobs_per_hour.is_none()
+    }
+
+    /// Checks that a client can dispatch another job.
+    ///
+    /// Fails with [`Error::QuotaExceeded`] if the new job would go over one
+    /// of the limits. The client's row is locked until the transaction of
+    /// `conn` ends, so concurrent dispatches of the client are checked one
+    /// after the other: the job must be saved in the same transaction for
+    /// the next check to count it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use ej_web::quota::DispatchQuota;
+    /// use uuid::Uuid;
+    /// # use ej_models::db::connection::DbConnection;
+    ///
+    /// # fn example(client_id: Uuid, connection: &DbConnection) -> Result<(), Box<dyn std::error::Error>> {
+    /// let quota = DispatchQuota {
+    ///     max_queued_jobs: Some(5),
+    ///     max_jobs_per_hour: Some(60),
+    /// };
+    /// connection.transaction(|conn| {
+    ///     quota.check_in(&client_id, conn)?;
+    ///     // Save the job on `conn`
+    ///     Ok::<_, ej_web::error::Error>(())
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn check_in(&self, client_id: &Uuid, conn: &mut PgConnection) -> Result<()> {
+        if self.is_unlimited() {
+            return Ok(());
+        }
+        EjClient::fetch_for_update(client_id, conn)?;
+        if let Some(limit) = self.max_queued_jobs
+            && EjJobDb::count_queued_by_client_in(client_id, conn)? >= i64::from(limit)
+        {
+            return Err(Error::QuotaExceeded(QuotaExceeded::QueuedJobs(limit)));
+        }
+        if let Some(limit) = self.max_jobs_per_hour {
+            let since = Utc::now() - TimeDelta::hours(1);
+            if EjJobDb::count_by_client_since_in(client_id, since, conn)? >= i64::from(limit) {
+                return Err(Error::QuotaExceeded(QuotaExceeded::JobsPerHour(limit)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DispatchQuota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max_queued_jobs {
+            Some(limit) => write!(f, "{limit} queued jobs")?,
+            None => write!(f, "unlimited queued jobs")?,
+        }
+        match self.max_jobs_per_hour {
+            Some(limit) => write!(f, ", {limit} jobs per hour"),
+            None => write!(f, ", unlimited jobs per hour"),
+        }
+    }
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaExceeded::QueuedJobs(limit) => {
+                write!(f, "at most {limit} jobs can be queued at once")
+            }
+            QuotaExceeded::JobsPerHour(limit) => {
+                write!(f, "at most {limit} jobs can be dispatched per hour")
+            }
+        }
+    }
+}
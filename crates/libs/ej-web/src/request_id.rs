@@ -0,0 +1,81 @@
+//! Request IDs used to correlate responses with the logs.
+//!
+//! Every request gets an ID, either the one sent by the caller in the
+//! `X-Request-Id` header or a freshly generated one. The ID is recorded in the
+//! tracing span of the request, echoed back in the `X-Request-Id` response
+//! header and included in error responses.
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{Instrument, info_span};
+use uuid::Uuid;
+
+/// Header carrying the request ID.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest request ID accepted from callers, longer ones are replaced.
+pub const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// ID of the request being handled, available to handlers as an extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// The ID of the request being handled by the current task, if any.
+///
+/// Used to include the request ID in error responses, which are built
+/// without access to the request.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Middleware assigning an ID to every request.
+///
+/// Callers can send their own ID in the `X-Request-Id` header as long as it's
+/// printable ASCII and at most [`MAX_REQUEST_ID_LENGTH`] characters long,
+/// otherwise a random one is generated. Add it as the outermost layer so the
+/// ID is available everywhere.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use axum::{Router, routing::get};
+/// use ej_web::request_id::mw_request_id;
+///
+/// let app: Router<()> = Router::new()
+///     .route("/", get(|| async { "Hello" }))
+///     .layer(axum::middleware::from_fn(mw_request_id));
+/// ```
+pub async fn mw_request_id(mut req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(String::from)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    req.extensions_mut().insert(RequestId(id.clone()));
+    let span = info_span!("request", request_id = %id);
+    let mut response = REQUEST_ID
+        .scope(id.clone(), next.run(req).instrument(span))
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id.bytes().all(|byte| byte.is_ascii_graphic())
+}
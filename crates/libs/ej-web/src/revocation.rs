@@ -0,0 +1,164 @@
+//! Revocation list for access tokens.
+//!
+//! Revoked tokens are stored in the database so the list survives restarts,
+//! and cached in memory so checking every request doesn't hit the database.
+//! Tokens are identified by their `jti` claim and only need to be remembered
+//! until they expire. Revoked sessions are kept in the same list, identified by
+//! the `sid` claim of their tokens.
+
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use chrono::{DateTime, Utc};
+use ej_models::{
+    auth::ejrevoked_token::{EjRevokedToken, EjRevokedTokenCreate},
+    db::connection::DbConnection,
+};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    auth_token::{AuthToken, decode_token},
+    ctx::{Ctx, CtxWho, ctx_client::ACCESS_TOKEN_EXPIRATION_TIME},
+    prelude::*,
+};
+
+/// Revoked access tokens, shared between every request handler.
+#[derive(Debug, Clone, Default)]
+pub struct EjRevocationStore {
+    revoked: Arc<RwLock<HashMap<Uuid, DateTime<Utc>>>>,
+}
+
+impl EjRevocationStore {
+    /// Loads the tokens revoked so far from the database.
+    pub fn load(connection: &DbConnection) -> Result<Self> {
+        let revoked = EjRevokedToken::fetch_active(connection)?
+            .into_iter()
+            .map(|token| (token.jti, token.expires_at))
+            .collect();
+        Ok(Self {
+            revoked: Arc::new(RwLock::new(revoked)),
+        })
+    }
+
+    /// Whether the token with this `jti` claim was revoked.
+    pub fn is_revoked(&self, jti: &Uuid) -> bool {
+        self.revoked
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains_key(jti)
+    }
+
+    /// Revokes a token, taking effect immediately.
+    ///
+    /// Expired tokens are forgotten along the way since they're rejected anyway.
+    ///
+    /// # Arguments
+    /// * `jti` - The `jti` claim of the token
+    /// * `expires_at` - When the token expires
+    pub fn revoke(
+        &self,
+        jti: Uuid,
+        expires_at: DateTime<Utc>,
+        connection: &DbConnection,
+    ) -> Result<()> {
+        EjRevokedTokenCreate { jti, expires_at }.save(connection)?;
+        EjRevokedToken::delete_expired(connection)?;
+
+        let now = Utc::now();
+        let mut revoked = self.revoked.write().unwrap_or_else(PoisonError::into_inner);
+        revoked.retain(|_, expires_at| *expires_at > now);
+        revoked.insert(jti, expires_at);
+        Ok(())
+    }
+
+    /// Revokes every access token issued for a session, taking effect immediately.
+    ///
+    /// The session ID is remembered for as long as the access tokens issued
+    /// for it stay valid.
+    pub fn revoke_session(&self, session_id: Uuid, connection: &DbConnection) -> Result<()> {
+        self.revoke(
+            session_id,
+            Utc::now() + ACCESS_TOKEN_EXPIRATION_TIME,
+            connection,
+        )
+    }
+
+    /// Number of revoked tokens that are still remembered.
+    pub fn len(&self) -> usize {
+        self.revoked
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Whether no token is currently revoked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Revokes an access token on behalf of `ctx`.
+///
+/// Anyone can revoke their own tokens. Revoking someone else's token requires
+/// `client.manage` for client tokens and `builder.manage` for builder tokens.
+/// API keys can't be used to revoke tokens.
+/// Expired tokens are left alone since they're no longer accepted.
+///
+/// # Examples
+///
+/// ```rust
+/// use ej_web::{ctx::Ctx, revocation::{EjRevocationStore, revoke_access_token}};
+/// # use ej_models::db::connection::DbConnection;
+///
+/// # fn example(ctx: &Ctx, connection: &DbConnection) -> Result<(), Box<dyn std::error::Error>> {
+/// let store = EjRevocationStore::load(connection)?;
+/// revoke_access_token(ctx, "leaked.jwt.token", &store, connection)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn revoke_access_token(
+    ctx: &Ctx,
+    token: &str,
+    store: &EjRevocationStore,
+    connection: &DbConnection,
+) -> Result<()> {
+    if ctx.api_key.is_some() {
+        return Err(Error::ApiForbidden);
+    }
+    match decode_token(token) {
+        Ok(token) => revoke_auth_token(ctx, &token, store, connection),
+        Err(Error::Auth(ej_auth::error::Error::TokenExpired)) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Revokes an access token already decoded, on behalf of `ctx`.
+///
+/// Follows the same rules as [`revoke_access_token`].
+pub fn revoke_auth_token(
+    ctx: &Ctx,
+    token: &AuthToken,
+    store: &EjRevocationStore,
+    connection: &DbConnection,
+) -> Result<()> {
+    if ctx.api_key.is_some() {
+        return Err(Error::ApiForbidden);
+    }
+    let permission = match token.who {
+        CtxWho::Client => "client.manage",
+        CtxWho::Builder => "builder.manage",
+    };
+    if token.sub != ctx.client.id && !ctx.permissions.contains(permission) {
+        return Err(Error::ApiForbidden);
+    }
+
+    let expires_at = DateTime::from_timestamp(token.claims.exp, 0)
+        .ok_or(Error::Auth(ej_auth::error::Error::InvalidToken))?;
+    store.revoke(token.claims.jti, expires_at, connection)?;
+    info!(
+        "Revoked token {} of {:?} {}",
+        token.claims.jti, token.who, token.sub
+    );
+    Ok(())
+}
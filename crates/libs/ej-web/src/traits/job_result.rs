@@ -14,4 +14,8 @@ pub trait EjJobResult {
 
     /// Returns the builder ID that produced this result.
     fn builder_id(&self) -> Uuid;
+
+    /// Returns the board configs the builder skipped because the job doesn't
+    /// target them or their board is unavailable.
+    fn skipped(&self) -> Vec<Uuid>;
 }
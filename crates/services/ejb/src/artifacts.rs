@@ -0,0 +1,130 @@
+//! Upload of build artifacts to EJD.
+//!
+//! Each board config may list the files its job produces in `artifacts`, as
+//! paths or as patterns with `*` and `?` wildcards in the file name. The
+//! matching files are collected once the job is done, after the build of
+//! build jobs and after the run of build and run jobs, and streamed to EJD
+//! over the WebSocket connection: an `ArtifactStart` message, the file
+//! content split in binary chunks and an `ArtifactEnd` message.
+
+use std::path::{Path, PathBuf};
+
+use ej_config::{ej_board_config::EjBoardConfig, ej_config::EjConfig, glob};
+use ej_dispatcher_sdk::ejws_message::{EJ_WS_ARTIFACT_CHUNK_SIZE, EjWsClientMessage, EjWsPayload};
+use tokio::{fs::File, io::AsyncReadExt, sync::mpsc::Sender};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::common::JobStop;
+use crate::prelude::*;
+
+/// Resolves an artifact path, relative paths being relative to the library path.
+pub fn artifact_path(board_config: &EjBoardConfig, artifact: &str) -> PathBuf {
+    let path = Path::new(artifact);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        Path::new(&board_config.library_path).join(path)
+    }
+}
+
+/// The existing files matching an artifact pattern, sorted by path.
+pub fn matching_files(board_config: &EjBoardConfig, artifact: &str) -> Vec<PathBuf> {
+    let pattern = artifact_path(board_config, artifact);
+    match glob::expand(&pattern) {
+        Ok(paths) => paths.into_iter().filter(|path| path.is_file()).collect(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => {
+            warn!("Failed to expand artifact {} - {err}", pattern.display());
+            Vec::new()
+        }
+    }
+}
+
+/// Uploads the artifacts of every board config.
+///
+/// A pattern matching no file or an unreadable artifact is logged and
+/// skipped, it doesn't fail the job.
+/// Board configs cancelled during the build have nothing to upload.
+///
+/// # Arguments
+/// * `config` - Builder configuration listing the artifacts
+/// * `job_id` - Job that produced the artifacts
+/// * `stop` - Cancellation flags of the job
+/// * `tx` - Channel forwarding the payloads to the WebSocket connection
+pub async fn upload_artifacts(
+    config: &EjConfig,
+    job_id: Uuid,
+    stop: &JobStop,
+    tx: &Sender<EjWsPayload>,
+) {
+    for board_config in config.boards.iter().flat_map(|board| &board.configs) {
+        if stop.is_board_cancelled(&board_config.id) {
+            continue;
+        }
+        for artifact in &board_config.artifacts {
+            let paths = matching_files(board_config, artifact);
+            if paths.is_empty() {
+                warn!("No artifact of {} matches `{artifact}`", board_config.name);
+            }
+            for path in paths {
+                match upload_artifact(&path, job_id, board_config.id, tx).await {
+                    Ok(size) => info!("Uploaded artifact {} ({size} bytes)", path.display()),
+                    Err(err) => error!("Failed to upload artifact {} - {err}", path.display()),
+                }
+            }
+        }
+    }
+}
+
+/// Streams a single artifact to EJD, returning its size.
+async fn upload_artifact(
+    path: &Path,
+    job_id: Uuid,
+    board_config_id: Uuid,
+    tx: &Sender<EjWsPayload>,
+) -> Result<u64> {
+    let mut file = File::open(path).await?;
+    let size = file.metadata().await?.len();
+    let artifact_id = Uuid::new_v4();
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| artifact_id.to_string());
+
+    send(
+        tx,
+        &EjWsClientMessage::ArtifactStart {
+            artifact_id,
+            job_id,
+            board_config_id,
+            name,
+            size,
+        },
+    )
+    .await?;
+
+    let mut buffer = vec![0; EJ_WS_ARTIFACT_CHUNK_SIZE];
+    let mut remaining = size;
+    while remaining > 0 {
+        let len = file.read(&mut buffer).await?;
+        if len == 0 {
+            break;
+        }
+        let len = len.min(remaining as usize);
+        tx.send(EjWsPayload::artifact_chunk(artifact_id, &buffer[..len]))
+            .await
+            .map_err(|_| Error::ArtifactChannelClosed)?;
+        remaining -= len as u64;
+    }
+
+    send(tx, &EjWsClientMessage::ArtifactEnd { artifact_id }).await?;
+    Ok(size)
+}
+
+/// Forwards a control message of the upload.
+async fn send(tx: &Sender<EjWsPayload>, message: &EjWsClientMessage) -> Result<()> {
+    tx.send(EjWsPayload::Text(serde_json::to_string(message)?))
+        .await
+        .map_err(|_| Error::ArtifactChannelClosed)
+}
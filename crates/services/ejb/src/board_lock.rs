@@ -0,0 +1,133 @@
+//! Exclusive access to the boards.
+//!
+//! Two jobs flashing or running the same board at once leave it in an
+//! unknown state, so the run scripts of a board only run while its lock is
+//! held. Locks are files of the lock directory, one per board, holding an
+//! advisory file lock. They're honoured by every job of the builder as well
+//! as by the other EJB processes sharing the machine, such as `ejb run-local`.
+//! Boards are locked independently, a job waiting for a board doesn't hold
+//! back the others.
+//!
+//! The boards locked by the builder are reported to EJD as they change.
+
+use std::{
+    collections::BTreeSet,
+    fs::{File, OpenOptions, TryLockError},
+    path::PathBuf,
+    sync::{Arc, atomic::Ordering},
+    time::Duration,
+};
+
+use ej_config::ej_board::EjBoard;
+use tokio::sync::watch;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::common::JobStop;
+use crate::prelude::*;
+
+/// Default directory of the board lock files.
+pub const DEFAULT_LOCK_DIR: &str = "/tmp/ejb-locks";
+
+/// Time between two attempts to lock a board used by another job.
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Locks of the boards of the builder. Cloning shares the locked boards.
+#[derive(Debug, Clone)]
+pub struct BoardLocks {
+    dir: PathBuf,
+    locked: Arc<watch::Sender<BTreeSet<Uuid>>>,
+}
+
+impl BoardLocks {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            locked: Arc::new(watch::Sender::new(BTreeSet::new())),
+        }
+    }
+
+    /// Waits for exclusive access to `board`.
+    ///
+    /// Returns `None` if every board config of the board is stopped while
+    /// waiting, as there's nothing left to run then.
+    pub async fn lock(&self, board: &EjBoard, stop: &JobStop) -> Result<Option<BoardLock>> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.dir.join(lock_file_name(&board.name)))?;
+        let mut waiting = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Error(err)) => return Err(err.into()),
+            }
+            let stopped = board
+                .configs
+                .iter()
+                .all(|board_config| stop.board(&board_config.id).load(Ordering::Relaxed));
+            if stopped {
+                return Ok(None);
+            }
+            if !waiting {
+                info!(
+                    "{} - Waiting for the board, another job is using it",
+                    board.name
+                );
+                waiting = true;
+            }
+            tokio::time::sleep(LOCK_RETRY_INTERVAL).await;
+        }
+        if waiting {
+            info!("{} - Board is free", board.name);
+        }
+        self.locked.send_modify(|locked| {
+            locked.insert(board.id);
+        });
+        Ok(Some(BoardLock {
+            _file: file,
+            board_id: board.id,
+            locked: Arc::clone(&self.locked),
+        }))
+    }
+
+    /// Boards currently locked by the builder, updated as they change.
+    pub fn subscribe(&self) -> watch::Receiver<BTreeSet<Uuid>> {
+        self.locked.subscribe()
+    }
+}
+
+/// Exclusive access to a board, released when dropped.
+#[derive(Debug)]
+pub struct BoardLock {
+    _file: File,
+    board_id: Uuid,
+    locked: Arc<watch::Sender<BTreeSet<Uuid>>>,
+}
+
+impl Drop for BoardLock {
+    fn drop(&mut self) {
+        self.locked.send_modify(|locked| {
+            locked.remove(&self.board_id);
+        });
+    }
+}
+
+/// Name of the lock file of a board, named after it so every process
+/// sharing the config agrees on it.
+fn lock_file_name(board_name: &str) -> String {
+    let name: String = board_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{name}.lock")
+}
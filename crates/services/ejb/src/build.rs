@@ -4,24 +4,32 @@
 //! for each board configuration. The build process:
 //!
 //! 1. Iterates through all board configurations sequentially
-//! 2. Executes the build script for each configuration
+//! 2. Executes the build script for each configuration, between its
+//!    `pre_build` and `post_build` hooks if it has any
 //! 3. Collects build output and logs
 //! 4. Reports build success/failure status
 //!
 //! All build configurations are completed before any run phase begins.
 //! Build scripts are executed sequentially to avoid resource conflicts,
 //! as each build script is expected to utilize all available CPU cores.
-//! Build processes can be cancelled if a stop signal is received.
+//! Build processes can be cancelled if a stop signal is received, either for
+//! the whole job or for a single board config, in which case the remaining
+//! board configs are still built. Board configs whose build is in the build
+//! cache are restored from it instead of being built, without running their
+//! hooks.
 
-use std::sync::{Arc, atomic::AtomicBool};
+use std::{path::Path, time::Duration};
 
 use ej_builder_sdk::Action;
 use ej_config::ej_config::EjConfig;
+use ej_dispatcher_sdk::ejjob::timeouts::EjJobTimeouts;
 use ej_io::runner::RunEvent;
 use tokio::sync::mpsc::channel;
 use tracing::{error, info};
 
-use crate::common::SpawnRunnerArgs;
+use crate::cache::BuildCache;
+use crate::common::{JobStop, SpawnRunnerArgs, run_hook};
+use crate::container::ScriptContainer;
 use crate::prelude::*;
 use crate::run_output::EjRunOutput;
 use crate::{builder::Builder, common::spawn_runner};
@@ -36,24 +44,54 @@ use crate::{builder::Builder, common::spawn_runner};
 /// * `builder` - The builder instance containing configuration and paths
 /// * `config` - The EJ configuration with board definitions
 /// * `output` - Output collector for logs and results
-/// * `stop` - Atomic boolean for cancellation signal
+/// * `stop` - Cancellation flags of the job
+/// * `timeouts` - Build timeouts of the board configs, overriding the ones of their config
+/// * `commit_hash` - Commit being built, builds are only cached when it's known
+/// * `workspace` - Workspace of the job the build scripts run in, if any
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` if all builds succeed, or the first error encountered.
+/// Returns `Ok(())` if all builds that weren't cancelled succeed, or the first
+/// error encountered. A build running past its timeout is killed and fails.
 pub async fn build(
     builder: &Builder,
     config: &EjConfig,
     output: &mut EjRunOutput<'_>,
-    stop: Arc<AtomicBool>,
+    stop: &JobStop,
+    timeouts: &EjJobTimeouts,
+    commit_hash: Option<&str>,
+    workspace: Option<&Path>,
 ) -> Result<()> {
     let board_count = config.boards.len();
 
     for (board_idx, board) in config.boards.iter().enumerate() {
         info!("Board {}/{}: {}", board_idx + 1, board_count, board.name);
         for (config_idx, board_config) in board.configs.iter().enumerate() {
-            let (tx, mut rx) = channel(10);
+            if stop.is_board_cancelled(&board_config.id) {
+                info!("{} - {} Build cancelled", board.name, board_config.name);
+                continue;
+            }
             info!("Config {}: {}", config_idx + 1, board_config.name);
+            let cache_key = match (&builder.build_cache, commit_hash) {
+                (Some(_), Some(commit_hash)) => {
+                    BuildCache::key(commit_hash, board, board_config, workspace)
+                }
+                _ => None,
+            };
+            if let (Some(cache), Some(key)) = (&builder.build_cache, &cache_key)
+                && let Some(logs) = cache.restore(key, board_config).await
+            {
+                info!(
+                    "{} - {} Build restored from cache {key}",
+                    board.name, board_config.name
+                );
+                for line in logs {
+                    output.push_log(board_config.id, line);
+                }
+                continue;
+            }
+            let log_start = output.logs.get(&board_config.id).map_or(0, Vec::len);
+            let (tx, mut rx) = channel(10);
 
             let args = SpawnRunnerArgs {
                 script_name: board_config.build_script.clone(),
@@ -62,9 +100,32 @@ pub async fn build(
                 config_name: board_config.name.clone(),
                 config_path: builder.config_path.clone(),
                 socket_path: builder.socket_path.clone(),
+                env: board.resolve_script_env(board_config)?,
+                script_messages: builder.script_messages.clone(),
+                timeout: timeouts
+                    .for_board(&board_config.name)
+                    .build
+                    .or(board_config.build_timeout.map(Duration::from_secs)),
+                current_dir: workspace.map(Path::to_path_buf),
+                container: ScriptContainer::new(&builder.container_engine, board_config),
             };
-            let stop = Arc::clone(&stop);
-            let handle = spawn_runner(args, tx, stop);
+            if let Some(pre_build) = &board_config.pre_build
+                && !run_hook(
+                    args.clone(),
+                    "Pre-build",
+                    pre_build,
+                    stop.board(&board_config.id),
+                    |line| output.push_log(board_config.id, line),
+                )
+                .await
+            {
+                if stop.is_board_cancelled(&board_config.id) {
+                    info!("{} - {} Build cancelled", board.name, board_config.name);
+                    continue;
+                }
+                return Err(Error::BuildError);
+            }
+            let handle = spawn_runner(args.clone(), tx, stop.board(&board_config.id));
 
             while let Some(event) = rx.recv().await {
                 match event {
@@ -77,26 +138,24 @@ pub async fn build(
                     RunEvent::ProcessCreated => {
                         info!("{} - {} Build started", board.name, board_config.name)
                     }
-                    RunEvent::ProcessEnd(success) => {
+                    RunEvent::ProcessEnd(success, usage) => {
                         if success {
                             info!(
-                                "{} - {} Build ended successfully",
+                                "{} - {} Build ended successfully ({usage})",
                                 board.name, board_config.name
                             );
                         } else {
-                            error!("{} - {} Build failed", board.name, board_config.name);
+                            error!(
+                                "{} - {} Build failed ({usage})",
+                                board.name, board_config.name
+                            );
                         }
                     }
                     RunEvent::ProcessNewOutputLine(line) => {
-                        let key = board_config.id;
-                        match output.logs.get_mut(&key) {
-                            Some(entry) => {
-                                entry.push(line);
-                            }
-                            None => {
-                                output.logs.insert(key, vec![line]);
-                            }
-                        }
+                        output.push_log(board_config.id, line);
+                    }
+                    RunEvent::Timeout => {
+                        error!("{} - {} Build timed out", board.name, board_config.name)
                     }
                 }
             }
@@ -104,11 +163,32 @@ pub async fn build(
                 .await
                 .map_err(|err| Error::ThreadJoin(err))?
                 .ok_or(Error::ProcessExitStatusUnavailable)?;
+            if let Some(post_build) = &board_config.post_build {
+                run_hook(
+                    args,
+                    "Post-build",
+                    post_build,
+                    stop.board(&board_config.id),
+                    |line| output.push_log(board_config.id, line),
+                )
+                .await;
+            }
 
+            if stop.is_board_cancelled(&board_config.id) {
+                info!("{} - {} Build cancelled", board.name, board_config.name);
+                continue;
+            }
             if !exit_status.success() {
                 error!("Build exit status {}", exit_status);
                 return Err(Error::BuildError);
             }
+            if let (Some(cache), Some(key)) = (&builder.build_cache, &cache_key) {
+                let logs = output
+                    .logs
+                    .get(&board_config.id)
+                    .map_or(&[][..], |logs| &logs[log_start..]);
+                cache.store(key, board_config, logs).await;
+            }
         }
     }
     Ok(())
@@ -3,25 +3,42 @@
 //! Provides the main `Builder` struct that manages configuration loading
 //! and local Unix socket communication for child processes. The Builder
 //! sets up a Unix socket server to communicate with spawned build/run scripts.
+//! Log lines and results the scripts send through it are added to their output.
+//! Heartbeats are sent to the scripts so they notice when the builder is gone,
+//! and scripts asked to exit are given a grace period before being killed.
 
+use crate::board_lock::{BoardLocks, DEFAULT_LOCK_DIR};
+use crate::cache::BuildCache;
+use crate::container::DEFAULT_CONTAINER_ENGINE;
 use crate::prelude::*;
-use ej_builder_sdk::BuilderEvent;
+use crate::probe::DEFAULT_PROBE_TIMEOUT;
+use crate::workspace::{DEFAULT_WORKSPACE_DIR, RetentionPolicy, Workspaces};
+use ej_builder_sdk::{
+    BuilderEvent, BuilderMessage, BuilderResponse, HEARTBEAT_INTERVAL,
+    protocol::{FrameReader, write_frame},
+};
 use ej_config::ej_config::{EjConfig, EjUserConfig};
+use ej_io::runner::RunEvent;
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicU32, Ordering},
     },
+    time::Duration,
 };
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::UnixStream,
-    sync::{broadcast, mpsc},
+    net::{UnixStream, unix::OwnedReadHalf},
+    sync::{broadcast, mpsc, watch},
     task::JoinHandle,
+    time::timeout,
 };
 use tracing::{error, info, warn};
 
+/// Default directory of the mirrors of the remotes jobs check out.
+pub const DEFAULT_MIRROR_DIR: &str = "/tmp/ejb-mirrors";
+
 /// Core builder instance that manages configuration and local communication.
 ///
 /// The Builder handles local Unix socket communication with child processes
@@ -37,6 +54,92 @@ pub struct Builder {
     pub socket_path: String,
     /// Channel sender for builder events.
     pub tx: mpsc::Sender<BuilderEvent>,
+    /// Messages of the running scripts.
+    pub script_messages: ScriptMessages,
+    /// How long scripts asked to exit are given before being killed.
+    pub exit_grace_period: Duration,
+    /// Number of scripts connected to the Unix socket.
+    pub connected_scripts: watch::Receiver<u32>,
+    /// Cache successful builds are restored from, disabled if unset.
+    pub build_cache: Option<BuildCache>,
+    /// Directory of the mirrors of the remotes jobs check out.
+    pub mirror_dir: PathBuf,
+    /// Whether Git LFS objects are pulled when checking out jobs.
+    pub lfs: bool,
+    /// Workspaces the jobs run in.
+    pub workspaces: Workspaces,
+    /// Program running the containers of the board configs that have one.
+    pub container_engine: String,
+    /// Time the board probes are given to succeed.
+    pub probe_timeout: Duration,
+    /// Locks giving the jobs exclusive access to the boards.
+    pub board_locks: BoardLocks,
+}
+
+/// Board and board config name of a script.
+type ScriptKey = (String, String);
+
+/// Messages sent by the running scripts through the Unix socket.
+///
+/// The socket isn't tied to a script, so the messages name the board config
+/// they're for. Log lines are added to the output of the script and results
+/// are kept until its run ends. Cloning shares the state.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptMessages {
+    outputs: Arc<Mutex<HashMap<ScriptKey, mpsc::WeakSender<RunEvent>>>>,
+    results: Arc<Mutex<HashMap<ScriptKey, String>>>,
+}
+
+impl ScriptMessages {
+    /// Adds the log lines of the script of a board config to the output sent
+    /// to `tx`, until the run ends.
+    pub fn register(&self, board_name: &str, config_name: &str, tx: &mpsc::Sender<RunEvent>) {
+        let key = (board_name.to_string(), config_name.to_string());
+        self.results
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(&key);
+        let mut outputs = self.outputs.lock().unwrap_or_else(|err| err.into_inner());
+        outputs.retain(|_, output| output.strong_count() > 0);
+        outputs.insert(key, tx.downgrade());
+    }
+
+    /// Takes the results the script of a board config submitted, if any.
+    pub fn take_results(&self, board_name: &str, config_name: &str) -> Option<String> {
+        self.results
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(&(board_name.to_string(), config_name.to_string()))
+    }
+
+    /// Adds a log line to the output of the script of a board config.
+    ///
+    /// Lines of scripts that aren't running are dropped.
+    async fn forward_log(&self, key: ScriptKey, line: String) {
+        let output = self
+            .outputs
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(&key)
+            .and_then(mpsc::WeakSender::upgrade);
+        match output {
+            Some(output) => {
+                let _ = output
+                    .send(RunEvent::ProcessNewOutputLine(format!("{line}\n")))
+                    .await;
+            }
+            None => warn!("Dropping log line of a script that isn't running - {line}"),
+        }
+    }
+
+    /// Keeps the results of the script of a board config, replacing the ones
+    /// it submitted before.
+    fn store_results(&self, key: ScriptKey, results: String) {
+        self.results
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(key, results);
+    }
 }
 
 impl Builder {
@@ -48,24 +151,40 @@ impl Builder {
     /// # Examples
     ///
     /// ```rust
-    /// use std::path::PathBuf;
+    /// use std::{path::PathBuf, time::Duration};
     /// use ejb::builder::Builder;
     ///
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// let config_path = PathBuf::from("config.toml");
     /// let socket_path = PathBuf::from("/tmp/ejb.sock");
     ///
-    /// let builder = Builder::create(config_path, socket_path).await?;
+    /// let exit_grace_period = Duration::from_secs(60);
+    ///
+    /// let builder = Builder::create(config_path, socket_path, exit_grace_period).await?;
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn create(config_path: PathBuf, socket_path: PathBuf) -> Result<Self> {
+    pub async fn create(
+        config_path: PathBuf,
+        socket_path: PathBuf,
+        exit_grace_period: Duration,
+    ) -> Result<Self> {
         let config = EjUserConfig::from_file(&config_path)?;
         let config = EjConfig::from_user_config(config);
         let (tx, rx) = mpsc::channel(32);
+        let script_messages = ScriptMessages::default();
+        let (connected_tx, connected_scripts) = watch::channel(0);
 
-        Builder::start_thread(rx, &socket_path).await?;
-        let config_path_str = config_path
+        Builder::start_thread(
+            rx,
+            &socket_path,
+            script_messages.clone(),
+            exit_grace_period,
+            connected_tx,
+        )
+        .await?;
+        // Scripts run in the workspace of their job
+        let config_path_str = std::path::absolute(&config_path)?
             .into_os_string()
             .into_string()
             .expect(&format!("Failed to convert config path to a valid string",));
@@ -79,12 +198,71 @@ impl Builder {
             config_path: config_path_str,
             socket_path: socket_path_str,
             tx,
+            script_messages,
+            exit_grace_period,
+            connected_scripts,
+            build_cache: None,
+            mirror_dir: PathBuf::from(DEFAULT_MIRROR_DIR),
+            lfs: false,
+            workspaces: Workspaces::new(
+                PathBuf::from(DEFAULT_WORKSPACE_DIR),
+                RetentionPolicy::default(),
+            ),
+            container_engine: String::from(DEFAULT_CONTAINER_ENGINE),
+            probe_timeout: DEFAULT_PROBE_TIMEOUT,
+            board_locks: BoardLocks::new(PathBuf::from(DEFAULT_LOCK_DIR)),
         })
     }
 
+    /// Runs the scripts of the board configs with a container using `engine`,
+    /// `docker` or `podman`.
+    pub fn with_container_engine(mut self, engine: String) -> Self {
+        self.container_engine = engine;
+        self
+    }
+
+    /// Considers the boards whose probe doesn't succeed within `timeout` unavailable.
+    pub fn with_probe_timeout(mut self, timeout: Duration) -> Self {
+        self.probe_timeout = timeout;
+        self
+    }
+
+    /// Keeps the lock files of the boards in `lock_dir`.
+    pub fn with_lock_dir(mut self, lock_dir: PathBuf) -> Self {
+        self.board_locks = BoardLocks::new(lock_dir);
+        self
+    }
+
+    /// Runs the jobs in workspaces managed by `workspaces`.
+    pub fn with_workspaces(mut self, workspaces: Workspaces) -> Self {
+        self.workspaces = workspaces;
+        self
+    }
+
+    /// Keeps the mirrors of the remotes jobs check out in `mirror_dir`.
+    pub fn with_mirror_dir(mut self, mirror_dir: PathBuf) -> Self {
+        self.mirror_dir = mirror_dir;
+        self
+    }
+
+    /// Pulls Git LFS objects when checking out jobs if `lfs` is set.
+    pub fn with_lfs(mut self, lfs: bool) -> Self {
+        self.lfs = lfs;
+        self
+    }
+
+    /// Restores unchanged board config builds from `cache` instead of building them.
+    pub fn with_build_cache(mut self, cache: BuildCache) -> Self {
+        self.build_cache = Some(cache);
+        self
+    }
+
     async fn start_thread(
         mut rx: mpsc::Receiver<BuilderEvent>,
         socket_path: &Path,
+        script_messages: ScriptMessages,
+        exit_grace_period: Duration,
+        connected_scripts: watch::Sender<u32>,
     ) -> Result<JoinHandle<()>> {
         let _ = std::fs::remove_file(&socket_path);
         let listener = tokio::net::UnixListener::bind(socket_path)?;
@@ -113,18 +291,29 @@ impl Builder {
                     Ok((stream, _)) => {
                         let id = connection_count.clone().load(Ordering::Relaxed);
                         connection_count.fetch_add(1, Ordering::Relaxed);
+                        connected_scripts.send_modify(|count| *count += 1);
 
                         let c_count = connection_count.clone();
+                        let connected_scripts = connected_scripts.clone();
                         let rx = broadcast_tx.subscribe();
+                        let script_messages = script_messages.clone();
                         tokio::spawn(async move {
                             info!("New socket connection {id}");
 
-                            if let Err(e) = Builder::handle_connection(stream, rx).await {
+                            if let Err(e) = Builder::handle_connection(
+                                stream,
+                                rx,
+                                script_messages,
+                                exit_grace_period,
+                            )
+                            .await
+                            {
                                 error!("Error handling client: {}", e);
                             }
 
                             info!("Socket connection {id} ended");
                             c_count.fetch_sub(1, Ordering::Relaxed);
+                            connected_scripts.send_modify(|count| *count -= 1);
                         });
                     }
                     Err(e) => {
@@ -137,36 +326,57 @@ impl Builder {
     async fn handle_connection(
         stream: UnixStream,
         mut rx: broadcast::Receiver<BuilderEvent>,
+        script_messages: ScriptMessages,
+        exit_grace_period: Duration,
     ) -> Result<()> {
-        let (mut reader, mut writer) = stream.into_split();
-        let mut buf = [0u8; 1];
+        let (reader, mut writer) = stream.into_split();
+        let mut frames = FrameReader::new(reader);
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
 
         loop {
             tokio::select! {
-                // Monitor socket - if client closes, read returns Ok(0) or Err
-                read_result = reader.read(&mut buf) => {
-                    match read_result? {
-                        0 => { return Ok(()); }
-                        _ => { /* Client sent unexpected data, ignore it.*/ }
+                // Monitor socket - if client closes, read returns Ok(None) or Err
+                read_result = frames.read_frame() => {
+                    match read_result {
+                        Ok(Some(response)) => {
+                            Builder::handle_response(response, &script_messages).await;
+                        }
+                        Ok(None) => return Ok(()),
+                        Err(ej_builder_sdk::error::Error::Json(err)) => {
+                            warn!("Ignoring invalid message from client - {err}");
+                        }
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+
+                _ = heartbeat.tick() => {
+                    if let Err(e) = write_frame(&mut writer, &BuilderMessage::Heartbeat).await {
+                        info!("Heartbeat failed: {}, client likely disconnected", e);
+                        break;
                     }
                 }
 
                 recv_result = rx.recv() => {
                     match recv_result {
-                        Ok(message) => {
-                            let serialized_response = serde_json::to_string(&message)?;
+                        Ok(event) => {
+                            let exit = matches!(event, BuilderEvent::Exit);
+                            let grace_period = matches!(event, BuilderEvent::Exit | BuilderEvent::Cancel { .. })
+                                .then_some(exit_grace_period);
+                            let message = BuilderMessage::Event { event, grace_period };
 
-                            if let Err(e) = writer.write_all(serialized_response.as_bytes()).await {
+                            if let Err(e) = write_frame(&mut writer, &message).await {
                                 info!("Write failed: {}, client likely disconnected", e);
                                 break;
                             }
-                            if let Err(e) = writer.write_all(b"\n").await {
-                                info!("Write newline failed: {}", e);
-                                break;
-                            }
 
-                            if matches!(message, BuilderEvent::Exit) {
-                                info!("Received exit message, closing connection");
+                            if exit {
+                                info!("Received exit message, waiting for the client to acknowledge it");
+                                if timeout(exit_grace_period, Builder::wait_for_ack(&mut frames, &script_messages))
+                                    .await
+                                    .is_err()
+                                {
+                                    warn!("Client didn't acknowledge the exit message in {exit_grace_period:?}");
+                                }
                                 break;
                             }
                         }
@@ -184,4 +394,49 @@ impl Builder {
 
         Ok(())
     }
+
+    /// Handles a message sent by a script, returning whether it's an ack.
+    async fn handle_response(response: BuilderResponse, script_messages: &ScriptMessages) -> bool {
+        match response {
+            BuilderResponse::Log {
+                board_name,
+                board_config_name,
+                line,
+            } => {
+                script_messages
+                    .forward_log((board_name, board_config_name), line)
+                    .await;
+            }
+            BuilderResponse::Results {
+                board_name,
+                board_config_name,
+                results,
+            } => {
+                script_messages.store_results((board_name, board_config_name), results.to_string());
+            }
+            BuilderResponse::Ack => return true,
+        }
+        false
+    }
+
+    /// Handles the messages of a script until it acks an event or disconnects.
+    async fn wait_for_ack(
+        frames: &mut FrameReader<OwnedReadHalf>,
+        script_messages: &ScriptMessages,
+    ) {
+        loop {
+            match frames.read_frame().await {
+                Ok(Some(response)) => {
+                    if Builder::handle_response(response, script_messages).await {
+                        return;
+                    }
+                }
+                Ok(None) => return,
+                Err(ej_builder_sdk::error::Error::Json(err)) => {
+                    warn!("Ignoring invalid message from client - {err}");
+                }
+                Err(_) => return,
+            }
+        }
+    }
 }
@@ -0,0 +1,397 @@
+//! Build cache of the EJ Builder Service.
+//!
+//! The artifacts and logs of successful builds are kept in a cache directory,
+//! keyed by a SHA-256 hash of the commit and of everything about the board
+//! config that affects its build: its settings, its environment and the
+//! content of its build script and build hooks. Building the same commit with
+//! an unchanged board config again restores the artifacts in place of running
+//! the build script and its hooks.
+//!
+//! Only board configs listing `artifacts` are cached, as nothing else their
+//! build produces is restored. Once the cache grows past its maximum size,
+//! the least recently used builds are evicted.
+//!
+//! Each entry is a directory named after its key holding the build logs,
+//! a directory per artifact pattern, in the order they're listed, with the
+//! files it matched at their path relative to the pattern's directory, and a
+//! `last_used` file whose modification time orders evictions.
+
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use ej_auth::sha256::generate_hash;
+use ej_config::{ej_board::EjBoard, ej_board_config::EjBoardConfig, glob};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::artifacts::{artifact_path, matching_files};
+use crate::prelude::*;
+
+const LOGS_FILE: &str = "build.log";
+const LAST_USED_FILE: &str = "last_used";
+const ARTIFACTS_DIR: &str = "artifacts";
+
+/// Content-addressed cache of board config builds.
+#[derive(Debug, Clone)]
+pub struct BuildCache {
+    /// Directory holding the cached builds.
+    dir: PathBuf,
+    /// Size in bytes above which the least recently used builds are evicted.
+    max_size: u64,
+}
+
+impl BuildCache {
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        Self { dir, max_size }
+    }
+
+    /// Key of the build of a board config at a commit, `None` if the board
+    /// config has no artifacts and can't be cached.
+    ///
+    /// Paths in the job's `workspace` are hashed relative to it, so builds
+    /// are restored across jobs.
+    pub fn key(
+        commit_hash: &str,
+        board: &EjBoard,
+        board_config: &EjBoardConfig,
+        workspace: Option<&Path>,
+    ) -> Option<String> {
+        if board_config.artifacts.is_empty() {
+            return None;
+        }
+        let relative =
+            |path: &str| match workspace.and_then(|ws| Path::new(path).strip_prefix(ws).ok()) {
+                Some(path) => path.to_string_lossy().into_owned(),
+                None => path.to_string(),
+            };
+        // IDs are generated every time the config is loaded
+        let config = EjBoardConfig {
+            id: Uuid::nil(),
+            library_path: relative(&board_config.library_path),
+            results_path: relative(&board_config.results_path),
+            ..board_config.clone()
+        };
+        let config = serde_json::to_string(&config).ok()?;
+        let env = serde_json::to_string(&board.script_env(board_config)).ok()?;
+        let scripts: Vec<u8> = [
+            board_config.pre_build.as_deref(),
+            Some(board_config.build_script.as_str()),
+            board_config.post_build.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|script| std::fs::read(script).unwrap_or_default())
+        .collect();
+        let payload = format!(
+            "{commit_hash}\n{}\n{config}\n{env}\n{}",
+            board.name,
+            String::from_utf8_lossy(&scripts)
+        );
+        Some(generate_hash(&payload))
+    }
+
+    /// Restores the artifacts of a cached build, returning its logs.
+    ///
+    /// Returns `None` if the build isn't cached or can't be restored, the
+    /// board config must be built then.
+    pub async fn restore(&self, key: &str, board_config: &EjBoardConfig) -> Option<Vec<String>> {
+        let entry = self.dir.join(key);
+        if !tokio::fs::try_exists(entry.join(LAST_USED_FILE))
+            .await
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        match restore_entry(&entry, board_config).await {
+            Ok(logs) => Some(logs),
+            Err(err) => {
+                warn!("Failed to restore cached build {key}, evicting it - {err}");
+                let _ = tokio::fs::remove_dir_all(&entry).await;
+                None
+            }
+        }
+    }
+
+    /// Caches the artifacts and logs of a successful build, then evicts the
+    /// least recently used builds if the cache grew too large.
+    pub async fn store(&self, key: &str, board_config: &EjBoardConfig, logs: &[String]) {
+        let entry = self.dir.join(key);
+        let tmp_entry = self.dir.join(format!(".{key}.{}", Uuid::new_v4()));
+        let result = async {
+            store_entry(&tmp_entry, board_config, logs).await?;
+            tokio::fs::rename(&tmp_entry, &entry).await?;
+            Ok::<_, Error>(())
+        };
+        if let Err(err) = result.await {
+            warn!("Failed to cache build {key} - {err}");
+            let _ = tokio::fs::remove_dir_all(&tmp_entry).await;
+            return;
+        }
+        info!("Cached build {key}");
+
+        let cache = self.clone();
+        match tokio::task::spawn_blocking(move || cache.collect_garbage()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => warn!("Failed to evict cached builds - {err}"),
+            Err(err) => warn!("Failed to evict cached builds - {err}"),
+        }
+    }
+
+    /// Evicts the least recently used builds until the cache fits in its maximum size.
+    fn collect_garbage(&self) -> std::io::Result<()> {
+        let mut entries = Vec::new();
+        let mut total_size = 0;
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            // Builds being stored are hidden until they're complete
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if hidden || !path.is_dir() {
+                continue;
+            }
+            let size = dir_size(&path)?;
+            let last_used = std::fs::metadata(path.join(LAST_USED_FILE))
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            total_size += size;
+            entries.push((last_used, size, path));
+        }
+
+        entries.sort_by_key(|(last_used, _, _)| *last_used);
+        for (_, size, path) in entries {
+            if total_size <= self.max_size {
+                break;
+            }
+            info!("Evicting cached build {}", path.display());
+            std::fs::remove_dir_all(&path)?;
+            total_size -= size;
+        }
+        Ok(())
+    }
+}
+
+async fn restore_entry(entry: &Path, board_config: &EjBoardConfig) -> Result<Vec<String>> {
+    let logs = serde_json::from_slice(&tokio::fs::read(entry.join(LOGS_FILE)).await?)?;
+    for (index, artifact) in board_config.artifacts.iter().enumerate() {
+        let pattern = artifact_path(board_config, artifact);
+        let root = glob::root(&pattern);
+        copy_dir(&entry.join(ARTIFACTS_DIR).join(index.to_string()), root).await?;
+    }
+    tokio::fs::write(entry.join(LAST_USED_FILE), b"").await?;
+    Ok(logs)
+}
+
+async fn store_entry(entry: &Path, board_config: &EjBoardConfig, logs: &[String]) -> Result<()> {
+    let artifacts_dir = entry.join(ARTIFACTS_DIR);
+    for (index, artifact) in board_config.artifacts.iter().enumerate() {
+        // Files produced by the run, such as its logs, don't match yet
+        let artifact_dir = artifacts_dir.join(index.to_string());
+        tokio::fs::create_dir_all(&artifact_dir).await?;
+        let pattern = artifact_path(board_config, artifact);
+        let root = glob::root(&pattern);
+        for path in matching_files(board_config, artifact) {
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            let cached = artifact_dir.join(relative);
+            if let Some(parent) = cached.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::copy(&path, cached).await?;
+        }
+    }
+    tokio::fs::write(entry.join(LOGS_FILE), serde_json::to_vec(logs)?).await?;
+    tokio::fs::write(entry.join(LAST_USED_FILE), b"").await?;
+    Ok(())
+}
+
+/// Copies the files of a directory and its subdirectories into `to`, keeping
+/// their paths relative to it.
+async fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    let mut directories = vec![PathBuf::new()];
+    while let Some(relative) = directories.pop() {
+        tokio::fs::create_dir_all(to.join(&relative)).await?;
+        let mut files = tokio::fs::read_dir(from.join(&relative)).await?;
+        while let Some(file) = files.next_entry().await? {
+            let path = relative.join(file.file_name());
+            if file.file_type().await?.is_dir() {
+                directories.push(path);
+            } else {
+                tokio::fs::copy(file.path(), to.join(path)).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Total size of the files in a directory and its subdirectories.
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs::File, time::Duration};
+
+    use ej_config::ej_config::{EjConfig, EjGlobalConfig};
+
+    use super::*;
+    use crate::workspace::relocate;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ejb-cache-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn board(build_script: &Path, library_path: &Path, artifacts: &[&str]) -> EjBoard {
+        let config = EjBoardConfig {
+            id: Uuid::new_v4(),
+            name: String::from("rpi3"),
+            tags: vec![String::from("arm64")],
+            build_script: build_script.to_string_lossy().into_owned(),
+            run_script: String::from("run.sh"),
+            results_path: String::from("results.json"),
+            library_path: library_path.to_string_lossy().into_owned(),
+            artifacts: artifacts
+                .iter()
+                .map(|artifact| artifact.to_string())
+                .collect(),
+            env: BTreeMap::new(),
+            container: None,
+            probe: None,
+            build_timeout: None,
+            run_timeout: None,
+            pre_build: None,
+            post_build: None,
+            pre_run: None,
+            post_run: None,
+        };
+        EjBoard {
+            id: Uuid::new_v4(),
+            name: String::from("Raspberry Pi 3"),
+            description: String::new(),
+            env: BTreeMap::new(),
+            configs: vec![config],
+        }
+    }
+
+    fn write(path: &Path, content: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_key_is_stable_across_workspaces() {
+        let dir = temp_dir();
+        let script = dir.join("build.sh");
+        write(&script, "make");
+        let config = EjConfig {
+            global: EjGlobalConfig {
+                version: String::from("1.0.0"),
+            },
+            boards: vec![board(&script, Path::new("lib"), &["build/*.bin"])],
+        };
+        let key = |workspace: &Path, commit_hash: &str| {
+            let config = relocate(&config, workspace);
+            let board = &config.boards[0];
+            BuildCache::key(commit_hash, board, &board.configs[0], Some(workspace))
+        };
+
+        let first = key(&dir.join("job-1"), "abc123");
+        assert!(first.is_some());
+        assert_eq!(first, key(&dir.join("job-2"), "abc123"));
+        assert_ne!(first, key(&dir.join("job-2"), "def456"));
+
+        write(&script, "make all");
+        assert_ne!(first, key(&dir.join("job-2"), "abc123"));
+
+        let board = board(&script, Path::new("lib"), &[]);
+        assert_eq!(
+            BuildCache::key("abc123", &board, &board.configs[0], None),
+            None
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_store_then_restore() {
+        let dir = temp_dir();
+        let cache = BuildCache::new(dir.join("cache"), u64::MAX);
+        std::fs::create_dir_all(dir.join("cache")).unwrap();
+        let built = dir.join("built");
+        write(&built.join("build/app.bin"), "app");
+        write(&built.join("build/boot.bin"), "boot");
+        write(&built.join("build/notes.txt"), "notes");
+        write(&built.join("out/app.bin"), "signed app");
+        let artifacts = ["build/*.bin", "out/app.bin"];
+        let built_board = board(&dir.join("build.sh"), &built, &artifacts);
+        let logs = vec![String::from("Building"), String::from("Done")];
+        cache.store("key", &built_board.configs[0], &logs).await;
+
+        assert_eq!(
+            cache.restore("missing", &built_board.configs[0]).await,
+            None
+        );
+
+        let restored = dir.join("restored");
+        let restored_board = board(&dir.join("build.sh"), &restored, &artifacts);
+        assert_eq!(
+            cache.restore("key", &restored_board.configs[0]).await,
+            Some(logs)
+        );
+        for (path, content) in [
+            ("build/app.bin", "app"),
+            ("build/boot.bin", "boot"),
+            ("out/app.bin", "signed app"),
+        ] {
+            assert_eq!(
+                std::fs::read_to_string(restored.join(path)).unwrap(),
+                content
+            );
+        }
+        assert!(!restored.join("build/notes.txt").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_garbage_evicts_least_recently_used() {
+        let dir = temp_dir();
+        let now = SystemTime::now();
+        // Entries of 10 bytes, last used 3, 1 and 2 hours ago
+        for (key, hours) in [("a", 3), ("b", 1), ("c", 2)] {
+            write(&dir.join(key).join(LOGS_FILE), "0123456789");
+            File::create(dir.join(key).join(LAST_USED_FILE))
+                .unwrap()
+                .set_modified(now - Duration::from_secs(hours * 3600))
+                .unwrap();
+        }
+        write(&dir.join(".d.tmp").join(LOGS_FILE), "0123456789");
+
+        let cache = BuildCache::new(dir.clone(), 25);
+        cache.collect_garbage().unwrap();
+        assert!(!dir.join("a").exists());
+        assert!(dir.join("b").exists());
+        assert!(dir.join("c").exists());
+        assert!(dir.join(".d.tmp").exists());
+
+        let cache = BuildCache::new(dir.clone(), 10);
+        cache.collect_garbage().unwrap();
+        assert!(dir.join("b").exists());
+        assert!(!dir.join("c").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
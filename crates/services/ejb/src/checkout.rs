@@ -6,17 +6,27 @@
 //! The checkout process:
 //! 1. Collects all unique library paths from board configurations
 //! 2. Performs deduplication to checkout each path only once
-//! 3. Builds the appropriate Git URL (with token if needed)
-//! 4. Clones the repository to the library path
-//! 5. Checks out the specified commit hash
-//! 6. Validates the checkout was successful
+//! 3. Fetches the commit into a persistent bare mirror of the remote, unless
+//!    the mirror already holds it
+//! 4. Fetches the commit from the mirror into the library path, creating a
+//!    repository there if needed
+//! 5. Checks out the specified commit hash, only the paths matching the
+//!    sparse-checkout patterns if the job gives some
+//! 6. Initializes the submodules recursively and, if enabled, pulls the Git
+//!    LFS objects
+//!
+//! Only the mirror talks to the remote, the Git URL with the token is never
+//! stored in a repository's configuration.
 
 use crate::{prelude::*, run_output::EjRunOutput};
+use ej_auth::sha256::generate_hash;
 use ej_config::{ej_board_config::EjBoardConfig, ej_config::EjConfig};
+use ej_dispatcher_sdk::ejjob::checkout::EjCheckoutOptions;
 use ej_io::runner::{RunEvent, Runner};
 use std::{
     collections::HashMap,
     io::stdout,
+    path::{Path, PathBuf},
     sync::{Arc, atomic::AtomicBool},
 };
 use tokio::sync::mpsc::channel;
@@ -44,10 +54,155 @@ fn build_remote_url(remote_url: &str, remote_token: Option<String>) -> String {
     let token = remote_token.unwrap();
     return format!("{}{}@{}", prefix, token, url);
 }
+
+/// Configuration overrides pointing the library path's submodules and LFS objects
+/// at the job's remote.
+///
+/// Relative submodule URLs are resolved from the remote, and the token is
+/// applied to the submodules on the same host as the remote. The overrides
+/// only last for the commands they're given to, so the token is never stored.
+fn remote_overrides(remote_url: &str, remote_token: Option<&str>) -> Vec<String> {
+    let mut overrides = vec![
+        String::from("-c"),
+        format!("remote.origin.url={remote_url}"),
+    ];
+    let host = ["https://", "http://"].into_iter().find_map(|scheme| {
+        let rest = remote_url.strip_prefix(scheme)?;
+        let host = rest.split('/').next()?;
+        Some(format!("{scheme}{host}/"))
+    });
+    if let (Some(host), Some(token)) = (host, remote_token) {
+        let authenticated = build_remote_url(&host, Some(token.to_string()));
+        overrides.push(String::from("-c"));
+        overrides.push(format!("url.{authenticated}.insteadOf={host}"));
+    }
+    overrides
+}
+
+/// Runs a git command, adding its output to the logs of the board config.
+///
+/// Returns whether the command succeeded.
+async fn git(
+    args: &[&str],
+    remote_token: Option<&str>,
+    config: &EjBoardConfig,
+    output: &mut EjRunOutput<'_>,
+) -> bool {
+    let command = match remote_token {
+        Some(token) => args.join(" ").replace(token, "<REDACTED>"),
+        None => args.join(" "),
+    };
+    let (tx, mut rx) = channel(10);
+    let stop = Arc::new(AtomicBool::new(false));
+    let runner = Runner::new("git", args.to_vec());
+    let result = tokio::spawn(async move { runner.run(tx, stop).await });
+
+    let mut success = false;
+    while let Some(event) = rx.recv().await {
+        match event {
+            RunEvent::ProcessCreationFailed(err) => {
+                error!("Failed to run command git {command} - {err}")
+            }
+            RunEvent::ProcessEnd(result, _) => success = result,
+            RunEvent::ProcessNewOutputLine(line) => {
+                let line = match remote_token {
+                    Some(token) => line.replace(token, "<REDACTED>"),
+                    None => line,
+                };
+                output.push_log(config.id, line);
+            }
+            _ => {}
+        }
+    }
+
+    if let Ok(result) = result.await {
+        info!("Result for command git {command} {:?}", result);
+    }
+    success
+}
+
+/// Makes sure the mirror of a remote holds a commit, returning the mirror's path.
+///
+/// The mirror is a bare repository under `mirror_dir` named after the hash of
+/// the remote URL, created on first use. Commits it already holds aren't
+/// fetched again, other commits are fetched alone when the remote allows it,
+/// along with every branch otherwise, `depth` limiting the history fetched.
+/// The commit is then pinned by [`pinned_ref`].
+async fn update_mirror(
+    mirror_dir: &Path,
+    commit_hash: &str,
+    remote_url: &str,
+    remote_token: Option<&str>,
+    depth: Option<&str>,
+    config: &EjBoardConfig,
+    output: &mut EjRunOutput<'_>,
+) -> Result<PathBuf> {
+    let mirror = mirror_dir.join(format!("{}.git", generate_hash(remote_url)));
+    let mirror_str = mirror.to_string_lossy();
+    if !tokio::fs::try_exists(&mirror).await? {
+        tokio::fs::create_dir_all(mirror_dir).await?;
+        if !git(&["init", "--bare", &mirror_str], None, config, output).await {
+            error!("Failed to create mirror {mirror_str} of {remote_url}");
+            return Err(Error::CheckoutError);
+        }
+    }
+
+    let commit = format!("{commit_hash}^{{commit}}");
+    let pinned_ref = pinned_ref(commit_hash);
+    let args = [
+        "-C",
+        &mirror_str,
+        "rev-parse",
+        "--quiet",
+        "--verify",
+        &commit,
+    ];
+    if git(&args, None, config, output).await {
+        info!("Commit {commit_hash} already in mirror {mirror_str}");
+    } else {
+        let url = build_remote_url(remote_url, remote_token.map(String::from));
+        let fetch = |refspec| {
+            let mut args = vec!["-C", &mirror_str, "fetch", "--no-tags"];
+            if let Some(depth) = depth {
+                args.extend(["--depth", depth]);
+            }
+            args.extend([url.as_str(), refspec]);
+            args
+        };
+        let refspec = format!("+{commit_hash}:{pinned_ref}");
+        if !git(&fetch(&refspec), remote_token, config, output).await {
+            info!(
+                "Failed to fetch commit {commit_hash} alone, fetching every branch of {remote_url}"
+            );
+            let mut args = fetch("+refs/heads/*:refs/heads/*");
+            args.push("--prune");
+            if !git(&args, remote_token, config, output).await {
+                error!("Failed to fetch {remote_url} into mirror {mirror_str}");
+                return Err(Error::CheckoutError);
+            }
+        }
+    }
+
+    let args = ["-C", &mirror_str, "update-ref", &pinned_ref, &commit];
+    if !git(&args, None, config, output).await {
+        error!("Commit {commit_hash} not found in {remote_url}");
+        return Err(Error::CheckoutError);
+    }
+    Ok(mirror)
+}
+
+/// Ref of the mirrors pinning a commit, so it can be fetched by this name and
+/// is never pruned.
+fn pinned_ref(commit_hash: &str) -> String {
+    format!("refs/ej/{commit_hash}")
+}
+
 async fn checkout(
+    builder: &Builder,
     commit_hash: &str,
     remote_url: &str,
-    remote_token: Option<String>,
+    remote_token: Option<&str>,
+    options: &EjCheckoutOptions,
     config: &EjBoardConfig,
     output: &mut EjRunOutput<'_>,
 ) -> Result<()> {
@@ -55,68 +210,69 @@ async fn checkout(
         "Checking out library at {} for board {}",
         config.library_path, config.id
     );
-    let remote_url = &build_remote_url(remote_url, remote_token.clone());
-    let commands = vec![
-        vec![
-            "git",
-            "-C",
-            &config.library_path,
-            "remote",
-            "remove",
-            "ejupstream",
-        ],
-        vec![
-            "git",
-            "-C",
-            &config.library_path,
-            "remote",
-            "add",
-            "ejupstream",
-            remote_url,
-        ],
-        vec!["git", "-C", &config.library_path, "fetch", "ejupstream"],
-        vec!["git", "-C", &config.library_path, "checkout", commit_hash],
-    ];
-
-    for (i, command) in commands.iter().enumerate() {
-        let (tx, mut rx) = channel(10);
-        let stop = Arc::new(AtomicBool::new(false));
-        let runner = Runner::new(command[0], command[1..].to_vec());
-        let result = tokio::spawn(async move { runner.run(tx, stop).await });
+    let depth = options.depth.map(|depth| depth.to_string());
+    let mirror = update_mirror(
+        &builder.mirror_dir,
+        commit_hash,
+        remote_url,
+        remote_token,
+        depth.as_deref(),
+        config,
+        output,
+    )
+    .await?;
 
-        while let Some(event) = rx.recv().await {
-            match event {
-                RunEvent::ProcessCreationFailed(err) => {
-                    error!("Failed to run command {:?} - {err}", command)
-                }
-                RunEvent::ProcessEnd(success) => {
-                    // First command is always to remove the remote, so we don't fail on it
-                    if !success && i != 0 {
-                        error!("Command {:?} failed", command);
-                        return Err(Error::CheckoutError);
-                    }
-                }
-                RunEvent::ProcessNewOutputLine(line) => {
-                    let line = if let Some(ref token) = remote_token {
-                        line.replace(token, "<REDACTED>")
-                    } else {
-                        line.clone()
-                    };
-                    match output.logs.get_mut(&config.id) {
-                        Some(entry) => {
-                            entry.push(line);
-                        }
-                        None => {
-                            output.logs.insert(config.id, vec![line]);
-                        }
-                    };
-                }
-                _ => {}
-            }
+    let library_path = &config.library_path;
+    if !tokio::fs::try_exists(Path::new(library_path).join(".git")).await?
+        && !git(&["init", library_path], None, config, output).await
+    {
+        error!("Failed to create repository at {library_path}");
+        return Err(Error::CheckoutError);
+    }
+    let mirror = mirror.to_string_lossy();
+    let pinned_ref = pinned_ref(commit_hash);
+    let mut fetch = vec!["-C", library_path, "fetch", "--no-tags"];
+    if let Some(depth) = &depth {
+        fetch.extend(["--depth", depth]);
+    }
+    fetch.extend([mirror.as_ref(), pinned_ref.as_str()]);
+    let mut sparse_checkout = vec!["-C", library_path, "sparse-checkout"];
+    if options.sparse_paths.is_empty() {
+        sparse_checkout.push("disable");
+    } else {
+        sparse_checkout.extend(["set", "--no-cone"]);
+        sparse_checkout.extend(options.sparse_paths.iter().map(String::as_str));
+    }
+    let commands = [
+        fetch,
+        sparse_checkout,
+        vec!["-C", library_path, "checkout", commit_hash],
+    ];
+    for command in commands {
+        if !git(&command, None, config, output).await {
+            error!("Failed to check out {commit_hash} at {library_path}");
+            return Err(Error::CheckoutError);
         }
+    }
 
-        if let Ok(result) = result.await {
-            info!("Result for command {:?} {:?}", command, result);
+    let mut commands = Vec::new();
+    if tokio::fs::try_exists(Path::new(library_path).join(".gitmodules")).await? {
+        commands.push(vec!["submodule", "sync", "--recursive"]);
+        commands.push(vec!["submodule", "update", "--init", "--recursive"]);
+    }
+    if builder.lfs {
+        // LFS objects are only downloaded by the pull, where the overrides apply
+        commands.push(vec!["lfs", "install", "--local", "--skip-smudge"]);
+        commands.push(vec!["lfs", "pull"]);
+    }
+    let overrides = remote_overrides(remote_url, remote_token);
+    for command in commands {
+        let mut args = vec!["-C", library_path.as_str()];
+        args.extend(overrides.iter().map(String::as_str));
+        args.extend(&command);
+        if !git(&args, remote_token, config, output).await {
+            error!("Failed to run git {} at {library_path}", command.join(" "));
+            return Err(Error::CheckoutError);
         }
     }
 
@@ -132,16 +288,20 @@ async fn checkout(
 ///
 /// # Arguments
 ///
+/// * `builder` - The builder instance
 /// * `config` - The EJ configuration containing board definitions
 /// * `commit_hash` - Git commit hash to check out
 /// * `remote_url` - Git repository URL
 /// * `remote_token` - Optional authentication token for private repositories
+/// * `options` - History depth and sparse-checkout patterns of the checkout
 /// * `output` - Output collector for logs and results
 pub async fn checkout_all(
+    builder: &Builder,
     config: &EjConfig,
     commit_hash: &str,
     remote_url: &str,
     remote_token: Option<String>,
+    options: &EjCheckoutOptions,
     output: &mut EjRunOutput<'_>,
 ) -> Result<()> {
     let mut paths: HashMap<&str, &Uuid> = HashMap::new();
@@ -160,9 +320,11 @@ pub async fn checkout_all(
                 );
             }
             checkout(
+                builder,
                 commit_hash,
                 remote_url,
-                remote_token.clone(),
+                remote_token.as_deref(),
+                options,
                 config,
                 output,
             )
@@ -184,19 +346,23 @@ pub async fn checkout_all(
 /// ```bash
 /// ejb checkout --commit-hash abc123 --remote-url https://github.com/user/repo.git
 /// ejb checkout --commit-hash def456 --remote-url https://github.com/user/private.git --remote-token token123
+/// ejb checkout --commit-hash abc123 --remote-url https://github.com/user/repo.git --depth 1 --sparse '/src/'
 /// ```
 pub async fn handle_checkout(
     builder: &Builder,
     commit_hash: String,
     remote_url: String,
     remote_token: Option<String>,
+    options: EjCheckoutOptions,
 ) -> Result<()> {
     let mut output = EjRunOutput::new(&builder.config);
     let result = checkout_all(
+        builder,
         &builder.config,
         &commit_hash,
         &remote_url,
         remote_token,
+        &options,
         &mut output,
     )
     .await;
@@ -5,6 +5,12 @@
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
+use crate::board_lock::DEFAULT_LOCK_DIR;
+use crate::builder::DEFAULT_MIRROR_DIR;
+use crate::container::DEFAULT_CONTAINER_ENGINE;
+use crate::log_batch::{DEFAULT_LOG_BATCH_INTERVAL, DEFAULT_LOG_BATCH_LINES};
+use crate::workspace::DEFAULT_WORKSPACE_DIR;
+
 /// Command-line interface for the EJ Builder Service.
 #[derive(Parser)]
 #[command(name = "ejb")]
@@ -22,10 +28,63 @@ pub struct Cli {
     #[arg(short, long)]
     pub token: Option<String>,
 
+    /// Builder credentials file written by `ejb login`, read when no id and token are given
+    /// (defaults to ~/.config/ejb/credentials.json)
+    #[arg(long)]
+    pub credentials: Option<PathBuf>,
+
     /// Builder socket used to communicate with child processes
     #[arg(short, long)]
     pub socket_path: Option<PathBuf>,
 
+    /// Seconds child processes are given to exit when asked to, before being killed
+    #[arg(long, default_value_t = 60)]
+    pub exit_grace_period: u64,
+
+    /// Build every board config instead of restoring unchanged ones from the build cache
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Directory of the build cache
+    #[arg(long, default_value = "/tmp/ejb-cache")]
+    pub cache_dir: PathBuf,
+
+    /// Size in MiB above which the least recently used cached builds are evicted
+    #[arg(long, default_value_t = 10240)]
+    pub cache_max_size: u64,
+
+    /// Directory of the Git mirrors jobs are checked out from
+    #[arg(long, default_value = DEFAULT_MIRROR_DIR)]
+    pub mirror_dir: PathBuf,
+
+    /// Pull Git LFS objects after checking out jobs, requires git-lfs
+    #[arg(long)]
+    pub lfs: bool,
+
+    /// Directory of the workspaces jobs run in, one per job
+    #[arg(long, default_value = DEFAULT_WORKSPACE_DIR)]
+    pub workspace_dir: PathBuf,
+
+    /// Number of workspaces of the most recent jobs kept, the others are removed
+    #[arg(long, default_value_t = 5)]
+    pub keep_workspaces: usize,
+
+    /// Keep the workspaces of failed jobs on top of the most recent ones
+    #[arg(long)]
+    pub keep_failed_workspaces: bool,
+
+    /// Program running the containers of board configs with a `container`, docker or podman
+    #[arg(long, default_value = DEFAULT_CONTAINER_ENGINE)]
+    pub container_engine: String,
+
+    /// Seconds the board probes are given to succeed before their board is considered unavailable
+    #[arg(long, default_value_t = 10)]
+    pub probe_timeout: u64,
+
+    /// Directory of the board lock files, shared by the EJB processes running jobs on the same boards
+    #[arg(long, default_value = DEFAULT_LOCK_DIR)]
+    pub lock_dir: PathBuf,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -51,11 +110,94 @@ pub enum Commands {
         /// Optional git remote token
         #[arg(long)]
         remote_token: Option<String>,
+
+        /// Number of commits of history to fetch, the whole history by default
+        #[arg(long)]
+        depth: Option<u32>,
+
+        /// Only check out the paths matching this pattern, can be repeated
+        #[arg(long = "sparse")]
+        sparse_paths: Vec<String>,
+    },
+    /// Check out, build and run a commit like a job of the dispatcher, without one
+    RunLocal {
+        /// Git commit hash
+        #[arg(long)]
+        commit_hash: String,
+
+        /// Git remote url
+        #[arg(long)]
+        remote_url: String,
+
+        /// Optional git remote token
+        #[arg(long)]
+        remote_token: Option<String>,
+
+        /// Only run the job on the board config with this name, can be repeated
+        #[arg(long = "board-config")]
+        board_configs: Vec<String>,
+
+        /// Only run the job on board configs with this tag, can be repeated
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// The maximum duration of each board config's build in seconds
+        #[arg(long)]
+        build_timeout: Option<u64>,
+
+        /// The maximum duration of each board config's run in seconds
+        #[arg(long)]
+        run_timeout: Option<u64>,
+
+        /// Number of commits of history to fetch, the whole history by default
+        #[arg(long)]
+        depth: Option<u32>,
+
+        /// Only check out the paths matching this pattern, can be repeated
+        #[arg(long = "sparse")]
+        sparse_paths: Vec<String>,
+
+        /// Only build the commit, like a build job
+        #[arg(long)]
+        build_only: bool,
+    },
+    /// Remove the workspaces of past jobs the retention policy doesn't keep
+    Clean {
+        /// Remove every workspace
+        #[arg(long)]
+        all: bool,
+    },
+    /// Register a new builder with a user's credentials and write its credentials file
+    Login {
+        /// Server URL to register the builder on
+        #[arg(long)]
+        server: String,
+
+        /// Name of the user registering the builder, its password is prompted for
+        #[arg(long)]
+        username: String,
+
+        /// Store the builder token in the OS keyring through secret-tool instead of the file
+        #[arg(long)]
+        keyring: bool,
     },
     /// Run the builder and connect to the server via websockets
     Connect {
-        /// Server URL to connect to
-        #[arg(short, long)]
-        server: String,
+        /// Server URL to connect to, can be repeated or comma-separated to fail over
+        /// to the next servers when the first ones can't be reached
+        #[arg(short, long = "server", required = true, value_delimiter = ',')]
+        servers: Vec<String>,
+
+        /// Don't compress large WebSocket payloads
+        #[arg(long)]
+        no_compression: bool,
+
+        /// Send the pending log lines to the server as soon as there are this many
+        #[arg(long, default_value_t = DEFAULT_LOG_BATCH_LINES)]
+        log_batch_lines: usize,
+
+        /// Milliseconds log lines wait to be sent to the server along with the next ones
+        #[arg(long, default_value_t = DEFAULT_LOG_BATCH_INTERVAL.as_millis() as u64)]
+        log_batch_interval: u64,
     },
 }
@@ -3,18 +3,26 @@
 //! Contains handler functions for different CLI commands:
 //! - Configuration parsing and display
 //! - Build and validation execution
+//! - Local job execution
 //! - Connection management
 
-use std::io::stdout;
-use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
+use ej_config::ej_board_config::{EjBoardConfig, EjBoardConfigApi};
+use ej_dispatcher_sdk::ejjob::{
+    EjBuildResult, EjDeployableJob, EjJobType, EjRunResult, timeouts::EjJobTimeouts,
+};
+use std::{io::stdout, sync::Arc};
+use tracing::info;
 
 use crate::build::build;
 use crate::builder::Builder;
+use crate::checkout::checkout_all;
+use crate::common::JobStop;
 use crate::logs::dump_logs;
 use crate::prelude::*;
+use crate::probe::available_boards;
 use crate::run::run;
 use crate::run_output::EjRunOutput;
+use crate::workspace::relocate;
 
 /// Handles the parse command to display configuration information.
 ///
@@ -30,6 +38,9 @@ pub async fn handle_parse(builder: &Builder) -> Result<()> {
     for (board_idx, board) in config.boards.iter().enumerate() {
         println!("\nBoard {}: {}", board_idx + 1, board.name);
         println!("  Description: {}", board.description);
+        if !board.env.is_empty() {
+            println!("  Environment: {:?}", board.env);
+        }
         println!("  Configurations: {}", board.configs.len());
 
         for (config_idx, board_config) in board.configs.iter().enumerate() {
@@ -39,6 +50,9 @@ pub async fn handle_parse(builder: &Builder) -> Result<()> {
             println!("      Run script: {:?}", board_config.run_script);
             println!("      Results path: {:?}", board_config.results_path);
             println!("      Library path: {:?}", board_config.library_path);
+            if !board_config.env.is_empty() {
+                println!("      Environment: {:?}", board_config.env);
+            }
         }
     }
 
@@ -56,13 +70,146 @@ pub async fn handle_run_and_build(builder: &Builder) -> Result<()> {
 
     let config = &builder.config;
     let mut output = EjRunOutput::new(&config);
-    let stop = Arc::new(AtomicBool::new(false));
-    let result = build(builder, &config, &mut output, Arc::clone(&stop)).await;
+    let stop = JobStop::new(&config);
+    let result = build(
+        builder,
+        &config,
+        &mut output,
+        &stop,
+        &EjJobTimeouts::default(),
+        None,
+        None,
+    )
+    .await;
     if result.is_err() {
         dump_logs(&output, stdout())?;
         return result;
     }
-    let result = run(builder, &config, &mut output, Arc::clone(&stop)).await;
+    let result = run(
+        builder,
+        &config,
+        &mut output,
+        &stop,
+        &EjJobTimeouts::default(),
+        None,
+    )
+    .await;
     dump_logs(&output, stdout())?;
     return result;
 }
+
+/// Handles the run-local command to execute a job without a dispatcher.
+///
+/// Probes the boards, then checks out, builds and, unless it's a build job,
+/// runs the commit of `job` in its own workspace, exactly like a job
+/// dispatched to the builder.
+/// The logs and results of each board config are then printed the way the
+/// dispatcher stores and reports them, so board scripts can be developed
+/// without a running EJD.
+pub async fn handle_run_local(builder: &Builder, job: EjDeployableJob) -> Result<()> {
+    let (config, skipped) = builder
+        .config
+        .filter_for_job(&job.board_configs, &job.required_tags);
+    let (config, unavailable) = available_boards(Arc::new(config), builder.probe_timeout).await;
+    let skipped = [skipped, unavailable].concat();
+    let workspace = builder.workspaces.create(job.id).await?;
+    info!("Running job {} in {}", job.id, workspace.display());
+    let config = relocate(&config, &workspace);
+
+    let mut output = EjRunOutput::new(&config);
+    let stop = JobStop::new(&config);
+    let mut result = checkout_all(
+        builder,
+        &config,
+        &job.commit_hash,
+        &job.remote_url,
+        job.remote_token,
+        &job.checkout,
+        &mut output,
+    )
+    .await;
+    if result.is_ok() {
+        result = build(
+            builder,
+            &config,
+            &mut output,
+            &stop,
+            &job.timeouts,
+            Some(&job.commit_hash),
+            Some(&workspace),
+        )
+        .await;
+    }
+    if result.is_ok() && job.job_type == EjJobType::BuildAndRun {
+        result = run(
+            builder,
+            &config,
+            &mut output,
+            &stop,
+            &job.timeouts,
+            Some(&workspace),
+        )
+        .await;
+    }
+    builder.workspaces.finish(&workspace, result.is_ok()).await;
+
+    let board_configs: Vec<&EjBoardConfig> = config
+        .boards
+        .iter()
+        .flat_map(|board| &board.configs)
+        .collect();
+    let logs = board_configs
+        .iter()
+        .filter_map(|board_config| {
+            let logs = output.logs.get(&board_config.id)?;
+            Some((board_config_api(board_config), logs.join("")))
+        })
+        .collect();
+    let skipped = builder
+        .config
+        .boards
+        .iter()
+        .flat_map(|board| &board.configs)
+        .filter(|board_config| skipped.contains(&board_config.id))
+        .map(board_config_api)
+        .collect();
+    let success = result.is_ok();
+    match job.job_type {
+        EjJobType::Build => println!(
+            "{}",
+            EjBuildResult {
+                logs,
+                skipped,
+                success,
+            }
+        ),
+        EjJobType::BuildAndRun => {
+            let results = board_configs
+                .iter()
+                .filter_map(|board_config| {
+                    let result = output.results.get(&board_config.id)?;
+                    Some((board_config_api(board_config), result.clone()))
+                })
+                .collect();
+            println!(
+                "{}",
+                EjRunResult {
+                    logs,
+                    results,
+                    skipped,
+                    success,
+                }
+            )
+        }
+    }
+    result
+}
+
+/// Board config as the dispatcher reports it.
+fn board_config_api(board_config: &EjBoardConfig) -> EjBoardConfigApi {
+    EjBoardConfigApi {
+        id: board_config.id,
+        name: board_config.name.clone(),
+        tags: board_config.tags.clone(),
+    }
+}
@@ -1,19 +1,31 @@
 //! Common utilities and types for the EJ Builder Service.
 //!
 //! Provides shared functionality used across different modules,
-//! including runner process management and argument handling.
+//! including runner process management, argument handling and job
+//! cancellation flags.
 
 use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
     process::ExitStatus,
-    sync::{Arc, atomic::AtomicBool},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
 };
 
+use crate::builder::ScriptMessages;
+use crate::container::ScriptContainer;
 use ej_builder_sdk::Action;
+use ej_config::ej_config::EjConfig;
 use ej_io::runner::{RunEvent, Runner};
 use tokio::{
-    sync::mpsc::Sender,
+    sync::mpsc::{Sender, channel},
     task::{self, JoinHandle},
 };
+use tracing::{error, info};
+use uuid::Uuid;
 
 /// Arguments for spawning a runner process.
 ///
@@ -33,37 +45,141 @@ pub struct SpawnRunnerArgs {
     pub config_name: String,
     /// Path to the Unix socket for communication.
     pub socket_path: String,
+    /// Environment variables of the board configuration.
+    pub env: BTreeMap<String, String>,
+    /// Where the messages the script sends through the socket go.
+    pub script_messages: ScriptMessages,
+    /// Maximum duration of the script, unlimited if unset.
+    pub timeout: Option<Duration>,
+    /// Directory the script runs in, the builder's if unset.
+    pub current_dir: Option<PathBuf>,
+    /// Container the script runs in, the builder itself if unset.
+    pub container: Option<ScriptContainer>,
 }
 
 impl SpawnRunnerArgs {
     /// Builds a runner instance from the provided arguments.
     ///
-    /// Creates a `Runner` with the script name and properly formatted
-    /// command-line arguments for the child process.
-    fn build_runner(self) -> Runner {
+    /// Creates a `Runner` with the script name, properly formatted
+    /// command-line arguments and the environment variables for the child process.
+    /// Scripts with a container are run by the container engine instead.
+    fn build_runner(self) -> std::io::Result<Runner> {
         // Set arguments for child process
         // argv[1] is the action the runner should take should be either `build` or `run`
-        // argv[2] is the config (.toml) path
+        // argv[2] is the config (.toml, .yaml or .json) path
         // argv[3] is the board name
         // argv[4] is the board config name
-        // argv[5] is the path to the socket so that he can establish a socket connection with ejb
-        Runner::new(
-            self.script_name,
-            vec![
-                String::from(self.action),
-                self.config_path,
-                self.board_name,
-                self.config_name,
-                self.socket_path,
-            ],
-        )
+        // argv[5] is the address of the socket so that he can establish a socket connection with ejb,
+        // see `ej_builder_sdk::transport::Endpoint`
+        let args = vec![
+            String::from(self.action),
+            self.config_path.clone(),
+            self.board_name,
+            self.config_name,
+            self.socket_path.clone(),
+        ];
+        let runner = match &self.container {
+            Some(container) => {
+                let (engine, args) = container.command(
+                    &self.script_name,
+                    args,
+                    &self.env,
+                    self.current_dir.as_deref(),
+                    &self.config_path,
+                    &self.socket_path,
+                )?;
+                Runner::new(engine, args)
+            }
+            None => Runner::new(self.script_name, args),
+        }
+        .with_env(self.env);
+        let runner = match self.current_dir {
+            Some(current_dir) => runner.with_current_dir(current_dir),
+            None => runner,
+        };
+        Ok(match self.timeout {
+            Some(timeout) => runner.with_timeout(timeout),
+            None => runner,
+        })
+    }
+}
+
+/// Cancellation flags of a job, one per board config.
+///
+/// Stopping the whole job force stops every board config, while stopping a
+/// single board config leaves the others running. Cloning shares the flags.
+#[derive(Debug, Clone)]
+pub struct JobStop {
+    job: Arc<AtomicBool>,
+    boards: Arc<HashMap<Uuid, Arc<AtomicBool>>>,
+}
+
+impl JobStop {
+    /// Creates the flags for every board config of the builder.
+    pub fn new(config: &EjConfig) -> Self {
+        let boards = config
+            .boards
+            .iter()
+            .flat_map(|board| &board.configs)
+            .map(|board_config| (board_config.id, Arc::new(AtomicBool::new(false))))
+            .collect();
+        Self {
+            job: Arc::new(AtomicBool::new(false)),
+            boards: Arc::new(boards),
+        }
+    }
+
+    /// Flag to hand to the runner executing a board config.
+    pub fn board(&self, board_config_id: &Uuid) -> Arc<AtomicBool> {
+        self.boards
+            .get(board_config_id)
+            .cloned()
+            .unwrap_or_else(|| Arc::clone(&self.job))
+    }
+
+    /// Force stops every board config of the job.
+    pub fn stop_all(&self) {
+        self.job.store(true, Ordering::Relaxed);
+        for stop in self.boards.values() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Force stops a single board config.
+    ///
+    /// # Returns
+    ///
+    /// Returns `false` if the board config isn't one of the builder's.
+    pub fn stop_board(&self, board_config_id: &Uuid) -> bool {
+        match self.boards.get(board_config_id) {
+            Some(stop) => {
+                stop.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether a single board config was cancelled while the job goes on.
+    ///
+    /// Cancelled board configs are skipped instead of failing the job.
+    pub fn is_board_cancelled(&self, board_config_id: &Uuid) -> bool {
+        !self.job.load(Ordering::Relaxed)
+            && self
+                .boards
+                .get(board_config_id)
+                .is_some_and(|stop| stop.load(Ordering::Relaxed))
     }
 }
 
 /// Spawns a runner process in a separate thread.
 ///
 /// Creates and starts a new runner process with the provided arguments,
-/// communication channels, and cancellation support.
+/// communication channels, and cancellation support. Log lines the script
+/// sends through the socket are reported as output, like the lines it prints,
+/// and its results are kept in [`SpawnRunnerArgs::script_messages`]. The
+/// script is killed if it runs past [`SpawnRunnerArgs::timeout`], and its
+/// container, if any, removed once it ended.
 ///
 /// # Arguments
 ///
@@ -80,6 +196,65 @@ pub fn spawn_runner(
     tx: Sender<RunEvent>,
     stop: Arc<AtomicBool>,
 ) -> JoinHandle<Option<ExitStatus>> {
+    args.script_messages
+        .register(&args.board_name, &args.config_name, &tx);
+    let container = args.container.clone();
     let runner = args.build_runner();
-    task::spawn(async move { runner.run(tx, stop).await })
+    task::spawn(async move {
+        let runner = match runner {
+            Ok(runner) => runner,
+            Err(err) => {
+                let _ = tx
+                    .send(RunEvent::ProcessCreationFailed(err.to_string()))
+                    .await;
+                return None;
+            }
+        };
+        let exit_status = runner.run(tx, stop).await;
+        if let Some(container) = container {
+            container.remove().await;
+        }
+        exit_status
+    })
+}
+
+/// Runs a hook of a board config, `script` taking the place of its build or
+/// run script with the same arguments, environment and container.
+///
+/// Lines the hook outputs are handed to `on_line`. Hooks of stopped board
+/// configs aren't run.
+///
+/// # Returns
+///
+/// Whether the hook ran and succeeded.
+pub async fn run_hook(
+    mut args: SpawnRunnerArgs,
+    hook: &str,
+    script: &str,
+    stop: Arc<AtomicBool>,
+    mut on_line: impl FnMut(String),
+) -> bool {
+    if stop.load(Ordering::Relaxed) {
+        return false;
+    }
+    let label = format!("{} - {} {hook} hook", args.board_name, args.config_name);
+    args.script_name = script.to_string();
+    let (tx, mut rx) = channel(10);
+    let handle = spawn_runner(args, tx, stop);
+    while let Some(event) = rx.recv().await {
+        match event {
+            RunEvent::ProcessCreationFailed(err) => error!("{label} failed to start - {err}"),
+            RunEvent::ProcessCreated => info!("{label} started"),
+            RunEvent::ProcessEnd(success, usage) => {
+                if success {
+                    info!("{label} ended successfully ({usage})");
+                } else {
+                    error!("{label} failed ({usage})");
+                }
+            }
+            RunEvent::ProcessNewOutputLine(line) => on_line(line),
+            RunEvent::Timeout => error!("{label} timed out"),
+        }
+    }
+    matches!(handle.await, Ok(Some(exit_status)) if exit_status.success())
 }
@@ -2,46 +2,105 @@
 //!
 //! This module handles the complete connection lifecycle with EJD:
 //!
-//! 1. **Authentication**: Login to EJD using builder credentials
+//! 1. **Authentication**: Login to EJD using builder credentials, refreshing the
+//!    short-lived access token before it expires
 //! 2. **Configuration Upload**: Send builder configuration to EJD  
-//! 3. **WebSocket Connection**: Establish persistent connection for job communication
-//! 4. **Job Execution**: Process incoming jobs (checkout, build, run)
+//! 3. **WebSocket Connection**: Establish persistent connection for job communication,
+//!    reconnecting and resynchronizing the current job if the connection drops
+//! 4. **Job Execution**: Process incoming jobs (checkout, build, run), streaming
+//!    their logs and artifacts to EJD as they're produced
 //! 5. **Result Reporting**: Send job results back to EJD via REST API
 //!
 //! The connection uses both REST API and WebSocket protocols to communicate
 //! with the dispatcher service efficiently.
 
-use std::str::FromStr;
+use std::collections::{BTreeSet, VecDeque};
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 use crate::prelude::*;
-use crate::run_output::EjRunOutput;
-use ej_auth::{AUTH_HEADER, AUTH_HEADER_PREFIX};
+use crate::run_output::{EjLogStream, EjRunOutput};
+use ej_auth::AUTH_HEADER;
 use ej_builder_sdk::BuilderEvent;
 use ej_config::ej_config::EjConfig;
-use ej_dispatcher_sdk::ejbuilder::EjBuilderApi;
-use ej_dispatcher_sdk::ejjob::EjJobCancelReason;
+use ej_dispatcher_sdk::ejbuilder::{EjBuilderApi, EjBuilderCapabilities};
 use ej_dispatcher_sdk::ejjob::results::{EjBuilderBuildResult, EjBuilderRunResult};
-use ej_dispatcher_sdk::ejws_message::EjWsServerMessage;
+use ej_dispatcher_sdk::ejjob::{EjDeployableJob, EjJobCancelReason};
+use ej_dispatcher_sdk::ejtoken::{EjRefreshTokenRequest, EjTokens};
+use ej_dispatcher_sdk::ejws_message::{
+    EJ_WS_COMPRESSION_HEADER, EJ_WS_PROTOCOL_VERSION, EJ_WS_PROTOCOL_VERSION_HEADER, EjWsBinary,
+    EjWsClientMessage, EjWsCompression, EjWsPayload, EjWsServerEnvelope, EjWsServerMessage,
+};
 use ej_requests::ApiClient;
 use futures_util::stream::SplitSink;
 use futures_util::{SinkExt, StreamExt};
 use tokio::net::TcpStream;
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+use tokio::sync::{Mutex, watch};
 use tokio::task::JoinHandle;
-use tokio::time::{interval, timeout};
+use tokio::time::{Instant, interval, sleep, sleep_until, timeout};
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
 use tokio_tungstenite::tungstenite::{Bytes, Message};
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite};
 use tracing::{debug, error, info, trace, warn};
 use uuid::Uuid;
 
+use crate::artifacts::upload_artifacts;
 use crate::build::build;
 use crate::builder::Builder;
 use crate::checkout::checkout_all;
+use crate::common::JobStop;
+use crate::log_batch::{LogBatch, LogBatchPolicy, LogEvent};
 use crate::logs::dump_logs_to_temporary_file;
+use crate::probe::available_boards;
 use crate::run::run;
+use crate::workspace::relocate;
+
+/// Interval between the heartbeat pings sent to EJD.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long EJD may stay silent before the connection is considered dead.
+const PONG_DEADLINE: Duration = Duration::from_secs(60);
+
+/// Delay before the first reconnection attempt after losing the connection to EJD.
+const RECONNECT_MIN_DELAY: Duration = Duration::from_secs(1);
+
+/// Upper bound for the exponential backoff between reconnection attempts.
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Maximum number of log messages buffered while waiting to be sent to EJD.
+const LOG_STREAM_CAPACITY: usize = 1024;
+
+/// How long a finished job waits for its streamed logs to be sent before reporting its results.
+const LOG_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of acknowledged message IDs remembered to ignore retransmissions.
+const DELIVERED_HISTORY: usize = 64;
+
+/// Maximum number of artifact payloads buffered while waiting to be sent to EJD.
+const ARTIFACT_STREAM_CAPACITY: usize = 16;
+
+/// Senders handed to running jobs to stream their output to EJD.
+#[derive(Clone)]
+struct JobSenders {
+    logs: Sender<LogEvent>,
+    artifacts: Sender<EjWsPayload>,
+}
+
+/// Channels used by running jobs to stream their logs and artifacts to EJD.
+///
+/// They outlive WebSocket sessions so output produced while reconnecting is
+/// sent once the connection is back. Logs are dropped if their buffer fills up
+/// while artifact uploads wait for room.
+struct JobChannels {
+    tx: JobSenders,
+    logs_rx: Receiver<LogEvent>,
+    artifacts_rx: Receiver<EjWsPayload>,
+    /// Logs received from the running job, waiting to be sent.
+    log_batch: LogBatch,
+}
 
 /// Handles the complete connection workflow with EJD dispatcher.
 ///
@@ -54,6 +113,18 @@ use crate::run::run;
 /// 4. Processes incoming job assignments
 /// 5. Reports job results back to EJD
 ///
+/// Several EJD URLs can be given for high availability deployments. The builder
+/// connects to the first reachable one, in the order they're given, and fails over
+/// to the next ones when it can't be reached.
+///
+/// If no EJD can be reached, or the WebSocket connection is lost, the builder keeps
+/// working on its current job and tries again with an exponential backoff: it logs in
+/// again, uploads its config again in case EJD lost it, reconnects and reports the job
+/// it's still executing so EJD can re-adopt it. Results of jobs are reported to the
+/// EJD the builder is connected to when they end. Each state change of the connection
+/// is logged. The builder only stops when EJD closes the connection, which it does
+/// when the builder is revoked, or when EJD speaks another protocol version.
+///
 /// # Examples
 ///
 /// ```bash
@@ -64,47 +135,351 @@ use crate::run::run;
 /// export EJB_ID=builder-123
 /// export EJB_TOKEN=jwt_token
 /// ejb connect --server https://dispatcher.example.com
+///
+/// # Or the credentials file written by `ejb login`
+/// ejb connect --server https://dispatcher.example.com
+///
+/// # Fail over to a standby dispatcher
+/// ejb connect --server https://ejd-1.example.com --server https://ejd-2.example.com
 /// ```
 pub async fn handle_connect(
     builder: Builder,
-    server_url: &str,
-    id: Option<String>,
-    token: Option<String>,
+    server_urls: &[String],
+    credentials: EjBuilderApi,
+    compression: EjWsCompression,
+    log_batch: LogBatchPolicy,
 ) -> Result<()> {
     info!("Starting builder with config: {:?}", builder.config_path);
 
-    info!("Connecting to server: {}", server_url);
-    let config = &builder.config;
+    let credentials = Arc::new(credentials);
+    let dispatchers: Vec<Dispatcher> = server_urls
+        .iter()
+        .map(|url| Dispatcher::new(url, &credentials))
+        .collect();
+    let Some(first) = dispatchers.first() else {
+        return Err(Error::ServerMissing);
+    };
 
-    let id = Uuid::from_str(
-        &id.or_else(|| std::env::var("EJB_ID").ok())
-            .ok_or_else(|| Error::BuilderIDMissing)?,
-    )?;
+    let mut state = ConnectionState::Disconnected;
+    let builder = Arc::new(builder);
+    // Jobs report their results to the EJD the builder is connected to when they end
+    let (active_client_tx, active_client) = watch::channel(Arc::clone(&first.client));
+    let mut active_url = first.url.clone();
+    let mut current_job: Option<(Uuid, JoinHandle<()>, JobStop)> = None;
+    let mut reconnect_delay = RECONNECT_MIN_DELAY;
+    let (logs_tx, logs_rx) = channel(LOG_STREAM_CAPACITY);
+    let (artifacts_tx, artifacts_rx) = channel(ARTIFACT_STREAM_CAPACITY);
+    let mut channels = JobChannels {
+        tx: JobSenders {
+            logs: logs_tx,
+            artifacts: artifacts_tx,
+        },
+        logs_rx,
+        artifacts_rx,
+        log_batch: LogBatch::new(log_batch),
+    };
 
-    let auth_token = token
-        .or_else(|| std::env::var("EJB_TOKEN").ok())
-        .ok_or_else(|| Error::BuilderTokenMissing)?;
+    let mut result = Ok(());
+    'reconnect: loop {
+        // The first reachable EJD is used, the next ones are only failed over to
+        for dispatcher in dispatchers.iter() {
+            info!("Connecting to server: {}", dispatcher.url);
+            let uploaded = match prepare_session(
+                dispatcher,
+                &credentials,
+                &builder.config,
+                &mut state,
+            )
+            .await
+            {
+                Ok(session) => session,
+                Err(err) => {
+                    error!("{err}");
+                    state.set(ConnectionState::Disconnected);
+                    continue;
+                }
+            };
+            let config = Arc::new(uploaded);
+            state.set(ConnectionState::Connecting);
+            let connection = connect_websocket(
+                &dispatcher.url,
+                dispatcher.client.authorization(),
+                compression,
+            )
+            .await;
+            let (ws_stream, compression) = match connection {
+                Ok(connection) => connection,
+                Err(
+                    err @ (Error::WsProtocolVersionMismatch { .. }
+                    | Error::WsProtocolVersionUnknown),
+                ) => {
+                    error!("{err}");
+                    result = Err(err);
+                    break 'reconnect;
+                }
+                Err(err) => {
+                    error!("Failed to connect to WebSocket - {err}");
+                    state.set(ConnectionState::Disconnected);
+                    continue;
+                }
+            };
+            state.set(ConnectionState::Connected);
+            info!("WebSocket connection established, compression: {compression:?}");
+            if dispatcher.url != active_url {
+                warn!("Failed over from {active_url} to {}", dispatcher.url);
+                active_url = dispatcher.url.clone();
+            }
+            active_client_tx.send_replace(Arc::clone(&dispatcher.client));
+            reconnect_delay = RECONNECT_MIN_DELAY;
+            match run_session(
+                ws_stream,
+                compression,
+                &config,
+                &builder,
+                &active_client,
+                &credentials,
+                &dispatcher.tokens,
+                &mut current_job,
+                &mut channels,
+            )
+            .await
+            {
+                Ok(SessionEnd::Closed) => break 'reconnect,
+                Ok(SessionEnd::ConnectionLost) => {}
+                Err(err) => error!("WebSocket session failed - {err}"),
+            }
+            break;
+        }
 
-    let client = ApiClient::new(server_url);
-    let builder_api = EjBuilderApi {
-        id,
-        token: auth_token.clone(),
-    };
+        state.set(ConnectionState::Disconnected);
+        warn!("Reconnecting to EJD in {:?}", reconnect_delay);
+        sleep(reconnect_delay).await;
+        reconnect_delay = (reconnect_delay * 2).min(RECONNECT_MAX_DELAY);
+    }
+    state.set(ConnectionState::Closed);
+
+    if let Some(job) = current_job.take()
+        && !job.1.is_finished()
+    {
+        cancel_job(
+            &builder,
+            &job.0,
+            job.1,
+            job.2,
+            EjJobCancelReason::ConnectionLost,
+        )
+        .await;
+    }
+
+    println!("Builder shutting down");
+    result
+}
+
+/// An EJD the builder can connect to.
+struct Dispatcher {
+    url: String,
+    /// Client renewing the builder's tokens when EJD rejects its access token.
+    client: Arc<ApiClient>,
+    /// Tokens EJD issued to the builder, `None` until it logged in.
+    tokens: Arc<Mutex<Option<EjTokens>>>,
+}
+
+impl Dispatcher {
+    fn new(url: &str, credentials: &Arc<EjBuilderApi>) -> Self {
+        let tokens = Arc::new(Mutex::new(None));
+        // The hook renews through a client without it, a rejected refresh would wait for itself
+        let auth_client = ApiClient::new(url);
+        let client = auth_client.clone().with_token_refresh({
+            let tokens = Arc::clone(&tokens);
+            let credentials = Arc::clone(credentials);
+            move || {
+                let auth_client = auth_client.clone();
+                let tokens = Arc::clone(&tokens);
+                let credentials = Arc::clone(&credentials);
+                async move {
+                    let mut tokens = tokens.lock().await;
+                    match renew_tokens(&auth_client, &credentials, &mut tokens).await {
+                        Ok(renewed) => Some(renewed.access_token.clone()),
+                        Err(err) => {
+                            error!("Failed to renew the access token - {err}");
+                            None
+                        }
+                    }
+                }
+            }
+        });
+        Self {
+            url: url.to_owned(),
+            client: Arc::new(client),
+            tokens,
+        }
+    }
+}
 
-    let body = serde_json::to_string(&builder_api)?;
-    let builder_api: EjBuilderApi = client
+/// Logs in to EJD with the builder's credentials, `client` using the new access token.
+async fn login(client: &ApiClient, credentials: &EjBuilderApi) -> Result<EjTokens> {
+    let body = serde_json::to_string(credentials)?;
+    let tokens: EjTokens = client
         .post_and_deserialize("v1/builder/login", body)
         .await
-        .expect("Failed to login");
+        .map_err(|err| Error::Login(err.to_string()))?;
+    client.set_token(tokens.access_token.clone());
+    Ok(tokens)
+}
+
+/// Renews the builder's access token, `client` using the new one.
+///
+/// The refresh token is used first, logging in again with the builder's
+/// credentials if EJD doesn't accept it anymore or the builder never logged in.
+///
+/// Only called by the token refresh hook of the [`Dispatcher`] client, which
+/// never runs twice at once, so a refresh token is never used twice.
+async fn renew_tokens<'a>(
+    client: &ApiClient,
+    credentials: &EjBuilderApi,
+    tokens: &'a mut Option<EjTokens>,
+) -> Result<&'a EjTokens> {
+    if let Some(previous) = tokens.as_ref() {
+        let body = serde_json::to_string(&EjRefreshTokenRequest::new(&previous.refresh_token))?;
+        match client
+            .post_and_deserialize::<_, EjTokens>("v1/token/refresh", body)
+            .await
+        {
+            Ok(refreshed) => {
+                debug!("Refreshed access token");
+                client.set_token(refreshed.access_token.clone());
+                return Ok(tokens.insert(refreshed));
+            }
+            Err(err) => warn!("Failed to refresh access token, logging in again - {err}"),
+        }
+    }
+    Ok(tokens.insert(login(client, credentials).await?))
+}
 
-    info!("Successfully logged in as builder {}", builder_api.id);
-    let body = serde_json::to_string(&config)?;
-    let config: EjConfig = client
+/// Uploads the builder's config, returning it as EJD stored it.
+///
+/// EJD keeps the IDs of a config it already knows, so uploading it again
+/// after reconnecting doesn't change them.
+async fn upload_config(client: &ApiClient, config: &EjConfig) -> Result<EjConfig> {
+    let body = serde_json::to_string(config)?;
+    client
         .post_and_deserialize("v1/builder/config", body)
         .await
-        .expect("Failed to push config");
+        .map_err(|err| Error::ConfigUpload(err.to_string()))
+}
+
+/// Gets a session with EJD ready: logs in, or renews the tokens of the
+/// previous session, then uploads the builder's config.
+///
+/// Returns the config as EJD stored it.
+async fn prepare_session(
+    dispatcher: &Dispatcher,
+    credentials: &EjBuilderApi,
+    config: &EjConfig,
+    state: &mut ConnectionState,
+) -> Result<EjConfig> {
+    state.set(ConnectionState::LoggingIn);
+    // Our token may have expired while we were away
+    if !dispatcher.client.renew_token().await {
+        return Err(Error::Login(format!(
+            "{} rejected the builder",
+            dispatcher.url
+        )));
+    }
+    info!("Successfully logged in as builder {}", credentials.id);
+
+    state.set(ConnectionState::UploadingConfig);
+    let config = upload_config(&dispatcher.client, config).await?;
     info!("Successfully pushed config");
+    Ok(config)
+}
+
+/// How long to wait before refreshing an access token valid for `expires_in` seconds.
+fn token_refresh_delay(expires_in: i64) -> Duration {
+    Duration::from_secs(expires_in.max(2) as u64 / 2)
+}
+
+/// How long to wait before refreshing the builder's current access token.
+async fn next_token_refresh(tokens: &Mutex<Option<EjTokens>>) -> Duration {
+    let expires_in = tokens
+        .lock()
+        .await
+        .as_ref()
+        .map_or(0, |tokens| tokens.expires_in);
+    token_refresh_delay(expires_in)
+}
+
+/// State of the builder's connection to EJD, logged on every change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectionState {
+    /// Not connected, waiting before trying again.
+    Disconnected,
+    /// Logging in, or renewing the tokens of the previous session.
+    LoggingIn,
+    /// Uploading the builder's config.
+    UploadingConfig,
+    /// Opening the WebSocket connection.
+    Connecting,
+    /// Receiving jobs over the WebSocket connection.
+    Connected,
+    /// EJD closed the connection, the builder is shutting down.
+    Closed,
+}
+
+impl ConnectionState {
+    /// Moves to `next`, logging the transition.
+    fn set(&mut self, next: Self) {
+        if *self != next {
+            info!("Connection to EJD: {self:?} -> {next:?}");
+            *self = next;
+        }
+    }
+}
+
+/// How a WebSocket session with EJD ended.
+#[derive(Debug, PartialEq, Eq)]
+enum SessionEnd {
+    /// EJD closed the connection, the builder should shut down.
+    Closed,
+    /// The connection was lost and should be re-established.
+    ConnectionLost,
+}
+
+/// Extracts the WebSocket protocol version EJD advertised in its upgrade response.
+fn dispatcher_protocol_version<T>(response: &tungstenite::http::Response<T>) -> Option<u32> {
+    response
+        .headers()
+        .get(EJ_WS_PROTOCOL_VERSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+/// Fails if EJD speaks a different WebSocket protocol version than ours.
+fn check_protocol_version(dispatcher: Option<u32>) -> Result<()> {
+    match dispatcher {
+        Some(EJ_WS_PROTOCOL_VERSION) => Ok(()),
+        Some(dispatcher) => Err(Error::WsProtocolVersionMismatch {
+            builder: EJ_WS_PROTOCOL_VERSION,
+            dispatcher,
+        }),
+        None => Err(Error::WsProtocolVersionUnknown),
+    }
+}
 
+/// Opens the WebSocket connection to EJD, authenticated with the builder's token.
+///
+/// Both ends exchange their WebSocket protocol version during the upgrade so
+/// mismatched EJB and EJD releases are detected before any message is sent.
+/// The compression of large payloads is negotiated at the same time.
+///
+/// # Returns
+///
+/// The WebSocket stream and the compression EJD agreed to use.
+async fn connect_websocket(
+    server_url: &str,
+    authorization: Option<String>,
+    compression: EjWsCompression,
+) -> Result<(WebSocketStream<MaybeTlsStream<TcpStream>>, EjWsCompression)> {
     let ws_url = if server_url.starts_with("https") {
         server_url.replace("https", "wss")
     } else {
@@ -119,97 +494,311 @@ pub async fn handle_connect(
         .into_client_request()
         .expect("Failed to create client websocket request");
 
-    request.headers_mut().insert(
-        AUTH_HEADER,
-        format!("{}{}", AUTH_HEADER_PREFIX, builder_api.token)
-            .parse()
-            .unwrap(),
-    );
+    if let Some(authorization) = authorization {
+        request
+            .headers_mut()
+            .insert(AUTH_HEADER, authorization.parse().unwrap());
+    }
 
-    let (ws_stream, _) = connect_async(request).await?;
+    request
+        .headers_mut()
+        .insert(EJ_WS_PROTOCOL_VERSION_HEADER, EJ_WS_PROTOCOL_VERSION.into());
+    if compression != EjWsCompression::None {
+        request.headers_mut().insert(
+            EJ_WS_COMPRESSION_HEADER,
+            compression.header_value().parse().unwrap(),
+        );
+    }
 
-    info!("WebSocket connection established");
+    let (ws_stream, response) = match connect_async(request).await {
+        Ok(connection) => connection,
+        Err(tungstenite::Error::Http(response))
+            if response.status() == tungstenite::http::StatusCode::UPGRADE_REQUIRED =>
+        {
+            check_protocol_version(dispatcher_protocol_version(&response))?;
+            return Err(tungstenite::Error::Http(response).into());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    check_protocol_version(dispatcher_protocol_version(&response))?;
+    let compression = EjWsCompression::negotiate(
+        response
+            .headers()
+            .get(EJ_WS_COMPRESSION_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    );
+    Ok((ws_stream, compression))
+}
 
+/// Runs a single WebSocket session with EJD until the connection closes or dies.
+///
+/// The session starts by reporting the job currently in progress, if any, so EJD
+/// can re-adopt it after a reconnection instead of dispatching it again, followed
+/// by the builder's capabilities.
+/// The current job outlives the session so it keeps running while reconnecting.
+/// Logs produced by the current job are forwarded to EJD in batches, and its
+/// artifacts as they come.
+/// The access token is refreshed before it expires so results can still be
+/// reported over the REST API.
+async fn run_session(
+    ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    compression: EjWsCompression,
+    config: &Arc<EjConfig>,
+    builder: &Arc<Builder>,
+    active_client: &watch::Receiver<Arc<ApiClient>>,
+    builder_api: &EjBuilderApi,
+    tokens: &Mutex<Option<EjTokens>>,
+    current_job: &mut Option<(Uuid, JoinHandle<()>, JobStop)>,
+    channels: &mut JobChannels,
+) -> Result<SessionEnd> {
+    let client = Arc::clone(&active_client.borrow());
     let (mut write, mut read) = ws_stream.split();
 
-    let mut current_job: Option<(Uuid, JoinHandle<()>, Arc<AtomicBool>)> = None;
-    let config = Arc::new(config);
-    let builder = Arc::new(builder);
-    let client = Arc::new(client);
-    let mut heartbeat_interval = interval(Duration::from_secs(30));
+    if current_job.as_ref().is_some_and(|job| job.1.is_finished()) {
+        *current_job = None;
+    }
+    let job_state = EjWsClientMessage::JobState {
+        job_id: current_job.as_ref().map(|job| job.0),
+    };
+    info!("Reporting job state {:?}", job_state);
+    write.send(encode_message(&job_state, compression)?).await?;
+    let capabilities = EjWsClientMessage::Capabilities(EjBuilderCapabilities::from_config(config));
+    write
+        .send(encode_message(&capabilities, compression)?)
+        .await?;
+    let mut locked_boards = builder.board_locks.subscribe();
+    let board_locks = board_locks_message(&mut locked_boards);
+    write
+        .send(encode_message(&board_locks, compression)?)
+        .await?;
+
+    let mut heartbeat_interval = interval(HEARTBEAT_INTERVAL);
+    let refresh = sleep(next_token_refresh(tokens).await);
+    tokio::pin!(refresh);
     let mut last_pong = std::time::Instant::now();
-    let connection_timeout = Duration::from_secs(60);
+    let mut delivered = VecDeque::with_capacity(DELIVERED_HISTORY);
 
     loop {
+        let logs_deadline = channels.log_batch.deadline();
         tokio::select! {
             message_result = timeout(Duration::from_secs(5), read.next()) => {
                 match message_result {
                     Ok(Some(message)) => {
-                            if let Some(ref job) = current_job {
-                                if job.1.is_finished() {
-                                    current_job = None;
-                                }
+                            if current_job.as_ref().is_some_and(|job| job.1.is_finished()) {
+                                *current_job = None;
                             }
-                            let close = handle_message(message?, &mut write, &config, &builder, &client, &builder_api, &mut current_job, &mut last_pong).await;
-                            if close {
-                                break;
+                            let message = match message {
+                                Ok(Message::Binary(data)) => match EjWsBinary::decode(&data) {
+                                    Ok(EjWsBinary::Message(text)) => Message::Text(text.into()),
+                                    Ok(EjWsBinary::ArtifactChunk { .. }) => {
+                                        warn!("Received unexpected artifact chunk");
+                                        continue;
+                                    }
+                                    Err(err) => {
+                                        error!("Failed to decode binary message - {err}");
+                                        continue;
+                                    }
+                                },
+                                Ok(message) => message,
+                                Err(err) => {
+                                    error!("Failed to read from WebSocket - {err}");
+                                    return Ok(SessionEnd::ConnectionLost);
+                                }
+                            };
+                            if let Some(end) = handle_message(message, &mut write, config, builder, active_client, builder_api, current_job, &channels.tx, &mut last_pong, &mut delivered).await {
+                                return Ok(end);
                             }
                         }
                     Ok(None) => {
                         warn!("WebSocket stream ended (received None)");
-                        break;
+                        return Ok(SessionEnd::ConnectionLost);
                     }
                     Err(err) => {
                         debug!("Message timeout - checking connection health - {err}");
-                        if last_pong.elapsed() > connection_timeout {
-                            error!("Connection appears dead - no pong received for {:?}", connection_timeout);
-                            break;
+                        if last_pong.elapsed() > PONG_DEADLINE {
+                            error!("Connection appears dead - no pong received for {:?}", PONG_DEADLINE);
+                            return Ok(SessionEnd::ConnectionLost);
                         }
                     }
                 }
             }
+            Some(event) = channels.logs_rx.recv() => match event {
+                LogEvent::Chunk(chunk) => {
+                    channels.log_batch.push(chunk);
+                    if channels.log_batch.is_full() && !send_logs(&mut write, &mut channels.log_batch, compression).await? {
+                        return Ok(SessionEnd::ConnectionLost);
+                    }
+                }
+                LogEvent::Flush(done) => {
+                    if !send_logs(&mut write, &mut channels.log_batch, compression).await? {
+                        return Ok(SessionEnd::ConnectionLost);
+                    }
+                    let _ = done.send(());
+                }
+            },
+            _ = sleep_until(logs_deadline.unwrap_or_else(Instant::now)), if logs_deadline.is_some() => {
+                if !send_logs(&mut write, &mut channels.log_batch, compression).await? {
+                    return Ok(SessionEnd::ConnectionLost);
+                }
+            }
+            Ok(()) = locked_boards.changed() => {
+                let board_locks = board_locks_message(&mut locked_boards);
+                if let Err(err) = write.send(encode_message(&board_locks, compression)?).await {
+                    error!("Failed to send board locks - {err}");
+                    return Ok(SessionEnd::ConnectionLost);
+                }
+            }
+            Some(payload) = channels.artifacts_rx.recv() => {
+                if let Err(err) = write.send(payload_to_message(payload)).await {
+                    error!("Failed to send artifact - {err}");
+                    return Ok(SessionEnd::ConnectionLost);
+                }
+            }
+            _ = &mut refresh => {
+                // Failures are logged by the token refresh hook
+                client.renew_token().await;
+                refresh.as_mut().reset(Instant::now() + next_token_refresh(tokens).await);
+            }
             _ = heartbeat_interval.tick() => {
                 debug!("Sending heartbeat ping");
                 if let Err(e) = write.send(Message::Ping(Bytes::new())).await {
                     error!("Failed to send heartbeat ping: {}", e);
-                    break;
+                    return Ok(SessionEnd::ConnectionLost);
                 }
 
                 // Check if we haven't received a pong in too long
-                if last_pong.elapsed() > connection_timeout {
-                    error!("No pong received for {:?} - connection likely dead", connection_timeout);
-                    break;
+                if last_pong.elapsed() > PONG_DEADLINE {
+                    error!("No pong received for {:?} - connection likely dead", PONG_DEADLINE);
+                    return Ok(SessionEnd::ConnectionLost);
                 }
             }
         }
     }
+}
+
+/// Serializes a message into a WebSocket frame, compressing it if it's large enough.
+fn encode_message(message: &EjWsClientMessage, compression: EjWsCompression) -> Result<Message> {
+    Ok(payload_to_message(
+        compression.compress(serde_json::to_string(message)?)?,
+    ))
+}
+
+/// Reports the boards currently locked by the jobs of the builder.
+fn board_locks_message(locked_boards: &mut watch::Receiver<BTreeSet<Uuid>>) -> EjWsClientMessage {
+    EjWsClientMessage::BoardLocks {
+        boards: locked_boards.borrow_and_update().iter().copied().collect(),
+    }
+}
+
+/// Sends the pending logs to EJD, returning whether they could be sent.
+///
+/// The logs that couldn't be sent are dropped.
+async fn send_logs(
+    write: &mut SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+    log_batch: &mut LogBatch,
+    compression: EjWsCompression,
+) -> Result<bool> {
+    for chunk in log_batch.take() {
+        if let Err(err) = write.send(encode_message(&chunk, compression)?).await {
+            error!("Failed to send logs - {err}");
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
 
-    println!("Builder shutting down");
-    Ok(())
+/// Wraps a payload into the matching WebSocket frame.
+fn payload_to_message(payload: EjWsPayload) -> Message {
+    match payload {
+        EjWsPayload::Text(text) => Message::Text(text.into()),
+        EjWsPayload::Binary(data) => Message::Binary(data.into()),
+    }
+}
+
+/// Restricts `config` to the board configs the job targets.
+///
+/// Returns the config the job runs against and the board configs it skips.
+fn config_for_job(config: &Arc<EjConfig>, job: &EjDeployableJob) -> (Arc<EjConfig>, Vec<Uuid>) {
+    if job.board_configs.is_empty() && job.required_tags.is_empty() {
+        return (Arc::clone(config), Vec::new());
+    }
+    let (filtered, skipped) = config.filter_for_job(&job.board_configs, &job.required_tags);
+    for board_config_id in skipped.iter() {
+        info!(
+            "Skipping board config {board_config_id}, it isn't in {:?} with every tag in {:?}",
+            job.board_configs, job.required_tags
+        );
+    }
+    (Arc::new(filtered), skipped)
+}
+
+/// Creates the workspace of a job and resolves the paths of `config` from it.
+///
+/// Returns the config the job runs against, its workspace and whether it
+/// could be created, the job fails if it couldn't.
+async fn job_workspace(
+    builder: &Builder,
+    config: Arc<EjConfig>,
+    job_id: Uuid,
+) -> (Arc<EjConfig>, Option<PathBuf>, Result<()>) {
+    match builder.workspaces.create(job_id).await {
+        Ok(workspace) => {
+            info!("Running job {job_id} in {}", workspace.display());
+            let config = Arc::new(relocate(&config, &workspace));
+            (config, Some(workspace), Ok(()))
+        }
+        Err(err) => {
+            error!("Failed to create the workspace of job {job_id} - {err}");
+            (config, None, Err(err))
+        }
+    }
 }
+
 async fn handle_message(
     message: tungstenite::protocol::Message,
     write: &mut SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
     config: &Arc<EjConfig>,
     builder: &Arc<Builder>,
-    client: &Arc<ApiClient>,
+    active_client: &watch::Receiver<Arc<ApiClient>>,
     builder_api: &EjBuilderApi,
-    current_job: &mut Option<(Uuid, JoinHandle<()>, Arc<AtomicBool>)>,
+    current_job: &mut Option<(Uuid, JoinHandle<()>, JobStop)>,
+    senders: &JobSenders,
     last_pong: &mut std::time::Instant,
-) -> bool {
+    delivered: &mut VecDeque<Uuid>,
+) -> Option<SessionEnd> {
     match message {
         Message::Text(text) => {
             info!("Received message: {}", text);
 
-            let server_message: EjWsServerMessage = match serde_json::from_str(&text) {
+            let envelope: EjWsServerEnvelope = match serde_json::from_str(&text) {
                 Ok(msg) => msg,
                 Err(e) => {
                     error!("Failed to parse server message: {}", e);
-                    return false;
+                    return None;
                 }
             };
 
-            match server_message {
+            if let Some(message_id) = envelope.id {
+                let ack = EjWsClientMessage::Ack { message_id };
+                match serde_json::to_string(&ack) {
+                    Ok(ack) => {
+                        if let Err(err) = write.send(Message::Text(ack.into())).await {
+                            error!("Failed to acknowledge message {message_id} - {err}");
+                        }
+                    }
+                    Err(err) => error!("Failed to serialize ack - {err}"),
+                }
+                if delivered.contains(&message_id) {
+                    debug!("Ignoring retransmitted message {message_id}");
+                    return None;
+                }
+                if delivered.len() == DELIVERED_HISTORY {
+                    delivered.pop_front();
+                }
+                delivered.push_back(message_id);
+            }
+
+            match envelope.message {
                 EjWsServerMessage::Build(job) => {
                     if let Some(job) = current_job.take() {
                         warn!(
@@ -219,39 +808,71 @@ async fn handle_message(
                             .await;
                     }
 
-                    let config = Arc::clone(&config);
+                    let (config, skipped) = config_for_job(&config, &job);
                     let builder = Arc::clone(&builder);
-                    let client = Arc::clone(&client);
-                    let stop = Arc::new(AtomicBool::new(false));
-                    let t_stop = Arc::clone(&stop);
+                    let active_client = active_client.clone();
+                    let stop = JobStop::new(&config);
+                    let t_stop = stop.clone();
 
                     let id = builder_api.id;
+                    let log_stream = EjLogStream::new(job.id, senders.logs.clone());
+                    let artifacts_tx = senders.artifacts.clone();
                     let handle = tokio::spawn(async move {
-                        let mut output = EjRunOutput::new(&config);
-                        let mut result = checkout_all(
-                            &config,
-                            &job.commit_hash,
-                            &job.remote_url,
-                            job.remote_token,
-                            &mut output,
-                        )
-                        .await;
+                        let (config, unavailable) =
+                            available_boards(config, builder.probe_timeout).await;
+                        let skipped = [skipped, unavailable].concat();
+                        let (config, workspace, mut result) =
+                            job_workspace(&builder, config, job.id).await;
+                        let mut output = EjRunOutput::new(&config).with_log_stream(log_stream);
                         if result.is_ok() {
-                            result = build(&builder, &config, &mut output, t_stop).await;
+                            result = checkout_all(
+                                &builder,
+                                &config,
+                                &job.commit_hash,
+                                &job.remote_url,
+                                job.remote_token,
+                                &job.checkout,
+                                &mut output,
+                            )
+                            .await;
+                        }
+                        if result.is_ok() {
+                            result = build(
+                                &builder,
+                                &config,
+                                &mut output,
+                                &t_stop,
+                                &job.timeouts,
+                                Some(&job.commit_hash),
+                                workspace.as_deref(),
+                            )
+                            .await;
+                        }
+                        if result.is_ok() {
+                            upload_artifacts(&config, job.id, &t_stop, &artifacts_tx).await;
                         }
                         if let Err(err) = dump_logs_to_temporary_file(&output) {
                             error!("Failed to dump logs to file - {err}");
                         }
+                        if let Some(workspace) = &workspace {
+                            builder.workspaces.finish(workspace, result.is_ok()).await;
+                        }
+                        if let Some(log_stream) = &output.log_stream {
+                            log_stream.flush(LOG_FLUSH_TIMEOUT).await;
+                        }
                         let response = EjBuilderBuildResult {
                             job_id: job.id,
                             builder_id: id,
                             logs: output.logs,
+                            skipped,
                             successful: result.is_ok(),
                         };
 
                         let body = serde_json::to_string(&response);
                         match body {
                             Ok(body) => {
+                                // The builder may have failed over to another EJD since the job started
+                                let client = Arc::clone(&active_client.borrow());
                                 match client.post("v1/builder/build_result", body).await {
                                     Ok(response) => info!("Build results sent {:?}", response),
                                     Err(err) => {
@@ -275,42 +896,81 @@ async fn handle_message(
                         cancel_job(&builder, &job.0, job.1, job.2, EjJobCancelReason::Timeout)
                             .await;
                     }
-                    let config = Arc::clone(&config);
+                    let (config, skipped) = config_for_job(&config, &job);
                     let builder = Arc::clone(&builder);
-                    let client = Arc::clone(&client);
-                    let stop = Arc::new(AtomicBool::new(false));
-                    let t_stop = Arc::clone(&stop);
+                    let active_client = active_client.clone();
+                    let stop = JobStop::new(&config);
+                    let t_stop = stop.clone();
                     let id = builder_api.id;
+                    let log_stream = EjLogStream::new(job.id, senders.logs.clone());
+                    let artifacts_tx = senders.artifacts.clone();
                     let handle = tokio::spawn(async move {
-                        let mut output = EjRunOutput::new(&config);
-                        let mut result = checkout_all(
-                            &config,
-                            &job.commit_hash,
-                            &job.remote_url,
-                            job.remote_token,
-                            &mut output,
-                        )
-                        .await;
+                        let (config, unavailable) =
+                            available_boards(config, builder.probe_timeout).await;
+                        let skipped = [skipped, unavailable].concat();
+                        let (config, workspace, mut result) =
+                            job_workspace(&builder, config, job.id).await;
+                        let mut output = EjRunOutput::new(&config).with_log_stream(log_stream);
                         if result.is_ok() {
-                            result =
-                                build(&builder, &config, &mut output, Arc::clone(&t_stop)).await;
+                            result = checkout_all(
+                                &builder,
+                                &config,
+                                &job.commit_hash,
+                                &job.remote_url,
+                                job.remote_token,
+                                &job.checkout,
+                                &mut output,
+                            )
+                            .await;
                         }
                         if result.is_ok() {
-                            result = run(&builder, &config, &mut output, t_stop).await;
+                            result = build(
+                                &builder,
+                                &config,
+                                &mut output,
+                                &t_stop,
+                                &job.timeouts,
+                                Some(&job.commit_hash),
+                                workspace.as_deref(),
+                            )
+                            .await;
+                        }
+                        let built = result.is_ok();
+                        if built {
+                            result = run(
+                                &builder,
+                                &config,
+                                &mut output,
+                                &t_stop,
+                                &job.timeouts,
+                                workspace.as_deref(),
+                            )
+                            .await;
+                        }
+                        if built {
+                            upload_artifacts(&config, job.id, &t_stop, &artifacts_tx).await;
                         }
                         if let Err(err) = dump_logs_to_temporary_file(&output) {
                             error!("Failed to dump logs to file - {err}");
                         }
+                        if let Some(workspace) = &workspace {
+                            builder.workspaces.finish(workspace, result.is_ok()).await;
+                        }
+                        if let Some(log_stream) = &output.log_stream {
+                            log_stream.flush(LOG_FLUSH_TIMEOUT).await;
+                        }
                         let response = EjBuilderRunResult {
                             job_id: job.id,
                             builder_id: id,
                             logs: output.logs,
                             results: output.results,
+                            skipped,
                             successful: result.is_ok(),
                         };
                         let body = serde_json::to_string(&response);
                         match body {
                             Ok(body) => {
+                                let client = Arc::clone(&active_client.borrow());
                                 match client.post("v1/builder/run_result", body).await {
                                     Ok(_) => trace!("Run results sent"),
                                     Err(err) => {
@@ -339,15 +999,49 @@ async fn handle_message(
                         info!("Received cancel request but no job is currently in progress. ")
                     }
                 }
+                EjWsServerMessage::Board {
+                    board_config_id,
+                    message,
+                } => match *message {
+                    EjWsServerMessage::Cancel(reason, job_id) => match current_job.as_ref() {
+                        Some(curr_job) if curr_job.0 == job_id => {
+                            info!(
+                                "Cancelling board config {board_config_id} of {job_id} - Reason: {reason}"
+                            );
+                            if !curr_job.2.stop_board(&board_config_id) {
+                                warn!(
+                                    "Received cancel request for unknown board config {board_config_id}"
+                                );
+                            }
+                        }
+                        Some(_) => warn!(
+                            "Received board cancel request for a job different than the one in progress. "
+                        ),
+                        None => info!(
+                            "Received board cancel request but no job is currently in progress. "
+                        ),
+                    },
+                    message => {
+                        warn!(
+                            "Ignoring unsupported message for board config {board_config_id} - {message:?}"
+                        )
+                    }
+                },
                 EjWsServerMessage::Close => {
                     println!("Received close command from server");
-                    return true;
+                    return Some(SessionEnd::Closed);
                 }
             };
         }
-        Message::Close(_) => {
+        // EJD closes the connection normally to get rid of the builder, other
+        // closes, like a proxy going away, are connection losses
+        Message::Close(Some(frame)) if frame.code == CloseCode::Normal => {
             println!("WebSocket connection closed by server");
-            return true;
+            return Some(SessionEnd::Closed);
+        }
+        Message::Close(frame) => {
+            warn!("WebSocket connection closed by server - {frame:?}");
+            return Some(SessionEnd::ConnectionLost);
         }
         Message::Ping(data) => {
             debug!("Received ping, sending pong");
@@ -366,24 +1060,28 @@ async fn handle_message(
             debug!("Received raw frame message");
         }
     }
-    return false;
+    return None;
 }
 async fn cancel_job(
     builder: &Builder,
     job_id: &Uuid,
     mut handle: JoinHandle<()>,
-    stop: Arc<AtomicBool>,
+    stop: JobStop,
     reason: EjJobCancelReason,
 ) {
     info!("Cancelling {job_id} - Reason: {reason}");
 
-    // This sends a message to the child process to exit
-    if let Err(err) = builder.tx.send(BuilderEvent::Exit).await {
-        error!("Failed to send exit request to builder task - {err}");
+    // This asks the child process to stop working on the job and exit
+    let event = BuilderEvent::Cancel {
+        job_id: *job_id,
+        reason: reason.to_string(),
+    };
+    if let Err(err) = builder.tx.send(event).await {
+        error!("Failed to send cancel request to builder task - {err}");
     }
 
     // Ideally, the child process finishes its execution by itself and its task handler will finish
-    let timeout_result = timeout(Duration::from_secs(60), &mut handle).await;
+    let timeout_result = timeout(builder.exit_grace_period, &mut handle).await;
 
     match timeout_result {
         Ok(Ok(())) => {
@@ -397,9 +1095,9 @@ async fn cancel_job(
                 "Process taking care of {job_id} did not complete within timeout, forcing it to exit. \
                 This can cause problems in future runs. \
                 EJ recommends using its builder sdk to handle these cases for you. \
-                If you're already using it, make sure you handle the exit message correctly"
+                If you're already using it, make sure you handle the cancel message correctly"
             );
-            stop.store(true, Ordering::Relaxed);
+            stop.stop_all();
             let timeout_result = timeout(Duration::from_secs(30), &mut handle).await;
 
             match timeout_result {
@@ -419,3 +1117,38 @@ async fn cancel_job(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_protocol_version() {
+        assert!(check_protocol_version(Some(EJ_WS_PROTOCOL_VERSION)).is_ok());
+        assert!(matches!(
+            check_protocol_version(Some(EJ_WS_PROTOCOL_VERSION + 1)),
+            Err(Error::WsProtocolVersionMismatch { builder, dispatcher })
+                if builder == EJ_WS_PROTOCOL_VERSION && dispatcher == EJ_WS_PROTOCOL_VERSION + 1
+        ));
+        assert!(matches!(
+            check_protocol_version(None),
+            Err(Error::WsProtocolVersionUnknown)
+        ));
+    }
+
+    #[test]
+    fn test_dispatcher_protocol_version() {
+        let response = tungstenite::http::Response::builder()
+            .status(tungstenite::http::StatusCode::UPGRADE_REQUIRED)
+            .header(EJ_WS_PROTOCOL_VERSION_HEADER, EJ_WS_PROTOCOL_VERSION)
+            .body(())
+            .unwrap();
+        assert_eq!(
+            dispatcher_protocol_version(&response),
+            Some(EJ_WS_PROTOCOL_VERSION)
+        );
+
+        let response = tungstenite::http::Response::builder().body(()).unwrap();
+        assert_eq!(dispatcher_protocol_version(&response), None);
+    }
+}
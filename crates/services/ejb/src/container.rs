@@ -0,0 +1,154 @@
+//! Container execution of the build and run scripts.
+//!
+//! Board configs with a `container` have their scripts run inside a container
+//! of its image, with Docker or Podman, instead of directly on the builder.
+//! Everything the scripts use is bind mounted at the same path as on the
+//! builder, so they see the same paths and arguments either way:
+//!
+//! - The directory they run in, the job's workspace if any
+//! - The library path and the directory of the results path
+//! - The script itself, the configuration file and the builder socket
+//!
+//! The devices of the container config are made available too. Scripts run
+//! as the builder user so the files they create can be cleaned up, and share
+//! the builder network to reach the boards. The container is removed once
+//! the script exits, is killed or times out.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use ej_config::{ej_board_config::EjBoardConfig, ej_container::EjContainerConfig};
+use tokio::process::Command;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Default program running the containers.
+pub const DEFAULT_CONTAINER_ENGINE: &str = "docker";
+
+/// Container a single script runs in.
+#[derive(Debug, Clone)]
+pub struct ScriptContainer {
+    /// Program running the container, `docker` or `podman`.
+    engine: String,
+    /// Name of the container, unique to the script.
+    name: String,
+    config: EjContainerConfig,
+    /// Library path of the board config.
+    library_path: PathBuf,
+    /// Results path of the board config.
+    results_path: PathBuf,
+}
+
+impl ScriptContainer {
+    /// Container the scripts of `board_config` run in, `None` if they run on the builder.
+    pub fn new(engine: &str, board_config: &EjBoardConfig) -> Option<Self> {
+        let config = board_config.container.clone()?;
+        Some(Self {
+            engine: engine.to_string(),
+            name: format!("ejb-{}", Uuid::new_v4()),
+            config,
+            library_path: PathBuf::from(&board_config.library_path),
+            results_path: PathBuf::from(&board_config.results_path),
+        })
+    }
+
+    /// Program and arguments running `script` with `args` in the container.
+    ///
+    /// # Arguments
+    ///
+    /// * `script` - Script to run
+    /// * `args` - Arguments of the script, the configuration file and socket paths among them
+    /// * `env` - Environment of the script, forwarded from the engine process
+    /// * `current_dir` - Directory the script runs in, the builder's if unset
+    /// * `config_path` - Configuration file of the builder
+    /// * `socket_path` - Socket of the builder
+    pub fn command(
+        &self,
+        script: &str,
+        args: Vec<String>,
+        env: &BTreeMap<String, String>,
+        current_dir: Option<&Path>,
+        config_path: &str,
+        socket_path: &str,
+    ) -> std::io::Result<(String, Vec<String>)> {
+        let current_dir = match current_dir {
+            Some(current_dir) => current_dir.to_path_buf(),
+            None => std::env::current_dir()?,
+        };
+        let library_path = std::path::absolute(&self.library_path)?;
+        let results_dir = std::path::absolute(&self.results_path)?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| current_dir.clone());
+        // Directories the engine would create as root otherwise
+        std::fs::create_dir_all(&library_path)?;
+        std::fs::create_dir_all(&results_dir)?;
+
+        // Writable mounts win over read-only ones of the same path
+        let mut mounts = BTreeMap::new();
+        for file in [script, config_path] {
+            mounts.insert(std::path::absolute(file)?, true);
+        }
+        let socket_path = std::path::absolute(socket_path)?;
+        for path in [current_dir.clone(), library_path, results_dir, socket_path] {
+            mounts.insert(path, false);
+        }
+
+        let mut command = vec![
+            String::from("run"),
+            String::from("--rm"),
+            String::from("--init"),
+            String::from("--network=host"),
+            format!("--name={}", self.name),
+            format!("--workdir={}", current_dir.display()),
+        ];
+        command.extend(self.user());
+        for (path, read_only) in mounts {
+            let path = path.display();
+            let options = if read_only { ":ro" } else { "" };
+            command.push(format!("--volume={path}:{path}{options}"));
+        }
+        for device in &self.config.devices {
+            command.push(format!("--device={device}"));
+        }
+        for name in env.keys() {
+            command.push(format!("--env={name}"));
+        }
+        command.push(self.config.image.clone());
+        command.push(std::path::absolute(script)?.to_string_lossy().into_owned());
+        command.extend(args);
+        Ok((self.engine.clone(), command))
+    }
+
+    /// Removes the container if it's still there, as killing the engine
+    /// process doesn't stop it.
+    pub async fn remove(&self) {
+        let status = Command::new(&self.engine)
+            .args(["rm", "--force", &self.name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await;
+        if let Err(err) = status {
+            warn!("Failed to remove container {} - {err}", self.name);
+        }
+    }
+
+    /// Options running the script as the builder user.
+    fn user(&self) -> Vec<String> {
+        // Rootless Podman maps the builder user to root in the container
+        let podman = Path::new(&self.engine)
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().contains("podman"));
+        if podman {
+            vec![String::from("--userns=keep-id")]
+        } else {
+            let uid = nix::unistd::getuid();
+            let gid = nix::unistd::getgid();
+            vec![format!("--user={uid}:{gid}")]
+        }
+    }
+}
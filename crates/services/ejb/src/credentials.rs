@@ -0,0 +1,201 @@
+//! Builder credentials file.
+//!
+//! Passing the builder id and token with `--id` and `--token` leaks them into
+//! the process list and the shell history. `ejb login` instead writes them to
+//! a credentials file only readable by its owner, which `ejb connect` reads
+//! when they're not given on the command line or by the environment.
+//!
+//! With `--keyring`, the token is stored in the OS keyring through
+//! `secret-tool` (libsecret) and the file only holds the builder id.
+
+use std::{
+    fs::{DirBuilder, OpenOptions},
+    io::Write,
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use ej_dispatcher_sdk::{
+    ejbuilder::EjBuilderApi,
+    ejclient::{EjClientLogin, EjClientLoginRequest},
+};
+use ej_requests::ApiClient;
+use serde::{Deserialize, Serialize};
+use tokio::{io::AsyncWriteExt, process::Command};
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+/// Keyring attribute every token of EJB is stored with.
+const KEYRING_SERVICE: &str = "ejb";
+
+/// Content of the credentials file.
+#[derive(Debug, Serialize, Deserialize)]
+struct CredentialsFile {
+    id: Uuid,
+    /// Builder token, `None` when it's stored in the OS keyring.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+}
+
+/// Registers a new builder with the user's credentials and writes its
+/// credentials file.
+///
+/// The user's password is prompted for, so it doesn't leak either.
+pub async fn handle_login(
+    server_url: &str,
+    username: String,
+    path: &Path,
+    keyring: bool,
+) -> Result<()> {
+    let secret = rpassword::prompt_password("Password > ")?;
+    let client = ApiClient::new(server_url);
+    let body = serde_json::to_string(&EjClientLoginRequest {
+        name: username,
+        secret,
+    })?;
+    let login: EjClientLogin = client
+        .post_and_deserialize("v1/login", body)
+        .await
+        .map_err(|err| Error::Login(err.to_string()))?;
+    client.set_token(login.access_token);
+
+    let credentials: EjBuilderApi = client
+        .post_no_body("v1/client/builder")
+        .await
+        .map_err(|err| Error::BuilderCreation(err.to_string()))?;
+    store(path, &credentials, keyring).await?;
+    println!(
+        "Logged in as builder {}, credentials written to {}",
+        credentials.id,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Default path of the credentials file, `ejb/credentials.json` in the user's
+/// config directory.
+pub fn default_path() -> PathBuf {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    config_dir.join("ejb").join("credentials.json")
+}
+
+/// Resolves the builder credentials.
+///
+/// The id and token given on the command line or by the `EJB_ID` and
+/// `EJB_TOKEN` environment variables take precedence, the credentials file
+/// is only read when neither is given.
+pub async fn resolve(
+    id: Option<String>,
+    token: Option<String>,
+    path: &Path,
+) -> Result<EjBuilderApi> {
+    let id = id.or_else(|| std::env::var("EJB_ID").ok());
+    let token = token.or_else(|| std::env::var("EJB_TOKEN").ok());
+    match (id, token) {
+        (Some(id), Some(token)) => Ok(EjBuilderApi {
+            id: id.parse()?,
+            token,
+        }),
+        (Some(_), None) => Err(Error::BuilderTokenMissing),
+        (None, Some(_)) => Err(Error::BuilderIDMissing),
+        (None, None) if !path.exists() => Err(Error::BuilderIDMissing),
+        (None, None) => load(path).await,
+    }
+}
+
+/// Reads the credentials file, refusing it if other users can access it.
+async fn load(path: &Path) -> Result<EjBuilderApi> {
+    let mode = std::fs::metadata(path)?.permissions().mode();
+    if mode & 0o077 != 0 {
+        return Err(Error::CredentialsPermissions(path.to_path_buf()));
+    }
+    let file: CredentialsFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let token = match file.token {
+        Some(token) => token,
+        None => keyring_lookup(&file.id).await?,
+    };
+    Ok(EjBuilderApi { id: file.id, token })
+}
+
+/// Writes the credentials file, only readable by its owner.
+///
+/// The token is stored in the OS keyring instead of the file if `keyring` is set.
+async fn store(path: &Path, credentials: &EjBuilderApi, keyring: bool) -> Result<()> {
+    let token = if keyring {
+        keyring_store(credentials).await?;
+        None
+    } else {
+        Some(credentials.token.clone())
+    };
+    let file = CredentialsFile {
+        id: credentials.id,
+        token,
+    };
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    // Written next to the file and renamed over it, so it's never seen partially written
+    let tmp_path = path.with_extension("tmp");
+    let mut tmp = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp_path)?;
+    tmp.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    tmp.write_all(serde_json::to_string_pretty(&file)?.as_bytes())?;
+    tmp.sync_all()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Stores the token of the builder in the OS keyring.
+async fn keyring_store(credentials: &EjBuilderApi) -> Result<()> {
+    let id = credentials.id.to_string();
+    let mut child = Command::new("secret-tool")
+        .args(["store", "--label", &format!("EJ builder {id}")])
+        .args(["service", KEYRING_SERVICE, "builder", &id])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| Error::Keyring(format!("can't run secret-tool: {err}")))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(credentials.token.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(Error::Keyring(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the token of the builder `id` from the OS keyring.
+async fn keyring_lookup(id: &Uuid) -> Result<String> {
+    let output = Command::new("secret-tool")
+        .args([
+            "lookup",
+            "service",
+            KEYRING_SERVICE,
+            "builder",
+            &id.to_string(),
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|err| Error::Keyring(format!("can't run secret-tool: {err}")))?;
+    if !output.status.success() {
+        return Err(Error::Keyring(format!("no token of builder {id} found")));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end_matches(['\n', '\r'])
+        .to_string())
+}
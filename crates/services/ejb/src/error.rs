@@ -18,14 +18,36 @@ pub enum Error {
     #[error("Build Error")]
     BuildError,
 
-    #[error("Builder ID is missing. Set EJB_ID environment variable or use --id cli argument")]
+    #[error(
+        "Builder ID is missing. Run `ejb login`, set EJB_ID environment variable or use --id cli argument"
+    )]
     BuilderIDMissing,
 
+    #[error("Server URL is missing. Use --server cli argument")]
+    ServerMissing,
+
     #[error(
-        "Builder Token is missing. Set EJB_TOKEN environment variable or use --token cli argument"
+        "Builder Token is missing. Run `ejb login`, set EJB_TOKEN environment variable or use --token cli argument"
     )]
     BuilderTokenMissing,
 
+    #[error("Failed to create builder - {0}")]
+    BuilderCreation(String),
+
+    #[error(
+        "Credentials file {} can be accessed by other users. Restrict it with `chmod 600`", .0.display()
+    )]
+    CredentialsPermissions(std::path::PathBuf),
+
+    #[error("Failed to access the OS keyring - {0}")]
+    Keyring(String),
+
+    #[error("Failed to login - {0}")]
+    Login(String),
+
+    #[error("Failed to upload config - {0}")]
+    ConfigUpload(String),
+
     #[error(transparent)]
     ThreadJoin(#[from] tokio::task::JoinError),
 
@@ -40,4 +62,23 @@ pub enum Error {
 
     #[error(transparent)]
     TokioTungstenite(#[from] tokio_tungstenite::tungstenite::Error),
+
+    #[error(transparent)]
+    DispatcherSdk(#[from] ej_dispatcher_sdk::error::Error),
+
+    #[error(transparent)]
+    BuilderSdk(#[from] ej_builder_sdk::error::Error),
+
+    #[error("The connection to EJD is gone, artifact upload aborted")]
+    ArtifactChannelClosed,
+
+    #[error(
+        "EJB speaks WebSocket protocol v{builder} but EJD speaks v{dispatcher}. Deploy matching EJB and EJD releases"
+    )]
+    WsProtocolVersionMismatch { builder: u32, dispatcher: u32 },
+
+    #[error(
+        "EJD didn't report its WebSocket protocol version, it's likely older than EJB. Deploy matching EJB and EJD releases"
+    )]
+    WsProtocolVersionUnknown,
 }
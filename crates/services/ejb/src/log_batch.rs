@@ -0,0 +1,206 @@
+//! Batching of the logs streamed to EJD.
+//!
+//! Log lines of the running job are buffered rather than sent to EJD one
+//! message per line. They're sent as a single `LogChunk` message per board
+//! config once enough lines are pending, or once the oldest pending line has
+//! waited for the batch interval, whichever comes first. Lines of a board
+//! config are always sent in the order they were produced. Jobs flush the
+//! batch before reporting their results, so their streamed logs are complete
+//! by then.
+
+use std::time::Duration;
+
+use ej_dispatcher_sdk::ejws_message::EjWsClientMessage;
+use tokio::{sync::oneshot, time::Instant};
+
+/// Default number of pending lines sending the batch right away.
+pub const DEFAULT_LOG_BATCH_LINES: usize = 100;
+
+/// Default time the oldest pending line waits before the batch is sent.
+pub const DEFAULT_LOG_BATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Message of a running job to the log batch.
+#[derive(Debug)]
+pub enum LogEvent {
+    /// New log lines.
+    Chunk(EjWsClientMessage),
+    /// Sends the pending logs right away, acknowledged once they're sent.
+    Flush(oneshot::Sender<()>),
+}
+
+/// When the pending logs are sent to EJD.
+#[derive(Debug, Clone, Copy)]
+pub struct LogBatchPolicy {
+    /// Number of pending lines sending the batch right away.
+    pub max_lines: usize,
+    /// Time the oldest pending line waits before the batch is sent.
+    pub interval: Duration,
+}
+
+impl Default for LogBatchPolicy {
+    fn default() -> Self {
+        Self {
+            max_lines: DEFAULT_LOG_BATCH_LINES,
+            interval: DEFAULT_LOG_BATCH_INTERVAL,
+        }
+    }
+}
+
+/// Log messages waiting to be sent to EJD.
+#[derive(Debug)]
+pub struct LogBatch {
+    policy: LogBatchPolicy,
+    chunks: Vec<EjWsClientMessage>,
+    lines: usize,
+    deadline: Option<Instant>,
+}
+
+impl LogBatch {
+    pub fn new(policy: LogBatchPolicy) -> Self {
+        Self {
+            policy,
+            chunks: Vec::new(),
+            lines: 0,
+            deadline: None,
+        }
+    }
+
+    /// Adds a log message to the batch, merging its lines into the pending
+    /// message of the same job and board config if any.
+    pub fn push(&mut self, message: EjWsClientMessage) {
+        let EjWsClientMessage::LogChunk {
+            job_id,
+            board_config_id,
+            lines,
+        } = message
+        else {
+            self.chunks.push(message);
+            return;
+        };
+
+        self.deadline
+            .get_or_insert_with(|| Instant::now() + self.policy.interval);
+        self.lines += lines.len();
+        let pending = self.chunks.iter_mut().find_map(|chunk| match chunk {
+            EjWsClientMessage::LogChunk {
+                job_id: pending_job_id,
+                board_config_id: pending_board_config_id,
+                lines,
+            } if *pending_job_id == job_id && *pending_board_config_id == board_config_id => {
+                Some(lines)
+            }
+            _ => None,
+        });
+        match pending {
+            Some(pending) => pending.extend(lines),
+            None => self.chunks.push(EjWsClientMessage::LogChunk {
+                job_id,
+                board_config_id,
+                lines,
+            }),
+        }
+    }
+
+    /// Whether enough lines are pending for the batch to be sent right away.
+    pub fn is_full(&self) -> bool {
+        self.lines >= self.policy.max_lines
+    }
+
+    /// When the batch must be sent, `None` if nothing is pending.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Takes the pending messages, emptying the batch.
+    pub fn take(&mut self) -> Vec<EjWsClientMessage> {
+        self.lines = 0;
+        self.deadline = None;
+        std::mem::take(&mut self.chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn policy(max_lines: usize) -> LogBatchPolicy {
+        LogBatchPolicy {
+            max_lines,
+            interval: Duration::from_secs(60),
+        }
+    }
+
+    fn chunk(job_id: Uuid, board_config_id: Uuid, lines: &[&str]) -> EjWsClientMessage {
+        EjWsClientMessage::LogChunk {
+            job_id,
+            board_config_id,
+            lines: lines.iter().map(|line| line.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_large_chunk_fills_the_batch() {
+        let mut batch = LogBatch::new(policy(3));
+        batch.push(chunk(Uuid::new_v4(), Uuid::new_v4(), &["a", "b", "c", "d"]));
+        assert!(batch.is_full());
+    }
+
+    #[test]
+    fn test_lines_add_up_until_the_batch_is_full() {
+        let job_id = Uuid::new_v4();
+        let board_config_id = Uuid::new_v4();
+        let other_board_config_id = Uuid::new_v4();
+        let mut batch = LogBatch::new(policy(4));
+
+        batch.push(chunk(job_id, board_config_id, &["a"]));
+        batch.push(chunk(job_id, other_board_config_id, &["b", "c"]));
+        assert!(!batch.is_full());
+        batch.push(chunk(job_id, board_config_id, &["d"]));
+        assert!(batch.is_full());
+
+        // Lines of a board config are merged in the order they were pushed
+        assert_eq!(
+            batch.take(),
+            vec![
+                chunk(job_id, board_config_id, &["a", "d"]),
+                chunk(job_id, other_board_config_id, &["b", "c"]),
+            ]
+        );
+        assert!(!batch.is_full());
+    }
+
+    #[test]
+    fn test_deadline_is_set_by_the_oldest_pending_line() {
+        let interval = Duration::from_millis(500);
+        let mut batch = LogBatch::new(LogBatchPolicy {
+            max_lines: 100,
+            interval,
+        });
+        assert_eq!(batch.deadline(), None);
+
+        let before = Instant::now();
+        batch.push(chunk(Uuid::new_v4(), Uuid::new_v4(), &["a"]));
+        let deadline = batch.deadline().expect("Pending lines set a deadline");
+        assert!(deadline >= before + interval);
+        assert!(deadline <= Instant::now() + interval);
+
+        // Later lines don't push the deadline back
+        batch.push(chunk(Uuid::new_v4(), Uuid::new_v4(), &["b"]));
+        assert_eq!(batch.deadline(), Some(deadline));
+
+        batch.take();
+        assert_eq!(batch.deadline(), None);
+    }
+
+    #[test]
+    fn test_take_from_empty_batch() {
+        let mut batch = LogBatch::new(LogBatchPolicy::default());
+        assert!(batch.take().is_empty());
+        assert!(!batch.is_full());
+        assert_eq!(batch.deadline(), None);
+    }
+}
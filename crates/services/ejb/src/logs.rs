@@ -6,7 +6,7 @@
 //! - Writing logs to various output destinations
 //! - Managing log file creation and cleanup
 
-use std::{fs::File, io::Write, path::PathBuf};
+use std::{fs::File, io::Write};
 
 use crate::{prelude::*, run_output::EjRunOutput};
 use strip_ansi_escapes::strip;
@@ -14,7 +14,8 @@ use tracing::{error, info};
 
 /// Dumps execution logs to a temporary file.
 ///
-/// Creates a temporary file and writes all collected logs to it.
+/// Creates a file in the system temporary directory and writes all collected
+/// logs to it, rather than in the working directory EJB was started from.
 /// Useful for debugging and log persistence.
 pub fn dump_logs_to_temporary_file(output: &EjRunOutput) -> Result<()> {
     match create_temp_and_dump(output) {
@@ -39,7 +40,7 @@ pub fn create_temp_and_dump(output: &EjRunOutput) -> Result<std::path::PathBuf>
         .unwrap_or(0);
 
     let filename = format!("ej_logs_{}.txt", timestamp);
-    let path = PathBuf::from(&filename);
+    let path = std::env::temp_dir().join(filename);
     let mut file = File::create(&path)?;
     dump_logs_internal(output, &mut file, true)?;
     Ok(path)
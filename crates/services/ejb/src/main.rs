@@ -6,6 +6,9 @@
 //! - **Parse**: Parse and validate build configurations
 //! - **Checkout**: Check out source code from remote repositories  
 //! - **Validate**: Run build and validation processes
+//! - **Run local**: Check out, build and run a commit without a dispatcher
+//! - **Clean**: Remove the workspaces of past jobs
+//! - **Login**: Register the builder and store its credentials
 //! - **Connect**: Connect to the EJD dispatcher service for job execution
 //!
 //! ## Communication Architecture
@@ -18,32 +21,53 @@
 //! The builder authenticates with EJD using JWT tokens and maintains a persistent
 //! WebSocket connection to receive job assignments and report results.
 
+mod artifacts;
+mod board_lock;
 mod build;
 mod builder;
+mod cache;
 mod checkout;
 mod cli;
 mod commands;
 mod common;
 mod connection;
+mod container;
+mod credentials;
 mod error;
+mod log_batch;
 mod logs;
 mod prelude;
+mod probe;
 mod run;
 mod run_output;
-use std::path::PathBuf;
+mod workspace;
+use std::{path::PathBuf, time::Duration};
 
 use clap::Parser;
 use cli::{Cli, Commands};
 use ej_builder_sdk::BuilderEvent;
+use ej_dispatcher_sdk::{
+    ejjob::{
+        EjDeployableJob, EjJobType,
+        checkout::EjCheckoutOptions,
+        timeouts::{EjJobTimeouts, EjPhaseTimeouts},
+    },
+    ejws_message::EjWsCompression,
+};
 use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use uuid::Uuid;
 
 use crate::prelude::*;
 use crate::{
     builder::Builder,
+    cache::BuildCache,
     checkout::handle_checkout,
-    commands::{handle_parse, handle_run_and_build},
+    commands::{handle_parse, handle_run_and_build, handle_run_local},
     connection::handle_connect,
+    credentials::handle_login,
+    log_batch::LogBatchPolicy,
+    workspace::{RetentionPolicy, Workspaces},
 };
 
 /// Main entry point for the EJ Builder Service.
@@ -63,8 +87,17 @@ use crate::{
 /// # Validate build
 /// ejb validate --config config.toml
 ///
-/// # Connect to dispatcher
-/// ejb connect --server http://dispatcher:8080 --id builder-123 --token builder_jwt_token
+/// # Check out, build and run a commit without a dispatcher
+/// ejb run-local --config config.toml --commit-hash abc123 --remote-url https://github.com/user/repo.git
+///
+/// # Remove the workspaces of past jobs
+/// ejb clean --config config.toml --keep-workspaces 2
+///
+/// # Register the builder and store its credentials
+/// ejb login --config config.toml --server http://dispatcher:8080 --username admin
+///
+/// # Connect to dispatcher, failing over to a second one when it can't be reached
+/// ejb connect --server http://dispatcher:8080,http://dispatcher-2:8080 --id builder-123 --token builder_jwt_token
 /// ```
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -77,10 +110,36 @@ async fn main() -> Result<()> {
         .init();
 
     let cli = Cli::parse();
+    let credentials_path = cli.credentials.unwrap_or_else(credentials::default_path);
     let default_socket_path = PathBuf::from("/tmp/ejb.sock");
-    let builder =
-        Builder::create(cli.config, cli.socket_path.unwrap_or(default_socket_path)).await?;
+    let mut builder = Builder::create(
+        cli.config,
+        cli.socket_path.unwrap_or(default_socket_path),
+        Duration::from_secs(cli.exit_grace_period),
+    )
+    .await?;
+    builder = builder
+        .with_mirror_dir(cli.mirror_dir)
+        .with_lfs(cli.lfs)
+        .with_container_engine(cli.container_engine)
+        .with_probe_timeout(Duration::from_secs(cli.probe_timeout))
+        .with_lock_dir(cli.lock_dir)
+        .with_workspaces(Workspaces::new(
+            cli.workspace_dir,
+            RetentionPolicy {
+                keep_last: cli.keep_workspaces,
+                keep_failures: cli.keep_failed_workspaces,
+            },
+        ));
+    if !cli.no_cache {
+        builder = builder.with_build_cache(BuildCache::new(
+            cli.cache_dir,
+            cli.cache_max_size * 1024 * 1024,
+        ));
+    }
     let shutdown_tx = builder.tx.clone();
+    let exit_grace_period = builder.exit_grace_period;
+    let mut connected_scripts = builder.connected_scripts.clone();
 
     tokio::select! {
         result = async {
@@ -90,9 +149,83 @@ async fn main() -> Result<()> {
                     commit_hash,
                     remote_url,
                     remote_token,
-                } => handle_checkout(&builder, commit_hash, remote_url, remote_token).await,
+                    depth,
+                    sparse_paths,
+                } => {
+                    let options = EjCheckoutOptions {
+                        depth,
+                        sparse_paths,
+                    };
+                    handle_checkout(&builder, commit_hash, remote_url, remote_token, options).await
+                }
                 Commands::Validate => handle_run_and_build(&builder).await,
-                Commands::Connect { server } => handle_connect(builder, &server, cli.id, cli.token).await,
+                Commands::RunLocal {
+                    commit_hash,
+                    remote_url,
+                    remote_token,
+                    board_configs,
+                    tags,
+                    build_timeout,
+                    run_timeout,
+                    depth,
+                    sparse_paths,
+                    build_only,
+                } => {
+                    let job = EjDeployableJob {
+                        id: Uuid::new_v4(),
+                        job_type: if build_only {
+                            EjJobType::Build
+                        } else {
+                            EjJobType::BuildAndRun
+                        },
+                        commit_hash,
+                        remote_url,
+                        remote_token,
+                        required_tags: tags,
+                        board_configs,
+                        timeouts: EjJobTimeouts {
+                            phases: EjPhaseTimeouts {
+                                build: build_timeout.map(Duration::from_secs),
+                                run: run_timeout.map(Duration::from_secs),
+                            },
+                            ..Default::default()
+                        },
+                        checkout: EjCheckoutOptions {
+                            depth,
+                            sparse_paths,
+                        },
+                    };
+                    handle_run_local(&builder, job).await
+                }
+                Commands::Clean { all } => {
+                    let removed = builder.workspaces.clean(all, None)?;
+                    println!("Removed {removed} workspaces");
+                    Ok(())
+                }
+                Commands::Login {
+                    server,
+                    username,
+                    keyring,
+                } => handle_login(&server, username, &credentials_path, keyring).await,
+                Commands::Connect {
+                    servers,
+                    no_compression,
+                    log_batch_lines,
+                    log_batch_interval,
+                } => {
+                    let compression = if no_compression {
+                        EjWsCompression::None
+                    } else {
+                        EjWsCompression::Gzip
+                    };
+                    let log_batch = LogBatchPolicy {
+                        max_lines: log_batch_lines,
+                        interval: Duration::from_millis(log_batch_interval),
+                    };
+                    let credentials =
+                        credentials::resolve(cli.id, cli.token, &credentials_path).await?;
+                    handle_connect(builder, &servers, credentials, compression, log_batch).await
+                }
             }
         } => {
             info!("Command completed: {:?}", result);
@@ -107,7 +240,15 @@ async fn main() -> Result<()> {
         warn!("Failed to send exit signal: {}", e);
     }
     info!("Waiting for cleanup to complete...");
-    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    if tokio::time::timeout(
+        exit_grace_period,
+        connected_scripts.wait_for(|count| *count == 0),
+    )
+    .await
+    .is_err()
+    {
+        warn!("Child processes didn't exit within {exit_grace_period:?}");
+    }
     info!("Shutdown complete");
     Ok(())
 }
@@ -0,0 +1,90 @@
+//! Board availability probes.
+//!
+//! Board configs may declare a `probe` command checking their board can be
+//! reached, such as pinging it or checking its serial device exists. Before
+//! a job, the probes run concurrently on the builder, with the environment
+//! of the board config scripts. Board configs whose probe fails or doesn't
+//! finish in time are skipped by the job, like the board configs it doesn't
+//! target, rather than failing their build.
+
+use std::{process::Stdio, sync::Arc, time::Duration};
+
+use ej_config::{ej_board::EjBoard, ej_board_config::EjBoardConfig, ej_config::EjConfig};
+use futures_util::future::join_all;
+use tokio::process::Command;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Default time a probe is given to succeed.
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Restricts `config` to the board configs whose board is available.
+///
+/// Returns the config the job runs against and the board configs whose probe failed.
+pub async fn available_boards(
+    config: Arc<EjConfig>,
+    timeout: Duration,
+) -> (Arc<EjConfig>, Vec<Uuid>) {
+    let probes = config.boards.iter().flat_map(|board| {
+        board
+            .configs
+            .iter()
+            .filter(|board_config| board_config.probe.is_some())
+            .map(move |board_config| async move {
+                (!probe(board, board_config, timeout).await).then_some(board_config.id)
+            })
+    });
+    let unavailable: Vec<Uuid> = join_all(probes).await.into_iter().flatten().collect();
+    if unavailable.is_empty() {
+        return (config, unavailable);
+    }
+    let config = Arc::new(config.without_board_configs(&unavailable));
+    (config, unavailable)
+}
+
+/// Runs the probe of a board config, returning whether its board is available.
+async fn probe(board: &EjBoard, board_config: &EjBoardConfig, timeout: Duration) -> bool {
+    let Some(command) = &board_config.probe else {
+        return true;
+    };
+    let env = match board.resolve_script_env(board_config) {
+        Ok(env) => env,
+        Err(err) => {
+            warn!(
+                "Skipping {} - {}, its environment can't be built - {err}",
+                board.name, board_config.name
+            );
+            return false;
+        }
+    };
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(env)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let reason = match tokio::time::timeout(timeout, output).await {
+        Ok(Ok(output)) if output.status.success() => {
+            info!("{} - {} is available", board.name, board_config.name);
+            return true;
+        }
+        Ok(Ok(output)) => {
+            let mut reason = output.status.to_string();
+            for stream in [&output.stdout, &output.stderr] {
+                let stream = String::from_utf8_lossy(stream);
+                if !stream.trim().is_empty() {
+                    reason.push_str(&format!("\n{}", stream.trim_end()));
+                }
+            }
+            reason
+        }
+        Ok(Err(err)) => format!("failed to start - {err}"),
+        Err(_) => format!("timed out after {timeout:?}"),
+    };
+    warn!(
+        "Skipping {} - {}, its board is unavailable. Probe `{command}` {reason}",
+        board.name, board_config.name
+    );
+    false
+}
@@ -4,31 +4,36 @@
 //! for each board configuration. The run process:
 //!
 //! 1. Spawns parallel execution threads for each board
-//! 2. Within each board, executes configurations sequentially
+//! 2. Within each board, executes configurations sequentially, running
+//!    their `pre_run` and `post_run` hooks around their run script
 //! 3. Collects runtime output, logs, and results
 //! 4. Handles result file collection from specified paths
 //! 5. Reports run success/failure status
 //!
 //! Boards run in parallel to maximize throughput, but configurations
-//! within each board run sequentially. Run processes can be cancelled
-//! if a stop signal is received.
+//! within each board run sequentially, while the job holds the board's lock
+//! so no other job uses the board at the same time. Run processes can be cancelled
+//! if a stop signal is received, either for the whole job or for a single
+//! board config, in which case the other board configs keep running.
 
 use ej_builder_sdk::Action;
 use ej_config::ej_board::EjBoard;
 use ej_config::ej_config::EjConfig;
+use ej_dispatcher_sdk::ejjob::timeouts::EjJobTimeouts;
 use ej_io::runner::RunEvent;
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::Duration;
 use tokio::sync::mpsc::channel;
 use tokio::task;
 use tracing::{error, info};
 use uuid::Uuid;
 
 use crate::builder::Builder;
-use crate::common::{SpawnRunnerArgs, spawn_runner};
+use crate::common::{JobStop, SpawnRunnerArgs, run_hook, spawn_runner};
+use crate::container::ScriptContainer;
 use crate::prelude::*;
-use crate::run_output::EjRunOutput;
+use crate::run_output::{EjLogStream, EjRunOutput};
 
 /// Executes run scripts for all board configurations.
 ///
@@ -40,21 +45,28 @@ use crate::run_output::EjRunOutput;
 /// * `builder` - The builder instance containing configuration and paths
 /// * `config` - The EJ configuration with board definitions
 /// * `output` - Output collector for logs and results
-/// * `stop` - Atomic boolean for cancellation signal
+/// * `stop` - Cancellation flags of the job
+/// * `timeouts` - Run timeouts of the board configs, overriding the ones of their config
+/// * `workspace` - Workspace of the job the run scripts run in, if any
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` if all runs succeed, or the first error encountered.
+/// Returns `Ok(())` if all runs succeed, or the first error encountered. A run
+/// running past its timeout is killed and doesn't produce results.
 pub async fn run(
     builder: &Builder,
     config: &EjConfig,
     output: &mut EjRunOutput<'_>,
-    stop: Arc<AtomicBool>,
+    stop: &JobStop,
+    timeouts: &EjJobTimeouts,
+    workspace: Option<&Path>,
 ) -> Result<()> {
     let mut join_handlers = Vec::new();
     for board in config.boards.iter() {
         let board = board.clone();
         let stop = stop.clone();
+        let timeouts = timeouts.clone();
+        let log_stream = output.log_stream.clone();
 
         let args = SpawnRunnerArgs {
             script_name: String::new(),
@@ -63,9 +75,27 @@ pub async fn run(
             config_name: String::new(),
             config_path: builder.config_path.clone(),
             socket_path: builder.socket_path.clone(),
+            env: BTreeMap::new(),
+            script_messages: builder.script_messages.clone(),
+            timeout: None,
+            current_dir: workspace.map(Path::to_path_buf),
+            container: None,
         };
+        let container_engine = builder.container_engine.clone();
+        let board_locks = builder.board_locks.clone();
         join_handlers.push(task::spawn(async move {
-            run_all_configs(args, &board, stop).await
+            let _lock = match board_locks.lock(&board, &stop).await {
+                Ok(Some(lock)) => lock,
+                Ok(None) => {
+                    info!("{} - Run cancelled while waiting for the board", board.name);
+                    return HashMap::new();
+                }
+                Err(err) => {
+                    error!("{} - Failed to lock the board - {err}", board.name);
+                    return HashMap::new();
+                }
+            };
+            run_all_configs(args, &board, &container_engine, log_stream, stop, &timeouts).await
         }));
     }
 
@@ -114,17 +144,50 @@ pub async fn run(
 async fn run_all_configs(
     mut args: SpawnRunnerArgs,
     board: &EjBoard,
-    stop: Arc<AtomicBool>,
+    container_engine: &str,
+    log_stream: Option<EjLogStream>,
+    stop: JobStop,
+    timeouts: &EjJobTimeouts,
 ) -> HashMap<Uuid, (Vec<String>, Option<String>)> {
     let mut outputs = HashMap::new();
     for board_config in board.configs.iter() {
+        if stop.is_board_cancelled(&board_config.id) {
+            info!("{} - Run cancelled", board_config.name);
+            continue;
+        }
         let (tx, mut rx) = channel(10);
 
         args.script_name = board_config.run_script.clone();
         args.config_name = board_config.name.clone();
-        let handle = spawn_runner(args.clone(), tx, Arc::clone(&stop));
-
+        args.timeout = timeouts
+            .for_board(&board_config.name)
+            .run
+            .or(board_config.run_timeout.map(Duration::from_secs));
+        args.container = ScriptContainer::new(container_engine, board_config);
         outputs.insert(board_config.id, (Vec::new(), None));
+        args.env = match board.resolve_script_env(board_config) {
+            Ok(env) => env,
+            Err(err) => {
+                push_line(&mut outputs, &log_stream, board_config.id, err.to_string());
+                info!("{} - Skipping the run", board_config.name);
+                continue;
+            }
+        };
+
+        if let Some(pre_run) = &board_config.pre_run
+            && !run_hook(
+                args.clone(),
+                "Pre-run",
+                pre_run,
+                stop.board(&board_config.id),
+                |line| push_line(&mut outputs, &log_stream, board_config.id, line),
+            )
+            .await
+        {
+            info!("{} - Skipping the run", board_config.name);
+            continue;
+        }
+        let handle = spawn_runner(args.clone(), tx, stop.board(&board_config.id));
 
         while let Some(event) = rx.recv().await {
             match event {
@@ -132,19 +195,39 @@ async fn run_all_configs(
                     error!("{} - Failed to create process {}", board_config.name, err)
                 }
                 RunEvent::ProcessCreated => info!("{} - Run started", board_config.name),
-                RunEvent::ProcessEnd(success) => {
+                RunEvent::ProcessEnd(success, usage) => {
                     if success {
-                        info!("{} - Run ended successfully", board_config.name);
+                        info!("{} - Run ended successfully ({usage})", board_config.name);
                     } else {
-                        error!("{} - Run failed", board_config.name);
+                        error!("{} - Run failed ({usage})", board_config.name);
                     }
                 }
                 RunEvent::ProcessNewOutputLine(line) => {
-                    outputs.get_mut(&board_config.id).unwrap().0.push(line);
+                    push_line(&mut outputs, &log_stream, board_config.id, line)
                 }
+                RunEvent::Timeout => error!("{} - Run timed out", board_config.name),
             }
         }
-        match handle.await {
+        let exit_status = handle.await;
+        // Taken before the post-run hook, whose own results would replace them
+        let submitted_results = args
+            .script_messages
+            .take_results(&board.name, &board_config.name);
+        if let Some(post_run) = &board_config.post_run {
+            run_hook(
+                args.clone(),
+                "Post-run",
+                post_run,
+                stop.board(&board_config.id),
+                |line| push_line(&mut outputs, &log_stream, board_config.id, line),
+            )
+            .await;
+        }
+        if stop.is_board_cancelled(&board_config.id) {
+            info!("{} - Run cancelled", board_config.name);
+            continue;
+        }
+        match exit_status {
             Ok(exit_status) => {
                 if let Some(exit_status) = exit_status {
                     if !exit_status.success() {
@@ -162,7 +245,12 @@ async fn run_all_configs(
             ),
         }
 
-        match std::fs::read_to_string(board_config.results_path.clone()) {
+        // Results submitted through the Builder SDK take precedence over the results file
+        let run_result = match submitted_results {
+            Some(results) => Ok(results),
+            None => std::fs::read_to_string(board_config.results_path.clone()),
+        };
+        match run_result {
             Ok(run_result) => {
                 outputs.get_mut(&board_config.id).unwrap().1 = Some(run_result);
             }
@@ -176,3 +264,16 @@ async fn run_all_configs(
     }
     outputs
 }
+
+/// Adds a line of a board config's output to its logs and streams it.
+fn push_line(
+    outputs: &mut HashMap<Uuid, (Vec<String>, Option<String>)>,
+    log_stream: &Option<EjLogStream>,
+    board_config_id: Uuid,
+    line: String,
+) {
+    if let Some(log_stream) = log_stream {
+        log_stream.send(board_config_id, &line);
+    }
+    outputs.entry(board_config_id).or_default().0.push(line);
+}
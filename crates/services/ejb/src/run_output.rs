@@ -3,11 +3,57 @@
 //! Provides the `EjRunOutput` struct for collecting and organizing
 //! execution results, logs, and artifacts from build and run processes.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use ej_config::ej_config::EjConfig;
+use ej_dispatcher_sdk::ejws_message::EjWsClientMessage;
+use tokio::sync::{mpsc::Sender, oneshot};
+use tracing::warn;
 use uuid::Uuid;
 
+use crate::log_batch::LogEvent;
+
+/// Forwards job output to EJD as it's produced.
+///
+/// Streaming is best effort: lines are dropped if the connection to EJD is
+/// too slow or down. The complete logs are always sent with the job results.
+#[derive(Debug, Clone)]
+pub struct EjLogStream {
+    job_id: Uuid,
+    tx: Sender<LogEvent>,
+}
+
+impl EjLogStream {
+    /// Creates a log stream for the given job.
+    pub fn new(job_id: Uuid, tx: Sender<LogEvent>) -> Self {
+        Self { job_id, tx }
+    }
+
+    /// Forwards a new output line for a board config.
+    pub fn send(&self, board_config_id: Uuid, line: &str) {
+        let _ = self
+            .tx
+            .try_send(LogEvent::Chunk(EjWsClientMessage::LogChunk {
+                job_id: self.job_id,
+                board_config_id,
+                lines: vec![line.to_string()],
+            }));
+    }
+
+    /// Waits for the lines forwarded so far to be sent to EJD, for at most `timeout`.
+    pub async fn flush(&self, timeout: Duration) {
+        let (tx, rx) = oneshot::channel();
+        let flushed = async {
+            if self.tx.send(LogEvent::Flush(tx)).await.is_ok() {
+                let _ = rx.await;
+            }
+        };
+        if tokio::time::timeout(timeout, flushed).await.is_err() {
+            warn!("Timed out sending the logs of job {}", self.job_id);
+        }
+    }
+}
+
 /// Collects and organizes output from job execution processes.
 ///
 /// Stores logs and results indexed by configuration UUID for easy
@@ -20,6 +66,8 @@ pub struct EjRunOutput<'a> {
     pub logs: HashMap<Uuid, Vec<String>>,
     /// Execution results indexed by configuration ID.
     pub results: HashMap<Uuid, String>,
+    /// Where to forward logs as they're produced, if anywhere.
+    pub log_stream: Option<EjLogStream>,
 }
 
 impl<'a> EjRunOutput<'a> {
@@ -29,6 +77,21 @@ impl<'a> EjRunOutput<'a> {
             config,
             logs: HashMap::new(),
             results: HashMap::new(),
+            log_stream: None,
+        }
+    }
+
+    /// Forwards logs to the given stream as they're produced.
+    pub fn with_log_stream(mut self, log_stream: EjLogStream) -> Self {
+        self.log_stream = Some(log_stream);
+        self
+    }
+
+    /// Stores a new output line for a board config and forwards it to the log stream.
+    pub fn push_log(&mut self, board_config_id: Uuid, line: String) {
+        if let Some(log_stream) = &self.log_stream {
+            log_stream.send(board_config_id, &line);
         }
+        self.logs.entry(board_config_id).or_default().push(line);
     }
 }
@@ -0,0 +1,171 @@
+//! Per-job workspaces of the EJ Builder Service.
+//!
+//! Every job dispatched to the builder gets its own directory, named after
+//! the job ID, its build and run scripts run in. The relative library and
+//! results paths of the board configs are resolved from it, so successive
+//! jobs, or builders sharing the machine, don't overwrite each other's
+//! checkouts, builds and results. Absolute paths are left untouched.
+//!
+//! Once a job finishes, the workspaces of the previous jobs are removed
+//! according to the [`RetentionPolicy`]. Workspaces of jobs that didn't
+//! finish, because they were cancelled or the builder stopped, count as
+//! failures.
+
+use std::{
+    cmp::Reverse,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use ej_config::ej_config::EjConfig;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+/// Default directory of the job workspaces.
+pub const DEFAULT_WORKSPACE_DIR: &str = "/tmp/ejb-workspaces";
+
+/// File of a workspace recording how its job ended.
+const STATUS_FILE: &str = ".ejb-status";
+const SUCCESS: &str = "success";
+const FAILURE: &str = "failure";
+
+/// Which workspaces of past jobs are kept.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    /// Number of workspaces of the most recent jobs kept, the current one included.
+    pub keep_last: usize,
+    /// Whether the workspaces of failed jobs are kept on top of the last ones.
+    pub keep_failures: bool,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_last: 5,
+            keep_failures: false,
+        }
+    }
+}
+
+/// Directory holding the workspaces of the jobs.
+#[derive(Debug, Clone)]
+pub struct Workspaces {
+    dir: PathBuf,
+    retention: RetentionPolicy,
+}
+
+impl Workspaces {
+    pub fn new(dir: PathBuf, retention: RetentionPolicy) -> Self {
+        Self { dir, retention }
+    }
+
+    /// Creates the workspace of a job, returning its path.
+    pub async fn create(&self, job_id: Uuid) -> Result<PathBuf> {
+        let workspace = std::path::absolute(self.dir.join(job_id.to_string()))?;
+        tokio::fs::create_dir_all(&workspace).await?;
+        Ok(workspace)
+    }
+
+    /// Records how the job of a workspace ended, then removes the workspaces
+    /// of the previous jobs the retention policy doesn't keep.
+    pub async fn finish(&self, workspace: &Path, success: bool) {
+        let status = if success { SUCCESS } else { FAILURE };
+        if let Err(err) = tokio::fs::write(workspace.join(STATUS_FILE), status).await {
+            warn!(
+                "Failed to record the status of workspace {} - {err}",
+                workspace.display()
+            );
+        }
+
+        let workspaces = self.clone();
+        let current = workspace.to_path_buf();
+        match tokio::task::spawn_blocking(move || workspaces.clean(false, Some(&current))).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => warn!("Failed to remove old workspaces - {err}"),
+            Err(err) => warn!("Failed to remove old workspaces - {err}"),
+        }
+    }
+
+    /// Removes the workspaces the retention policy doesn't keep, or every
+    /// workspace if `all` is set, returning how many were removed.
+    ///
+    /// The workspace of the `current` job is never removed.
+    pub fn clean(&self, all: bool, current: Option<&Path>) -> std::io::Result<usize> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err),
+        };
+        let mut workspaces = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if !path.is_dir() || current.is_some_and(|current| current == path) {
+                continue;
+            }
+            let status = path.join(STATUS_FILE);
+            let failed =
+                std::fs::read_to_string(&status).map_or(true, |status| status.trim() != SUCCESS);
+            let finished = std::fs::metadata(&status)
+                .or_else(|_| std::fs::metadata(&path))
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            workspaces.push((finished, failed, path));
+        }
+
+        // Most recent first
+        workspaces.sort_by_key(|(finished, _, _)| Reverse(*finished));
+        let mut kept = usize::from(current.is_some());
+        let mut removed = 0;
+        for (_, failed, path) in workspaces {
+            if !all && failed && self.retention.keep_failures {
+                continue;
+            }
+            if !all && kept < self.retention.keep_last {
+                kept += 1;
+                continue;
+            }
+            info!("Removing workspace {}", path.display());
+            std::fs::remove_dir_all(&path)?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+}
+
+/// Resolves the relative library and results paths of the board configs
+/// from `workspace`.
+///
+/// Relative script paths are made absolute, as they're relative to the
+/// builder's working directory rather than to the workspace the scripts run in.
+pub fn relocate(config: &EjConfig, workspace: &Path) -> EjConfig {
+    let mut config = config.clone();
+    for board_config in config
+        .boards
+        .iter_mut()
+        .flat_map(|board| &mut board.configs)
+    {
+        for path in [
+            &mut board_config.library_path,
+            &mut board_config.results_path,
+        ] {
+            *path = workspace.join(&*path).to_string_lossy().into_owned();
+        }
+        let hooks = [
+            &mut board_config.pre_build,
+            &mut board_config.post_build,
+            &mut board_config.pre_run,
+            &mut board_config.post_run,
+        ];
+        let scripts = [&mut board_config.build_script, &mut board_config.run_script]
+            .into_iter()
+            .chain(hooks.into_iter().flatten());
+        for script in scripts {
+            if let Ok(absolute) = std::path::absolute(&*script) {
+                *script = absolute.to_string_lossy().into_owned();
+            }
+        }
+    }
+    config
+}
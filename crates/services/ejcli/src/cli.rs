@@ -3,7 +3,9 @@
 //! Defines the CLI structure, commands, and arguments for the EJ testing
 //! and setup tool.
 
-use clap::{Args, Parser, Subcommand};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use ej_dispatcher_sdk::ejjob::{EjJobStatus, EjJobType};
 use std::{path::PathBuf, time::Duration};
 use uuid::Uuid;
 
@@ -12,10 +14,97 @@ use uuid::Uuid;
 #[command(name = "ejc")]
 #[command(about = "EJ CLI - Testing and setup tool for the EJ system")]
 pub struct Cli {
+    /// Format of the jobs, results and builders printed by the commands
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+/// Format of the data printed by the commands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// One JSON document per line, for scripts
+    Json,
+}
+
+/// Report format of run results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// JUnit XML, with a test case per board config
+    Junit,
+}
+
+/// Job status accepted by `--status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum JobStatusArg {
+    NotStarted,
+    Running,
+    Success,
+    Failed,
+    Cancelled,
+}
+
+/// Job type accepted by `--type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum JobTypeArg {
+    /// Build jobs
+    Build,
+    /// Build and run jobs
+    Run,
+}
+
+impl From<JobStatusArg> for EjJobStatus {
+    fn from(status: JobStatusArg) -> Self {
+        match status {
+            JobStatusArg::NotStarted => EjJobStatus::NotStarted,
+            JobStatusArg::Running => EjJobStatus::Running,
+            JobStatusArg::Success => EjJobStatus::Success,
+            JobStatusArg::Failed => EjJobStatus::Failed,
+            JobStatusArg::Cancelled => EjJobStatus::Cancelled,
+        }
+    }
+}
+
+impl From<JobTypeArg> for EjJobType {
+    fn from(job_type: JobTypeArg) -> Self {
+        match job_type {
+            JobTypeArg::Build => EjJobType::Build,
+            JobTypeArg::Run => EjJobType::BuildAndRun,
+        }
+    }
+}
+
+/// Parses a date (`YYYY-MM-DD` or RFC 3339) or an age relative to now
+/// (a number followed by `s`, `m`, `h`, `d` or `w`).
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(Default::default()).and_utc());
+    }
+
+    let invalid = || format!("invalid date or age `{value}`, expected YYYY-MM-DD or 7d");
+    let split = value.len().saturating_sub(1);
+    let (amount, unit) = (value.get(..split), value.get(split..));
+    let amount: i64 = amount.and_then(|a| a.parse().ok()).ok_or_else(invalid)?;
+    let age = match unit {
+        Some("s") => chrono::Duration::try_seconds(amount),
+        Some("m") => chrono::Duration::try_minutes(amount),
+        Some("h") => chrono::Duration::try_hours(amount),
+        Some("d") => chrono::Duration::try_days(amount),
+        Some("w") => chrono::Duration::try_weeks(amount),
+        _ => None,
+    }
+    .ok_or_else(invalid)?;
+    Utc::now().checked_sub_signed(age).ok_or_else(invalid)
+}
+
 /// Available commands for the EJ CLI testing and setup tool.
 #[derive(Subcommand)]
 pub enum Commands {
@@ -57,17 +146,49 @@ pub enum Commands {
         client: UserArgs,
     },
 
-    /// Fetchs jobs associated to a commit hash
-    FetchJobs {
+    /// List the job history, newest first
+    #[command(alias = "fetch-jobs", alias = "list-jobs")]
+    Jobs {
         /// Server socket
         #[arg(short, long)]
         socket: PathBuf,
 
+        /// Only list jobs for this commit hash
+        #[arg(long)]
+        commit_hash: Option<String>,
+
+        /// Only list jobs for this remote url
         #[arg(long)]
-        commit_hash: String,
+        remote_url: Option<String>,
+
+        /// Only list jobs with this status
+        #[arg(long, value_enum)]
+        status: Option<JobStatusArg>,
+
+        /// Only list jobs of this type
+        #[arg(long = "type", value_enum)]
+        job_type: Option<JobTypeArg>,
+
+        /// Only list jobs created since this date (YYYY-MM-DD or RFC 3339) or
+        /// for this long (such as 30m, 12h or 7d)
+        #[arg(long, value_parser = parse_time)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only list jobs created before this date (YYYY-MM-DD or RFC 3339) or
+        /// this long ago (such as 30m, 12h or 7d)
+        #[arg(long, value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
+
+        /// Page number, starting at 1
+        #[arg(long, default_value_t = 1)]
+        page: u32,
+
+        /// Number of jobs per page
+        #[arg(long, default_value_t = 50)]
+        per_page: u32,
     },
 
-    /// Fetchs jobs associated to a commit hash
+    /// Fetch the logs and results of a run job
     FetchRunResult {
         /// Server socket
         #[arg(short, long)]
@@ -75,6 +196,189 @@ pub enum Commands {
 
         #[arg(long)]
         job_id: Uuid,
+
+        /// Report format, overrides --output
+        #[arg(long, value_enum)]
+        format: Option<ReportFormat>,
+
+        /// Write the result to this file instead of printing it
+        #[arg(short = 'o', long)]
+        output_file: Option<PathBuf>,
+    },
+
+    /// Fetch the build logs of a job
+    FetchBuildResult {
+        /// Server socket
+        #[arg(short, long)]
+        socket: PathBuf,
+
+        #[arg(long)]
+        job_id: Uuid,
+    },
+
+    /// Print the output of a running or queued job until it finishes
+    TailLogs {
+        /// Server socket
+        #[arg(short, long)]
+        socket: PathBuf,
+
+        #[arg(long)]
+        job_id: Uuid,
+    },
+
+    /// Stream the output of a running or queued job, then summarize its result
+    Watch {
+        /// Server socket
+        #[arg(short, long)]
+        socket: PathBuf,
+
+        #[arg(long)]
+        job_id: Uuid,
+    },
+
+    /// Dispatch a new job with the same commit, remote, type and targets as a past job
+    Rerun {
+        /// Server socket
+        #[arg(short, long)]
+        socket: PathBuf,
+
+        /// The job to re-run
+        #[arg(long)]
+        job_id: Uuid,
+
+        /// The maximum job duration in seconds
+        #[arg(long)]
+        seconds: u64,
+
+        /// Optional git remote token, tokens of past jobs aren't kept
+        #[arg(long)]
+        remote_token: Option<String>,
+
+        /// Print the builders' output and the result once the job finishes
+        #[arg(long)]
+        watch: bool,
+    },
+
+    /// Search the logs and results of every job
+    SearchJobs {
+        /// Server socket
+        #[arg(short, long)]
+        socket: PathBuf,
+
+        /// Text to search for, quote phrases to match them as written
+        #[arg(long)]
+        query: String,
+
+        /// Only search jobs for this commit hash
+        #[arg(long)]
+        commit_hash: Option<String>,
+
+        /// Only search this job
+        #[arg(long)]
+        job_id: Option<Uuid>,
+
+        /// Maximum number of matches printed
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+
+    /// Print statistics about the finished jobs
+    Stats {
+        /// Server socket
+        #[arg(short, long)]
+        socket: PathBuf,
+
+        /// Only count jobs for these commits, e.g. from `git rev-list a..b`
+        #[arg(long, value_delimiter = ',')]
+        commits: Vec<String>,
+
+        /// Maximum number of entries printed in each list
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+
+    /// List the registered builders and when they were last online
+    ListBuilders {
+        /// Server socket
+        #[arg(short, long)]
+        socket: PathBuf,
+
+        /// Seconds without hearing from a builder after which it's considered stale
+        #[arg(long, default_value_t = 300)]
+        stale_after: u64,
+
+        /// Only list stale builders
+        #[arg(long)]
+        stale: bool,
+
+        /// Only list the builders that are currently connected
+        #[arg(long)]
+        connected: bool,
+    },
+
+    /// Inspect builder configs
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Rotate the keys the dispatcher signs tokens with
+    JwtKey {
+        #[command(subcommand)]
+        command: JwtKeyCommands,
+    },
+}
+
+/// Builder config commands.
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Compare a local config with the latest one a builder uploaded
+    Diff {
+        /// Server socket
+        #[arg(short, long)]
+        socket: PathBuf,
+
+        /// Builder whose uploaded config is compared
+        #[arg(long)]
+        builder_id: Uuid,
+
+        /// Local config file
+        #[arg(short, long)]
+        config: PathBuf,
+    },
+}
+
+/// JWT key commands.
+#[derive(Subcommand)]
+pub enum JwtKeyCommands {
+    /// Sign new tokens with a new key, the previous keys keep verifying the tokens they signed
+    Rotate {
+        /// Server socket
+        #[arg(short, long)]
+        socket: PathBuf,
+
+        /// ID of the new key
+        #[arg(long)]
+        kid: String,
+
+        /// Shared secret file with HS256, PEM private key otherwise, read by the dispatcher
+        #[arg(long)]
+        key: PathBuf,
+
+        /// PEM public key, read by the dispatcher, unused with HS256
+        #[arg(long)]
+        public_key: Option<PathBuf>,
+    },
+
+    /// Reject the tokens signed with a previous key
+    Retire {
+        /// Server socket
+        #[arg(short, long)]
+        socket: PathBuf,
+
+        /// ID of the key to retire
+        #[arg(long)]
+        kid: String,
     },
 }
 
@@ -85,6 +389,14 @@ pub struct DispatchArgs {
     #[arg(long)]
     pub seconds: u64,
 
+    /// The maximum duration of each board config's build in seconds
+    #[arg(long)]
+    pub build_timeout: Option<u64>,
+
+    /// The maximum duration of each board config's run in seconds
+    #[arg(long)]
+    pub run_timeout: Option<u64>,
+
     /// Git commit hash
     #[arg(long)]
     pub commit_hash: String,
@@ -96,6 +408,26 @@ pub struct DispatchArgs {
     /// Optional git remote token
     #[arg(long)]
     pub remote_token: Option<String>,
+
+    /// Only run the job on the board config with this name, can be repeated
+    #[arg(long = "board-config")]
+    pub board_configs: Vec<String>,
+
+    /// Only run the job on board configs with this tag, can be repeated
+    #[arg(long = "tag")]
+    pub tags: Vec<String>,
+
+    /// Number of commits of history builders fetch, the whole history by default
+    #[arg(long)]
+    pub depth: Option<u32>,
+
+    /// Only check out the paths matching this pattern, can be repeated
+    #[arg(long = "sparse")]
+    pub sparse_paths: Vec<String>,
+
+    /// Print the builders' output as it's produced
+    #[arg(long)]
+    pub follow: bool,
 }
 /// User arguments for creating a new user or builder.
 #[derive(Args)]
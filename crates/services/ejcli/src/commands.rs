@@ -1,10 +1,27 @@
+use chrono::Utc;
+use ej_auth::socket_signature::SocketKey;
+use ej_config::{EjConfig, EjUserConfig};
 use ej_dispatcher_sdk::ejbuilder::EjBuilderApi;
 use ej_dispatcher_sdk::ejclient::{EjClientLogin, EjClientLoginRequest, EjClientPost};
+use ej_dispatcher_sdk::ejjob::{
+    EjJob, EjJobQuery, EjJobTarget, EjJobType, EjJobUpdate, EjLogChunk,
+    checkout::EjCheckoutOptions,
+    search::EjJobSearchQuery,
+    timeouts::{EjDispatchTimeouts, EjJobTimeouts, EjPhaseTimeouts},
+};
 use ej_dispatcher_sdk::ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage};
+use ej_dispatcher_sdk::ejstats::EjStatsQuery;
+use ej_dispatcher_sdk::fetch_build_result::fetch_build_result;
+use ej_dispatcher_sdk::fetch_builder_config::fetch_builder_config;
 use ej_dispatcher_sdk::fetch_run_result::fetch_run_result;
-use ej_dispatcher_sdk::run::dispatch_run;
-use ej_dispatcher_sdk::{build::dispatch_build, ejjob::EjJobType};
+use ej_dispatcher_sdk::fetch_stats::fetch_stats;
+use ej_dispatcher_sdk::jwt_keys::{retire_jwt_key, rotate_jwt_key};
+use ej_dispatcher_sdk::list_builders::{list_builders, list_connected_builders};
+use ej_dispatcher_sdk::search_jobs::search_jobs;
+use ej_dispatcher_sdk::subscribe_logs::subscribe_logs;
 use ej_requests::ApiClient;
+use futures_util::StreamExt;
+use serde::Serialize;
 use std::cmp::Ordering;
 use std::path::Path;
 use std::{collections::HashMap, path::PathBuf, time::Duration};
@@ -14,50 +31,108 @@ use tokio::{
 };
 use uuid::Uuid;
 
-use crate::cli::{DispatchArgs, UserArgs};
-use ej_dispatcher_sdk::{fetch_jobs::fetch_jobs, prelude::*};
+use crate::cli::{DispatchArgs, OutputFormat, ReportFormat, UserArgs};
+use ej_dispatcher_sdk::{
+    dispatch_job::dispatch_job,
+    fetch_jobs::{fetch_job, query_jobs},
+    prelude::*,
+};
 
 pub async fn handle_dispatch(
     socket_path: &Path,
     dispatch: DispatchArgs,
     job_type: EjJobType,
+    output: OutputFormat,
 ) -> Result<()> {
-    println!("Dispatching job");
-
-    if job_type == EjJobType::Build {
-        let build_result = dispatch_build(
-            socket_path,
-            dispatch.commit_hash,
-            dispatch.remote_url,
-            dispatch.remote_token,
-            Duration::from_secs(dispatch.seconds),
-        )
-        .await?;
-        println!("Received Build Result {}", build_result);
-    } else {
-        let run_result = dispatch_run(
-            socket_path,
-            dispatch.commit_hash,
-            dispatch.remote_url,
-            dispatch.remote_token,
-            Duration::from_secs(dispatch.seconds),
-        )
-        .await?;
-        println!("Received Run Result {}", run_result);
+    if output == OutputFormat::Text {
+        println!("Dispatching job");
+    }
+
+    let follow = dispatch.follow;
+    let target = EjJobTarget {
+        board_configs: dispatch.board_configs,
+        required_tags: dispatch.tags,
+    };
+    let timeouts = EjDispatchTimeouts::new(
+        Duration::from_secs(dispatch.seconds),
+        EjJobTimeouts {
+            phases: EjPhaseTimeouts {
+                build: dispatch.build_timeout.map(Duration::from_secs),
+                run: dispatch.run_timeout.map(Duration::from_secs),
+            },
+            ..Default::default()
+        },
+    );
+    let on_update = |update: &EjJobUpdate| {
+        if !follow {
+            return;
+        }
+        if let EjJobUpdate::LogChunk {
+            board_config,
+            lines,
+        } = update
+        {
+            let chunk = EjLogChunk {
+                board_config: board_config.clone(),
+                lines: lines.clone(),
+            };
+            if let Err(err) = print_log_chunk(&chunk, output) {
+                log::error!("Failed to print the job output - {err}");
+            }
+        }
+    };
+
+    let checkout = EjCheckoutOptions {
+        depth: dispatch.depth,
+        sparse_paths: dispatch.sparse_paths,
+    };
+    let job = EjJob::new(
+        job_type.clone(),
+        dispatch.commit_hash,
+        dispatch.remote_url,
+        dispatch.remote_token,
+    )
+    .with_target(target)
+    .with_checkout(checkout);
+
+    let (_, updates) = dispatch_job(socket_path, job, timeouts).await?;
+    let mut updates = std::pin::pin!(updates);
+    while let Some(update) = updates.next().await {
+        on_update(&update);
+        match update {
+            EjJobUpdate::BuildFinished(build_result) if job_type == EjJobType::Build => {
+                match output {
+                    OutputFormat::Text => println!("Received Build Result {}", build_result),
+                    OutputFormat::Json => print_json(&build_result)?,
+                }
+                return Ok(());
+            }
+            EjJobUpdate::RunFinished(run_result) => {
+                match output {
+                    OutputFormat::Text => println!("Received Run Result {}", run_result),
+                    OutputFormat::Json => print_json(&run_result)?,
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+    match job_type {
+        EjJobType::Build => Err(Error::BuildError),
+        EjJobType::BuildAndRun => Err(Error::RunError),
     }
-    Ok(())
 }
 pub async fn handle_create_root_user(socket_path: &Path, args: UserArgs) -> Result<()> {
     println!("Creating user");
     let mut stream = UnixStream::connect(socket_path).await?;
 
     let name = args.username;
-    let secret = args
-        .password
-        .unwrap_or(rpassword::prompt_password("Password > ").expect("Failed to get password"));
+    let secret = args.password.unwrap_or_else(|| {
+        rpassword::prompt_password("Password > ").expect("Failed to get password")
+    });
 
     let message = EjSocketClientMessage::CreateRootUser(EjClientPost { name, secret });
-    let payload = serde_json::to_string(&message)?;
+    let payload = message.encode(SocketKey::from_env().as_ref())?;
     stream.write_all(payload.as_bytes()).await;
     stream.write_all(b"\n").await;
     stream.flush().await;
@@ -65,7 +140,7 @@ pub async fn handle_create_root_user(socket_path: &Path, args: UserArgs) -> Resu
     let mut response = String::new();
     stream.read_to_string(&mut response).await?;
     let response: EjSocketServerMessage = serde_json::from_str(&response)?;
-    println!("{:?}", response);
+    println!("{}", response);
     Ok(())
 }
 
@@ -97,29 +172,333 @@ pub async fn handle_create_builder(server: &str, args: UserArgs) -> Result<()> {
     Ok(())
 }
 
-pub async fn handle_fetch_jobs(socket: &Path, commit_hash: String) -> Result<()> {
-    let mut jobs = fetch_jobs(&socket, commit_hash.clone()).await?;
+/// Prints a page of the job history as a table.
+pub async fn handle_jobs(socket: &Path, query: EjJobQuery, output: OutputFormat) -> Result<()> {
+    let page = query.page.page.unwrap_or(1);
+    let per_page = query.page.per_page;
+    let jobs = query_jobs(socket, query).await?;
+    if output == OutputFormat::Json {
+        return print_json(&jobs);
+    }
+    if jobs.is_empty() {
+        println!("No jobs found");
+        return Ok(());
+    }
+
     println!(
-        "Found {} job(s) associated with {} commit",
-        jobs.len(),
-        commit_hash
+        "{:<36}  {:<13}  {:<11}  {:<10}  {:<16}  {:<8}  REMOTE",
+        "ID", "TYPE", "STATUS", "COMMIT", "DISPATCHED", "DURATION"
     );
+    for job in jobs.iter() {
+        let commit: String = job.commit_hash.chars().take(10).collect();
+        let dispatched = job
+            .dispatched_at
+            .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| String::from("-"));
+        let duration = match (job.dispatched_at, job.finished_at) {
+            (Some(dispatched), Some(finished)) => {
+                format!("{}s", (finished - dispatched).num_seconds())
+            }
+            (Some(_), None) => String::from("running"),
+            (None, _) => String::from("pending"),
+        };
+        println!(
+            "{:<36}  {:<13}  {:<11}  {:<10}  {:<16}  {:<8}  {}",
+            job.id,
+            job.job_type.to_string(),
+            job.status.to_string(),
+            commit,
+            dispatched,
+            duration,
+            job.remote_url
+        );
+    }
+    if per_page.is_some_and(|per_page| jobs.len() >= per_page as usize) {
+        println!("Page {page}, pass --page {} for older jobs", page + 1);
+    }
+    Ok(())
+}
 
-    jobs.sort_by(|a, b| match (&a.finished_at, &b.finished_at) {
-        (Some(a_finished), Some(b_finished)) => a_finished.cmp(b_finished),
-        (Some(_), None) => Ordering::Less,
-        (None, Some(_)) => Ordering::Greater,
-        (None, None) => Ordering::Equal,
-    });
+/// Prints the run result of a job, or writes it to `output_file`.
+pub async fn handle_fetch_run_results(
+    socket: &Path,
+    job_id: Uuid,
+    output: OutputFormat,
+    format: Option<ReportFormat>,
+    output_file: Option<&Path>,
+) -> Result<()> {
+    let run_result = fetch_run_result(&socket, job_id).await?;
+    let report = match (format, output) {
+        (Some(ReportFormat::Junit), _) => run_result.to_junit(&format!("ej job {job_id}")),
+        (None, OutputFormat::Text) => format!("{run_result}\n"),
+        (None, OutputFormat::Json) => format!("{}\n", serde_json::to_string(&run_result)?),
+    };
+    match output_file {
+        Some(path) => {
+            std::fs::write(path, report)?;
+            eprintln!("Wrote the result of job {job_id} to {}", path.display());
+        }
+        None => print!("{report}"),
+    }
+    Ok(())
+}
 
-    for job in jobs {
-        println!("{}", job);
+pub async fn handle_fetch_build_result(
+    socket: &Path,
+    job_id: Uuid,
+    output: OutputFormat,
+) -> Result<()> {
+    let build_result = fetch_build_result(socket, job_id).await?;
+    print_output(&build_result, output)
+}
+
+pub async fn handle_tail_logs(socket: &Path, job_id: Uuid, output: OutputFormat) -> Result<()> {
+    print_logs(socket, job_id, output).await?;
+    if output == OutputFormat::Text {
+        println!("No more logs for job {job_id}");
     }
     Ok(())
 }
 
-pub async fn handle_fetch_run_results(socket: &Path, job_id: Uuid) -> Result<()> {
-    let run_result = fetch_run_result(&socket, job_id).await?;
-    println!("{}", run_result);
+/// Prints the output of a job until it finishes, then a summary of its result.
+///
+/// With JSON output, the log chunks are followed by the run result.
+pub async fn handle_watch(socket: &Path, job_id: Uuid, output: OutputFormat) -> Result<()> {
+    let line_counts = print_logs(socket, job_id, output).await?;
+    let result = fetch_run_result(socket, job_id).await?;
+    if output == OutputFormat::Json {
+        return print_json(&result);
+    }
+    let status = if result.success {
+        "successfully"
+    } else {
+        "with failures"
+    };
+    println!("\n=======================================");
+    println!("Job {job_id} finished {status}");
+    println!("=======================================");
+    for (board_config, _) in result.logs.iter() {
+        let lines = line_counts.get(&board_config.name).copied().unwrap_or(0);
+        let has_result = result
+            .results
+            .iter()
+            .any(|(config, _)| config.id == board_config.id);
+        let result = if has_result { "result" } else { "no result" };
+        println!("[{}] {lines} line(s) streamed, {result}", board_config.name);
+    }
+    Ok(())
+}
+
+/// Prints the output of a job, prefixed with its board config, until it finishes.
+///
+/// Returns the number of lines printed per board config name.
+async fn print_logs(
+    socket: &Path,
+    job_id: Uuid,
+    output: OutputFormat,
+) -> Result<HashMap<String, usize>> {
+    let logs = subscribe_logs(socket, job_id).await?;
+    let mut logs = std::pin::pin!(logs);
+    let mut line_counts: HashMap<String, usize> = HashMap::new();
+    while let Some(chunk) = logs.next().await {
+        *line_counts
+            .entry(chunk.board_config.name.clone())
+            .or_default() += print_log_chunk(&chunk, output)?;
+    }
+    Ok(line_counts)
+}
+
+/// Prints a chunk of a job's output, prefixing each line with its board config.
+///
+/// Returns the number of lines printed, the chunk is printed as a single
+/// JSON document with JSON output.
+fn print_log_chunk(chunk: &EjLogChunk, output: OutputFormat) -> Result<usize> {
+    if output == OutputFormat::Json {
+        print_json(chunk)?;
+        return Ok(chunk.lines.iter().map(|lines| lines.lines().count()).sum());
+    }
+    let mut count = 0;
+    // Builders forward output as it's read, so an entry may hold several lines
+    for line in chunk.lines.iter().flat_map(|lines| lines.lines()) {
+        println!("[{}] {}", chunk.board_config.name, line);
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Prints `value` as a single line of JSON.
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+/// Prints `value` in the requested format.
+fn print_output<T: Serialize + std::fmt::Display>(value: &T, output: OutputFormat) -> Result<()> {
+    match output {
+        OutputFormat::Text => println!("{}", value),
+        OutputFormat::Json => print_json(value)?,
+    }
+    Ok(())
+}
+
+/// Dispatches a new job identical to a past one and prints its ID.
+///
+/// With `watch`, also prints the builders' output and the job's result.
+pub async fn handle_rerun(
+    socket: &Path,
+    job_id: Uuid,
+    max_duration: Duration,
+    remote_token: Option<String>,
+    watch: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let past_job = fetch_job(socket, job_id).await?;
+    let job_type = past_job.job_type.clone();
+    let job = EjJob::new(
+        past_job.job_type,
+        past_job.commit_hash,
+        past_job.remote_url,
+        remote_token,
+    )
+    .with_required_tags(past_job.required_tags)
+    .with_board_configs(past_job.board_configs);
+
+    let (job, updates) = dispatch_job(socket, job, max_duration).await?;
+    match output {
+        OutputFormat::Text => println!("Dispatched job {} re-running job {job_id}", job.id),
+        OutputFormat::Json => print_json(&serde_json::json!({ "job_id": job.id }))?,
+    }
+    if !watch {
+        return Ok(());
+    }
+
+    let mut updates = std::pin::pin!(updates);
+    while let Some(update) = updates.next().await {
+        match update {
+            EjJobUpdate::LogChunk {
+                board_config,
+                lines,
+            } => {
+                print_log_chunk(
+                    &EjLogChunk {
+                        board_config,
+                        lines,
+                    },
+                    output,
+                )?;
+            }
+            EjJobUpdate::BuildFinished(result) => return print_output(&result, output),
+            EjJobUpdate::RunFinished(result) => return print_output(&result, output),
+            update => {
+                if output == OutputFormat::Text {
+                    println!("{update}");
+                }
+            }
+        }
+    }
+    match job_type {
+        EjJobType::Build => Err(Error::BuildError),
+        EjJobType::BuildAndRun => Err(Error::RunError),
+    }
+}
+
+pub async fn handle_search_jobs(
+    socket: &Path,
+    query: EjJobSearchQuery,
+    output: OutputFormat,
+) -> Result<()> {
+    let hits = search_jobs(socket, query).await?;
+    if output == OutputFormat::Json {
+        return print_json(&hits);
+    }
+    println!("Found {} match(es)", hits.len());
+    for hit in hits {
+        println!("{}", hit);
+    }
+    Ok(())
+}
+
+pub async fn handle_stats(socket: &Path, query: EjStatsQuery, output: OutputFormat) -> Result<()> {
+    let stats = fetch_stats(socket, query).await?;
+    match output {
+        OutputFormat::Text => print!("{}", stats),
+        OutputFormat::Json => print_json(&stats)?,
+    }
+    Ok(())
+}
+
+pub async fn handle_list_builders(
+    socket: &Path,
+    stale_after: Duration,
+    stale: bool,
+    connected: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let now = Utc::now();
+    let mut builders = if connected {
+        list_connected_builders(socket).await?
+    } else {
+        list_builders(socket).await?
+    };
+    if stale {
+        builders.retain(|builder| builder.is_stale(now, stale_after));
+    }
+    if output == OutputFormat::Json {
+        return print_json(&builders);
+    }
+    for builder in builders {
+        let is_stale = builder.is_stale(now, stale_after);
+        if is_stale {
+            print!("[stale] ");
+        }
+        println!("{}", builder);
+        for builder_connection in builder.connection_history.iter() {
+            println!("  {}", builder_connection);
+        }
+    }
+    Ok(())
+}
+
+/// Makes the dispatcher sign new tokens with the key at `key`.
+pub async fn handle_rotate_jwt_key(
+    socket: &Path,
+    kid: String,
+    key: PathBuf,
+    public_key: Option<PathBuf>,
+    output: OutputFormat,
+) -> Result<()> {
+    let keys = rotate_jwt_key(socket, kid, key, public_key).await?;
+    if output == OutputFormat::Json {
+        return print_json(&keys);
+    }
+    println!("{}", keys);
+    Ok(())
+}
+
+/// Makes the dispatcher reject the tokens signed with the key `kid`.
+pub async fn handle_retire_jwt_key(socket: &Path, kid: String, output: OutputFormat) -> Result<()> {
+    let keys = retire_jwt_key(socket, kid).await?;
+    if output == OutputFormat::Json {
+        return print_json(&keys);
+    }
+    println!("{}", keys);
+    Ok(())
+}
+
+/// Prints what uploading the config at `config_path` would change in the
+/// latest config uploaded by the builder.
+pub async fn handle_config_diff(socket: &Path, builder_id: Uuid, config_path: &Path) -> Result<()> {
+    let local = EjConfig::from_user_config(EjUserConfig::from_file(config_path)?);
+    let uploaded = match fetch_builder_config(socket, builder_id).await? {
+        Some(uploaded) => uploaded,
+        None => {
+            println!("Builder {builder_id} didn't upload a config yet");
+            EjConfig {
+                global: local.global.clone(),
+                boards: Vec::new(),
+            }
+        }
+    };
+    print!("{}", uploaded.diff(&local));
     Ok(())
 }
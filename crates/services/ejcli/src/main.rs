@@ -13,12 +13,23 @@
 mod cli;
 mod commands;
 
+use std::time::Duration;
+
 use clap::Parser;
-use cli::{Cli, Commands};
+use cli::{Cli, Commands, ConfigCommands, JwtKeyCommands};
 use commands::{handle_create_builder, handle_create_root_user, handle_dispatch};
-use ej_dispatcher_sdk::{ejjob::EjJobType, prelude::*};
+use ej_dispatcher_sdk::{
+    ejjob::{EjJobQuery, EjJobType, search::EjJobSearchQuery},
+    ejpage::{EjPageQuery, EjSortOrder},
+    ejstats::EjStatsQuery,
+    prelude::*,
+};
 
-use crate::commands::{handle_fetch_jobs, handle_fetch_run_results};
+use crate::commands::{
+    handle_config_diff, handle_fetch_build_result, handle_fetch_run_results, handle_jobs,
+    handle_list_builders, handle_rerun, handle_retire_jwt_key, handle_rotate_jwt_key,
+    handle_search_jobs, handle_stats, handle_tail_logs, handle_watch,
+};
 
 /// Main entry point for the EJ CLI testing and setup tool.
 ///
@@ -39,31 +50,169 @@ use crate::commands::{handle_fetch_jobs, handle_fetch_run_results};
 ///
 /// # Testing: Dispatch a test run job and view logs
 /// ejcli dispatch-run --socket /tmp/ejd.sock --seconds 600 --commit-hash def456 --remote-url https://github.com/user/repo.git
+///
+/// # Testing: Follow a dispatched job live and summarize it once it finishes
+/// ejcli watch --socket /tmp/ejd.sock --job-id <job-uuid>
+///
+/// # Testing: Re-run a past job and follow it until it finishes
+/// ejcli rerun --socket /tmp/ejd.sock --seconds 600 --job-id <job-uuid> --watch
+///
+/// # CI: Report the results of a run job to GitLab or Jenkins
+/// ejcli fetch-run-result --socket /tmp/ejd.sock --job-id <job-uuid> --format junit -o results.xml
+///
+/// # Debugging: The jobs of a repository that failed last week
+/// ejcli jobs --socket /tmp/ejd.sock --status failed --since 7d --remote-url https://github.com/user/repo.git
+///
+/// # Debugging: Find the jobs that hit a linker error
+/// ejcli search-jobs --socket /tmp/ejd.sock --query '"undefined reference"'
+///
+/// # Setup: Check what a builder config change would change before restarting it
+/// ejcli config diff --socket /tmp/ejd.sock --builder-id <builder-uuid> --config config.toml
+///
+/// # Scripting: IDs of the jobs of a commit
+/// ejcli --output json jobs --socket /tmp/ejd.sock --commit-hash abc123 | jq -r '.[].id'
+///
+/// # Debugging: Success rate of the last 10 commits
+/// ejcli stats --socket /tmp/ejd.sock --commits $(git rev-list -n 10 HEAD | paste -sd,)
 /// ```
 #[tokio::main]
 async fn main() -> Result<()> {
     pretty_env_logger::init();
 
     let cli = Cli::parse();
+    let output = cli.output;
 
     let result = match cli.command {
         Commands::DispatchBuild { socket, job } => {
-            handle_dispatch(&socket, job, EjJobType::Build).await
+            handle_dispatch(&socket, job, EjJobType::Build, output).await
         }
         Commands::DispatchRun { socket, job } => {
-            handle_dispatch(&socket, job, EjJobType::BuildAndRun).await
+            handle_dispatch(&socket, job, EjJobType::BuildAndRun, output).await
         }
         Commands::CreateRootUser { socket, client } => {
             handle_create_root_user(&socket, client).await
         }
         Commands::CreateBuilder { server, client } => handle_create_builder(&server, client).await,
-        Commands::FetchJobs {
+        Commands::Jobs {
             socket,
             commit_hash,
-        } => handle_fetch_jobs(&socket, commit_hash).await,
-        Commands::FetchRunResult { socket, job_id } => {
-            handle_fetch_run_results(&socket, job_id).await
+            remote_url,
+            status,
+            job_type,
+            since,
+            until,
+            page,
+            per_page,
+        } => {
+            let query = EjJobQuery {
+                commit_hash,
+                remote_url,
+                status: status.map(Into::into),
+                job_type: job_type.map(Into::into),
+                since,
+                until,
+                page: EjPageQuery {
+                    page: Some(page),
+                    per_page: Some(per_page),
+                    sort: Some(String::from("created_at")),
+                    order: Some(EjSortOrder::Desc),
+                },
+            };
+            handle_jobs(&socket, query, output).await
+        }
+        Commands::FetchRunResult {
+            socket,
+            job_id,
+            format,
+            output_file,
+        } => {
+            handle_fetch_run_results(&socket, job_id, output, format, output_file.as_deref()).await
+        }
+        Commands::FetchBuildResult { socket, job_id } => {
+            handle_fetch_build_result(&socket, job_id, output).await
+        }
+        Commands::TailLogs { socket, job_id } => handle_tail_logs(&socket, job_id, output).await,
+        Commands::Watch { socket, job_id } => handle_watch(&socket, job_id, output).await,
+        Commands::Rerun {
+            socket,
+            job_id,
+            seconds,
+            remote_token,
+            watch,
+        } => {
+            handle_rerun(
+                &socket,
+                job_id,
+                Duration::from_secs(seconds),
+                remote_token,
+                watch,
+                output,
+            )
+            .await
+        }
+        Commands::SearchJobs {
+            socket,
+            query,
+            commit_hash,
+            job_id,
+            limit,
+        } => {
+            let query = EjJobSearchQuery {
+                q: query,
+                commit_hash,
+                job_id,
+                limit,
+                ..Default::default()
+            };
+            handle_search_jobs(&socket, query, output).await
+        }
+        Commands::Stats {
+            socket,
+            commits,
+            limit,
+        } => {
+            let query = EjStatsQuery {
+                commits: (!commits.is_empty()).then(|| commits.join(",")),
+                limit,
+                ..Default::default()
+            };
+            handle_stats(&socket, query, output).await
+        }
+        Commands::ListBuilders {
+            socket,
+            stale_after,
+            stale,
+            connected,
+        } => {
+            handle_list_builders(
+                &socket,
+                Duration::from_secs(stale_after),
+                stale,
+                connected,
+                output,
+            )
+            .await
         }
+        Commands::Config {
+            command:
+                ConfigCommands::Diff {
+                    socket,
+                    builder_id,
+                    config,
+                },
+        } => handle_config_diff(&socket, builder_id, &config).await,
+        Commands::JwtKey {
+            command:
+                JwtKeyCommands::Rotate {
+                    socket,
+                    kid,
+                    key,
+                    public_key,
+                },
+        } => handle_rotate_jwt_key(&socket, kid, key, public_key, output).await,
+        Commands::JwtKey {
+            command: JwtKeyCommands::Retire { socket, kid },
+        } => handle_retire_jwt_key(&socket, kid, output).await,
     };
 
     if let Err(ref e) = result {
@@ -2,43 +2,90 @@
 
 use axum::{
     Json, Router,
-    body::Bytes,
+    body::{Body, Bytes},
     extract::{
-        DefaultBodyLimit, State,
+        DefaultBodyLimit, Path, Query, State,
         ws::{Message, Utf8Bytes, WebSocket, WebSocketUpgrade},
     },
+    http::{HeaderMap, HeaderValue, StatusCode, header},
     middleware,
-    response::IntoResponse,
-    routing::{any, post},
+    response::{IntoResponse, Response},
+    routing::{any, delete, get, post, put},
 };
 use ej_config::ej_config::{EjConfig, EjUserConfig};
 use ej_dispatcher_sdk::{
-    ejbuilder::EjBuilderApi,
-    ejclient::{EjClientApi, EjClientLogin, EjClientLoginRequest, EjClientPost},
+    ejapi_key::{EjApiKeyApi, EjApiKeyCreated, EjApiKeyPost},
+    ejaudit::{EjAuditAction, EjAuditEntry, EjAuditQuery},
+    ejbuilder::{EjBuilderApi, EjBuilderConfigApi, EjBuilderInfo},
+    ejclient::{
+        EjClientApi, EjClientLogin, EjClientLoginRequest, EjClientPost, EjClientUpdate, EjRoleApi,
+    },
     ejjob::{
         EjDeployableJob, EjJob,
+        artifact::EjJobArtifact,
         results::{EjBuilderBuildResult, EjBuilderRunResult},
+        search::{EjJobSearchHit, EjJobSearchQuery},
+    },
+    ejpage::EjPageQuery,
+    ejsession::EjSessionApi,
+    ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage},
+    ejstats::{EjStats, EjStatsQuery},
+    ejtoken::{EjAccessTokenRevokeRequest, EjRefreshTokenRequest, EjTokens},
+    ejws_message::{
+        EJ_WS_COMPRESSION_HEADER, EJ_WS_PROTOCOL_VERSION, EJ_WS_PROTOCOL_VERSION_HEADER,
+        EjWsBinary, EjWsClientMessage, EjWsCompression, EjWsPayload, EjWsServerEnvelope,
+        EjWsServerMessage,
     },
-    ejws_message::{EjWsClientMessage, EjWsServerMessage},
+};
+use ej_models::{
+    builder::ejbuilder_connection::{EjBuilderConnection, EjBuilderConnectionCreate},
+    job::ejjob_artifact::EjJobArtifactCreate,
 };
 use ej_web::{
+    audit::{query_audit_log, record_audit},
     ctx::{
         Ctx,
-        resolver::{login_builder, login_client, mw_ctx_resolver},
+        resolver::{CtxResolverState, login_builder, login_client, mw_ctx_resolver, refresh_login},
+    },
+    ejapi_key::{create_api_key, list_api_keys, revoke_api_key},
+    ejartifact::{fetch_artifact, is_builder_board_config, list_job_artifacts},
+    ejbuilder::{builder_info, fetch_builder, list_builders, mark_builder_seen, revoke_builder},
+    ejclient::{
+        create_client, delete_client, fetch_client, fetch_client_permissions, fetch_client_roles,
+        grant_permission, grant_role, list_clients, list_permissions, list_roles,
+        revoke_permission, revoke_role, update_client,
     },
-    ejclient::create_client,
     ejconfig::save_config,
-    ejjob::create_job,
-    mw_auth::mw_require_auth,
+    ejconnected_builder::{BUILDER_QUEUE_CAPACITY, EjOverflowPolicy},
+    ejjob::{
+        cancel_undelivered_job, check_job_board_cancel, create_job, list_job_configs, search_jobs,
+    },
+    ejsession::{list_sessions, logout, revoke_session},
+    ejstats::fetch_stats,
+    ejtoken::revoke_refresh_token,
+    mw_auth::{mw_require_active_builder, mw_require_auth},
+    pagination::{Page, Pagination},
+    request_id::mw_request_id,
     require_permission,
+    revocation::revoke_access_token,
     traits::job_result::EjJobResult,
 };
-use tokio::{sync::mpsc::channel, task::JoinHandle};
+use tokio::{
+    sync::{
+        mpsc::{Sender, channel},
+        watch,
+    },
+    task::JoinHandle,
+    time::{Instant, interval, timeout},
+};
+use tokio_util::io::ReaderStream;
 use tower_cookies::{CookieManagerLayer, Cookies};
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::Duration;
 use tower_http::{
     cors::CorsLayer,
     trace::{DefaultMakeSpan, TraceLayer},
@@ -48,9 +95,13 @@ use axum::extract::connect_info::ConnectInfo;
 use axum::extract::ws::CloseFrame;
 use futures::{sink::SinkExt, stream::StreamExt};
 
+use crate::artifacts::ArtifactUpload;
+use crate::delivery::PendingDeliveries;
 use crate::dispatcher::Dispatcher;
+use crate::openapi::{ApiError, OPENAPI_JSON_PATH, SWAGGER_UI_PATH, openapi_json, swagger_ui};
 use crate::prelude::*;
-use ej_web::prelude::Result as EjWebResult;
+use crate::tls::TlsConfig;
+use ej_web::prelude::{Result as EjWebResult, W};
 
 /// Helper function to create versioned API paths.
 fn v1(path: &str) -> String {
@@ -65,21 +116,25 @@ fn v1(path: &str) -> String {
 /// - WebSocket endpoints for real-time communication
 /// - Middleware for authentication, logging, and CORS
 ///
+/// The API is served over TLS when a `TlsConfig` is provided, plain HTTP otherwise.
+///
 /// # Returns
 ///
 /// Returns a `JoinHandle` for the spawned HTTP server task.
-pub async fn setup_api(dispatcher: Dispatcher) -> Result<JoinHandle<Result<()>>> {
+pub async fn setup_api(
+    dispatcher: Dispatcher,
+    addr: SocketAddr,
+    tls: Option<TlsConfig>,
+) -> Result<JoinHandle<Result<()>>> {
     let builder_routes = Router::new()
         .route(&v1("builder/ws"), any(builder_handler))
         .route(&v1("builder/config"), post(push_config))
-        .route(
-            &v1("builder/build_result"),
-            post(job_result::<EjBuilderBuildResult>),
-        )
-        .route(
-            &v1("builder/run_result"),
-            post(job_result::<EjBuilderRunResult>),
-        )
+        .route(&v1("builder/build_result"), post(build_result))
+        .route(&v1("builder/run_result"), post(run_result))
+        .route_layer(middleware::from_fn_with_state(
+            dispatcher.connection.clone(),
+            mw_require_active_builder,
+        ))
         .route_layer(require_permission!("builder"))
         .route_layer(middleware::from_fn(mw_require_auth));
 
@@ -88,50 +143,135 @@ pub async fn setup_api(dispatcher: Dispatcher) -> Result<JoinHandle<Result<()>>>
         .route_layer(require_permission!("builder.create"))
         .route_layer(middleware::from_fn(mw_require_auth));
 
+    let builder_manage_routes = Router::new()
+        .route(&v1("client/builders"), get(get_builders))
+        .route(&v1("client/builders/{id}"), get(get_builder))
+        .route(&v1("client/builders/{id}/revoke"), post(revoke_builder_api))
+        .route_layer(require_permission!("builder.manage"))
+        .route_layer(middleware::from_fn(mw_require_auth));
+
     let client_dispatch_routes = Router::new()
         .route(&v1("client/dispatch"), post(dispatch_job))
+        .route(&v1("client/dispatch/ws"), any(dispatch_ws_handler))
+        .route(
+            &v1("client/jobs/{id}/boards/{board_config_id}/cancel"),
+            post(cancel_job_board),
+        )
         .route_layer(require_permission!("client.dispatch"))
         .route_layer(middleware::from_fn(mw_require_auth));
 
+    let client_results_routes = Router::new()
+        .route(&v1("client/jobs/search"), get(get_job_search))
+        .route(&v1("client/jobs/{id}/artifacts"), get(get_job_artifacts))
+        .route(&v1("client/jobs/{id}/configs"), get(get_job_configs))
+        .route(&v1("client/artifacts/{id}"), get(download_artifact))
+        .route(&v1("stats"), get(get_stats))
+        .route_layer(require_permission!("client.results"))
+        .route_layer(middleware::from_fn(mw_require_auth));
+
     let client_create_routes = Router::new()
         .route(&v1("client"), post(post_client))
         .route_layer(require_permission!("client.create"))
         .route_layer(middleware::from_fn(mw_require_auth));
 
+    let client_manage_routes = Router::new()
+        .route(&v1("clients"), get(get_clients))
+        .route(
+            &v1("clients/{id}"),
+            get(get_client)
+                .patch(patch_client)
+                .delete(delete_client_api),
+        )
+        .route(&v1("clients/{id}/permissions"), get(get_client_permissions))
+        .route(
+            &v1("clients/{id}/permissions/{permission}"),
+            put(grant_client_permission).delete(revoke_client_permission),
+        )
+        .route(&v1("clients/{id}/roles"), get(get_client_roles))
+        .route(
+            &v1("clients/{id}/roles/{role}"),
+            put(grant_client_role).delete(revoke_client_role),
+        )
+        .route(&v1("permissions"), get(get_permissions))
+        .route(&v1("roles"), get(get_roles))
+        .route_layer(require_permission!("client.manage"))
+        .route_layer(middleware::from_fn(mw_require_auth));
+
+    let audit_routes = Router::new()
+        .route(&v1("audit"), get(get_audit_log))
+        .route_layer(require_permission!("audit.read"))
+        .route_layer(middleware::from_fn(mw_require_auth));
+
+    let token_routes = Router::new()
+        .route(&v1("client/tokens/revoke"), post(revoke_access_token_api))
+        .route(&v1("client/api_keys"), get(get_api_keys).post(post_api_key))
+        .route(&v1("client/api_keys/{id}"), delete(delete_api_key))
+        .route(&v1("sessions"), get(get_sessions))
+        .route(&v1("sessions/{id}"), delete(delete_session))
+        .route(&v1("logout"), post(logout_api))
+        .route_layer(middleware::from_fn(mw_require_auth));
+
     let client_routes = Router::new()
         .route(&v1("login"), post(login))
-        .route(&v1("builder/login"), post(login_builder_api));
+        .route(&v1("builder/login"), post(login_builder_api))
+        .route(&v1("token/refresh"), post(refresh_token))
+        .route(&v1("token/revoke"), post(revoke_token));
 
     let app = Router::new()
         .merge(builder_routes)
         .merge(client_routes)
+        .merge(token_routes)
         .merge(builder_create_routes)
+        .merge(builder_manage_routes)
         .merge(client_create_routes)
+        .merge(client_manage_routes)
         .merge(client_dispatch_routes)
+        .merge(client_results_routes)
+        .merge(audit_routes)
+        .route(OPENAPI_JSON_PATH, get(openapi_json))
+        .route(SWAGGER_UI_PATH, get(swagger_ui))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(DefaultMakeSpan::default().include_headers(true)),
         )
         .layer(CorsLayer::permissive())
         .layer(middleware::from_fn_with_state(
-            dispatcher.clone(),
+            CtxResolverState {
+                revoked_tokens: dispatcher.revoked_tokens.clone(),
+                connection: dispatcher.connection.clone(),
+            },
             mw_ctx_resolver,
         ))
         .layer(CookieManagerLayer::new())
         .layer(DefaultBodyLimit::max(10 * 1024 * 1024))
+        .layer(middleware::from_fn(mw_request_id))
         .with_state(dispatcher);
 
-    // run it with hyper
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
-    tracing::debug!("listening on {}", listener.local_addr().unwrap());
-    let handle = tokio::spawn(async move {
-        axum::serve(
-            listener,
-            app.into_make_service_with_connect_info::<SocketAddr>(),
-        )
-        .await?;
-        Ok(())
-    });
+    let handle = match tls {
+        Some(tls) => {
+            let rustls_config = tls.load().await?;
+            info!("Listening on https://{addr}");
+            tokio::spawn(async move {
+                axum_server::bind_rustls(addr, rustls_config)
+                    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                    .await?;
+                Ok(())
+            })
+        }
+        None => {
+            // run it with hyper
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            tracing::debug!("listening on {}", listener.local_addr().unwrap());
+            tokio::spawn(async move {
+                axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .await?;
+                Ok(())
+            })
+        }
+    };
 
     Ok(handle)
 }
@@ -139,30 +279,490 @@ pub async fn setup_api(dispatcher: Dispatcher) -> Result<JoinHandle<Result<()>>>
 /// Creates a new client in the system.
 ///
 /// Handles POST requests to create new clients with authentication credentials.
-async fn post_client(
+#[utoipa::path(
+    post,
+    path = "/v1/client",
+    tag = "client",
+    request_body = EjClientPost,
+    responses(
+        (status = 200, description = "Client created", body = EjClientApi),
+        (status = 400, description = "Secret doesn't satisfy the password policy", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.create` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn post_client(
     State(state): State<Dispatcher>,
     Json(payload): Json<EjClientPost>,
 ) -> EjWebResult<Json<EjClientApi>> {
-    let client = create_client(payload, &state.connection)?;
+    let client = create_client(payload, &state.secret_policy, &state.connection)?;
     Ok(Json(client))
 }
 
+/// Lists the registered clients page by page.
+///
+/// Clients can be sorted by `name` (the default) or `created_at`.
+#[utoipa::path(
+    get,
+    path = "/v1/clients",
+    tag = "client",
+    params(EjPageQuery),
+    responses(
+        (status = 200, description = "Registered clients", body = Vec<EjClientApi>,
+            headers(("x-total-count" = i64, description = "Number of registered clients"))),
+        (status = 400, description = "Invalid pagination or sort field", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn get_clients(
+    State(state): State<Dispatcher>,
+    pagination: Pagination,
+) -> EjWebResult<Page<EjClientApi>> {
+    list_clients(&pagination, &state.connection)
+}
+
+/// Inspects a single client.
+#[utoipa::path(
+    get,
+    path = "/v1/clients/{id}",
+    tag = "client",
+    params(("id" = Uuid, Path, description = "Client ID")),
+    responses(
+        (status = 200, description = "Client details", body = EjClientApi),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+        (status = 404, description = "Client not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn get_client(
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+) -> EjWebResult<Json<EjClientApi>> {
+    Ok(Json(W::from(fetch_client(&id, &state.connection)?).0))
+}
+
+/// Updates a client's name and/or secret.
+#[utoipa::path(
+    patch,
+    path = "/v1/clients/{id}",
+    tag = "client",
+    params(("id" = Uuid, Path, description = "Client ID")),
+    request_body = EjClientUpdate,
+    responses(
+        (status = 200, description = "Client updated", body = EjClientApi),
+        (status = 400, description = "Secret doesn't satisfy the password policy", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+        (status = 404, description = "Client not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn patch_client(
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<EjClientUpdate>,
+) -> EjWebResult<Json<EjClientApi>> {
+    Ok(Json(update_client(
+        &id,
+        payload,
+        &state.secret_policy,
+        &state.connection,
+    )?))
+}
+
+/// Deletes a client.
+///
+/// Its builders must be revoked first, they're kept without an owner.
+#[utoipa::path(
+    delete,
+    path = "/v1/clients/{id}",
+    tag = "client",
+    params(("id" = Uuid, Path, description = "Client ID")),
+    responses(
+        (status = 200, description = "Client deleted"),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+        (status = 404, description = "Client not found", body = ApiError),
+        (status = 409, description = "Client still owns builders that aren't revoked", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn delete_client_api(
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+) -> EjWebResult<()> {
+    delete_client(&id, &state.connection)
+}
+
+/// Lists the permissions granted to a client.
+#[utoipa::path(
+    get,
+    path = "/v1/clients/{id}/permissions",
+    tag = "client",
+    params(("id" = Uuid, Path, description = "Client ID")),
+    responses(
+        (status = 200, description = "Client permissions", body = Vec<String>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+        (status = 404, description = "Client not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn get_client_permissions(
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+) -> EjWebResult<Json<Vec<String>>> {
+    Ok(Json(fetch_client_permissions(&id, &state.connection)?))
+}
+
+/// Grants a permission to a client.
+#[utoipa::path(
+    put,
+    path = "/v1/clients/{id}/permissions/{permission}",
+    tag = "client",
+    params(
+        ("id" = Uuid, Path, description = "Client ID"),
+        ("permission" = String, Path, description = "Permission ID"),
+    ),
+    responses(
+        (status = 200, description = "Resulting client permissions", body = Vec<String>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+        (status = 404, description = "Client or permission not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn grant_client_permission(
+    State(state): State<Dispatcher>,
+    ctx: Ctx,
+    Path((id, permission)): Path<(Uuid, String)>,
+) -> EjWebResult<Json<Vec<String>>> {
+    let permissions = grant_permission(&id, &permission, &state.connection)?;
+    record_audit(
+        EjAuditAction::PermissionGranted,
+        Some(ctx.client.id),
+        Some(format!("{id}/{permission}")),
+        &state.connection,
+    );
+    Ok(Json(permissions))
+}
+
+/// Revokes a permission from a client.
+#[utoipa::path(
+    delete,
+    path = "/v1/clients/{id}/permissions/{permission}",
+    tag = "client",
+    params(
+        ("id" = Uuid, Path, description = "Client ID"),
+        ("permission" = String, Path, description = "Permission ID"),
+    ),
+    responses(
+        (status = 200, description = "Resulting client permissions", body = Vec<String>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+        (status = 404, description = "Client not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn revoke_client_permission(
+    State(state): State<Dispatcher>,
+    ctx: Ctx,
+    Path((id, permission)): Path<(Uuid, String)>,
+) -> EjWebResult<Json<Vec<String>>> {
+    let permissions = revoke_permission(&id, &permission, &state.connection)?;
+    record_audit(
+        EjAuditAction::PermissionRevoked,
+        Some(ctx.client.id),
+        Some(format!("{id}/{permission}")),
+        &state.connection,
+    );
+    Ok(Json(permissions))
+}
+
+/// Lists the roles assigned to a client.
+#[utoipa::path(
+    get,
+    path = "/v1/clients/{id}/roles",
+    tag = "client",
+    params(("id" = Uuid, Path, description = "Client ID")),
+    responses(
+        (status = 200, description = "Client roles", body = Vec<String>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+        (status = 404, description = "Client not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn get_client_roles(
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+) -> EjWebResult<Json<Vec<String>>> {
+    Ok(Json(fetch_client_roles(&id, &state.connection)?))
+}
+
+/// Assigns a role to a client.
+#[utoipa::path(
+    put,
+    path = "/v1/clients/{id}/roles/{role}",
+    tag = "client",
+    params(
+        ("id" = Uuid, Path, description = "Client ID"),
+        ("role" = String, Path, description = "Role ID"),
+    ),
+    responses(
+        (status = 200, description = "Resulting client roles", body = Vec<String>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+        (status = 404, description = "Client or role not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn grant_client_role(
+    State(state): State<Dispatcher>,
+    ctx: Ctx,
+    Path((id, role)): Path<(Uuid, String)>,
+) -> EjWebResult<Json<Vec<String>>> {
+    let roles = grant_role(&id, &role, &state.connection)?;
+    record_audit(
+        EjAuditAction::RoleGranted,
+        Some(ctx.client.id),
+        Some(format!("{id}/{role}")),
+        &state.connection,
+    );
+    Ok(Json(roles))
+}
+
+/// Removes a role from a client.
+#[utoipa::path(
+    delete,
+    path = "/v1/clients/{id}/roles/{role}",
+    tag = "client",
+    params(
+        ("id" = Uuid, Path, description = "Client ID"),
+        ("role" = String, Path, description = "Role ID"),
+    ),
+    responses(
+        (status = 200, description = "Resulting client roles", body = Vec<String>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+        (status = 404, description = "Client not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn revoke_client_role(
+    State(state): State<Dispatcher>,
+    ctx: Ctx,
+    Path((id, role)): Path<(Uuid, String)>,
+) -> EjWebResult<Json<Vec<String>>> {
+    let roles = revoke_role(&id, &role, &state.connection)?;
+    record_audit(
+        EjAuditAction::RoleRevoked,
+        Some(ctx.client.id),
+        Some(format!("{id}/{role}")),
+        &state.connection,
+    );
+    Ok(Json(roles))
+}
+
+/// Lists every permission that can be granted.
+#[utoipa::path(
+    get,
+    path = "/v1/permissions",
+    tag = "client",
+    responses(
+        (status = 200, description = "Available permissions", body = Vec<String>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn get_permissions(
+    State(state): State<Dispatcher>,
+) -> EjWebResult<Json<Vec<String>>> {
+    Ok(Json(list_permissions(&state.connection)?))
+}
+
+/// Lists every role along with the permissions it grants.
+#[utoipa::path(
+    get,
+    path = "/v1/roles",
+    tag = "client",
+    responses(
+        (status = 200, description = "Available roles", body = Vec<EjRoleApi>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.manage` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn get_roles(
+    State(state): State<Dispatcher>,
+) -> EjWebResult<Json<Vec<EjRoleApi>>> {
+    Ok(Json(list_roles(&state.connection)?))
+}
+
+/// Queries the audit log.
+///
+/// Returns the entries matching the query, newest first.
+#[utoipa::path(
+    get,
+    path = "/v1/audit",
+    tag = "client",
+    params(EjAuditQuery),
+    responses(
+        (status = 200, description = "Matching audit log entries", body = Vec<EjAuditEntry>),
+        (status = 400, description = "Invalid query", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `audit.read` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn get_audit_log(
+    State(state): State<Dispatcher>,
+    Query(query): Query<EjAuditQuery>,
+) -> EjWebResult<Json<Vec<EjAuditEntry>>> {
+    Ok(Json(query_audit_log(query, &state.connection)?))
+}
+
 /// Creates a new builder for an authenticated client.
 ///
 /// Generates a builder instance with appropriate permissions and authentication token
 /// for the requesting client.
-async fn create_builder(
+#[utoipa::path(
+    post,
+    path = "/v1/client/builder",
+    tag = "client",
+    responses(
+        (status = 200, description = "Builder created", body = EjBuilderApi),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `builder.create` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn create_builder(
     State(mut state): State<Dispatcher>,
     ctx: Ctx,
 ) -> EjWebResult<Json<EjBuilderApi>> {
     Ok(Json(ctx.client.create_builder(&mut state.connection)?))
 }
 
+/// Lists the registered builders page by page.
+///
+/// Includes the connection status and the latest config uploaded by each builder.
+/// Builders can be sorted by `created_at` (the default) or `updated_at`.
+#[utoipa::path(
+    get,
+    path = "/v1/client/builders",
+    tag = "client",
+    params(EjPageQuery),
+    responses(
+        (status = 200, description = "Registered builders", body = Vec<EjBuilderInfo>,
+            headers(("x-total-count" = i64, description = "Number of registered builders"))),
+        (status = 400, description = "Invalid pagination or sort field", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `builder.manage` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn get_builders(
+    State(state): State<Dispatcher>,
+    pagination: Pagination,
+) -> EjWebResult<Page<EjBuilderInfo>> {
+    let connected = state.connected_builders().await;
+    let mut page = list_builders(&connected, &pagination, &state.connection)?;
+    state.set_current_jobs(&mut page.items).await;
+    Ok(page)
+}
+
+/// Inspects a single builder.
+#[utoipa::path(
+    get,
+    path = "/v1/client/builders/{id}",
+    tag = "client",
+    params(("id" = Uuid, Path, description = "Builder ID")),
+    responses(
+        (status = 200, description = "Builder details", body = EjBuilderInfo),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `builder.manage` permission", body = ApiError),
+        (status = 404, description = "Builder not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn get_builder(
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+) -> EjWebResult<Json<EjBuilderInfo>> {
+    let builder = fetch_builder(&id, &state.connection)?;
+    let connected = state.connected_builders().await;
+    let mut builder = builder_info(builder, &connected, &state.connection)?;
+    state
+        .set_current_jobs(std::slice::from_mut(&mut builder))
+        .await;
+    Ok(Json(builder))
+}
+
+/// Revokes a builder.
+///
+/// The builder's token stops being accepted and any open WebSocket connection
+/// it has with the dispatcher is closed.
+#[utoipa::path(
+    post,
+    path = "/v1/client/builders/{id}/revoke",
+    tag = "client",
+    params(("id" = Uuid, Path, description = "Builder ID")),
+    responses(
+        (status = 200, description = "Builder revoked", body = EjBuilderInfo),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `builder.manage` permission", body = ApiError),
+        (status = 404, description = "Builder not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn revoke_builder_api(
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+) -> EjWebResult<Json<EjBuilderInfo>> {
+    let builder = revoke_builder(&id, &state.connection)?;
+    info!("Builder {id} revoked");
+
+    // Closing can wait for a slow builder, the others must not wait for it
+    let revoked = {
+        let mut builders = state.builders.lock().await;
+        let (revoked, kept): (Vec<_>, Vec<_>) =
+            builders.drain(..).partition(|b| b.builder.id == id);
+        *builders = kept;
+        revoked
+    };
+    for connected in revoked.iter() {
+        if let Err(err) = connected
+            .send(EjWsServerMessage::Close, EjOverflowPolicy::Wait)
+            .await
+        {
+            error!("Failed to close connection with revoked builder {id} - {err}");
+        }
+    }
+
+    Ok(Json(builder_info(builder, &[], &state.connection)?))
+}
+
 /// Handles client login requests.
 ///
 /// Authenticates clients using their credentials and sets authentication cookies
 /// for subsequent requests.
-async fn login(
+#[utoipa::path(
+    post,
+    path = "/v1/login",
+    tag = "auth",
+    request_body = EjClientLoginRequest,
+    responses(
+        (status = 200, description = "Client authenticated", body = EjClientLogin),
+        (status = 401, description = "Invalid credentials", body = ApiError),
+    )
+)]
+pub(crate) async fn login(
     state: State<Dispatcher>,
     cookies: Cookies,
     Json(payload): Json<EjClientLoginRequest>,
@@ -173,42 +773,581 @@ async fn login(
 /// Handles builder login requests.
 ///
 /// Authenticates builders using their JWT tokens and sets authentication cookies
-/// for WebSocket and API communication.
-async fn login_builder_api(
+/// for WebSocket and API communication. Builders get a short-lived access token
+/// and a refresh token back.
+#[utoipa::path(
+    post,
+    path = "/v1/builder/login",
+    tag = "auth",
+    request_body = EjBuilderApi,
+    responses(
+        (status = 200, description = "Builder authenticated", body = EjTokens),
+        (status = 401, description = "Invalid or revoked builder token", body = ApiError),
+    )
+)]
+pub(crate) async fn login_builder_api(
+    state: State<Dispatcher>,
     cookies: Cookies,
     Json(payload): Json<EjBuilderApi>,
-) -> EjWebResult<Json<EjBuilderApi>> {
-    Ok(Json(login_builder(payload, &cookies)?))
+) -> EjWebResult<Json<EjTokens>> {
+    Ok(Json(login_builder(&payload, &state.connection, &cookies)?))
+}
+
+/// Exchanges a refresh token for a new access token and refresh token.
+///
+/// Works for both clients and builders. The refresh token is revoked in the
+/// process, using it again revokes every refresh token of its owner.
+#[utoipa::path(
+    post,
+    path = "/v1/token/refresh",
+    tag = "auth",
+    request_body = EjRefreshTokenRequest,
+    responses(
+        (status = 200, description = "Tokens refreshed", body = EjTokens),
+        (status = 401, description = "Invalid, expired or already used refresh token", body = ApiError),
+    )
+)]
+pub(crate) async fn refresh_token(
+    state: State<Dispatcher>,
+    cookies: Cookies,
+    Json(payload): Json<EjRefreshTokenRequest>,
+) -> EjWebResult<Json<EjTokens>> {
+    Ok(Json(refresh_login(
+        &payload,
+        &state.revoked_tokens,
+        &state.connection,
+        &cookies,
+    )?))
+}
+
+/// Revokes a refresh token.
+///
+/// Revoking an unknown or already revoked token succeeds as well.
+#[utoipa::path(
+    post,
+    path = "/v1/token/revoke",
+    tag = "auth",
+    request_body = EjRefreshTokenRequest,
+    responses(
+        (status = 204, description = "Refresh token revoked"),
+    )
+)]
+pub(crate) async fn revoke_token(
+    state: State<Dispatcher>,
+    Json(payload): Json<EjRefreshTokenRequest>,
+) -> EjWebResult<StatusCode> {
+    revoke_refresh_token(&payload.refresh_token, &state.connection)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists the active sessions of the authenticated client.
+#[utoipa::path(
+    get,
+    path = "/v1/sessions",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Active sessions, newest first", body = Vec<EjSessionApi>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "API keys can't manage sessions", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn get_sessions(
+    ctx: Ctx,
+    State(state): State<Dispatcher>,
+) -> EjWebResult<Json<Vec<EjSessionApi>>> {
+    Ok(Json(list_sessions(&ctx, &state.connection)?))
+}
+
+/// Revokes one of the sessions of the authenticated client.
+///
+/// The access tokens issued for the session stop being accepted immediately
+/// and its refresh tokens can't be used anymore.
+#[utoipa::path(
+    delete,
+    path = "/v1/sessions/{id}",
+    tag = "auth",
+    params(("id" = Uuid, Path, description = "Session ID")),
+    responses(
+        (status = 204, description = "Session revoked"),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "API keys can't manage sessions", body = ApiError),
+        (status = 404, description = "Session not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn delete_session(
+    ctx: Ctx,
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+) -> EjWebResult<StatusCode> {
+    revoke_session(&ctx, &id, &state.revoked_tokens, &state.connection)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Logs out, revoking the current session and clearing the authentication cookie.
+#[utoipa::path(
+    post,
+    path = "/v1/logout",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Logged out"),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "API keys can't manage sessions", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn logout_api(
+    ctx: Ctx,
+    State(state): State<Dispatcher>,
+    cookies: Cookies,
+) -> EjWebResult<StatusCode> {
+    logout(&ctx, &state.revoked_tokens, &state.connection, &cookies)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Revokes an access token before it expires.
+///
+/// The token stops being accepted immediately. Clients can revoke their own
+/// tokens, revoking other tokens requires `client.manage` for client tokens
+/// and `builder.manage` for builder tokens.
+#[utoipa::path(
+    post,
+    path = "/v1/client/tokens/revoke",
+    tag = "auth",
+    request_body = EjAccessTokenRevokeRequest,
+    responses(
+        (status = 204, description = "Token revoked"),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Not allowed to revoke this token", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn revoke_access_token_api(
+    ctx: Ctx,
+    State(state): State<Dispatcher>,
+    Json(payload): Json<EjAccessTokenRevokeRequest>,
+) -> EjWebResult<StatusCode> {
+    revoke_access_token(
+        &ctx,
+        &payload.access_token,
+        &state.revoked_tokens,
+        &state.connection,
+    )?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists the API keys of the authenticated client.
+#[utoipa::path(
+    get,
+    path = "/v1/client/api_keys",
+    tag = "auth",
+    responses(
+        (status = 200, description = "API keys of the client", body = Vec<EjApiKeyApi>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []), ("api_key" = []))
+)]
+pub(crate) async fn get_api_keys(
+    ctx: Ctx,
+    State(state): State<Dispatcher>,
+) -> EjWebResult<Json<Vec<EjApiKeyApi>>> {
+    Ok(Json(list_api_keys(&ctx, &state.connection)?))
+}
+
+/// Creates an API key for the authenticated client.
+///
+/// The key is only returned once. It can only be granted scopes whose
+/// permission the client has, and API keys can't create other keys.
+#[utoipa::path(
+    post,
+    path = "/v1/client/api_keys",
+    tag = "auth",
+    request_body = EjApiKeyPost,
+    responses(
+        (status = 200, description = "API key created", body = EjApiKeyCreated),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Scope not allowed for this client", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn post_api_key(
+    ctx: Ctx,
+    State(state): State<Dispatcher>,
+    Json(payload): Json<EjApiKeyPost>,
+) -> EjWebResult<Json<EjApiKeyCreated>> {
+    Ok(Json(create_api_key(&ctx, payload, &state.connection)?))
+}
+
+/// Revokes one of the API keys of the authenticated client.
+#[utoipa::path(
+    delete,
+    path = "/v1/client/api_keys/{id}",
+    tag = "auth",
+    params(("id" = Uuid, Path, description = "API key ID")),
+    responses(
+        (status = 200, description = "API key revoked", body = EjApiKeyApi),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 404, description = "API key not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []), ("api_key" = []))
+)]
+pub(crate) async fn delete_api_key(
+    ctx: Ctx,
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+) -> EjWebResult<Json<EjApiKeyApi>> {
+    Ok(Json(revoke_api_key(&ctx, &id, &state.connection)?))
 }
 
 /// Dispatches a job to all connected builders.
 ///
 /// Creates a deployable job from the request and sends it to all available builders
 /// via WebSocket connections. Returns the created job for tracking.
-async fn dispatch_job(
+/// The job is rejected if the client went over its dispatch quota or if no
+/// builder is connected, and cancelled if no builder received it.
+#[utoipa::path(
+    post,
+    path = "/v1/client/dispatch",
+    tag = "client",
+    request_body = EjJob,
+    responses(
+        (status = 200, description = "Job created and dispatched", body = EjDeployableJob),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.dispatch` permission", body = ApiError),
+        (status = 404, description = "No builders available", body = ApiError),
+        (status = 429, description = "Dispatch quota exceeded", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []), ("api_key" = []))
+)]
+pub(crate) async fn dispatch_job(
     State(mut state): State<Dispatcher>,
+    ctx: Ctx,
     Json(payload): Json<EjJob>,
 ) -> EjWebResult<Json<EjDeployableJob>> {
-    let builders = state.builders.lock().await;
-    let job = create_job(payload, &mut state.connection)?;
+    // Sent from a copy so a slow builder doesn't hold up every other dispatch
+    let builders = state.connected_builders().await;
+    if builders.is_empty() {
+        return Err(ej_web::error::Error::NoBuildersAvailable);
+    }
+    let job = create_job(
+        payload,
+        Some(ctx.client.id),
+        &state.dispatch_quota,
+        &mut state.connection,
+    )?;
+    record_audit(
+        EjAuditAction::Dispatch,
+        Some(ctx.client.id),
+        Some(job.id.to_string()),
+        &state.connection,
+    );
+    let mut delivered = false;
     for builder in builders.iter() {
-        if let Err(err) = builder
-            .tx
-            .send(EjWsServerMessage::BuildAndRun(job.clone()))
+        match builder
+            .send(
+                EjWsServerMessage::BuildAndRun(job.clone()),
+                EjOverflowPolicy::Wait,
+            )
             .await
         {
-            tracing::error!("Failed to dispatch job {err}");
+            Ok(()) => delivered = true,
+            Err(err) => tracing::error!("Failed to dispatch job {err}"),
         }
     }
+    if !delivered {
+        warn!("No builder received job {}, cancelling it", job.id);
+        cancel_undelivered_job(&job.id, &state.connection)?;
+    }
     Ok(Json(job))
 }
 
+/// Dispatches a job and streams its updates over a WebSocket.
+///
+/// Lets clients on other machines follow their jobs the way the Unix socket
+/// does: the client sends an `EjSocketClientMessage::Dispatch` message and the
+/// dispatcher answers with the same `EjSocketServerMessage`s as the socket,
+/// then closes the connection once the job finished.
+/// The job is rejected if the client went over its dispatch quota.
+#[utoipa::path(
+    get,
+    path = "/v1/client/dispatch/ws",
+    tag = "client",
+    description = "Upgrades the connection to a WebSocket. The client then sends an `EjSocketClientMessage::Dispatch` JSON message and receives `EjSocketServerMessage` JSON messages.",
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol"),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.dispatch` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []), ("api_key" = []))
+)]
+pub(crate) async fn dispatch_ws_handler(
+    ws: WebSocketUpgrade,
+    ctx: Ctx,
+    State(state): State<Dispatcher>,
+) -> Response {
+    ws.on_upgrade(move |socket| handle_dispatch_socket(ctx, state, socket))
+}
+
+/// Serves a client's dispatch WebSocket, reporting failures to the client before closing it.
+async fn handle_dispatch_socket(ctx: Ctx, mut dispatcher: Dispatcher, mut socket: WebSocket) {
+    if let Err(err) = dispatch_over_socket(&ctx, &mut dispatcher, &mut socket).await {
+        warn!("Dispatch requested by {} failed - {err}", ctx.client.id);
+        let message = EjSocketServerMessage::Error(err.to_string());
+        if let Err(err) = send_socket_message(&mut socket, &message).await {
+            debug!("Failed to report the error to {} - {err}", ctx.client.id);
+        }
+    }
+    if let Err(err) = socket.send(Message::Close(None)).await {
+        debug!(
+            "Failed to close dispatch WebSocket of {} - {err}",
+            ctx.client.id
+        );
+    }
+}
+
+/// Dispatches the job the client sends and forwards its updates until it finishes.
+async fn dispatch_over_socket(
+    ctx: &Ctx,
+    dispatcher: &mut Dispatcher,
+    socket: &mut WebSocket,
+) -> Result<()> {
+    let (job, timeout) = loop {
+        let text = match socket.recv().await {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(Message::Close(_))) | None => return Ok(()),
+            Some(Ok(_)) => continue,
+            Some(Err(err)) => return Err(err.into()),
+        };
+        match serde_json::from_str(&text)? {
+            EjSocketClientMessage::Dispatch { job, timeout } => break (job, timeout),
+            _ => return Err(Error::InvalidWsMessage),
+        }
+    };
+
+    let (tx, mut rx) = channel(16);
+    let job = dispatcher
+        .dispatch_client_job(job, Some(ctx.client.id), tx, timeout)
+        .await?;
+    record_audit(
+        EjAuditAction::Dispatch,
+        Some(ctx.client.id),
+        Some(job.id.to_string()),
+        &dispatcher.connection,
+    );
+    send_socket_message(socket, &EjSocketServerMessage::DispatchOk(job)).await?;
+    while let Some(update) = rx.recv().await {
+        send_socket_message(socket, &EjSocketServerMessage::JobUpdate(update)).await?;
+    }
+    Ok(())
+}
+
+async fn send_socket_message(
+    socket: &mut WebSocket,
+    message: &EjSocketServerMessage,
+) -> Result<()> {
+    let text = serde_json::to_string(message)?;
+    socket.send(Message::Text(text.into())).await?;
+    Ok(())
+}
+
+/// Searches the logs and results of every job.
+///
+/// Returns excerpts of the matching logs and results, newest first, so the
+/// jobs that produced an error message can be found without downloading
+/// every log.
+#[utoipa::path(
+    get,
+    path = "/v1/client/jobs/search",
+    tag = "client",
+    params(EjJobSearchQuery),
+    responses(
+        (status = 200, description = "Matching logs and results", body = Vec<EjJobSearchHit>),
+        (status = 400, description = "Empty or invalid query", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.results` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []), ("api_key" = []))
+)]
+pub(crate) async fn get_job_search(
+    State(state): State<Dispatcher>,
+    Query(query): Query<EjJobSearchQuery>,
+) -> EjWebResult<Json<Vec<EjJobSearchHit>>> {
+    Ok(Json(search_jobs(query, &state.connection)?))
+}
+
+/// Computes statistics about the jobs that ran to completion.
+///
+/// Returns the job durations per board config, the success rate per commit
+/// and the number of jobs run by each builder.
+#[utoipa::path(
+    get,
+    path = "/v1/stats",
+    tag = "client",
+    params(EjStatsQuery),
+    responses(
+        (status = 200, description = "Job statistics", body = EjStats),
+        (status = 400, description = "Invalid query", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.results` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []), ("api_key" = []))
+)]
+pub(crate) async fn get_stats(
+    State(state): State<Dispatcher>,
+    Query(query): Query<EjStatsQuery>,
+) -> EjWebResult<Json<EjStats>> {
+    Ok(Json(fetch_stats(query, &state.connection)?))
+}
+
+/// Lists the artifacts uploaded by the builders for a job.
+#[utoipa::path(
+    get,
+    path = "/v1/client/jobs/{id}/artifacts",
+    tag = "client",
+    params(("id" = Uuid, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "Artifacts produced by the job, oldest first", body = Vec<EjJobArtifact>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.results` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []), ("api_key" = []))
+)]
+pub(crate) async fn get_job_artifacts(
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+) -> EjWebResult<Json<Vec<EjJobArtifact>>> {
+    Ok(Json(list_job_artifacts(&id, &state.connection)?))
+}
+
+/// Lists the config versions the builders ran a job against.
+#[utoipa::path(
+    get,
+    path = "/v1/client/jobs/{id}/configs",
+    tag = "client",
+    params(("id" = Uuid, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "Config versions the job ran against, oldest first", body = Vec<EjBuilderConfigApi>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.results` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []), ("api_key" = []))
+)]
+pub(crate) async fn get_job_configs(
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+) -> EjWebResult<Json<Vec<EjBuilderConfigApi>>> {
+    Ok(Json(list_job_configs(&id, &state.connection)?))
+}
+
+/// Cancels a single board config of a running job.
+///
+/// The other board configs of the job keep going. The request is ignored if
+/// the job isn't the one currently running. Clients can cancel their own jobs,
+/// cancelling other jobs requires `client.manage`.
+#[utoipa::path(
+    post,
+    path = "/v1/client/jobs/{id}/boards/{board_config_id}/cancel",
+    tag = "client",
+    params(
+        ("id" = Uuid, Path, description = "Job ID"),
+        ("board_config_id" = Uuid, Path, description = "Board config ID"),
+    ),
+    responses(
+        (status = 202, description = "Cancellation requested"),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.dispatch` permission or job of another client", body = ApiError),
+        (status = 404, description = "Job or board config not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []), ("api_key" = []))
+)]
+pub(crate) async fn cancel_job_board(
+    State(state): State<Dispatcher>,
+    ctx: Ctx,
+    Path((id, board_config_id)): Path<(Uuid, Uuid)>,
+) -> EjWebResult<StatusCode> {
+    check_job_board_cancel(&ctx, &id, &board_config_id, &state.connection)?;
+    state
+        .cancel_board(id, board_config_id)
+        .await
+        .map_err(|err| {
+            error!("Failed to cancel board config {board_config_id} of job {id} - {err}");
+            ej_web::error::Error::InternalErrorDispatchingJob
+        })?;
+    record_audit(
+        EjAuditAction::Cancel,
+        Some(ctx.client.id),
+        Some(format!("{id}/{board_config_id}")),
+        &state.connection,
+    );
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Downloads the content of an artifact.
+///
+/// The file is streamed from disk rather than loaded in memory as firmware
+/// images can be large.
+#[utoipa::path(
+    get,
+    path = "/v1/client/artifacts/{id}",
+    tag = "client",
+    params(("id" = Uuid, Path, description = "Artifact ID")),
+    responses(
+        (status = 200, description = "Artifact content", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `client.results` permission", body = ApiError),
+        (status = 404, description = "Artifact not found", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []), ("api_key" = []))
+)]
+pub(crate) async fn download_artifact(
+    State(state): State<Dispatcher>,
+    Path(id): Path<Uuid>,
+) -> EjWebResult<Response> {
+    let artifact = fetch_artifact(&id, &state.connection)?;
+    let file = tokio::fs::File::open(state.artifacts.path(&id))
+        .await
+        .map_err(|err| {
+            error!("Failed to open artifact {id} - {err}");
+            ej_web::error::Error::ArtifactNotFound
+        })?;
+
+    let disposition = format!(
+        "attachment; filename=\"{}\"",
+        artifact.name.replace('"', "")
+    );
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                String::from("application/octet-stream"),
+            ),
+            (header::CONTENT_LENGTH, artifact.size.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    )
+        .into_response())
+}
+
 /// Handles builder configuration uploads.
 ///
 /// Receives and stores configuration from authenticated builders, converting
 /// user configurations to the internal format.
+#[utoipa::path(
+    post,
+    path = "/v1/builder/config",
+    tag = "builder",
+    request_body = EjUserConfig,
+    responses(
+        (status = 200, description = "Configuration stored", body = EjConfig),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `builder` permission", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
 #[axum::debug_handler]
-async fn push_config(
+pub(crate) async fn push_config(
     State(mut state): State<Dispatcher>,
     ctx: Ctx,
     Json(payload): Json<EjUserConfig>,
@@ -218,6 +1357,48 @@ async fn push_config(
     Ok(Json(config))
 }
 
+/// Handles build result submissions from builders.
+#[utoipa::path(
+    post,
+    path = "/v1/builder/build_result",
+    tag = "builder",
+    request_body = EjBuilderBuildResult,
+    responses(
+        (status = 200, description = "Build result accepted"),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `builder` permission", body = ApiError),
+        (status = 404, description = "No builders available", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn build_result(
+    dispatcher: State<Dispatcher>,
+    payload: Json<EjBuilderBuildResult>,
+) -> EjWebResult<()> {
+    job_result(dispatcher, payload).await
+}
+
+/// Handles run result submissions from builders.
+#[utoipa::path(
+    post,
+    path = "/v1/builder/run_result",
+    tag = "builder",
+    request_body = EjBuilderRunResult,
+    responses(
+        (status = 200, description = "Run result accepted"),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `builder` permission", body = ApiError),
+        (status = 404, description = "No builders available", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+pub(crate) async fn run_result(
+    dispatcher: State<Dispatcher>,
+    payload: Json<EjBuilderRunResult>,
+) -> EjWebResult<()> {
+    job_result(dispatcher, payload).await
+}
+
 /// Handles job result submissions from builders.
 ///
 /// Generic endpoint that accepts build or run results from builders and
@@ -241,17 +1422,84 @@ async fn job_result<T: EjJobResult>(
 /// The handler for the HTTP request (this gets called when the HTTP request lands at the start
 /// of websocket negotiation). After this completes, the actual switching from HTTP to
 /// websocket protocol will occur.
+///
+/// Builders must advertise the WebSocket protocol version they speak. Mismatched
+/// builders are rejected before the upgrade so they fail with a clear error instead
+/// of misparsing messages. The dispatcher's version is returned in every response.
+///
+/// Builders may also offer to compress large payloads, the picked compression is
+/// returned in the response.
+#[utoipa::path(
+    get,
+    path = "/v1/builder/ws",
+    tag = "builder",
+    description = "Upgrades the connection to a WebSocket. The dispatcher then sends `EjWsServerMessage` JSON messages to the builder.",
+    params(
+        ("x-ej-protocol-version" = u32, Header, description = "WebSocket protocol version spoken by the builder"),
+        ("x-ej-compression" = Option<String>, Header, description = "Comma separated list of compressions supported by the builder, such as `gzip`"),
+    ),
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol"),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Missing `builder` permission", body = ApiError),
+        (status = 426, description = "The builder speaks a different WebSocket protocol version", body = ApiError),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
 #[axum::debug_handler]
-async fn builder_handler(
+pub(crate) async fn builder_handler(
     ws: WebSocketUpgrade,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     ctx: Ctx,
     State(state): State<Dispatcher>,
-) -> impl IntoResponse {
+) -> Response {
     println!("Client at {addr} connected.");
 
     info!("ctx: {} {:?}", ctx.client.id, ctx.who);
-    ws.on_upgrade(move |socket| handle_socket(ctx, state, socket, addr))
+    let builder_version = builder_protocol_version(&headers);
+
+    let compression = EjWsCompression::negotiate(
+        headers
+            .get(EJ_WS_COMPRESSION_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    );
+
+    let response = if builder_version == Some(EJ_WS_PROTOCOL_VERSION) {
+        ws.on_upgrade(move |socket| handle_socket(ctx, state, socket, addr, compression))
+            .into_response()
+    } else {
+        warn!(
+            "Rejecting builder {} at {addr}: its WebSocket protocol version ({}) doesn't match ours (v{})",
+            ctx.client.id,
+            builder_version.map_or(String::from("unknown"), |v| format!("v{v}")),
+            EJ_WS_PROTOCOL_VERSION
+        );
+        ej_web::error::Error::WsProtocolVersionMismatch.into_response()
+    };
+    with_protocol_headers(response, compression)
+}
+
+/// Extracts the WebSocket protocol version a builder advertised in its upgrade request.
+fn builder_protocol_version(headers: &HeaderMap) -> Option<u32> {
+    headers
+        .get(EJ_WS_PROTOCOL_VERSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+/// Tells the builder our WebSocket protocol version and the compression agreed on,
+/// whether its upgrade was accepted or not.
+fn with_protocol_headers(mut response: Response, compression: EjWsCompression) -> Response {
+    response.headers_mut().insert(
+        EJ_WS_PROTOCOL_VERSION_HEADER,
+        HeaderValue::from(EJ_WS_PROTOCOL_VERSION),
+    );
+    response.headers_mut().insert(
+        EJ_WS_COMPRESSION_HEADER,
+        HeaderValue::from_static(compression.header_value()),
+    );
+    response
 }
 
 /// RAII guard to automatically remove builders from the dispatcher when connections close.
@@ -261,10 +1509,14 @@ struct BuilderGuard {
 }
 
 impl Drop for BuilderGuard {
-    /// Automatically removes the builder from the dispatcher's builder list when dropped.
+    /// Automatically removes the builder from the dispatcher's builder list when dropped
+    /// and records the connection as closed.
     fn drop(&mut self) {
         let builders = self.dispatcher.builders.clone();
         let connection_id = self.connection_id;
+        if let Err(err) = EjBuilderConnection::close(&connection_id, &self.dispatcher.connection) {
+            error!("Failed to record that connection {connection_id} was closed - {err}");
+        }
         tokio::spawn(async move {
             builders
                 .lock()
@@ -273,9 +1525,237 @@ impl Drop for BuilderGuard {
         });
     }
 }
+/// State of a builder's WebSocket connection.
+///
+/// Tracks the artifacts the builder is currently uploading so their binary
+/// chunks can be written as they arrive.
+struct BuilderSession {
+    dispatcher: Dispatcher,
+    builder_id: Uuid,
+    connection_id: Uuid,
+    addr: SocketAddr,
+    uploads: HashMap<Uuid, ArtifactUpload>,
+    acks: Sender<Uuid>,
+}
+
+impl BuilderSession {
+    /// Whether the builder is deployed on `job_id` and `board_config_id` is
+    /// one of its board configs.
+    async fn is_running(&self, job_id: &Uuid, board_config_id: &Uuid) -> bool {
+        let deployed = self
+            .dispatcher
+            .builder_jobs
+            .lock()
+            .await
+            .get(&self.builder_id)
+            == Some(job_id);
+        if !deployed {
+            return false;
+        }
+        is_builder_board_config(
+            &self.builder_id,
+            board_config_id,
+            &self.dispatcher.connection,
+        )
+        .unwrap_or_else(|err| {
+            error!(
+                "Failed to check the board config {board_config_id} of builder {} - {err}",
+                self.builder_id
+            );
+            false
+        })
+    }
+
+    /// Handles a text message received from the builder.
+    async fn handle_text(&mut self, text: &str) -> Result<()> {
+        match serde_json::from_str(text)? {
+            EjWsClientMessage::JobState { job_id } => {
+                self.dispatcher
+                    .on_builder_job_state(self.builder_id, self.connection_id, job_id)
+                    .await
+            }
+            EjWsClientMessage::Ack { message_id } => self
+                .acks
+                .send(message_id)
+                .await
+                .map_err(|_| Error::WsSocketReceiveFail),
+            EjWsClientMessage::Capabilities(capabilities) => {
+                info!(
+                    "Builder {} has {} board(s) with tags {:?}",
+                    self.builder_id, capabilities.parallel_capacity, capabilities.tags
+                );
+                if !self
+                    .dispatcher
+                    .on_builder_capabilities(self.connection_id, capabilities)
+                    .await
+                {
+                    warn!(
+                        "Received capabilities for a closed connection from {}",
+                        self.addr
+                    );
+                }
+                Ok(())
+            }
+            EjWsClientMessage::LogChunk {
+                job_id,
+                board_config_id,
+                lines,
+            } => {
+                self.dispatcher
+                    .on_log_chunk(self.builder_id, job_id, board_config_id, lines)
+                    .await
+            }
+            EjWsClientMessage::ArtifactStart {
+                artifact_id,
+                job_id,
+                board_config_id,
+                name,
+                size,
+            } => {
+                let Ok(size) = i64::try_from(size) else {
+                    warn!(
+                        "{} from {}",
+                        Error::ArtifactTooLarge(artifact_id),
+                        self.addr
+                    );
+                    return Ok(());
+                };
+                if !self.is_running(&job_id, &board_config_id).await {
+                    warn!(
+                        "Rejecting artifact {artifact_id} from {} - {}",
+                        self.addr,
+                        Error::ArtifactNotOwned(job_id, board_config_id)
+                    );
+                    return Ok(());
+                }
+                let artifact = EjJobArtifactCreate {
+                    id: artifact_id,
+                    ejjob_id: job_id,
+                    ejboard_config_id: board_config_id,
+                    name,
+                    size,
+                };
+                match self.dispatcher.artifacts.begin(artifact).await {
+                    Ok(upload) => {
+                        self.uploads.insert(artifact_id, upload);
+                    }
+                    Err(err) => {
+                        error!(
+                            "Failed to start artifact {artifact_id} from {} - {err}",
+                            self.addr
+                        )
+                    }
+                }
+                Ok(())
+            }
+            EjWsClientMessage::ArtifactEnd { artifact_id } => {
+                let Some(upload) = self.uploads.remove(&artifact_id) else {
+                    warn!("{}", Error::UnknownArtifact(artifact_id));
+                    return Ok(());
+                };
+                match upload.finish(&self.dispatcher.connection).await {
+                    Ok(artifact) => info!(
+                        "Received artifact {} ({} bytes) for job {} from {}",
+                        artifact.name, artifact.size, artifact.job_id, self.addr
+                    ),
+                    Err(err) => {
+                        error!(
+                            "Failed to store artifact {artifact_id} from {} - {err}",
+                            self.addr
+                        )
+                    }
+                }
+                Ok(())
+            }
+            EjWsClientMessage::BoardLocks { boards } => {
+                if !self
+                    .dispatcher
+                    .on_builder_board_locks(self.connection_id, boards)
+                    .await
+                {
+                    warn!(
+                        "Received board locks for a closed connection from {}",
+                        self.addr
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Handles a binary frame received from the builder.
+    ///
+    /// Failing to store an artifact chunk discards that artifact but keeps the
+    /// connection open.
+    async fn handle_binary(&mut self, frame: &[u8]) -> Result<()> {
+        match EjWsBinary::decode(frame).map_err(|_| Error::InvalidWsMessage)? {
+            EjWsBinary::Message(text) => self.handle_text(&text).await,
+            EjWsBinary::ArtifactChunk { artifact_id, data } => {
+                let Some(upload) = self.uploads.get_mut(&artifact_id) else {
+                    warn!("{}", Error::UnknownArtifact(artifact_id));
+                    return Ok(());
+                };
+                if let Err(err) = upload.write(&data).await {
+                    error!(
+                        "Discarding artifact {artifact_id} from {} - {err}",
+                        self.addr
+                    );
+                    self.uploads.remove(&artifact_id);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Handles a text or binary message received from the builder.
+    async fn handle_message(&mut self, message: Message) -> Result<()> {
+        match message {
+            Message::Text(t) => self.handle_text(&t).await,
+            Message::Binary(data) => self.handle_binary(&data).await,
+            Message::Ping(_) | Message::Pong(_) | Message::Close(_) => Ok(()),
+        }
+    }
+}
+
+/// Interval between the heartbeat pings sent to connected builders.
+const WS_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long a builder may stay silent before its connection is considered dead.
+const WS_PONG_DEADLINE: Duration = Duration::from_secs(60);
+
+/// How often unacknowledged messages are checked for retransmission.
+const WS_RETRANSMIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Serializes a message envelope into a WebSocket frame, compressing it if it's large enough.
+fn encode_envelope(envelope: &EjWsServerEnvelope, compression: EjWsCompression) -> Result<Message> {
+    Ok(
+        match compression.compress(serde_json::to_string(envelope)?)? {
+            EjWsPayload::Text(text) => Message::Text(text.into()),
+            EjWsPayload::Binary(data) => Message::Binary(data.into()),
+        },
+    )
+}
+
 /// Actual websocket statemachine (one will be spawned per connection)
-async fn handle_socket(ctx: Ctx, dispatcher: Dispatcher, mut socket: WebSocket, addr: SocketAddr) {
-    let (tx, mut rx) = channel(2);
+///
+/// Builders are pinged every `WS_PING_INTERVAL`. Each ping records when the builder was
+/// last heard from in its connection history. If nothing is received from a builder
+/// within `WS_PONG_DEADLINE`, the socket is closed.
+///
+/// Job messages are retransmitted until the builder acknowledges them, see
+/// [`crate::delivery`]. A builder that never does is disconnected as well.
+///
+/// However the session ends, the builder is removed from the dispatcher and the
+/// dispatcher is told it was lost so any job it was working on doesn't wait for it
+/// until the job times out.
+async fn handle_socket(
+    ctx: Ctx,
+    dispatcher: Dispatcher,
+    mut socket: WebSocket,
+    addr: SocketAddr,
+    compression: EjWsCompression,
+) {
+    let (tx, mut rx) = channel(BUILDER_QUEUE_CAPACITY);
 
     if socket
         .send(Message::Ping(Bytes::from_static(&[1, 2, 3])))
@@ -288,18 +1768,36 @@ async fn handle_socket(ctx: Ctx, dispatcher: Dispatcher, mut socket: WebSocket,
         return;
     }
 
+    // Builders may send their job state before answering the ping. Keep it around
+    // until the connection is registered
+    let mut first_message = None;
     if let Some(msg) = socket.recv().await {
         if let Ok(msg) = msg {
-            if let Message::Close(_) = msg {
-                return;
+            match msg {
+                Message::Close(_) => return,
+                Message::Text(_) | Message::Binary(_) => first_message = Some(msg),
+                _ => {}
             }
         } else {
             return;
         }
     }
 
+    let builder_id = ctx.client.id;
     let connection_id = {
         let mut builders = dispatcher.builders.lock().await;
+        // A builder reconnecting after a network blip may still have its old connection
+        // registered. Drop it so jobs aren't dispatched twice to the same builder.
+        for stale in builders.iter().filter(|b| b.builder.id == builder_id) {
+            info!(
+                "Builder {builder_id} reconnected from {addr}. Closing its previous connection from {}",
+                stale.addr
+            );
+            let _ = stale
+                .send(EjWsServerMessage::Close, EjOverflowPolicy::Drop)
+                .await;
+        }
+        builders.retain(|b| b.builder.id != builder_id);
         let connected_client = ctx.client.connect(tx.clone(), addr);
         let connection_id = connected_client.connection_id.clone();
         builders.push(connected_client);
@@ -310,40 +1808,103 @@ async fn handle_socket(ctx: Ctx, dispatcher: Dispatcher, mut socket: WebSocket,
         dispatcher: dispatcher.clone(),
         connection_id,
     };
+    let builder_connection = EjBuilderConnectionCreate {
+        id: connection_id,
+        ejbuilder_id: builder_id,
+        address: addr.to_string(),
+    };
+    if let Err(err) = builder_connection.create(&dispatcher.connection) {
+        error!("Failed to record connection of builder {builder_id} from {addr} - {err}");
+    }
+
+    let (ack_tx, mut ack_rx) = channel(BUILDER_QUEUE_CAPACITY);
+    let mut session = BuilderSession {
+        dispatcher: dispatcher.clone(),
+        builder_id,
+        connection_id,
+        addr,
+        uploads: HashMap::new(),
+        acks: ack_tx,
+    };
+
+    if let Some(message) = first_message
+        && let Err(err) = session.handle_message(message).await
+    {
+        tracing::error!("Failed to handle message from {addr} - {err}. Closing connection");
+        return;
+    }
 
     let (mut sender, mut receiver) = socket.split();
+    let (last_seen_tx, last_seen_rx) = watch::channel(Instant::now());
+    let db = dispatcher.connection.clone();
 
     let mut send_task: JoinHandle<Result<()>> = tokio::spawn(async move {
+        let mut heartbeat = interval(WS_PING_INTERVAL);
+        let mut last_recorded = *last_seen_rx.borrow();
+        let mut retransmit = interval(WS_RETRANSMIT_INTERVAL);
+        let mut deliveries = PendingDeliveries::default();
         loop {
-            let message = rx.recv().await;
-
-            if let Some(message) = message {
-                let is_close = matches!(message, EjWsServerMessage::Close);
+            tokio::select! {
+                message = rx.recv() => {
+                    if let Some(message) = message {
+                        let is_close = matches!(message, EjWsServerMessage::Close);
 
-                if is_close {
-                    println!("Sending close to {addr}...");
-                    sender
-                        .send(Message::Close(Some(CloseFrame {
-                            code: axum::extract::ws::close_code::NORMAL,
-                            reason: Utf8Bytes::from_static("Goodbye"),
-                        })))
-                        .await?;
+                        if is_close {
+                            println!("Sending close to {addr}...");
+                            sender
+                                .send(Message::Close(Some(CloseFrame {
+                                    code: axum::extract::ws::close_code::NORMAL,
+                                    reason: Utf8Bytes::from_static("Goodbye"),
+                                })))
+                                .await?;
 
-                    return Ok(());
+                            return Ok(());
+                        }
+                        let envelope = deliveries.track(message, Instant::now());
+                        sender.send(encode_envelope(&envelope, compression)?).await?;
+                    } else {
+                        info!("Websocket send channel closed");
+                        return Ok(());
+                    }
+                }
+                Some(message_id) = ack_rx.recv() => {
+                    if !deliveries.ack(&message_id) {
+                        debug!("{addr} acknowledged unknown message {message_id}");
+                    }
+                }
+                _ = retransmit.tick() => {
+                    for envelope in deliveries.due(Instant::now())? {
+                        warn!("{addr} didn't acknowledge {:?} in time, retransmitting", envelope.id);
+                        sender.send(encode_envelope(&envelope, compression)?).await?;
+                    }
+                }
+                _ = heartbeat.tick() => {
+                    let silence = last_seen_rx.borrow().elapsed();
+                    if silence > WS_PONG_DEADLINE {
+                        warn!("{addr} hasn't answered in {silence:?}. Closing connection");
+                        let close = sender.send(Message::Close(Some(CloseFrame {
+                            code: axum::extract::ws::close_code::AWAY,
+                            reason: Utf8Bytes::from_static("Heartbeat timeout"),
+                        })));
+                        if timeout(Duration::from_secs(1), close).await.is_err() {
+                            debug!("Timed out sending close to {addr}");
+                        }
+                        return Err(Error::WsHeartbeatTimeout);
+                    }
+                    let last_seen = *last_seen_rx.borrow();
+                    if last_seen != last_recorded {
+                        if let Err(err) = mark_builder_seen(&connection_id, silence, &db) {
+                            warn!("Failed to record when {addr} was last seen - {err}");
+                        }
+                        last_recorded = last_seen;
+                    }
+                    sender.send(Message::Ping(Bytes::new())).await?;
                 }
-                let serialized_message = serde_json::to_string(&message)?;
-
-                sender
-                    .send(Message::Text(serialized_message.into()))
-                    .await?;
-            } else {
-                info!("Websocket send channel closed");
-                return Ok(());
             }
         }
     });
 
-    let mut recv_task = tokio::spawn(async move {
+    let mut recv_task: JoinHandle<Result<()>> = tokio::spawn(async move {
         loop {
             let message = receiver
                 .next()
@@ -351,10 +1912,9 @@ async fn handle_socket(ctx: Ctx, dispatcher: Dispatcher, mut socket: WebSocket,
                 .ok_or(Error::WsSocketReceiveFail)?
                 .map_err(|err| Error::WsSocketReceiveError(err.to_string()))?;
 
+            last_seen_tx.send_replace(Instant::now());
+
             match message {
-                Message::Text(t) => {
-                    let _message: EjWsClientMessage = serde_json::from_str(&t)?;
-                }
                 Message::Close(c) => {
                     if let Some(cf) = c {
                         tracing::info!(
@@ -368,10 +1928,7 @@ async fn handle_socket(ctx: Ctx, dispatcher: Dispatcher, mut socket: WebSocket,
                     }
                     return Ok(());
                 }
-                Message::Binary(_) => {
-                    return Err(Error::InvalidWsMessage);
-                }
-                Message::Ping(_) | Message::Pong(_) => {}
+                message => session.handle_message(message).await?,
             }
         }
     });
@@ -385,6 +1942,58 @@ async fn handle_socket(ctx: Ctx, dispatcher: Dispatcher, mut socket: WebSocket,
             tracing::info!("{:?}", rv_b);
             send_task.abort();
         }
+    };
+
+    if let Err(err) = dispatcher
+        .on_builder_disconnected(builder_id, connection_id)
+        .await
+    {
+        error!("Failed to notify dispatcher that builder {builder_id} was lost - {err}");
     }
     tracing::info!("Websocket context {addr} destroyed");
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builder_protocol_version() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(builder_protocol_version(&headers), None);
+
+        headers.insert(
+            EJ_WS_PROTOCOL_VERSION_HEADER,
+            HeaderValue::from_static("v2"),
+        );
+        assert_eq!(builder_protocol_version(&headers), None);
+
+        headers.insert(
+            EJ_WS_PROTOCOL_VERSION_HEADER,
+            HeaderValue::from(EJ_WS_PROTOCOL_VERSION),
+        );
+        assert_eq!(
+            builder_protocol_version(&headers),
+            Some(EJ_WS_PROTOCOL_VERSION)
+        );
+    }
+
+    #[test]
+    fn test_protocol_version_mismatch_is_rejected() {
+        let response = with_protocol_headers(
+            ej_web::error::Error::WsProtocolVersionMismatch.into_response(),
+            EjWsCompression::Gzip,
+        );
+
+        assert_eq!(response.status(), StatusCode::UPGRADE_REQUIRED);
+        let headers = response.headers();
+        assert_eq!(
+            builder_protocol_version(headers),
+            Some(EJ_WS_PROTOCOL_VERSION)
+        );
+        assert_eq!(
+            headers.get(EJ_WS_COMPRESSION_HEADER).unwrap(),
+            EjWsCompression::Gzip.header_value()
+        );
+    }
+}
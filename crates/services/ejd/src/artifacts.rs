@@ -0,0 +1,232 @@
+//! Storage for the artifacts uploaded by builders.
+//!
+//! Builders stream artifacts over their WebSocket connection: an `ArtifactStart`
+//! message announces the upload, binary frames carry its content and an
+//! `ArtifactEnd` message completes it. The content is written to
+//! `<artifacts_path>/<id>.part` while it's received and moved to
+//! `<artifacts_path>/<id>` once complete, at which point its metadata is saved
+//! to the database. Incomplete uploads are discarded.
+//!
+//! Builders pick the artifact IDs, so uploads never replace an existing file:
+//! an ID already in use is rejected.
+
+use std::path::{Path, PathBuf};
+
+use ej_dispatcher_sdk::ejjob::artifact::EjJobArtifact;
+use ej_models::{db::connection::DbConnection, job::ejjob_artifact::EjJobArtifactCreate};
+use ej_web::ejartifact::save_artifact;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+};
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+/// Directory holding the content of every artifact.
+#[derive(Debug, Clone)]
+pub struct ArtifactStore {
+    root: PathBuf,
+    max_size: u64,
+}
+
+/// Artifact being received from a builder.
+pub struct ArtifactUpload {
+    artifact: EjJobArtifactCreate,
+    file: File,
+    written: u64,
+    part_path: PathBuf,
+    path: PathBuf,
+    done: bool,
+}
+
+impl ArtifactStore {
+    /// Opens the artifact store, creating its directory if needed.
+    ///
+    /// # Arguments
+    /// * `root` - Directory where artifacts are stored
+    /// * `max_size` - Size in bytes of the largest artifact accepted
+    pub fn open(root: PathBuf, max_size: u64) -> Result<Self> {
+        std::fs::create_dir_all(&root)?;
+        Ok(Self { root, max_size })
+    }
+
+    /// Path of the content of a complete artifact.
+    pub fn path(&self, id: &Uuid) -> PathBuf {
+        self.root.join(id.to_string())
+    }
+
+    /// Starts receiving an artifact.
+    ///
+    /// Only the file name of `artifact.name` is kept, builders may send paths.
+    /// Fails if the artifact is larger than the store accepts or if its ID is
+    /// already in use.
+    ///
+    /// # Arguments
+    /// * `artifact` - Metadata announced by the builder
+    pub async fn begin(&self, mut artifact: EjJobArtifactCreate) -> Result<ArtifactUpload> {
+        if artifact.size as u64 > self.max_size {
+            return Err(Error::ArtifactTooLarge(artifact.id));
+        }
+        artifact.name = Path::new(&artifact.name)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| artifact.id.to_string());
+
+        let path = self.path(&artifact.id);
+        if tokio::fs::try_exists(&path).await? {
+            return Err(Error::ArtifactExists(artifact.id));
+        }
+        let part_path = path.with_extension("part");
+        let file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&part_path)
+            .await
+        {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(Error::ArtifactExists(artifact.id));
+            }
+            Err(err) => return Err(err.into()),
+        };
+        Ok(ArtifactUpload {
+            artifact,
+            file,
+            written: 0,
+            part_path,
+            path,
+            done: false,
+        })
+    }
+}
+
+impl ArtifactUpload {
+    /// Appends the next piece of the artifact.
+    ///
+    /// Fails if the artifact grows past the size announced by the builder.
+    pub async fn write(&mut self, data: &[u8]) -> Result<()> {
+        let written = self.written + data.len() as u64;
+        if written > self.artifact.size as u64 {
+            return Err(Error::ArtifactSizeMismatch(self.artifact.id));
+        }
+        self.file.write_all(data).await?;
+        self.written = written;
+        Ok(())
+    }
+
+    /// Completes the upload and saves the artifact metadata.
+    ///
+    /// Fails if fewer bytes than announced were received.
+    pub async fn finish(mut self, connection: &DbConnection) -> Result<EjJobArtifact> {
+        if self.written != self.artifact.size as u64 {
+            return Err(Error::ArtifactSizeMismatch(self.artifact.id));
+        }
+        self.file.flush().await?;
+        // Linking fails rather than replacing an artifact stored in the meantime
+        match tokio::fs::hard_link(&self.part_path, &self.path).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(Error::ArtifactExists(self.artifact.id));
+            }
+            Err(err) => return Err(err.into()),
+        }
+        let _ = tokio::fs::remove_file(&self.part_path).await;
+
+        match save_artifact(self.artifact.clone(), connection) {
+            Ok(artifact) => {
+                self.done = true;
+                Ok(artifact)
+            }
+            Err(err) => {
+                let _ = std::fs::remove_file(&self.path);
+                Err(err.into())
+            }
+        }
+    }
+}
+
+impl Drop for ArtifactUpload {
+    /// Discards the content received so far if the upload didn't complete.
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = std::fs::remove_file(&self.part_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_upload_size_is_enforced() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::open(dir.path().join("artifacts"), 1024).unwrap();
+        let artifact = EjJobArtifactCreate {
+            id: Uuid::new_v4(),
+            ejjob_id: Uuid::new_v4(),
+            ejboard_config_id: Uuid::new_v4(),
+            name: String::from("build/../firmware.bin"),
+            size: 4,
+        };
+        let part_path = store.path(&artifact.id).with_extension("part");
+
+        let mut upload = store.begin(artifact).await.unwrap();
+        assert_eq!(upload.artifact.name, "firmware.bin");
+        upload.write(b"abc").await.unwrap();
+        assert!(matches!(
+            upload.write(b"de").await,
+            Err(Error::ArtifactSizeMismatch(_))
+        ));
+        assert!(part_path.exists());
+
+        drop(upload);
+        assert!(!part_path.exists());
+    }
+
+    fn artifact(size: i64) -> EjJobArtifactCreate {
+        EjJobArtifactCreate {
+            id: Uuid::new_v4(),
+            ejjob_id: Uuid::new_v4(),
+            ejboard_config_id: Uuid::new_v4(),
+            name: String::from("firmware.bin"),
+            size,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_large_artifacts_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::open(dir.path().join("artifacts"), 1024).unwrap();
+        assert!(store.begin(artifact(1024)).await.is_ok());
+        assert!(matches!(
+            store.begin(artifact(1025)).await,
+            Err(Error::ArtifactTooLarge(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_artifact_ids_in_use_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::open(dir.path().join("artifacts"), 1024).unwrap();
+
+        let stored = artifact(4);
+        std::fs::write(store.path(&stored.id), b"keep").unwrap();
+        assert!(matches!(
+            store.begin(stored.clone()).await,
+            Err(Error::ArtifactExists(_))
+        ));
+        assert_eq!(std::fs::read(store.path(&stored.id)).unwrap(), b"keep");
+
+        let uploading = artifact(4);
+        let part_path = store.path(&uploading.id).with_extension("part");
+        let mut upload = store.begin(uploading.clone()).await.unwrap();
+        upload.write(b"abc").await.unwrap();
+        assert!(matches!(
+            store.begin(uploading).await,
+            Err(Error::ArtifactExists(_))
+        ));
+        assert!(part_path.exists());
+    }
+}
@@ -0,0 +1,372 @@
+//! Runtime configuration for the EJ Dispatcher Service.
+//!
+//! Every setting is read from an environment variable and validated at startup
+//! so a misconfigured deployment fails fast with a clear error instead of
+//! misbehaving later on.
+//!
+//! | Variable             | Default              | Description                                  |
+//! |----------------------|----------------------|----------------------------------------------|
+//! | `EJD_BIND_ADDR`      | `0.0.0.0`            | IP address the REST API listens on           |
+//! | `EJD_PORT`           | `3000`               | Port the REST API listens on                 |
+//! | `EJD_SOCKET_PATH`    | `/tmp/ejd.sock`      | Path of the administration Unix socket       |
+//! | `EJD_SOCKET_MODE`    | unset                | Octal permissions applied to the Unix socket |
+//! | `EJ_SOCKET_KEY`      | unset                | Key socket messages must be signed with      |
+//! | `EJD_TLS_CERT`       | unset                | PEM certificate chain used to serve TLS      |
+//! | `EJD_TLS_KEY`        | unset                | PEM private key used to serve TLS            |
+//! | `EJD_ARTIFACTS_PATH` | `/tmp/ejd-artifacts` | Directory where build artifacts are stored   |
+//! | `EJD_MAX_ARTIFACT_SIZE` | `1073741824`      | Size in bytes of the largest artifact stored |
+//! | `EJD_SECRET_MIN_LENGTH` | `12`              | Minimum length of client secrets             |
+//! | `EJD_SECRET_MIN_CLASSES` | `3`              | Character classes client secrets must mix    |
+//! | `EJD_SECRET_REJECT_COMMON` | `true`         | Reject commonly used client secrets          |
+//! | `EJD_MAX_QUEUED_JOBS` | unset               | Jobs a client can have queued at once        |
+//! | `EJD_MAX_JOBS_PER_HOUR` | unset             | Jobs a client can dispatch per hour          |
+
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
+
+use ej_auth::{
+    secret_hash::SecretPolicy,
+    socket_signature::{SOCKET_KEY_ENV, SocketKey},
+};
+use ej_web::quota::DispatchQuota;
+
+use crate::prelude::*;
+use crate::tls::{TLS_CERT_ENV, TLS_KEY_ENV, TlsConfig};
+
+/// Environment variable holding the IP address the API binds to.
+pub const BIND_ADDR_ENV: &str = "EJD_BIND_ADDR";
+
+/// Environment variable holding the port the API binds to.
+pub const PORT_ENV: &str = "EJD_PORT";
+
+/// Environment variable holding the Unix socket path.
+pub const SOCKET_PATH_ENV: &str = "EJD_SOCKET_PATH";
+
+/// Environment variable holding the Unix socket permissions.
+pub const SOCKET_MODE_ENV: &str = "EJD_SOCKET_MODE";
+
+/// Environment variable holding the directory where artifacts are stored.
+pub const ARTIFACTS_PATH_ENV: &str = "EJD_ARTIFACTS_PATH";
+
+/// Environment variable holding the size in bytes of the largest artifact builders can upload.
+pub const MAX_ARTIFACT_SIZE_ENV: &str = "EJD_MAX_ARTIFACT_SIZE";
+
+/// Environment variable holding the minimum length of client secrets.
+pub const SECRET_MIN_LENGTH_ENV: &str = "EJD_SECRET_MIN_LENGTH";
+
+/// Environment variable holding the number of character classes client secrets must mix.
+pub const SECRET_MIN_CLASSES_ENV: &str = "EJD_SECRET_MIN_CLASSES";
+
+/// Environment variable controlling whether common client secrets are rejected.
+pub const SECRET_REJECT_COMMON_ENV: &str = "EJD_SECRET_REJECT_COMMON";
+
+/// Environment variable holding the number of jobs a client can have queued at once.
+pub const MAX_QUEUED_JOBS_ENV: &str = "EJD_MAX_QUEUED_JOBS";
+
+/// Environment variable holding the number of jobs a client can dispatch per hour.
+pub const MAX_JOBS_PER_HOUR_ENV: &str = "EJD_MAX_JOBS_PER_HOUR";
+
+const DEFAULT_BIND_ADDR: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+const DEFAULT_PORT: u16 = 3000;
+const DEFAULT_SOCKET_PATH: &str = "/tmp/ejd.sock";
+const DEFAULT_ARTIFACTS_PATH: &str = "/tmp/ejd-artifacts";
+const DEFAULT_MAX_ARTIFACT_SIZE: u64 = 1 << 30;
+
+/// Validated ejd configuration.
+#[derive(Debug, Clone)]
+pub struct EjdConfig {
+    /// Address the REST API and builder WebSocket listen on.
+    pub api_addr: SocketAddr,
+    /// Path of the administration Unix socket.
+    pub socket_path: PathBuf,
+    /// Permissions applied to the Unix socket once created.
+    pub socket_mode: Option<u32>,
+    /// Key socket messages must be signed with, unsigned messages are accepted if unset.
+    pub socket_key: Option<SocketKey>,
+    /// TLS configuration, the API is served over plain HTTP if unset.
+    pub tls: Option<TlsConfig>,
+    /// Directory where artifacts uploaded by builders are stored.
+    pub artifacts_path: PathBuf,
+    /// Size in bytes of the largest artifact builders can upload.
+    pub max_artifact_size: u64,
+    /// Strength requirements for client secrets.
+    pub secret_policy: SecretPolicy,
+    /// Limits on the jobs a single client can dispatch.
+    pub dispatch_quota: DispatchQuota,
+}
+
+impl EjdConfig {
+    /// Create the configuration from environment variables.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Create the configuration from an arbitrary variable source.
+    ///
+    /// `get` returns the value of the variable with the given name, if set.
+    pub fn from_vars(get: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let ip = match get(BIND_ADDR_ENV) {
+            Some(value) => value
+                .parse::<IpAddr>()
+                .map_err(|_| Error::InvalidBindAddress(value))?,
+            None => DEFAULT_BIND_ADDR,
+        };
+
+        let port = match get(PORT_ENV) {
+            Some(value) => value
+                .parse::<u16>()
+                .map_err(|_| Error::InvalidPort(value))?,
+            None => DEFAULT_PORT,
+        };
+
+        let socket_path = PathBuf::from(
+            get(SOCKET_PATH_ENV).unwrap_or_else(|| String::from(DEFAULT_SOCKET_PATH)),
+        );
+        match socket_path.parent() {
+            Some(parent) if parent.as_os_str().is_empty() || parent.is_dir() => {}
+            _ => return Err(Error::InvalidSocketPath(socket_path)),
+        }
+
+        let socket_mode = match get(SOCKET_MODE_ENV) {
+            Some(value) => Some(
+                u32::from_str_radix(&value, 8)
+                    .ok()
+                    .filter(|mode| *mode <= 0o777)
+                    .ok_or(Error::InvalidSocketMode(value))?,
+            ),
+            None => None,
+        };
+
+        let socket_key = get(SOCKET_KEY_ENV)
+            .filter(|key| !key.is_empty())
+            .map(SocketKey::new);
+
+        let tls = TlsConfig::from_paths(
+            get(TLS_CERT_ENV).map(PathBuf::from),
+            get(TLS_KEY_ENV).map(PathBuf::from),
+        )?;
+
+        let artifacts_path = PathBuf::from(
+            get(ARTIFACTS_PATH_ENV).unwrap_or_else(|| String::from(DEFAULT_ARTIFACTS_PATH)),
+        );
+        let max_artifact_size = match get(MAX_ARTIFACT_SIZE_ENV) {
+            Some(value) => value
+                .parse::<u64>()
+                .ok()
+                .filter(|size| *size > 0)
+                .ok_or(Error::InvalidArtifactSize(value))?,
+            None => DEFAULT_MAX_ARTIFACT_SIZE,
+        };
+
+        let defaults = SecretPolicy::default();
+        let min_length = match get(SECRET_MIN_LENGTH_ENV) {
+            Some(value) => value
+                .parse::<usize>()
+                .map_err(|_| Error::InvalidSecretPolicy(SECRET_MIN_LENGTH_ENV, value))?,
+            None => defaults.min_length,
+        };
+        let min_classes = match get(SECRET_MIN_CLASSES_ENV) {
+            Some(value) => value
+                .parse::<usize>()
+                .ok()
+                .filter(|classes| *classes <= 4)
+                .ok_or(Error::InvalidSecretPolicy(SECRET_MIN_CLASSES_ENV, value))?,
+            None => defaults.min_classes,
+        };
+        let reject_common = match get(SECRET_REJECT_COMMON_ENV) {
+            Some(value) => value
+                .parse::<bool>()
+                .map_err(|_| Error::InvalidSecretPolicy(SECRET_REJECT_COMMON_ENV, value))?,
+            None => defaults.reject_common,
+        };
+
+        let max_queued_jobs = match get(MAX_QUEUED_JOBS_ENV) {
+            Some(value) => Some(
+                value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|limit| *limit > 0)
+                    .ok_or(Error::InvalidQuota(MAX_QUEUED_JOBS_ENV, value))?,
+            ),
+            None => None,
+        };
+        let max_jobs_per_hour = match get(MAX_JOBS_PER_HOUR_ENV) {
+            Some(value) => Some(
+                value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|limit| *limit > 0)
+                    .ok_or(Error::InvalidQuota(MAX_JOBS_PER_HOUR_ENV, value))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            api_addr: SocketAddr::new(ip, port),
+            socket_path,
+            socket_mode,
+            socket_key,
+            tls,
+            artifacts_path,
+            max_artifact_size,
+            secret_policy: SecretPolicy {
+                min_length,
+                min_classes,
+                reject_common,
+            },
+            dispatch_quota: DispatchQuota {
+                max_queued_jobs,
+                max_jobs_per_hour,
+            },
+        })
+    }
+}
+
+impl fmt::Display for EjdConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+        write!(
+            f,
+            "API on {scheme}://{}, socket at {}",
+            self.api_addr,
+            self.socket_path.display()
+        )?;
+        if let Some(mode) = self.socket_mode {
+            write!(f, " (mode {mode:o})")?;
+        }
+        if self.socket_key.is_some() {
+            write!(f, " accepting signed messages only")?;
+        }
+        write!(f, ", artifacts in {}", self.artifacts_path.display())?;
+        if !self.dispatch_quota.is_unlimited() {
+            write!(f, ", clients limited to {}", self.dispatch_quota)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn config(vars: &[(&str, &str)]) -> Result<EjdConfig> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        EjdConfig::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_default_config() {
+        let config = config(&[]).unwrap();
+        assert_eq!(config.api_addr, "0.0.0.0:3000".parse().unwrap());
+        assert_eq!(config.socket_path, PathBuf::from("/tmp/ejd.sock"));
+        assert_eq!(config.socket_mode, None);
+        assert!(config.socket_key.is_none());
+        assert!(config.tls.is_none());
+        assert_eq!(config.artifacts_path, PathBuf::from("/tmp/ejd-artifacts"));
+        assert_eq!(config.max_artifact_size, 1 << 30);
+        assert_eq!(config.secret_policy, SecretPolicy::default());
+        assert!(config.dispatch_quota.is_unlimited());
+    }
+
+    #[test]
+    fn test_custom_config() {
+        let config = config(&[
+            (BIND_ADDR_ENV, "127.0.0.1"),
+            (PORT_ENV, "8080"),
+            (SOCKET_PATH_ENV, "/tmp/custom.sock"),
+            (SOCKET_MODE_ENV, "660"),
+            (SOCKET_KEY_ENV, "shared secret"),
+            (ARTIFACTS_PATH_ENV, "/srv/ejd/artifacts"),
+            (MAX_ARTIFACT_SIZE_ENV, "1048576"),
+            (SECRET_MIN_LENGTH_ENV, "16"),
+            (SECRET_MIN_CLASSES_ENV, "4"),
+            (SECRET_REJECT_COMMON_ENV, "false"),
+            (MAX_QUEUED_JOBS_ENV, "5"),
+            (MAX_JOBS_PER_HOUR_ENV, "60"),
+        ])
+        .unwrap();
+        assert!(config.socket_key.is_some());
+        assert_eq!(config.api_addr, "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(config.socket_path, PathBuf::from("/tmp/custom.sock"));
+        assert_eq!(config.socket_mode, Some(0o660));
+        assert_eq!(config.artifacts_path, PathBuf::from("/srv/ejd/artifacts"));
+        assert_eq!(config.max_artifact_size, 1 << 20);
+        assert_eq!(
+            config.secret_policy,
+            SecretPolicy {
+                min_length: 16,
+                min_classes: 4,
+                reject_common: false,
+            }
+        );
+        assert_eq!(
+            config.dispatch_quota,
+            DispatchQuota {
+                max_queued_jobs: Some(5),
+                max_jobs_per_hour: Some(60),
+            }
+        );
+    }
+
+    #[test]
+    fn test_ipv6_bind_address() {
+        let config = config(&[(BIND_ADDR_ENV, "::1")]).unwrap();
+        assert_eq!(config.api_addr, "[::1]:3000".parse().unwrap());
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        assert!(matches!(
+            config(&[(BIND_ADDR_ENV, "localhost")]),
+            Err(Error::InvalidBindAddress(_))
+        ));
+        assert!(matches!(
+            config(&[(PORT_ENV, "70000")]),
+            Err(Error::InvalidPort(_))
+        ));
+        assert!(matches!(
+            config(&[(SOCKET_PATH_ENV, "/this/dir/does/not/exist/ejd.sock")]),
+            Err(Error::InvalidSocketPath(_))
+        ));
+        assert!(matches!(
+            config(&[(SOCKET_MODE_ENV, "rw")]),
+            Err(Error::InvalidSocketMode(_))
+        ));
+        assert!(matches!(
+            config(&[(SOCKET_MODE_ENV, "1777")]),
+            Err(Error::InvalidSocketMode(_))
+        ));
+        assert!(matches!(
+            config(&[(MAX_ARTIFACT_SIZE_ENV, "1GB")]),
+            Err(Error::InvalidArtifactSize(_))
+        ));
+        assert!(matches!(
+            config(&[(SECRET_MIN_LENGTH_ENV, "-1")]),
+            Err(Error::InvalidSecretPolicy(SECRET_MIN_LENGTH_ENV, _))
+        ));
+        assert!(matches!(
+            config(&[(SECRET_MIN_CLASSES_ENV, "5")]),
+            Err(Error::InvalidSecretPolicy(SECRET_MIN_CLASSES_ENV, _))
+        ));
+        assert!(matches!(
+            config(&[(SECRET_REJECT_COMMON_ENV, "yes")]),
+            Err(Error::InvalidSecretPolicy(SECRET_REJECT_COMMON_ENV, _))
+        ));
+        assert!(matches!(
+            config(&[(MAX_QUEUED_JOBS_ENV, "0")]),
+            Err(Error::InvalidQuota(MAX_QUEUED_JOBS_ENV, _))
+        ));
+        assert!(matches!(
+            config(&[(MAX_JOBS_PER_HOUR_ENV, "many")]),
+            Err(Error::InvalidQuota(MAX_JOBS_PER_HOUR_ENV, _))
+        ));
+    }
+}
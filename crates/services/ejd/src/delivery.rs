@@ -0,0 +1,134 @@
+//! At-least-once delivery of job messages to builders.
+//!
+//! Every message that must be acknowledged is tracked per connection until the
+//! builder answers with `EjWsClientMessage::Ack`. Messages left unacknowledged
+//! for `WS_ACK_TIMEOUT` are retransmitted with the same ID, and the builder is
+//! considered lost once `WS_MAX_DELIVERY_ATTEMPTS` have gone unanswered.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use ej_dispatcher_sdk::ejws_message::{EjWsServerEnvelope, EjWsServerMessage};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+/// How long a builder has to acknowledge a message before it's retransmitted.
+pub const WS_ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of times a message is sent before giving up on the builder.
+pub const WS_MAX_DELIVERY_ATTEMPTS: u32 = 3;
+
+/// Message waiting for its acknowledgment.
+struct PendingDelivery {
+    envelope: EjWsServerEnvelope,
+    attempts: u32,
+    sent_at: Instant,
+}
+
+/// Messages sent on a connection that haven't been acknowledged yet.
+#[derive(Default)]
+pub struct PendingDeliveries {
+    pending: HashMap<Uuid, PendingDelivery>,
+}
+
+impl PendingDeliveries {
+    /// Wraps a message about to be sent, tracking it if it must be acknowledged.
+    ///
+    /// # Arguments
+    /// * `message` - The message to send
+    /// * `now` - When the message is sent
+    pub fn track(&mut self, message: EjWsServerMessage, now: Instant) -> EjWsServerEnvelope {
+        let envelope = EjWsServerEnvelope::new(message);
+        if let Some(id) = envelope.id {
+            self.pending.insert(
+                id,
+                PendingDelivery {
+                    envelope: envelope.clone(),
+                    attempts: 1,
+                    sent_at: now,
+                },
+            );
+        }
+        envelope
+    }
+
+    /// Marks a message as delivered.
+    ///
+    /// # Returns
+    /// `false` if no message with this ID was waiting for an acknowledgment
+    pub fn ack(&mut self, id: &Uuid) -> bool {
+        self.pending.remove(id).is_some()
+    }
+
+    /// Collects the messages that must be retransmitted.
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    ///
+    /// # Returns
+    /// `Error::WsDeliveryFailed` if a message went unacknowledged too many times
+    pub fn due(&mut self, now: Instant) -> Result<Vec<EjWsServerEnvelope>> {
+        let mut due = Vec::new();
+        for (id, delivery) in self.pending.iter_mut() {
+            if now.duration_since(delivery.sent_at) < WS_ACK_TIMEOUT {
+                continue;
+            }
+            if delivery.attempts >= WS_MAX_DELIVERY_ATTEMPTS {
+                return Err(Error::WsDeliveryFailed(*id));
+            }
+            delivery.attempts += 1;
+            delivery.sent_at = now;
+            due.push(delivery.envelope.clone());
+        }
+        Ok(due)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ej_dispatcher_sdk::ejjob::EjJobCancelReason;
+
+    #[test]
+    fn test_acknowledged_messages_are_not_retransmitted() {
+        let mut deliveries = PendingDeliveries::default();
+        let now = Instant::now();
+
+        let close = deliveries.track(EjWsServerMessage::Close, now);
+        assert_eq!(close.id, None);
+
+        let cancel = deliveries.track(
+            EjWsServerMessage::Cancel(EjJobCancelReason::Timeout, Uuid::new_v4()),
+            now,
+        );
+        let id = cancel.id.unwrap();
+        assert!(deliveries.due(now).unwrap().is_empty());
+        assert!(deliveries.ack(&id));
+        assert!(!deliveries.ack(&id));
+        assert!(deliveries.due(now + WS_ACK_TIMEOUT).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unacknowledged_messages_are_retransmitted_then_fail() {
+        let mut deliveries = PendingDeliveries::default();
+        let mut now = Instant::now();
+        let cancel = deliveries.track(
+            EjWsServerMessage::Cancel(EjJobCancelReason::Timeout, Uuid::new_v4()),
+            now,
+        );
+
+        for _ in 1..WS_MAX_DELIVERY_ATTEMPTS {
+            assert!(deliveries.due(now).unwrap().is_empty());
+            now += WS_ACK_TIMEOUT;
+            assert_eq!(deliveries.due(now).unwrap(), vec![cancel.clone()]);
+        }
+
+        now += WS_ACK_TIMEOUT;
+        assert!(matches!(
+            deliveries.due(now),
+            Err(Error::WsDeliveryFailed(id)) if Some(id) == cancel.id
+        ));
+    }
+}
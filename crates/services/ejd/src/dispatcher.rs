@@ -6,40 +6,56 @@
 //! - Job queuing and distribution
 //! - Builder connection management
 //! - Job timeout and cancellation
+//! - Handling builders that stop responding mid-job
+//! - Re-adopting work from builders that reconnect
+//! - Forwarding builder logs to clients while jobs run
+//! - Streaming the logs of a job to the clients following it
+//! - Cancelling a single board config of a running job
 //! - Result collection and persistence
 //!
 //! The dispatcher runs as a background task that processes events and
 //! manages the lifecycle of jobs from submission to completion.
 
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use std::time::Duration;
 
+use crate::artifacts::ArtifactStore;
 use crate::prelude::*;
+use ej_auth::secret_hash::SecretPolicy;
+use ej_dispatcher_sdk::ejbuilder::{EjBuilderCapabilities, EjBuilderInfo};
 use ej_dispatcher_sdk::ejjob::{
-    EjBuildResult, EjDeployableJob, EjJob, EjJobCancelReason, EjJobType, EjJobUpdate, EjRunResult,
+    EjBuildResult, EjDeployableJob, EjJob, EjJobCancelReason, EjJobType, EjJobUpdate, EjLogChunk,
+    EjRunResult,
 };
 use ej_dispatcher_sdk::ejws_message::EjWsServerMessage;
+use ej_models::config::ejboard_config::EjBoardConfigDb;
 use ej_models::db::connection::DbConnection;
 use ej_models::job::ejjob::EjJobDb;
 use ej_models::job::ejjob_logs::EjJobLog;
 use ej_models::job::ejjob_results::EjJobResultDb;
 use ej_models::job::ejjob_status::EjJobStatus;
 use ej_web::ejconfig::board_config_db_to_board_config_api;
-use ej_web::ejconnected_builder::EjConnectedBuilder;
+use ej_web::ejconnected_builder::{EjConnectedBuilder, EjOverflowPolicy};
 use ej_web::ejjob::create_job;
+use ej_web::quota::DispatchQuota;
+use ej_web::revocation::EjRevocationStore;
 use ej_web::traits::job_result::EjJobResult;
 use tokio::time::sleep;
 use tokio::{
     sync::{
         Mutex,
-        mpsc::{Receiver, Sender, channel},
+        mpsc::{Receiver, Sender, channel, error::TrySendError},
     },
     task::JoinHandle,
 };
 use tracing::{debug, error, info, trace, warn};
 use uuid::Uuid;
 
+/// Log chunks buffered for a client following the logs of a job, the next
+/// ones are dropped until it catches up.
+const LOG_SUBSCRIPTION_CAPACITY: usize = 256;
+
 /// Events that can be sent to the dispatcher.
 #[derive(Debug)]
 pub enum DispatcherEvent {
@@ -51,17 +67,46 @@ pub enum DispatcherEvent {
     JobCompleted {
         job_id: Uuid,
         builder_id: Uuid,
+        skipped: Vec<Uuid>,
     },
 
     Timeout {
         job_id: Uuid,
     },
+    BuilderLost {
+        builder_id: Uuid,
+    },
+    BuilderJobState {
+        builder_id: Uuid,
+        connection_id: Uuid,
+        job_id: Option<Uuid>,
+    },
+    LogChunk {
+        builder_id: Uuid,
+        job_id: Uuid,
+        board_config_id: Uuid,
+        lines: Vec<String>,
+    },
+    CancelBoard {
+        job_id: Uuid,
+        board_config_id: Uuid,
+    },
+    SubscribeLogs {
+        job_id: Uuid,
+        tx: Sender<EjLogChunk>,
+    },
 }
 
 #[derive(Clone)]
 pub struct Dispatcher {
     pub builders: Arc<Mutex<Vec<EjConnectedBuilder>>>,
+    /// Job each builder working on the running job is executing, by builder ID.
+    pub builder_jobs: Arc<Mutex<HashMap<Uuid, Uuid>>>,
     pub connection: DbConnection,
+    pub artifacts: ArtifactStore,
+    pub revoked_tokens: EjRevocationStore,
+    pub secret_policy: SecretPolicy,
+    pub dispatch_quota: DispatchQuota,
     pub tx: Sender<DispatcherEvent>,
 }
 
@@ -77,6 +122,8 @@ struct RunningJob {
     data: EjDeployableJob,
     job_update_tx: Sender<EjJobUpdate>,
     deployed_builders: HashSet<Uuid>,
+    cancelled_boards: HashSet<Uuid>,
+    skipped_boards: HashSet<Uuid>,
 
     dispatcher_tx: Sender<DispatcherEvent>,
     timeout: Duration,
@@ -139,6 +186,8 @@ impl RunningJob {
             job_update_tx: job.tx,
             timeout: job.timeout,
             deployed_builders,
+            cancelled_boards: HashSet::new(),
+            skipped_boards: HashSet::new(),
             timeout_handle: RunningJob::create_task(tx, job_id, timeout),
             dispatcher_tx,
         }
@@ -178,12 +227,14 @@ struct DispatcherPrivate {
     dispatcher: Dispatcher,
     state: DispatcherState,
     pending_jobs: VecDeque<DispatchedJob>,
+    /// Clients following the logs of a running or pending job, by job ID.
+    log_subscribers: HashMap<Uuid, Vec<Sender<EjLogChunk>>>,
 }
 
 #[derive(Debug)]
 enum DispatcherState {
     Idle,
-    DispatchedJob { job: RunningJob },
+    DispatchedJob { job: Box<RunningJob> },
 }
 
 impl DispatcherPrivate {
@@ -191,17 +242,35 @@ impl DispatcherPrivate {
     ///
     /// # Arguments
     /// * `connection` - Database connection for job and builder management
+    /// * `artifacts` - Storage for the artifacts uploaded by builders
+    /// * `revoked_tokens` - Access tokens that are no longer accepted
+    /// * `secret_policy` - Strength requirements for client secrets
+    /// * `dispatch_quota` - Limits on the jobs a single client can dispatch
     ///
     /// # Returns
     /// A tuple containing the dispatcher interface and its background task handle
-    fn create(connection: DbConnection) -> (Dispatcher, JoinHandle<()>) {
+    fn create(
+        connection: DbConnection,
+        artifacts: ArtifactStore,
+        revoked_tokens: EjRevocationStore,
+        secret_policy: SecretPolicy,
+        dispatch_quota: DispatchQuota,
+    ) -> (Dispatcher, JoinHandle<()>) {
         let (tx, rx) = channel(32);
-        let dispatcher = Dispatcher::new(connection, tx);
+        let dispatcher = Dispatcher::new(
+            connection,
+            artifacts,
+            revoked_tokens,
+            secret_policy,
+            dispatch_quota,
+            tx,
+        );
 
         let private = Self {
             dispatcher: dispatcher.clone(),
             state: DispatcherState::Idle,
             pending_jobs: VecDeque::new(),
+            log_subscribers: HashMap::new(),
         };
         let handle = private.start_thread(rx);
         (dispatcher, handle)
@@ -213,6 +282,11 @@ impl DispatcherPrivate {
     /// - Job dispatch requests
     /// - Job completion notifications
     /// - Job timeout events
+    /// - Lost builder notifications
+    /// - Builder job state reports sent on (re)connection
+    /// - Log chunks streamed by builders
+    /// - Board config cancellation requests
+    /// - Log subscriptions
     ///
     /// # Arguments
     /// * `rx` - Receiver for dispatcher events
@@ -235,14 +309,46 @@ impl DispatcherPrivate {
                         self.handle_dispatch_job(DispatchedJob::new(job, job_update_tx, timeout))
                             .await
                     }
-                    DispatcherEvent::JobCompleted { job_id, builder_id } => {
-                        self.handle_job_completed(job_id, builder_id).await
-                    }
+                    DispatcherEvent::JobCompleted {
+                        job_id,
+                        builder_id,
+                        skipped,
+                    } => self.handle_job_completed(job_id, builder_id, skipped).await,
                     DispatcherEvent::Timeout { job_id } => self.handle_job_timeout(job_id).await,
+                    DispatcherEvent::BuilderLost { builder_id } => {
+                        self.handle_builder_lost(builder_id).await
+                    }
+                    DispatcherEvent::BuilderJobState {
+                        builder_id,
+                        connection_id,
+                        job_id,
+                    } => {
+                        self.handle_builder_job_state(builder_id, connection_id, job_id)
+                            .await
+                    }
+                    DispatcherEvent::LogChunk {
+                        builder_id,
+                        job_id,
+                        board_config_id,
+                        lines,
+                    } => {
+                        self.handle_log_chunk(builder_id, job_id, board_config_id, lines)
+                            .await
+                    }
+                    DispatcherEvent::CancelBoard {
+                        job_id,
+                        board_config_id,
+                    } => self.handle_cancel_board(job_id, board_config_id).await,
+                    DispatcherEvent::SubscribeLogs { job_id, tx } => {
+                        self.handle_subscribe_logs(job_id, tx);
+                        Ok(())
+                    }
                 };
                 if let Err(err) = result {
                     error!("Error while handling last dispatcher message - {}", err);
                 }
+                self.drop_finished_log_subscribers();
+                self.publish_builder_jobs().await;
             }
         })
     }
@@ -263,7 +369,7 @@ impl DispatcherPrivate {
         } else {
             EjWsServerMessage::Build(job)
         };
-        if let Err(err) = builder.tx.send(message).await {
+        if let Err(err) = builder.send(message, EjOverflowPolicy::Wait).await {
             error!("Failed to dispatch builder {:?} - {err}", builder);
             return false;
         }
@@ -274,7 +380,8 @@ impl DispatcherPrivate {
     ///
     /// This function:
     /// - Updates job status to running in the database
-    /// - Sends the job to all connected builders
+    /// - Sends the job to all connected builders that may have a board config the
+    ///   job targets
     /// - Tracks which builders successfully received the job
     /// - Transitions to DispatchedJob state or cancels if no builders available
     ///
@@ -289,7 +396,7 @@ impl DispatcherPrivate {
             );
         }
 
-        let builders = self.dispatcher.builders.lock().await;
+        let builders = self.dispatcher.connected_builders().await;
         info!(
             "Dispatching job {} to {} builders",
             job.data.id,
@@ -298,6 +405,17 @@ impl DispatcherPrivate {
 
         let mut dispatched_builders = HashSet::new();
         for builder in builders.iter() {
+            // Builders that didn't advertise their capabilities filter the job themselves
+            let matches_job = builder.capabilities.as_ref().is_none_or(|capabilities| {
+                capabilities.matches_job(&job.data.board_configs, &job.data.required_tags)
+            });
+            if !matches_job {
+                debug!(
+                    "Not dispatching job {} to builder {}, none of its board configs is in {:?} with tags {:?}",
+                    job.data.id, builder.builder.id, job.data.board_configs, job.data.required_tags
+                );
+                continue;
+            }
             if DispatcherPrivate::dispatch_job_to_single_builder(job.data.clone(), &builder).await {
                 dispatched_builders.insert(builder.builder.id);
             }
@@ -311,7 +429,7 @@ impl DispatcherPrivate {
             .await;
             let jobdb = EjJobDb::fetch_by_id(&job.data.id, &self.dispatcher.connection).unwrap();
             if let Err(err) =
-                jobdb.update_status(EjJobStatus::running(), &self.dispatcher.connection)
+                jobdb.update_status(EjJobStatus::cancelled(), &self.dispatcher.connection)
             {
                 error!(
                     "Failed to update job {} status in database {err}",
@@ -327,7 +445,7 @@ impl DispatcherPrivate {
             )
             .await;
             self.state = DispatcherState::DispatchedJob {
-                job: job.start(self.dispatcher.tx.clone(), dispatched_builders),
+                job: Box::new(job.start(self.dispatcher.tx.clone(), dispatched_builders)),
             };
         }
     }
@@ -395,6 +513,14 @@ impl DispatcherPrivate {
             let config_api = board_config_db_to_board_config_api(board_config_db, connection)?;
             logs.push((config_api, logdb.log));
         }
+        let mut skipped = Vec::new();
+        for board_config_id in job.skipped_boards.iter() {
+            let board_config_db = EjBoardConfigDb::fetch_by_id(board_config_id, connection)?;
+            skipped.push(board_config_db_to_board_config_api(
+                board_config_db,
+                connection,
+            )?);
+        }
 
         if job.data.job_type == EjJobType::Build {
             DispatcherPrivate::send_job_update(
@@ -402,6 +528,7 @@ impl DispatcherPrivate {
                 EjJobUpdate::BuildFinished(EjBuildResult {
                     success: jobdb.success(),
                     logs,
+                    skipped,
                 }),
             )
             .await;
@@ -421,6 +548,7 @@ impl DispatcherPrivate {
                     logs,
                     success: jobdb.success(),
                     results,
+                    skipped,
                 }),
             )
             .await;
@@ -438,6 +566,8 @@ impl DispatcherPrivate {
     /// # Arguments
     /// * `completed_job_id` - The ID of the job that was completed
     /// * `builder_id` - The ID of the builder that completed the job
+    /// * `skipped` - The board configs the builder skipped because the job doesn't target them
+    ///   or their board is unavailable
     ///
     /// # Returns
     /// Result indicating success or failure of handling the completion
@@ -445,6 +575,7 @@ impl DispatcherPrivate {
         &mut self,
         completed_job_id: Uuid,
         builder_id: Uuid,
+        skipped: Vec<Uuid>,
     ) -> Result<()> {
         match self.state {
             /* Got a result from a builder that had probably timed out in the past. */
@@ -466,6 +597,7 @@ impl DispatcherPrivate {
                             builder_id
                         );
                     }
+                    job.skipped_boards.extend(skipped);
                     if job.deployed_builders.is_empty() {
                         info!(
                             "Job completed by all builders. # of pending jobs {}",
@@ -502,7 +634,7 @@ impl DispatcherPrivate {
                             "Builder {} has NOT been dispatched for current job {}. Dispatching him",
                             builder_id, job.data.id
                         );
-                        let connected_builders = self.dispatcher.builders.lock().await;
+                        let connected_builders = self.dispatcher.connected_builders().await;
                         match connected_builders
                             .iter()
                             .find(|b| b.builder.id == builder_id)
@@ -555,7 +687,7 @@ impl DispatcherPrivate {
         connection: &DbConnection,
         reason: EjJobCancelReason,
     ) -> Result<()> {
-        let connected_builders = builders.lock().await;
+        let connected_builders = builders.lock().await.clone();
         for connected_builder in connected_builders.iter() {
             if !job
                 .deployed_builders
@@ -564,8 +696,10 @@ impl DispatcherPrivate {
                 continue;
             }
             if let Err(err) = connected_builder
-                .tx
-                .send(EjWsServerMessage::Cancel(reason, job.data.id.clone()))
+                .send(
+                    EjWsServerMessage::Cancel(reason, job.data.id),
+                    EjOverflowPolicy::Wait,
+                )
                 .await
             {
                 error!(
@@ -655,20 +789,356 @@ impl DispatcherPrivate {
             }
         }
     }
+
+    /// Handles a builder whose WebSocket connection died while connected.
+    ///
+    /// If the builder was working on the current job, clients are notified with
+    /// a `BuilderLost` update, the job is marked as failed and the builder is
+    /// treated as done with it so the job can complete with the remaining builders.
+    ///
+    /// # Arguments
+    /// * `builder_id` - The ID of the builder that stopped responding
+    ///
+    /// # Returns
+    /// Result indicating success or failure of handling the lost builder
+    async fn handle_builder_lost(&mut self, builder_id: Uuid) -> Result<()> {
+        if self
+            .dispatcher
+            .builders
+            .lock()
+            .await
+            .iter()
+            .any(|b| b.builder.id == builder_id)
+        {
+            debug!("Builder {builder_id} lost a connection but has already reconnected");
+            return Ok(());
+        }
+        let job_id = match self.state {
+            DispatcherState::Idle => {
+                debug!("Builder {builder_id} lost but we're in idle state");
+                return Ok(());
+            }
+            DispatcherState::DispatchedJob { ref job } => {
+                if !job.deployed_builders.contains(&builder_id) {
+                    debug!(
+                        "Builder {builder_id} lost but it wasn't working on job {}",
+                        job.data.id
+                    );
+                    return Ok(());
+                }
+                warn!(
+                    "Builder {builder_id} stopped responding while working on job {}",
+                    job.data.id
+                );
+                DispatcherPrivate::send_job_update(
+                    &job.job_update_tx,
+                    EjJobUpdate::BuilderLost { builder_id },
+                )
+                .await;
+                job.data.id
+            }
+        };
+
+        let jobdb = EjJobDb::fetch_by_id(&job_id, &self.dispatcher.connection)?;
+        if let Err(err) = jobdb.update_status(EjJobStatus::failed(), &self.dispatcher.connection) {
+            error!("Failed to update job {job_id} status in database {err}");
+        }
+        self.handle_job_completed(job_id, builder_id, Vec::new())
+            .await
+    }
+
+    /// Handles the job state reported by a builder when it (re)connects.
+    ///
+    /// This lets the dispatcher pick up where it left off after a network blip:
+    /// - A builder still executing the current job is re-adopted for it
+    /// - A builder still executing an older job is left alone, it gets the current
+    ///   job once it reports the old one as completed
+    /// - A builder that was deployed for the current job but lost it is sent the
+    ///   job again on its new connection
+    /// - Any other builder is considered fresh and isn't sent anything
+    ///
+    /// # Arguments
+    /// * `builder_id` - The ID of the builder reporting its state
+    /// * `connection_id` - The connection the report was received on
+    /// * `job_id` - The job the builder is currently executing, if any
+    ///
+    /// # Returns
+    /// Result indicating success or failure of handling the report
+    async fn handle_builder_job_state(
+        &mut self,
+        builder_id: Uuid,
+        connection_id: Uuid,
+        job_id: Option<Uuid>,
+    ) -> Result<()> {
+        let job = match self.state {
+            DispatcherState::Idle => {
+                if let Some(job_id) = job_id {
+                    info!(
+                        "Builder {builder_id} is still working on job {job_id} but we're in idle state"
+                    );
+                }
+                return Ok(());
+            }
+            DispatcherState::DispatchedJob { ref mut job } => job,
+        };
+
+        match job_id {
+            Some(job_id) if job_id == job.data.id => {
+                if job.deployed_builders.insert(builder_id) {
+                    info!("Re-adopting builder {builder_id} for job {job_id}");
+                } else {
+                    info!("Builder {builder_id} resumed job {job_id}");
+                }
+            }
+            Some(job_id) => {
+                info!(
+                    "Builder {builder_id} is still working on job {job_id}. Job {} will be dispatched to it once it's done",
+                    job.data.id
+                );
+            }
+            None if job.deployed_builders.contains(&builder_id) => {
+                info!(
+                    "Builder {builder_id} lost job {} while reconnecting. Dispatching it again",
+                    job.data.id
+                );
+                let connected_builders = self.dispatcher.connected_builders().await;
+                match connected_builders
+                    .iter()
+                    .find(|b| b.connection_id == connection_id)
+                {
+                    Some(builder) => {
+                        if DispatcherPrivate::dispatch_job_to_single_builder(
+                            job.data.clone(),
+                            builder,
+                        )
+                        .await
+                        {
+                            job.renew_timeout();
+                        }
+                    }
+                    None => warn!(
+                        "Couldn't find connection {connection_id} of builder {builder_id} in the connected builder's list"
+                    ),
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Handles new output streamed by a builder while it executes a job.
+    ///
+    /// The output is appended to the job's logs in the database and forwarded to
+    /// the clients following the job. Output for a job that isn't running, or from
+    /// a builder that already reported its results, is ignored as the final results
+    /// contain the complete logs.
+    ///
+    /// # Arguments
+    /// * `builder_id` - The ID of the builder that produced the output
+    /// * `job_id` - The ID of the job the output belongs to
+    /// * `board_config_id` - The board config the output belongs to
+    /// * `lines` - The new output lines
+    ///
+    /// # Returns
+    /// Result indicating success or failure of handling the output
+    async fn handle_log_chunk(
+        &mut self,
+        builder_id: Uuid,
+        job_id: Uuid,
+        board_config_id: Uuid,
+        lines: Vec<String>,
+    ) -> Result<()> {
+        let DispatcherState::DispatchedJob { ref job } = self.state else {
+            trace!("Dropping logs for job {job_id} from builder {builder_id}, we're in idle state");
+            return Ok(());
+        };
+        if job.data.id != job_id || !job.deployed_builders.contains(&builder_id) {
+            trace!("Dropping logs for job {job_id} from builder {builder_id}");
+            return Ok(());
+        }
+
+        let connection = &self.dispatcher.connection;
+        EjJobLog::append(&job_id, &board_config_id, &lines.concat(), connection)?;
+        let board_config = EjBoardConfigDb::fetch_by_id(&board_config_id, connection)?;
+        let board_config = board_config_db_to_board_config_api(board_config, connection)?;
+        if let Some(subscribers) = self.log_subscribers.get_mut(&job_id) {
+            let chunk = EjLogChunk {
+                board_config: board_config.clone(),
+                lines: lines.clone(),
+            };
+            subscribers.retain(|tx| match tx.try_send(chunk.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    warn!("Log subscriber of job {job_id} is too slow, dropping a log chunk");
+                    true
+                }
+                Err(TrySendError::Closed(_)) => false,
+            });
+        }
+        DispatcherPrivate::send_job_update(
+            &job.job_update_tx,
+            EjJobUpdate::LogChunk {
+                board_config,
+                lines,
+            },
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Whether a job is running or waiting in the queue.
+    fn is_job_active(&self, job_id: &Uuid) -> bool {
+        let running = match &self.state {
+            DispatcherState::DispatchedJob { job } => job.data.id == *job_id,
+            DispatcherState::Idle => false,
+        };
+        running || self.pending_jobs.iter().any(|job| job.data.id == *job_id)
+    }
+
+    /// Forwards the output of a job to a client until the job finishes.
+    ///
+    /// Subscriptions to jobs that aren't running or queued end right away.
+    ///
+    /// # Arguments
+    /// * `job_id` - The job whose output is followed
+    /// * `tx` - Channel the log chunks are sent through
+    fn handle_subscribe_logs(&mut self, job_id: Uuid, tx: Sender<EjLogChunk>) {
+        if !self.is_job_active(&job_id) {
+            debug!("Ignoring log subscription to job {job_id}, it isn't running or queued");
+            return;
+        }
+        self.log_subscribers.entry(job_id).or_default().push(tx);
+    }
+
+    /// Shares which builders are working on the running job, see
+    /// [`Dispatcher::builder_jobs`].
+    async fn publish_builder_jobs(&self) {
+        let builder_jobs = match &self.state {
+            DispatcherState::DispatchedJob { job } => job
+                .deployed_builders
+                .iter()
+                .map(|builder_id| (*builder_id, job.data.id))
+                .collect(),
+            DispatcherState::Idle => HashMap::new(),
+        };
+        *self.dispatcher.builder_jobs.lock().await = builder_jobs;
+    }
+
+    /// Ends the log subscriptions of the jobs that finished.
+    fn drop_finished_log_subscribers(&mut self) {
+        let finished: Vec<Uuid> = self
+            .log_subscribers
+            .keys()
+            .filter(|job_id| !self.is_job_active(job_id))
+            .copied()
+            .collect();
+        for job_id in finished {
+            debug!("Job {job_id} finished, ending its log subscriptions");
+            self.log_subscribers.remove(&job_id);
+        }
+    }
+
+    /// Cancels a single board config of the running job.
+    ///
+    /// The cancellation is sent as a board-scoped message to the deployed builders
+    /// owning the board config, their other board configs keep going. Builders that
+    /// didn't advertise their capabilities receive it too and ignore it if the
+    /// board config isn't theirs.
+    ///
+    /// # Arguments
+    /// * `job_id` - The ID of the job the board config is working on
+    /// * `board_config_id` - The board config to cancel
+    ///
+    /// # Returns
+    /// Result indicating success or failure of the cancellation
+    async fn handle_cancel_board(&mut self, job_id: Uuid, board_config_id: Uuid) -> Result<()> {
+        let DispatcherState::DispatchedJob { ref mut job } = self.state else {
+            debug!("Ignoring cancellation of board config {board_config_id}, we're in idle state");
+            return Ok(());
+        };
+        if job.data.id != job_id {
+            debug!(
+                "Ignoring cancellation of board config {board_config_id} for job {job_id}, we're running {}",
+                job.data.id
+            );
+            return Ok(());
+        }
+        if !job.cancelled_boards.insert(board_config_id) {
+            debug!("Board config {board_config_id} of job {job_id} is already cancelled");
+            return Ok(());
+        }
+
+        info!("Cancelling board config {board_config_id} of job {job_id}");
+        let reason = EjJobCancelReason::Requested;
+        let connected_builders = self.dispatcher.connected_builders().await;
+        for connected_builder in connected_builders.iter() {
+            let owns_board = connected_builder
+                .capabilities
+                .as_ref()
+                .is_none_or(|capabilities| capabilities.has_board_config(&board_config_id));
+            if !owns_board
+                || !job
+                    .deployed_builders
+                    .contains(&connected_builder.builder.id)
+            {
+                continue;
+            }
+            let message = EjWsServerMessage::for_board(
+                board_config_id,
+                EjWsServerMessage::Cancel(reason, job_id),
+            );
+            if let Err(err) = connected_builder
+                .send(message, EjOverflowPolicy::Wait)
+                .await
+            {
+                error!(
+                    "Failed to send board cancel message to builder {} - {err}",
+                    connected_builder.builder.id
+                );
+            }
+        }
+        drop(connected_builders);
+
+        DispatcherPrivate::send_job_update(
+            &job.job_update_tx,
+            EjJobUpdate::BoardCancelled {
+                board_config_id,
+                reason,
+            },
+        )
+        .await;
+        Ok(())
+    }
 }
 impl Dispatcher {
     /// Creates a new Dispatcher instance with database connection and event channel.
     ///
     /// # Arguments
     /// * `connection` - Database connection for job and builder management
+    /// * `artifacts` - Storage for the artifacts uploaded by builders
+    /// * `revoked_tokens` - Access tokens that are no longer accepted
+    /// * `secret_policy` - Strength requirements for client secrets
+    /// * `dispatch_quota` - Limits on the jobs a single client can dispatch
     /// * `tx` - Event channel for sending dispatcher events
     ///
     /// # Returns
     /// A new Dispatcher instance
-    fn new(connection: DbConnection, tx: Sender<DispatcherEvent>) -> Self {
+    fn new(
+        connection: DbConnection,
+        artifacts: ArtifactStore,
+        revoked_tokens: EjRevocationStore,
+        secret_policy: SecretPolicy,
+        dispatch_quota: DispatchQuota,
+        tx: Sender<DispatcherEvent>,
+    ) -> Self {
         Self {
             connection,
+            artifacts,
+            revoked_tokens,
+            secret_policy,
+            dispatch_quota,
             builders: Arc::new(Mutex::new(Vec::new())),
+            builder_jobs: Arc::new(Mutex::new(HashMap::new())),
             tx,
         }
     }
@@ -679,6 +1149,10 @@ impl Dispatcher {
     ///
     /// # Arguments
     /// * `connection` - Database connection for job and builder management
+    /// * `artifacts` - Storage for the artifacts uploaded by builders
+    /// * `revoked_tokens` - Access tokens that are no longer accepted
+    /// * `secret_policy` - Strength requirements for client secrets
+    /// * `dispatch_quota` - Limits on the jobs a single client can dispatch
     ///
     /// # Returns
     /// A tuple containing:
@@ -687,12 +1161,31 @@ impl Dispatcher {
     ///
     /// # Example
     /// ```rust
-    /// let (dispatcher, task_handle) = Dispatcher::create(db_connection);
+    /// let revoked_tokens = EjRevocationStore::load(&db_connection)?;
+    /// let (dispatcher, task_handle) = Dispatcher::create(
+    ///     db_connection,
+    ///     artifact_store,
+    ///     revoked_tokens,
+    ///     SecretPolicy::default(),
+    ///     DispatchQuota::default(),
+    /// );
     /// // Use dispatcher for job management
     /// // task_handle will run the background processing
     /// ```
-    pub fn create(connection: DbConnection) -> (Self, JoinHandle<()>) {
-        DispatcherPrivate::create(connection)
+    pub fn create(
+        connection: DbConnection,
+        artifacts: ArtifactStore,
+        revoked_tokens: EjRevocationStore,
+        secret_policy: SecretPolicy,
+        dispatch_quota: DispatchQuota,
+    ) -> (Self, JoinHandle<()>) {
+        DispatcherPrivate::create(
+            connection,
+            artifacts,
+            revoked_tokens,
+            secret_policy,
+            dispatch_quota,
+        )
     }
 
     /// Dispatches a job for execution by available builders.
@@ -732,11 +1225,25 @@ impl Dispatcher {
         job: EjJob,
         job_update_tx: Sender<EjJobUpdate>,
         timeout: Duration,
+    ) -> Result<EjDeployableJob> {
+        self.dispatch_client_job(job, None, job_update_tx, timeout)
+            .await
+    }
+
+    /// Same as [`Dispatcher::dispatch_job`] for a job dispatched by the client `owner`.
+    ///
+    /// The job is counted against the owner's dispatch quota.
+    pub async fn dispatch_client_job(
+        &mut self,
+        job: EjJob,
+        owner: Option<Uuid>,
+        job_update_tx: Sender<EjJobUpdate>,
+        timeout: Duration,
     ) -> Result<EjDeployableJob> {
         if self.builders.lock().await.len() == 0 {
             return Err(Error::NoBuildersAvailable);
         }
-        let job = create_job(job, &mut self.connection)?;
+        let job = create_job(job, owner, &self.dispatch_quota, &mut self.connection)?;
 
         self.tx
             .send(DispatcherEvent::DispatchJob {
@@ -776,65 +1283,316 @@ impl Dispatcher {
     pub async fn on_job_result(&mut self, result: impl EjJobResult) -> Result<()> {
         let job_id = result.job_id();
         let builder_id = result.builder_id();
+        let skipped = result.skipped();
         result.save(&mut self.connection)?;
 
         self.tx
             .send(DispatcherEvent::JobCompleted {
                 job_id: job_id,
                 builder_id: builder_id,
+                skipped,
             })
             .await?;
 
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use diesel::prelude::*;
-    use diesel::r2d2::{ConnectionManager, Pool};
-    use ej_dispatcher_sdk::ejjob::results::{EjBuilderBuildResult, EjBuilderRunResult};
-    use ej_models::db::config::DbConfig;
-    use ej_models::db::connection::DbConnection;
-    use ej_web::ctx::ctx_client::CtxClient;
-    use ej_web::ejconnected_builder::EjConnectedBuilder;
-    use std::collections::HashMap;
-    use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
-    use std::time::Duration;
-    use tokio::sync::mpsc;
-    use tokio::time::timeout;
-    use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-    use uuid::Uuid;
+    /// Notifies the dispatcher that a builder stopped responding.
+    ///
+    /// The builder's connection must already have been removed from
+    /// [`Dispatcher::builders`]. If the builder was working on a job, the job's
+    /// clients receive an [`EjJobUpdate::BuilderLost`] update.
+    ///
+    /// # Arguments
+    /// * `builder_id` - The ID of the builder that stopped responding
+    ///
+    /// # Returns
+    /// Result indicating whether the event was delivered to the background task
+    pub async fn on_builder_lost(&self, builder_id: Uuid) -> Result<()> {
+        self.tx
+            .send(DispatcherEvent::BuilderLost { builder_id })
+            .await?;
+        Ok(())
+    }
 
-    static INIT: std::sync::Once = std::sync::Once::new();
+    /// Removes the connection of a builder whose WebSocket session ended.
+    ///
+    /// However the session ended, the dispatcher is told the builder was lost
+    /// so a job deployed on it fails right away instead of waiting for its
+    /// timeout. Builders that already reconnected keep their job.
+    ///
+    /// # Arguments
+    /// * `builder_id` - The ID of the builder whose session ended
+    /// * `connection_id` - The connection the session was running on
+    ///
+    /// # Returns
+    /// Result indicating whether the event was delivered to the background task
+    pub async fn on_builder_disconnected(
+        &self,
+        builder_id: Uuid,
+        connection_id: Uuid,
+    ) -> Result<()> {
+        self.builders
+            .lock()
+            .await
+            .retain(|b| b.connection_id != connection_id);
+        self.on_builder_lost(builder_id).await
+    }
 
-    fn setup_test_environment() {
-        INIT.call_once(|| {
-            tracing_subscriber::registry()
-                .with(tracing_subscriber::fmt::layer())
-                .init();
-        });
+    /// Forwards new output streamed by a builder while it executes a job.
+    ///
+    /// # Arguments
+    /// * `builder_id` - The ID of the builder that produced the output
+    /// * `job_id` - The ID of the job the output belongs to
+    /// * `board_config_id` - The board config the output belongs to
+    /// * `lines` - The new output lines
+    ///
+    /// # Returns
+    /// Result indicating whether the event was delivered to the background task
+    pub async fn on_log_chunk(
+        &self,
+        builder_id: Uuid,
+        job_id: Uuid,
+        board_config_id: Uuid,
+        lines: Vec<String>,
+    ) -> Result<()> {
+        self.tx
+            .send(DispatcherEvent::LogChunk {
+                builder_id,
+                job_id,
+                board_config_id,
+                lines,
+            })
+            .await?;
+        Ok(())
     }
 
-    struct DbTestContext {
-        pub connection: DbConnection,
-        base_url: String,
-        db_name: String,
+    /// Forwards the job state reported by a builder after it connected.
+    ///
+    /// # Arguments
+    /// * `builder_id` - The ID of the builder reporting its state
+    /// * `connection_id` - The connection the report was received on
+    /// * `job_id` - The job the builder is currently executing, if any
+    ///
+    /// # Returns
+    /// Result indicating whether the event was delivered to the background task
+    pub async fn on_builder_job_state(
+        &self,
+        builder_id: Uuid,
+        connection_id: Uuid,
+        job_id: Option<Uuid>,
+    ) -> Result<()> {
+        self.tx
+            .send(DispatcherEvent::BuilderJobState {
+                builder_id,
+                connection_id,
+                job_id,
+            })
+            .await?;
+        Ok(())
     }
-    impl DbTestContext {
-        pub fn create() -> Self {
-            let base_url =
-                std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL env variable missing");
 
-            let test_db_name = format!("ej_test_{}", uuid::Uuid::new_v4().simple());
+    /// Cancels a single board config of a running job.
+    ///
+    /// The request is ignored if the job isn't the one currently running.
+    ///
+    /// # Arguments
+    /// * `job_id` - The ID of the running job
+    /// * `board_config_id` - The board config to cancel
+    ///
+    /// # Returns
+    /// Result indicating whether the event was delivered to the background task
+    pub async fn cancel_board(&self, job_id: Uuid, board_config_id: Uuid) -> Result<()> {
+        self.tx
+            .send(DispatcherEvent::CancelBoard {
+                job_id,
+                board_config_id,
+            })
+            .await?;
+        Ok(())
+    }
 
-            // Connect to base database to create test database
-            let base_manager =
-                ConnectionManager::<PgConnection>::new(&format!("{}/postgres", base_url));
-            let base_pool = Pool::builder()
-                .max_size(1)
-                .build(base_manager)
+    /// Copy of the connected builders.
+    ///
+    /// Lets callers query the database about them without holding the lock
+    /// every dispatch, connection and disconnection waits for.
+    pub async fn connected_builders(&self) -> Vec<EjConnectedBuilder> {
+        self.builders.lock().await.clone()
+    }
+
+    /// Sets the job each builder is working on.
+    ///
+    /// # Arguments
+    /// * `builders` - The builders to update
+    pub async fn set_current_jobs(&self, builders: &mut [EjBuilderInfo]) {
+        let builder_jobs = self.builder_jobs.lock().await;
+        for builder in builders.iter_mut() {
+            builder.current_job = builder_jobs.get(&builder.id).copied();
+        }
+    }
+
+    /// Follows the output builders produce for a running or queued job.
+    ///
+    /// The receiver gets the log chunks of the job until it finishes, it's
+    /// closed right away if the job isn't running or queued.
+    ///
+    /// # Arguments
+    /// * `job_id` - The ID of the job to follow
+    ///
+    /// # Returns
+    /// The receiving end of the subscription
+    pub async fn subscribe_logs(&self, job_id: Uuid) -> Result<Receiver<EjLogChunk>> {
+        let (tx, rx) = channel(LOG_SUBSCRIPTION_CAPACITY);
+        self.tx
+            .send(DispatcherEvent::SubscribeLogs { job_id, tx })
+            .await?;
+        Ok(rx)
+    }
+
+    /// Stores the capabilities advertised by a builder on its connection.
+    ///
+    /// # Arguments
+    /// * `connection_id` - The connection the capabilities were received on
+    /// * `capabilities` - Boards, tags and parallel capacity of the builder
+    ///
+    /// # Returns
+    /// Whether the connection is still registered
+    pub async fn on_builder_capabilities(
+        &self,
+        connection_id: Uuid,
+        capabilities: EjBuilderCapabilities,
+    ) -> bool {
+        let mut builders = self.builders.lock().await;
+        match builders
+            .iter_mut()
+            .find(|builder| builder.connection_id == connection_id)
+        {
+            Some(builder) => {
+                builder.capabilities = Some(capabilities);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stores the boards a builder reported its jobs have exclusive access to.
+    ///
+    /// # Arguments
+    /// * `connection_id` - The connection the locked boards were received on
+    /// * `boards` - IDs of the locked boards
+    ///
+    /// # Returns
+    /// Whether the connection is still registered
+    pub async fn on_builder_board_locks(&self, connection_id: Uuid, boards: Vec<Uuid>) -> bool {
+        let mut builders = self.builders.lock().await;
+        match builders
+            .iter_mut()
+            .find(|builder| builder.connection_id == connection_id)
+        {
+            Some(builder) => {
+                builder.locked_boards = boards;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{
+        Json,
+        extract::{Path, State},
+    };
+    use chrono::{TimeDelta, Utc};
+    use diesel::prelude::*;
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use ej_auth::refresh_token::hash_refresh_token;
+    use ej_auth::secret_hash::{SecretViolation, is_secret_valid};
+    use ej_config::EjConfigBuilder;
+    use ej_config::ej_board_config::{EjBoardConfig, EjBoardConfigApi};
+    use ej_config::ej_config::EjConfig;
+    use ej_dispatcher_sdk::ejapi_key::{EjApiKeyPost, EjApiKeyScope};
+    use ej_dispatcher_sdk::ejaudit::{EjAuditAction, EjAuditQuery};
+    use ej_dispatcher_sdk::ejbuilder::EjBuilderBoardApi;
+    use ej_dispatcher_sdk::ejclient::{EjClientPost, EjClientUpdate};
+    use ej_dispatcher_sdk::ejjob::checkout::EjCheckoutOptions;
+    use ej_dispatcher_sdk::ejjob::results::{EjBuilderBuildResult, EjBuilderRunResult};
+    use ej_dispatcher_sdk::ejjob::search::{EjJobSearchQuery, EjJobSearchSource};
+    use ej_dispatcher_sdk::ejjob::timeouts::{EjJobTimeouts, EjPhaseTimeouts};
+    use ej_dispatcher_sdk::ejjob::{EjJobQuery, EjJobStatus as EjJobStatusApi};
+    use ej_dispatcher_sdk::ejpage::{EjPageQuery, EjSortOrder};
+    use ej_dispatcher_sdk::ejstats::EjStatsQuery;
+    use ej_models::auth::ejrefresh_token::EjRefreshToken;
+    use ej_models::auth::ejsession::EjSession;
+    use ej_models::builder::ejbuilder::EjBuilderCreate;
+    use ej_models::builder::ejbuilder_connection::{
+        EjBuilderConnection, EjBuilderConnectionCreate,
+    };
+    use ej_models::db::config::DbConfig;
+    use ej_models::db::connection::DbConnection;
+    use ej_models::job::ejjob_log_chunk::EjJobLogChunk;
+    use ej_models::job::ejjob_results::EjJobResultCreate;
+    use ej_web::audit::query_audit_log;
+    use ej_web::auth_token::AuthToken;
+    use ej_web::ctx::ctx_client::CtxClient;
+    use ej_web::ctx::resolver::token_ctx;
+    use ej_web::ctx::{Ctx, CtxWho};
+    use ej_web::ejapi_key::{authenticate_api_key, create_api_key, revoke_api_key};
+    use ej_web::ejartifact::is_builder_board_config;
+    use ej_web::ejbuilder::revoke_builder;
+    use ej_web::ejbuilder::{builder_info, fetch_builder, mark_builder_seen};
+    use ej_web::ejclient::{
+        create_client, delete_client, fetch_client, grant_permission, grant_role, list_clients,
+        revoke_permission, revoke_role, update_client,
+    };
+    use ej_web::ejconfig::{fetch_latest_config, save_config};
+    use ej_web::ejconnected_builder::{BUILDER_SEND_TIMEOUT, EjConnectedBuilder};
+    use ej_web::ejjob::{check_job_board_cancel, list_job_configs, query_jobs, search_jobs};
+    use ej_web::ejsession::start_session;
+    use ej_web::ejstats::fetch_stats;
+    use ej_web::ejtoken::{issue_client_refresh_token, refresh_tokens, revoke_refresh_token};
+    use ej_web::pagination::Pagination;
+    use ej_web::quota::QuotaExceeded;
+    use ej_web::revocation::{EjRevocationStore, revoke_auth_token};
+    use ej_web::traits::job_result::EjJobResult;
+    use std::collections::HashMap;
+    use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio::time::timeout;
+    use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+    use uuid::Uuid;
+
+    static INIT: std::sync::Once = std::sync::Once::new();
+
+    fn setup_test_environment() {
+        INIT.call_once(|| {
+            tracing_subscriber::registry()
+                .with(tracing_subscriber::fmt::layer())
+                .init();
+        });
+    }
+
+    struct DbTestContext {
+        pub connection: DbConnection,
+        base_url: String,
+        db_name: String,
+    }
+    impl DbTestContext {
+        pub fn create() -> Self {
+            let base_url =
+                std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL env variable missing");
+
+            let test_db_name = format!("ej_test_{}", uuid::Uuid::new_v4().simple());
+
+            // Connect to base database to create test database
+            let base_manager =
+                ConnectionManager::<PgConnection>::new(&format!("{}/postgres", base_url));
+            let base_pool = Pool::builder()
+                .max_size(1)
+                .build(base_manager)
                 .expect("Failed to connect to base database");
 
             {
@@ -843,9 +1601,7 @@ mod test {
                     .execute(&mut conn)
                     .expect("Failed to create test database");
             }
-            let config = DbConfig {
-                database_url: format!("{}/{}", base_url, test_db_name),
-            };
+            let config = DbConfig::new(format!("{}/{}", base_url, test_db_name));
             let test_connection = DbConnection::new(&config).setup();
             let db_context = DbTestContext {
                 connection: test_connection,
@@ -890,6 +1646,9 @@ mod test {
             tx,
             addr: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 11111)),
             connection_id: Uuid::new_v4(),
+            capabilities: None,
+            locked_boards: Vec::new(),
+            stats: Default::default(),
         }
     }
 
@@ -899,11 +1658,24 @@ mod test {
             commit_hash: String::from("HASH"),
             remote_url: String::from("URL"),
             remote_token: None,
+            required_tags: Vec::new(),
+            board_configs: Vec::new(),
+            timeouts: Default::default(),
+            checkout: Default::default(),
         }
     }
 
     async fn setup_dispatcher(connection: DbConnection) -> (Dispatcher, JoinHandle<()>) {
-        Dispatcher::create(connection)
+        let artifacts =
+            ArtifactStore::open(std::env::temp_dir().join("ejd-test-artifacts"), 1 << 20)
+                .expect("Failed to open artifact store");
+        Dispatcher::create(
+            connection,
+            artifacts,
+            EjRevocationStore::default(),
+            SecretPolicy::default(),
+            DispatchQuota::default(),
+        )
     }
 
     macro_rules! test {
@@ -1075,6 +1847,7 @@ mod test {
                 builder_id,
                 logs: HashMap::new(),
                 successful: true,
+                skipped: Vec::new(),
             };
 
             let completion_result = dispatcher.on_job_result(job_result).await;
@@ -1088,7 +1861,8 @@ mod test {
                 update,
                 EjJobUpdate::BuildFinished(EjBuildResult {
                     success: true,
-                    logs: Vec::new()
+                    logs: Vec::new(),
+                    skipped: Vec::new(),
                 })
             );
         })
@@ -1129,6 +1903,7 @@ mod test {
                     builder_id,
                     successful: true,
                     logs: HashMap::new(),
+                    skipped: Vec::new(),
                 };
 
                 let completion_result = dispatcher.on_job_result(job_result).await;
@@ -1147,6 +1922,7 @@ mod test {
                 builder_id: builder_ids[2],
                 logs: HashMap::new(),
                 successful: true,
+                skipped: Vec::new(),
             };
 
             let completion_result = dispatcher.on_job_result(job_result).await;
@@ -1161,7 +1937,8 @@ mod test {
                 update,
                 EjJobUpdate::BuildFinished(EjBuildResult {
                     success: true,
-                    logs: Vec::new()
+                    logs: Vec::new(),
+                    skipped: Vec::new(),
                 })
             );
         })
@@ -1211,6 +1988,7 @@ mod test {
                 builder_id,
                 successful: true,
                 logs: HashMap::new(),
+                skipped: Vec::new(),
             };
 
             let completion_result = dispatcher.on_job_result(job1_result).await;
@@ -1222,7 +2000,8 @@ mod test {
                 job1_finished,
                 EjJobUpdate::BuildFinished(EjBuildResult {
                     success: true,
-                    logs: Vec::new()
+                    logs: Vec::new(),
+                    skipped: Vec::new(),
                 })
             );
 
@@ -1244,6 +2023,7 @@ mod test {
                 builder_id,
                 successful: true,
                 logs: HashMap::new(),
+                skipped: Vec::new(),
             };
 
             let completion_result = dispatcher.on_job_result(job2_result).await;
@@ -1255,7 +2035,8 @@ mod test {
                 job2_finished,
                 EjJobUpdate::BuildFinished(EjBuildResult {
                     success: true,
-                    logs: Vec::new()
+                    logs: Vec::new(),
+                    skipped: Vec::new(),
                 })
             );
         })
@@ -1299,6 +2080,7 @@ mod test {
                 successful: true,
                 logs: HashMap::new(),
                 results: HashMap::new(),
+                skipped: Vec::new(),
             };
 
             let completion_result = dispatcher.on_job_result(job_result).await;
@@ -1315,7 +2097,8 @@ mod test {
                 EjJobUpdate::RunFinished(EjRunResult {
                     success: true,
                     logs: Vec::new(),
-                    results: Vec::new()
+                    results: Vec::new(),
+                    skipped: Vec::new(),
                 })
             );
         })
@@ -1334,6 +2117,7 @@ mod test {
                 builder_id,
                 successful: true,
                 logs: HashMap::new(),
+                skipped: Vec::new(),
             };
 
             let completion_result = dispatcher.on_job_result(job_result).await;
@@ -1392,4 +2176,2031 @@ mod test {
             );
         });
     }
+
+    #[tokio::test]
+    async fn test_builder_lost_during_job() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+
+            let builder_id = Uuid::new_v4();
+            let (builder_tx, _builder_rx) = channel(32);
+            let builder = create_builder(builder_id, builder_tx);
+            dispatcher.builders.lock().await.push(builder);
+
+            let job = create_test_job();
+            let result = dispatcher
+                .dispatch_job(job, job_update_tx, Duration::from_secs(60))
+                .await;
+            assert!(result.is_ok());
+
+            let job_update = timeout(Duration::from_millis(100), job_update_rx.recv())
+                .await
+                .expect("Should receive update")
+                .expect("Should have update");
+            assert_eq!(job_update, EjJobUpdate::JobStarted { nb_builders: 1 });
+
+            dispatcher.builders.lock().await.clear();
+            dispatcher
+                .on_builder_lost(builder_id)
+                .await
+                .expect("Should notify dispatcher");
+
+            let job_update = timeout(Duration::from_millis(100), job_update_rx.recv())
+                .await
+                .expect("Should receive update")
+                .expect("Should have update");
+            assert_eq!(job_update, EjJobUpdate::BuilderLost { builder_id });
+
+            let job_update = timeout(Duration::from_millis(100), job_update_rx.recv())
+                .await
+                .expect("Should receive update")
+                .expect("Should have update");
+            assert_eq!(
+                job_update,
+                EjJobUpdate::BuildFinished(EjBuildResult {
+                    success: false,
+                    logs: Vec::new(),
+                    skipped: Vec::new(),
+                })
+            );
+        });
+    }
+
+    #[tokio::test]
+    async fn test_builder_disconnecting_during_job_is_lost() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+
+            let builder_id = Uuid::new_v4();
+            let (builder_tx, _builder_rx) = channel(32);
+            let builder = create_builder(builder_id, builder_tx);
+            let connection_id = builder.connection_id;
+            dispatcher.builders.lock().await.push(builder);
+
+            dispatcher
+                .dispatch_job(create_test_job(), job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            let job_update = job_update_rx.recv().await.unwrap();
+            assert_eq!(job_update, EjJobUpdate::JobStarted { nb_builders: 1 });
+
+            // The builder closed its session cleanly, it won't send its results anymore
+            dispatcher
+                .on_builder_disconnected(builder_id, connection_id)
+                .await
+                .unwrap();
+            assert!(dispatcher.builders.lock().await.is_empty());
+
+            let job_update = timeout(Duration::from_millis(100), job_update_rx.recv())
+                .await
+                .expect("Should receive update")
+                .expect("Should have update");
+            assert_eq!(job_update, EjJobUpdate::BuilderLost { builder_id });
+        });
+    }
+
+    #[tokio::test]
+    async fn test_replaced_builder_session_keeps_job() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+
+            let builder_id = Uuid::new_v4();
+            let (builder_tx, _builder_rx) = channel(32);
+            let builder = create_builder(builder_id, builder_tx.clone());
+            let old_connection_id = builder.connection_id;
+            dispatcher.builders.lock().await.push(builder);
+
+            dispatcher
+                .dispatch_job(create_test_job(), job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            let job_update = job_update_rx.recv().await.unwrap();
+            assert_eq!(job_update, EjJobUpdate::JobStarted { nb_builders: 1 });
+
+            // The builder reconnected before its previous session ended
+            let reconnected = create_builder(builder_id, builder_tx);
+            dispatcher.builders.lock().await.push(reconnected);
+            dispatcher
+                .on_builder_disconnected(builder_id, old_connection_id)
+                .await
+                .unwrap();
+            assert_eq!(dispatcher.builders.lock().await.len(), 1);
+
+            let job_update = timeout(Duration::from_millis(100), job_update_rx.recv()).await;
+            assert!(job_update.is_err(), "Unexpected update {job_update:?}");
+        });
+    }
+
+    #[tokio::test]
+    async fn test_builder_reconnect_readopts_job() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+
+            let builder_ids: Vec<Uuid> = (0..2).map(|_| Uuid::new_v4()).collect();
+            let (builders_tx, _builders_rx) = channel(10);
+            for &builder_id in &builder_ids {
+                let mock_builder = create_builder(builder_id, builders_tx.clone());
+                dispatcher.builders.lock().await.push(mock_builder);
+            }
+
+            let result = dispatcher
+                .dispatch_job(create_test_job(), job_update_tx, Duration::from_secs(60))
+                .await;
+            let job = result.unwrap();
+            let update = job_update_rx
+                .recv()
+                .await
+                .expect("Should receive JobStarted");
+            assert_eq!(update, EjJobUpdate::JobStarted { nb_builders: 2 });
+
+            // First builder drops and comes back, still working on the job
+            let lost_id = builder_ids[0];
+            dispatcher
+                .builders
+                .lock()
+                .await
+                .retain(|b| b.builder.id != lost_id);
+            dispatcher.on_builder_lost(lost_id).await.unwrap();
+            let update = job_update_rx
+                .recv()
+                .await
+                .expect("Should receive BuilderLost");
+            assert_eq!(
+                update,
+                EjJobUpdate::BuilderLost {
+                    builder_id: lost_id
+                }
+            );
+
+            let reconnected = create_builder(lost_id, builders_tx.clone());
+            let connection_id = reconnected.connection_id;
+            dispatcher.builders.lock().await.push(reconnected);
+            dispatcher
+                .on_builder_job_state(lost_id, connection_id, Some(job.id))
+                .await
+                .unwrap();
+
+            // The job must wait for the re-adopted builder
+            for &builder_id in builder_ids.iter().rev() {
+                let job_result = EjBuilderBuildResult {
+                    job_id: job.id,
+                    builder_id,
+                    successful: true,
+                    logs: HashMap::new(),
+                    skipped: Vec::new(),
+                };
+                dispatcher.on_job_result(job_result).await.unwrap();
+                if builder_id != lost_id {
+                    let timeout_result =
+                        timeout(Duration::from_millis(50), job_update_rx.recv()).await;
+                    assert!(timeout_result.is_err(), "Should not finish yet");
+                }
+            }
+
+            let update = timeout(Duration::from_millis(100), job_update_rx.recv())
+                .await
+                .expect("Should receive update")
+                .expect("Should have update");
+            assert_eq!(
+                update,
+                EjJobUpdate::BuildFinished(EjBuildResult {
+                    success: true,
+                    logs: Vec::new(),
+                    skipped: Vec::new(),
+                })
+            );
+        });
+    }
+
+    #[tokio::test]
+    async fn test_builder_reconnect_without_job_gets_it_again() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+
+            let builder_id = Uuid::new_v4();
+            let (builder_tx, mut builder_rx) = channel(10);
+            let builder = create_builder(builder_id, builder_tx);
+            dispatcher.builders.lock().await.push(builder);
+
+            let job = dispatcher
+                .dispatch_job(create_test_job(), job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            let update = job_update_rx
+                .recv()
+                .await
+                .expect("Should receive JobStarted");
+            assert_eq!(update, EjJobUpdate::JobStarted { nb_builders: 1 });
+            let dispatch = timeout(Duration::from_millis(100), builder_rx.recv())
+                .await
+                .expect("Should receive dispatch")
+                .unwrap();
+            assert_eq!(dispatch, EjWsServerMessage::Build(job.clone()));
+
+            // The builder reconnects before the old connection is detected as dead
+            let (new_tx, mut new_rx) = channel(10);
+            let reconnected = create_builder(builder_id, new_tx);
+            let connection_id = reconnected.connection_id;
+            {
+                let mut builders = dispatcher.builders.lock().await;
+                builders.clear();
+                builders.push(reconnected);
+            }
+            dispatcher
+                .on_builder_job_state(builder_id, connection_id, None)
+                .await
+                .unwrap();
+
+            let dispatch = timeout(Duration::from_millis(100), new_rx.recv())
+                .await
+                .expect("Should receive dispatch")
+                .unwrap();
+            assert_eq!(dispatch, EjWsServerMessage::Build(job));
+            assert!(
+                builder_rx.recv().await.is_none(),
+                "Old connection shouldn't receive the job again"
+            );
+        });
+    }
+
+    /// Stores a builder with a single board config in the database.
+    fn create_builder_config(connection: &mut DbConnection) -> (Uuid, EjBoardConfig) {
+        let client = create_client(
+            EjClientPost {
+                name: format!("client-{}", Uuid::new_v4()),
+                secret: String::from("Correct-Horse-42"),
+            },
+            &SecretPolicy::default(),
+            connection,
+        )
+        .unwrap();
+        let builder = EjBuilderCreate::new(client.id).create(connection).unwrap();
+        let config = create_config("config");
+        let board_config = config.boards[0].configs[0].clone();
+        save_config(config, &builder.id, connection).unwrap();
+        (builder.id, board_config)
+    }
+
+    /// Builds a config with a single board config, with new IDs every time.
+    fn create_config(board_config_name: &str) -> EjConfig {
+        let config = EjConfigBuilder::new("1.0.0")
+            .board("board", "Test board")
+            .config(board_config_name)
+            .build_script("build.sh")
+            .run_script("run.sh")
+            .results_path("results")
+            .library_path("lib")
+            .build()
+            .unwrap();
+        EjConfig::from_user_config(config)
+    }
+
+    #[tokio::test]
+    async fn test_log_chunks_are_stored_and_forwarded() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+            let (builder_id, board_config) = create_builder_config(&mut dispatcher.connection);
+            let (builder_tx, _builder_rx) = channel(10);
+            let builder = create_builder(builder_id, builder_tx);
+            dispatcher.builders.lock().await.push(builder);
+
+            let job = dispatcher
+                .dispatch_job(create_test_job(), job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            let update = job_update_rx
+                .recv()
+                .await
+                .expect("Should receive JobStarted");
+            assert_eq!(update, EjJobUpdate::JobStarted { nb_builders: 1 });
+
+            for lines in [vec!["Compiling\n", "Linking\n"], vec!["Done\n"]] {
+                let lines: Vec<String> = lines.into_iter().map(String::from).collect();
+                dispatcher
+                    .on_log_chunk(builder_id, job.id, board_config.id, lines.clone())
+                    .await
+                    .unwrap();
+                let update = timeout(Duration::from_millis(100), job_update_rx.recv())
+                    .await
+                    .expect("Should receive update")
+                    .expect("Should have update");
+                assert_eq!(
+                    update,
+                    EjJobUpdate::LogChunk {
+                        board_config: EjBoardConfigApi {
+                            id: board_config.id,
+                            name: board_config.name.clone(),
+                            tags: Vec::new(),
+                        },
+                        lines,
+                    }
+                );
+            }
+
+            // Logs from a builder that isn't working on the job are dropped
+            dispatcher
+                .on_log_chunk(Uuid::new_v4(), job.id, board_config.id, vec![])
+                .await
+                .unwrap();
+            assert!(
+                timeout(Duration::from_millis(50), job_update_rx.recv())
+                    .await
+                    .is_err()
+            );
+
+            let logs = EjJobLog::fetch_by_job_id(&job.id, &dispatcher.connection).unwrap();
+            assert_eq!(logs.len(), 1);
+            assert_eq!(logs[0].log, "Compiling\nLinking\nDone\n");
+
+            let tail =
+                EjJobLogChunk::fetch_tail(&job.id, &board_config.id, 1, &dispatcher.connection)
+                    .unwrap();
+            assert_eq!(tail.len(), 1);
+            assert_eq!(tail[0].sequence, 1);
+            assert_eq!(tail[0].content, "Done\n");
+            let range = EjJobLogChunk::fetch_range(
+                &job.id,
+                &board_config.id,
+                0,
+                10,
+                &dispatcher.connection,
+            )
+            .unwrap();
+            let sequences: Vec<i32> = range.iter().map(|chunk| chunk.sequence).collect();
+            assert_eq!(sequences, vec![0, 1]);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_log_subscribers_follow_job_until_it_finishes() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (job_update_tx, _job_update_rx) = mpsc::channel(32);
+            let (builder_id, board_config) = create_builder_config(&mut dispatcher.connection);
+            let (builder_tx, _builder_rx) = channel(10);
+            let builder = create_builder(builder_id, builder_tx);
+            dispatcher.builders.lock().await.push(builder);
+
+            let job = dispatcher
+                .dispatch_job(create_test_job(), job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            let mut logs_rx = dispatcher.subscribe_logs(job.id).await.unwrap();
+
+            // Subscriptions to jobs that aren't running end right away
+            let mut other_rx = dispatcher.subscribe_logs(Uuid::new_v4()).await.unwrap();
+            assert!(other_rx.recv().await.is_none());
+
+            let lines = vec![String::from("Compiling\n")];
+            dispatcher
+                .on_log_chunk(builder_id, job.id, board_config.id, lines.clone())
+                .await
+                .unwrap();
+            let chunk = timeout(Duration::from_millis(100), logs_rx.recv())
+                .await
+                .expect("Should receive log chunk")
+                .expect("Should have log chunk");
+            assert_eq!(chunk.board_config.id, board_config.id);
+            assert_eq!(chunk.lines, lines);
+
+            let job_result = EjBuilderBuildResult {
+                job_id: job.id,
+                builder_id,
+                logs: HashMap::new(),
+                successful: true,
+                skipped: Vec::new(),
+            };
+            dispatcher.on_job_result(job_result).await.unwrap();
+            let end = timeout(Duration::from_millis(100), logs_rx.recv())
+                .await
+                .expect("Subscription should end with the job");
+            assert!(end.is_none());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_builders_report_their_current_job() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+            let (builder_id, _board_config) = create_builder_config(&mut dispatcher.connection);
+            let (builder_tx, _builder_rx) = channel(10);
+            let builder = create_builder(builder_id, builder_tx);
+            dispatcher.builders.lock().await.push(builder);
+
+            let job = dispatcher
+                .dispatch_job(create_test_job(), job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            job_update_rx
+                .recv()
+                .await
+                .expect("Should receive JobStarted");
+
+            let mut builders = {
+                let builder = fetch_builder(&builder_id, &dispatcher.connection).unwrap();
+                let connected = dispatcher.builders.lock().await;
+                vec![builder_info(builder, &connected, &dispatcher.connection).unwrap()]
+            };
+            timeout(Duration::from_millis(100), async {
+                loop {
+                    dispatcher.set_current_jobs(&mut builders).await;
+                    if builders[0].current_job.is_some() {
+                        break;
+                    }
+                    tokio::task::yield_now().await;
+                }
+            })
+            .await
+            .expect("Builder should be working on the job");
+            assert_eq!(builders[0].current_job, Some(job.id));
+
+            let job_result = EjBuilderBuildResult {
+                job_id: job.id,
+                builder_id,
+                logs: HashMap::new(),
+                successful: true,
+                skipped: Vec::new(),
+            };
+            dispatcher.on_job_result(job_result).await.unwrap();
+            timeout(Duration::from_millis(100), async {
+                loop {
+                    dispatcher.set_current_jobs(&mut builders).await;
+                    if builders[0].current_job.is_none() {
+                        break;
+                    }
+                    tokio::task::yield_now().await;
+                }
+            })
+            .await
+            .expect("Builder should be idle once the job finishes");
+        });
+    }
+
+    #[tokio::test]
+    async fn test_search_logs_and_results() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+            let (builder_id, board_config) = create_builder_config(&mut dispatcher.connection);
+            let (builder_tx, _builder_rx) = channel(10);
+            let builder = create_builder(builder_id, builder_tx);
+            dispatcher.builders.lock().await.push(builder);
+
+            let job = dispatcher
+                .dispatch_job(create_test_job(), job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            let update = job_update_rx
+                .recv()
+                .await
+                .expect("Should receive JobStarted");
+            assert_eq!(update, EjJobUpdate::JobStarted { nb_builders: 1 });
+            for line in [
+                "Compiling main.c\n",
+                "main.c:(.text+0x1a): undefined reference to `sensor_init'\n",
+            ] {
+                dispatcher
+                    .on_log_chunk(builder_id, job.id, board_config.id, vec![line.to_string()])
+                    .await
+                    .unwrap();
+                timeout(Duration::from_millis(100), job_update_rx.recv())
+                    .await
+                    .expect("Should receive update")
+                    .expect("Should have update");
+            }
+            EjJobResultCreate {
+                ejjob_id: job.id,
+                ejboard_config_id: board_config.id,
+                result: String::from("sensor_init failed with timeout"),
+            }
+            .save(&dispatcher.connection)
+            .unwrap();
+
+            let search = |q: &str| EjJobSearchQuery {
+                q: q.to_string(),
+                ..Default::default()
+            };
+
+            let hits =
+                search_jobs(search("\"undefined reference\""), &dispatcher.connection).unwrap();
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].job_id, job.id);
+            assert_eq!(hits[0].commit_hash, "HASH");
+            assert_eq!(hits[0].board_config_id, board_config.id);
+            assert_eq!(hits[0].source, EjJobSearchSource::Log);
+            assert!(hits[0].snippet.contains("**undefined** **reference**"));
+
+            let hits = search_jobs(search("sensor_init"), &dispatcher.connection).unwrap();
+            assert_eq!(hits.len(), 2);
+
+            let hits = search_jobs(
+                EjJobSearchQuery {
+                    source: Some(EjJobSearchSource::Result),
+                    ..search("sensor_init")
+                },
+                &dispatcher.connection,
+            )
+            .unwrap();
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].source, EjJobSearchSource::Result);
+
+            let hits = search_jobs(
+                EjJobSearchQuery {
+                    commit_hash: Some(String::from("OTHER")),
+                    ..search("sensor_init")
+                },
+                &dispatcher.connection,
+            )
+            .unwrap();
+            assert!(hits.is_empty());
+
+            let hits = search_jobs(search("segfault"), &dispatcher.connection).unwrap();
+            assert!(hits.is_empty());
+            assert!(matches!(
+                search_jobs(search("  "), &dispatcher.connection),
+                Err(ej_web::error::Error::EmptySearchQuery)
+            ));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_query_jobs() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let first = create_job(
+                create_test_job(),
+                None,
+                &DispatchQuota::default(),
+                &mut dispatcher.connection,
+            )
+            .unwrap();
+            let mut job = create_test_job();
+            job.job_type = EjJobType::BuildAndRun;
+            job.remote_url = String::from("OTHER_URL");
+            let second = create_job(
+                job,
+                None,
+                &DispatchQuota::default(),
+                &mut dispatcher.connection,
+            )
+            .unwrap();
+            EjJobDb::fetch_by_id(&second.id, &dispatcher.connection)
+                .unwrap()
+                .update_status(EjJobStatus::failed(), &dispatcher.connection)
+                .unwrap();
+
+            let ids = |query: EjJobQuery| -> Vec<Uuid> {
+                query_jobs(query, &dispatcher.connection)
+                    .unwrap()
+                    .items
+                    .into_iter()
+                    .map(|job| job.id)
+                    .collect()
+            };
+            assert_eq!(ids(EjJobQuery::default()), vec![first.id, second.id]);
+            let newest_first = EjPageQuery {
+                sort: Some(String::from("created_at")),
+                order: Some(EjSortOrder::Desc),
+                ..Default::default()
+            };
+            assert_eq!(
+                ids(EjJobQuery {
+                    page: newest_first.clone(),
+                    ..Default::default()
+                }),
+                vec![second.id, first.id]
+            );
+            assert_eq!(
+                ids(EjJobQuery {
+                    page: EjPageQuery {
+                        page: Some(2),
+                        per_page: Some(1),
+                        ..newest_first
+                    },
+                    ..Default::default()
+                }),
+                vec![first.id]
+            );
+            assert_eq!(
+                ids(EjJobQuery {
+                    status: Some(EjJobStatusApi::Failed),
+                    ..Default::default()
+                }),
+                vec![second.id]
+            );
+            assert_eq!(
+                ids(EjJobQuery {
+                    job_type: Some(EjJobType::Build),
+                    ..Default::default()
+                }),
+                vec![first.id]
+            );
+            assert_eq!(
+                ids(EjJobQuery {
+                    remote_url: Some(String::from("OTHER_URL")),
+                    commit_hash: Some(String::from("HASH")),
+                    ..Default::default()
+                }),
+                vec![second.id]
+            );
+            assert!(
+                ids(EjJobQuery {
+                    since: Some(Utc::now()),
+                    ..Default::default()
+                })
+                .is_empty()
+            );
+
+            let page = query_jobs(
+                EjJobQuery {
+                    page: EjPageQuery {
+                        per_page: Some(1),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                &dispatcher.connection,
+            )
+            .unwrap();
+            assert_eq!(page.total, 2);
+            let invalid_sort = EjJobQuery {
+                page: EjPageQuery {
+                    sort: Some(String::from("commit_hash")),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            assert!(query_jobs(invalid_sort, &dispatcher.connection).is_err());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_stats_count_finished_jobs() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (builder_id, board_config) = create_builder_config(&mut dispatcher.connection);
+            let (builder_tx, _builder_rx) = channel(10);
+            let builder = create_builder(builder_id, builder_tx);
+            dispatcher.builders.lock().await.push(builder);
+
+            for successful in [true, false] {
+                let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+                let job = dispatcher
+                    .dispatch_job(create_test_job(), job_update_tx, Duration::from_secs(60))
+                    .await
+                    .unwrap();
+                let update = job_update_rx
+                    .recv()
+                    .await
+                    .expect("Should receive JobStarted");
+                assert_eq!(update, EjJobUpdate::JobStarted { nb_builders: 1 });
+
+                let logs = HashMap::from([(board_config.id, vec![String::from("Done\n")])]);
+                dispatcher
+                    .on_job_result(EjBuilderBuildResult {
+                        job_id: job.id,
+                        builder_id,
+                        logs,
+                        successful,
+                        skipped: Vec::new(),
+                    })
+                    .await
+                    .unwrap();
+                timeout(Duration::from_millis(100), job_update_rx.recv())
+                    .await
+                    .expect("Should receive BuildFinished")
+                    .expect("Should have update");
+            }
+
+            let stats = fetch_stats(EjStatsQuery::default(), &dispatcher.connection).unwrap();
+            assert_eq!(stats.board_configs.len(), 1);
+            assert_eq!(stats.board_configs[0].board_config_id, board_config.id);
+            assert_eq!(stats.board_configs[0].jobs, 2);
+            assert!(stats.board_configs[0].p95_seconds >= stats.board_configs[0].p50_seconds);
+            assert_eq!(stats.commits.len(), 1);
+            assert_eq!(stats.commits[0].commit_hash, "HASH");
+            assert_eq!(stats.commits[0].jobs, 2);
+            assert_eq!(stats.commits[0].successful, 1);
+            assert_eq!(stats.commits[0].success_rate, 0.5);
+            assert_eq!(stats.builders.len(), 1);
+            assert_eq!(stats.builders[0].builder_id, builder_id);
+            assert_eq!(stats.builders[0].jobs, 2);
+
+            let stats = fetch_stats(
+                EjStatsQuery {
+                    commits: Some(String::from("OTHER, ANOTHER")),
+                    ..Default::default()
+                },
+                &dispatcher.connection,
+            )
+            .unwrap();
+            assert!(stats.board_configs.is_empty());
+            assert!(stats.commits.is_empty());
+            assert!(stats.builders.is_empty());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_failed_result_save_is_rolled_back() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (builder_id, board_config) = create_builder_config(&mut dispatcher.connection);
+            let (builder_tx, _builder_rx) = channel(10);
+            let builder = create_builder(builder_id, builder_tx);
+            dispatcher.builders.lock().await.push(builder);
+
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+            let mut job = create_test_job();
+            job.job_type = EjJobType::BuildAndRun;
+            let job = dispatcher
+                .dispatch_job(job, job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            let update = job_update_rx
+                .recv()
+                .await
+                .expect("Should receive JobStarted");
+            assert_eq!(update, EjJobUpdate::JobStarted { nb_builders: 1 });
+
+            // The result of an unknown board config can't be stored, which must
+            // undo the status update and the logs saved before it
+            let run_result = EjBuilderRunResult {
+                job_id: job.id,
+                builder_id,
+                successful: true,
+                logs: HashMap::from([(board_config.id, vec![String::from("Done\n")])]),
+                results: HashMap::from([(Uuid::new_v4(), String::from("PASS"))]),
+                skipped: Vec::new(),
+            };
+            assert!(run_result.save(&dispatcher.connection).is_err());
+
+            let jobdb = EjJobDb::fetch_by_id(&job.id, &dispatcher.connection).unwrap();
+            assert_eq!(jobdb.status, EjJobStatus::running());
+            assert!(jobdb.finished_at.is_none());
+            let logs = EjJobLog::fetch_by_job_id(&job.id, &dispatcher.connection).unwrap();
+            assert!(logs.is_empty());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_config_versions() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (builder_id, board_config) = create_builder_config(&mut dispatcher.connection);
+            let latest_config_id = |dispatcher: &Dispatcher| {
+                let builder = fetch_builder(&builder_id, &dispatcher.connection).unwrap();
+                builder_info(builder, &[], &dispatcher.connection)
+                    .unwrap()
+                    .config
+                    .unwrap()
+                    .id
+            };
+            let first_version = latest_config_id(&dispatcher);
+
+            // Uploading the same config again reuses the stored version and its IDs
+            let config = save_config(
+                create_config("config"),
+                &builder_id,
+                &mut dispatcher.connection,
+            )
+            .unwrap();
+            assert_eq!(config.boards[0].configs[0].id, board_config.id);
+            assert_eq!(latest_config_id(&dispatcher), first_version);
+
+            let changed = save_config(
+                create_config("changed"),
+                &builder_id,
+                &mut dispatcher.connection,
+            )
+            .unwrap();
+            assert_ne!(changed.boards[0].configs[0].id, board_config.id);
+            assert_ne!(latest_config_id(&dispatcher), first_version);
+
+            save_config(
+                create_config("config"),
+                &builder_id,
+                &mut dispatcher.connection,
+            )
+            .unwrap();
+            assert_eq!(latest_config_id(&dispatcher), first_version);
+
+            let (builder_tx, _builder_rx) = channel(10);
+            let builder = create_builder(builder_id, builder_tx);
+            dispatcher.builders.lock().await.push(builder);
+            let (job_update_tx, _job_update_rx) = mpsc::channel(32);
+            let mut job = create_test_job();
+            job.job_type = EjJobType::BuildAndRun;
+            let job = dispatcher
+                .dispatch_job(job, job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            EjBuilderRunResult {
+                job_id: job.id,
+                builder_id,
+                successful: true,
+                logs: HashMap::from([(board_config.id, vec![String::from("Done\n")])]),
+                results: HashMap::from([(board_config.id, String::from("PASS"))]),
+                skipped: Vec::new(),
+            }
+            .save(&dispatcher.connection)
+            .unwrap();
+
+            let configs = list_job_configs(&job.id, &dispatcher.connection).unwrap();
+            assert_eq!(configs.len(), 1);
+            assert_eq!(configs[0].id, first_version);
+            assert_eq!(configs[0].builder_id, builder_id);
+            assert_eq!(configs[0].boards[0].configs[0].id, board_config.id);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_fetch_latest_config_diff() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (builder_id, _) = create_builder_config(&mut dispatcher.connection);
+            let uploaded = fetch_latest_config(&builder_id, &dispatcher.connection)
+                .unwrap()
+                .unwrap();
+
+            assert!(uploaded.diff(&create_config("config")).is_empty());
+            let diff = uploaded.diff(&create_config("changed"));
+            assert_eq!(diff.changed_boards[0].added_configs, vec!["changed"]);
+            assert_eq!(diff.changed_boards[0].removed_configs, vec!["config"]);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_builder_connection_history() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (builder_id, _board_config) = create_builder_config(&mut dispatcher.connection);
+            let builder = fetch_builder(&builder_id, &dispatcher.connection).unwrap();
+            let info = builder_info(builder, &[], &dispatcher.connection).unwrap();
+            assert!(info.last_seen_at.is_none());
+            assert!(info.is_stale(Utc::now(), Duration::from_secs(300)));
+
+            let connection_id = Uuid::new_v4();
+            EjBuilderConnectionCreate {
+                id: connection_id,
+                ejbuilder_id: builder_id,
+                address: String::from("127.0.0.1:4242"),
+            }
+            .create(&dispatcher.connection)
+            .unwrap();
+            mark_builder_seen(&connection_id, Duration::ZERO, &dispatcher.connection).unwrap();
+            EjBuilderConnection::close(&connection_id, &dispatcher.connection).unwrap();
+
+            // A connection left open by a dispatcher that stopped is closed on startup
+            let dangling_id = Uuid::new_v4();
+            EjBuilderConnectionCreate {
+                id: dangling_id,
+                ejbuilder_id: builder_id,
+                address: String::from("127.0.0.1:4343"),
+            }
+            .create(&dispatcher.connection)
+            .unwrap();
+            assert_eq!(
+                EjBuilderConnection::close_dangling(&dispatcher.connection).unwrap(),
+                1
+            );
+
+            let builder = fetch_builder(&builder_id, &dispatcher.connection).unwrap();
+            let info = builder_info(builder, &[], &dispatcher.connection).unwrap();
+            let last_seen_at = info.last_seen_at.expect("Builder should have been seen");
+            assert!(!info.is_stale(Utc::now(), Duration::from_secs(300)));
+            assert!(info.is_stale(
+                last_seen_at + TimeDelta::seconds(301),
+                Duration::from_secs(300)
+            ));
+            assert_eq!(info.connection_history.len(), 2);
+            assert_eq!(info.connection_history[0].address, "127.0.0.1:4343");
+            assert_eq!(
+                info.connection_history[0].disconnected_at,
+                Some(info.connection_history[0].last_seen_at)
+            );
+            assert_eq!(info.connection_history[1].address, "127.0.0.1:4242");
+            assert!(info.connection_history[1].disconnected_at.is_some());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_builder_capabilities_are_stored_on_connection() {
+        test!(|dispatcher: Dispatcher, _handle| async move {
+            let (builder_tx, _builder_rx) = channel(10);
+            let builder = create_builder(Uuid::new_v4(), builder_tx);
+            let connection_id = builder.connection_id;
+            dispatcher.builders.lock().await.push(builder);
+
+            let capabilities = EjBuilderCapabilities {
+                boards: Vec::new(),
+                tags: vec![String::from("arm64")],
+                parallel_capacity: 2,
+            };
+            assert!(
+                dispatcher
+                    .on_builder_capabilities(connection_id, capabilities.clone())
+                    .await
+            );
+            assert_eq!(
+                dispatcher.builders.lock().await[0].capabilities,
+                Some(capabilities.clone())
+            );
+
+            // Capabilities of a connection that's gone are ignored
+            assert!(
+                !dispatcher
+                    .on_builder_capabilities(Uuid::new_v4(), capabilities)
+                    .await
+            );
+        });
+    }
+
+    #[tokio::test]
+    async fn test_builder_board_locks_are_stored_on_connection() {
+        test!(|dispatcher: Dispatcher, _handle| async move {
+            let (builder_tx, _builder_rx) = channel(10);
+            let builder = create_builder(Uuid::new_v4(), builder_tx);
+            let connection_id = builder.connection_id;
+            dispatcher.builders.lock().await.push(builder);
+
+            let board_id = Uuid::new_v4();
+            assert!(
+                dispatcher
+                    .on_builder_board_locks(connection_id, vec![board_id])
+                    .await
+            );
+            assert_eq!(
+                dispatcher.builders.lock().await[0].locked_boards,
+                vec![board_id]
+            );
+
+            // Released boards replace the previous report
+            assert!(
+                dispatcher
+                    .on_builder_board_locks(connection_id, Vec::new())
+                    .await
+            );
+            assert!(dispatcher.builders.lock().await[0].locked_boards.is_empty());
+
+            // Locks reported on a connection that's gone are ignored
+            assert!(
+                !dispatcher
+                    .on_builder_board_locks(Uuid::new_v4(), vec![board_id])
+                    .await
+            );
+        });
+    }
+
+    #[tokio::test]
+    async fn test_cancel_board_only_reaches_owning_builder() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+            let board_config_id = Uuid::new_v4();
+            let capabilities = |board_config_id| EjBuilderCapabilities {
+                boards: vec![EjBuilderBoardApi {
+                    id: Uuid::new_v4(),
+                    name: String::from("rpi4"),
+                    description: String::new(),
+                    configs: vec![EjBoardConfigApi {
+                        id: board_config_id,
+                        name: String::from("wayland"),
+                        tags: Vec::new(),
+                    }],
+                }],
+                tags: Vec::new(),
+                parallel_capacity: 1,
+            };
+
+            let (owner_tx, mut owner_rx) = channel(32);
+            let mut owner = create_builder(Uuid::new_v4(), owner_tx);
+            owner.capabilities = Some(capabilities(board_config_id));
+            let (other_tx, mut other_rx) = channel(32);
+            let mut other = create_builder(Uuid::new_v4(), other_tx);
+            other.capabilities = Some(capabilities(Uuid::new_v4()));
+            dispatcher.builders.lock().await.push(owner);
+            dispatcher.builders.lock().await.push(other);
+
+            let job = dispatcher
+                .dispatch_job(create_test_job(), job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            owner_rx.recv().await.unwrap();
+            other_rx.recv().await.unwrap();
+            job_update_rx.recv().await.unwrap();
+
+            dispatcher
+                .cancel_board(job.id, board_config_id)
+                .await
+                .unwrap();
+            let message = timeout(Duration::from_millis(100), owner_rx.recv())
+                .await
+                .expect("Owner should receive the cancellation")
+                .unwrap();
+            assert_eq!(
+                message,
+                EjWsServerMessage::for_board(
+                    board_config_id,
+                    EjWsServerMessage::Cancel(EjJobCancelReason::Requested, job.id)
+                )
+            );
+            let update = timeout(Duration::from_millis(100), job_update_rx.recv())
+                .await
+                .expect("Should receive update")
+                .unwrap();
+            assert_eq!(
+                update,
+                EjJobUpdate::BoardCancelled {
+                    board_config_id,
+                    reason: EjJobCancelReason::Requested,
+                }
+            );
+            assert!(other_rx.try_recv().is_err());
+
+            // Cancelling the same board config twice is a no-op
+            dispatcher
+                .cancel_board(job.id, board_config_id)
+                .await
+                .unwrap();
+            assert!(
+                timeout(Duration::from_millis(100), owner_rx.recv())
+                    .await
+                    .is_err()
+            );
+        });
+    }
+
+    #[tokio::test]
+    async fn test_job_required_tags() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (builder_id, board_config) = create_builder_config(&mut dispatcher.connection);
+            let (builder_tx, mut builder_rx) = channel(10);
+            dispatcher
+                .builders
+                .lock()
+                .await
+                .push(create_builder(builder_id, builder_tx));
+
+            let (other_tx, mut other_rx) = channel(10);
+            let mut other = create_builder(Uuid::new_v4(), other_tx);
+            other.capabilities = Some(EjBuilderCapabilities {
+                boards: vec![EjBuilderBoardApi {
+                    id: Uuid::new_v4(),
+                    name: String::from("x86"),
+                    description: String::new(),
+                    configs: vec![EjBoardConfigApi {
+                        id: Uuid::new_v4(),
+                        name: String::from("sdl"),
+                        tags: vec![String::from("x86_64")],
+                    }],
+                }],
+                tags: vec![String::from("x86_64")],
+                parallel_capacity: 1,
+            });
+            dispatcher.builders.lock().await.push(other);
+
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+            let job = EjJob {
+                required_tags: vec![String::from("arm64")],
+                ..create_test_job()
+            };
+            let job = dispatcher
+                .dispatch_job(job, job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(job.required_tags, vec![String::from("arm64")]);
+            let update = job_update_rx.recv().await.unwrap();
+            assert_eq!(update, EjJobUpdate::JobStarted { nb_builders: 1 });
+            builder_rx.recv().await.unwrap();
+            assert!(other_rx.try_recv().is_err());
+
+            // Builders without advertised capabilities filter the job themselves
+            dispatcher
+                .on_job_result(EjBuilderBuildResult {
+                    job_id: job.id,
+                    builder_id,
+                    logs: HashMap::new(),
+                    skipped: vec![board_config.id],
+                    successful: true,
+                })
+                .await
+                .unwrap();
+            let update = timeout(Duration::from_millis(100), job_update_rx.recv())
+                .await
+                .expect("Should receive BuildFinished")
+                .unwrap();
+            let EjJobUpdate::BuildFinished(result) = update else {
+                panic!("Expected BuildFinished, got {update:?}");
+            };
+            assert!(result.success);
+            assert_eq!(result.skipped.len(), 1);
+            assert_eq!(result.skipped[0].id, board_config.id);
+            assert_eq!(result.skipped[0].name, board_config.name);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_job_board_configs() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let capabilities = |name: &str| EjBuilderCapabilities {
+                boards: vec![EjBuilderBoardApi {
+                    id: Uuid::new_v4(),
+                    name: String::from("x86"),
+                    description: String::new(),
+                    configs: vec![EjBoardConfigApi {
+                        id: Uuid::new_v4(),
+                        name: String::from(name),
+                        tags: Vec::new(),
+                    }],
+                }],
+                tags: Vec::new(),
+                parallel_capacity: 1,
+            };
+            let (builder_tx, mut builder_rx) = channel(10);
+            let mut builder = create_builder(Uuid::new_v4(), builder_tx);
+            builder.capabilities = Some(capabilities("sdl"));
+            dispatcher.builders.lock().await.push(builder);
+
+            let (other_tx, mut other_rx) = channel(10);
+            let mut other = create_builder(Uuid::new_v4(), other_tx);
+            other.capabilities = Some(capabilities("wayland"));
+            dispatcher.builders.lock().await.push(other);
+
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+            let job = EjJob {
+                board_configs: vec![String::from("sdl")],
+                ..create_test_job()
+            };
+            let job = dispatcher
+                .dispatch_job(job, job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(job.board_configs, vec![String::from("sdl")]);
+            let update = job_update_rx.recv().await.unwrap();
+            assert_eq!(update, EjJobUpdate::JobStarted { nb_builders: 1 });
+            let EjWsServerMessage::Build(dispatched) = builder_rx.recv().await.unwrap() else {
+                panic!("Expected the job to be dispatched to the builder");
+            };
+            assert_eq!(dispatched.board_configs, vec![String::from("sdl")]);
+            assert!(other_rx.try_recv().is_err());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_job_timeouts_reach_builders() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (builder_tx, mut builder_rx) = channel(10);
+            dispatcher
+                .builders
+                .lock()
+                .await
+                .push(create_builder(Uuid::new_v4(), builder_tx));
+
+            let (job_update_tx, _job_update_rx) = mpsc::channel(32);
+            let timeouts = EjJobTimeouts::default()
+                .with_build(Duration::from_secs(120))
+                .with_board(
+                    "sdl",
+                    EjPhaseTimeouts {
+                        build: Some(Duration::from_secs(30)),
+                        ..Default::default()
+                    },
+                );
+            let job = create_test_job().with_timeouts(timeouts.clone());
+            dispatcher
+                .dispatch_job(job, job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+
+            let EjWsServerMessage::Build(dispatched) = builder_rx.recv().await.unwrap() else {
+                panic!("Expected the job to be dispatched to the builder");
+            };
+            assert_eq!(dispatched.timeouts, timeouts);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_job_checkout_options_reach_builders() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (builder_tx, mut builder_rx) = channel(10);
+            dispatcher
+                .builders
+                .lock()
+                .await
+                .push(create_builder(Uuid::new_v4(), builder_tx));
+
+            let (job_update_tx, _job_update_rx) = mpsc::channel(32);
+            let checkout = EjCheckoutOptions::default()
+                .with_depth(1)
+                .with_sparse_paths(vec![String::from("/src/")]);
+            let job = create_test_job().with_checkout(checkout.clone());
+            dispatcher
+                .dispatch_job(job, job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+
+            let EjWsServerMessage::Build(dispatched) = builder_rx.recv().await.unwrap() else {
+                panic!("Expected the job to be dispatched to the builder");
+            };
+            assert_eq!(dispatched.checkout, checkout);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_stalled_builder_is_skipped_after_send_timeout() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+
+            // This builder never drains its queue
+            let (stalled_tx, _stalled_rx) = channel(1);
+            stalled_tx.try_send(EjWsServerMessage::Close).unwrap();
+            let stalled = create_builder(Uuid::new_v4(), stalled_tx);
+            let stats = Arc::clone(&stalled.stats);
+            dispatcher.builders.lock().await.push(stalled);
+
+            let (builder_tx, mut builder_rx) = channel(32);
+            dispatcher
+                .builders
+                .lock()
+                .await
+                .push(create_builder(Uuid::new_v4(), builder_tx));
+
+            let job = dispatcher
+                .dispatch_job(create_test_job(), job_update_tx, Duration::from_secs(60))
+                .await
+                .unwrap();
+
+            let update = timeout(
+                BUILDER_SEND_TIMEOUT + Duration::from_secs(1),
+                job_update_rx.recv(),
+            )
+            .await
+            .expect("Should receive update")
+            .expect("Should have update");
+            assert_eq!(update, EjJobUpdate::JobStarted { nb_builders: 1 });
+            assert_eq!(
+                builder_rx.recv().await.unwrap(),
+                EjWsServerMessage::Build(job)
+            );
+            assert_eq!(stats.slow(), 1);
+            assert_eq!(stats.dropped(), 1);
+        });
+    }
+
+    /// Creates a client dispatch quotas are counted against.
+    fn create_quota_client(connection: &mut DbConnection) -> Uuid {
+        create_client(
+            EjClientPost {
+                name: format!("client-{}", Uuid::new_v4()),
+                secret: String::from("Correct-Horse-42"),
+            },
+            &SecretPolicy::default(),
+            connection,
+        )
+        .unwrap()
+        .id
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_refused_over_queued_jobs_quota() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let client_id = create_quota_client(&mut dispatcher.connection);
+            let quota = DispatchQuota {
+                max_queued_jobs: Some(2),
+                max_jobs_per_hour: None,
+            };
+            let mut connection = dispatcher.connection.clone();
+            let mut dispatch =
+                || create_job(create_test_job(), Some(client_id), &quota, &mut connection);
+
+            let first = dispatch().unwrap();
+            dispatch().unwrap();
+            assert!(matches!(
+                dispatch(),
+                Err(ej_web::error::Error::QuotaExceeded(
+                    QuotaExceeded::QueuedJobs(2)
+                ))
+            ));
+
+            // Finished jobs don't count
+            EjJobDb::fetch_by_id(&first.id, &dispatcher.connection)
+                .unwrap()
+                .update_status(EjJobStatus::success(), &dispatcher.connection)
+                .unwrap();
+            dispatch().unwrap();
+
+            // Other clients have their own quota, jobs without owner aren't limited
+            let other_client_id = create_quota_client(&mut dispatcher.connection);
+            create_job(
+                create_test_job(),
+                Some(other_client_id),
+                &quota,
+                &mut dispatcher.connection,
+            )
+            .unwrap();
+            create_job(create_test_job(), None, &quota, &mut dispatcher.connection).unwrap();
+        });
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_refused_over_jobs_per_hour_quota() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let client_id = create_quota_client(&mut dispatcher.connection);
+            let quota = DispatchQuota {
+                max_queued_jobs: None,
+                max_jobs_per_hour: Some(2),
+            };
+            for _ in 0..2 {
+                let job = create_job(
+                    create_test_job(),
+                    Some(client_id),
+                    &quota,
+                    &mut dispatcher.connection,
+                )
+                .unwrap();
+                // Finished jobs still count against the hourly quota
+                EjJobDb::fetch_by_id(&job.id, &dispatcher.connection)
+                    .unwrap()
+                    .update_status(EjJobStatus::success(), &dispatcher.connection)
+                    .unwrap();
+            }
+            assert!(matches!(
+                create_job(
+                    create_test_job(),
+                    Some(client_id),
+                    &quota,
+                    &mut dispatcher.connection,
+                ),
+                Err(ej_web::error::Error::QuotaExceeded(
+                    QuotaExceeded::JobsPerHour(2)
+                ))
+            ));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_dispatches_dont_exceed_quota() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let client_id = create_quota_client(&mut dispatcher.connection);
+            let quota = DispatchQuota {
+                max_queued_jobs: Some(2),
+                max_jobs_per_hour: None,
+            };
+            let dispatched = std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..6)
+                    .map(|_| {
+                        let mut connection = dispatcher.connection.clone();
+                        scope.spawn(move || {
+                            create_job(create_test_job(), Some(client_id), &quota, &mut connection)
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap())
+                    .filter(|result| result.is_ok())
+                    .count()
+            });
+            assert_eq!(dispatched, 2);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_only_rotated_refresh_tokens_count_as_reused() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let client_id = create_quota_client(&mut dispatcher.connection);
+            let connection = &dispatcher.connection;
+            let store = EjRevocationStore::default();
+            let session = start_session(&client_id, connection).unwrap();
+            let is_session_revoked = || {
+                EjSession::fetch_by_id(&session.id, connection)
+                    .unwrap()
+                    .is_revoked()
+            };
+
+            // Revoked on purpose, e.g. on logout
+            let revoked = issue_client_refresh_token(&session, connection).unwrap();
+            revoke_refresh_token(&revoked, connection).unwrap();
+            assert!(matches!(
+                refresh_tokens(&revoked, &store, connection),
+                Err(ej_web::error::Error::RefreshTokenInvalid)
+            ));
+            assert!(!is_session_revoked());
+            assert!(store.is_empty());
+
+            // Exchanged for a new token, without signing its access token
+            let rotated = issue_client_refresh_token(&session, connection).unwrap();
+            EjRefreshToken::rotate(&hash_refresh_token(&rotated), connection)
+                .unwrap()
+                .unwrap();
+            let next = issue_client_refresh_token(&session, connection).unwrap();
+            assert!(matches!(
+                refresh_tokens(&rotated, &store, connection),
+                Err(ej_web::error::Error::RefreshTokenInvalid)
+            ));
+            assert!(is_session_revoked());
+            assert!(store.is_revoked(&session.id));
+            assert!(refresh_tokens(&next, &store, connection).is_err());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_artifacts_only_accepted_for_own_board_configs() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (builder_id, board_config) = create_builder_config(&mut dispatcher.connection);
+            let (other_id, other_config) = create_builder_config(&mut dispatcher.connection);
+            let connection = &dispatcher.connection;
+
+            assert!(is_builder_board_config(&builder_id, &board_config.id, connection).unwrap());
+            assert!(is_builder_board_config(&other_id, &other_config.id, connection).unwrap());
+            assert!(!is_builder_board_config(&builder_id, &other_config.id, connection).unwrap());
+            assert!(!is_builder_board_config(&builder_id, &Uuid::new_v4(), connection).unwrap());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_only_owners_and_managers_cancel_job_boards() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let owner_id = create_quota_client(&mut dispatcher.connection);
+            let other_id = create_quota_client(&mut dispatcher.connection);
+            let (_, board_config) = create_builder_config(&mut dispatcher.connection);
+            let job = create_job(
+                create_test_job(),
+                Some(owner_id),
+                &DispatchQuota::default(),
+                &mut dispatcher.connection,
+            )
+            .unwrap();
+            let connection = &dispatcher.connection;
+            let ctx = |id, permissions: &[&str]| {
+                let permissions = permissions.iter().map(|p| p.to_string()).collect();
+                Ctx::new(id, CtxWho::Client, permissions)
+            };
+            let owner = ctx(owner_id, &["client.dispatch"]);
+            let other = ctx(other_id, &["client.dispatch"]);
+            let manager = ctx(other_id, &["client.dispatch", "client.manage"]);
+
+            assert!(check_job_board_cancel(&owner, &job.id, &board_config.id, connection).is_ok());
+            assert!(
+                check_job_board_cancel(&manager, &job.id, &board_config.id, connection).is_ok()
+            );
+            assert!(matches!(
+                check_job_board_cancel(&other, &job.id, &board_config.id, connection),
+                Err(ej_web::error::Error::ApiForbidden)
+            ));
+            assert!(matches!(
+                check_job_board_cancel(&owner, &Uuid::new_v4(), &board_config.id, connection),
+                Err(ej_web::error::Error::JobNotFound)
+            ));
+            assert!(matches!(
+                check_job_board_cancel(&owner, &job.id, &Uuid::new_v4(), connection),
+                Err(ej_web::error::Error::BoardConfigNotFound)
+            ));
+        });
+    }
+
+    /// Counts the jobs of a client still queued against its quota.
+    fn queued_jobs(client_id: &Uuid, connection: &DbConnection) -> i64 {
+        connection
+            .transaction(|conn| EjJobDb::count_queued_by_client_in(client_id, conn))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_jobs_no_builder_received_are_cancelled() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let client_id = create_quota_client(&mut dispatcher.connection);
+            let (job_update_tx, mut job_update_rx) = mpsc::channel(32);
+            let (builder_tx, builder_rx) = channel(32);
+            drop(builder_rx);
+            dispatcher
+                .builders
+                .lock()
+                .await
+                .push(create_builder(Uuid::new_v4(), builder_tx));
+
+            let job = dispatcher
+                .dispatch_client_job(
+                    create_test_job(),
+                    Some(client_id),
+                    job_update_tx,
+                    Duration::from_secs(60),
+                )
+                .await
+                .unwrap();
+            let update = timeout(Duration::from_millis(100), job_update_rx.recv())
+                .await
+                .expect("Should receive update");
+            assert_eq!(
+                update,
+                Some(EjJobUpdate::JobCancelled(EjJobCancelReason::NoBuilders))
+            );
+            let jobdb = EjJobDb::fetch_by_id(&job.id, &dispatcher.connection).unwrap();
+            assert_eq!(jobdb.status, EjJobStatus::cancelled());
+
+            // Same through the REST API, which sends the job to the builders itself
+            let ctx = Ctx::new(client_id, CtxWho::Client, HashSet::new());
+            let Json(job) =
+                crate::api::dispatch_job(State(dispatcher.clone()), ctx, Json(create_test_job()))
+                    .await
+                    .unwrap();
+            let jobdb = EjJobDb::fetch_by_id(&job.id, &dispatcher.connection).unwrap();
+            assert_eq!(jobdb.status, EjJobStatus::cancelled());
+            assert_eq!(queued_jobs(&client_id, &dispatcher.connection), 0);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_rest_dispatch_refused_without_builders() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let client_id = create_quota_client(&mut dispatcher.connection);
+            let ctx = Ctx::new(client_id, CtxWho::Client, HashSet::new());
+            let result =
+                crate::api::dispatch_job(State(dispatcher.clone()), ctx, Json(create_test_job()))
+                    .await;
+            assert!(matches!(
+                result,
+                Err(ej_web::error::Error::NoBuildersAvailable)
+            ));
+            assert_eq!(queued_jobs(&client_id, &dispatcher.connection), 0);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_unfinished_jobs_are_failed_on_startup() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let client_id = create_quota_client(&mut dispatcher.connection);
+            let quota = DispatchQuota::default();
+            let dispatch = || {
+                create_job(
+                    create_test_job(),
+                    Some(client_id),
+                    &quota,
+                    &mut dispatcher.connection.clone(),
+                )
+                .unwrap()
+            };
+            let queued = dispatch();
+            let running = dispatch();
+            let finished = dispatch();
+            let connection = &dispatcher.connection;
+            EjJobDb::fetch_by_id(&running.id, connection)
+                .unwrap()
+                .update_status(EjJobStatus::running(), connection)
+                .unwrap();
+            EjJobDb::fetch_by_id(&finished.id, connection)
+                .unwrap()
+                .update_status(EjJobStatus::success(), connection)
+                .unwrap();
+            assert_eq!(queued_jobs(&client_id, connection), 2);
+
+            // Left behind by a dispatcher that stopped
+            assert_eq!(EjJobDb::fail_unfinished(connection).unwrap(), 2);
+
+            assert_eq!(queued_jobs(&client_id, connection), 0);
+            for (job, status) in [
+                (queued, EjJobStatus::failed()),
+                (running, EjJobStatus::failed()),
+                (finished, EjJobStatus::success()),
+            ] {
+                let jobdb = EjJobDb::fetch_by_id(&job.id, connection).unwrap();
+                assert_eq!(jobdb.status, status);
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_permission_changes_apply_to_issued_tokens() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let client_id = create_quota_client(&mut dispatcher.connection);
+            let connection = &dispatcher.connection;
+            // Claims issued before the changes below
+            let token = || {
+                let permissions = HashSet::from([String::from("client.manage")]);
+                AuthToken::new_client(&client_id, permissions, TimeDelta::minutes(15)).unwrap()
+            };
+            let has_permission = |permission: &str| {
+                token_ctx(token(), connection)
+                    .unwrap()
+                    .permissions
+                    .contains(permission)
+            };
+
+            assert!(!has_permission("client.manage"));
+            grant_permission(&client_id, "client.manage", connection).unwrap();
+            assert!(has_permission("client.manage"));
+            revoke_permission(&client_id, "client.manage", connection).unwrap();
+            assert!(!has_permission("client.manage"));
+
+            grant_role(&client_id, "viewer", connection).unwrap();
+            assert!(has_permission("client.results"));
+            revoke_role(&client_id, "viewer", connection).unwrap();
+            assert!(!has_permission("client.results"));
+
+            // Builders keep the permissions of their token
+            let builder_id = Uuid::new_v4();
+            let permissions = HashSet::from([String::from("builder")]);
+            let builder_token =
+                AuthToken::new_builder(&builder_id, permissions.clone(), TimeDelta::minutes(15))
+                    .unwrap();
+            let ctx = token_ctx(builder_token, connection).unwrap();
+            assert_eq!(ctx.permissions, permissions);
+
+            // Tokens of clients that were deleted are rejected
+            let permissions = HashSet::from([String::from("client.manage")]);
+            let orphan =
+                AuthToken::new_client(&Uuid::new_v4(), permissions, TimeDelta::minutes(15))
+                    .unwrap();
+            assert!(matches!(
+                token_ctx(orphan, connection),
+                Err(ej_web::error::Error::Auth(
+                    ej_auth::error::Error::InvalidToken
+                ))
+            ));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_permission_and_role_changes_are_audited() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let manager_id = create_quota_client(&mut dispatcher.connection);
+            let client_id = create_quota_client(&mut dispatcher.connection);
+            let ctx = Ctx::new(manager_id, CtxWho::Client, HashSet::new());
+            let permission = || Path((client_id, String::from("client.dispatch")));
+            let role = || Path((client_id, String::from("viewer")));
+
+            let state = || State(dispatcher.clone());
+            let Json(permissions) =
+                crate::api::grant_client_permission(state(), ctx.clone(), permission())
+                    .await
+                    .unwrap();
+            assert_eq!(permissions, vec![String::from("client.dispatch")]);
+            let Json(permissions) =
+                crate::api::revoke_client_permission(state(), ctx.clone(), permission())
+                    .await
+                    .unwrap();
+            assert!(permissions.is_empty());
+            let Json(roles) = crate::api::grant_client_role(state(), ctx.clone(), role())
+                .await
+                .unwrap();
+            assert_eq!(roles, vec![String::from("viewer")]);
+            let Json(roles) = crate::api::revoke_client_role(state(), ctx.clone(), role())
+                .await
+                .unwrap();
+            assert!(roles.is_empty());
+            // Failed changes aren't recorded
+            let unknown = Path((client_id, String::from("unknown")));
+            assert!(
+                crate::api::grant_client_role(state(), ctx.clone(), unknown)
+                    .await
+                    .is_err()
+            );
+
+            let query = EjAuditQuery {
+                actor_id: Some(manager_id),
+                ..Default::default()
+            };
+            let mut entries: Vec<_> = query_audit_log(query, &dispatcher.connection)
+                .unwrap()
+                .into_iter()
+                .map(|entry| (entry.action, entry.target.unwrap()))
+                .collect();
+            entries.sort_by_key(|(action, _)| action.as_str());
+            assert_eq!(
+                entries,
+                vec![
+                    (
+                        EjAuditAction::PermissionGranted,
+                        format!("{client_id}/client.dispatch")
+                    ),
+                    (
+                        EjAuditAction::PermissionRevoked,
+                        format!("{client_id}/client.dispatch")
+                    ),
+                    (EjAuditAction::RoleGranted, format!("{client_id}/viewer")),
+                    (EjAuditAction::RoleRevoked, format!("{client_id}/viewer")),
+                ]
+            );
+        });
+    }
+
+    #[tokio::test]
+    async fn test_clients_are_listed_and_inspected() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let first = create_quota_client(&mut dispatcher.connection);
+            let second = create_quota_client(&mut dispatcher.connection);
+
+            let page = list_clients(&Pagination::default(), &dispatcher.connection).unwrap();
+            assert_eq!(page.total, 2);
+            let mut ids: Vec<Uuid> = page.items.iter().map(|client| client.id).collect();
+            ids.sort();
+            let mut expected = vec![first, second];
+            expected.sort();
+            assert_eq!(ids, expected);
+
+            let client = fetch_client(&first, &dispatcher.connection).unwrap();
+            assert_eq!(client.id, first);
+            assert!(matches!(
+                fetch_client(&Uuid::new_v4(), &dispatcher.connection),
+                Err(ej_web::error::Error::ClientNotFound)
+            ));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_client_permissions_are_revoked() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let client_id = create_quota_client(&mut dispatcher.connection);
+            let connection = &dispatcher.connection;
+            grant_permission(&client_id, "client.dispatch", connection).unwrap();
+            grant_permission(&client_id, "client.results", connection).unwrap();
+
+            let permissions = revoke_permission(&client_id, "client.dispatch", connection).unwrap();
+            assert_eq!(permissions, vec![String::from("client.results")]);
+
+            // Revoking a permission the client doesn't have is a no-op
+            let permissions = revoke_permission(&client_id, "client.dispatch", connection).unwrap();
+            assert_eq!(permissions, vec![String::from("client.results")]);
+            assert!(matches!(
+                revoke_permission(&Uuid::new_v4(), "client.results", connection),
+                Err(ej_web::error::Error::ClientNotFound)
+            ));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_clients_are_deleted_once_their_builders_are_revoked() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let (builder_id, _) = create_builder_config(&mut dispatcher.connection);
+            let connection = &dispatcher.connection;
+            let client_id = fetch_builder(&builder_id, connection)
+                .unwrap()
+                .ejclient_id
+                .unwrap();
+
+            assert!(matches!(
+                delete_client(&client_id, connection),
+                Err(ej_web::error::Error::ClientHasBuilders)
+            ));
+
+            revoke_builder(&builder_id, connection).unwrap();
+            delete_client(&client_id, connection).unwrap();
+            assert!(matches!(
+                fetch_client(&client_id, connection),
+                Err(ej_web::error::Error::ClientNotFound)
+            ));
+
+            // The builder is kept along with its config, without an owner
+            let builder = fetch_builder(&builder_id, connection).unwrap();
+            assert!(builder.is_revoked());
+            assert_eq!(builder.ejclient_id, None);
+            assert!(
+                fetch_latest_config(&builder_id, connection)
+                    .unwrap()
+                    .is_some()
+            );
+
+            assert!(matches!(
+                delete_client(&client_id, connection),
+                Err(ej_web::error::Error::ClientNotFound)
+            ));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_revocation_store_is_loaded_from_the_database() {
+        test!(|dispatcher: Dispatcher, _handle| async move {
+            let connection = &dispatcher.connection;
+            let store = EjRevocationStore::load(connection).unwrap();
+            assert!(store.is_empty());
+
+            let revoked = Uuid::new_v4();
+            let expired = Uuid::new_v4();
+            store
+                .revoke(expired, Utc::now() - TimeDelta::minutes(1), connection)
+                .unwrap();
+            store
+                .revoke(revoked, Utc::now() + TimeDelta::minutes(15), connection)
+                .unwrap();
+
+            // Expired tokens are forgotten the next time a token is revoked
+            assert!(store.is_revoked(&revoked));
+            assert!(!store.is_revoked(&expired));
+            assert_eq!(store.len(), 1);
+
+            // The list survives restarts, without the expired tokens
+            let reloaded = EjRevocationStore::load(connection).unwrap();
+            assert!(reloaded.is_revoked(&revoked));
+            assert!(!reloaded.is_revoked(&expired));
+            assert!(!reloaded.is_revoked(&Uuid::new_v4()));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_revoking_access_tokens_requires_ownership_or_permission() {
+        test!(|dispatcher: Dispatcher, _handle| async move {
+            let connection = &dispatcher.connection;
+            let store = EjRevocationStore::load(connection).unwrap();
+            let duration = TimeDelta::minutes(15);
+            let client_id = Uuid::new_v4();
+            let client_token =
+                || AuthToken::new_client(&client_id, HashSet::new(), duration).unwrap();
+            let builder_token =
+                || AuthToken::new_builder(&Uuid::new_v4(), HashSet::new(), duration).unwrap();
+            let ctx = |id: Uuid, permissions: &[&str]| {
+                let permissions = permissions.iter().map(|p| p.to_string()).collect();
+                Ctx::new(id, CtxWho::Client, permissions)
+            };
+
+            // Anyone can revoke their own tokens
+            let own = client_token();
+            revoke_auth_token(&ctx(client_id, &[]), &own, &store, connection).unwrap();
+            assert!(store.is_revoked(&own.claims.jti));
+
+            // Someone else's client token requires client.manage
+            let other = ctx(Uuid::new_v4(), &["builder.manage"]);
+            let token = client_token();
+            assert!(matches!(
+                revoke_auth_token(&other, &token, &store, connection),
+                Err(ej_web::error::Error::ApiForbidden)
+            ));
+            assert!(!store.is_revoked(&token.claims.jti));
+            let manager = ctx(Uuid::new_v4(), &["client.manage"]);
+            revoke_auth_token(&manager, &token, &store, connection).unwrap();
+            assert!(store.is_revoked(&token.claims.jti));
+
+            // A builder token requires builder.manage
+            let token = builder_token();
+            assert!(matches!(
+                revoke_auth_token(&manager, &token, &store, connection),
+                Err(ej_web::error::Error::ApiForbidden)
+            ));
+            revoke_auth_token(&other, &token, &store, connection).unwrap();
+            assert!(store.is_revoked(&token.claims.jti));
+
+            // API keys can't revoke tokens, even their owner's
+            let api_key = Ctx::from_api_key(client_id, Uuid::new_v4(), HashSet::new());
+            let token = client_token();
+            assert!(matches!(
+                revoke_auth_token(&api_key, &token, &store, connection),
+                Err(ej_web::error::Error::ApiForbidden)
+            ));
+            assert!(!store.is_revoked(&token.claims.jti));
+        });
+    }
+
+    /// Client with the permissions of every API key scope, and its context.
+    fn create_api_key_client(connection: &mut DbConnection) -> Ctx {
+        let client_id = create_quota_client(connection);
+        let mut permissions = Vec::new();
+        for permission in ["client.dispatch", "client.results"] {
+            permissions = grant_permission(&client_id, permission, connection).unwrap();
+        }
+        Ctx::new(client_id, CtxWho::Client, permissions.into_iter().collect())
+    }
+
+    fn api_key_post(scopes: Vec<EjApiKeyScope>, expires_in_days: Option<u32>) -> EjApiKeyPost {
+        EjApiKeyPost {
+            name: String::from("ci"),
+            scopes,
+            expires_in_days,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_api_key_scopes_are_limited_to_client_permissions() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let ctx = create_api_key_client(&mut dispatcher.connection);
+            let connection = &dispatcher.connection;
+            let post = api_key_post(EjApiKeyScope::ALL.to_vec(), None);
+            let created = create_api_key(&ctx, post, connection).unwrap();
+
+            let key_ctx = authenticate_api_key(&created.key, connection).unwrap();
+            assert_eq!(key_ctx.client.id, ctx.client.id);
+            assert_eq!(key_ctx.api_key, Some(created.api_key.id));
+            let expected: HashSet<String> = ["client.dispatch", "client.results"]
+                .into_iter()
+                .map(String::from)
+                .collect();
+            assert_eq!(key_ctx.permissions, expected);
+
+            // Keys lose the scopes whose permission their owner lost
+            revoke_permission(&ctx.client.id, "client.results", connection).unwrap();
+            let key_ctx = authenticate_api_key(&created.key, connection).unwrap();
+            assert_eq!(
+                key_ctx.permissions,
+                HashSet::from([String::from("client.dispatch")])
+            );
+
+            // Clients can't grant scopes they don't have, nor create keys with a key
+            let limited = Ctx::new(
+                ctx.client.id,
+                CtxWho::Client,
+                HashSet::from([String::from("client.dispatch")]),
+            );
+            let post = api_key_post(vec![EjApiKeyScope::ReadResults], None);
+            assert!(matches!(
+                create_api_key(&limited, post, connection),
+                Err(ej_web::error::Error::ApiForbidden)
+            ));
+            let post = api_key_post(vec![EjApiKeyScope::Dispatch], None);
+            assert!(matches!(
+                create_api_key(&key_ctx, post, connection),
+                Err(ej_web::error::Error::ApiForbidden)
+            ));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_revoked_and_expired_api_keys_are_rejected() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let ctx = create_api_key_client(&mut dispatcher.connection);
+            let connection = &dispatcher.connection;
+            let auth_error = |key: &str| match authenticate_api_key(key, connection) {
+                Err(ej_web::error::Error::Auth(err)) => err,
+                other => panic!("Unexpected authentication result {other:?}"),
+            };
+
+            let post = api_key_post(vec![EjApiKeyScope::Dispatch], None);
+            let created = create_api_key(&ctx, post, connection).unwrap();
+            let (head, last) = created.key.split_at(created.key.len() - 1);
+            let forged = format!("{head}{}", if last == "0" { "1" } else { "0" });
+            assert!(matches!(
+                auth_error(&forged),
+                ej_auth::error::Error::InvalidToken
+            ));
+            assert!(matches!(
+                auth_error("not-a-key"),
+                ej_auth::error::Error::InvalidToken
+            ));
+
+            // Only the owner can revoke a key
+            let other = Ctx::new(Uuid::new_v4(), CtxWho::Client, HashSet::new());
+            assert!(matches!(
+                revoke_api_key(&other, &created.api_key.id, connection),
+                Err(ej_web::error::Error::ApiKeyNotFound)
+            ));
+            assert!(authenticate_api_key(&created.key, connection).is_ok());
+            revoke_api_key(&ctx, &created.api_key.id, connection).unwrap();
+            assert!(matches!(
+                auth_error(&created.key),
+                ej_auth::error::Error::TokenRevoked
+            ));
+
+            // A key valid for 0 days expires right away
+            let post = api_key_post(vec![EjApiKeyScope::Dispatch], Some(0));
+            let expired = create_api_key(&ctx, post, connection).unwrap();
+            assert!(matches!(
+                auth_error(&expired.key),
+                ej_auth::error::Error::TokenExpired
+            ));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_weak_client_secrets_are_rejected() {
+        test!(|mut dispatcher: Dispatcher, _handle| async move {
+            let connection = &mut dispatcher.connection;
+            let policy = SecretPolicy::default();
+            let name = format!("client-{}", Uuid::new_v4());
+
+            let result = create_client(
+                EjClientPost {
+                    name: name.clone(),
+                    secret: String::from("password"),
+                },
+                &policy,
+                connection,
+            );
+            assert!(matches!(
+                result,
+                Err(ej_web::error::Error::Auth(ej_auth::error::Error::WeakSecret(violations)))
+                    if violations == vec![
+                        SecretViolation::TooShort { min_length: 12 },
+                        SecretViolation::TooFewCharacterClasses { min_classes: 3 },
+                        SecretViolation::Common,
+                    ]
+            ));
+            let clients = list_clients(&Pagination::default(), connection).unwrap();
+            assert_eq!(clients.total, 0, "Rejected client shouldn't be stored");
+
+            let client = create_client(
+                EjClientPost {
+                    name,
+                    secret: String::from("Correct-Horse-42"),
+                },
+                &policy,
+                connection,
+            )
+            .unwrap();
+            let result = update_client(
+                &client.id,
+                EjClientUpdate {
+                    name: Some(String::from("renamed")),
+                    secret: Some(String::from("Welcome@1234")),
+                },
+                &policy,
+                connection,
+            );
+            assert!(matches!(
+                result,
+                Err(ej_web::error::Error::Auth(ej_auth::error::Error::WeakSecret(violations)))
+                    if violations == vec![SecretViolation::Common]
+            ));
+            let stored = fetch_client(&client.id, connection).unwrap();
+            assert_eq!(stored.name, client.name, "Rejected update shouldn't apply");
+            assert!(is_secret_valid("Correct-Horse-42", &stored.hash).unwrap());
+        });
+    }
 }
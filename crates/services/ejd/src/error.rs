@@ -16,7 +16,7 @@ pub enum Error {
     Json(#[from] serde_json::error::Error),
 
     #[error(transparent)]
-    DispatcherEventSendError(#[from] tokio::sync::mpsc::error::SendError<DispatcherEvent>),
+    DispatcherEventSendError(Box<tokio::sync::mpsc::error::SendError<DispatcherEvent>>),
 
     #[error(transparent)]
     Config(#[from] ej_config::error::Error),
@@ -27,11 +27,17 @@ pub enum Error {
     #[error(transparent)]
     Web(#[from] ej_web::error::Error),
 
+    #[error(transparent)]
+    Auth(#[from] ej_auth::error::Error),
+
+    #[error(transparent)]
+    DispatcherSdk(#[from] ej_dispatcher_sdk::error::Error),
+
     #[error(transparent)]
     Uuid(#[from] uuid::Error),
 
     #[error(transparent)]
-    TokioTungstenite(#[from] tokio_tungstenite::tungstenite::Error),
+    TokioTungstenite(Box<tokio_tungstenite::tungstenite::Error>),
 
     #[error("No builders available")]
     NoBuildersAvailable,
@@ -45,6 +51,70 @@ pub enum Error {
     #[error("Invalide WebSocket Message")]
     InvalidWsMessage,
 
+    #[error("WebSocket peer stopped answering heartbeats")]
+    WsHeartbeatTimeout,
+
+    #[error("Builder never acknowledged message {0}")]
+    WsDeliveryFailed(uuid::Uuid),
+
+    #[error("Artifact {0} doesn't match the size announced by the builder")]
+    ArtifactSizeMismatch(uuid::Uuid),
+
+    #[error("Received data for unknown artifact {0}")]
+    UnknownArtifact(uuid::Uuid),
+
     #[error("WebSocket Receive Error {0}")]
     Axum(#[from] axum::Error),
+
+    #[error("Invalid bind address '{0}', expected an IP address")]
+    InvalidBindAddress(String),
+
+    #[error("Invalid port '{0}'")]
+    InvalidPort(String),
+
+    #[error("Invalid socket path {0}, its parent directory doesn't exist")]
+    InvalidSocketPath(std::path::PathBuf),
+
+    #[error("Invalid socket mode '{0}', expected octal permissions such as 660")]
+    InvalidSocketMode(String),
+
+    #[error("Invalid maximum artifact size '{0}', expected a positive number of bytes")]
+    InvalidArtifactSize(String),
+
+    #[error("Artifact {0} is larger than the maximum artifact size")]
+    ArtifactTooLarge(uuid::Uuid),
+
+    #[error("Artifact {0} already exists")]
+    ArtifactExists(uuid::Uuid),
+
+    #[error("Builder isn't running job {0} with board config {1}")]
+    ArtifactNotOwned(uuid::Uuid, uuid::Uuid),
+
+    #[error("Invalid {0} '{1}'")]
+    InvalidSecretPolicy(&'static str, String),
+
+    #[error("Invalid {0} '{1}', expected a positive number of jobs")]
+    InvalidQuota(&'static str, String),
+
+    #[error("TLS certificate set but the TLS key is missing")]
+    TlsKeyMissing,
+
+    #[error("TLS key set but the TLS certificate is missing")]
+    TlsCertMissing,
+
+    #[error("TLS file {0} not found")]
+    TlsFileNotFound(std::path::PathBuf),
+}
+
+// Boxed so every `Result` of the crate doesn't carry their size
+impl From<tokio::sync::mpsc::error::SendError<DispatcherEvent>> for Error {
+    fn from(err: tokio::sync::mpsc::error::SendError<DispatcherEvent>) -> Self {
+        Self::DispatcherEventSendError(Box::new(err))
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::TokioTungstenite(Box::new(err))
+    }
 }
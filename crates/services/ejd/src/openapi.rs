@@ -0,0 +1,295 @@
+//! OpenAPI specification for the EJ Dispatcher Service REST API.
+//!
+//! The specification is generated from the `#[utoipa::path]` annotations on the
+//! API handlers and served as JSON at `/v1/openapi.json`. A Swagger UI is also
+//! served at `/v1/docs` so client authors can browse and try the API.
+
+use axum::{Json, response::Html};
+use ej_config::{
+    ej_board::{EjBoard, EjUserBoard},
+    ej_board_config::{EjBoardConfig, EjBoardConfigApi, EjUserBoardConfig},
+    ej_config::{EjConfig, EjGlobalConfig, EjUserConfig},
+};
+use ej_dispatcher_sdk::{
+    ejapi_key::{EjApiKeyApi, EjApiKeyCreated, EjApiKeyPost, EjApiKeyScope},
+    ejaudit::{EjAuditAction, EjAuditEntry},
+    ejbuilder::{
+        EjBuilderApi, EjBuilderBoardApi, EjBuilderConfigApi, EjBuilderConnectionApi, EjBuilderInfo,
+    },
+    ejclient::{
+        EjClientApi, EjClientLogin, EjClientLoginRequest, EjClientPost, EjClientUpdate, EjRoleApi,
+    },
+    ejjob::{
+        EjDeployableJob, EjJob, EjJobApi, EjJobStatus, EjJobType,
+        artifact::EjJobArtifact,
+        results::{EjBuilderBuildResult, EjBuilderRunResult},
+        search::{EjJobSearchHit, EjJobSearchSource},
+    },
+    ejpage::EjSortOrder,
+    ejsession::EjSessionApi,
+    ejstats::{EjBoardConfigStats, EjBuilderStats, EjCommitStats, EjStats},
+    ejtoken::{EjAccessTokenRevokeRequest, EjRefreshTokenRequest, EjTokens},
+};
+use utoipa::{
+    Modify, OpenApi, ToSchema,
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+use crate::api;
+
+/// Path where the OpenAPI specification is served.
+pub const OPENAPI_JSON_PATH: &str = "/v1/openapi.json";
+
+/// Path where the Swagger UI is served.
+pub const SWAGGER_UI_PATH: &str = "/v1/docs";
+
+/// Error body returned by every endpoint on failure.
+///
+/// An RFC 7807 `application/problem+json` body mirroring `ej_web::error::ApiProblem`.
+/// Only used to describe the schema, it is never constructed.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct ApiError {
+    /// URI identifying the kind of error, `urn:ej:error:<code>`.
+    #[schema(rename = "type")]
+    pub kind: String,
+    /// Human readable summary of the error.
+    pub title: String,
+    /// HTTP status code.
+    pub status: u16,
+    /// Stable machine-readable error code, e.g. `client_not_found`.
+    pub code: String,
+    /// Explanation specific to this occurrence of the error, e.g. the quota that was exceeded.
+    pub detail: Option<String>,
+    /// ID of the request, also sent in the `X-Request-Id` response header.
+    pub request_id: Option<String>,
+    /// Password policy rules broken by a rejected secret.
+    #[schema(value_type = Option<Vec<Object>>)]
+    pub violations: Option<Vec<serde_json::Value>>,
+}
+
+/// Registers the authentication schemes accepted by the API.
+///
+/// Clients can either send the JWT as a `Bearer` token in the `Authorization`
+/// header or rely on the `auth-token` cookie set by the login endpoints.
+/// Non-interactive clients can send an API key in the `X-Api-Key` header instead.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("auth-token"))),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))),
+        );
+    }
+}
+
+/// OpenAPI document describing the EJD REST API.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "EJ Dispatcher API",
+        description = "REST API used by EJ clients and builders to interact with the dispatcher."
+    ),
+    paths(
+        api::login,
+        api::login_builder_api,
+        api::refresh_token,
+        api::revoke_token,
+        api::revoke_access_token_api,
+        api::get_sessions,
+        api::delete_session,
+        api::logout_api,
+        api::get_api_keys,
+        api::post_api_key,
+        api::delete_api_key,
+        api::post_client,
+        api::get_clients,
+        api::get_client,
+        api::patch_client,
+        api::delete_client_api,
+        api::get_client_permissions,
+        api::grant_client_permission,
+        api::revoke_client_permission,
+        api::get_client_roles,
+        api::grant_client_role,
+        api::revoke_client_role,
+        api::get_permissions,
+        api::get_roles,
+        api::get_audit_log,
+        api::create_builder,
+        api::get_builders,
+        api::get_builder,
+        api::revoke_builder_api,
+        api::dispatch_job,
+        api::dispatch_ws_handler,
+        api::get_job_search,
+        api::get_job_artifacts,
+        api::get_job_configs,
+        api::get_stats,
+        api::cancel_job_board,
+        api::download_artifact,
+        api::push_config,
+        api::build_result,
+        api::run_result,
+        api::builder_handler,
+    ),
+    components(schemas(
+        ApiError,
+        EjClientApi,
+        EjClientPost,
+        EjClientUpdate,
+        EjRoleApi,
+        EjAuditAction,
+        EjAuditEntry,
+        EjSortOrder,
+        EjClientLogin,
+        EjClientLoginRequest,
+        EjRefreshTokenRequest,
+        EjAccessTokenRevokeRequest,
+        EjSessionApi,
+        EjTokens,
+        EjApiKeyScope,
+        EjApiKeyPost,
+        EjApiKeyApi,
+        EjApiKeyCreated,
+        EjBuilderApi,
+        EjBuilderInfo,
+        EjBuilderConfigApi,
+        EjBuilderBoardApi,
+        EjBuilderConnectionApi,
+        EjJob,
+        EjJobApi,
+        EjJobType,
+        EjJobStatus,
+        EjDeployableJob,
+        EjJobArtifact,
+        EjJobSearchSource,
+        EjJobSearchHit,
+        EjStats,
+        EjBoardConfigStats,
+        EjCommitStats,
+        EjBuilderStats,
+        EjBuilderBuildResult,
+        EjBuilderRunResult,
+        EjGlobalConfig,
+        EjUserConfig,
+        EjUserBoard,
+        EjUserBoardConfig,
+        EjConfig,
+        EjBoard,
+        EjBoardConfig,
+        EjBoardConfigApi,
+    )),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "auth", description = "Client and builder authentication"),
+        (name = "client", description = "Client management and job dispatching"),
+        (name = "builder", description = "Builder configuration, results and WebSocket connection"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Serves the OpenAPI specification as JSON.
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Serves a Swagger UI page pointing at the OpenAPI specification.
+///
+/// The Swagger UI assets are loaded from a CDN so ejd doesn't need to bundle them.
+pub async fn swagger_ui() -> Html<String> {
+    Html(format!(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>EJ Dispatcher API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.onload = () => {{
+      window.ui = SwaggerUIBundle({{ url: "{OPENAPI_JSON_PATH}", dom_id: "#swagger-ui" }});
+    }};
+  </script>
+</body>
+</html>"##
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_openapi_contains_all_routes() {
+        let spec = ApiDoc::openapi();
+        let paths: Vec<&String> = spec.paths.paths.keys().collect();
+        for path in [
+            "/v1/login",
+            "/v1/builder/login",
+            "/v1/token/refresh",
+            "/v1/token/revoke",
+            "/v1/client/tokens/revoke",
+            "/v1/sessions",
+            "/v1/sessions/{id}",
+            "/v1/logout",
+            "/v1/client/api_keys",
+            "/v1/client/api_keys/{id}",
+            "/v1/client",
+            "/v1/client/builder",
+            "/v1/clients",
+            "/v1/clients/{id}",
+            "/v1/clients/{id}/permissions",
+            "/v1/clients/{id}/permissions/{permission}",
+            "/v1/clients/{id}/roles",
+            "/v1/clients/{id}/roles/{role}",
+            "/v1/permissions",
+            "/v1/roles",
+            "/v1/audit",
+            "/v1/client/builders",
+            "/v1/client/builders/{id}",
+            "/v1/client/builders/{id}/revoke",
+            "/v1/client/dispatch",
+            "/v1/client/jobs/search",
+            "/v1/client/jobs/{id}/artifacts",
+            "/v1/client/jobs/{id}/configs",
+            "/v1/client/jobs/{id}/boards/{board_config_id}/cancel",
+            "/v1/client/artifacts/{id}",
+            "/v1/stats",
+            "/v1/builder/config",
+            "/v1/builder/build_result",
+            "/v1/builder/run_result",
+            "/v1/builder/ws",
+        ] {
+            assert!(
+                spec.paths.paths.contains_key(path),
+                "missing {path} in {paths:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_openapi_serializes() {
+        let json = ApiDoc::openapi().to_json().unwrap();
+        assert!(json.contains("EjClientApi"));
+        assert!(json.contains("EjJobApi"));
+    }
+}
@@ -7,24 +7,39 @@
 //! - Root user creation during initial setup
 //! - Direct job dispatch for administrative tools
 //! - Real-time job status updates
+//! - Streaming the logs of running jobs
 //! - Error handling and client connection management
 //!
 //! The socket interface is primarily used by the ejcli tool for setup and
 //! testing operations that cannot be performed through the regular HTTP API.
 
 use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 
-use ej_dispatcher_sdk::EjRunResult;
+use ej_auth::jwt::{JwtKeys, jwt_keyring, jwt_retire_key, jwt_rotate_key};
+use ej_auth::socket_signature::SocketKey;
+use ej_config::ej_board_config::EjBoardConfigApi;
+use ej_dispatcher_sdk::ejaudit::EjAuditAction;
 use ej_dispatcher_sdk::ejjob::{EjJobApi, EjJobStatus};
-use ej_dispatcher_sdk::ejsocket_message::{EjSocketClientMessage, EjSocketServerMessage};
-use ej_models::auth::client_permission::{ClientPermission, NewClientPermission};
-use ej_models::auth::permission::Permission;
+use ej_dispatcher_sdk::ejsocket_message::{
+    EjSignedSocketMessage, EjSocketClientMessage, EjSocketServerMessage,
+};
+use ej_dispatcher_sdk::ejtoken::EjJwtKeys;
+use ej_dispatcher_sdk::{EjBuildResult, EjRunResult};
+use ej_models::auth::client_role::{ClientRole, NewClientRole};
+use ej_models::auth::role::ADMIN_ROLE;
 use ej_models::client::ejclient::EjClient;
+use ej_models::db::connection::DbConnection;
 use ej_models::job::ejjob::EjJobDb;
 use ej_models::job::ejjob_logs::EjJobLog;
 use ej_models::job::ejjob_results::EjJobResultDb;
+use ej_web::audit::record_audit;
+use ej_web::ejbuilder::list_all_builders;
 use ej_web::ejclient::create_client;
-use ej_web::ejconfig::board_config_db_to_board_config_api;
+use ej_web::ejconfig::{board_config_db_to_board_config_api, fetch_latest_config};
+use ej_web::ejjob::{query_jobs, search_jobs};
+use ej_web::ejstats::fetch_stats;
 use ej_web::prelude::*;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::UnixStream;
@@ -32,6 +47,7 @@ use tokio::net::unix::OwnedWriteHalf;
 use tokio::sync::mpsc::channel;
 use tokio::task::JoinHandle;
 use tracing::{error, info, warn};
+use uuid::Uuid;
 
 use crate::dispatcher::Dispatcher;
 
@@ -62,8 +78,15 @@ async fn send_message(writer: &mut OwnedWriteHalf, response: EjSocketServerMessa
 /// Handles incoming socket messages and dispatches them to appropriate handlers.
 ///
 /// This function processes different types of client messages:
-/// - `CreateRootUser`: Creates the initial administrative user with all permissions
+/// - `CreateRootUser`: Creates the initial administrative user with the `admin` role
 /// - `Dispatch`: Submits a job for execution and streams status updates back
+/// - `QueryJobs`: Lists a page of the jobs matching a query
+/// - `FetchJob`: Fetches a single job
+/// - `SearchJobs`: Searches the logs and results of every job
+/// - `FetchJobResults` / `FetchBuildResult`: Fetches the logs and results of a job
+/// - `FetchStats`: Computes statistics about the finished jobs
+/// - `ListBuilders` / `ListConnectedBuilders`: Lists the builders with the job they're working on
+/// - `SubscribeLogs`: Streams the logs of a running or queued job until it finishes
 ///
 /// # Arguments
 /// * `writer` - The write half of the socket for sending responses
@@ -77,6 +100,8 @@ async fn send_message(writer: &mut OwnedWriteHalf, response: EjSocketServerMessa
 /// ```text
 /// Client -> CreateRootUser -> Server creates user -> CreateRootUserOk
 /// Client -> Dispatch -> Server starts job -> DispatchOk -> JobUpdate...
+/// Client -> SubscribeLogs -> SubscribeLogsOk -> LogChunk...
+/// Client -> RotateJwtKey -> Server switches keys -> JwtKeys
 /// ```
 async fn handle_message(
     writer: &mut OwnedWriteHalf,
@@ -91,19 +116,14 @@ async fn handle_message(
                 return Err(Error::ApiForbidden);
             }
             info!("Creating root user {}", payload.name);
-            let client = create_client(payload, &dispatcher.connection)?;
+            let client = create_client(payload, &dispatcher.secret_policy, &dispatcher.connection)?;
 
-            let permissions = Permission::fetch_all(&dispatcher.connection)?;
-            for permission in permissions.iter() {
-                let client_permission = NewClientPermission {
-                    ejclient_id: client.id,
-                    permission_id: permission.id.clone(),
-                };
-                let client_permission =
-                    ClientPermission::new(&dispatcher.connection, client_permission);
-                if let Err(err) = client_permission {
-                    error!("Failed to add permission {} to user {}", permission.id, err);
-                }
+            let client_role = NewClientRole {
+                ejclient_id: client.id,
+                role_id: String::from(ADMIN_ROLE),
+            };
+            if let Err(err) = ClientRole::new(&dispatcher.connection, client_role) {
+                error!("Failed to assign role {} to user {}", ADMIN_ROLE, err);
             }
             send_message(writer, EjSocketServerMessage::CreateRootUserOk(client)).await?;
             Ok(())
@@ -113,6 +133,12 @@ async fn handle_message(
             let (tx, mut rx) = channel(16);
             match dispatcher.dispatch_job(job, tx, timeout).await {
                 Ok(job) => {
+                    record_audit(
+                        EjAuditAction::Dispatch,
+                        None,
+                        Some(job.id.to_string()),
+                        &dispatcher.connection,
+                    );
                     send_message(writer, EjSocketServerMessage::DispatchOk(job)).await?;
                     while let Some(msg) = rx.recv().await {
                         send_message(writer, EjSocketServerMessage::JobUpdate(msg)).await?;
@@ -140,30 +166,32 @@ async fn handle_message(
             send_message(writer, EjSocketServerMessage::Jobs(jobs)).await
         }
 
-        EjSocketClientMessage::FetchJobResults { job_id } => {
-            // TODO: Duplicated code
+        EjSocketClientMessage::QueryJobs(query) => {
+            let jobs = query_jobs(query, &dispatcher.connection)?;
+            send_message(writer, EjSocketServerMessage::Jobs(jobs.items)).await
+        }
+
+        EjSocketClientMessage::FetchJob { job_id } => {
             let job = EjJobDb::fetch_by_id(&job_id, &dispatcher.connection)?;
+            let job: ej_web::prelude::W<EjJobApi> = job.into();
+            send_message(writer, EjSocketServerMessage::Job(job.0)).await
+        }
+
+        EjSocketClientMessage::FetchJobResults { job_id } => {
+            let reader = dispatcher.connection.reader();
+            let job = EjJobDb::fetch_by_id(&job_id, &reader)?;
             let status: EjJobStatus = job.status.into();
-            let logsdb =
-                EjJobLog::fetch_with_board_config_by_job_id(&job_id, &dispatcher.connection)?;
-            let resultsdb =
-                EjJobResultDb::fetch_with_board_config_by_job_id(&job_id, &dispatcher.connection)?;
-            let mut logs = Vec::new();
+            let logs = fetch_job_logs(&job_id, &reader)?;
+            let resultsdb = EjJobResultDb::fetch_with_board_config_by_job_id(&job_id, &reader)?;
             let mut results = Vec::new();
-            let mut configs = HashMap::new();
-            for (logdb, board_config_db) in logsdb {
-                let config_api =
-                    board_config_db_to_board_config_api(board_config_db, &dispatcher.connection)?;
-                configs.insert(config_api.id, config_api.clone());
-                logs.push((config_api, logdb.log));
-            }
+            let configs: HashMap<_, _> = logs
+                .iter()
+                .map(|(config_api, _)| (config_api.id, config_api.clone()))
+                .collect();
             for (resultdb, board_config_db) in resultsdb {
                 let config_api = match configs.get(&board_config_db.id) {
                     Some(config) => config.clone(),
-                    None => board_config_db_to_board_config_api(
-                        board_config_db,
-                        &dispatcher.connection,
-                    )?,
+                    None => board_config_db_to_board_config_api(board_config_db, &reader)?,
                 };
                 results.push((config_api, resultdb.result));
             }
@@ -171,14 +199,142 @@ async fn handle_message(
             let result = EjRunResult {
                 logs,
                 results,
+                skipped: Vec::new(),
                 success: status == EjJobStatus::Success,
             };
 
             send_message(writer, EjSocketServerMessage::RunResult(result)).await
         }
+
+        EjSocketClientMessage::FetchBuildResult { job_id } => {
+            let reader = dispatcher.connection.reader();
+            let job = EjJobDb::fetch_by_id(&job_id, &reader)?;
+            let status: EjJobStatus = job.status.into();
+            let result = EjBuildResult {
+                logs: fetch_job_logs(&job_id, &reader)?,
+                skipped: Vec::new(),
+                success: status == EjJobStatus::Success,
+            };
+
+            send_message(writer, EjSocketServerMessage::BuildResult(result)).await
+        }
+
+        EjSocketClientMessage::SearchJobs(query) => {
+            let hits = search_jobs(query, &dispatcher.connection)?;
+            send_message(writer, EjSocketServerMessage::SearchResults(hits)).await
+        }
+
+        EjSocketClientMessage::FetchStats(query) => {
+            let stats = fetch_stats(query, &dispatcher.connection)?;
+            send_message(writer, EjSocketServerMessage::Stats(stats)).await
+        }
+
+        EjSocketClientMessage::ListBuilders => {
+            let connected = dispatcher.connected_builders().await;
+            let mut builders = list_all_builders(&connected, &dispatcher.connection)?;
+            dispatcher.set_current_jobs(&mut builders).await;
+            send_message(writer, EjSocketServerMessage::Builders(builders)).await
+        }
+
+        EjSocketClientMessage::ListConnectedBuilders => {
+            let connected = dispatcher.connected_builders().await;
+            let mut builders = list_all_builders(&connected, &dispatcher.connection)?;
+            builders.retain(|builder| builder.is_connected());
+            dispatcher.set_current_jobs(&mut builders).await;
+            send_message(writer, EjSocketServerMessage::Builders(builders)).await
+        }
+
+        EjSocketClientMessage::FetchBuilderConfig { builder_id } => {
+            let config = fetch_latest_config(&builder_id, &dispatcher.connection)?;
+            send_message(writer, EjSocketServerMessage::BuilderConfig(config)).await
+        }
+
+        EjSocketClientMessage::RotateJwtKey {
+            kid,
+            key_path,
+            public_key_path,
+        } => {
+            let algorithm = jwt_keyring()?.current().algorithm();
+            let keys = JwtKeys::from_files(algorithm, &key_path, public_key_path.as_deref())?;
+            jwt_rotate_key(kid.clone(), keys)?;
+            info!("Signing JWT tokens with key {kid}");
+            send_message(writer, EjSocketServerMessage::JwtKeys(jwt_keys()?)).await
+        }
+
+        EjSocketClientMessage::RetireJwtKey { kid } => {
+            jwt_retire_key(&kid)?;
+            info!("Retired JWT key {kid}");
+            send_message(writer, EjSocketServerMessage::JwtKeys(jwt_keys()?)).await
+        }
+
+        EjSocketClientMessage::SubscribeLogs { job_id } => {
+            let job = EjJobDb::fetch_by_id(&job_id, &dispatcher.connection)?;
+            let status: EjJobStatus = job.status.into();
+            if !matches!(status, EjJobStatus::NotStarted | EjJobStatus::Running) {
+                let error = format!("Job {job_id} isn't running");
+                return send_message(writer, EjSocketServerMessage::Error(error)).await;
+            }
+            let mut rx = match dispatcher.subscribe_logs(job_id).await {
+                Ok(rx) => rx,
+                Err(err) => {
+                    error!("Failed to subscribe to the logs of job {job_id} - {err}");
+                    let error = err.to_string();
+                    return send_message(writer, EjSocketServerMessage::Error(error)).await;
+                }
+            };
+            send_message(writer, EjSocketServerMessage::SubscribeLogsOk).await?;
+            while let Some(chunk) = rx.recv().await {
+                send_message(writer, EjSocketServerMessage::LogChunk(chunk)).await?;
+            }
+            Ok(())
+        }
     }
 }
 
+/// Parses a message received over the socket.
+///
+/// When a socket key is configured, the message must be an
+/// [`EjSignedSocketMessage`] whose signature matches its payload.
+///
+/// # Arguments
+/// * `line` - The received line, without the trailing newline
+/// * `socket_key` - Key the message must be signed with, if any
+///
+/// # Errors
+/// Returns an error if the message can't be parsed or its signature is invalid
+/// Fetches the logs of a job along with the board config each one belongs to.
+/// Keys the dispatcher signs and verifies tokens with.
+fn jwt_keys() -> Result<EjJwtKeys> {
+    let keyring = jwt_keyring()?;
+    Ok(EjJwtKeys {
+        current: keyring.current_id().to_string(),
+        key_ids: keyring.key_ids().into_iter().map(String::from).collect(),
+    })
+}
+
+fn fetch_job_logs(
+    job_id: &Uuid,
+    connection: &DbConnection,
+) -> Result<Vec<(EjBoardConfigApi, String)>> {
+    EjJobLog::fetch_with_board_config_by_job_id(job_id, connection)?
+        .into_iter()
+        .map(|(logdb, board_config_db)| {
+            let config_api = board_config_db_to_board_config_api(board_config_db, connection)?;
+            Ok((config_api, logdb.log))
+        })
+        .collect()
+}
+
+fn parse_message(line: &str, socket_key: Option<&SocketKey>) -> Result<EjSocketClientMessage> {
+    let Some(socket_key) = socket_key else {
+        return Ok(serde_json::from_str(line)?);
+    };
+    let signed: EjSignedSocketMessage = serde_json::from_str(line)
+        .map_err(|_| Error::Auth(ej_auth::error::Error::InvalidSignature))?;
+    socket_key.verify(signed.payload.as_bytes(), &signed.signature)?;
+    Ok(serde_json::from_str(&signed.payload)?)
+}
+
 /// Handles a single client connection to the Unix socket.
 ///
 /// This function:
@@ -190,15 +346,21 @@ async fn handle_message(
 /// # Arguments
 /// * `dispatcher` - Dispatcher instance for handling job operations
 /// * `stream` - The Unix socket stream for this client connection
+/// * `socket_key` - Key messages must be signed with, if any
 ///
 /// # Returns
 /// Result indicating success or failure of client handling
 ///
 /// # Protocol
 /// - Messages are JSON objects separated by newlines
+/// - Messages are wrapped in a signed envelope when a socket key is configured
 /// - Each message receives a response before the next is processed
 /// - Connection closes after message processing completes or on error
-async fn handle_client(mut dispatcher: Dispatcher, stream: UnixStream) -> Result<()> {
+async fn handle_client(
+    mut dispatcher: Dispatcher,
+    stream: UnixStream,
+    socket_key: Option<SocketKey>,
+) -> Result<()> {
     info!("Connected to socket client");
     let (reader, mut writer) = stream.into_split();
     let mut reader = BufReader::new(reader);
@@ -209,7 +371,16 @@ async fn handle_client(mut dispatcher: Dispatcher, stream: UnixStream) -> Result
             0 => break,
             _ => {
                 line.pop();
-                if let Ok(message) = serde_json::from_str::<EjSocketClientMessage>(&line) {
+                let message = match parse_message(&line, socket_key.as_ref()) {
+                    Err(Error::Auth(err)) => {
+                        warn!("Rejected socket message - {err}");
+                        send_message(&mut writer, EjSocketServerMessage::Error(err.to_string()))
+                            .await?;
+                        return Err(Error::Auth(err));
+                    }
+                    message => message,
+                };
+                if let Ok(message) = message {
                     info!("Socket Message {:?}", message);
                     match handle_message(&mut writer, message, &mut dispatcher).await {
                         Ok(_) => {
@@ -238,13 +409,16 @@ async fn handle_client(mut dispatcher: Dispatcher, stream: UnixStream) -> Result
 /// Sets up and starts the Unix socket server for administrative operations.
 ///
 /// This function:
-/// - Creates a Unix socket at `/tmp/ejd.sock`
+/// - Creates a Unix socket at `socket_path`, applying `socket_mode` if set
 /// - Starts a background task to accept connections
 /// - Spawns individual handlers for each client connection
 /// - Manages the socket lifecycle and error handling
 ///
 /// # Arguments
 /// * `dispatcher` - The dispatcher instance to clone for each client
+/// * `socket_path` - Where to create the Unix socket
+/// * `socket_mode` - Optional permissions to apply to the socket file
+/// * `socket_key` - Optional key every message must be signed with
 ///
 /// # Returns
 /// Result containing a JoinHandle for the socket server task
@@ -257,32 +431,40 @@ async fn handle_client(mut dispatcher: Dispatcher, stream: UnixStream) -> Result
 ///
 /// # Example
 /// ```rust
-/// let socket_task = setup_socket(dispatcher).await?;
+/// let socket_task = setup_socket(dispatcher, Path::new("/tmp/ejd.sock"), Some(0o660), None).await?;
 /// // Socket server runs in background
 /// // Use ejcli or direct socket connection to communicate
 /// ```
-pub async fn setup_socket(dispatcher: Dispatcher) -> Result<JoinHandle<Result<()>>> {
-    let socket_path = "/tmp/ejd.sock";
-
+pub async fn setup_socket(
+    dispatcher: Dispatcher,
+    socket_path: &Path,
+    socket_mode: Option<u32>,
+    socket_key: Option<SocketKey>,
+) -> Result<JoinHandle<Result<()>>> {
     let listener = match tokio::net::UnixListener::bind(socket_path) {
         Ok(listener) => listener,
         Err(err) => {
-            warn!("Failed to bind {} - {err}", socket_path);
+            warn!("Failed to bind {} - {err}", socket_path.display());
             info!("Removing the file and trying again");
             let _ = std::fs::remove_file(socket_path);
             tokio::net::UnixListener::bind(socket_path)?
         }
     };
 
-    tracing::debug!("Socket listening on {}", socket_path);
+    if let Some(mode) = socket_mode {
+        std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(mode))?;
+    }
+
+    tracing::debug!("Socket listening on {}", socket_path.display());
 
     let handler = tokio::spawn(async move {
         loop {
             match listener.accept().await {
                 Ok((stream, _)) => {
                     let dispatcher = dispatcher.clone();
+                    let socket_key = socket_key.clone();
                     tokio::spawn(async move {
-                        if let Err(e) = handle_client(dispatcher, stream).await {
+                        if let Err(e) = handle_client(dispatcher, stream, socket_key).await {
                             tracing::error!("Error handling client: {}", e);
                         }
                     });
@@ -295,3 +477,100 @@ pub async fn setup_socket(dispatcher: Dispatcher) -> Result<JoinHandle<Result<()
     });
     Ok(handler)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_signed_message() {
+        let key = SocketKey::new("shared secret");
+        let message = EjSocketClientMessage::FetchJobs {
+            commit_hash: String::from("abc123"),
+        };
+
+        let signed = message.encode(Some(&key)).unwrap();
+        assert!(matches!(
+            parse_message(&signed, Some(&key)),
+            Ok(EjSocketClientMessage::FetchJobs { commit_hash }) if commit_hash == "abc123"
+        ));
+
+        let unsigned = message.encode(None).unwrap();
+        assert!(parse_message(&unsigned, None).is_ok());
+        assert!(matches!(
+            parse_message(&unsigned, Some(&key)),
+            Err(Error::Auth(ej_auth::error::Error::InvalidSignature))
+        ));
+        assert!(matches!(
+            parse_message(&signed, Some(&SocketKey::new("another secret"))),
+            Err(Error::Auth(ej_auth::error::Error::InvalidSignature))
+        ));
+    }
+
+    fn signed_fetch_jobs(key: &SocketKey) -> EjSignedSocketMessage {
+        let message = EjSocketClientMessage::FetchJobs {
+            commit_hash: String::from("abc123"),
+        };
+        serde_json::from_str(&message.encode(Some(key)).unwrap()).unwrap()
+    }
+
+    fn is_invalid_signature(result: Result<EjSocketClientMessage>) -> bool {
+        matches!(
+            result,
+            Err(Error::Auth(ej_auth::error::Error::InvalidSignature))
+        )
+    }
+
+    #[test]
+    fn test_parse_tampered_message() {
+        let key = SocketKey::new("shared secret");
+
+        let mut signed = signed_fetch_jobs(&key);
+        signed.payload = signed.payload.replace("abc123", "def456");
+        let line = serde_json::to_string(&signed).unwrap();
+        assert!(is_invalid_signature(parse_message(&line, Some(&key))));
+
+        let mut signed = signed_fetch_jobs(&key);
+        let flipped = if signed.signature.starts_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+        signed.signature.replace_range(..1, flipped);
+        let line = serde_json::to_string(&signed).unwrap();
+        assert!(is_invalid_signature(parse_message(&line, Some(&key))));
+
+        let mut signed = signed_fetch_jobs(&key);
+        signed.signature.truncate(signed.signature.len() / 2);
+        let line = serde_json::to_string(&signed).unwrap();
+        assert!(is_invalid_signature(parse_message(&line, Some(&key))));
+    }
+
+    #[test]
+    fn test_parse_message_without_signature() {
+        let key = SocketKey::new("shared secret");
+        let signed = signed_fetch_jobs(&key);
+
+        let line = serde_json::json!({ "payload": signed.payload }).to_string();
+        assert!(is_invalid_signature(parse_message(&line, Some(&key))));
+
+        for signature in ["", "not hexadecimal"] {
+            let line = serde_json::json!({
+                "payload": signed.payload,
+                "signature": signature,
+            })
+            .to_string();
+            assert!(is_invalid_signature(parse_message(&line, Some(&key))));
+        }
+
+        assert!(is_invalid_signature(parse_message("", Some(&key))));
+        assert!(is_invalid_signature(parse_message("not json", Some(&key))));
+    }
+
+    #[test]
+    fn test_parse_signed_message_without_key() {
+        let key = SocketKey::new("shared secret");
+        let line = serde_json::to_string(&signed_fetch_jobs(&key)).unwrap();
+        assert!(parse_message(&line, None).is_err());
+    }
+}
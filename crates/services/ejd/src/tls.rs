@@ -0,0 +1,114 @@
+//! TLS configuration for the EJ Dispatcher Service.
+//!
+//! When configured, ejd terminates TLS itself so builders and clients can reach
+//! the API over `https://` and the builder WebSocket over `wss://` without an
+//! external reverse proxy in front of it.
+//!
+//! TLS is enabled by setting both of these environment variables:
+//!
+//! - `EJD_TLS_CERT`: path to the PEM encoded certificate chain
+//! - `EJD_TLS_KEY`: path to the PEM encoded private key
+
+use std::path::PathBuf;
+
+use axum_server::tls_rustls::RustlsConfig;
+use tracing::info;
+
+use crate::prelude::*;
+
+/// Environment variable holding the path to the PEM certificate chain.
+pub const TLS_CERT_ENV: &str = "EJD_TLS_CERT";
+
+/// Environment variable holding the path to the PEM private key.
+pub const TLS_KEY_ENV: &str = "EJD_TLS_KEY";
+
+/// Paths to the certificate and key used to serve the API over TLS.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// PEM encoded certificate chain.
+    pub cert_path: PathBuf,
+    /// PEM encoded private key.
+    pub key_path: PathBuf,
+}
+
+impl TlsConfig {
+    /// Create the TLS configuration from optional certificate and key paths.
+    ///
+    /// Both paths must be either set or unset, and if set they must exist.
+    pub fn from_paths(cert: Option<PathBuf>, key: Option<PathBuf>) -> Result<Option<Self>> {
+        let (cert_path, key_path) = match (cert, key) {
+            (None, None) => return Ok(None),
+            (Some(cert), Some(key)) => (cert, key),
+            (Some(_), None) => return Err(Error::TlsKeyMissing),
+            (None, Some(_)) => return Err(Error::TlsCertMissing),
+        };
+
+        for path in [&cert_path, &key_path] {
+            if !path.is_file() {
+                return Err(Error::TlsFileNotFound(path.clone()));
+            }
+        }
+
+        Ok(Some(Self {
+            cert_path,
+            key_path,
+        }))
+    }
+
+    /// Load the certificate and key into a rustls server configuration.
+    pub async fn load(&self) -> Result<RustlsConfig> {
+        info!(
+            "Loading TLS certificate {} and key {}",
+            self.cert_path.display(),
+            self.key_path.display()
+        );
+        Ok(RustlsConfig::from_pem_file(&self.cert_path, &self.key_path).await?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_tls_disabled_without_paths() {
+        assert!(TlsConfig::from_paths(None, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_tls_requires_both_paths() {
+        let file = NamedTempFile::new().unwrap();
+        let path = Some(file.path().to_path_buf());
+        assert!(matches!(
+            TlsConfig::from_paths(path.clone(), None),
+            Err(Error::TlsKeyMissing)
+        ));
+        assert!(matches!(
+            TlsConfig::from_paths(None, path),
+            Err(Error::TlsCertMissing)
+        ));
+    }
+
+    #[test]
+    fn test_tls_missing_file() {
+        let file = NamedTempFile::new().unwrap();
+        let missing = PathBuf::from("/this/path/does/not/exist.pem");
+        let result = TlsConfig::from_paths(Some(file.path().to_path_buf()), Some(missing.clone()));
+        assert!(matches!(result, Err(Error::TlsFileNotFound(path)) if path == missing));
+    }
+
+    #[test]
+    fn test_tls_enabled() {
+        let cert = NamedTempFile::new().unwrap();
+        let key = NamedTempFile::new().unwrap();
+        let config = TlsConfig::from_paths(
+            Some(cert.path().to_path_buf()),
+            Some(key.path().to_path_buf()),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.cert_path, cert.path());
+        assert_eq!(config.key_path, key.path());
+    }
+}
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DELETE FROM client_permission WHERE permission_id = 'builder.manage';
+DELETE FROM permission WHERE id = 'builder.manage';
+
+ALTER TABLE ejbuilder DROP COLUMN revoked_at;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+
+ALTER TABLE ejbuilder ADD COLUMN revoked_at TIMESTAMPTZ;
+
+INSERT INTO permission (id) VALUES ('builder.manage');
+
+-- Root clients got every permission when they were created, grant them the new one too
+INSERT INTO client_permission (ejclient_id, permission_id)
+	SELECT ejclient_id, 'builder.manage' FROM client_permission
+	GROUP BY ejclient_id
+	HAVING COUNT(*) = (SELECT COUNT(*) FROM permission WHERE id <> 'builder.manage');
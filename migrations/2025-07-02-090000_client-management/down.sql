@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DELETE FROM client_permission WHERE permission_id = 'client.manage';
+DELETE FROM permission WHERE id = 'client.manage';
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+
+INSERT INTO permission (id) VALUES ('client.manage');
+
+-- Root clients got every permission when they were created, grant them the new one too
+INSERT INTO client_permission (ejclient_id, permission_id)
+	SELECT ejclient_id, 'client.manage' FROM client_permission
+	GROUP BY ejclient_id
+	HAVING COUNT(*) = (SELECT COUNT(*) FROM permission WHERE id <> 'client.manage');
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+
+CREATE TABLE ejjobartifact (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	ejjob_id uuid REFERENCES ejjob(id) ON DELETE CASCADE NOT NULL,
+	ejboard_config_id uuid REFERENCES ejboard_config(id) ON DELETE CASCADE NOT NULL,
+	name VARCHAR NOT NULL,
+	size BIGINT NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+SELECT diesel_manage_updated_at('ejjobartifact');
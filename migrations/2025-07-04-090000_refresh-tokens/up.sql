@@ -0,0 +1,14 @@
+-- Your SQL goes here
+
+CREATE TABLE ejrefreshtoken (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	token_hash VARCHAR(64) UNIQUE NOT NULL,
+	ejclient_id uuid REFERENCES ejclient(id) ON DELETE CASCADE,
+	ejbuilder_id uuid REFERENCES ejbuilder(id) ON DELETE CASCADE,
+	expires_at TIMESTAMPTZ NOT NULL,
+	revoked_at TIMESTAMPTZ,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CHECK ((ejclient_id IS NULL) <> (ejbuilder_id IS NULL))
+);
+SELECT diesel_manage_updated_at('ejrefreshtoken');
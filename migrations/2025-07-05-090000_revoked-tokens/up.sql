@@ -0,0 +1,9 @@
+-- Your SQL goes here
+
+CREATE TABLE ejrevokedtoken (
+	jti uuid PRIMARY KEY,
+	expires_at TIMESTAMPTZ NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+SELECT diesel_manage_updated_at('ejrevokedtoken');
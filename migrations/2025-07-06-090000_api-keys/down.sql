@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DELETE FROM permission WHERE id = 'client.results';
+
+DROP TABLE ejapikey;
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+
+CREATE TABLE ejapikey (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	ejclient_id uuid REFERENCES ejclient(id) ON DELETE CASCADE NOT NULL,
+	name VARCHAR NOT NULL,
+	prefix VARCHAR(16) UNIQUE NOT NULL,
+	hash VARCHAR(64) NOT NULL,
+	scopes TEXT[] NOT NULL,
+	expires_at TIMESTAMPTZ,
+	revoked_at TIMESTAMPTZ,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+SELECT diesel_manage_updated_at('ejapikey');
+
+INSERT INTO permission (id) VALUES ('client.results');
+
+INSERT INTO client_permission (ejclient_id, permission_id)
+	SELECT ejclient_id, 'client.results' FROM client_permission WHERE permission_id = 'client.dispatch';
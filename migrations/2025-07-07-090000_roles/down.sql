@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE client_role;
+DROP TABLE role_permission;
+DROP TABLE role;
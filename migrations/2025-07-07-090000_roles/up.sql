@@ -0,0 +1,42 @@
+-- Your SQL goes here
+
+CREATE TABLE role (
+	id VARCHAR PRIMARY KEY
+);
+
+CREATE TABLE role_permission (
+	role_id VARCHAR REFERENCES role(id) ON DELETE CASCADE NOT NULL,
+	permission_id VARCHAR REFERENCES permission(id) ON DELETE CASCADE NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (role_id, permission_id)
+);
+
+SELECT diesel_manage_updated_at('role_permission');
+
+CREATE TABLE client_role (
+	ejclient_id uuid REFERENCES ejclient(id) ON DELETE CASCADE NOT NULL,
+	role_id VARCHAR REFERENCES role(id) ON DELETE CASCADE NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (ejclient_id, role_id)
+);
+
+SELECT diesel_manage_updated_at('client_role');
+
+INSERT INTO role (id) VALUES ('admin'), ('operator'), ('viewer');
+
+INSERT INTO role_permission (role_id, permission_id)
+SELECT 'admin', id FROM permission;
+
+INSERT INTO role_permission (role_id, permission_id) VALUES
+	('operator', 'builder.create'),
+	('operator', 'client.dispatch'),
+	('operator', 'client.results'),
+	('viewer', 'client.results');
+
+-- Root clients got every permission when they were created, they're admins
+INSERT INTO client_role (ejclient_id, role_id)
+	SELECT ejclient_id, 'admin' FROM client_permission
+	GROUP BY ejclient_id
+	HAVING COUNT(*) = (SELECT COUNT(*) FROM permission);
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+
+CREATE TABLE ejauditlog (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	action VARCHAR NOT NULL,
+	actor_id uuid,
+	target VARCHAR,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX ejauditlog_created_at_idx ON ejauditlog (created_at);
+CREATE INDEX ejauditlog_action_idx ON ejauditlog (action);
+
+INSERT INTO permission (id) VALUES ('audit.read');
+
+INSERT INTO role_permission (role_id, permission_id) VALUES ('admin', 'audit.read');
+
+INSERT INTO client_permission (ejclient_id, permission_id)
+	SELECT ejclient_id, 'audit.read' FROM client_permission WHERE permission_id = 'client.manage';
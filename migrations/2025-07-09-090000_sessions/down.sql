@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE ejrefreshtoken DROP COLUMN ejsession_id;
+
+DROP TABLE ejsession;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+
+CREATE TABLE ejsession (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	ejclient_id uuid REFERENCES ejclient(id) ON DELETE CASCADE NOT NULL,
+	expires_at TIMESTAMPTZ NOT NULL,
+	revoked_at TIMESTAMPTZ,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+SELECT diesel_manage_updated_at('ejsession');
+
+ALTER TABLE ejrefreshtoken ADD COLUMN ejsession_id uuid REFERENCES ejsession(id) ON DELETE CASCADE;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX ejjob_ejclient_id_created_at_idx;
+
+ALTER TABLE ejjob DROP COLUMN ejclient_id;
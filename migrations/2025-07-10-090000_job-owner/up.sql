@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+ALTER TABLE ejjob ADD COLUMN ejclient_id uuid REFERENCES ejclient(id) ON DELETE SET NULL;
+
+CREATE INDEX ejjob_ejclient_id_created_at_idx ON ejjob (ejclient_id, created_at);
@@ -0,0 +1,19 @@
+-- This file should undo anything in `up.sql`
+
+CREATE TABLE ejjoblog (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	ejjob_id uuid REFERENCES ejjob(id) ON DELETE CASCADE NOT NULL,
+	ejboard_config_id uuid REFERENCES ejboard_config(id) ON DELETE CASCADE NOT NULL,
+	log VARCHAR NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+SELECT diesel_manage_updated_at('ejjoblog');
+
+INSERT INTO ejjoblog (ejjob_id, ejboard_config_id, log, created_at)
+	SELECT ejjob_id, ejboard_config_id, string_agg(content, '' ORDER BY sequence), MIN(created_at)
+	FROM ejjoblogchunk
+	GROUP BY ejjob_id, ejboard_config_id;
+
+DROP TABLE ejjoblogchunk;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+
+CREATE TABLE ejjoblogchunk (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	ejjob_id uuid REFERENCES ejjob(id) ON DELETE CASCADE NOT NULL,
+	ejboard_config_id uuid REFERENCES ejboard_config(id) ON DELETE CASCADE NOT NULL,
+	sequence INTEGER NOT NULL,
+	content VARCHAR NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	UNIQUE (ejjob_id, ejboard_config_id, sequence)
+);
+
+INSERT INTO ejjoblogchunk (ejjob_id, ejboard_config_id, sequence, content, created_at)
+	SELECT ejjob_id, ejboard_config_id, 0, log, created_at FROM ejjoblog;
+
+DROP TABLE ejjoblog;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX ejjobresult_search_idx;
+DROP INDEX ejjoblogchunk_search_idx;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+CREATE INDEX ejjoblogchunk_search_idx ON ejjoblogchunk USING GIN (to_tsvector('simple', content));
+CREATE INDEX ejjobresult_search_idx ON ejjobresult USING GIN (to_tsvector('simple', result));
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE ejbuilderconnection;
+
+ALTER TABLE ejbuilder DROP COLUMN last_seen_at;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+
+ALTER TABLE ejbuilder ADD COLUMN last_seen_at TIMESTAMPTZ;
+
+CREATE TABLE ejbuilderconnection (
+	id uuid PRIMARY KEY,
+	ejbuilder_id uuid REFERENCES ejbuilder(id) ON DELETE CASCADE NOT NULL,
+	address VARCHAR NOT NULL,
+	connected_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	last_seen_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	disconnected_at TIMESTAMPTZ
+);
+
+CREATE INDEX ejbuilderconnection_builder_idx ON ejbuilderconnection (ejbuilder_id, connected_at DESC);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE ejjobconfig;
+
+ALTER TABLE ejconfig DROP COLUMN uploaded_at;
+ALTER TABLE ejconfig DROP COLUMN content;
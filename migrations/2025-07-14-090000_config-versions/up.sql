@@ -0,0 +1,13 @@
+-- Your SQL goes here
+
+ALTER TABLE ejconfig ADD COLUMN content TEXT;
+ALTER TABLE ejconfig ADD COLUMN uploaded_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+UPDATE ejconfig SET uploaded_at = created_at;
+
+CREATE TABLE ejjobconfig (
+	ejjob_id uuid REFERENCES ejjob(id) ON DELETE CASCADE NOT NULL,
+	ejconfig_id uuid REFERENCES ejconfig(id) ON DELETE CASCADE NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (ejjob_id, ejconfig_id)
+);
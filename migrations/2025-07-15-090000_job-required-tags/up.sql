@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+ALTER TABLE ejjob ADD COLUMN required_tags TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+ALTER TABLE ejjob ADD COLUMN board_configs TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE ejrefreshtoken DROP COLUMN rotated_at;
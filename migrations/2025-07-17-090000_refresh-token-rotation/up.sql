@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+-- Set when a token is exchanged for a new one, only those are reused by a thief
+ALTER TABLE ejrefreshtoken ADD COLUMN rotated_at TIMESTAMPTZ;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE ejbuilder DROP CONSTRAINT ejbuilder_ejclient_id_fkey;
+ALTER TABLE ejbuilder ADD CONSTRAINT ejbuilder_ejclient_id_fkey
+	FOREIGN KEY (ejclient_id) REFERENCES ejclient(id);
+ALTER TABLE ejbuilder ALTER COLUMN ejclient_id SET NOT NULL;
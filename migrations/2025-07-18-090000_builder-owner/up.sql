@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+-- Revoked builders outlive the client that owned them
+ALTER TABLE ejbuilder ALTER COLUMN ejclient_id DROP NOT NULL;
+ALTER TABLE ejbuilder DROP CONSTRAINT ejbuilder_ejclient_id_fkey;
+ALTER TABLE ejbuilder ADD CONSTRAINT ejbuilder_ejclient_id_fkey
+	FOREIGN KEY (ejclient_id) REFERENCES ejclient(id) ON DELETE SET NULL;